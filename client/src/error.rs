@@ -0,0 +1,56 @@
+use std::fmt;
+
+use serde::Deserialize;
+
+/// Error payload returned by the service on both HTTP and MQTT.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ApiError {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub title: String,
+    #[serde(default)]
+    pub detail: Option<String>,
+    pub status: u16,
+    /// Missing in responses of older service versions so treated as transient.
+    #[serde(default)]
+    pub is_permanent: bool,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// The service has responded with an error.
+    Api(ApiError),
+    /// The payload can't be serialized.
+    InvalidRequest(String),
+    /// The request hasn't reached the service or the response hasn't been received.
+    Transport(String),
+    /// The response has been received but can't be parsed.
+    InvalidResponse(String),
+}
+
+impl Error {
+    /// Whether repeating the same request may succeed.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::Api(err) => !err.is_permanent,
+            Self::Transport(_) => true,
+            Self::InvalidRequest(_) | Self::InvalidResponse(_) => false,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Api(err) => match err.detail {
+                Some(ref detail) => write!(f, "{} ({}): {}", err.title, err.status, detail),
+                None => write!(f, "{} ({})", err.title, err.status),
+            },
+            Self::InvalidRequest(err) => write!(f, "Invalid request: {err}"),
+            Self::Transport(err) => write!(f, "Transport error: {err}"),
+            Self::InvalidResponse(err) => write!(f, "Invalid response: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,79 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde_json::Value as JsonValue;
+use tracing::{instrument, Span};
+
+use crate::{ApiError, Error, Request, Transport, Verb};
+
+/// Sends requests to the HTTP API, e.g. `https://event.example.org/api/v1`.
+pub struct HttpTransport {
+    client: reqwest::Client,
+    base_url: String,
+    token: String,
+}
+
+impl HttpTransport {
+    pub fn new(base_url: &str, token: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_owned(),
+            token: token.to_owned(),
+        }
+    }
+
+    pub fn timeout(self, timeout: Duration) -> Result<Self, Error> {
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .map_err(|err| Error::Transport(err.to_string()))?;
+
+        Ok(Self { client, ..self })
+    }
+}
+
+#[async_trait]
+impl Transport for HttpTransport {
+    #[instrument(skip_all, fields(path = %request.path, status))]
+    async fn send(&self, request: &Request) -> Result<JsonValue, Error> {
+        let url = format!("{}{}", self.base_url, request.path);
+
+        let builder = match request.verb {
+            Verb::Get => {
+                let query = serde_qs::to_string(&request.payload)
+                    .map_err(|err| Error::InvalidRequest(err.to_string()))?;
+
+                self.client.get(format!("{url}?{query}"))
+            }
+            Verb::Post => self.client.post(url).json(&request.payload),
+            Verb::Patch => self.client.patch(url).json(&request.payload),
+        };
+
+        let response = builder
+            .bearer_auth(&self.token)
+            .send()
+            .await
+            .map_err(|err| Error::Transport(err.to_string()))?;
+
+        let status = response.status();
+        Span::current().record("status", status.as_u16());
+
+        let body = response
+            .bytes()
+            .await
+            .map_err(|err| Error::Transport(err.to_string()))?;
+
+        if status.is_success() {
+            serde_json::from_slice(&body).map_err(|err| Error::InvalidResponse(err.to_string()))
+        } else {
+            match serde_json::from_slice::<ApiError>(&body) {
+                Ok(err) => Err(Error::Api(err)),
+                // Proxies may respond with non-JSON bodies when the service is unavailable.
+                Err(_) if status.is_server_error() => Err(Error::Transport(format!(
+                    "Unexpected response status: {status}"
+                ))),
+                Err(err) => Err(Error::InvalidResponse(err.to_string())),
+            }
+        }
+    }
+}
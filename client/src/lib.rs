@@ -0,0 +1,354 @@
+//! Typed client of the event service.
+//!
+//! [`Client`] builds requests from the payload types in [`types`] and sends them with a
+//! [`Transport`]. Both the HTTP and the MQTT transports address the same endpoints so switching
+//! between them doesn't change the calling code.
+
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{Map as JsonMap, Value as JsonValue};
+use tracing::{instrument, warn, Span};
+use uuid::Uuid;
+
+pub use crate::error::{ApiError, Error};
+pub use crate::http::HttpTransport;
+pub use crate::mqtt::MqttTransport;
+pub use crate::retry::RetryPolicy;
+
+use crate::types::*;
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verb {
+    Get,
+    Post,
+    Patch,
+}
+
+/// A request to an endpoint in the transport independent form.
+#[derive(Clone, Debug)]
+pub struct Request {
+    /// MQTT method, e.g. `room.read`.
+    pub method: &'static str,
+    pub verb: Verb,
+    /// HTTP path relative to the API root, e.g. `/rooms/<id>`.
+    pub path: String,
+    /// Identifiers which are a part of the HTTP path but go to the MQTT payload.
+    pub ids: JsonMap<String, JsonValue>,
+    pub payload: JsonMap<String, JsonValue>,
+    /// Whether sending the request twice has the same effect as sending it once.
+    /// Only such requests are retried since a failed attempt may have been handled by the service.
+    pub idempotent: bool,
+}
+
+impl Request {
+    pub fn new<P: Serialize>(
+        method: &'static str,
+        verb: Verb,
+        path: String,
+        payload: &P,
+    ) -> Result<Self, Error> {
+        let payload = match serde_json::to_value(payload) {
+            Ok(JsonValue::Object(payload)) => payload,
+            Ok(_) => JsonMap::new(),
+            Err(err) => return Err(Error::InvalidRequest(err.to_string())),
+        };
+
+        Ok(Self {
+            method,
+            verb,
+            path,
+            ids: JsonMap::new(),
+            payload,
+            idempotent: verb == Verb::Get,
+        })
+    }
+
+    pub fn idempotent(self, idempotent: bool) -> Self {
+        Self { idempotent, ..self }
+    }
+
+    pub fn id(mut self, key: &str, id: Uuid) -> Self {
+        self.ids
+            .insert(key.to_owned(), JsonValue::String(id.to_string()));
+        self
+    }
+
+    /// Payload with identifiers as it's sent over MQTT.
+    pub fn mqtt_payload(&self) -> JsonValue {
+        let mut payload = self.payload.clone();
+        payload.extend(self.ids.clone());
+        JsonValue::Object(payload)
+    }
+}
+
+#[async_trait]
+pub trait Transport: Send + Sync {
+    /// Sends the request once and returns the successful response payload.
+    async fn send(&self, request: &Request) -> Result<JsonValue, Error>;
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+pub struct Client<T> {
+    transport: T,
+    retry_policy: RetryPolicy,
+}
+
+impl<T: Transport> Client<T> {
+    pub fn new(transport: T) -> Self {
+        Self {
+            transport,
+            retry_policy: RetryPolicy::default(),
+        }
+    }
+
+    pub fn retry_policy(self, retry_policy: RetryPolicy) -> Self {
+        Self {
+            retry_policy,
+            ..self
+        }
+    }
+
+    pub async fn read_room(&self, room_id: Uuid) -> Result<Room, Error> {
+        let request = Request::new(
+            "room.read",
+            Verb::Get,
+            format!("/rooms/{room_id}"),
+            &JsonMap::new(),
+        )?
+        .id("id", room_id);
+
+        self.call(request).await
+    }
+
+    pub async fn create_room(&self, payload: &CreateRoomPayload) -> Result<Room, Error> {
+        let request = Request::new("room.create", Verb::Post, "/rooms".to_owned(), payload)?;
+        self.call(request).await
+    }
+
+    pub async fn update_room(
+        &self,
+        room_id: Uuid,
+        payload: &UpdateRoomPayload,
+    ) -> Result<Room, Error> {
+        let request = Request::new(
+            "room.update",
+            Verb::Patch,
+            format!("/rooms/{room_id}"),
+            payload,
+        )?
+        .id("id", room_id);
+
+        self.call(request).await
+    }
+
+    pub async fn create_event(
+        &self,
+        room_id: Uuid,
+        payload: &CreateEventPayload,
+    ) -> Result<Event, Error> {
+        let request = Request::new(
+            "event.create",
+            Verb::Post,
+            format!("/rooms/{room_id}/events"),
+            payload,
+        )?
+        .id("room_id", room_id);
+
+        self.call(request).await
+    }
+
+    pub async fn list_events(
+        &self,
+        room_id: Uuid,
+        payload: &ListEventsPayload,
+    ) -> Result<Vec<Event>, Error> {
+        let request = Request::new(
+            "event.list",
+            Verb::Get,
+            format!("/rooms/{room_id}/events"),
+            payload,
+        )?
+        .id("room_id", room_id);
+
+        self.call(request).await
+    }
+
+    pub async fn read_state(
+        &self,
+        room_id: Uuid,
+        payload: &ReadStatePayload,
+    ) -> Result<State, Error> {
+        let request = Request::new(
+            "state.read",
+            Verb::Get,
+            format!("/rooms/{room_id}/state"),
+            payload,
+        )?
+        .id("room_id", room_id);
+
+        self.call(request).await
+    }
+
+    pub async fn list_agents(
+        &self,
+        room_id: Uuid,
+        payload: &ListAgentsPayload,
+    ) -> Result<Vec<Agent>, Error> {
+        let request = Request::new(
+            "agent.list",
+            Verb::Get,
+            format!("/rooms/{room_id}/agents"),
+            payload,
+        )?
+        .id("room_id", room_id);
+
+        self.call(request).await
+    }
+
+    /// Sends the request retrying transient failures of idempotent requests according to the retry policy.
+    ///
+    /// Other requests are sent once since a transport error doesn't tell whether the service
+    /// has handled them, e.g. a retried `event.create` could create the event twice.
+    #[instrument(skip_all, fields(method = request.method, attempt))]
+    pub async fn call<R: DeserializeOwned>(&self, request: Request) -> Result<R, Error> {
+        let mut attempt = 1;
+
+        loop {
+            Span::current().record("attempt", attempt);
+
+            match self.transport.send(&request).await {
+                Ok(payload) => {
+                    return serde_json::from_value(payload)
+                        .map_err(|err| Error::InvalidResponse(err.to_string()));
+                }
+                Err(err)
+                    if request.idempotent
+                        && err.is_transient()
+                        && attempt < self.retry_policy.max_attempts() =>
+                {
+                    let delay = self.retry_policy.delay(attempt);
+                    warn!(%err, ?delay, "Request failed, retrying");
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+pub mod types;
+
+mod error;
+mod http;
+mod mqtt;
+mod retry;
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    use serde_json::json;
+
+    use super::*;
+
+    struct FlakyTransport {
+        responses: Mutex<Vec<Result<JsonValue, Error>>>,
+    }
+
+    #[async_trait]
+    impl Transport for FlakyTransport {
+        async fn send(&self, _request: &Request) -> Result<JsonValue, Error> {
+            self.responses.lock().unwrap().remove(0)
+        }
+    }
+
+    fn api_error(is_permanent: bool) -> Error {
+        Error::Api(ApiError {
+            kind: "database_query_failed".to_owned(),
+            title: "Database query failed".to_owned(),
+            detail: None,
+            status: 422,
+            is_permanent,
+        })
+    }
+
+    fn client(responses: Vec<Result<JsonValue, Error>>) -> Client<FlakyTransport> {
+        let transport = FlakyTransport {
+            responses: Mutex::new(responses),
+        };
+
+        Client::new(transport).retry_policy(RetryPolicy::new(
+            3,
+            Duration::from_millis(1),
+            Duration::from_millis(1),
+        ))
+    }
+
+    #[tokio::test]
+    async fn retry_transient_errors() {
+        let client = client(vec![
+            Err(Error::Transport("Connection refused".to_owned())),
+            Err(api_error(false)),
+            Ok(json!({ "value": 1 })),
+        ]);
+
+        let request = Request::new("test.call", Verb::Get, "/".to_owned(), &json!({})).unwrap();
+        let response: JsonValue = client.call(request).await.expect("Call failed");
+        assert_eq!(response, json!({ "value": 1 }));
+    }
+
+    #[tokio::test]
+    async fn dont_retry_permanent_errors() {
+        let client = client(vec![Err(api_error(true)), Ok(json!({}))]);
+
+        let request = Request::new("test.call", Verb::Get, "/".to_owned(), &json!({})).unwrap();
+        let err = client.call::<JsonValue>(request).await.unwrap_err();
+        assert!(matches!(err, Error::Api(ApiError { status: 422, .. })));
+    }
+
+    #[tokio::test]
+    async fn dont_retry_non_idempotent_requests() {
+        let client = client(vec![
+            Err(Error::Transport("Connection reset".to_owned())),
+            Ok(json!({})),
+        ]);
+
+        let request = Request::new("test.call", Verb::Post, "/".to_owned(), &json!({})).unwrap();
+        assert!(!request.idempotent);
+
+        let err = client.call::<JsonValue>(request).await.unwrap_err();
+        assert!(matches!(err, Error::Transport(_)));
+    }
+
+    #[test]
+    fn mqtt_payload_includes_ids() {
+        let room_id = Uuid::new_v4();
+        let request = Request::new(
+            "event.list",
+            Verb::Get,
+            format!("/rooms/{room_id}/events"),
+            &ListEventsPayload {
+                limit: Some(10),
+                ..Default::default()
+            },
+        )
+        .unwrap()
+        .id("room_id", room_id);
+
+        assert_eq!(
+            request.mqtt_payload(),
+            json!({
+                "room_id": room_id,
+                "limit": 10,
+                "binary_data": false,
+                "include_deleted": false,
+            })
+        );
+        assert!(!request.payload.contains_key("room_id"));
+    }
+}
@@ -0,0 +1,108 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use serde_json::Value as JsonValue;
+use svc_agent::{
+    mqtt::{
+        OutgoingMessage, OutgoingRequest, OutgoingRequestProperties,
+        OutgoingShortTermTimingProperties, SubscriptionTopic,
+    },
+    request::Dispatcher,
+    AccountId, AgentId, Subscription,
+};
+use tracing::{instrument, Span};
+use uuid::Uuid;
+
+use crate::{ApiError, Error, Request, Transport};
+
+const API_VERSION: &str = "v1";
+
+/// Sends multicast requests to the service over MQTT.
+///
+/// Responses are matched by the `dispatcher` so the consumer service must pass incoming
+/// responses to `Dispatcher::response` and be subscribed to unicast responses from the service.
+pub struct MqttTransport {
+    dispatcher: Arc<Dispatcher>,
+    agent_id: AgentId,
+    event_account_id: AccountId,
+    timeout: Duration,
+}
+
+impl MqttTransport {
+    pub fn new(
+        dispatcher: Arc<Dispatcher>,
+        agent_id: AgentId,
+        event_account_id: AccountId,
+    ) -> Self {
+        Self {
+            dispatcher,
+            agent_id,
+            event_account_id,
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    pub fn timeout(self, timeout: Duration) -> Self {
+        Self { timeout, ..self }
+    }
+}
+
+#[async_trait]
+impl Transport for MqttTransport {
+    #[instrument(skip_all, fields(correlation_data, status))]
+    async fn send(&self, request: &Request) -> Result<JsonValue, Error> {
+        let response_topic = Subscription::unicast_responses_from(&self.event_account_id)
+            .subscription_topic(&self.agent_id, API_VERSION)
+            .map_err(|err| Error::Transport(err.to_string()))?;
+
+        let corr_data = Uuid::new_v4().to_string();
+        Span::current().record("correlation_data", corr_data.as_str());
+
+        let props = OutgoingRequestProperties::new(
+            request.method,
+            &response_topic,
+            &corr_data,
+            OutgoingShortTermTimingProperties::new(Utc::now()),
+        );
+
+        let message = OutgoingRequest::multicast(
+            request.mqtt_payload(),
+            props,
+            &self.event_account_id,
+            API_VERSION,
+        );
+
+        let OutgoingMessage::Request(outgoing) = message else {
+            unreachable!("OutgoingRequest::multicast builds a request");
+        };
+
+        let response = tokio::time::timeout(
+            self.timeout,
+            self.dispatcher.request::<_, JsonValue>(outgoing),
+        )
+        .await;
+
+        let response = match response {
+            Ok(response) => response.map_err(|err| Error::Transport(err.to_string()))?,
+            Err(_) => {
+                // Stop awaiting so a late response doesn't take the correlation data slot.
+                let _ = self.dispatcher.cancel_request(&corr_data);
+                return Err(Error::Transport("Response timed out".to_owned()));
+            }
+        };
+
+        let status = response.properties().status();
+        Span::current().record("status", status.as_u16());
+
+        if status.is_success() {
+            Ok(response.extract_payload())
+        } else {
+            match serde_json::from_value::<ApiError>(response.extract_payload()) {
+                Ok(err) => Err(Error::Api(err)),
+                Err(err) => Err(Error::InvalidResponse(err.to_string())),
+            }
+        }
+    }
+}
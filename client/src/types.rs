@@ -0,0 +1,57 @@
+//! Payload and response types of the service.
+//!
+//! Most of them are generated from the OpenAPI document of the service into `types/generated.rs`
+//! so they follow the endpoint types. Only responses without a schema in the document are
+//! written here.
+
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+
+pub use self::generated::*;
+
+mod generated;
+
+////////////////////////////////////////////////////////////////////////////////
+
+impl CreateEventPayload {
+    /// A persistent event with the service's defaults.
+    pub fn new(kind: &str, data: JsonValue) -> Self {
+        Self {
+            kind: kind.to_owned(),
+            set: None,
+            label: None,
+            attribute: None,
+            attributes: vec![],
+            data,
+            is_claim: false,
+            is_persistent: true,
+            removed: false,
+            content_encrypted: false,
+            key_id: None,
+            skip_broadcast_if_empty: false,
+            expected_last_occurred_at: None,
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct State {
+    /// Present only when a single set has been requested.
+    #[serde(default)]
+    pub has_next: Option<bool>,
+    #[serde(flatten)]
+    pub sets: HashMap<String, SetState>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum SetState {
+    /// The only element of a simple set.
+    Element(Box<Event>),
+    /// Latest events for each label of a collection.
+    Collection(Vec<Event>),
+}
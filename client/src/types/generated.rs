@@ -0,0 +1,269 @@
+// @generated from the OpenAPI document of the service by its `client_types_are_up_to_date` test.
+// Don't edit by hand, run `UPDATE_CLIENT_TYPES=1 cargo test client_types_are_up_to_date` instead.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use uuid::Uuid;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Room {
+    pub audience: String,
+    /// Limit of agents in the room. `config.room_capacity.default` applies when missing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capacity: Option<i32>,
+    pub classroom_id: Uuid,
+    pub created_at: i64,
+    pub id: Uuid,
+    #[serde(default)]
+    pub keep_open: bool,
+    pub kind: ClassType,
+    #[serde(default)]
+    pub locked_types: HashMap<String, bool>,
+    #[serde(default)]
+    pub moderated: bool,
+    pub preserve_history: bool,
+    /// Audience of another tenant co-hosting the room. Its authorization grants access too.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secondary_audience: Option<String>,
+    #[serde(default)]
+    pub settings: JsonValue,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_room_id: Option<Uuid>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags: Option<JsonValue>,
+    /// `[opened_at, closed_at)` in seconds, `closed_at` is null for unbounded rooms.
+    pub time: Vec<Option<i64>>,
+    #[serde(default)]
+    pub whiteboard_access: JsonValue,
+}
+
+/// Lowercase names are accepted as well like they're written in the docs and the config.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ClassType {
+    Webinar,
+    P2P,
+    Minigroup,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CreateRoomPayload {
+    pub audience: String,
+    /// Limit of agents in the room.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capacity: Option<i32>,
+    pub classroom_id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep_open: Option<bool>,
+    pub kind: ClassType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub moderated: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preserve_history: Option<bool>,
+    /// Audience of another tenant co-hosting the room.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secondary_audience: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags: Option<JsonValue>,
+    /// `[opened_at, closed_at)` in seconds, `closed_at` may be null for an unbounded room.
+    pub time: Vec<Option<i64>>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct UpdateRoomPayload {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capacity: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub classroom_id: Option<Uuid>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep_open: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub moderated: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secondary_audience: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags: Option<JsonValue>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time: Option<Vec<Option<i64>>>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Event {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attribute: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attributes: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub binary_data: Option<BinaryData>,
+    #[serde(default)]
+    pub content_encrypted: bool,
+    pub created_at: i64,
+    pub created_by: String,
+    pub data: JsonValue,
+    pub id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub moderation_status: Option<ModerationStatus>,
+    pub occurred_at: i64,
+    pub original_created_by: String,
+    pub original_occurred_at: i64,
+    pub removed: bool,
+    pub room_id: Uuid,
+    /// Monotonically increasing number missing in events created before it was introduced.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seq: Option<i64>,
+    pub set: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+/// Event data in its storage binary format for clients decoding it on their side.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct BinaryData {
+    /// Binary format, `postcard` at the moment.
+    pub encoding: String,
+    /// Base64 encoded bytes.
+    pub payload: String,
+}
+
+/// Events created by non-privileged agents in moderated rooms are kept `pending`
+/// until a moderator approves or rejects them. Only approved events are visible.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ModerationStatus {
+    #[serde(rename = "pending")]
+    Pending,
+    #[serde(rename = "approved")]
+    Approved,
+    #[serde(rename = "rejected")]
+    Rejected,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CreateEventPayload {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attribute: Option<String>,
+    #[serde(default)]
+    pub attributes: Vec<String>,
+    /// `data` is a ciphertext string encrypted by clients. It's passed through as is.
+    #[serde(default)]
+    pub content_encrypted: bool,
+    pub data: JsonValue,
+    /// `occurred_at` of the latest event with the same set and label the client has seen.
+    /// The event is rejected if there's another one since then.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_last_occurred_at: Option<i64>,
+    #[serde(default)]
+    pub is_claim: bool,
+    #[serde(default)]
+    pub is_persistent: bool,
+    /// Identifier of the key `data` is encrypted with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(default)]
+    pub removed: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub set: Option<String>,
+    /// Don't notify room subscribers when the room has no ready agents.
+    #[serde(default)]
+    pub skip_broadcast_if_empty: bool,
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct ListEventsPayload {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attribute: Option<String>,
+    /// Return binary encoded events as is for clients to decode them.
+    #[serde(default)]
+    pub binary_data: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub direction: Option<Direction>,
+    /// Comma separated list of event fields to return.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fields: Option<String>,
+    /// Inclusive lower bound of events' `occurred_at`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from_occurred_at: Option<i64>,
+    /// Return deleted events with `deleted_at` too. Requires `update` permission on the room.
+    #[serde(default)]
+    pub include_deleted: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// Matches events whose label starts with the prefix.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label_prefix: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_occurred_at: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+    /// Event set or a list of them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub set: Option<String>,
+    /// Exclusive upper bound of events' `occurred_at`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to_occurred_at: Option<i64>,
+    /// Event type or a list of them.
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Direction {
+    #[serde(rename = "forward")]
+    Forward,
+    #[serde(rename = "backward")]
+    Backward,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ReadStatePayload {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attribute: Option<String>,
+    /// Comma separated list of event fields to return.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fields: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub occurred_at: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_occurred_at: Option<i64>,
+    pub sets: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Agent {
+    pub agent_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub banned: Option<bool>,
+    pub created_at: i64,
+    /// Seconds since the agent has entered the room.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub presence_duration: Option<i64>,
+    /// Client-defined state of the agent in the room set with `agent.update`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub presence_meta: Option<JsonValue>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    pub room_id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub whiteboard_access: Option<bool>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct ListAgentsPayload {
+    /// Comma separated list of optional properties to add: `presence`, `whiteboard_access`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub include: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<usize>,
+}
@@ -0,0 +1,30 @@
+-- Existing rows are moved to `attributes` by the `/event_attributes/backfill` admin job in batches
+-- rather than here not to rewrite the whole event table inside the migration.
+-- `attribute` is dropped by a later migration once the backfill is done.
+ALTER TABLE event ADD COLUMN IF NOT EXISTS attributes TEXT[] NOT NULL DEFAULT '{}';
+
+-- Keeps both columns in sync while instances of the previous release still write `attribute`.
+CREATE OR REPLACE FUNCTION on_event_sync_attributes() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$
+BEGIN
+    IF TG_OP = 'UPDATE' AND NEW.attributes IS DISTINCT FROM OLD.attributes THEN
+        NEW.attribute := NEW.attributes[1];
+    ELSIF TG_OP = 'UPDATE' AND NEW.attribute IS DISTINCT FROM OLD.attribute THEN
+        NEW.attributes := CASE WHEN NEW.attribute IS NULL THEN '{}' ELSE ARRAY[NEW.attribute] END;
+    ELSIF TG_OP = 'INSERT' AND NEW.attributes = '{}' AND NEW.attribute IS NOT NULL THEN
+        NEW.attributes := ARRAY[NEW.attribute];
+    ELSIF TG_OP = 'INSERT' THEN
+        NEW.attribute := NEW.attributes[1];
+    END IF;
+
+    RETURN NEW;
+END;
+$$;
+
+DO $$ BEGIN
+    CREATE TRIGGER event_sync_attributes_trigger BEFORE INSERT OR UPDATE OF attribute, attributes
+    ON event FOR EACH ROW EXECUTE FUNCTION on_event_sync_attributes();
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS tenant_ban (
+    id uuid DEFAULT gen_random_uuid() NOT NULL,
+    audience TEXT NOT NULL,
+    account_id account_id NOT NULL,
+    reason TEXT,
+    created_by account_id NOT NULL,
+    created_at timestamp with time zone DEFAULT now() NOT NULL,
+
+    PRIMARY KEY (id),
+    UNIQUE (account_id, audience)
+);
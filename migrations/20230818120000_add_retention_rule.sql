@@ -0,0 +1,21 @@
+CREATE TABLE IF NOT EXISTS retention_rule (
+    id uuid DEFAULT gen_random_uuid() NOT NULL,
+    room_id uuid,
+    audience TEXT,
+    kind TEXT NOT NULL,
+    max_lifetime BIGINT NOT NULL,
+    created_at timestamp with time zone DEFAULT now() NOT NULL,
+
+    PRIMARY KEY (id),
+    FOREIGN KEY (room_id) REFERENCES room (id) ON DELETE CASCADE,
+    CHECK ((room_id IS NULL) <> (audience IS NULL)),
+    CHECK (max_lifetime > 0)
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS retention_rule_room_id_kind_idx
+ON retention_rule (room_id, kind)
+WHERE room_id IS NOT NULL;
+
+CREATE UNIQUE INDEX IF NOT EXISTS retention_rule_audience_kind_idx
+ON retention_rule (audience, kind)
+WHERE audience IS NOT NULL;
@@ -0,0 +1,17 @@
+CREATE TABLE IF NOT EXISTS nats_dead_letter (
+    id uuid DEFAULT gen_random_uuid() NOT NULL,
+    subject TEXT NOT NULL,
+    classroom_id uuid NOT NULL,
+    entity_type TEXT NOT NULL,
+    entity_event_id BIGINT NOT NULL,
+    label TEXT NOT NULL,
+    created_by agent_id NOT NULL,
+    event_created_at timestamp with time zone NOT NULL,
+    error TEXT NOT NULL,
+    attempts INT DEFAULT 1 NOT NULL,
+    created_at timestamp with time zone DEFAULT now() NOT NULL,
+    updated_at timestamp with time zone DEFAULT now() NOT NULL,
+
+    PRIMARY KEY (id),
+    UNIQUE (entity_type, entity_event_id)
+);
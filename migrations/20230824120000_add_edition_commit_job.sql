@@ -0,0 +1,23 @@
+CREATE TYPE edition_commit_job_status AS ENUM ('running', 'succeeded', 'failed');
+
+CREATE TABLE IF NOT EXISTS edition_commit_job (
+    id uuid DEFAULT gen_random_uuid() NOT NULL,
+    edition_id uuid NOT NULL,
+    time_offset BIGINT NOT NULL,
+    status edition_commit_job_status DEFAULT 'running' NOT NULL,
+    destination_room_id uuid,
+    checkpoint BIGINT,
+    cloned_events BIGINT DEFAULT 0 NOT NULL,
+    attempts INT DEFAULT 1 NOT NULL,
+    error TEXT,
+    created_at timestamp with time zone DEFAULT now() NOT NULL,
+    updated_at timestamp with time zone DEFAULT now() NOT NULL,
+
+    FOREIGN KEY (edition_id) REFERENCES edition (id) ON DELETE CASCADE,
+    FOREIGN KEY (destination_room_id) REFERENCES room (id) ON DELETE SET NULL,
+    PRIMARY KEY (id)
+);
+
+CREATE INDEX IF NOT EXISTS edition_commit_job_running_idx
+ON edition_commit_job (updated_at)
+WHERE status = 'running';
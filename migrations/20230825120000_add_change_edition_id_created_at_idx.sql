@@ -0,0 +1 @@
+CREATE INDEX IF NOT EXISTS change_edition_id_created_at_idx ON change USING btree (edition_id, created_at DESC, id DESC);
@@ -0,0 +1,3 @@
+ALTER TABLE event
+    ADD COLUMN IF NOT EXISTS content_encrypted BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN IF NOT EXISTS key_id TEXT;
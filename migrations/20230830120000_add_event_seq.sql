@@ -0,0 +1,9 @@
+-- Events get a monotonically increasing number to let reconnecting clients fetch what they missed.
+-- The column is added without a default first so existing rows aren't rewritten and keep NULL.
+CREATE SEQUENCE IF NOT EXISTS event_seq;
+
+ALTER TABLE event ADD COLUMN IF NOT EXISTS seq BIGINT;
+ALTER TABLE event ALTER COLUMN seq SET DEFAULT nextval('event_seq');
+ALTER SEQUENCE event_seq OWNED BY event.seq;
+
+CREATE INDEX IF NOT EXISTS event_room_id_seq_idx ON event USING btree (room_id, seq) WHERE seq IS NOT NULL;
@@ -0,0 +1 @@
+ALTER TABLE room ADD COLUMN IF NOT EXISTS keep_open BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,2 @@
+CREATE INDEX IF NOT EXISTS room_tags_idx ON room USING GIN ((tags::jsonb) jsonb_path_ops);
+CREATE INDEX IF NOT EXISTS room_audience_created_at_idx ON room (audience, created_at);
@@ -0,0 +1,21 @@
+CREATE TYPE adjustment_status AS ENUM ('running', 'succeeded', 'failed');
+
+ALTER TABLE adjustment
+    ADD COLUMN status adjustment_status DEFAULT 'running' NOT NULL,
+    ADD COLUMN original_room_id uuid,
+    ADD COLUMN modified_room_id uuid,
+    ADD COLUMN modified_segments int8range[],
+    ADD COLUMN cut_original_segments int8range[],
+    ADD COLUMN error jsonb,
+    ADD COLUMN finished_at timestamp with time zone,
+    ADD FOREIGN KEY (original_room_id) REFERENCES room (id) ON DELETE SET NULL,
+    ADD FOREIGN KEY (modified_room_id) REFERENCES room (id) ON DELETE SET NULL;
+
+-- Results of past adjustments are unknown but successful ones have left derived rooms.
+UPDATE adjustment AS a
+SET status = CASE
+        WHEN EXISTS (SELECT 1 FROM room WHERE source_room_id = a.room_id)
+        THEN 'succeeded'::adjustment_status
+        ELSE 'failed'::adjustment_status
+    END,
+    finished_at = a.created_at;
@@ -0,0 +1,40 @@
+-- Hourly event counts per room and kind maintained on event insert so analytics don't have to
+-- aggregate the event table. Activity is tracked since this migration, history isn't backfilled.
+CREATE TABLE IF NOT EXISTS room_activity (
+    room_id uuid NOT NULL,
+    kind text NOT NULL,
+    hour timestamptz NOT NULL,
+    count bigint NOT NULL,
+    last_event_at timestamptz NOT NULL,
+
+    FOREIGN KEY (room_id) REFERENCES room (id) ON DELETE CASCADE,
+    PRIMARY KEY (room_id, kind, hour)
+);
+
+-- Statement level so that bulk inserts like adjustment clones update each row once
+-- with the statement's totals instead of once per event.
+CREATE OR REPLACE FUNCTION on_event_insert_update_activity() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$
+BEGIN
+    -- Rows are locked in the same order by concurrent statements not to deadlock.
+    INSERT INTO room_activity (room_id, kind, hour, count, last_event_at)
+    SELECT room_id, kind, date_trunc('hour', created_at), COUNT(*), MAX(created_at)
+    FROM inserted_event
+    GROUP BY room_id, kind, date_trunc('hour', created_at)
+    ORDER BY room_id, kind, date_trunc('hour', created_at)
+    ON CONFLICT (room_id, kind, hour) DO UPDATE
+    SET count = room_activity.count + EXCLUDED.count,
+        last_event_at = GREATEST(room_activity.last_event_at, EXCLUDED.last_event_at);
+
+    RETURN NULL;
+END;
+$$;
+
+DO $$ BEGIN
+    CREATE TRIGGER event_insert_activity_trigger AFTER INSERT
+    ON event REFERENCING NEW TABLE AS inserted_event
+    FOR EACH STATEMENT EXECUTE FUNCTION on_event_insert_update_activity();
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
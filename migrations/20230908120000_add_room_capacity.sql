@@ -0,0 +1,13 @@
+ALTER TABLE room ADD COLUMN IF NOT EXISTS capacity INT;
+
+CREATE TABLE IF NOT EXISTS room_queue (
+    room_id uuid NOT NULL,
+    agent_id agent_id NOT NULL,
+    created_at timestamp with time zone DEFAULT now() NOT NULL,
+    admitted_at timestamp with time zone,
+
+    PRIMARY KEY (room_id, agent_id),
+    FOREIGN KEY (room_id) REFERENCES room (id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS room_queue_room_id_created_at_idx ON room_queue (room_id, created_at);
@@ -0,0 +1,3 @@
+CREATE INDEX IF NOT EXISTS event_room_id_set_occurred_at_idx
+    ON event USING btree (room_id, set, occurred_at)
+    WHERE deleted_at IS NULL;
@@ -0,0 +1 @@
+ALTER TABLE room ADD COLUMN IF NOT EXISTS settings JSONB NOT NULL DEFAULT '{}'::JSONB;
@@ -0,0 +1,4 @@
+-- Speeds up the scan for draw events to re-encode into the binary format.
+CREATE INDEX IF NOT EXISTS event_legacy_draw_idx
+    ON event (id)
+    WHERE kind = 'draw' AND binary_data IS NULL AND data IS NOT NULL;
@@ -0,0 +1,17 @@
+-- Locked types changes to apply to a room later, either at `apply_at` or `before_close` seconds
+-- before the room closes.
+CREATE TABLE IF NOT EXISTS room_lock_schedule (
+    id uuid DEFAULT gen_random_uuid() NOT NULL,
+    room_id uuid NOT NULL,
+    locked_types jsonb NOT NULL,
+    apply_at timestamp with time zone,
+    before_close INT,
+    created_by agent_id NOT NULL,
+    created_at timestamp with time zone DEFAULT now() NOT NULL,
+
+    CHECK ((apply_at IS NULL) <> (before_close IS NULL)),
+    FOREIGN KEY (room_id) REFERENCES room (id) ON DELETE CASCADE,
+    PRIMARY KEY (id)
+);
+
+CREATE INDEX IF NOT EXISTS room_lock_schedule_room_id_idx ON room_lock_schedule (room_id);
@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS processed_entity_event (
+    entity_type TEXT NOT NULL,
+    entity_event_id BIGINT NOT NULL,
+    created_at timestamp with time zone DEFAULT now() NOT NULL,
+
+    PRIMARY KEY (entity_type, entity_event_id)
+);
+
+INSERT INTO processed_entity_event (entity_type, entity_event_id, created_at)
+SELECT entity_type, entity_event_id, created_at
+FROM event
+WHERE entity_type IS NOT NULL
+AND   entity_event_id IS NOT NULL
+ON CONFLICT DO NOTHING;
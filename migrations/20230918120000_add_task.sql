@@ -0,0 +1,19 @@
+CREATE TYPE task_status AS ENUM ('running', 'succeeded', 'failed');
+
+-- Asynchronous tasks started by requests along with the notifications they finished with
+-- so that clients who missed the notification could still get the outcome.
+CREATE TABLE IF NOT EXISTS task (
+    id uuid DEFAULT gen_random_uuid() NOT NULL,
+    kind text NOT NULL,
+    room_id uuid NOT NULL,
+    status task_status DEFAULT 'running' NOT NULL,
+    notification jsonb,
+    created_by agent_id NOT NULL,
+    created_at timestamp with time zone DEFAULT now() NOT NULL,
+    finished_at timestamp with time zone,
+
+    FOREIGN KEY (room_id) REFERENCES room (id) ON DELETE CASCADE,
+    PRIMARY KEY (id)
+);
+
+CREATE INDEX IF NOT EXISTS task_room_id_idx ON task (room_id);
@@ -0,0 +1,2 @@
+-- Small client-defined state of the agent in the room like a raised hand.
+ALTER TABLE agent ADD COLUMN IF NOT EXISTS presence_meta jsonb;
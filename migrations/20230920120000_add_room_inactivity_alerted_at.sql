@@ -0,0 +1,2 @@
+-- When tenants were last alerted about events having stopped arriving in the room.
+ALTER TABLE room ADD COLUMN IF NOT EXISTS inactivity_alerted_at timestamptz;
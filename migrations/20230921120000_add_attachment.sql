@@ -0,0 +1,54 @@
+-- Attachments referenced by `attachment` events maintained on event insert so they may be listed
+-- and filtered by type without looking into event data.
+CREATE TABLE IF NOT EXISTS attachment (
+    event_id uuid PRIMARY KEY,
+    room_id uuid NOT NULL,
+    url text NOT NULL,
+    size bigint NOT NULL,
+    mime text NOT NULL,
+    name text,
+    created_by agent_id NOT NULL,
+    created_at timestamptz NOT NULL,
+
+    FOREIGN KEY (event_id) REFERENCES event (id) ON DELETE CASCADE,
+    FOREIGN KEY (room_id) REFERENCES room (id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS attachment_room_id_created_at_idx ON attachment (room_id, created_at);
+CREATE INDEX IF NOT EXISTS attachment_room_id_mime_idx ON attachment (room_id, mime);
+
+CREATE OR REPLACE FUNCTION on_event_insert_record_attachment() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$
+BEGIN
+    -- Events cloned from before validation may lack the fields so they're skipped.
+    IF NEW.kind = 'attachment'
+        AND NOT NEW.removed
+        AND jsonb_typeof(NEW.data->'url') = 'string'
+        AND jsonb_typeof(NEW.data->'size') = 'number'
+        AND jsonb_typeof(NEW.data->'mime') = 'string'
+    THEN
+        INSERT INTO attachment (event_id, room_id, url, size, mime, name, created_by, created_at)
+        VALUES (
+            NEW.id,
+            NEW.room_id,
+            NEW.data->>'url',
+            (NEW.data->>'size')::bigint,
+            NEW.data->>'mime',
+            NEW.data->>'name',
+            NEW.created_by,
+            NEW.created_at
+        )
+        ON CONFLICT (event_id) DO NOTHING;
+    END IF;
+
+    RETURN NULL;
+END;
+$$;
+
+DO $$ BEGIN
+    CREATE TRIGGER event_insert_attachment_trigger AFTER INSERT
+    ON event FOR EACH ROW EXECUTE FUNCTION on_event_insert_record_attachment();
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
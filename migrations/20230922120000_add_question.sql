@@ -0,0 +1,25 @@
+-- Questions of the Q&A are `question` events. Their vote counters and answers are kept aside
+-- so that questions may be sorted by votes without scanning events.
+CREATE TABLE IF NOT EXISTS question (
+    id uuid PRIMARY KEY,
+    room_id uuid NOT NULL,
+    votes integer NOT NULL DEFAULT 0,
+    answer_id uuid,
+    answered_at timestamptz,
+
+    FOREIGN KEY (id) REFERENCES event (id) ON DELETE CASCADE,
+    FOREIGN KEY (room_id) REFERENCES room (id) ON DELETE CASCADE,
+    FOREIGN KEY (answer_id) REFERENCES event (id) ON DELETE SET NULL
+);
+
+CREATE INDEX IF NOT EXISTS question_room_id_votes_idx ON question (room_id, votes);
+
+-- An account votes for a question once.
+CREATE TABLE IF NOT EXISTS question_vote (
+    question_id uuid NOT NULL,
+    account_id account_id NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now(),
+
+    PRIMARY KEY (question_id, account_id),
+    FOREIGN KEY (question_id) REFERENCES question (id) ON DELETE CASCADE
+);
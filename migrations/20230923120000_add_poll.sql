@@ -0,0 +1,39 @@
+-- Polls are `poll` events. Votes are counted aside so that individual votes never get into
+-- the room history and results are available without scanning votes.
+CREATE TABLE IF NOT EXISTS poll (
+    id uuid PRIMARY KEY,
+    room_id uuid NOT NULL,
+    options integer NOT NULL,
+    -- Quizzes have a correct option revealed with the results once the poll gets closed.
+    correct_option integer,
+    closes_at timestamptz,
+    closed_at timestamptz,
+
+    FOREIGN KEY (id) REFERENCES event (id) ON DELETE CASCADE,
+    FOREIGN KEY (room_id) REFERENCES room (id) ON DELETE CASCADE,
+    CHECK (options > 0),
+    CHECK (correct_option IS NULL OR (correct_option >= 0 AND correct_option < options))
+);
+
+CREATE INDEX IF NOT EXISTS poll_closes_at_idx ON poll (closes_at) WHERE closed_at IS NULL;
+
+-- An account votes in a poll once. Kept to deduplicate votes only.
+CREATE TABLE IF NOT EXISTS poll_vote (
+    poll_id uuid NOT NULL,
+    account_id account_id NOT NULL,
+    option integer NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now(),
+
+    PRIMARY KEY (poll_id, account_id),
+    FOREIGN KEY (poll_id) REFERENCES poll (id) ON DELETE CASCADE
+);
+
+-- Vote counters updated along with inserting votes.
+CREATE TABLE IF NOT EXISTS poll_tally (
+    poll_id uuid NOT NULL,
+    option integer NOT NULL,
+    votes integer NOT NULL DEFAULT 0,
+
+    PRIMARY KEY (poll_id, option),
+    FOREIGN KEY (poll_id) REFERENCES poll (id) ON DELETE CASCADE
+);
@@ -0,0 +1,12 @@
+-- Agents who raised their hands in the room in the order of raising.
+-- Hands are lowered when agents leave the room.
+CREATE TABLE IF NOT EXISTS hand_queue (
+    room_id uuid NOT NULL,
+    agent_id agent_id NOT NULL,
+    raised_at timestamptz NOT NULL DEFAULT now(),
+
+    PRIMARY KEY (room_id, agent_id),
+    FOREIGN KEY (agent_id, room_id) REFERENCES agent (agent_id, room_id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS hand_queue_room_id_raised_at_idx ON hand_queue (room_id, raised_at);
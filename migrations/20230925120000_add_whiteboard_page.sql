@@ -0,0 +1,65 @@
+-- Whiteboard pages maintained from `page` events on insert so clients may fetch the page list
+-- without replaying the whole history. The label of a page event is the set of its draw events.
+CREATE TABLE IF NOT EXISTS whiteboard_page (
+    room_id uuid NOT NULL,
+    label text NOT NULL,
+    event_id uuid NOT NULL,
+    position double precision,
+    data jsonb NOT NULL,
+    removed boolean NOT NULL,
+    occurred_at bigint NOT NULL,
+    created_at timestamptz NOT NULL,
+    updated_at timestamptz NOT NULL,
+
+    PRIMARY KEY (room_id, label),
+    FOREIGN KEY (event_id) REFERENCES event (id) ON DELETE CASCADE,
+    FOREIGN KEY (room_id) REFERENCES room (id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS whiteboard_page_event_id_idx ON whiteboard_page (event_id);
+
+CREATE OR REPLACE FUNCTION on_event_insert_record_whiteboard_page() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$
+BEGIN
+    -- Events may get inserted out of order on cloning so only the latest one is kept.
+    IF NEW.kind = 'page'
+        AND NEW.label IS NOT NULL
+        AND (NEW.removed OR jsonb_typeof(NEW.data->'position') = 'number')
+    THEN
+        INSERT INTO whiteboard_page (
+            room_id, label, event_id, position, data, removed, occurred_at, created_at, updated_at
+        )
+        VALUES (
+            NEW.room_id,
+            NEW.label,
+            NEW.id,
+            CASE WHEN jsonb_typeof(NEW.data->'position') = 'number'
+                THEN (NEW.data->>'position')::double precision
+            END,
+            NEW.data,
+            NEW.removed,
+            NEW.occurred_at,
+            NEW.created_at,
+            NEW.created_at
+        )
+        ON CONFLICT (room_id, label) DO UPDATE
+        SET event_id = EXCLUDED.event_id,
+            position = COALESCE(EXCLUDED.position, whiteboard_page.position),
+            data = EXCLUDED.data,
+            removed = EXCLUDED.removed,
+            occurred_at = EXCLUDED.occurred_at,
+            updated_at = EXCLUDED.updated_at
+        WHERE whiteboard_page.occurred_at < EXCLUDED.occurred_at;
+    END IF;
+
+    RETURN NULL;
+END;
+$$;
+
+DO $$ BEGIN
+    CREATE TRIGGER event_insert_whiteboard_page_trigger AFTER INSERT
+    ON event FOR EACH ROW EXECUTE FUNCTION on_event_insert_record_whiteboard_page();
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
@@ -0,0 +1,10 @@
+-- Disagreements of the shadow adjustment pipeline with the one whose result was persisted.
+CREATE TABLE IF NOT EXISTS adjustment_comparison (
+    room_id uuid NOT NULL,
+    matched boolean NOT NULL,
+    diff jsonb NOT NULL,
+    created_at timestamp with time zone DEFAULT now() NOT NULL,
+
+    PRIMARY KEY (room_id),
+    FOREIGN KEY (room_id) REFERENCES adjustment(room_id) ON DELETE CASCADE
+);
@@ -0,0 +1,70 @@
+-- Sets of each room with the creation time of their latest event maintained on event insert
+-- and approval so that clients may list sets of a room without reading its whole state.
+-- Existing events are picked up by the `/set_heads/backfill` admin job rather than here
+-- not to scan the whole event table inside the migration.
+CREATE TABLE IF NOT EXISTS set_head (
+    room_id uuid NOT NULL,
+    set text NOT NULL,
+    updated_at timestamptz NOT NULL,
+
+    FOREIGN KEY (room_id) REFERENCES room (id) ON DELETE CASCADE,
+    PRIMARY KEY (room_id, set)
+);
+
+-- Statement level so that bulk inserts like adjustment clones update each row once.
+CREATE OR REPLACE FUNCTION on_event_insert_set_head() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$
+BEGIN
+    -- Rows are locked in the same order by concurrent statements not to deadlock.
+    INSERT INTO set_head (room_id, set, updated_at)
+    SELECT room_id, set, MAX(created_at)
+    FROM inserted_event
+    WHERE deleted_at IS NULL
+    AND   moderation_status = 'approved'
+    GROUP BY room_id, set
+    ORDER BY room_id, set
+    ON CONFLICT (room_id, set) DO UPDATE
+    SET updated_at = GREATEST(set_head.updated_at, EXCLUDED.updated_at);
+
+    RETURN NULL;
+END;
+$$;
+
+-- Approval only, other updates don't move `updated_at` since `created_at` never changes.
+CREATE OR REPLACE FUNCTION on_event_approve_set_head() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$
+BEGIN
+    INSERT INTO set_head (room_id, set, updated_at)
+    SELECT new_event.room_id, new_event.set, MAX(new_event.created_at)
+    FROM new_event
+    INNER JOIN old_event
+    ON old_event.id = new_event.id
+    WHERE new_event.deleted_at IS NULL
+    AND   new_event.moderation_status = 'approved'
+    AND   old_event.moderation_status <> 'approved'
+    GROUP BY new_event.room_id, new_event.set
+    ORDER BY new_event.room_id, new_event.set
+    ON CONFLICT (room_id, set) DO UPDATE
+    SET updated_at = GREATEST(set_head.updated_at, EXCLUDED.updated_at);
+
+    RETURN NULL;
+END;
+$$;
+
+DO $$ BEGIN
+    CREATE TRIGGER event_insert_set_head_trigger AFTER INSERT
+    ON event REFERENCING NEW TABLE AS inserted_event
+    FOR EACH STATEMENT EXECUTE FUNCTION on_event_insert_set_head();
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+DO $$ BEGIN
+    CREATE TRIGGER event_approve_set_head_trigger AFTER UPDATE
+    ON event REFERENCING OLD TABLE AS old_event NEW TABLE AS new_event
+    FOR EACH STATEMENT EXECUTE FUNCTION on_event_approve_set_head();
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
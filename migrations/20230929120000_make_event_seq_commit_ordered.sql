@@ -0,0 +1,97 @@
+-- `seq` taken from the sequence default is allocated on insert, so a transaction committing
+-- later may expose a smaller `seq` than the one a client has already seen and `event.since` skips it.
+-- The default stays as a provisional value and a deferred trigger assigns the final `seq` right before
+-- commit. A per-room transaction lock is taken there too so within a room `seq` grows in commit order
+-- while writers are only serialized from the assignment to the commit, not for whole transactions.
+-- The final `seq` is always greater than the provisional one so resuming from the latter doesn't skip
+-- anything but may return the event again.
+--
+-- `seq` is assigned once and never changes afterwards so an event's `seq` always points
+-- at the place it was inserted at.
+CREATE OR REPLACE FUNCTION on_event_assign_seq() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$
+BEGIN
+    PERFORM pg_advisory_xact_lock(hashtextextended('event_seq:' || NEW.room_id::text, 0));
+
+    UPDATE event
+    SET seq = nextval('event_seq')
+    WHERE id = NEW.id;
+
+    RETURN NULL;
+END;
+$$;
+
+-- Bulk inserts into rooms nobody writes to yet, e.g. adjustment clones, keep the provisional `seq`
+-- with `SELECT set_config('cfg.event_seq_on_commit', 'FALSE', true)` not to update every row again.
+DO $$ BEGIN
+    CREATE CONSTRAINT TRIGGER event_insert_seq_trigger AFTER INSERT
+    ON event DEFERRABLE INITIALLY DEFERRED
+    FOR EACH ROW
+    WHEN (current_setting('cfg.event_seq_on_commit', 't') IS DISTINCT FROM 'FALSE')
+    EXECUTE FUNCTION on_event_assign_seq();
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+-- Approval, attribute changes and soft deletion are appended to the room's change log
+-- with `seq` from the same sequence so that clients resuming with `event.since` learn about them.
+CREATE TABLE IF NOT EXISTS event_change (
+    room_id uuid NOT NULL,
+    seq bigint NOT NULL,
+    event_id uuid NOT NULL,
+    changed_at timestamptz NOT NULL DEFAULT now(),
+
+    FOREIGN KEY (room_id) REFERENCES room (id) ON DELETE CASCADE,
+    FOREIGN KEY (event_id) REFERENCES event (id) ON DELETE CASCADE,
+    PRIMARY KEY (room_id, seq)
+);
+
+CREATE INDEX IF NOT EXISTS event_change_event_id_idx ON event_change (event_id);
+
+-- Statement level so that bulk changes append to the log with a single insert.
+-- The room locks are the ones of the `seq` assignment and are held until commit, so changes
+-- and inserts of a room still get `seq` in commit order. Changes are short statements so
+-- holding the lock for the rest of their transactions costs little.
+CREATE OR REPLACE FUNCTION on_event_log_change() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$
+BEGIN
+    -- `seq` assignment on commit doesn't change the event for clients.
+    IF pg_trigger_depth() > 1 THEN
+        RETURN NULL;
+    END IF;
+
+    -- Rooms are locked in the same order by concurrent statements not to deadlock.
+    PERFORM pg_advisory_xact_lock(hashtextextended('event_seq:' || changed_room.room_id::text, 0))
+    FROM (
+        SELECT DISTINCT new_event.room_id
+        FROM new_event
+        INNER JOIN old_event
+        ON old_event.id = new_event.id
+        WHERE old_event.moderation_status IS DISTINCT FROM new_event.moderation_status
+        OR    old_event.attributes IS DISTINCT FROM new_event.attributes
+        OR    old_event.deleted_at IS DISTINCT FROM new_event.deleted_at
+        ORDER BY new_event.room_id
+    ) AS changed_room;
+
+    INSERT INTO event_change (room_id, seq, event_id)
+    SELECT new_event.room_id, nextval('event_seq'), new_event.id
+    FROM new_event
+    INNER JOIN old_event
+    ON old_event.id = new_event.id
+    WHERE old_event.moderation_status IS DISTINCT FROM new_event.moderation_status
+    OR    old_event.attributes IS DISTINCT FROM new_event.attributes
+    OR    old_event.deleted_at IS DISTINCT FROM new_event.deleted_at;
+
+    RETURN NULL;
+END;
+$$;
+
+DO $$ BEGIN
+    CREATE TRIGGER event_log_change_trigger AFTER UPDATE
+    ON event REFERENCING OLD TABLE AS old_event NEW TABLE AS new_event
+    FOR EACH STATEMENT EXECUTE FUNCTION on_event_log_change();
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
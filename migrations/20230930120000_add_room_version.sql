@@ -0,0 +1,58 @@
+-- Version of each room's events bumped on every insert, update and delete so that entity tags
+-- change whenever anything in the response may have changed. A missing row means version 0.
+CREATE TABLE IF NOT EXISTS room_version (
+    room_id uuid NOT NULL,
+    version bigint NOT NULL,
+
+    FOREIGN KEY (room_id) REFERENCES room (id) ON DELETE CASCADE,
+    PRIMARY KEY (room_id)
+);
+
+-- Statement level so that bulk changes like adjustment clones, edition commits and vacuum
+-- bump each room's version once instead of updating the same row for every event.
+CREATE OR REPLACE FUNCTION on_event_change_room_version() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$
+BEGIN
+    -- Changes made by other triggers, e.g. `seq` assignment on commit or cascading deletes,
+    -- go along with a statement which has already bumped the version.
+    IF pg_trigger_depth() > 1 THEN
+        RETURN NULL;
+    END IF;
+
+    -- Rooms are locked in the same order by concurrent statements not to deadlock.
+    INSERT INTO room_version (room_id, version)
+    SELECT DISTINCT room_id, 1
+    FROM changed_event
+    WHERE EXISTS (SELECT 1 FROM room WHERE id = changed_event.room_id)
+    ORDER BY room_id
+    ON CONFLICT (room_id) DO UPDATE
+    SET version = room_version.version + 1;
+
+    RETURN NULL;
+END;
+$$;
+
+DO $$ BEGIN
+    CREATE TRIGGER event_insert_room_version_trigger AFTER INSERT
+    ON event REFERENCING NEW TABLE AS changed_event
+    FOR EACH STATEMENT EXECUTE FUNCTION on_event_change_room_version();
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+DO $$ BEGIN
+    CREATE TRIGGER event_update_room_version_trigger AFTER UPDATE
+    ON event REFERENCING NEW TABLE AS changed_event
+    FOR EACH STATEMENT EXECUTE FUNCTION on_event_change_room_version();
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+DO $$ BEGIN
+    CREATE TRIGGER event_delete_room_version_trigger AFTER DELETE
+    ON event REFERENCING OLD TABLE AS changed_event
+    FOR EACH STATEMENT EXECUTE FUNCTION on_event_change_room_version();
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
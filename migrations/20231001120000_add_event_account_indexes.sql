@@ -0,0 +1,6 @@
+-- Account export and anonymization look events up by author in both columns.
+CREATE INDEX IF NOT EXISTS event_created_by_account_id_idx
+    ON event (((created_by).account_id));
+
+CREATE INDEX IF NOT EXISTS event_original_created_by_account_id_idx
+    ON event (((original_created_by).account_id));
@@ -0,0 +1,11 @@
+-- Follows the `/event_attributes/backfill` admin job of the previous release.
+-- Picks up events it may have missed, there should be none left so nothing is rewritten.
+UPDATE event
+SET attributes = ARRAY[attribute]
+WHERE attribute IS NOT NULL
+AND   attributes = '{}';
+
+DROP TRIGGER IF EXISTS event_sync_attributes_trigger ON event;
+DROP FUNCTION IF EXISTS on_event_sync_attributes();
+
+ALTER TABLE event DROP COLUMN IF EXISTS attribute;
@@ -24,12 +24,8 @@ impl SubscriptionRequest {
         }
     }
 
-    fn room_events(subject: &AgentId, room_id: Uuid) -> Self {
-        let object = vec![
-            "rooms".to_string(),
-            room_id.to_string(),
-            "events".to_string(),
-        ];
+    fn topic(subject: &AgentId, topic: &str) -> Self {
+        let object = topic.split('/').map(|s| s.to_string()).collect();
         Self::new(subject.clone(), object)
     }
 }
@@ -70,8 +66,12 @@ mod payload_test {
 #[cfg_attr(test, automock)]
 #[async_trait]
 pub trait BrokerClient: Sync + Send {
-    async fn enter_room(&self, id: Uuid, subject: &AgentId)
-        -> anyhow::Result<CreateDeleteResponse>;
+    /// Subscribes the agent to the room topic of the room's audience.
+    async fn enter_room(
+        &self,
+        topic: &str,
+        subject: &AgentId,
+    ) -> anyhow::Result<CreateDeleteResponse>;
     async fn enter_broadcast_room(
         &self,
         id: Uuid,
@@ -129,11 +129,10 @@ impl HttpBrokerClient {
 impl BrokerClient for HttpBrokerClient {
     async fn enter_room(
         &self,
-        id: Uuid,
+        topic: &str,
         subject: &AgentId,
     ) -> anyhow::Result<CreateDeleteResponse> {
-        let payload =
-            serde_json::to_string(&SubscriptionRequest::room_events(subject, id)).unwrap();
+        let payload = serde_json::to_string(&SubscriptionRequest::topic(subject, topic)).unwrap();
 
         let url = self.host.join("/api/v1/subscriptions").unwrap();
         let response = self.http.post(url).body(payload).send().await?;
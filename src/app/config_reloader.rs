@@ -0,0 +1,49 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use futures::StreamExt;
+use signal_hook::consts::SIGHUP;
+use tokio::{sync::watch, task::JoinHandle};
+use tracing::{error, info};
+
+use crate::app::context::{AppContext, GlobalContext};
+use crate::config;
+
+/// Reloads runtime-tunable config values on SIGHUP until shutdown.
+pub fn run(
+    context: Arc<AppContext>,
+    mut shutdown_rx: watch::Receiver<()>,
+) -> Result<JoinHandle<()>> {
+    let mut signals =
+        signal_hook_tokio::Signals::new([SIGHUP]).context("Failed to listen to SIGHUP")?;
+
+    let handle = tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                signal = signals.next() => {
+                    if signal.is_none() {
+                        return;
+                    }
+                }
+                _ = shutdown_rx.changed() => return,
+            }
+
+            if let Err(err) = reload(context.as_ref()) {
+                error!("Failed to reload config: {:?}", err);
+            }
+        }
+    });
+
+    Ok(handle)
+}
+
+/// Loads the config from scratch and applies its tunable values.
+///
+/// Invalid config leaves the current one untouched.
+pub fn reload<C: GlobalContext + ?Sized>(context: &C) -> Result<()> {
+    let fresh = config::load().context("Failed to load config")?;
+    let config = context.config().with_tunables(fresh);
+    info!("Reloaded tunable config values");
+    context.store_config(config);
+    Ok(())
+}
@@ -0,0 +1,123 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use arc_swap::ArcSwap;
+use async_trait::async_trait;
+#[cfg(test)]
+use mockall::automock;
+use reqwest::header;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::config::{Config, ContentFilterConfig};
+use crate::db::room::Object as Room;
+
+/// Attribute added to messages the classifier flags for moderators to review.
+pub const FLAGGED_ATTRIBUTE: &str = "flagged";
+
+/// Classifier's decision about a message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Verdict {
+    Allow,
+    Flag,
+    Reject,
+}
+
+#[cfg_attr(test, automock)]
+#[async_trait]
+pub trait ContentFilter: Sync + Send {
+    /// Classifies data of a `message` event in the room. Messages are allowed when the filter
+    /// isn't configured for the room's audience. Fails only when the classifier is unavailable
+    /// and the filter fails closed.
+    async fn check(&self, room: &Room, data: &JsonValue) -> Result<Verdict>;
+}
+
+#[derive(Serialize)]
+struct ClassifyRequest<'a> {
+    audience: &'a str,
+    room_id: Uuid,
+    classroom_id: Uuid,
+    data: &'a JsonValue,
+}
+
+#[derive(Deserialize)]
+struct ClassifyResponse {
+    verdict: Verdict,
+}
+
+pub struct HttpContentFilter {
+    http: reqwest::Client,
+    /// Shared with the app context to pick up the reloaded filter settings.
+    config: Arc<ArcSwap<Config>>,
+}
+
+impl HttpContentFilter {
+    pub fn new(config: Arc<ArcSwap<Config>>) -> Result<Self> {
+        let http = reqwest::Client::builder()
+            .user_agent(format!("event-{}", crate::APP_VERSION))
+            .build()?;
+
+        Ok(Self { http, config })
+    }
+
+    async fn classify(
+        &self,
+        filter: &ContentFilterConfig,
+        room: &Room,
+        data: &JsonValue,
+    ) -> Result<Verdict> {
+        let request = ClassifyRequest {
+            audience: room.audience(),
+            room_id: room.id(),
+            classroom_id: room.classroom_id(),
+            data,
+        };
+
+        let body = serde_json::to_vec(&request).context("Failed to serialize request")?;
+
+        let response = self
+            .http
+            .post(&filter.url)
+            .timeout(filter.timeout)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body)
+            .send()
+            .await
+            .and_then(|resp| resp.error_for_status())
+            .context("Classifier request failed")?;
+
+        let body = response
+            .bytes()
+            .await
+            .context("Failed to read classifier response")?;
+
+        let response = serde_json::from_slice::<ClassifyResponse>(&body)
+            .context("Failed to parse classifier response")?;
+
+        Ok(response.verdict)
+    }
+}
+
+#[async_trait]
+impl ContentFilter for HttpContentFilter {
+    async fn check(&self, room: &Room, data: &JsonValue) -> Result<Verdict> {
+        let config = self.config.load();
+
+        let filter = match config.content_filter {
+            Some(ref filter) if filter.applies_to(room.audience()) => filter,
+            _ => return Ok(Verdict::Allow),
+        };
+
+        match self.classify(filter, room, data).await {
+            Ok(verdict) => Ok(verdict),
+            Err(err) if filter.fail_open => {
+                warn!(room_id = %room.id(), "Content filter failed open: {:?}", err);
+                Ok(Verdict::Allow)
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
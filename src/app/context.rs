@@ -1,21 +1,36 @@
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use anyhow::Context as AnyhowContext;
+use arc_swap::ArcSwap;
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use sqlx::pool::PoolConnection;
 use sqlx::postgres::{PgPool as Db, Postgres};
 use svc_agent::{queue_counter::QueueCounterHandle, AgentId};
 use svc_authz::cache::ConnectionPool as RedisConnectionPool;
+use tracing::warn;
 
 use crate::config::Config;
+use crate::db::event::DataEncryption;
 use crate::{
     app::error::{Error as AppError, ErrorExt, ErrorKind as AppErrorKind},
     metrics::Metrics,
 };
-use crate::{app::s3_client::S3Client, authz::Authz};
+use crate::{
+    app::{
+        event_hooks::EventHooks, jobs::JobRegistry, presence_cache::PresenceCache,
+        redis_bridge::ClassroomIds, room_cache::RoomCache, s3_client::S3Client,
+    },
+    authz::Authz,
+};
 
 use super::broker_client::BrokerClient;
+use super::content_filter::{ContentFilter, HttpContentFilter};
+use super::webhook_client::{HttpWebhookClient, WebhookClient};
+
+/// Acquiring a connection for longer is a sign of the pool exhaustion.
+const SLOW_ACQUIRE_THRESHOLD: Duration = Duration::from_secs(1);
 
 ///////////////////////////////////////////////////////////////////////////////
 
@@ -24,33 +39,70 @@ pub trait Context: GlobalContext + MessageContext {}
 #[async_trait]
 pub trait GlobalContext: Sync {
     fn authz(&self) -> &Authz;
-    fn config(&self) -> &Config;
+    /// A snapshot of the current config. Hold it for the whole operation to see consistent values.
+    fn config(&self) -> Arc<Config>;
+    /// Replaces the config for subsequent `config` calls.
+    fn store_config(&self, config: Config);
     fn db(&self) -> &Db;
     fn ro_db(&self) -> &Db;
+    /// Pool for heavy background operations so they don't starve request handling.
+    fn background_db(&self) -> &Db;
     fn agent_id(&self) -> &AgentId;
     fn queue_counter(&self) -> &Option<QueueCounterHandle>;
     fn redis_pool(&self) -> &Option<RedisConnectionPool>;
     fn metrics(&self) -> Arc<Metrics>;
     fn s3_client(&self) -> Option<S3Client>;
     fn broker_client(&self) -> &dyn BrokerClient;
+    fn webhook_client(&self) -> Arc<dyn WebhookClient>;
+    fn content_filter(&self) -> Arc<dyn ContentFilter>;
+    fn jobs(&self) -> Arc<JobRegistry>;
+    fn presence_cache(&self) -> Arc<PresenceCache>;
+    fn room_cache(&self) -> Arc<RoomCache>;
+    fn classroom_ids(&self) -> Arc<ClassroomIds>;
+    fn event_hooks(&self) -> Arc<EventHooks>;
+    /// Encryption of event data at rest. Disabled when it's not configured.
+    fn data_encryption(&self) -> Option<Arc<DataEncryption>>;
 
     async fn get_conn(&self) -> Result<PoolConnection<Postgres>, AppError> {
-        self.db()
-            .acquire()
+        acquire_conn(self.db(), "rw", &self.metrics())
             .await
             .context("Failed to acquire DB connection")
             .error(AppErrorKind::DbConnAcquisitionFailed)
     }
 
     async fn get_ro_conn(&self) -> Result<PoolConnection<Postgres>, AppError> {
-        self.ro_db()
-            .acquire()
+        acquire_conn(self.ro_db(), "ro", &self.metrics())
             .await
             .context("Failed to acquire read-only DB connection")
             .error(AppErrorKind::DbConnAcquisitionFailed)
     }
 }
 
+async fn acquire_conn(
+    db: &Db,
+    pool: &str,
+    metrics: &Metrics,
+) -> sqlx::Result<PoolConnection<Postgres>> {
+    let waited = db.num_idle() == 0;
+    let start = Instant::now();
+    let result = db.acquire().await;
+    let duration = start.elapsed();
+
+    metrics.observe_db_acquire(pool, waited, duration);
+
+    if duration > SLOW_ACQUIRE_THRESHOLD {
+        warn!(
+            pool,
+            size = db.size(),
+            idle = db.num_idle(),
+            "Waited {:?} for DB connection",
+            duration
+        );
+    }
+
+    result
+}
+
 pub trait MessageContext: Send {
     fn start_timestamp(&self) -> DateTime<Utc>;
 }
@@ -59,16 +111,25 @@ pub trait MessageContext: Send {
 
 #[derive(Clone)]
 pub struct AppContext {
-    config: Arc<Config>,
+    config: Arc<ArcSwap<Config>>,
     authz: Authz,
     db: Db,
     ro_db: Option<Db>,
+    background_db: Option<Db>,
     agent_id: AgentId,
     queue_counter: Option<QueueCounterHandle>,
     redis_pool: Option<RedisConnectionPool>,
     metrics: Arc<Metrics>,
     s3_client: Option<S3Client>,
     broker_client: Arc<dyn BrokerClient>,
+    webhook_client: Arc<dyn WebhookClient>,
+    content_filter: Arc<dyn ContentFilter>,
+    jobs: Arc<JobRegistry>,
+    presence_cache: Arc<PresenceCache>,
+    room_cache: Arc<RoomCache>,
+    classroom_ids: Arc<ClassroomIds>,
+    event_hooks: Arc<EventHooks>,
+    data_encryption: Option<Arc<DataEncryption>>,
 }
 
 impl AppContext {
@@ -82,8 +143,12 @@ impl GlobalContext for AppContext {
         &self.authz
     }
 
-    fn config(&self) -> &Config {
-        &self.config
+    fn config(&self) -> Arc<Config> {
+        self.config.load_full()
+    }
+
+    fn store_config(&self, config: Config) {
+        self.config.store(Arc::new(config));
     }
 
     fn db(&self) -> &Db {
@@ -94,6 +159,10 @@ impl GlobalContext for AppContext {
         self.ro_db.as_ref().unwrap_or(&self.db)
     }
 
+    fn background_db(&self) -> &Db {
+        self.background_db.as_ref().unwrap_or(&self.db)
+    }
+
     fn agent_id(&self) -> &AgentId {
         &self.agent_id
     }
@@ -117,6 +186,38 @@ impl GlobalContext for AppContext {
     fn broker_client(&self) -> &dyn BrokerClient {
         self.broker_client.as_ref()
     }
+
+    fn webhook_client(&self) -> Arc<dyn WebhookClient> {
+        self.webhook_client.clone()
+    }
+
+    fn content_filter(&self) -> Arc<dyn ContentFilter> {
+        self.content_filter.clone()
+    }
+
+    fn jobs(&self) -> Arc<JobRegistry> {
+        self.jobs.clone()
+    }
+
+    fn presence_cache(&self) -> Arc<PresenceCache> {
+        self.presence_cache.clone()
+    }
+
+    fn room_cache(&self) -> Arc<RoomCache> {
+        self.room_cache.clone()
+    }
+
+    fn classroom_ids(&self) -> Arc<ClassroomIds> {
+        self.classroom_ids.clone()
+    }
+
+    fn event_hooks(&self) -> Arc<EventHooks> {
+        self.event_hooks.clone()
+    }
+
+    fn data_encryption(&self) -> Option<Arc<DataEncryption>> {
+        self.data_encryption.clone()
+    }
 }
 
 ///////////////////////////////////////////////////////////////////////////////
@@ -140,10 +241,14 @@ impl<'a, C: GlobalContext> GlobalContext for AppMessageContext<'a, C> {
         self.global_context.authz()
     }
 
-    fn config(&self) -> &Config {
+    fn config(&self) -> Arc<Config> {
         self.global_context.config()
     }
 
+    fn store_config(&self, config: Config) {
+        self.global_context.store_config(config)
+    }
+
     fn db(&self) -> &Db {
         self.global_context.db()
     }
@@ -152,6 +257,10 @@ impl<'a, C: GlobalContext> GlobalContext for AppMessageContext<'a, C> {
         self.global_context.ro_db()
     }
 
+    fn background_db(&self) -> &Db {
+        self.global_context.background_db()
+    }
+
     fn agent_id(&self) -> &AgentId {
         self.global_context.agent_id()
     }
@@ -175,6 +284,38 @@ impl<'a, C: GlobalContext> GlobalContext for AppMessageContext<'a, C> {
     fn broker_client(&self) -> &dyn BrokerClient {
         self.global_context.broker_client()
     }
+
+    fn webhook_client(&self) -> Arc<dyn WebhookClient> {
+        self.global_context.webhook_client()
+    }
+
+    fn content_filter(&self) -> Arc<dyn ContentFilter> {
+        self.global_context.content_filter()
+    }
+
+    fn jobs(&self) -> Arc<JobRegistry> {
+        self.global_context.jobs()
+    }
+
+    fn presence_cache(&self) -> Arc<PresenceCache> {
+        self.global_context.presence_cache()
+    }
+
+    fn room_cache(&self) -> Arc<RoomCache> {
+        self.global_context.room_cache()
+    }
+
+    fn classroom_ids(&self) -> Arc<ClassroomIds> {
+        self.global_context.classroom_ids()
+    }
+
+    fn event_hooks(&self) -> Arc<EventHooks> {
+        self.global_context.event_hooks()
+    }
+
+    fn data_encryption(&self) -> Option<Arc<DataEncryption>> {
+        self.global_context.data_encryption()
+    }
 }
 
 impl<'a, C: GlobalContext> MessageContext for AppMessageContext<'a, C> {
@@ -193,9 +334,12 @@ pub struct AppContextBuilder {
     db: Db,
     broker_client: Arc<dyn BrokerClient>,
     ro_db: Option<Db>,
+    background_db: Option<Db>,
     agent_id: AgentId,
     queue_counter: Option<QueueCounterHandle>,
     redis_pool: Option<RedisConnectionPool>,
+    event_hooks: EventHooks,
+    data_encryption: Option<DataEncryption>,
 }
 
 impl AppContextBuilder {
@@ -208,9 +352,12 @@ impl AppContextBuilder {
             db,
             broker_client,
             ro_db: None,
+            background_db: None,
             agent_id,
             queue_counter: None,
             redis_pool: None,
+            event_hooks: EventHooks::new(),
+            data_encryption: None,
         }
     }
 
@@ -221,6 +368,13 @@ impl AppContextBuilder {
         }
     }
 
+    pub fn background_db(self, background_db: Db) -> Self {
+        Self {
+            background_db: Some(background_db),
+            ..self
+        }
+    }
+
     pub fn queue_counter(self, qc: QueueCounterHandle) -> Self {
         Self {
             queue_counter: Some(qc),
@@ -235,18 +389,49 @@ impl AppContextBuilder {
         }
     }
 
+    pub fn event_hooks(self, event_hooks: EventHooks) -> Self {
+        Self {
+            event_hooks,
+            ..self
+        }
+    }
+
+    pub fn data_encryption(self, data_encryption: DataEncryption) -> Self {
+        Self {
+            data_encryption: Some(data_encryption),
+            ..self
+        }
+    }
+
     pub fn build(self, metrics: Arc<Metrics>) -> AppContext {
+        let config = Arc::new(ArcSwap::from_pointee(self.config));
+
+        let webhook_client = HttpWebhookClient::new(config.clone(), metrics.clone())
+            .expect("Failed to create Http Webhook Client");
+
+        let content_filter =
+            HttpContentFilter::new(config.clone()).expect("Failed to create content filter");
+
         AppContext {
-            config: Arc::new(self.config),
+            config,
             authz: self.authz,
             db: self.db,
             ro_db: self.ro_db,
+            background_db: self.background_db,
             broker_client: self.broker_client,
+            webhook_client: Arc::new(webhook_client),
+            content_filter: Arc::new(content_filter),
             agent_id: self.agent_id,
             queue_counter: self.queue_counter,
             redis_pool: self.redis_pool,
             metrics,
             s3_client: S3Client::new(),
+            jobs: Arc::new(JobRegistry::new()),
+            presence_cache: Arc::new(PresenceCache::new()),
+            room_cache: Arc::new(RoomCache::new()),
+            classroom_ids: Arc::new(ClassroomIds::new()),
+            event_hooks: Arc::new(self.event_hooks),
+            data_encryption: self.data_encryption.map(Arc::new),
         }
     }
 }
@@ -0,0 +1,30 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use sqlx::postgres::PgPool as Db;
+use tokio::{sync::watch, task::JoinHandle};
+
+use crate::metrics::Metrics;
+
+/// How often to sample pool statistics.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Periodically records connection pools state to metrics until shutdown.
+pub fn run(
+    metrics: Arc<Metrics>,
+    pools: Vec<(&'static str, Db)>,
+    mut shutdown_rx: watch::Receiver<()>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            for (name, db) in pools.iter() {
+                metrics.observe_db_pool(name, db.size(), db.num_idle());
+            }
+
+            tokio::select! {
+                _ = tokio::time::sleep(SAMPLE_INTERVAL) => {}
+                _ = shutdown_rx.changed() => return,
+            }
+        }
+    })
+}
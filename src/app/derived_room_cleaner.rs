@@ -0,0 +1,307 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use sqlx::postgres::PgConnection;
+use sqlx::Connection;
+use tokio::{sync::watch, task::JoinHandle};
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::app::context::{AppContext, GlobalContext};
+use crate::app::operations::RoomOperationLock;
+use crate::app::sentry;
+use crate::db;
+use crate::metrics::QueryKey;
+
+/// Advisory lock key to make sure that only one replica deletes rooms at a time.
+const DERIVED_ROOM_CLEANUP_LOCK_KEY: i64 = 0x6576_656e_745f_6472; // "event_dr"
+
+/// Periodically deletes derived rooms superseded by newer ones according to
+/// the `derived_room_cleanup` config.
+pub fn run(
+    context: Arc<AppContext>,
+    mut shutdown_rx: watch::Receiver<()>,
+) -> Option<JoinHandle<()>> {
+    let check_interval = context.config().derived_room_cleanup.check_interval;
+
+    context.config().derived_room_cleanup.max_age?;
+
+    let handle = tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(check_interval) => {}
+                _ = shutdown_rx.changed() => return,
+            }
+
+            if let Err(err) = run_exclusively(&context).await {
+                error!("Failed to delete stale derived rooms: {:?}", err);
+
+                sentry::send(&err, &[]);
+            }
+        }
+    });
+
+    Some(handle)
+}
+
+async fn run_exclusively(context: &AppContext) -> Result<()> {
+    let mut lock_conn = context
+        .background_db()
+        .acquire()
+        .await
+        .context("Failed to acquire db connection")?;
+
+    // The lock lives as long as the transaction which gets rolled back when dropped
+    // so it never stays on the pooled connection. Deletion goes on another connection
+    // not to run in a single long transaction.
+    let mut lock_txn = lock_conn
+        .begin()
+        .await
+        .context("Failed to begin transaction")?;
+
+    let locked = db::advisory_lock::TryXactLockQuery::new(DERIVED_ROOM_CLEANUP_LOCK_KEY)
+        .execute(&mut lock_txn)
+        .await
+        .context("Failed to take derived room cleanup lock")?;
+
+    if !locked {
+        return Ok(());
+    }
+
+    let mut conn = context
+        .background_db()
+        .acquire()
+        .await
+        .context("Failed to acquire db connection")?;
+
+    let result = delete_stale_rooms(context, &mut conn).await;
+
+    lock_txn
+        .rollback()
+        .await
+        .context("Failed to release derived room cleanup lock")?;
+
+    result.map(|_| ())
+}
+
+/// Deletes stale derived rooms with the rooms derived from them and returns their ids.
+///
+/// Events are deleted in batches before the room itself not to lock the table for long
+/// with a single cascading delete.
+///
+/// Each room is deleted under its operation lock not to race with an adjustment or
+/// an edition commit. The run stops at a room with an operation in progress since its
+/// sources follow it and deleting them would detach it. The next run picks it up.
+pub async fn delete_stale_rooms<C: GlobalContext>(
+    context: &C,
+    conn: &mut PgConnection,
+) -> Result<Vec<Uuid>> {
+    let config = context.config();
+    let config = &config.derived_room_cleanup;
+    let metrics = context.metrics();
+
+    let max_age = match config.max_age {
+        Some(max_age) => max_age,
+        None => return Ok(vec![]),
+    };
+
+    let query = db::room::StaleDerivedListQuery::new(max_age, config.max_rooms);
+
+    let room_ids = metrics
+        .measure_query(QueryKey::RoomStaleDerivedListQuery, query.execute(conn))
+        .await
+        .context("Failed to list stale derived rooms")?;
+
+    let mut deleted_room_ids = Vec::with_capacity(room_ids.len());
+
+    for room_id in room_ids {
+        let lock = RoomOperationLock::try_acquire(context.background_db(), room_id)
+            .await
+            .with_context(|| format!("Failed to lock room = '{}'", room_id))?;
+
+        let lock = match lock {
+            Some(lock) => lock,
+            None => {
+                info!(%room_id, "Stale derived room has an operation in progress, stop deletion");
+                break;
+            }
+        };
+
+        let mut deleted_events = 0;
+
+        loop {
+            let query = db::event::RoomBatchDeleteQuery::new(room_id, config.batch_size);
+
+            let deleted = metrics
+                .measure_query(QueryKey::EventRoomBatchDeleteQuery, query.execute(conn))
+                .await
+                .with_context(|| format!("Failed to delete events of room = '{}'", room_id))?;
+
+            deleted_events += deleted;
+            metrics.derived_room_cleanup_events.inc_by(deleted);
+
+            if (deleted as usize) < config.batch_size {
+                break;
+            }
+
+            tokio::time::sleep(config.batch_interval).await;
+        }
+
+        let query = db::room::DeleteQuery::new(room_id);
+
+        metrics
+            .measure_query(QueryKey::RoomDeleteQuery, query.execute(conn))
+            .await
+            .with_context(|| format!("Failed to delete room = '{}'", room_id))?;
+
+        lock.release().await?;
+
+        metrics.derived_room_cleanup_rooms.inc();
+        context.room_cache().invalidate(room_id);
+        deleted_room_ids.push(room_id);
+
+        info!(%room_id, deleted_events, "Deleted stale derived room");
+    }
+
+    Ok(deleted_room_ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Bound;
+    use std::time::Duration as StdDuration;
+
+    use chrono::{Duration, Utc};
+    use serde_json::json;
+    use serial_test::serial;
+
+    use super::*;
+    use crate::db::room::{ClassType, Object as Room};
+    use crate::test_helpers::prelude::*;
+
+    async fn insert_derived_room(
+        conn: &mut PgConnection,
+        source: &Room,
+        age: Duration,
+        preserve_history: bool,
+    ) -> Room {
+        let now = Utc::now();
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = factory::Room::new(Uuid::new_v4(), ClassType::Webinar)
+            .audience(USR_AUDIENCE)
+            .time((Bound::Included(now - age), Bound::Unbounded))
+            .source_room_id(source.id())
+            .preserve_history(preserve_history)
+            .insert(conn)
+            .await;
+
+        sqlx::query("UPDATE room SET created_at = $2 WHERE id = $1")
+            .bind(room.id())
+            .bind(Utc::now() - age)
+            .execute(&mut *conn)
+            .await
+            .expect("Failed to set room creation time");
+
+        factory::Event::new()
+            .room_id(room.id())
+            .kind("message")
+            .set("messages")
+            .occurred_at(1_000_000_000)
+            .data(&json!({"message": "hello"}))
+            .created_by(agent.agent_id())
+            .insert(conn)
+            .await;
+
+        room
+    }
+
+    async fn exists(conn: &mut PgConnection, room: &Room) -> bool {
+        db::room::FindQuery::by_id(room.id())
+            .execute(conn)
+            .await
+            .expect("Failed to find room")
+            .is_some()
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn delete_stale_rooms() {
+        let db = TestDb::new().await;
+        let mut conn = db.get_conn().await;
+        let source = shared_helpers::insert_room(&mut conn).await;
+
+        // Ages are far beyond other tests' rooms so they aren't affected.
+        let stale = insert_derived_room(&mut conn, &source, Duration::days(300), false).await;
+        let stale_child = insert_derived_room(&mut conn, &stale, Duration::days(290), false).await;
+
+        // A stale room with a recent derivative is kept along with it.
+        let used = insert_derived_room(&mut conn, &source, Duration::days(280), false).await;
+        let used_child = insert_derived_room(&mut conn, &used, Duration::hours(1), false).await;
+
+        let preserved = insert_derived_room(&mut conn, &source, Duration::days(270), true).await;
+        let latest = insert_derived_room(&mut conn, &source, Duration::days(260), false).await;
+
+        let context = TestContext::new(db, TestAuthz::new());
+
+        context.update_config(|config| {
+            config.derived_room_cleanup.max_age = Some(StdDuration::from_secs(200 * 24 * 3600));
+            config.derived_room_cleanup.batch_size = 1;
+            config.derived_room_cleanup.batch_interval = StdDuration::ZERO;
+        });
+
+        let deleted = super::delete_stale_rooms(&context, &mut conn)
+            .await
+            .expect("Failed to delete stale rooms");
+
+        // Derived rooms go before their sources.
+        assert_eq!(deleted, vec![stale_child.id(), stale.id()]);
+
+        assert!(!exists(&mut conn, &stale).await);
+        assert!(!exists(&mut conn, &stale_child).await);
+
+        for room in [&source, &used, &used_child, &preserved, &latest] {
+            assert!(exists(&mut conn, room).await);
+        }
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn skip_stale_room_under_operation() {
+        let db = TestDb::new().await;
+        let mut conn = db.get_conn().await;
+        let source = shared_helpers::insert_room(&mut conn).await;
+
+        let stale = insert_derived_room(&mut conn, &source, Duration::days(300), false).await;
+        let stale_child = insert_derived_room(&mut conn, &stale, Duration::days(290), false).await;
+        insert_derived_room(&mut conn, &source, Duration::days(260), false).await;
+
+        let lock = RoomOperationLock::try_acquire(db.connection_pool(), stale_child.id())
+            .await
+            .expect("Failed to lock room")
+            .expect("Room is already locked");
+
+        let context = TestContext::new(db, TestAuthz::new());
+
+        context.update_config(|config| {
+            config.derived_room_cleanup.max_age = Some(StdDuration::from_secs(200 * 24 * 3600));
+        });
+
+        let deleted = super::delete_stale_rooms(&context, &mut conn)
+            .await
+            .expect("Failed to delete stale rooms");
+
+        // The source of the locked room isn't deleted either not to detach it.
+        assert!(deleted.is_empty());
+        assert!(exists(&mut conn, &stale).await);
+        assert!(exists(&mut conn, &stale_child).await);
+
+        lock.release().await.expect("Failed to release lock");
+
+        let deleted = super::delete_stale_rooms(&context, &mut conn)
+            .await
+            .expect("Failed to delete stale rooms");
+
+        assert_eq!(deleted, vec![stale_child.id(), stale.id()]);
+    }
+}
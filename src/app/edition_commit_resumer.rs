@@ -0,0 +1,102 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use futures::StreamExt;
+use svc_agent::mqtt::Agent;
+use tokio::{sync::watch, task::JoinHandle};
+use tracing::{error, info, warn};
+
+use crate::app::context::{AppContext, GlobalContext};
+use crate::app::endpoint::edition::start_commit;
+use crate::app::endpoint::helpers::operation_span;
+use crate::app::message_handler::publish_message;
+use crate::app::operations::RoomOperationLock;
+use crate::db;
+use crate::metrics::QueryKey;
+
+/// How often to look for edition commit jobs abandoned by dead replicas.
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A running job which hasn't moved its checkpoint for this long is considered abandoned.
+/// Must be longer than cloning a single chunk takes.
+const STALE_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Periodically resumes edition commits interrupted by a replica restart or crash.
+pub fn run(
+    context: Arc<AppContext>,
+    agent: Agent,
+    mut shutdown_rx: watch::Receiver<()>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(CHECK_INTERVAL) => {}
+                _ = shutdown_rx.changed() => return,
+            }
+
+            if let Err(err) = resume(&context, &agent).await {
+                error!("Failed to resume edition commit jobs: {:?}", err);
+            }
+        }
+    })
+}
+
+async fn resume(context: &AppContext, agent: &Agent) -> Result<()> {
+    let mut conn = context
+        .db()
+        .acquire()
+        .await
+        .context("Failed to acquire db connection")?;
+
+    let jobs = context
+        .metrics()
+        .measure_query(
+            QueryKey::EditionCommitJobClaimQuery,
+            db::edition_commit_job::ClaimStaleQuery::new(STALE_TIMEOUT).execute(&mut conn),
+        )
+        .await
+        .context("Failed to claim stale edition commit jobs")?;
+
+    for job in jobs {
+        let query = db::edition::FindWithRoomQuery::new(job.edition_id());
+
+        let (edition, room) = match query.execute(&mut conn).await? {
+            Some(edition_with_room) => edition_with_room,
+            None => {
+                warn!(job_id = %job.id(), "Edition of the commit job not found");
+                continue;
+            }
+        };
+
+        // The job gets claimed again after the timeout if another operation is running.
+        let lock = match RoomOperationLock::try_acquire(context.background_db(), room.id()).await? {
+            Some(lock) => lock,
+            None => {
+                warn!(job_id = %job.id(), "Another operation on the room is in progress");
+                continue;
+            }
+        };
+
+        info!(
+            job_id = %job.id(),
+            edition_id = %edition.id(),
+            checkpoint = ?job.checkpoint(),
+            "Resuming edition commit job"
+        );
+
+        let span = operation_span("edition_commit", &room, None);
+        let mut notifications = start_commit(context, job, edition, room, lock, span);
+        let mut agent = agent.clone();
+
+        tokio::spawn(async move {
+            while let Some(message) = notifications.next().await {
+                if let Err(err) = publish_message(&mut agent, message) {
+                    error!("Failed to publish edition commit notification: {:?}", err);
+                }
+            }
+        });
+    }
+
+    Ok(())
+}
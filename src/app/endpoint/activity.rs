@@ -0,0 +1,292 @@
+use std::collections::BTreeMap;
+
+use anyhow::Context as AnyhowContext;
+use async_trait::async_trait;
+use axum::extract::{self, Path, RawQuery};
+use chrono::{DateTime, TimeZone, Utc};
+use serde_derive::{Deserialize, Serialize};
+use svc_agent::mqtt::ResponseStatus;
+use svc_utils::extractors::AgentIdExtractor;
+use tracing::{field::display, instrument, Span};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::app::context::Context;
+use crate::app::endpoint::prelude::*;
+use crate::db;
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ReadPayload {
+    /// Unix time in milliseconds of the first hour to include.
+    from: Option<i64>,
+    /// Unix time in milliseconds to include hours before.
+    to: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReadRequest {
+    classroom_id: Uuid,
+    #[serde(flatten)]
+    payload: ReadPayload,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RoomActivity {
+    room_id: Uuid,
+    /// Unix time in milliseconds of the latest event of each kind.
+    last_event_at: BTreeMap<String, i64>,
+    hours: Vec<HourActivity>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct HourActivity {
+    #[serde(with = "chrono::serde::ts_milliseconds")]
+    #[schema(value_type = i64)]
+    hour: DateTime<Utc>,
+    kind: String,
+    count: i64,
+}
+
+#[utoipa::path(
+    get,
+    path = "/classrooms/{classroom_id}/activity",
+    tag = "activity",
+    params(
+        ("classroom_id" = Uuid, Path, description = "Classroom identifier"),
+        ReadPayload
+    ),
+    responses(
+        (status = 200, description = "Activity of the classroom's rooms", body = [RoomActivity]),
+        (status = 403, description = "Not authorized", body = ErrorPayload),
+        (status = 404, description = "No rooms in the classroom", body = ErrorPayload),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn read(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(classroom_id): Path<Uuid>,
+    RawQuery(query): RawQuery,
+) -> RequestResult {
+    let payload = serde_qs::from_str(&query.unwrap_or_default())
+        .context("Failed to parse qs")
+        .error(AppErrorKind::InvalidQueryString)?;
+    let request = ReadRequest {
+        classroom_id,
+        payload,
+    };
+    ReadHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct ReadHandler;
+
+#[async_trait]
+impl RequestHandler for ReadHandler {
+    type Payload = ReadRequest;
+
+    #[instrument(skip_all, fields(classroom_id))]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload {
+            classroom_id,
+            payload,
+        }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        Span::current().record("classroom_id", &display(classroom_id));
+
+        // Any room of the classroom gives its audience for authorization.
+        let room = helpers::find_room_by_classroom_id(context, classroom_id).await?;
+
+        let object = AuthzObject::new(&["classrooms", &classroom_id.to_string()]).into();
+
+        let authz_time = context
+            .authz()
+            .authorize_room(
+                &room,
+                reqp.as_account_id().to_owned(),
+                object,
+                "read".into(),
+            )
+            .await?;
+
+        let mut query = db::room_activity::ClassroomListQuery::new(classroom_id);
+
+        if let Some(from) = payload.from {
+            query = query.from(parse_millis(from)?);
+        }
+
+        if let Some(to) = payload.to {
+            query = query.to(parse_millis(to)?);
+        }
+
+        let mut conn = context.get_ro_conn().await?;
+
+        let rows = context
+            .metrics()
+            .measure_query(QueryKey::RoomActivityListQuery, query.execute(&mut conn))
+            .await
+            .context("Failed to list room activity")
+            .error(AppErrorKind::DbQueryFailed)?;
+
+        Ok(AppResponse::new(
+            ResponseStatus::OK,
+            group_by_room(rows),
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+fn parse_millis(millis: i64) -> Result<DateTime<Utc>, AppError> {
+    Utc.timestamp_millis_opt(millis)
+        .single()
+        .context("Invalid timestamp")
+        .error(AppErrorKind::InvalidQueryString)
+}
+
+/// Groups rows ordered by room into activity of each room.
+fn group_by_room(rows: Vec<db::room_activity::Object>) -> Vec<RoomActivity> {
+    let mut activity: Vec<RoomActivity> = vec![];
+
+    for row in rows {
+        if activity.last().map(|room| room.room_id) != Some(row.room_id()) {
+            activity.push(RoomActivity {
+                room_id: row.room_id(),
+                last_event_at: BTreeMap::new(),
+                hours: vec![],
+            });
+        }
+
+        let room = activity.last_mut().expect("Room activity just pushed");
+
+        let last_event_at = room
+            .last_event_at
+            .entry(row.kind().to_owned())
+            .or_insert(i64::MIN);
+
+        *last_event_at = std::cmp::max(*last_event_at, row.last_event_at().timestamp_millis());
+
+        room.hours.push(HourActivity {
+            hour: row.hour(),
+            kind: row.kind().to_owned(),
+            count: row.count(),
+        });
+    }
+
+    activity
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value as JsonValue};
+
+    use crate::test_helpers::prelude::*;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn read_activity() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+
+            for (kind, occurred_at) in [("message", 1000), ("message", 2000), ("note", 3000)] {
+                factory::Event::new()
+                    .room_id(room.id())
+                    .kind(kind)
+                    .set(kind)
+                    .data(&json!({}))
+                    .occurred_at(occurred_at)
+                    .created_by(agent.agent_id())
+                    .insert(&mut conn)
+                    .await;
+            }
+
+            room
+        };
+
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        authz.allow(
+            agent.account_id(),
+            vec!["classrooms", &classroom_id],
+            "read",
+        );
+
+        let mut context = TestContext::new(db, authz);
+
+        let payload = ReadRequest {
+            classroom_id: room.classroom_id(),
+            payload: ReadPayload {
+                from: None,
+                to: None,
+            },
+        };
+
+        let messages = handle_request::<ReadHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Activity reading failed");
+
+        let (activity, respp, _) = find_response::<JsonValue>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+
+        let rooms = activity.as_array().expect("Expected a list");
+        assert_eq!(rooms.len(), 1);
+        assert_eq!(rooms[0]["room_id"], json!(room.id()));
+        assert!(rooms[0]["last_event_at"]["message"].is_i64());
+        assert!(rooms[0]["last_event_at"]["note"].is_i64());
+
+        let total: i64 = rooms[0]["hours"]
+            .as_array()
+            .expect("Expected hours")
+            .iter()
+            .filter(|hour| hour["kind"] == "message")
+            .map(|hour| hour["count"].as_i64().expect("Expected count"))
+            .sum();
+
+        assert_eq!(total, 2);
+    }
+
+    #[tokio::test]
+    async fn read_activity_not_authorized() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut context = TestContext::new(db, TestAuthz::new());
+
+        let payload = ReadRequest {
+            classroom_id: room.classroom_id(),
+            payload: ReadPayload {
+                from: None,
+                to: None,
+            },
+        };
+
+        let err = handle_request::<ReadHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success reading activity with no authorization");
+
+        assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+    }
+}
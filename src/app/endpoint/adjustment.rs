@@ -0,0 +1,210 @@
+use std::sync::Arc;
+
+use anyhow::Context as AnyhowContext;
+use async_trait::async_trait;
+use axum::extract::{self, Path};
+use serde_derive::Deserialize;
+use svc_agent::mqtt::ResponseStatus;
+use svc_utils::extractors::AgentIdExtractor;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::app::context::{AppContext, Context};
+use crate::app::endpoint::prelude::*;
+use crate::db;
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct ReadRequest {
+    room_id: Uuid,
+}
+
+pub async fn read(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<Uuid>,
+) -> RequestResult {
+    let request = ReadRequest { room_id };
+    ReadHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct ReadHandler;
+
+#[async_trait]
+impl RequestHandler for ReadHandler {
+    type Payload = ReadRequest;
+
+    #[instrument(skip_all, fields(room_id = %payload.room_id, scope, classroom_id))]
+    async fn handle<C: Context>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let room =
+            helpers::find_room(context, payload.room_id, helpers::RoomTimeRequirement::Any).await?;
+
+        let object = AuthzObject::room(&room).into();
+
+        let authz_time = context
+            .authz()
+            .authorize_room(
+                &room,
+                reqp.as_account_id().to_owned(),
+                object,
+                "read".into(),
+            )
+            .await?;
+
+        let adjustment = {
+            let query = db::adjustment::FindQuery::new(room.id());
+            let mut conn = context.get_ro_conn().await?;
+
+            context
+                .metrics()
+                .measure_query(QueryKey::AdjustmentFindQuery, query.execute(&mut conn))
+                .await
+                .context("Failed to find adjustment")
+                .error(AppErrorKind::DbQueryFailed)?
+                .ok_or_else(|| anyhow!("Adjustment not found"))
+                .error(AppErrorKind::AdjustmentNotFound)?
+        };
+
+        Ok(AppResponse::new(
+            ResponseStatus::OK,
+            adjustment,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use serde_json::Value as JsonValue;
+
+    use crate::db::adjustment::{FinishQuery, InsertQuery, Segments};
+    use crate::test_helpers::prelude::*;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn read_adjustment() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let (room, modified_room) = {
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+            let modified_room = shared_helpers::insert_room(&mut conn).await;
+            let segments = Segments::from(vec![]);
+
+            InsertQuery::new(
+                room.id(),
+                Utc::now() - Duration::hours(1),
+                segments.clone(),
+                0,
+            )
+            .execute(&mut conn)
+            .await
+            .expect("Failed to insert adjustment");
+
+            FinishQuery::succeeded(
+                room.id(),
+                room.id(),
+                modified_room.id(),
+                segments.clone(),
+                segments,
+            )
+            .execute(&mut conn)
+            .await
+            .expect("Failed to finish adjustment")
+            .expect("Adjustment not found");
+
+            (room, modified_room)
+        };
+
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        authz.allow(
+            agent.account_id(),
+            vec!["classrooms", &classroom_id],
+            "read",
+        );
+
+        let mut context = TestContext::new(db, authz);
+        let payload = ReadRequest { room_id: room.id() };
+
+        let messages = handle_request::<ReadHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Adjustment reading failed");
+
+        let (adjustment, respp, _) = find_response::<JsonValue>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert_eq!(adjustment["status"], "succeeded");
+        assert_eq!(
+            adjustment["modified_room_id"],
+            modified_room.id().to_string()
+        );
+        assert!(adjustment.get("error").is_none());
+    }
+
+    #[tokio::test]
+    async fn read_missing_adjustment() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        authz.allow(
+            agent.account_id(),
+            vec!["classrooms", &classroom_id],
+            "read",
+        );
+
+        let mut context = TestContext::new(db, authz);
+        let payload = ReadRequest { room_id: room.id() };
+
+        let err = handle_request::<ReadHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success reading missing adjustment");
+
+        assert_eq!(err.status(), ResponseStatus::NOT_FOUND);
+        assert_eq!(err.kind(), "adjustment_not_found");
+    }
+
+    #[tokio::test]
+    async fn read_adjustment_not_authorized() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut context = TestContext::new(db, TestAuthz::new());
+        let payload = ReadRequest { room_id: room.id() };
+
+        let err = handle_request::<ReadHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success reading adjustment without authorization");
+
+        assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+    }
+}
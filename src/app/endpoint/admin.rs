@@ -0,0 +1,1202 @@
+//! Operational endpoints available over HTTP under `/admin/v1`.
+//!
+//! All of them require `admin` action on `system` object in the service audience.
+
+use std::ops::Bound;
+use std::sync::Arc;
+
+use anyhow::Context as AnyhowContext;
+use async_trait::async_trait;
+use axum::{
+    extract::{self, Path, Query},
+    Json,
+};
+use chrono::Utc;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::json;
+use svc_agent::{mqtt::ResponseStatus, AccountId};
+use svc_authn::Authenticable;
+use svc_utils::extractors::AgentIdExtractor;
+use tracing::{error, instrument};
+use uuid::Uuid;
+
+use crate::app::config_reloader;
+use crate::app::context::Context;
+use crate::app::endpoint::prelude::*;
+use crate::app::endpoint::system::start_vacuum;
+use crate::app::nats_consumer::{self, HandleMessageError, NatsEvent};
+use crate::app::operations;
+use crate::app::sentry;
+use crate::app::webhook_client::Webhook;
+use crate::db;
+
+///////////////////////////////////////////////////////////////////////////////
+
+const MAX_LIMIT: usize = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct RoomListPayload {
+    open: Option<bool>,
+    classroom_id: Option<Uuid>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RoomListRequest {
+    audience: String,
+    #[serde(flatten)]
+    payload: RoomListPayload,
+}
+
+pub async fn list_rooms(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(audience): Path<String>,
+    Query(payload): Query<RoomListPayload>,
+) -> RequestResult {
+    let request = RoomListRequest { audience, payload };
+    RoomListHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct RoomListHandler;
+
+#[async_trait]
+impl RequestHandler for RoomListHandler {
+    type Payload = RoomListRequest;
+
+    #[instrument(skip_all, fields(audience))]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload { audience, payload }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let authz_time = authorize(context, reqp).await?;
+
+        let rooms = {
+            let mut query = db::room::ListQuery::new(
+                audience,
+                payload.offset.unwrap_or(0),
+                std::cmp::min(payload.limit.unwrap_or(MAX_LIMIT), MAX_LIMIT),
+            );
+
+            if let Some(open) = payload.open {
+                query = query.open(open);
+            }
+
+            if let Some(classroom_id) = payload.classroom_id {
+                query = query.classroom_id(classroom_id);
+            }
+
+            let mut conn = context.get_ro_conn().await?;
+
+            context
+                .metrics()
+                .measure_query(QueryKey::RoomListQuery, query.execute(&mut conn))
+                .await
+                .context("Failed to list rooms")
+                .error(AppErrorKind::DbQueryFailed)?
+        };
+
+        Ok(AppResponse::new(
+            ResponseStatus::OK,
+            rooms,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct RoomCloseRequest {
+    id: Uuid,
+}
+
+pub async fn close_room(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(id): Path<Uuid>,
+) -> RequestResult {
+    let request = RoomCloseRequest { id };
+    RoomCloseHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct RoomCloseHandler;
+
+#[async_trait]
+impl RequestHandler for RoomCloseHandler {
+    type Payload = RoomCloseRequest;
+
+    #[instrument(skip_all, fields(room_id))]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload { id }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let authz_time = authorize(context, reqp).await?;
+        let room = helpers::find_room(context, id, helpers::RoomTimeRequirement::Open).await?;
+
+        // Cut the room time at the current moment.
+        let time = {
+            let room_time = room
+                .time()
+                .map_err(|e| anyhow!(e))
+                .error(AppErrorKind::InvalidRoomTime)?;
+
+            let new_time = (
+                Bound::Included(*room_time.start()),
+                Bound::Excluded(Utc::now()),
+            );
+
+            match room_time.update(new_time) {
+                Some(time) => time,
+                None => {
+                    return Err(anyhow!("Invalid room time")).error(AppErrorKind::InvalidRoomTime)
+                }
+            }
+        };
+
+        let room = {
+            let query = db::room::UpdateQuery::new(room.id()).time(Some(time.into()));
+            let mut conn = context.get_conn().await?;
+
+            context
+                .metrics()
+                .measure_query(QueryKey::RoomUpdateQuery, query.execute(&mut conn))
+                .await
+                .context("Failed to update room")
+                .error(AppErrorKind::DbQueryFailed)?
+        };
+
+        context.room_cache().invalidate(room.id());
+
+        let mut response = AppResponse::new(
+            ResponseStatus::OK,
+            room.clone(),
+            context.start_timestamp(),
+            Some(authz_time),
+        );
+
+        response.add_notification(
+            "room.update",
+            &context.config().audience_topic(room.audience()),
+            room.clone(),
+            context.start_timestamp(),
+        )?;
+
+        context
+            .webhook_client()
+            .send(room.audience(), Webhook::new("room.close", &room));
+
+        response.add_notification(
+            "room.close",
+            &context.config().room_topic(room.audience(), room.id()),
+            room,
+            context.start_timestamp(),
+        )?;
+
+        Ok(response)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct DeadLetterListRequest {
+    limit: Option<usize>,
+}
+
+pub async fn list_dead_letters(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Query(request): Query<DeadLetterListRequest>,
+) -> RequestResult {
+    DeadLetterListHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct DeadLetterListHandler;
+
+#[async_trait]
+impl RequestHandler for DeadLetterListHandler {
+    type Payload = DeadLetterListRequest;
+
+    #[instrument(skip_all)]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload { limit }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let authz_time = authorize(context, reqp).await?;
+
+        let dead_letters = {
+            let query = db::nats_dead_letter::ListQuery::new()
+                .limit(std::cmp::min(limit.unwrap_or(MAX_LIMIT), MAX_LIMIT));
+
+            let mut conn = context.get_ro_conn().await?;
+
+            context
+                .metrics()
+                .measure_query(QueryKey::NatsDeadLetterListQuery, query.execute(&mut conn))
+                .await
+                .context("Failed to list nats dead letters")
+                .error(AppErrorKind::DbQueryFailed)?
+        };
+
+        Ok(AppResponse::new(
+            ResponseStatus::OK,
+            dead_letters,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Default, Deserialize)]
+pub struct DeadLetterRequeueRequest {
+    /// Dead letters to requeue, the oldest ones if omitted.
+    ids: Option<Vec<Uuid>>,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct DeadLetterRequeueResult {
+    requeued: Vec<Uuid>,
+    failed: Vec<Uuid>,
+}
+
+pub async fn requeue_dead_letters(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Json(request): Json<DeadLetterRequeueRequest>,
+) -> RequestResult {
+    DeadLetterRequeueHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct DeadLetterRequeueHandler;
+
+#[async_trait]
+impl RequestHandler for DeadLetterRequeueHandler {
+    type Payload = DeadLetterRequeueRequest;
+
+    #[instrument(skip_all)]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload { ids }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let authz_time = authorize(context, reqp).await?;
+
+        let dead_letters = {
+            let mut query = db::nats_dead_letter::ListQuery::new().limit(MAX_LIMIT);
+
+            if let Some(ids) = ids {
+                query = query.ids(ids);
+            }
+
+            let mut conn = context.get_ro_conn().await?;
+
+            context
+                .metrics()
+                .measure_query(QueryKey::NatsDeadLetterListQuery, query.execute(&mut conn))
+                .await
+                .context("Failed to list nats dead letters")
+                .error(AppErrorKind::DbQueryFailed)?
+        };
+
+        let mut result = DeadLetterRequeueResult::default();
+
+        // Handle the events once again the same way the nats consumer does.
+        for dead_letter in dead_letters {
+            let event = NatsEvent::from(&dead_letter);
+
+            match nats_consumer::apply_event(&*context, &event).await {
+                Ok(()) => {
+                    let query = db::nats_dead_letter::DeleteQuery::new(dead_letter.id());
+                    let mut conn = context.get_conn().await?;
+
+                    context
+                        .metrics()
+                        .measure_query(
+                            QueryKey::NatsDeadLetterDeleteQuery,
+                            query.execute(&mut conn),
+                        )
+                        .await
+                        .context("Failed to delete nats dead letter")
+                        .error(AppErrorKind::DbQueryFailed)?;
+
+                    result.requeued.push(dead_letter.id());
+                }
+                Err(HandleMessageError::DbConnAcquisitionFailed(err)) => return Err(err),
+                Err(HandleMessageError::Other(err)) => {
+                    error!(
+                        dead_letter_id = %dead_letter.id(),
+                        "Failed to requeue nats dead letter: {:?}", err
+                    );
+
+                    let query = db::nats_dead_letter::InsertQuery::new(
+                        dead_letter.subject().to_owned(),
+                        dead_letter.classroom_id(),
+                        dead_letter.entity_type().to_owned(),
+                        dead_letter.entity_event_id(),
+                        dead_letter.label().to_owned(),
+                        dead_letter.created_by().to_owned(),
+                        dead_letter.event_created_at(),
+                    )
+                    .error(format!("{:#}", err));
+
+                    let mut conn = context.get_conn().await?;
+
+                    context
+                        .metrics()
+                        .measure_query(
+                            QueryKey::NatsDeadLetterInsertQuery,
+                            query.execute(&mut conn),
+                        )
+                        .await
+                        .context("Failed to update nats dead letter")
+                        .error(AppErrorKind::DbQueryFailed)?;
+
+                    result.failed.push(dead_letter.id());
+                }
+            }
+        }
+
+        Ok(AppResponse::new(
+            ResponseStatus::OK,
+            result,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Default, Deserialize)]
+pub struct VacuumRequest {
+    /// Only count events to be deleted without deleting them.
+    #[serde(default)]
+    dry_run: bool,
+}
+
+pub async fn vacuum(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Json(request): Json<VacuumRequest>,
+) -> RequestResult {
+    VacuumHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct VacuumHandler;
+
+#[async_trait]
+impl RequestHandler for VacuumHandler {
+    type Payload = VacuumRequest;
+
+    #[instrument(skip_all)]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload { dry_run }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let authz_time = authorize(context, reqp).await?;
+        start_vacuum(context, dry_run, authz_time).await
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ReencodeDrawEventsRequest {
+    /// Resume a previous run after the last event id it reported.
+    after_id: Option<Uuid>,
+}
+
+pub async fn reencode_draw_events(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Json(request): Json<ReencodeDrawEventsRequest>,
+) -> RequestResult {
+    ReencodeDrawEventsHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct ReencodeDrawEventsHandler;
+
+#[async_trait]
+impl RequestHandler for ReencodeDrawEventsHandler {
+    type Payload = ReencodeDrawEventsRequest;
+
+    #[instrument(skip_all)]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload { after_id }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let authz_time = authorize(context, reqp).await?;
+
+        let db = context.background_db().to_owned();
+        let metrics = context.metrics();
+        let jobs = context.jobs();
+        let job_id = jobs.start("reencode_draw_events", None);
+
+        // Converted events drop out of the scan so the job is safe to restart from scratch,
+        // `after_id` only lets skipping what has been already scanned.
+        tokio::task::spawn(async move {
+            let result = operations::reencode_draw_events(&db, &metrics, after_id, |stats| {
+                jobs.report(job_id, stats)
+            })
+            .await;
+
+            jobs.finish(job_id, &result);
+
+            if let Err(err) = result {
+                error!("Draw events re-encoding failed: {:?}", err);
+
+                sentry::send(&err, &[]);
+            }
+        });
+
+        Ok(AppResponse::new(
+            ResponseStatus::ACCEPTED,
+            json!({ "job_id": job_id }),
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Default, Deserialize)]
+pub struct BackfillSetHeadsRequest {
+    /// Resume a previous run after the last room id it reported.
+    after_room_id: Option<Uuid>,
+}
+
+pub async fn backfill_set_heads(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Json(request): Json<BackfillSetHeadsRequest>,
+) -> RequestResult {
+    BackfillSetHeadsHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct BackfillSetHeadsHandler;
+
+#[async_trait]
+impl RequestHandler for BackfillSetHeadsHandler {
+    type Payload = BackfillSetHeadsRequest;
+
+    #[instrument(skip_all)]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload { after_room_id }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let authz_time = authorize(context, reqp).await?;
+
+        let db = context.background_db().to_owned();
+        let metrics = context.metrics();
+        let jobs = context.jobs();
+        let job_id = jobs.start("backfill_set_heads", None);
+
+        tokio::task::spawn(async move {
+            let result = operations::backfill_set_heads(&db, &metrics, after_room_id, |stats| {
+                jobs.report(job_id, stats)
+            })
+            .await;
+
+            jobs.finish(job_id, &result);
+
+            if let Err(err) = result {
+                error!("Set heads backfill failed: {:?}", err);
+
+                sentry::send(&err, &[]);
+            }
+        });
+
+        Ok(AppResponse::new(
+            ResponseStatus::ACCEPTED,
+            json!({ "job_id": job_id }),
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct AccountEventsPayload {
+    /// Events are collected across rooms of the audience.
+    audience: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AccountEventsRequest {
+    account_id: AccountId,
+    #[serde(flatten)]
+    payload: AccountEventsPayload,
+}
+
+pub async fn export_account_events(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(account_id): Path<AccountId>,
+    Json(payload): Json<AccountEventsPayload>,
+) -> RequestResult {
+    let request = AccountEventsRequest {
+        account_id,
+        payload,
+    };
+    AccountEventsExportHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct AccountEventsExportHandler;
+
+#[async_trait]
+impl RequestHandler for AccountEventsExportHandler {
+    type Payload = AccountEventsRequest;
+
+    #[instrument(skip_all, fields(account_id = %payload.account_id))]
+    async fn handle<C: Context>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let authz_time = authorize(context, reqp).await?;
+
+        let s3_client = context
+            .s3_client()
+            .ok_or_else(|| anyhow!("No S3Client"))
+            .error(AppErrorKind::NoS3Client)?;
+
+        let db = context.background_db().to_owned();
+        let metrics = context.metrics();
+        let jobs = context.jobs();
+        let job_id = jobs.start("export_account_events", None);
+
+        let AccountEventsRequest {
+            account_id,
+            payload: AccountEventsPayload { audience },
+        } = payload;
+
+        tokio::task::spawn(async move {
+            let result = operations::export_account_events(
+                &db,
+                &metrics,
+                s3_client,
+                &account_id,
+                &audience,
+                |stats| jobs.report(job_id, stats),
+            )
+            .await;
+
+            // The job keeps the uri of the export in its progress.
+            if let Ok(ref stats) = result {
+                jobs.report(job_id, stats);
+            }
+
+            jobs.finish(job_id, &result);
+
+            if let Err(err) = result {
+                error!(%account_id, "Account events export failed: {:?}", err);
+
+                sentry::send(&err, &[]);
+            }
+        });
+
+        Ok(AppResponse::new(
+            ResponseStatus::ACCEPTED,
+            json!({ "job_id": job_id }),
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+pub async fn erase_account_events(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(account_id): Path<AccountId>,
+    Json(payload): Json<AccountEventsPayload>,
+) -> RequestResult {
+    let request = AccountEventsRequest {
+        account_id,
+        payload,
+    };
+    AccountEventsEraseHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct AccountEventsEraseHandler;
+
+#[async_trait]
+impl RequestHandler for AccountEventsEraseHandler {
+    type Payload = AccountEventsRequest;
+
+    #[instrument(skip_all, fields(account_id = %payload.account_id))]
+    async fn handle<C: Context>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let authz_time = authorize(context, reqp).await?;
+
+        let db = context.background_db().to_owned();
+        let metrics = context.metrics();
+        let author_fields = context.config().erasure.author_fields.clone();
+        let jobs = context.jobs();
+        let job_id = jobs.start("erase_account_events", None);
+
+        let AccountEventsRequest {
+            account_id,
+            payload: AccountEventsPayload { audience },
+        } = payload;
+
+        tokio::task::spawn(async move {
+            let result = operations::erase_account_events(
+                &db,
+                &metrics,
+                &account_id,
+                &audience,
+                &author_fields,
+                |stats| jobs.report(job_id, stats),
+            )
+            .await;
+
+            jobs.finish(job_id, &result);
+
+            if let Err(err) = result {
+                error!(%account_id, "Account events erasure failed: {:?}", err);
+
+                sentry::send(&err, &[]);
+            }
+        });
+
+        Ok(AppResponse::new(
+            ResponseStatus::ACCEPTED,
+            json!({ "job_id": job_id }),
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct JobListRequest {}
+
+pub async fn list_jobs(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+) -> RequestResult {
+    JobListHandler::handle(
+        &mut ctx.start_message(),
+        JobListRequest {},
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct JobListHandler;
+
+#[async_trait]
+impl RequestHandler for JobListHandler {
+    type Payload = JobListRequest;
+
+    #[instrument(skip_all)]
+    async fn handle<C: Context>(
+        context: &mut C,
+        _payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let authz_time = authorize(context, reqp).await?;
+
+        // Jobs are tracked in memory so only ones of the replica serving the request are listed.
+        Ok(AppResponse::new(
+            ResponseStatus::OK,
+            context.jobs().list(),
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct ConfigReloadRequest {}
+
+pub async fn reload_config(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+) -> RequestResult {
+    ConfigReloadHandler::handle(
+        &mut ctx.start_message(),
+        ConfigReloadRequest {},
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct ConfigReloadHandler;
+
+#[async_trait]
+impl RequestHandler for ConfigReloadHandler {
+    type Payload = ConfigReloadRequest;
+
+    #[instrument(skip_all)]
+    async fn handle<C: Context>(
+        context: &mut C,
+        _payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let authz_time = authorize(context, reqp).await?;
+
+        // Like SIGHUP it reloads only the replica serving the request.
+        config_reloader::reload(context).error(AppErrorKind::ConfigReloadFailed)?;
+
+        Ok(AppResponse::new(
+            ResponseStatus::OK,
+            json!({}),
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+async fn authorize<C: Context>(
+    context: &mut C,
+    reqp: RequestParams<'_>,
+) -> Result<chrono::Duration, AppError> {
+    let authz_time = context
+        .authz()
+        .authorize(
+            context.agent_id().as_account_id().audience().into(),
+            reqp.as_account_id().to_owned(),
+            AuthzObject::new(&["system"]).into(),
+            "admin".into(),
+        )
+        .await?;
+
+    Ok(authz_time)
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Bound;
+
+    use serde_json::{json, Value as JsonValue};
+
+    use crate::db::room::ClassType;
+    use crate::test_helpers::prelude::*;
+
+    use super::*;
+
+    fn admin_authz(agent: &TestAgent) -> TestAuthz {
+        let mut authz = TestAuthz::new();
+        authz.set_audience(SVC_AUDIENCE);
+        authz.allow(agent.account_id(), vec!["system"], "admin");
+        authz
+    }
+
+    #[tokio::test]
+    async fn list_rooms() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("alpha", "ops", SVC_AUDIENCE);
+        let audience = format!("{}.example.org", Uuid::new_v4());
+
+        let (open_room, closed_room) = {
+            let mut conn = db.get_conn().await;
+            let now = Utc::now();
+
+            let open_room = factory::Room::new(Uuid::new_v4(), ClassType::Webinar)
+                .audience(&audience)
+                .time((Bound::Included(now), Bound::Unbounded))
+                .insert(&mut conn)
+                .await;
+
+            let closed_room = factory::Room::new(Uuid::new_v4(), ClassType::Webinar)
+                .audience(&audience)
+                .time((
+                    Bound::Included(now - chrono::Duration::hours(2)),
+                    Bound::Excluded(now - chrono::Duration::hours(1)),
+                ))
+                .insert(&mut conn)
+                .await;
+
+            (open_room, closed_room)
+        };
+
+        let mut context = TestContext::new(db, admin_authz(&agent));
+
+        let payload: RoomListRequest =
+            serde_json::from_value(json!({ "audience": audience })).unwrap();
+
+        let messages = handle_request::<RoomListHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Rooms listing failed");
+
+        let (rooms, respp, _) = find_response::<Vec<JsonValue>>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert_eq!(rooms.len(), 2);
+
+        let payload: RoomListRequest =
+            serde_json::from_value(json!({ "audience": audience, "open": false })).unwrap();
+
+        let messages = handle_request::<RoomListHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Rooms listing failed");
+
+        let (rooms, _, _) = find_response::<Vec<JsonValue>>(messages.as_slice());
+        assert_eq!(rooms.len(), 1);
+        assert_eq!(rooms[0]["id"], closed_room.id().to_string());
+
+        let payload: RoomListRequest = serde_json::from_value(json!({
+            "audience": audience,
+            "classroom_id": open_room.classroom_id(),
+        }))
+        .unwrap();
+
+        let messages = handle_request::<RoomListHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Rooms listing failed");
+
+        let (rooms, _, _) = find_response::<Vec<JsonValue>>(messages.as_slice());
+        assert_eq!(rooms.len(), 1);
+        assert_eq!(rooms[0]["id"], open_room.id().to_string());
+    }
+
+    #[tokio::test]
+    async fn list_rooms_not_authorized() {
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let mut context = TestContext::new(TestDb::new().await, TestAuthz::new());
+
+        let payload: RoomListRequest =
+            serde_json::from_value(json!({ "audience": USR_AUDIENCE })).unwrap();
+
+        let err = handle_request::<RoomListHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success on rooms listing");
+
+        assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn close_room() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("alpha", "ops", SVC_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut context = TestContext::new(db, admin_authz(&agent));
+        let payload = RoomCloseRequest { id: room.id() };
+
+        let messages = handle_request::<RoomCloseHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Room closing failed");
+
+        let (_, respp, _) = find_response::<JsonValue>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+
+        let (closed_room, evp, topic) =
+            find_event_by_predicate::<JsonValue, _>(messages.as_slice(), |evp| {
+                evp.label() == "room.close"
+            })
+            .expect("Failed to find room.close event");
+
+        assert_eq!(evp.label(), "room.close");
+        assert!(topic.ends_with(&format!("/rooms/{}/events", room.id())));
+        assert!(closed_room["time"][1].is_i64());
+    }
+
+    #[tokio::test]
+    async fn requeue_dead_letters() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("alpha", "ops", SVC_AUDIENCE);
+        let sender = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let classroom_id = Uuid::new_v4();
+
+        // The room didn't exist when the message came.
+        let dead_letter = {
+            let mut conn = db.get_conn().await;
+
+            db::nats_dead_letter::InsertQuery::new(
+                format!("classrooms.{}.video_group", classroom_id),
+                classroom_id,
+                "video_group".to_owned(),
+                rand::random::<i64>().abs(),
+                "created".to_owned(),
+                sender.agent_id().to_owned(),
+                Utc::now(),
+            )
+            .error("failed to get room by classroom_id".to_owned())
+            .execute(&mut conn)
+            .await
+            .expect("Failed to insert nats dead letter")
+        };
+
+        let mut context = TestContext::new(db.clone(), admin_authz(&agent));
+
+        let payload = DeadLetterRequeueRequest {
+            ids: Some(vec![dead_letter.id()]),
+        };
+
+        let messages = handle_request::<DeadLetterRequeueHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Dead letters requeue failed");
+
+        let (result, _, _) = find_response::<JsonValue>(messages.as_slice());
+        assert_eq!(result["failed"], json!([dead_letter.id()]));
+
+        let dead_letters = {
+            let mut conn = db.get_conn().await;
+
+            db::nats_dead_letter::ListQuery::new()
+                .ids(vec![dead_letter.id()])
+                .execute(&mut conn)
+                .await
+                .expect("Failed to list nats dead letters")
+        };
+
+        assert_eq!(dead_letters[0].attempts(), 2);
+
+        // Requeue once the room is there.
+        let room = {
+            let mut conn = db.get_conn().await;
+
+            factory::Room::new(classroom_id, ClassType::Webinar)
+                .audience(USR_AUDIENCE)
+                .time((Bound::Included(Utc::now()), Bound::Unbounded))
+                .insert(&mut conn)
+                .await
+        };
+
+        let payload = DeadLetterRequeueRequest {
+            ids: Some(vec![dead_letter.id()]),
+        };
+
+        let messages = handle_request::<DeadLetterRequeueHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Dead letters requeue failed");
+
+        let (result, _, _) = find_response::<JsonValue>(messages.as_slice());
+        assert_eq!(result["requeued"], json!([dead_letter.id()]));
+
+        let mut conn = db.get_conn().await;
+
+        let events = db::event::ListQuery::new()
+            .room_id(room.id())
+            .kind("video_group".to_owned())
+            .execute(&mut conn)
+            .await
+            .expect("Failed to list events");
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].data()["video_group"], "created");
+
+        let dead_letters = db::nats_dead_letter::ListQuery::new()
+            .ids(vec![dead_letter.id()])
+            .execute(&mut conn)
+            .await
+            .expect("Failed to list nats dead letters");
+
+        assert!(dead_letters.is_empty());
+    }
+
+    #[tokio::test]
+    async fn list_jobs() {
+        let agent = TestAgent::new("alpha", "ops", SVC_AUDIENCE);
+        let mut context = TestContext::new(TestDb::new().await, admin_authz(&agent));
+
+        let job_id = context.jobs().start("adjust", None);
+        context.jobs().finish(job_id, &Ok::<_, String>(()));
+
+        let messages = handle_request::<JobListHandler>(&mut context, &agent, JobListRequest {})
+            .await
+            .expect("Jobs listing failed");
+
+        let (jobs, _, _) = find_response::<Vec<JsonValue>>(messages.as_slice());
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0]["kind"], "adjust");
+        assert_eq!(jobs[0]["status"], "succeeded");
+    }
+
+    #[tokio::test]
+    async fn reencode_draw_events() {
+        let agent = TestAgent::new("alpha", "ops", SVC_AUDIENCE);
+        let mut context = TestContext::new(TestDb::new().await, admin_authz(&agent));
+
+        let messages = handle_request::<ReencodeDrawEventsHandler>(
+            &mut context,
+            &agent,
+            ReencodeDrawEventsRequest::default(),
+        )
+        .await
+        .expect("Draw events re-encoding failed");
+
+        let (payload, respp, _) = find_response::<JsonValue>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::ACCEPTED);
+        assert!(payload["job_id"].is_number());
+
+        let jobs = context.jobs().list();
+        assert_eq!(jobs.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn backfill_set_heads() {
+        let agent = TestAgent::new("alpha", "ops", SVC_AUDIENCE);
+        let mut context = TestContext::new(TestDb::new().await, admin_authz(&agent));
+
+        let messages = handle_request::<BackfillSetHeadsHandler>(
+            &mut context,
+            &agent,
+            BackfillSetHeadsRequest::default(),
+        )
+        .await
+        .expect("Set heads backfill failed");
+
+        let (payload, respp, _) = find_response::<JsonValue>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::ACCEPTED);
+        assert!(payload["job_id"].is_number());
+
+        let jobs = context.jobs().list();
+        assert_eq!(jobs[0].kind(), "backfill_set_heads");
+    }
+
+    #[tokio::test]
+    async fn erase_account_events() {
+        let agent = TestAgent::new("alpha", "ops", SVC_AUDIENCE);
+        let user = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let mut context = TestContext::new(TestDb::new().await, admin_authz(&agent));
+
+        let payload = AccountEventsRequest {
+            account_id: user.account_id().to_owned(),
+            payload: AccountEventsPayload {
+                audience: format!("{}.example.org", Uuid::new_v4()),
+            },
+        };
+
+        let messages = handle_request::<AccountEventsEraseHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Account events erasure failed");
+
+        let (payload, respp, _) = find_response::<JsonValue>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::ACCEPTED);
+        assert!(payload["job_id"].is_number());
+
+        let jobs = context.jobs().list();
+        assert_eq!(jobs[0].kind(), "erase_account_events");
+    }
+
+    #[tokio::test]
+    async fn export_account_events_without_s3() {
+        let agent = TestAgent::new("alpha", "ops", SVC_AUDIENCE);
+        let user = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let mut context = TestContext::new(TestDb::new().await, admin_authz(&agent));
+
+        let payload = AccountEventsRequest {
+            account_id: user.account_id().to_owned(),
+            payload: AccountEventsPayload {
+                audience: USR_AUDIENCE.to_owned(),
+            },
+        };
+
+        let err = handle_request::<AccountEventsExportHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success on account events export");
+
+        assert_eq!(err.kind(), "no_s3_client");
+    }
+
+    #[tokio::test]
+    async fn reload_config_not_authorized() {
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let mut context = TestContext::new(TestDb::new().await, TestAuthz::new());
+
+        let err =
+            handle_request::<ConfigReloadHandler>(&mut context, &agent, ConfigReloadRequest {})
+                .await
+                .expect_err("Unexpected success on config reload");
+
+        assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+    }
+}
@@ -5,14 +5,16 @@ use async_trait::async_trait;
 use axum::extract::{
     self, Json, {Path, Query},
 };
+use chrono::{Duration, Utc};
 use serde_derive::{Deserialize, Serialize};
-use serde_json::json;
+use serde_json::{json, Value as JsonValue};
 use sqlx::Acquire;
 use svc_agent::mqtt::ResponseStatus;
-use svc_agent::{AccountId, Addressable};
+use svc_agent::{AccountId, Addressable, AgentId};
 use svc_authn::Authenticable;
 use svc_utils::extractors::AgentIdExtractor;
 use tracing::{error, instrument};
+use utoipa::IntoParams;
 use uuid::Uuid;
 
 use crate::app::context::Context;
@@ -25,10 +27,40 @@ use crate::db::room_ban::{DeleteQuery as BanDeleteQuery, InsertQuery as BanInser
 
 const MAX_LIMIT: usize = 25;
 
-#[derive(Debug, Deserialize)]
+/// How far back events of a banned account get removed on `remove_events`.
+const REMOVE_EVENTS_PERIOD_HOURS: i64 = 24;
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct ListPayload {
     offset: Option<usize>,
     limit: Option<usize>,
+    /// Comma separated list of optional properties to add: `presence`, `whiteboard_access`.
+    include: Option<String>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct ListIncludes {
+    presence: bool,
+    whiteboard_access: bool,
+}
+
+impl std::str::FromStr for ListIncludes {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut includes = Self::default();
+
+        for include in s.split(',').map(str::trim).filter(|i| !i.is_empty()) {
+            match include {
+                "presence" => includes.presence = true,
+                "whiteboard_access" => includes.whiteboard_access = true,
+                _ => bail!("Unknown include = '{}'", include),
+            }
+        }
+
+        Ok(includes)
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -38,6 +70,17 @@ pub struct ListRequest {
     payload: ListPayload,
 }
 
+#[utoipa::path(
+    get,
+    path = "/rooms/{id}/agents",
+    tag = "agent",
+    params(("id" = Uuid, Path, description = "Room identifier"), ListPayload),
+    responses(
+        (status = 200, description = "Agents in the room", body = [db::agent::AgentWithBan]),
+        (status = 404, description = "Room not found", body = ErrorPayload),
+    ),
+    security(("bearer" = []))
+)]
 pub async fn list(
     ctx: extract::Extension<Arc<AppContext>>,
     AgentIdExtractor(agent_id): AgentIdExtractor,
@@ -66,6 +109,13 @@ impl RequestHandler for ListHandler {
         Self::Payload { room_id, payload }: Self::Payload,
         reqp: RequestParams<'_>,
     ) -> RequestResult {
+        let includes = match payload.include {
+            Some(ref include) => include
+                .parse::<ListIncludes>()
+                .error(AppErrorKind::InvalidPayload)?,
+            None => ListIncludes::default(),
+        };
+
         let room = helpers::find_room(context, room_id, helpers::RoomTimeRequirement::Open).await?;
 
         // Authorize agents listing in the room.
@@ -77,8 +127,8 @@ impl RequestHandler for ListHandler {
 
         let authz_time = context
             .authz()
-            .authorize(
-                room.audience().into(),
+            .authorize_room(
+                &room,
                 reqp.as_account_id().to_owned(),
                 object,
                 "read".into(),
@@ -94,7 +144,9 @@ impl RequestHandler for ListHandler {
                 db::agent::Status::Ready,
                 payload.offset.unwrap_or(0),
                 std::cmp::min(payload.limit.unwrap_or(MAX_LIMIT), MAX_LIMIT),
-            );
+            )
+            .presence(includes.presence)
+            .whiteboard_access(includes.whiteboard_access);
 
             context
                 .metrics()
@@ -117,10 +169,26 @@ impl RequestHandler for ListHandler {
 ///////////////////////////////////////////////////////////////////////////////
 
 #[derive(Debug, Deserialize)]
-pub struct UpdatePayload {
+#[serde(untagged)]
+pub enum UpdatePayload {
+    Ban(BanPayload),
+    PresenceMeta(PresenceMetaPayload),
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BanPayload {
     account_id: AccountId,
     value: bool,
     reason: Option<String>,
+    /// Marks the account's recent events in the room as removed along with the ban.
+    #[serde(default)]
+    remove_events: bool,
+}
+
+/// Sets `presence_meta` of the current agent, `null` clears it.
+#[derive(Debug, Deserialize)]
+pub struct PresenceMetaPayload {
+    presence_meta: JsonValue,
 }
 
 #[derive(Debug, Deserialize)]
@@ -138,6 +206,12 @@ pub struct BanNotification {
     reason: Option<String>,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct PresenceMetaNotification {
+    agent_id: AgentId,
+    presence_meta: Option<JsonValue>,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct TenantBanNotification {
     room_id: Uuid,
@@ -178,153 +252,272 @@ impl RequestHandler for UpdateHandler {
         Self::Payload { room_id, payload }: Self::Payload,
         reqp: RequestParams<'_>,
     ) -> RequestResult {
-        let room = helpers::find_room(context, room_id, helpers::RoomTimeRequirement::Open).await?;
+        match payload {
+            UpdatePayload::Ban(payload) => update_ban(context, room_id, payload, reqp).await,
+            UpdatePayload::PresenceMeta(payload) => {
+                update_presence_meta(context, room_id, payload, reqp).await
+            }
+        }
+    }
+}
 
-        let author = reqp.as_account_id().to_string();
+async fn update_ban<C: Context>(
+    context: &mut C,
+    room_id: Uuid,
+    payload: BanPayload,
+    reqp: RequestParams<'_>,
+) -> RequestResult {
+    let room = helpers::find_room(context, room_id, helpers::RoomTimeRequirement::Open).await?;
 
-        let object = {
-            let object = room.authz_object();
-            let mut object = object.iter().map(|s| s.as_ref()).collect::<Vec<_>>();
-            object.extend(["claims", "role", "authors", &author].iter());
-            AuthzObject::new(&object)
-        };
+    let author = reqp.as_account_id().to_string();
 
-        let authz_time = context
-            .authz()
-            .authorize(
-                room.audience().into(),
-                reqp.to_owned(),
-                object.into(),
-                "create".into(),
-            )
-            .await?;
+    let object = {
+        let object = room.authz_object();
+        let mut object = object.iter().map(|s| s.as_ref()).collect::<Vec<_>>();
+        object.extend(["claims", "role", "authors", &author].iter());
+        AuthzObject::new(&object)
+    };
 
-        let object = {
-            let object = room.authz_object();
-            let mut object = object.iter().map(|s| s.as_ref()).collect::<Vec<_>>();
-            object.push("events");
-            AuthzObject::new(&object)
-        };
+    let authz_time = context
+        .authz()
+        .authorize_room(&room, reqp.to_owned(), object.into(), "create".into())
+        .await?;
 
-        let mut conn = context.get_conn().await?;
-
-        let mut txn = conn
-            .begin()
-            .await
-            .context("Failed to acquire transaction")
-            .error(AppErrorKind::DbQueryFailed)?;
-        if payload.value {
-            let mut query = BanInsertQuery::new(payload.account_id.clone(), room_id);
+    let object = {
+        let object = room.authz_object();
+        let mut object = object.iter().map(|s| s.as_ref()).collect::<Vec<_>>();
+        object.push("events");
+        AuthzObject::new(&object)
+    };
 
-            if let Some(ref reason) = payload.reason {
-                query.reason(reason);
-            }
+    let mut conn = context.get_conn().await?;
 
-            context
-                .metrics()
-                .measure_query(QueryKey::BanInsertQuery, query.execute(&mut txn))
-                .await
-                .context("Failed to insert room ban")
-                .error(AppErrorKind::DbQueryFailed)?;
-        } else {
-            let query = BanDeleteQuery::new(payload.account_id.clone(), room_id);
+    let mut txn = conn
+        .begin()
+        .await
+        .context("Failed to acquire transaction")
+        .error(AppErrorKind::DbQueryFailed)?;
+    if payload.value {
+        let mut query = BanInsertQuery::new(payload.account_id.clone(), room_id);
 
-            context
-                .metrics()
-                .measure_query(QueryKey::BanDeleteQuery, query.execute(&mut txn))
-                .await
-                .context("Failed to delete room ban")
-                .error(AppErrorKind::DbQueryFailed)?;
+        if let Some(ref reason) = payload.reason {
+            query.reason(reason);
         }
 
         context
             .metrics()
-            .measure_query(
-                QueryKey::EventInsertQuery,
-                insert_account_ban_event(
-                    &room,
-                    &payload.account_id,
-                    payload.value,
-                    payload.reason.clone(),
-                    reqp.as_agent_id(),
-                    &mut txn,
-                ),
-            )
+            .measure_query(QueryKey::BanInsertQuery, query.execute(&mut txn))
             .await
-            .context("Failed to insert event")
+            .context("Failed to insert room ban")
             .error(AppErrorKind::DbQueryFailed)?;
-        txn.commit()
+    } else {
+        let query = BanDeleteQuery::new(payload.account_id.clone(), room_id);
+
+        context
+            .metrics()
+            .measure_query(QueryKey::BanDeleteQuery, query.execute(&mut txn))
             .await
-            .context("Failed to commit transaction")
+            .context("Failed to delete room ban")
             .error(AppErrorKind::DbQueryFailed)?;
+    }
 
+    let deleted_events = if payload.value && payload.remove_events {
+        let since = Utc::now() - Duration::hours(REMOVE_EVENTS_PERIOD_HOURS);
+        let query =
+            db::event::DeleteByAccountQuery::new(room_id, payload.account_id.clone(), since);
+
+        context
+            .metrics()
+            .measure_query(QueryKey::EventDeleteByAccountQuery, query.execute(&mut txn))
+            .await
+            .context("Failed to delete account events")
+            .error(AppErrorKind::DbQueryFailed)?
+    } else {
+        vec![]
+    };
+
+    let data_encryption = context.data_encryption();
+
+    context
+        .metrics()
+        .measure_query(
+            QueryKey::EventInsertQuery,
+            insert_account_ban_event(
+                &room,
+                &payload.account_id,
+                payload.value,
+                payload.reason.clone(),
+                reqp.as_agent_id(),
+                data_encryption.as_deref(),
+                &mut txn,
+            ),
+        )
+        .await
+        .context("Failed to insert event")
+        .error(AppErrorKind::DbQueryFailed)?;
+    txn.commit()
+        .await
+        .context("Failed to commit transaction")
+        .error(AppErrorKind::DbQueryFailed)?;
+
+    // Ban keys are per audience so a co-hosting tenant gets its own one.
+    for audience in room.audiences() {
         if let Err(e) = context
             .authz()
             .ban(
-                room.audience().into(),
+                audience.into(),
                 payload.account_id.clone(),
-                object.into(),
+                object.clone().into(),
                 payload.value,
                 context.config().ban_duration() as usize,
             )
             .await
         {
             error!(
-                "Failed to write account ban into redis, account = {}, ban = {}, reason = {}",
-                &author, payload.value, e
-            );
+                    "Failed to write account ban into redis, account = {}, audience = {}, ban = {}, reason = {}",
+                    &author, audience, payload.value, e
+                );
         }
+    }
 
-        // Respond to the agent.
-        let mut response = AppResponse::new(
-            ResponseStatus::OK,
-            json!({}),
+    // Respond to the agent.
+    let mut response = AppResponse::new(
+        ResponseStatus::OK,
+        json!({}),
+        context.start_timestamp(),
+        Some(authz_time),
+    );
+
+    let tenant_notification = TenantBanNotification {
+        room_id: room.id(),
+        account_id: payload.account_id.clone(),
+        reason: payload.reason.clone(),
+        banned_by: reqp.to_owned().as_account_id().to_owned(),
+        banned: payload.value,
+        classroom_id: room.classroom_id(),
+    };
+
+    response.add_notification(
+        "agent.ban",
+        &context.config().audience_topic(room.audience()),
+        tenant_notification,
+        context.start_timestamp(),
+    )?;
+
+    let room_notification = BanNotification {
+        account_id: payload.account_id,
+        banned: payload.value,
+        reason: payload.reason,
+    };
+
+    // Notify room subscribers.
+    response.add_notification(
+        "agent.update",
+        &context.config().room_topic(room.audience(), room.id()),
+        room_notification,
+        context.start_timestamp(),
+    )?;
+
+    for tombstone in deleted_events.iter().filter_map(db::event::Tombstone::new) {
+        response.add_notification(
+            "event.delete",
+            &context.config().room_topic(room.audience(), room.id()),
+            tombstone,
             context.start_timestamp(),
-            Some(authz_time),
-        );
+        )?;
+    }
 
-        let tenant_notification = TenantBanNotification {
-            room_id: room.id(),
-            account_id: payload.account_id.clone(),
-            reason: payload.reason.clone(),
-            banned_by: reqp.to_owned().as_account_id().to_owned(),
-            banned: payload.value,
-            classroom_id: room.classroom_id(),
-        };
+    Ok(response)
+}
 
-        response.add_notification(
-            "agent.ban",
-            &format!("audiences/{}/events", room.audience()),
-            tenant_notification,
-            context.start_timestamp(),
-        );
+async fn update_presence_meta<C: Context>(
+    context: &mut C,
+    room_id: Uuid,
+    payload: PresenceMetaPayload,
+    reqp: RequestParams<'_>,
+) -> RequestResult {
+    let room = helpers::find_room(context, room_id, helpers::RoomTimeRequirement::Open).await?;
 
-        let room_notification = BanNotification {
-            account_id: payload.account_id,
-            banned: payload.value,
-            reason: payload.reason,
-        };
+    let presence_meta = Some(payload.presence_meta).filter(|meta| !meta.is_null());
+    let max_size = context.config().constraint.presence_meta_size;
 
-        // Notify room subscribers.
-        response.add_notification(
-            "agent.update",
-            &format!("rooms/{}/events", room.id()),
-            room_notification,
-            context.start_timestamp(),
-        );
+    if let Some(ref meta) = presence_meta {
+        if meta.to_string().len() > max_size {
+            return Err(anyhow!("Presence meta exceeds {} bytes", max_size))
+                .error(AppErrorKind::PayloadSizeExceeded);
+        }
+    }
+
+    let object = {
+        let object = room.authz_object();
+        let object = object.iter().map(|s| s.as_ref()).collect::<Vec<_>>();
+        AuthzObject::new(&object).into()
+    };
+
+    let authz_time = context
+        .authz()
+        .authorize_room(
+            &room,
+            reqp.as_account_id().to_owned(),
+            object,
+            "read".into(),
+        )
+        .await?;
+
+    let query = db::agent::UpdatePresenceMetaQuery::new(
+        reqp.as_agent_id().to_owned(),
+        room.id(),
+        presence_meta.clone(),
+    );
+
+    let mut conn = context.get_conn().await?;
 
-        Ok(response)
+    let updated = context
+        .metrics()
+        .measure_query(
+            QueryKey::AgentUpdatePresenceMetaQuery,
+            query.execute(&mut conn),
+        )
+        .await
+        .context("Failed to update presence meta")
+        .error(AppErrorKind::DbQueryFailed)?;
+
+    if !updated {
+        return Err(anyhow!("Agent has not entered the room"))
+            .error(AppErrorKind::AgentNotEnteredTheRoom);
     }
+
+    let mut response = AppResponse::new(
+        ResponseStatus::OK,
+        json!({}),
+        context.start_timestamp(),
+        Some(authz_time),
+    );
+
+    response.add_notification(
+        "agent.update",
+        &context.config().room_topic(room.audience(), room.id()),
+        PresenceMetaNotification {
+            agent_id: reqp.as_agent_id().to_owned(),
+            presence_meta,
+        },
+        context.start_timestamp(),
+    )?;
+
+    Ok(response)
 }
 
 ///////////////////////////////////////////////////////////////////////////////
 
 #[cfg(test)]
 mod tests {
+    use chrono::Utc;
     use serde_derive::Deserialize;
+    use serde_json::Value as JsonValue;
     use svc_agent::AgentId;
     use uuid::Uuid;
 
+    use crate::test_helpers::outgoing_envelope::OutgoingEnvelopeProperties;
     use crate::test_helpers::prelude::*;
 
     use super::*;
@@ -372,10 +565,7 @@ mod tests {
 
         let payload = ListRequest {
             room_id: room.id(),
-            payload: ListPayload {
-                offset: None,
-                limit: None,
-            },
+            payload: ListPayload::default(),
         };
 
         let messages = handle_request::<ListHandler>(&mut context, &agent, payload)
@@ -395,6 +585,110 @@ mod tests {
         assert_eq!(agents[0].banned, Some(true));
     }
 
+    #[tokio::test]
+    async fn list_agents_with_includes() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let other_agent = TestAgent::new("web", "user456", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+
+            shared_helpers::insert_agent(&mut conn, agent.agent_id(), room.id()).await;
+            shared_helpers::insert_agent(&mut conn, other_agent.agent_id(), room.id()).await;
+
+            factory::Event::new()
+                .room_id(room.id())
+                .kind("agent_enter")
+                .data(&serde_json::Value::Null)
+                .occurred_at(0)
+                .created_by(agent.agent_id())
+                .created_at(Utc::now() - chrono::Duration::minutes(5))
+                .insert(&mut conn)
+                .await;
+
+            let mut whiteboard_access = std::collections::HashMap::new();
+            whiteboard_access.insert(agent.account_id().to_owned(), true);
+
+            db::room::UpdateQuery::new(room.id())
+                .whiteboard_access(whiteboard_access)
+                .execute(&mut conn)
+                .await
+                .expect("Failed to update room");
+
+            room
+        };
+
+        let mut authz = TestAuthz::new();
+        authz.allow(
+            agent.account_id(),
+            vec!["classrooms", &room.classroom_id().to_string()],
+            "read",
+        );
+
+        let mut context = TestContext::new(db, authz);
+
+        let payload = ListRequest {
+            room_id: room.id(),
+            payload: ListPayload {
+                include: Some(String::from("presence,whiteboard_access")),
+                ..Default::default()
+            },
+        };
+
+        let messages = handle_request::<ListHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Agents listing failed");
+
+        let (agents, respp, _) = find_response::<Vec<JsonValue>>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert_eq!(agents.len(), 2);
+
+        let presence = agents[1]["presence_duration"].as_i64().unwrap();
+        assert!((300..360).contains(&presence));
+        assert_eq!(agents[1]["whiteboard_access"], true);
+        assert_eq!(agents[1]["banned"], false);
+
+        // No `agent_enter` event so it's counted from the entrance.
+        assert!(agents[0]["presence_duration"].as_i64().unwrap() < 60);
+        assert_eq!(agents[0]["whiteboard_access"], false);
+
+        // Nothing extra without includes.
+        let payload = ListRequest {
+            room_id: room.id(),
+            payload: ListPayload::default(),
+        };
+
+        let messages = handle_request::<ListHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Agents listing failed");
+
+        let (agents, _, _) = find_response::<Vec<JsonValue>>(messages.as_slice());
+        assert!(agents[0].get("presence_duration").is_none());
+        assert!(agents[0].get("whiteboard_access").is_none());
+    }
+
+    #[tokio::test]
+    async fn list_agents_unknown_include() {
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let mut context = TestContext::new(TestDb::new().await, TestAuthz::new());
+
+        let payload = ListRequest {
+            room_id: Uuid::new_v4(),
+            payload: ListPayload {
+                include: Some(String::from("presence,secrets")),
+                ..Default::default()
+            },
+        };
+
+        let err = handle_request::<ListHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success listing agents with unknown include");
+
+        assert_eq!(err.kind(), "invalid_payload");
+    }
+
     #[tokio::test]
     async fn list_agents_not_authorized() {
         let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
@@ -409,10 +703,7 @@ mod tests {
 
         let payload = ListRequest {
             room_id: room.id(),
-            payload: ListPayload {
-                offset: None,
-                limit: None,
-            },
+            payload: ListPayload::default(),
         };
 
         let err = handle_request::<ListHandler>(&mut context, &agent, payload)
@@ -446,10 +737,7 @@ mod tests {
 
         let payload = ListRequest {
             room_id: room.id(),
-            payload: ListPayload {
-                offset: None,
-                limit: None,
-            },
+            payload: ListPayload::default(),
         };
 
         let err = handle_request::<ListHandler>(&mut context, &agent, payload)
@@ -467,10 +755,7 @@ mod tests {
 
         let payload = ListRequest {
             room_id: Uuid::new_v4(),
-            payload: ListPayload {
-                offset: None,
-                limit: None,
-            },
+            payload: ListPayload::default(),
         };
 
         let err = handle_request::<ListHandler>(&mut context, &agent, payload)
@@ -538,10 +823,15 @@ mod tests {
                 set: Some(String::from("messages")),
                 label: Some(String::from("message-1")),
                 attribute: None,
+                attributes: vec![],
                 data: json!({ "text": "banmsg" }),
                 is_claim: false,
                 is_persistent: true,
                 removed: false,
+                content_encrypted: false,
+                key_id: None,
+                skip_broadcast_if_empty: false,
+                expected_last_occurred_at: None,
             },
         };
 
@@ -562,11 +852,12 @@ mod tests {
         // Admin bans user
         let payload = UpdateRequest {
             room_id: room.id(),
-            payload: UpdatePayload {
+            payload: UpdatePayload::Ban(BanPayload {
                 account_id: user.account_id().to_owned(),
                 value: true,
                 reason: Some("some reason".into()),
-            },
+                remove_events: false,
+            }),
         };
 
         let messages = handle_request::<UpdateHandler>(&mut context, &admin, payload)
@@ -614,11 +905,12 @@ mod tests {
         // Ban once again, this should do nothing
         let payload = UpdateRequest {
             room_id: room.id(),
-            payload: UpdatePayload {
+            payload: UpdatePayload::Ban(BanPayload {
                 account_id: user.account_id().to_owned(),
                 value: true,
                 reason: None,
-            },
+                remove_events: false,
+            }),
         };
 
         let messages = handle_request::<UpdateHandler>(&mut context, &admin, payload)
@@ -637,10 +929,15 @@ mod tests {
                 set: Some(String::from("messages")),
                 label: Some(String::from("message-1")),
                 attribute: None,
+                attributes: vec![],
                 data: json!({ "text": "hello" }),
                 is_claim: false,
                 is_persistent: true,
                 removed: false,
+                content_encrypted: false,
+                key_id: None,
+                skip_broadcast_if_empty: false,
+                expected_last_occurred_at: None,
             },
         };
 
@@ -654,11 +951,12 @@ mod tests {
         // Unban the user
         let payload = UpdateRequest {
             room_id: room.id(),
-            payload: UpdatePayload {
+            payload: UpdatePayload::Ban(BanPayload {
                 account_id: user.account_id().to_owned(),
                 value: false,
                 reason: None,
-            },
+                remove_events: false,
+            }),
         };
 
         let messages = handle_request::<UpdateHandler>(&mut context, &admin, payload)
@@ -708,10 +1006,15 @@ mod tests {
                 set: Some(String::from("messages")),
                 label: Some(String::from("message-1")),
                 attribute: None,
+                attributes: vec![],
                 data: json!({ "text": "hello 2" }),
                 is_claim: false,
                 is_persistent: true,
                 removed: false,
+                content_encrypted: false,
+                key_id: None,
+                skip_broadcast_if_empty: false,
+                expected_last_occurred_at: None,
             },
         };
 
@@ -729,4 +1032,212 @@ mod tests {
         let (_, respp, _) = find_response::<crate::db::event::Object>(messages.as_slice());
         assert_eq!(respp.status(), ResponseStatus::CREATED);
     }
+
+    #[tokio::test]
+    async fn ban_agent_deleting_events() {
+        let db = TestDb::new().await;
+        let user = TestAgent::new("web", "user", USR_AUDIENCE);
+        let admin = TestAgent::new("web", "admin", USR_AUDIENCE);
+
+        let (room, recent_event) = {
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_unbounded_room(&mut conn).await;
+
+            let recent_event = factory::Event::new()
+                .room_id(room.id())
+                .kind("message")
+                .data(&json!({ "text": "banmsg" }))
+                .occurred_at(1000)
+                .created_by(user.agent_id())
+                .insert(&mut conn)
+                .await;
+
+            // Too old to get removed.
+            factory::Event::new()
+                .room_id(room.id())
+                .kind("message")
+                .data(&json!({ "text": "hello" }))
+                .occurred_at(0)
+                .created_by(user.agent_id())
+                .created_at(Utc::now() - chrono::Duration::days(2))
+                .insert(&mut conn)
+                .await;
+
+            (room, recent_event)
+        };
+
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+
+        authz.allow(
+            admin.account_id(),
+            vec![
+                "classrooms",
+                &classroom_id,
+                "claims",
+                "role",
+                "authors",
+                &admin.account_id().to_string(),
+            ],
+            "create",
+        );
+
+        let mut context = TestContext::new(db, authz);
+
+        let payload = UpdateRequest {
+            room_id: room.id(),
+            payload: UpdatePayload::Ban(BanPayload {
+                account_id: user.account_id().to_owned(),
+                value: true,
+                reason: None,
+                remove_events: true,
+            }),
+        };
+
+        let messages = handle_request::<UpdateHandler>(&mut context, &admin, payload)
+            .await
+            .expect("Agent ban failed");
+
+        let tombstones = messages
+            .iter()
+            .filter(|message| match message.properties() {
+                OutgoingEnvelopeProperties::Event(evp) => evp.label() == "event.delete",
+                _ => false,
+            })
+            .map(|message| message.payload::<db::event::Tombstone>())
+            .collect::<Vec<_>>();
+
+        assert_eq!(tombstones.len(), 1);
+        assert_eq!(tombstones[0].id(), recent_event.id());
+
+        // The deleted event is listed only on demand.
+        let mut conn = context.get_conn().await.expect("Failed to get conn");
+
+        let events = db::event::ListQuery::new()
+            .room_id(room.id())
+            .kind("message".to_string())
+            .execute(&mut conn)
+            .await
+            .expect("Failed to list events");
+
+        assert_eq!(events.len(), 1);
+        assert_ne!(events[0].id(), recent_event.id());
+
+        let events = db::event::ListQuery::new()
+            .room_id(room.id())
+            .kind("message".to_string())
+            .include_deleted()
+            .execute(&mut conn)
+            .await
+            .expect("Failed to list events");
+
+        assert_eq!(events.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn update_presence_meta() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+            shared_helpers::insert_agent(&mut conn, agent.agent_id(), room.id()).await;
+            room
+        };
+
+        let mut authz = TestAuthz::new();
+        authz.allow(
+            agent.account_id(),
+            vec!["classrooms", &room.classroom_id().to_string()],
+            "read",
+        );
+
+        let mut context = TestContext::new(db, authz);
+
+        let payload = UpdateRequest {
+            room_id: room.id(),
+            payload: UpdatePayload::PresenceMeta(PresenceMetaPayload {
+                presence_meta: json!({ "hand_raised": true }),
+            }),
+        };
+
+        let messages = handle_request::<UpdateHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Presence meta update failed");
+
+        let (_, respp, _) = find_response::<JsonValue>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+
+        let (notification, evp, _) = find_event::<PresenceMetaNotification>(messages.as_slice());
+        assert_eq!(evp.label(), "agent.update");
+        assert_eq!(&notification.agent_id, agent.agent_id());
+        assert_eq!(
+            notification.presence_meta,
+            Some(json!({ "hand_raised": true }))
+        );
+
+        // The meta is listed along with the agent.
+        let payload = ListRequest {
+            room_id: room.id(),
+            payload: ListPayload::default(),
+        };
+
+        let messages = handle_request::<ListHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Agents listing failed");
+
+        let (agents, _, _) = find_response::<Vec<JsonValue>>(messages.as_slice());
+        assert_eq!(agents.len(), 1);
+        assert_eq!(agents[0]["presence_meta"], json!({ "hand_raised": true }));
+
+        // Too large meta.
+        context.update_config(|config| config.constraint.presence_meta_size = 8);
+
+        let payload = UpdateRequest {
+            room_id: room.id(),
+            payload: UpdatePayload::PresenceMeta(PresenceMetaPayload {
+                presence_meta: json!({ "hand_raised": true }),
+            }),
+        };
+
+        let err = handle_request::<UpdateHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success on presence meta update");
+
+        assert_eq!(err.kind(), "payload_size_exceeded");
+    }
+
+    #[tokio::test]
+    async fn update_presence_meta_not_entered() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut authz = TestAuthz::new();
+        authz.allow(
+            agent.account_id(),
+            vec!["classrooms", &room.classroom_id().to_string()],
+            "read",
+        );
+
+        let mut context = TestContext::new(db, authz);
+
+        let payload = UpdateRequest {
+            room_id: room.id(),
+            payload: UpdatePayload::PresenceMeta(PresenceMetaPayload {
+                presence_meta: json!({ "hand_raised": true }),
+            }),
+        };
+
+        let err = handle_request::<UpdateHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success on presence meta update");
+
+        assert_eq!(err.kind(), "agent_not_entered_the_room");
+    }
 }
@@ -0,0 +1,216 @@
+use anyhow::Context as AnyhowContext;
+use async_trait::async_trait;
+use axum::extract::{self, Path, RawQuery};
+use chrono::{TimeZone, Utc};
+use serde_derive::Deserialize;
+use svc_agent::mqtt::ResponseStatus;
+use svc_utils::extractors::AgentIdExtractor;
+use tracing::{field::display, instrument, Span};
+use utoipa::IntoParams;
+use uuid::Uuid;
+
+use crate::app::context::Context;
+use crate::app::endpoint::prelude::*;
+use crate::db;
+
+const MAX_LIMIT: usize = 100;
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListPayload {
+    /// Only attachments which MIME type starts with the prefix, e.g. `image/`.
+    mime: Option<String>,
+    /// Unix time in milliseconds to list attachments created before.
+    before: Option<i64>,
+    /// Maximum number of attachments, 100 at most.
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListRequest {
+    room_id: Uuid,
+    #[serde(flatten)]
+    payload: ListPayload,
+}
+
+#[utoipa::path(
+    get,
+    path = "/rooms/{id}/attachments",
+    tag = "attachment",
+    params(("id" = Uuid, Path, description = "Room identifier"), ListPayload),
+    responses(
+        (status = 200, description = "Attachments, the latest first", body = [crate::db::attachment::Object]),
+        (status = 403, description = "Not authorized", body = ErrorPayload),
+        (status = 404, description = "Room not found", body = ErrorPayload),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn list(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<Uuid>,
+    RawQuery(query): RawQuery,
+) -> RequestResult {
+    let payload = serde_qs::from_str(&query.unwrap_or_default())
+        .context("Failed to parse qs")
+        .error(AppErrorKind::InvalidQueryString)?;
+    let request = ListRequest { room_id, payload };
+    ListHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct ListHandler;
+
+#[async_trait]
+impl RequestHandler for ListHandler {
+    type Payload = ListRequest;
+
+    #[instrument(skip_all, fields(room_id, scope, classroom_id))]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload { room_id, payload }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        Span::current().record("room_id", &display(room_id));
+
+        let room = helpers::find_room(context, room_id, helpers::RoomTimeRequirement::Any).await?;
+
+        // The same permission as for listing events.
+        let classroom_id = room.classroom_id().to_string();
+        let object = AuthzObject::new(&["classrooms", &classroom_id]);
+        let authz_time = helpers::authorize_room(context, &room, reqp, object, "read").await?;
+
+        let limit = std::cmp::min(payload.limit.unwrap_or(MAX_LIMIT), MAX_LIMIT);
+        let mut query = db::attachment::RoomListQuery::new(room.id(), limit as i64);
+
+        if let Some(mime) = payload.mime {
+            query = query.mime_prefix(mime);
+        }
+
+        if let Some(before) = payload.before {
+            let before = Utc
+                .timestamp_millis_opt(before)
+                .single()
+                .context("Invalid 'before'")
+                .error(AppErrorKind::InvalidPayload)?;
+
+            query = query.before(before);
+        }
+
+        let mut conn = context.get_ro_conn().await?;
+
+        let attachments = context
+            .metrics()
+            .measure_query(QueryKey::AttachmentListQuery, query.execute(&mut conn))
+            .await
+            .context("Failed to list attachments")
+            .error(AppErrorKind::DbQueryFailed)?;
+
+        Ok(AppResponse::new(
+            ResponseStatus::OK,
+            attachments,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::db::attachment::Object as Attachment;
+    use crate::test_helpers::prelude::*;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn list_attachments() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let (room, image) = {
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+
+            let attachment = |mime: &str| {
+                factory::Event::new()
+                    .room_id(room.id())
+                    .kind("attachment")
+                    .data(&json!({
+                        "url": "https://storage.example.org/files/1",
+                        "size": 1024,
+                        "mime": mime,
+                    }))
+                    .occurred_at(1000)
+                    .created_by(agent.agent_id())
+            };
+
+            let image = attachment("image/png").insert(&mut conn).await;
+            attachment("application/pdf").insert(&mut conn).await;
+            (room, image)
+        };
+
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        authz.allow(
+            agent.account_id(),
+            vec!["classrooms", &classroom_id],
+            "read",
+        );
+
+        let mut context = TestContext::new(db, authz);
+
+        let payload = ListRequest {
+            room_id: room.id(),
+            payload: ListPayload {
+                mime: Some("image/".to_owned()),
+                ..Default::default()
+            },
+        };
+
+        let messages = handle_request::<ListHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Attachments listing failed");
+
+        let (attachments, respp, _) = find_response::<Vec<Attachment>>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert_eq!(attachments.len(), 1);
+        assert_eq!(attachments[0].event_id(), image.id());
+        assert_eq!(attachments[0].mime(), "image/png");
+    }
+
+    #[tokio::test]
+    async fn list_attachments_not_authorized() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut context = TestContext::new(db, TestAuthz::new());
+
+        let payload = ListRequest {
+            room_id: room.id(),
+            payload: ListPayload::default(),
+        };
+
+        let err = handle_request::<ListHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success listing attachments without authorization");
+
+        assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+    }
+}
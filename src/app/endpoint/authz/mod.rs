@@ -1,7 +1,7 @@
 use std::pin::Pin;
 use std::sync::Arc;
 
-use sqlx::postgres::PgPool as Db;
+use sqlx::postgres::{PgConnection, PgPool as Db};
 use svc_agent::AccountId;
 use svc_authz::IntentObject;
 use tracing::error;
@@ -72,15 +72,10 @@ pub fn db_ban_callback(db: Db) -> svc_authz::BanCallback {
                         [obj, classroom_id, ..] if obj == "classrooms" => {
                             if let Ok(classroom_id) = Uuid::parse_str(classroom_id) {
                                 if let Ok(mut conn) = db_.acquire().await {
-                                    let ban = crate::db::room_ban::ClassroomFindQuery::new(
-                                        account_id.to_owned(),
-                                        classroom_id,
-                                    )
-                                    .execute(&mut conn)
-                                    .await;
+                                    let ban = is_banned(&mut conn, &account_id, classroom_id).await;
 
                                     match ban {
-                                        Ok(maybe_ban) => return maybe_ban.is_some(),
+                                        Ok(is_banned) => return is_banned,
                                         Err(e) => {
                                             error!(
                                             "Failed to fetch ban from db, account = {}, classroom_id = {}, reason = {}",
@@ -109,6 +104,29 @@ pub fn db_ban_callback(db: Db) -> svc_authz::BanCallback {
     ) as svc_authz::BanCallback
 }
 
+/// Checks whether the account is banned either in the classroom or in its whole audience.
+pub async fn is_banned(
+    conn: &mut PgConnection,
+    account_id: &AccountId,
+    classroom_id: Uuid,
+) -> sqlx::Result<bool> {
+    let room_ban =
+        crate::db::room_ban::ClassroomFindQuery::new(account_id.to_owned(), classroom_id)
+            .execute(conn)
+            .await?;
+
+    if room_ban.is_some() {
+        return Ok(true);
+    }
+
+    let tenant_ban =
+        crate::db::tenant_ban::ClassroomFindQuery::new(account_id.to_owned(), classroom_id)
+            .execute(conn)
+            .await?;
+
+    Ok(tenant_ban.is_some())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
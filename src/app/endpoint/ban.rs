@@ -57,8 +57,8 @@ impl RequestHandler for ListHandler {
 
         let authz_time = context
             .authz()
-            .authorize(
-                room.audience().into(),
+            .authorize_room(
+                &room,
                 reqp.as_account_id().to_owned(),
                 object,
                 "update".into(),
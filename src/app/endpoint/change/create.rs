@@ -79,8 +79,8 @@ impl RequestHandler for CreateHandler {
 
         let authz_time = context
             .authz()
-            .authorize(
-                room.audience().into(),
+            .authorize_room(
+                &room,
                 reqp.as_account_id().to_owned(),
                 object,
                 "update".into(),
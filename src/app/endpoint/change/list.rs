@@ -2,7 +2,7 @@ use anyhow::Context as AnyhowContext;
 use async_trait::async_trait;
 use axum::extract::{self, Path, Query};
 use chrono::{DateTime, Utc};
-use serde_derive::Deserialize;
+use serde_derive::{Deserialize, Serialize};
 use svc_agent::mqtt::ResponseStatus;
 use svc_authn::Authenticable;
 use svc_utils::extractors::AgentIdExtractor;
@@ -12,15 +12,30 @@ use uuid::Uuid;
 use crate::app::context::Context;
 use crate::app::endpoint::prelude::*;
 use crate::db;
+use crate::db::change::{ChangeType, Counts, Object as Change};
 
 pub struct ListHandler;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 pub struct ListPayload {
     pub last_created_at: Option<DateTime<Utc>>,
+    pub cursor: Option<Uuid>,
+    #[serde(rename = "type")]
+    pub kind: Option<ChangeType>,
+    pub event_type: Option<String>,
     pub limit: Option<usize>,
 }
 
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ListResponse {
+    pub changes: Vec<Change>,
+    /// Id of the last change to pass as `cursor` for the next page.
+    pub next_cursor: Option<Uuid>,
+    /// Number of changes matching the filters.
+    pub total: i64,
+    pub counts: Counts,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct ListRequest {
     pub id: Uuid,
@@ -82,8 +97,8 @@ impl RequestHandler for ListHandler {
 
         let authz_time = context
             .authz()
-            .authorize(
-                room.audience().into(),
+            .authorize_room(
+                &room,
                 reqp.as_account_id().to_owned(),
                 object,
                 "update".into(),
@@ -96,24 +111,64 @@ impl RequestHandler for ListHandler {
             query = query.last_created_at(last_created_at);
         }
 
+        if let Some(cursor) = payload.cursor {
+            query = query.cursor(cursor);
+        }
+
+        if let Some(kind) = payload.kind {
+            query = query.change_type(kind);
+        }
+
+        if let Some(ref event_type) = payload.event_type {
+            query = query.kind(event_type);
+        }
+
         if let Some(limit) = payload.limit {
             query = query.limit(limit);
         }
 
-        let changes = {
+        let (changes, counts) = {
             let mut conn = context.get_ro_conn().await?;
 
-            context
+            let changes = context
                 .metrics()
                 .measure_query(QueryKey::ChangeListQuery, query.execute(&mut conn))
                 .await
                 .context("Failed to list changes")
-                .error(AppErrorKind::DbQueryFailed)?
+                .error(AppErrorKind::DbQueryFailed)?;
+
+            let counts = context
+                .metrics()
+                .measure_query(QueryKey::ChangeCountQuery, query.counts(&mut conn))
+                .await
+                .context("Failed to count changes")
+                .error(AppErrorKind::DbQueryFailed)?;
+
+            (changes, counts)
+        };
+
+        let limit = payload.limit.unwrap_or(db::change::DEFAULT_LIST_LIMIT);
+
+        let next_cursor = match changes.last() {
+            Some(change) if changes.len() >= limit => Some(change.id()),
+            _ => None,
+        };
+
+        let total = match payload.kind {
+            Some(kind) => counts.get(kind),
+            None => counts.total(),
+        };
+
+        let response = ListResponse {
+            changes,
+            next_cursor,
+            total,
+            counts,
         };
 
         Ok(AppResponse::new(
             ResponseStatus::OK,
-            changes,
+            response,
             context.start_timestamp(),
             Some(authz_time),
         ))
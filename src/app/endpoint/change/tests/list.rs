@@ -3,7 +3,7 @@ use svc_agent::mqtt::ResponseStatus;
 use uuid::Uuid;
 
 use super::super::*;
-use crate::db::change::{ChangeType, Object as Change};
+use crate::db::change::{ChangeType, Counts};
 use crate::test_helpers::prelude::*;
 
 #[tokio::test]
@@ -52,23 +52,155 @@ async fn list_changes() {
 
     let payload = ListRequest {
         id: edition.id(),
-        payload: ListPayload {
-            last_created_at: None,
-            limit: None,
-        },
+        payload: ListPayload::default(),
     };
 
     let messages = handle_request::<ListHandler>(&mut context, &agent, payload)
         .await
         .expect("Failed to list changes");
 
-    let (response_changes, respp, _) = find_response::<Vec<Change>>(messages.as_slice());
+    let (response, respp, _) = find_response::<ListResponse>(messages.as_slice());
     assert_eq!(respp.status(), ResponseStatus::OK);
-    assert_eq!(response_changes.len(), 25);
+    assert_eq!(response.changes.len(), 25);
+    assert_eq!(response.total, 34);
+    assert_eq!(response.counts.modification, 34);
+    assert_eq!(response.next_cursor, Some(response.changes[24].id()));
 
     let ids = changes.into_iter().map(|c| c.id()).collect::<Vec<Uuid>>();
 
-    assert!(ids.contains(&response_changes[0].id()));
+    assert!(ids.contains(&response.changes[0].id()));
+}
+
+#[tokio::test]
+async fn list_changes_filtered_and_paginated() {
+    let db = TestDb::new().await;
+    let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+    let (room, edition) = {
+        let mut conn = db.get_conn().await;
+        let room = shared_helpers::insert_room(&mut conn).await;
+
+        let edition = shared_helpers::insert_edition(&mut conn, &room, agent.agent_id()).await;
+
+        for idx in 0..5 {
+            factory::Change::new(edition.id(), ChangeType::Addition)
+                .event_data(json!({ "text": format!("message {}", idx) }))
+                .event_kind("message")
+                .event_set("messages")
+                .event_label(&format!("message-{}", idx))
+                .event_occurred_at(idx * 1000)
+                .event_created_by(agent.agent_id())
+                .insert(&mut conn)
+                .await;
+        }
+
+        for idx in 0..3 {
+            factory::Change::new(edition.id(), ChangeType::Addition)
+                .event_data(json!({ "stroke": idx }))
+                .event_kind("draw")
+                .event_set("draw")
+                .event_label(&format!("draw-{}", idx))
+                .event_occurred_at(idx * 1000)
+                .event_created_by(agent.agent_id())
+                .insert(&mut conn)
+                .await;
+        }
+
+        for _ in 0..2 {
+            let event = factory::Event::new()
+                .room_id(room.id())
+                .kind("message")
+                .data(&json!({ "text": "removed" }))
+                .occurred_at(1000)
+                .created_by(agent.agent_id())
+                .insert(&mut conn)
+                .await;
+
+            factory::Change::new(edition.id(), ChangeType::Removal)
+                .event_id(event.id())
+                .insert(&mut conn)
+                .await;
+        }
+
+        (room, edition)
+    };
+
+    let mut authz = TestAuthz::new();
+    authz.allow(
+        agent.account_id(),
+        vec!["classrooms", &room.classroom_id().to_string()],
+        "update",
+    );
+
+    let mut context = TestContext::new(db, authz);
+
+    // Walk through additions of messages page by page.
+    let mut seen = vec![];
+    let mut cursor = None;
+
+    loop {
+        let payload = ListRequest {
+            id: edition.id(),
+            payload: ListPayload {
+                cursor,
+                kind: Some(ChangeType::Addition),
+                event_type: Some(String::from("message")),
+                limit: Some(2),
+                ..Default::default()
+            },
+        };
+
+        let messages = handle_request::<ListHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Failed to list changes");
+
+        let (response, _, _) = find_response::<ListResponse>(messages.as_slice());
+        assert_eq!(response.total, 5);
+        assert_eq!(response.counts.addition, 5);
+        assert_eq!(response.counts.removal, 0);
+
+        for change in &response.changes {
+            assert_eq!(change.kind(), ChangeType::Addition);
+            assert_eq!(change.set().map(|s| s.as_str()), Some("messages"));
+            seen.push(change.id());
+        }
+
+        match response.next_cursor {
+            Some(next_cursor) => cursor = Some(next_cursor),
+            None => break,
+        }
+    }
+
+    seen.dedup();
+    assert_eq!(seen.len(), 5);
+
+    // Counts by type without the event kind filter.
+    let payload = ListRequest {
+        id: edition.id(),
+        payload: ListPayload {
+            kind: Some(ChangeType::Removal),
+            ..Default::default()
+        },
+    };
+
+    let messages = handle_request::<ListHandler>(&mut context, &agent, payload)
+        .await
+        .expect("Failed to list changes");
+
+    let (response, _, _) = find_response::<ListResponse>(messages.as_slice());
+    assert_eq!(response.changes.len(), 2);
+    assert_eq!(response.total, 2);
+    assert_eq!(response.next_cursor, None);
+
+    assert_eq!(
+        response.counts,
+        Counts {
+            addition: 8,
+            modification: 0,
+            removal: 2,
+            bulk_removal: 0,
+        }
+    );
 }
 
 #[tokio::test]
@@ -110,10 +242,7 @@ async fn list_changes_not_authorized() {
 
     let payload = ListRequest {
         id: edition.id(),
-        payload: ListPayload {
-            last_created_at: None,
-            limit: None,
-        },
+        payload: ListPayload::default(),
     };
 
     let resp = handle_request::<ListHandler>(&mut context, &agent, payload)
@@ -130,10 +259,7 @@ async fn list_changes_missing_edition() {
 
     let payload = ListRequest {
         id: Uuid::new_v4(),
-        payload: ListPayload {
-            last_created_at: None,
-            limit: None,
-        },
+        payload: ListPayload::default(),
     };
 
     let err = handle_request::<ListHandler>(&mut context, &agent, payload)
@@ -2,22 +2,27 @@ use anyhow::Context as AnyhowContext;
 use async_trait::async_trait;
 use axum::extract::{self, Json, Path};
 use chrono::Utc;
+use futures::channel::mpsc;
 use serde_derive::{Deserialize, Serialize};
 use serde_json::{json, Value as JsonValue};
-use svc_agent::mqtt::{
-    OutgoingEvent, OutgoingEventProperties, ResponseStatus, ShortTermTimingProperties,
+use sqlx::Acquire;
+use svc_agent::{
+    mqtt::{OutgoingEvent, OutgoingEventProperties, ResponseStatus, ShortTermTimingProperties},
+    Addressable, AgentId,
 };
 use svc_authn::Authenticable;
-use svc_error::Error as SvcError;
 use svc_utils::extractors::AgentIdExtractor;
-use tracing::{error, field::display, instrument, Span};
+use tracing::{error, field::display, instrument, warn, Instrument, Span};
 use uuid::Uuid;
 
+use crate::app::context::{Context, GlobalContext};
 use crate::app::endpoint::prelude::*;
-use crate::app::operations::commit_edition;
-use crate::app::{context::Context, message_handler::Message};
+use crate::app::message_handler::{Message, MessageStream};
+use crate::app::operations::{commit_edition, RoomOperationLock};
+use crate::app::webhook_client::Webhook;
 use crate::db;
 use crate::db::adjustment::Segments;
+use crate::db::change::{ChangeType, MergeCandidate};
 
 pub struct CommitHandler;
 
@@ -25,6 +30,35 @@ pub struct CommitHandler;
 pub struct CommitPayload {
     #[serde(default)]
     pub offset: i64,
+    /// Editions of the same room to apply after this one in order.
+    #[serde(default)]
+    pub editions: Vec<Uuid>,
+}
+
+/// Changes of merged editions targeting the same event.
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub struct MergeConflict {
+    pub event_id: Uuid,
+    pub applied: MergedChange,
+    pub discarded: Vec<MergedChange>,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub struct MergedChange {
+    pub edition_id: Uuid,
+    pub change_id: Uuid,
+    #[serde(rename = "type")]
+    pub kind: ChangeType,
+}
+
+impl From<MergeCandidate> for MergedChange {
+    fn from(candidate: MergeCandidate) -> Self {
+        Self {
+            edition_id: candidate.edition_id,
+            change_id: candidate.change_id,
+            kind: candidate.kind,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -60,7 +94,7 @@ impl RequestHandler for CommitHandler {
         context: &mut C,
         CommitRequest {
             id,
-            payload: CommitPayload { offset },
+            payload: CommitPayload { offset, editions },
         }: Self::Payload,
         reqp: RequestParams<'_>,
     ) -> RequestResult {
@@ -93,21 +127,240 @@ impl RequestHandler for CommitHandler {
 
         let authz_time = context
             .authz()
-            .authorize(
-                room.audience().into(),
+            .authorize_room(
+                &room,
                 reqp.as_account_id().to_owned(),
                 object,
                 "update".into(),
             )
             .await?;
 
-        // Run commit task asynchronously.
-        let db = context.db().to_owned();
-        let metrics = context.metrics();
-        let cfg = context.config().to_owned();
+        // Only one operation deriving rooms may run on the source room at a time.
+        let lock = RoomOperationLock::try_acquire(context.background_db(), room.id())
+            .await
+            .context("Failed to lock room")
+            .error(AppErrorKind::DbQueryFailed)?
+            .ok_or_else(|| anyhow!("Another operation on the room is in progress"))
+            .error(AppErrorKind::OperationInProgress)?;
+
+        // Merge editions into a new one to commit them at once.
+        let (edition, conflicts) = if editions.is_empty() {
+            (edition, None)
+        } else {
+            let (merged, conflicts) =
+                merge_editions(context, &edition, &editions, reqp.as_agent_id()).await?;
+
+            (merged, Some(conflicts))
+        };
+
+        // Persist the job first so it could be resumed if this replica dies.
+        let job = {
+            let query = db::edition_commit_job::InsertQuery::new(edition.id(), offset);
+            let mut conn = context.get_conn().await?;
+
+            context
+                .metrics()
+                .measure_query(
+                    QueryKey::EditionCommitJobInsertQuery,
+                    query.execute(&mut conn),
+                )
+                .await
+                .context("Failed to insert edition commit job")
+                .error(AppErrorKind::DbQueryFailed)?
+        };
+
+        let payload = match conflicts {
+            Some(conflicts) => json!({
+                "job_id": job.id(),
+                "edition_id": edition.id(),
+                "conflicts": conflicts,
+            }),
+            None => json!({ "job_id": job.id() }),
+        };
+
+        let span = helpers::operation_span("edition_commit", &room, Some(reqp.as_agent_id()));
+        let notifications = start_commit(&*context, job, edition, room, lock, span);
+
+        // Respond with 202.
+        // Progress and the actual task result will be broadcasted to the audience topic.
+        let mut response = AppResponse::new(
+            ResponseStatus::ACCEPTED,
+            payload,
+            context.start_timestamp(),
+            Some(authz_time),
+        );
+
+        response.add_async_stream(notifications);
+
+        Ok(response)
+    }
+}
+
+/// Copies changes of `edition` followed by `editions` into a new edition.
+/// Of the changes targeting the same event the one of the latest edition wins.
+async fn merge_editions<C: Context>(
+    context: &mut C,
+    edition: &db::edition::Object,
+    editions: &[Uuid],
+    agent_id: &AgentId,
+) -> Result<(db::edition::Object, Vec<MergeConflict>), AppError> {
+    let mut edition_ids = vec![edition.id()];
+
+    for id in editions {
+        if edition_ids.contains(id) {
+            return Err(anyhow!("Edition = '{}' is listed more than once", id))
+                .error(AppErrorKind::InvalidPayload);
+        }
+
+        let query = db::edition::FindWithRoomQuery::new(*id);
+        let mut conn = context.get_ro_conn().await?;
+
+        let maybe_edition = context
+            .metrics()
+            .measure_query(QueryKey::EditionFindWithRoomQuery, query.execute(&mut conn))
+            .await
+            .context("Failed to find edition with room")
+            .error(AppErrorKind::DbQueryFailed)?;
+
+        match maybe_edition {
+            Some((other, _)) if other.source_room_id() == edition.source_room_id() => {
+                edition_ids.push(other.id());
+            }
+            Some(_) => {
+                return Err(anyhow!(
+                    "Edition = '{}' belongs to another room than edition = '{}'",
+                    id,
+                    edition.id()
+                ))
+                .error(AppErrorKind::InvalidPayload);
+            }
+            None => {
+                return Err(anyhow!("Edition = '{}' not found", id))
+                    .error(AppErrorKind::EditionNotFound);
+            }
+        }
+    }
+
+    let mut conn = context.get_conn().await?;
+
+    let mut txn = conn
+        .begin()
+        .await
+        .context("Failed to acquire transaction")
+        .error(AppErrorKind::DbQueryFailed)?;
 
-        let notification_future = tokio::task::spawn(async move {
-            let result = commit_edition(&db, &metrics, &edition, &room, offset, cfg.adjust).await;
+    let merged = {
+        let query = db::edition::InsertQuery::new(edition.source_room_id(), agent_id);
+
+        context
+            .metrics()
+            .measure_query(QueryKey::EditionInsertQuery, query.execute(&mut txn))
+            .await
+            .context("Failed to insert edition")
+            .error(AppErrorKind::DbQueryFailed)?
+    };
+
+    let candidates = {
+        let query = db::change::MergeConflictListQuery::new(&edition_ids);
+
+        context
+            .metrics()
+            .measure_query(
+                QueryKey::ChangeMergeConflictListQuery,
+                query.execute(&mut txn),
+            )
+            .await
+            .context("Failed to list merge conflicts")
+            .error(AppErrorKind::DbQueryFailed)?
+    };
+
+    {
+        let query = db::change::MergeQuery::new(&edition_ids, merged.id());
+
+        context
+            .metrics()
+            .measure_query(QueryKey::ChangeMergeQuery, query.execute(&mut txn))
+            .await
+            .context("Failed to merge changes")
+            .error(AppErrorKind::DbQueryFailed)?;
+    }
+
+    context
+        .metrics()
+        .measure_query(QueryKey::EditionMergeTxnCommit, txn.commit())
+        .await
+        .context("Failed to commit transaction")
+        .error(AppErrorKind::DbQueryFailed)?;
+
+    Ok((merged, collect_conflicts(candidates)))
+}
+
+/// Groups merge candidates by event. They come in precedence order so the first
+/// candidate of each event is the one applied.
+fn collect_conflicts(candidates: Vec<MergeCandidate>) -> Vec<MergeConflict> {
+    let mut conflicts: Vec<MergeConflict> = vec![];
+
+    for candidate in candidates {
+        match conflicts.last_mut() {
+            Some(conflict) if conflict.event_id == candidate.event_id => {
+                conflict.discarded.push(candidate.into());
+            }
+            _ => conflicts.push(MergeConflict {
+                event_id: candidate.event_id,
+                applied: candidate.into(),
+                discarded: vec![],
+            }),
+        }
+    }
+
+    conflicts
+}
+
+/// Runs the commit job in background returning the stream of its notifications.
+/// The room `lock` is released when the job finishes. The job runs within `span`.
+pub(crate) fn start_commit<C: GlobalContext>(
+    context: &C,
+    job: db::edition_commit_job::Object,
+    edition: db::edition::Object,
+    room: db::room::Object,
+    lock: RoomOperationLock,
+    span: Span,
+) -> MessageStream {
+    let db = context.background_db().to_owned();
+    let metrics = context.metrics();
+    let cfg = context.config().adjust_for(room.kind());
+    let jobs = context.jobs();
+    let webhook_client = context.webhook_client();
+    let path = context.config().audience_topic(room.audience());
+    let (tx, rx) = mpsc::unbounded::<Message>();
+
+    tokio::task::spawn(
+        async move {
+            let progress = |job: &db::edition_commit_job::Object| {
+                let notification = EditionCommitProgressNotification {
+                    job_id: job.id(),
+                    source_room_id: edition.source_room_id(),
+                    committed_room_id: job.destination_room_id(),
+                    cloned_events: job.cloned_events(),
+                    tags: room.tags().map(|t| t.to_owned()),
+                };
+
+                let timing = ShortTermTimingProperties::new(Utc::now());
+                let props = OutgoingEventProperties::new("edition.commit.progress", timing);
+                let event = OutgoingEvent::broadcast(notification, props, &path);
+
+                // The receiver is gone only when nobody is interested in notifications anymore.
+                let _ = tx.unbounded_send(Box::new(event) as Message);
+            };
+
+            let registry_job_id = jobs.start("edition_commit", Some(room.id()));
+            let result = commit_edition(&db, &metrics, &edition, &room, job, cfg, progress).await;
+
+            if let Err(err) = lock.release().await {
+                warn!("Failed to release room lock: {:?}", err);
+            }
+
+            jobs.finish(registry_job_id, &result);
 
             // Handle result.
             let result = match result {
@@ -117,11 +370,11 @@ impl RequestHandler for CommitHandler {
                     modified_segments,
                 },
                 Err(err) => {
-                    error!("Room adjustment job failed: {:?}", err);
+                    error!("Edition commit job failed: {:?}", err);
                     let app_error = AppError::new(AppErrorKind::EditionCommitTaskFailed, err);
                     app_error.notify_sentry();
                     EditionCommitResult::Error {
-                        error: app_error.to_svc_error(),
+                        error: app_error.to_payload(),
                     }
                 }
             };
@@ -133,27 +386,32 @@ impl RequestHandler for CommitHandler {
                 result,
             };
 
+            webhook_client.send(
+                room.audience(),
+                Webhook::new("edition.commit", &notification),
+            );
+
             let timing = ShortTermTimingProperties::new(Utc::now());
             let props = OutgoingEventProperties::new("edition.commit", timing);
-            let path = format!("audiences/{}/events", room.audience());
             let event = OutgoingEvent::broadcast(notification, props, &path);
 
-            Box::new(event) as Message
-        });
-
-        // Respond with 202.
-        // The actual task result will be broadcasted to the room topic when finished.
-        let mut response = AppResponse::new(
-            ResponseStatus::ACCEPTED,
-            json!({}),
-            context.start_timestamp(),
-            Some(authz_time),
-        );
+            let _ = tx.unbounded_send(Box::new(event) as Message);
+        }
+        .instrument(span),
+    );
 
-        response.add_async_task(notification_future);
+    Box::new(rx)
+}
 
-        Ok(response)
-    }
+#[derive(Serialize, Deserialize)]
+pub struct EditionCommitProgressNotification {
+    pub job_id: Uuid,
+    pub source_room_id: Uuid,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub committed_room_id: Option<Uuid>,
+    pub cloned_events: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<JsonValue>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -176,8 +434,8 @@ pub enum EditionCommitResult {
     },
     Error {
         // хак для того что-бы добавить Deserialize, нужно для тестов
-        #[serde(skip_deserializing, default = "default_svc_error")]
-        error: SvcError,
+        #[serde(skip_deserializing, default = "default_error_payload")]
+        error: ErrorPayload,
     },
 }
 
@@ -190,6 +448,10 @@ impl EditionCommitResult {
     }
 }
 
-fn default_svc_error() -> SvcError {
-    SvcError::builder().build()
+fn default_error_payload() -> ErrorPayload {
+    AppError::new(
+        AppErrorKind::EditionCommitTaskFailed,
+        anyhow!("Unknown error"),
+    )
+    .to_payload()
 }
@@ -62,8 +62,8 @@ impl RequestHandler for CreateHandler {
 
         let authz_time = context
             .authz()
-            .authorize(
-                room.audience().to_owned(),
+            .authorize_room(
+                &room,
                 reqp.as_account_id().to_owned(),
                 object,
                 "update".into(),
@@ -96,7 +96,7 @@ impl RequestHandler for CreateHandler {
             &format!("rooms/{}/editions", payload.room_id),
             edition,
             context.start_timestamp(),
-        );
+        )?;
 
         Ok(response)
     }
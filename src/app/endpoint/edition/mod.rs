@@ -6,6 +6,8 @@ mod delete;
 pub use self::delete::*;
 mod commit;
 pub use self::commit::*;
+mod summary;
+pub use self::summary::*;
 
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,130 @@
+use anyhow::Context as AnyhowContext;
+use async_trait::async_trait;
+use axum::extract::{self, Path};
+use serde_derive::{Deserialize, Serialize};
+use svc_agent::mqtt::ResponseStatus;
+use svc_authn::Authenticable;
+use svc_utils::extractors::AgentIdExtractor;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::app::context::Context;
+use crate::app::endpoint::prelude::*;
+use crate::db;
+use crate::db::change::SummaryGroup;
+
+pub struct SummaryHandler;
+
+#[derive(Debug, Deserialize)]
+pub struct SummaryRequest {
+    pub id: Uuid,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SummaryResponse {
+    pub edition_id: Uuid,
+    pub total: i64,
+    pub groups: Vec<SummaryGroup>,
+    /// Range of `occurred_at` of the events affected by the changes.
+    pub time_range: Option<(i64, i64)>,
+}
+
+impl SummaryResponse {
+    fn new(edition_id: Uuid, groups: Vec<SummaryGroup>) -> Self {
+        let started_at = groups.iter().filter_map(|g| g.started_at).min();
+        let finished_at = groups.iter().filter_map(|g| g.finished_at).max();
+
+        Self {
+            edition_id,
+            total: groups.iter().map(|g| g.count).sum(),
+            time_range: started_at.zip(finished_at),
+            groups,
+        }
+    }
+}
+
+pub async fn summary(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(id): Path<Uuid>,
+) -> RequestResult {
+    let request = SummaryRequest { id };
+    SummaryHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+#[async_trait]
+impl RequestHandler for SummaryHandler {
+    type Payload = SummaryRequest;
+
+    #[instrument(
+        skip_all,
+        fields(
+            edition_id = %payload.id,
+            room_id, scope, classroom_id
+        )
+    )]
+    async fn handle<C: Context>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let (edition, room) = {
+            let query = db::edition::FindWithRoomQuery::new(payload.id);
+            let mut conn = context.get_ro_conn().await?;
+
+            let maybe_edition = context
+                .metrics()
+                .measure_query(QueryKey::EditionFindWithRoomQuery, query.execute(&mut conn))
+                .await
+                .context("Failed to find edition with room")
+                .error(AppErrorKind::DbQueryFailed)?;
+
+            match maybe_edition {
+                Some(edition_with_room) => edition_with_room,
+                None => {
+                    return Err(anyhow!("Edition not found")).error(AppErrorKind::EditionNotFound);
+                }
+            }
+        };
+
+        helpers::add_room_logger_tags(&room);
+
+        let object = AuthzObject::room(&room).into();
+
+        let authz_time = context
+            .authz()
+            .authorize_room(
+                &room,
+                reqp.as_account_id().to_owned(),
+                object,
+                "update".into(),
+            )
+            .await?;
+
+        let groups = {
+            let query = db::change::SummaryQuery::new(edition.id());
+            let mut conn = context.get_ro_conn().await?;
+
+            context
+                .metrics()
+                .measure_query(QueryKey::ChangeSummaryQuery, query.execute(&mut conn))
+                .await
+                .context("Failed to summarize changes")
+                .error(AppErrorKind::DbQueryFailed)?
+        };
+
+        Ok(AppResponse::new(
+            ResponseStatus::OK,
+            SummaryResponse::new(edition.id(), groups),
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
@@ -7,6 +7,7 @@ use crate::db::{
     change::{ChangeType, Object as Change},
     event,
 };
+use crate::test_helpers::outgoing_envelope::OutgoingEnvelope;
 use crate::test_helpers::prelude::*;
 
 use super::super::*;
@@ -77,7 +78,10 @@ async fn addition() {
 
     let payload = CommitRequest {
         id: edition.id(),
-        payload: CommitPayload { offset: 0 },
+        payload: CommitPayload {
+            offset: 0,
+            editions: vec![],
+        },
     };
 
     let messages = handle_request::<CommitHandler>(&mut context, &agent, payload.clone())
@@ -88,7 +92,10 @@ async fn addition() {
     let (_, respp, _) = find_response::<serde_json::Value>(messages.as_slice());
     assert_eq!(respp.status(), ResponseStatus::ACCEPTED);
 
-    let (commit_notification, _, _) = find_event::<EditionCommitNotification>(messages.as_slice());
+    let (progress, _, _) = find_event::<EditionCommitProgressNotification>(messages.as_slice());
+    assert_eq!(progress.source_room_id, room.id());
+
+    let commit_notification = find_commit_notification(messages.as_slice());
     let new_room_id = match commit_notification.result {
         EditionCommitResult::Error { .. } => panic!("error in edition commit notification"),
         EditionCommitResult::Success {
@@ -193,7 +200,10 @@ async fn modification() {
 
     let payload = CommitRequest {
         id: edition.id(),
-        payload: CommitPayload { offset: 0 },
+        payload: CommitPayload {
+            offset: 0,
+            editions: vec![],
+        },
     };
 
     let messages = handle_request::<CommitHandler>(&mut context, &agent, payload.clone())
@@ -204,7 +214,7 @@ async fn modification() {
     let (_, respp, _) = find_response::<serde_json::Value>(messages.as_slice());
     assert_eq!(respp.status(), ResponseStatus::ACCEPTED);
 
-    let (commit_notification, _, _) = find_event::<EditionCommitNotification>(messages.as_slice());
+    let commit_notification = find_commit_notification(messages.as_slice());
     let new_room_id = match commit_notification.result {
         EditionCommitResult::Error { .. } => panic!("error in edition commit notification"),
         EditionCommitResult::Success {
@@ -294,7 +304,10 @@ async fn removal() {
 
     let payload = CommitRequest {
         id: edition.id(),
-        payload: CommitPayload { offset: 0 },
+        payload: CommitPayload {
+            offset: 0,
+            editions: vec![],
+        },
     };
 
     let messages = handle_request::<CommitHandler>(&mut context, &agent, payload.clone())
@@ -305,7 +318,7 @@ async fn removal() {
     let (_, respp, _) = find_response::<serde_json::Value>(messages.as_slice());
     assert_eq!(respp.status(), ResponseStatus::ACCEPTED);
 
-    let (commit_notification, _, _) = find_event::<EditionCommitNotification>(messages.as_slice());
+    let commit_notification = find_commit_notification(messages.as_slice());
     let new_room_id = match commit_notification.result {
         EditionCommitResult::Error { .. } => panic!("error in edition commit notification"),
         EditionCommitResult::Success {
@@ -401,7 +414,10 @@ async fn bulk_removal() {
 
     let payload = CommitRequest {
         id: edition.id(),
-        payload: CommitPayload { offset: 0 },
+        payload: CommitPayload {
+            offset: 0,
+            editions: vec![],
+        },
     };
 
     let messages = handle_request::<CommitHandler>(&mut context, &agent, payload.clone())
@@ -412,7 +428,7 @@ async fn bulk_removal() {
     let (_, respp, _) = find_response::<serde_json::Value>(messages.as_slice());
     assert_eq!(respp.status(), ResponseStatus::ACCEPTED);
 
-    let (commit_notification, _, _) = find_event::<EditionCommitNotification>(messages.as_slice());
+    let commit_notification = find_commit_notification(messages.as_slice());
     let new_room_id = match commit_notification.result {
         EditionCommitResult::Error { .. } => panic!("error in edition commit notification"),
         EditionCommitResult::Success {
@@ -433,3 +449,195 @@ async fn bulk_removal() {
         assert_eq!(ev.kind(), "message");
     }
 }
+
+#[tokio::test]
+async fn merge() {
+    let db = TestDb::new().await;
+    let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+    let (room, first, second, events) = {
+        let mut conn = db.get_conn().await;
+        let room = shared_helpers::insert_room(&mut conn).await;
+
+        let first = shared_helpers::insert_edition(&mut conn, &room, agent.agent_id()).await;
+        let second = shared_helpers::insert_edition(&mut conn, &room, agent.agent_id()).await;
+
+        let mut events = vec![];
+
+        for i in 0..3 {
+            let event = factory::Event::new()
+                .room_id(room.id())
+                .set("set1")
+                .kind("message")
+                .data(&json!({ "text": format!("message {}", i) }))
+                .occurred_at(i * 1000)
+                .created_by(agent.agent_id())
+                .insert(&mut conn)
+                .await;
+
+            events.push(event);
+        }
+
+        factory::Change::new(first.id(), ChangeType::Modification)
+            .event_id(events[0].id())
+            .event_data(json!({ "text": "first" }))
+            .insert(&mut conn)
+            .await;
+
+        factory::Change::new(first.id(), ChangeType::Removal)
+            .event_id(events[1].id())
+            .insert(&mut conn)
+            .await;
+
+        factory::Change::new(first.id(), ChangeType::Addition)
+            .event_kind("message")
+            .event_set("set1")
+            .event_data(json!({ "text": "added" }))
+            .event_occurred_at(5000)
+            .event_created_by(agent.agent_id())
+            .insert(&mut conn)
+            .await;
+
+        factory::Change::new(second.id(), ChangeType::Modification)
+            .event_id(events[0].id())
+            .event_data(json!({ "text": "second" }))
+            .insert(&mut conn)
+            .await;
+
+        factory::Change::new(second.id(), ChangeType::Modification)
+            .event_id(events[1].id())
+            .event_data(json!({ "text": "second" }))
+            .insert(&mut conn)
+            .await;
+
+        (room, first, second, events)
+    };
+
+    let mut authz = TestAuthz::new();
+    authz.allow(
+        agent.account_id(),
+        vec!["classrooms", &room.classroom_id().to_string()],
+        "update",
+    );
+
+    let mut context = TestContext::new(db.clone(), authz);
+
+    let payload = CommitRequest {
+        id: first.id(),
+        payload: CommitPayload {
+            offset: 0,
+            editions: vec![second.id()],
+        },
+    };
+
+    let messages = handle_request::<CommitHandler>(&mut context, &agent, payload)
+        .await
+        .expect("Failed to commit editions");
+
+    // Assert conflicts are resolved in favor of the latter edition.
+    let (resp, respp, _) = find_response::<serde_json::Value>(messages.as_slice());
+    assert_eq!(respp.status(), ResponseStatus::ACCEPTED);
+
+    let merged_edition_id = resp["edition_id"].as_str().expect("Missing edition_id");
+    assert_ne!(merged_edition_id, first.id().to_string());
+
+    let mut conflicts = serde_json::from_value::<Vec<MergeConflict>>(resp["conflicts"].clone())
+        .expect("Failed to parse conflicts");
+
+    conflicts.sort_by_key(|c| events.iter().position(|e| e.id() == c.event_id));
+    assert_eq!(conflicts.len(), 2);
+
+    for (conflict, discarded_kind) in conflicts
+        .iter()
+        .zip([ChangeType::Modification, ChangeType::Removal])
+    {
+        assert_eq!(conflict.applied.edition_id, second.id());
+        assert_eq!(conflict.applied.kind, ChangeType::Modification);
+        assert_eq!(conflict.discarded.len(), 1);
+        assert_eq!(conflict.discarded[0].edition_id, first.id());
+        assert_eq!(conflict.discarded[0].kind, discarded_kind);
+    }
+
+    // Assert the committed room.
+    let commit_notification = find_commit_notification(messages.as_slice());
+    let new_room_id = match commit_notification.result {
+        EditionCommitResult::Error { .. } => panic!("error in edition commit notification"),
+        EditionCommitResult::Success {
+            committed_room_id, ..
+        } => committed_room_id,
+    };
+
+    let mut conn = db.get_conn().await;
+    let committed_events = event::ListQuery::new()
+        .room_id(new_room_id)
+        .execute(&mut conn)
+        .await
+        .expect("Failed to fetch events");
+
+    let texts = committed_events
+        .iter()
+        .map(|ev| {
+            (
+                ev.occurred_at(),
+                ev.data()["text"].as_str().unwrap().to_owned(),
+            )
+        })
+        .collect::<HashMap<_, _>>();
+
+    assert_eq!(texts.len(), 4);
+    assert_eq!(texts[&0], "second");
+    assert_eq!(texts[&1000], "second");
+    assert_eq!(texts[&2000], "message 2");
+    assert_eq!(texts[&5000], "added");
+}
+
+#[tokio::test]
+async fn merge_edition_of_another_room() {
+    let db = TestDb::new().await;
+    let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+    let (room, edition, foreign_edition) = {
+        let mut conn = db.get_conn().await;
+        let room = shared_helpers::insert_room(&mut conn).await;
+        let other_room = shared_helpers::insert_room(&mut conn).await;
+        let edition = shared_helpers::insert_edition(&mut conn, &room, agent.agent_id()).await;
+
+        let foreign_edition =
+            shared_helpers::insert_edition(&mut conn, &other_room, agent.agent_id()).await;
+
+        (room, edition, foreign_edition)
+    };
+
+    let mut authz = TestAuthz::new();
+    authz.allow(
+        agent.account_id(),
+        vec!["classrooms", &room.classroom_id().to_string()],
+        "update",
+    );
+
+    let mut context = TestContext::new(db, authz);
+
+    let payload = CommitRequest {
+        id: edition.id(),
+        payload: CommitPayload {
+            offset: 0,
+            editions: vec![foreign_edition.id()],
+        },
+    };
+
+    let err = handle_request::<CommitHandler>(&mut context, &agent, payload)
+        .await
+        .expect_err("Unexpected success merging an edition of another room");
+
+    assert_eq!(err.kind(), "invalid_payload");
+}
+
+fn find_commit_notification(messages: &[OutgoingEnvelope]) -> EditionCommitNotification {
+    let (notification, _, _) =
+        find_event_by_predicate::<EditionCommitNotification, _>(messages, |evp| {
+            evp.label() == "edition.commit"
+        })
+        .expect("Failed to find edition.commit event");
+
+    notification
+}
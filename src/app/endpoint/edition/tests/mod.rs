@@ -6,3 +6,4 @@ mod delete;
 pub use self::delete::*;
 mod commit;
 pub use self::commit::*;
+mod summary;
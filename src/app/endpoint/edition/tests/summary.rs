@@ -0,0 +1,168 @@
+use serde_json::json;
+use svc_agent::mqtt::ResponseStatus;
+use uuid::Uuid;
+
+use super::super::*;
+use crate::db::change::ChangeType;
+use crate::test_helpers::prelude::*;
+
+#[tokio::test]
+async fn summarize_edition() {
+    let db = TestDb::new().await;
+    let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+    let (room, edition) = {
+        let mut conn = db.get_conn().await;
+        let room = shared_helpers::insert_room(&mut conn).await;
+        let edition = shared_helpers::insert_edition(&mut conn, &room, agent.agent_id()).await;
+
+        let message = factory::Event::new()
+            .room_id(room.id())
+            .kind("message")
+            .set("messages")
+            .label("message-1")
+            .data(&json!({ "text": "hello" }))
+            .occurred_at(1000)
+            .created_by(agent.agent_id())
+            .insert(&mut conn)
+            .await;
+
+        let cut = factory::Event::new()
+            .room_id(room.id())
+            .kind("stream")
+            .set("stream")
+            .label("stream-1")
+            .data(&json!({ "cut": "start" }))
+            .occurred_at(5000)
+            .created_by(agent.agent_id())
+            .insert(&mut conn)
+            .await;
+
+        for occurred_at in [3000, 7000] {
+            factory::Change::new(edition.id(), ChangeType::Addition)
+                .event_kind("message")
+                .event_set("messages")
+                .event_label(&format!("message-{}", occurred_at))
+                .event_data(json!({ "text": "added" }))
+                .event_occurred_at(occurred_at)
+                .event_created_by(agent.agent_id())
+                .insert(&mut conn)
+                .await;
+        }
+
+        factory::Change::new(edition.id(), ChangeType::Modification)
+            .event_id(message.id())
+            .event_occurred_at(2000)
+            .insert(&mut conn)
+            .await;
+
+        factory::Change::new(edition.id(), ChangeType::Removal)
+            .event_id(cut.id())
+            .insert(&mut conn)
+            .await;
+
+        (room, edition)
+    };
+
+    let mut authz = TestAuthz::new();
+    authz.allow(
+        agent.account_id(),
+        vec!["classrooms", &room.classroom_id().to_string()],
+        "update",
+    );
+
+    let mut context = TestContext::new(db, authz);
+    let payload = SummaryRequest { id: edition.id() };
+
+    let messages = handle_request::<SummaryHandler>(&mut context, &agent, payload)
+        .await
+        .expect("Failed to summarize edition");
+
+    let (summary, respp, _) = find_response::<SummaryResponse>(messages.as_slice());
+    assert_eq!(respp.status(), ResponseStatus::OK);
+    assert_eq!(summary.edition_id, edition.id());
+    assert_eq!(summary.total, 4);
+    assert_eq!(summary.time_range, Some((1000, 7000)));
+
+    let groups = summary
+        .groups
+        .iter()
+        .map(|g| (g.kind, g.event_kind.as_deref(), g.count))
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        groups,
+        vec![
+            (ChangeType::Addition, Some("message"), 2),
+            (ChangeType::Modification, Some("message"), 1),
+            (ChangeType::Removal, Some("stream"), 1),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn summarize_empty_edition() {
+    let db = TestDb::new().await;
+    let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+    let (room, edition) = {
+        let mut conn = db.get_conn().await;
+        let room = shared_helpers::insert_room(&mut conn).await;
+        let edition = shared_helpers::insert_edition(&mut conn, &room, agent.agent_id()).await;
+        (room, edition)
+    };
+
+    let mut authz = TestAuthz::new();
+    authz.allow(
+        agent.account_id(),
+        vec!["classrooms", &room.classroom_id().to_string()],
+        "update",
+    );
+
+    let mut context = TestContext::new(db, authz);
+    let payload = SummaryRequest { id: edition.id() };
+
+    let messages = handle_request::<SummaryHandler>(&mut context, &agent, payload)
+        .await
+        .expect("Failed to summarize edition");
+
+    let (summary, _, _) = find_response::<SummaryResponse>(messages.as_slice());
+    assert_eq!(summary.total, 0);
+    assert!(summary.groups.is_empty());
+    assert_eq!(summary.time_range, None);
+}
+
+#[tokio::test]
+async fn summarize_edition_not_authorized() {
+    let db = TestDb::new().await;
+    let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+    let edition = {
+        let mut conn = db.get_conn().await;
+        let room = shared_helpers::insert_room(&mut conn).await;
+        shared_helpers::insert_edition(&mut conn, &room, agent.agent_id()).await
+    };
+
+    let mut context = TestContext::new(db, TestAuthz::new());
+    let payload = SummaryRequest { id: edition.id() };
+
+    let resp = handle_request::<SummaryHandler>(&mut context, &agent, payload)
+        .await
+        .expect_err("Unexpected success without authorization on edition summary");
+
+    assert_eq!(resp.status(), ResponseStatus::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn summarize_missing_edition() {
+    let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+    let mut context = TestContext::new(TestDb::new().await, TestAuthz::new());
+    let payload = SummaryRequest { id: Uuid::new_v4() };
+
+    let err = handle_request::<SummaryHandler>(&mut context, &agent, payload)
+        .await
+        .expect_err("Unexpected success summarizing missing edition");
+
+    assert_eq!(err.status(), ResponseStatus::NOT_FOUND);
+    assert_eq!(err.kind(), "edition_not_found");
+}
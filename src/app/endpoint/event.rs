@@ -4,30 +4,40 @@ use anyhow::Context as AnyhowContext;
 use async_trait::async_trait;
 use axum::{
     extract::{self, Path, Query},
+    http::HeaderMap,
     Json,
 };
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use serde_derive::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
-use svc_agent::Authenticable;
+use sqlx::Acquire;
 use svc_agent::{mqtt::ResponseStatus, Addressable};
+use svc_agent::{AgentId, Authenticable};
 use svc_utils::extractors::AgentIdExtractor;
 use tracing::{field::display, instrument, Span};
+use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
+use crate::app::content_filter::{Verdict, FLAGGED_ATTRIBUTE};
+use crate::app::context::GlobalContext;
 use crate::app::endpoint::prelude::*;
+use crate::app::share_token::{ShareToken, ShareTokenExtractor};
+use crate::config::{AttachmentsConfig, Constraint};
 use crate::db;
-use crate::db::event::Object as Event;
+use crate::db::event::{EventKind, Object as Event};
 
 ///////////////////////////////////////////////////////////////////////////////
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, ToSchema)]
 pub struct CreatePayload {
     #[serde(rename = "type")]
     pub kind: String,
     pub set: Option<String>,
     pub label: Option<String>,
     pub attribute: Option<String>,
+    #[serde(default)]
+    pub attributes: Vec<String>,
+    #[schema(value_type = Object)]
     pub data: JsonValue,
     #[serde(default = "CreateRequest::default_is_claim")]
     pub is_claim: bool,
@@ -35,6 +45,17 @@ pub struct CreatePayload {
     pub is_persistent: bool,
     #[serde(default)]
     pub removed: bool,
+    /// `data` is a ciphertext string encrypted by clients. It's passed through as is.
+    #[serde(default)]
+    pub content_encrypted: bool,
+    /// Identifier of the key `data` is encrypted with.
+    pub key_id: Option<String>,
+    /// Don't notify room subscribers when the room has no ready agents.
+    #[serde(default)]
+    pub skip_broadcast_if_empty: bool,
+    /// `occurred_at` of the latest event with the same set and label the client has seen.
+    /// The event is rejected if there's another one since then.
+    pub expected_last_occurred_at: Option<i64>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -54,6 +75,20 @@ impl CreateRequest {
     }
 }
 
+#[utoipa::path(
+    post,
+    path = "/rooms/{id}/events",
+    tag = "event",
+    params(("id" = Uuid, Path, description = "Room identifier")),
+    request_body = inline(CreatePayload),
+    responses(
+        (status = 201, description = "Event created", body = Event),
+        (status = 403, description = "Not authorized", body = ErrorPayload),
+        (status = 404, description = "Room not found", body = ErrorPayload),
+        (status = 409, description = "Conflicting event", body = ErrorPayload),
+    ),
+    security(("bearer" = []))
+)]
 pub async fn create(
     ctx: extract::Extension<Arc<AppContext>>,
     AgentIdExtractor(agent_id): AgentIdExtractor,
@@ -133,48 +168,57 @@ impl RequestHandler for CreateHandler {
         };
 
         let is_claim = payload.is_claim;
+        let skip_broadcast_if_empty = payload.skip_broadcast_if_empty;
+
+        // Legacy single `attribute` goes first so that it's still exposed as `attribute`.
+        let mut attributes = payload.attribute.clone().into_iter().collect::<Vec<_>>();
+
+        for attribute in &payload.attributes {
+            if !attributes.contains(attribute) {
+                attributes.push(attribute.to_owned());
+            }
+        }
 
         // Authorize event creation on tenant with cache.
-        let key = if let Some(ref attribute) = payload.attribute {
-            attribute
+        let keys = if !attributes.is_empty() {
+            attributes.iter().map(|a| a.as_str()).collect()
         } else if payload.is_claim {
-            "claims"
+            vec!["claims"]
         } else {
-            "events"
-        };
-
-        // NOTE:
-        // Currently we simply override authz object to room update if event type is in locked_types
-        // or if room mandates whiteboard access validation and the user is not allowed access through whiteboard access map
-        //
-        // Assumption here is that admins can always update rooms and its ok for them to post messages in locked chat
-        // So room update authz check works for them
-        // But the same check always fails for common users
-        //
-        // This is probably a temporary solution, relying on room update being allowed only to those who can post in locked chat
-        let (object, action) = {
-            let object = room.authz_object();
-            let mut object = object.iter().map(|s| s.as_ref()).collect::<Vec<_>>();
-
-            if room.event_should_authz_room_update(&payload.kind, reqp.as_account_id()) {
-                (AuthzObject::new(&object).into(), "update")
-            } else {
-                object.extend([key, &payload.kind, "authors", &author].iter());
+            vec!["events"]
+        };
+
+        let mut authz_time =
+            authorize_event(context, &room, &payload.kind, &author, &keys, reqp).await?;
+
+        // In moderated rooms events of those who can't update the room await moderator's approval.
+        let moderation_status = if room.moderated() && payload.is_persistent {
+            let object = AuthzObject::room(&room).into();
+
+            let result = context
+                .authz()
+                .authorize_room(
+                    &room,
+                    reqp.as_account_id().to_owned(),
+                    object,
+                    "update".into(),
+                )
+                .await;
 
-                (AuthzObject::new(&object).into(), "create")
+            match result {
+                Ok(duration) => {
+                    authz_time = authz_time + duration;
+                    db::event::ModerationStatus::Approved
+                }
+                Err(err) => match err.kind() {
+                    svc_authz::ErrorKind::Forbidden(_) => db::event::ModerationStatus::Pending,
+                    _ => return Err(err.into()),
+                },
             }
+        } else {
+            db::event::ModerationStatus::Approved
         };
 
-        let authz_time = context
-            .authz()
-            .authorize(
-                room.audience().into(),
-                reqp.as_account_id().to_owned(),
-                object,
-                action.into(),
-            )
-            .await?;
-
         // Calculate occurrence date.
         let occurred_at = match room.time().map(|t| t.start().to_owned()) {
             Ok(opened_at) => (Utc::now() - opened_at)
@@ -189,25 +233,142 @@ impl RequestHandler for CreateHandler {
             data,
             set,
             label,
-            attribute,
             removed,
+            content_encrypted,
+            key_id,
+            expected_last_occurred_at,
             ..
         } = payload;
 
-        if data.to_string().len() >= context.config().constraint.payload_size {
-            return Err(anyhow!("Payload size exceeded")).error(AppErrorKind::PayloadSizeExceeded);
+        let expected_head = match (expected_last_occurred_at, &label) {
+            (Some(_), _) if !payload.is_persistent => {
+                return Err(anyhow!(
+                    "'expected_last_occurred_at' is not allowed for transient events"
+                ))
+                .error(AppErrorKind::InvalidPayload);
+            }
+            (Some(occurred_at), Some(label)) => {
+                let set = set.clone().unwrap_or_else(|| kind.clone());
+                Some((set, label.to_owned(), occurred_at))
+            }
+            (Some(_), None) => {
+                return Err(anyhow!("'expected_last_occurred_at' requires 'label'"))
+                    .error(AppErrorKind::InvalidPayload);
+            }
+            (None, _) => None,
+        };
+
+        // Events of CRDT sets are deltas merged by data keys for each label.
+        let set_name = set.as_deref().unwrap_or(&kind);
+
+        if payload.is_persistent && context.config().crdt.is_crdt_set(set_name) {
+            if label.is_none() {
+                return Err(anyhow!("Events of CRDT sets require 'label'"))
+                    .error(AppErrorKind::InvalidEvent);
+            }
+
+            if !removed && (content_encrypted || !data.is_object()) {
+                return Err(anyhow!("Events of CRDT sets must have object data"))
+                    .error(AppErrorKind::InvalidEvent);
+            }
+
+            // Deltas are merged by the service so it must be able to look into them.
+            if let Some(encryption) = context.data_encryption() {
+                if encryption.is_encrypted_kind(&kind) {
+                    return Err(anyhow!("Events of CRDT sets can't be encrypted at rest"))
+                        .error(AppErrorKind::InvalidEvent);
+                }
+            }
+        }
+
+        // Encrypted content is never looked into so only its size gets validated.
+        let data_size = match data {
+            JsonValue::String(ref ciphertext) if content_encrypted => ciphertext.len(),
+            _ if content_encrypted => {
+                return Err(anyhow!("Encrypted data must be a string"))
+                    .error(AppErrorKind::InvalidEvent);
+            }
+            _ if key_id.is_some() => {
+                return Err(anyhow!("'key_id' is only allowed for encrypted content"))
+                    .error(AppErrorKind::InvalidEvent);
+            }
+            _ => data.to_string().len(),
+        };
+
+        check_data_size(&context.config().constraint, &kind, data_size)?;
+
+        if kind.as_str() == EventKind::Attachment && !removed {
+            if content_encrypted {
+                return Err(anyhow!("Attachments must not be encrypted"))
+                    .error(AppErrorKind::InvalidEvent);
+            }
+
+            check_attachment(&context.config().attachments, room.audience(), &data)?;
+        }
+
+        // Pages are indexed by label, see `whiteboard.pages`.
+        if kind.as_str() == EventKind::Page {
+            if label.is_none() {
+                return Err(anyhow!("Page events require 'label'"))
+                    .error(AppErrorKind::InvalidEvent);
+            }
+
+            let has_position = data.get("position").is_some_and(JsonValue::is_number);
+
+            if !removed && (content_encrypted || !has_position) {
+                return Err(anyhow!("Page events must have numeric 'position'"))
+                    .error(AppErrorKind::InvalidEvent);
+            }
+        }
+
+        // Encrypted content can't be looked into by the classifier.
+        if kind.as_str() == EventKind::Message && !content_encrypted && !removed {
+            let verdict = context
+                .content_filter()
+                .check(&room, &data)
+                .await
+                .context("Failed to check message content")
+                .error(AppErrorKind::ContentFilterUnavailable)?;
+
+            match verdict {
+                Verdict::Allow => (),
+                Verdict::Flag => {
+                    if !attributes.iter().any(|a| a == FLAGGED_ATTRIBUTE) {
+                        attributes.push(FLAGGED_ATTRIBUTE.to_owned());
+                    }
+                }
+                Verdict::Reject => {
+                    return Err(anyhow!("Message rejected by content filter"))
+                        .error(AppErrorKind::ContentRejected);
+                }
+            }
         }
 
         let event = if payload.is_persistent {
+            let data_encryption = context.data_encryption();
+
             // Insert event into the DB.
-            let mut query = db::event::InsertQuery::new(
-                room.id(),
-                kind,
-                data,
-                occurred_at,
-                reqp.as_agent_id().to_owned(),
-            )
-            .error(AppErrorKind::InvalidEvent)?;
+            let mut query = if content_encrypted {
+                db::event::InsertQuery::new_encrypted(
+                    room.id(),
+                    kind,
+                    data,
+                    occurred_at,
+                    reqp.as_agent_id().to_owned(),
+                    key_id,
+                    data_encryption.as_deref(),
+                )
+            } else {
+                db::event::InsertQuery::new(
+                    room.id(),
+                    kind,
+                    data,
+                    occurred_at,
+                    reqp.as_agent_id().to_owned(),
+                    data_encryption.as_deref(),
+                )
+            }
+            .map_err(|err| helpers::invalid_event(context, err))?;
 
             if let Some(set) = set {
                 query = query.set(set);
@@ -217,27 +378,38 @@ impl RequestHandler for CreateHandler {
                 query = query.label(label);
             }
 
-            if let Some(attribute) = attribute {
-                query = query.attribute(attribute);
+            if !attributes.is_empty() {
+                query = query.attributes(attributes);
             }
 
             if removed {
                 query = query.removed(true);
             }
 
-            {
-                let mut conn = context.get_conn().await?;
+            if !moderation_status.is_approved() {
+                query = query.moderation_status(moderation_status);
+            }
 
-                let event = context
-                    .metrics()
-                    .measure_query(QueryKey::EventInsertQuery, query.execute(&mut conn))
-                    .await
-                    .context("Failed to insert event")
-                    .error(AppErrorKind::DbQueryFailed)?;
+            let mut event = match expected_head {
+                Some((set, label, occurred_at)) => {
+                    insert_if_head_matches(context, query, set, label, occurred_at).await?
+                }
+                None => {
+                    let mut conn = context.get_conn().await?;
 
-                Span::current().record("event_id", &display(event.id()));
-                event
-            }
+                    context
+                        .metrics()
+                        .measure_query(QueryKey::EventInsertQuery, query.execute(&mut conn))
+                        .await
+                        .context("Failed to insert event")
+                        .error(AppErrorKind::DbQueryFailed)?
+                }
+            };
+
+            Span::current().record("event_id", &display(event.id()));
+            helpers::open_event(context, &mut event)?;
+            context.event_hooks().dispatch(&room, &event);
+            event
         } else {
             // Build transient event.
             let mut builder = db::event::Builder::new()
@@ -255,8 +427,12 @@ impl RequestHandler for CreateHandler {
                 builder = builder.label(label)
             }
 
-            if let Some(ref attribute) = attribute {
-                builder = builder.attribute(attribute)
+            if !attributes.is_empty() {
+                builder = builder.attributes(&attributes)
+            }
+
+            if content_encrypted {
+                builder = builder.encrypted(key_id.as_deref())
             }
 
             builder
@@ -273,6 +449,11 @@ impl RequestHandler for CreateHandler {
             Some(authz_time),
         );
 
+        // Pending events are not broadcasted until a moderator approves them.
+        if !moderation_status.is_approved() {
+            return Ok(response);
+        }
+
         // If the event is claim notify the tenant.
         if is_claim {
             let claim_notification = TenantClaimNotification {
@@ -282,63 +463,275 @@ impl RequestHandler for CreateHandler {
 
             response.add_notification(
                 "event.create",
-                &format!("audiences/{}/events", room.audience()),
+                &context.config().audience_topic(room.audience()),
                 claim_notification,
                 context.start_timestamp(),
-            );
+            )?;
+        }
+
+        // Nobody listens to bot-generated rooms and imports so save the broker some traffic.
+        if skip_broadcast_if_empty && !helpers::room_has_ready_agents(context, room.id()).await? {
+            return Ok(response);
         }
 
         // Notify room subscribers.
         response.add_notification(
             "event.create",
-            &format!("rooms/{}/events", room.id()),
+            &context.config().room_topic(room.audience(), room.id()),
             event,
             context.start_timestamp(),
-        );
+        )?;
 
         Ok(response)
     }
 }
 
-///////////////////////////////////////////////////////////////////////////////
+/// Inserts the event unless another one with the same set and label has been created since
+/// the one which `occurred_at` the client expects to be the latest.
+async fn insert_if_head_matches<C: Context>(
+    context: &mut C,
+    query: db::event::InsertQuery,
+    set: String,
+    label: String,
+    expected_occurred_at: i64,
+) -> Result<Event, AppError> {
+    let mut conn = context.get_conn().await?;
+
+    let mut txn = conn
+        .begin()
+        .await
+        .context("Failed to acquire transaction")
+        .error(AppErrorKind::DbQueryFailed)?;
+
+    // Serialize concurrent writers of the same set element until the insert gets committed.
+    let lock_name = format!("event:{}:{}:{}", query.room_id(), set, label);
+
+    db::advisory_lock::XactLockQuery::new(lock_name)
+        .execute(&mut txn)
+        .await
+        .context("Failed to lock set element")
+        .error(AppErrorKind::DbQueryFailed)?;
+
+    let head_query = db::event::LatestEventQuery::new(query.room_id(), set, label);
+
+    let head = context
+        .metrics()
+        .measure_query(
+            QueryKey::EventLatestEventQuery,
+            head_query.execute(&mut txn),
+        )
+        .await
+        .context("Failed to find latest event")
+        .error(AppErrorKind::DbQueryFailed)?;
+
+    // The current head goes to the error detail so that the client may catch up without re-reading.
+    match head {
+        Some(ref head) if head.occurred_at() == expected_occurred_at => (),
+        Some(head) => {
+            return Err(anyhow!(
+                "The latest event is id = {}, occurred_at = {}, seq = {}",
+                head.id(),
+                head.occurred_at(),
+                head.seq().map(|seq| seq.to_string()).unwrap_or_default(),
+            ))
+            .error(AppErrorKind::EventConflict);
+        }
+        None => {
+            return Err(anyhow!("There's no event with the given set and label"))
+                .error(AppErrorKind::EventConflict);
+        }
+    }
 
-const MAX_LIMIT: usize = 100;
+    let event = context
+        .metrics()
+        .measure_query(QueryKey::EventInsertQuery, query.execute(&mut txn))
+        .await
+        .context("Failed to insert event")
+        .error(AppErrorKind::DbQueryFailed)?;
 
-#[derive(Debug, Deserialize, PartialEq)]
-#[serde(untagged)]
-enum ListTypesFilter {
-    Single(String),
-    Multiple(Vec<String>),
+    txn.commit()
+        .await
+        .context("Failed to commit transaction")
+        .error(AppErrorKind::DbQueryFailed)?;
+
+    Ok(event)
+}
+
+/// Authorizes event creation with each of the `keys` (attributes, `claims` or `events`).
+pub(crate) async fn authorize_event<C: Context>(
+    context: &mut C,
+    room: &db::room::Object,
+    kind: &str,
+    author: &str,
+    keys: &[&str],
+    reqp: RequestParams<'_>,
+) -> Result<chrono::Duration, AppError> {
+    // NOTE:
+    // Currently we simply override authz object to room update if event type is in locked_types
+    // or if room mandates whiteboard access validation and the user is not allowed access through whiteboard access map
+    //
+    // Assumption here is that admins can always update rooms and its ok for them to post messages in locked chat
+    // So room update authz check works for them
+    // But the same check always fails for common users
+    //
+    // This is probably a temporary solution, relying on room update being allowed only to those who can post in locked chat
+    let object = room.authz_object();
+    let object = object.iter().map(|s| s.as_ref()).collect::<Vec<_>>();
+
+    let intents = if room.event_should_authz_room_update(kind, reqp.as_account_id()) {
+        vec![(object, "update")]
+    } else {
+        keys.iter()
+            .map(|key| {
+                let mut object = object.clone();
+                object.extend([*key, kind, "authors", author].iter());
+                (object, "create")
+            })
+            .collect()
+    };
+
+    let mut authz_time = chrono::Duration::zero();
+
+    for (object, action) in intents {
+        let duration = context
+            .authz()
+            .authorize_room(
+                room,
+                reqp.as_account_id().to_owned(),
+                AuthzObject::new(&object).into(),
+                action.into(),
+            )
+            .await?;
+
+        authz_time = authz_time + duration;
+    }
+
+    Ok(authz_time)
+}
+
+#[derive(Deserialize)]
+struct AttachmentData {
+    url: String,
+    /// Stored as `bigint`.
+    size: i64,
+    mime: String,
+}
+
+/// Checks that the attachment has its size and type and references an allowed storage.
+fn check_attachment(
+    config: &AttachmentsConfig,
+    audience: &str,
+    data: &JsonValue,
+) -> Result<(), AppError> {
+    let attachment = serde_json::from_value::<AttachmentData>(data.to_owned())
+        .context("Attachment data must have 'url', 'size' and 'mime'")
+        .error(AppErrorKind::InvalidEvent)?;
+
+    let url = url::Url::parse(&attachment.url)
+        .context("Invalid attachment URL")
+        .error(AppErrorKind::InvalidEvent)?;
+
+    let allowed = config
+        .audiences
+        .get(audience)
+        .context("Attachments are not allowed in the audience")
+        .error(AppErrorKind::InvalidEvent)?;
+
+    if attachment.mime.is_empty() || attachment.size < 0 {
+        return Err(anyhow!(
+            "Failed to validate attachment with empty 'mime' or negative 'size', host = '{}'",
+            url.host_str().unwrap_or_default(),
+        ))
+        .error(AppErrorKind::InvalidEvent);
+    }
+
+    if let Some(max_size) = allowed.max_size {
+        if attachment.size as u64 > max_size {
+            return Err(anyhow!("Attachment must not exceed {} bytes", max_size))
+                .error(AppErrorKind::InvalidEvent);
+        }
+    }
+
+    // Prefixes are compared by parts so `https://storage.example.org.evil.com` doesn't pass
+    // for `https://storage.example.org`.
+    let is_allowed = allowed.allowed_urls.iter().any(|prefix| {
+        url::Url::parse(prefix)
+            .map(|prefix| {
+                prefix.scheme() == url.scheme()
+                    && prefix.host_str() == url.host_str()
+                    && prefix.port_or_known_default() == url.port_or_known_default()
+                    && is_path_within(url.path(), prefix.path())
+            })
+            .unwrap_or(false)
+    });
+
+    if !is_allowed {
+        return Err(anyhow!("Attachment URL is not allowed")).error(AppErrorKind::InvalidEvent);
+    }
+
+    Ok(())
+}
+
+/// Whether `path` equals `prefix` or lies under it so `/files` doesn't allow `/files-evil`.
+fn is_path_within(path: &str, prefix: &str) -> bool {
+    match path.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || prefix.ends_with('/') || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+/// Checks event `data` size against the general payload limit and the limit of its `kind`.
+fn check_data_size(constraint: &Constraint, kind: &str, data_size: usize) -> Result<(), AppError> {
+    if data_size >= constraint.payload_size {
+        return Err(anyhow!("Payload size exceeded")).error(AppErrorKind::PayloadSizeExceeded);
+    }
+
+    if let Some(&max_size) = constraint.event_data_size.get(kind) {
+        if data_size > max_size {
+            let mut err = AppError::new(
+                AppErrorKind::PayloadTooLarge,
+                anyhow!(
+                    "Data of '{}' events must not exceed {} bytes, got {}",
+                    kind,
+                    max_size,
+                    data_size
+                ),
+            );
+
+            err.tag("event_kind", kind);
+            err.tag("max_size", &max_size.to_string());
+            err.tag("size", &data_size.to_string());
+            return Err(err);
+        }
+    }
+
+    Ok(())
 }
 
+///////////////////////////////////////////////////////////////////////////////
+
 #[derive(Debug, Deserialize)]
-pub struct ListPayload {
-    #[serde(rename = "type")]
-    kind: Option<ListTypesFilter>,
-    set: Option<String>,
-    label: Option<String>,
-    attribute: Option<String>,
-    last_occurred_at: Option<i64>,
-    #[serde(default)]
-    direction: db::event::Direction,
-    limit: Option<usize>,
+pub struct SetAttributePayload {
+    set: String,
+    label: String,
+    attribute: String,
 }
 
 #[derive(Debug, Deserialize)]
-pub struct ListRequest {
+pub struct SetAttributeRequest {
     room_id: Uuid,
     #[serde(flatten)]
-    payload: ListPayload,
+    payload: SetAttributePayload,
 }
 
-pub async fn list(
+pub async fn set_attribute(
     ctx: extract::Extension<Arc<AppContext>>,
     AgentIdExtractor(agent_id): AgentIdExtractor,
     Path(room_id): Path<Uuid>,
-    Query(payload): Query<ListPayload>,
+    Json(payload): Json<SetAttributePayload>,
 ) -> RequestResult {
-    let request = ListRequest { room_id, payload };
-    ListHandler::handle(
+    let request = SetAttributeRequest { room_id, payload };
+    SetAttributeHandler::handle(
         &mut ctx.start_message(),
         request,
         RequestParams::Http {
@@ -348,11 +741,11 @@ pub async fn list(
     .await
 }
 
-pub struct ListHandler;
+pub struct SetAttributeHandler;
 
 #[async_trait]
-impl RequestHandler for ListHandler {
-    type Payload = ListRequest;
+impl RequestHandler for SetAttributeHandler {
+    type Payload = SetAttributeRequest;
 
     #[instrument(skip_all, fields(room_id, scope, classroom_id))]
     async fn handle<C: Context>(
@@ -360,108 +753,2384 @@ impl RequestHandler for ListHandler {
         Self::Payload { room_id, payload }: Self::Payload,
         reqp: RequestParams<'_>,
     ) -> RequestResult {
-        let room = helpers::find_room(context, room_id, helpers::RoomTimeRequirement::Any).await?;
+        let SetAttributePayload {
+            set,
+            label,
+            attribute,
+        } = payload;
 
-        // Authorize room events listing.
-        let classroom_id = room.classroom_id().to_string();
-        let object = AuthzObject::new(&["classrooms", &classroom_id]).into();
+        let update = AttributeUpdate::Add(attribute);
+        update_attribute(context, room_id, set, label, update, reqp).await
+    }
+}
 
-        let authz_time = context
-            .authz()
-            .authorize(
-                room.audience().into(),
-                reqp.as_account_id().to_owned(),
-                object,
-                "read".into(),
-            )
-            .await?;
+///////////////////////////////////////////////////////////////////////////////
 
-        // Retrieve events from the DB.
-        let mut query = db::event::ListQuery::new().room_id(room.id());
+#[derive(Debug, Deserialize)]
+pub struct ClearAttributePayload {
+    set: String,
+    label: String,
+    attribute: Option<String>,
+}
 
-        let ListPayload {
-            kind,
+#[derive(Debug, Deserialize)]
+pub struct ClearAttributeRequest {
+    room_id: Uuid,
+    #[serde(flatten)]
+    payload: ClearAttributePayload,
+}
+
+pub async fn clear_attribute(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<Uuid>,
+    Query(payload): Query<ClearAttributePayload>,
+) -> RequestResult {
+    let request = ClearAttributeRequest { room_id, payload };
+    ClearAttributeHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct ClearAttributeHandler;
+
+#[async_trait]
+impl RequestHandler for ClearAttributeHandler {
+    type Payload = ClearAttributeRequest;
+
+    #[instrument(skip_all, fields(room_id, scope, classroom_id))]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload { room_id, payload }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let ClearAttributePayload {
             set,
             label,
             attribute,
-            last_occurred_at,
-            ..
         } = payload;
 
-        query = match kind {
-            Some(ListTypesFilter::Single(kind)) => query.kind(kind),
-            Some(ListTypesFilter::Multiple(kinds)) => query.kinds(kinds),
-            None => query,
+        let update = match attribute {
+            Some(attribute) => AttributeUpdate::Remove(attribute),
+            None => AttributeUpdate::Clear,
         };
 
-        if let Some(ref set) = set {
-            query = query.set(set);
-        }
-
-        if let Some(ref label) = label {
-            query = query.label(label);
-        }
+        update_attribute(context, room_id, set, label, update, reqp).await
+    }
+}
 
-        if let Some(ref attribute) = attribute {
-            query = query.attribute(attribute);
-        }
+enum AttributeUpdate {
+    Add(String),
+    Remove(String),
+    Clear,
+}
 
-        if let Some(last_occurred_at) = last_occurred_at {
-            query = query.last_occurred_at(last_occurred_at);
+/// Updates attributes of the latest event in the given set/label
+/// and notifies room subscribers with `event.attribute_update`.
+async fn update_attribute<C: Context>(
+    context: &mut C,
+    room_id: Uuid,
+    set: String,
+    label: String,
+    update: AttributeUpdate,
+    reqp: RequestParams<'_>,
+) -> RequestResult {
+    Span::current().record("set", set.as_str());
+    Span::current().record("set_label", label.as_str());
+
+    let room = helpers::find_room(context, room_id, helpers::RoomTimeRequirement::Open).await?;
+    let mut conn = context.get_conn().await?;
+
+    // Keep the event locked from authorization until the update is committed so that
+    // attributes are authorized against the same event and state which get updated.
+    let mut txn = conn
+        .begin()
+        .await
+        .context("Failed to acquire transaction")
+        .error(AppErrorKind::DbQueryFailed)?;
+
+    let event = {
+        let query = db::event::LatestEventQuery::new(room.id(), set, label).for_update();
+
+        context
+            .metrics()
+            .measure_query(QueryKey::EventLatestEventQuery, query.execute(&mut txn))
+            .await
+            .context("Failed to find latest event")
+            .error(AppErrorKind::DbQueryFailed)?
+            .ok_or_else(|| anyhow!("Event not found"))
+            .error(AppErrorKind::EventNotFound)?
+    };
+
+    // Authorize the same way as creating the next event with the new attribute would be.
+    // When clearing all attributes each of the current ones is being authorized since they're revoked.
+    let keys = match update {
+        AttributeUpdate::Add(ref attribute) | AttributeUpdate::Remove(ref attribute) => {
+            vec![attribute.as_str()]
         }
+        AttributeUpdate::Clear if event.attributes().is_empty() => vec!["events"],
+        AttributeUpdate::Clear => event.attributes().iter().map(|a| a.as_str()).collect(),
+    };
 
-        let events = {
-            let mut conn = context.get_ro_conn().await?;
+    let author = event.original_created_by().as_account_id().to_string();
+    let authz_time = authorize_event(context, &room, event.kind(), &author, &keys, reqp).await?;
 
-            query = query
-                .direction(payload.direction)
-                .limit(std::cmp::min(payload.limit.unwrap_or(MAX_LIMIT), MAX_LIMIT));
+    let mut event = {
+        let mut query = db::event::AttributeUpdateQuery::new(event.id());
 
-            context
-                .metrics()
-                .measure_query(QueryKey::EventListQuery, query.execute(&mut conn))
-                .await
-                .context("Failed to list events")
-                .error(AppErrorKind::DbQueryFailed)?
+        query = match update {
+            AttributeUpdate::Add(attribute) => query.add(attribute),
+            AttributeUpdate::Remove(attribute) => query.remove(attribute),
+            AttributeUpdate::Clear => query,
         };
 
-        // Respond with events list.
-        Ok(AppResponse::new(
-            ResponseStatus::OK,
-            events,
-            context.start_timestamp(),
-            Some(authz_time),
-        ))
-    }
+        context
+            .metrics()
+            .measure_query(QueryKey::EventAttributeUpdateQuery, query.execute(&mut txn))
+            .await
+            .context("Failed to update event attributes")
+            .error(AppErrorKind::DbQueryFailed)?
+            .ok_or_else(|| anyhow!("Event not found"))
+            .error(AppErrorKind::EventNotFound)?
+    };
+
+    txn.commit()
+        .await
+        .context("Failed to commit transaction")
+        .error(AppErrorKind::DbQueryFailed)?;
+
+    Span::current().record("event_id", display(event.id()));
+    helpers::open_event(context, &mut event)?;
+
+    let mut response = AppResponse::new(
+        ResponseStatus::OK,
+        event.clone(),
+        context.start_timestamp(),
+        Some(authz_time),
+    );
+
+    response.add_notification(
+        "event.attribute_update",
+        &context.config().room_topic(room.audience(), room.id()),
+        event,
+        context.start_timestamp(),
+    )?;
+
+    Ok(response)
 }
 
 ///////////////////////////////////////////////////////////////////////////////
 
-#[cfg(test)]
-mod tests {
-    use std::collections::HashMap;
-
-    use serde_json::json;
+const MAX_IMPORT_EVENTS: usize = 1000;
 
-    use crate::db::event::{Direction, Object as Event};
-    use crate::test_helpers::outgoing_envelope::OutgoingEnvelopeProperties;
-    use crate::test_helpers::prelude::*;
+/// An event from a legacy system with its original authorship and timing.
+#[derive(Debug, Deserialize)]
+pub struct ImportEvent {
+    #[serde(rename = "type")]
+    kind: String,
+    set: Option<String>,
+    label: Option<String>,
+    #[serde(default)]
+    attributes: Vec<String>,
+    data: JsonValue,
+    occurred_at: i64,
+    created_by: AgentId,
+    #[serde(with = "chrono::serde::ts_milliseconds")]
+    created_at: DateTime<Utc>,
+    #[serde(default)]
+    removed: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportPayload {
+    events: Vec<ImportEvent>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportRequest {
+    room_id: Uuid,
+    #[serde(flatten)]
+    payload: ImportPayload,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportResponse {
+    imported: usize,
+}
+
+pub async fn import(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<Uuid>,
+    Json(payload): Json<ImportPayload>,
+) -> RequestResult {
+    let request = ImportRequest { room_id, payload };
+    ImportHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct ImportHandler;
+
+#[async_trait]
+impl RequestHandler for ImportHandler {
+    type Payload = ImportRequest;
+
+    #[instrument(skip_all, fields(room_id, scope, classroom_id))]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload { room_id, payload }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        // History may be imported into closed rooms as well.
+        let room = helpers::find_room(context, room_id, helpers::RoomTimeRequirement::Any).await?;
+
+        // Setting authorship and timing is a privilege of trusted migration tools.
+        let object = AuthzObject::room(&room).into();
+
+        let authz_time = context
+            .authz()
+            .authorize_room(
+                &room,
+                reqp.as_account_id().to_owned(),
+                object,
+                "import".into(),
+            )
+            .await?;
+
+        if payload.events.len() > MAX_IMPORT_EVENTS {
+            return Err(anyhow!(
+                "Too many events to import, maximum is {}",
+                MAX_IMPORT_EVENTS
+            ))
+            .error(AppErrorKind::InvalidPayload);
+        }
+
+        let constraint = context.config().constraint.clone();
+        let data_encryption = context.data_encryption();
+        let mut queries = Vec::with_capacity(payload.events.len());
+
+        for event in payload.events {
+            check_data_size(&constraint, &event.kind, event.data.to_string().len())?;
+
+            let mut query = db::event::InsertQuery::new(
+                room.id(),
+                event.kind,
+                event.data,
+                event.occurred_at,
+                event.created_by,
+                data_encryption.as_deref(),
+            )
+            .map_err(|err| helpers::invalid_event(context, err))?
+            .created_at(event.created_at)
+            .attributes(event.attributes)
+            .removed(event.removed);
+
+            if let Some(set) = event.set {
+                query = query.set(set);
+            }
+
+            if let Some(label) = event.label {
+                query = query.label(label);
+            }
+
+            queries.push(query);
+        }
+
+        // Import all or nothing so that a failed import may be simply retried.
+        let imported = queries.len();
+
+        {
+            let mut conn = context.get_conn().await?;
+
+            let mut txn = conn
+                .begin()
+                .await
+                .context("Failed to acquire transaction")
+                .error(AppErrorKind::DbQueryFailed)?;
+
+            for query in queries {
+                context
+                    .metrics()
+                    .measure_query(QueryKey::EventInsertQuery, query.execute(&mut txn))
+                    .await
+                    .context("Failed to insert event")
+                    .error(AppErrorKind::DbQueryFailed)?;
+            }
+
+            txn.commit()
+                .await
+                .context("Failed to commit transaction")
+                .error(AppErrorKind::DbQueryFailed)?;
+        }
+
+        // Imported history is not broadcasted, clients get it on reading the room.
+        Ok(AppResponse::new(
+            ResponseStatus::CREATED,
+            ImportResponse { imported },
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+const MAX_LIMIT: usize = 100;
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(untagged)]
+enum ListTypesFilter {
+    Single(String),
+    Multiple(Vec<String>),
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListPayload {
+    /// Event type or a list of them.
+    #[serde(rename = "type")]
+    #[param(value_type = Option<String>)]
+    kind: Option<ListTypesFilter>,
+    /// Event set or a list of them.
+    #[param(value_type = Option<String>)]
+    set: Option<ListTypesFilter>,
+    label: Option<String>,
+    /// Matches events whose label starts with the prefix.
+    label_prefix: Option<String>,
+    attribute: Option<String>,
+    last_occurred_at: Option<i64>,
+    /// Inclusive lower bound of events' `occurred_at`.
+    from_occurred_at: Option<i64>,
+    /// Exclusive upper bound of events' `occurred_at`.
+    to_occurred_at: Option<i64>,
+    #[serde(default)]
+    direction: db::event::Direction,
+    limit: Option<usize>,
+    /// Comma separated list of event fields to return.
+    fields: Option<String>,
+    /// Return binary encoded events as is for clients to decode them.
+    #[serde(default)]
+    binary_data: bool,
+    /// Return deleted events with `deleted_at` too. Requires `update` permission on the room.
+    #[serde(default)]
+    include_deleted: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListRequest {
+    room_id: Uuid,
+    #[serde(flatten)]
+    payload: ListPayload,
+    /// `If-None-Match` header of a conditional HTTP request.
+    #[serde(skip)]
+    if_none_match: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/rooms/{id}/events",
+    tag = "event",
+    params(("id" = Uuid, Path, description = "Room identifier"), ListPayload),
+    responses(
+        (status = 200, description = "Events", body = [Event]),
+        (status = 304, description = "Events haven't changed since `If-None-Match`"),
+        (status = 404, description = "Room not found", body = ErrorPayload),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn list(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<Uuid>,
+    Query(payload): Query<ListPayload>,
+    headers: HeaderMap,
+) -> RequestResult {
+    let request = ListRequest {
+        room_id,
+        payload,
+        if_none_match: helpers::if_none_match(&headers),
+    };
+    ListHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+/// `event.list` for viewers with a share token of the room instead of authentication.
+pub async fn shared_list(
+    ctx: extract::Extension<Arc<AppContext>>,
+    ShareTokenExtractor(token): ShareTokenExtractor,
+    Path(room_id): Path<Uuid>,
+    Query(payload): Query<ListPayload>,
+    headers: HeaderMap,
+) -> RequestResult {
+    let agent_id = ShareToken::viewer_agent_id(ctx.agent_id().as_account_id());
+    let request = ListRequest {
+        room_id,
+        payload,
+        if_none_match: helpers::if_none_match(&headers),
+    };
+    ListHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Shared {
+            agent_id: &agent_id,
+            room_id: token.room_id(),
+        },
+    )
+    .await
+}
+
+pub struct ListHandler;
+
+#[async_trait]
+impl RequestHandler for ListHandler {
+    type Payload = ListRequest;
+
+    #[instrument(skip_all, fields(room_id, scope, classroom_id))]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload {
+            room_id,
+            payload,
+            if_none_match,
+        }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let fields = payload
+            .fields
+            .as_deref()
+            .map(|fields| fields.parse::<helpers::EventFields>())
+            .transpose()
+            .error(AppErrorKind::InvalidPayload)?;
+
+        let room = helpers::find_room(context, room_id, helpers::RoomTimeRequirement::Any).await?;
+
+        // Authorize room events listing.
+        let classroom_id = room.classroom_id().to_string();
+        let object = AuthzObject::new(&["classrooms", &classroom_id]);
+        let authz_time = helpers::authorize_room(context, &room, reqp, object, "read").await?;
+
+        // Deleted events are available to those who can update the room only.
+        let authz_time = if payload.include_deleted {
+            let object = AuthzObject::room(&room);
+
+            let update_authz_time =
+                helpers::authorize_room(context, &room, reqp, object, "update").await?;
+
+            authz_time + update_authz_time
+        } else {
+            authz_time
+        };
+
+        // Moderator-only parts of event data are hidden from the others.
+        let (redaction, redaction_authz_time) =
+            helpers::Redaction::for_agent(context, &room, reqp).await?;
+
+        let authz_time = authz_time + redaction_authz_time.unwrap_or_else(chrono::Duration::zero);
+
+        // Retrieve events from the DB.
+        let mut query = db::event::ListQuery::new().room_id(room.id());
+
+        let ListPayload {
+            kind,
+            set,
+            label,
+            label_prefix,
+            attribute,
+            last_occurred_at,
+            from_occurred_at,
+            to_occurred_at,
+            ..
+        } = payload;
+
+        query = match kind {
+            Some(ListTypesFilter::Single(kind)) => query.kind(kind),
+            Some(ListTypesFilter::Multiple(kinds)) => query.kinds(kinds),
+            None => query,
+        };
+
+        query = match set {
+            Some(ListTypesFilter::Single(ref set)) => query.set(set),
+            Some(ListTypesFilter::Multiple(sets)) => query.sets(sets),
+            None => query,
+        };
+
+        if let Some(ref label) = label {
+            query = query.label(label);
+        }
+
+        if let Some(ref label_prefix) = label_prefix {
+            query = query.label_prefix(label_prefix);
+        }
+
+        if let Some(ref attribute) = attribute {
+            query = query.attribute(attribute);
+        }
+
+        if let Some(last_occurred_at) = last_occurred_at {
+            query = query.last_occurred_at(last_occurred_at);
+        }
+
+        if let Some(from_occurred_at) = from_occurred_at {
+            query = query.occurred_at_from(from_occurred_at);
+        }
+
+        if let Some(to_occurred_at) = to_occurred_at {
+            query = query.occurred_at_to(to_occurred_at);
+        }
+
+        // Skip fetching and decoding heavy event data when it's not requested.
+        if matches!(fields, Some(ref fields) if !fields.contains("data")) {
+            query = query.without_data();
+        }
+
+        // Binary data can't be redacted so it gets decoded when redaction applies.
+        if payload.binary_data && redaction.is_empty() {
+            query = query.with_binary_data();
+        }
+
+        if payload.include_deleted {
+            query = query.include_deleted();
+        }
+
+        query = query
+            .direction(payload.direction)
+            .limit(std::cmp::min(payload.limit.unwrap_or(MAX_LIMIT), MAX_LIMIT));
+
+        // The same events may be shaped differently by the query and redaction
+        // so they get into the tag along with the room version.
+        let etag =
+            helpers::room_events_etag(context, &room, (&query, &fields, !redaction.is_empty()))
+                .await?;
+
+        if helpers::etag_matches(if_none_match.as_deref(), &etag) {
+            return Ok(AppResponse::not_modified(
+                etag,
+                context.start_timestamp(),
+                Some(authz_time),
+            ));
+        }
+
+        let mut events = {
+            let mut conn = context.get_ro_conn().await?;
+
+            context
+                .metrics()
+                .measure_query(QueryKey::EventListQuery, query.execute(&mut conn))
+                .await
+                .context("Failed to list events")
+                .error(AppErrorKind::DbQueryFailed)?
+        };
+
+        helpers::open_events(context, &mut events)?;
+        redaction.apply(&mut events);
+
+        // Respond with events list.
+        let response = match fields {
+            Some(fields) => {
+                let mut events = serde_json::to_value(events)
+                    .context("Failed to serialize events")
+                    .error(AppErrorKind::SerializationFailed)?;
+
+                fields.retain(&mut events);
+
+                AppResponse::new(
+                    ResponseStatus::OK,
+                    events,
+                    context.start_timestamp(),
+                    Some(authz_time),
+                )
+            }
+            None => AppResponse::new(
+                ResponseStatus::OK,
+                events,
+                context.start_timestamp(),
+                Some(authz_time),
+            ),
+        };
+
+        Ok(response.etag(etag))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+const MAX_SINCE_LIMIT: i64 = 1000;
+
+#[derive(Debug, Deserialize)]
+pub struct SincePayload {
+    /// `seq` of the last event seen by the client.
+    seq: Option<i64>,
+    /// Alternatively the id of the last event seen by the client.
+    event_id: Option<Uuid>,
+    limit: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SinceRequest {
+    room_id: Uuid,
+    #[serde(flatten)]
+    payload: SincePayload,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SinceResponse {
+    events: Vec<Event>,
+    /// The client has missed too much or it's unknown what it has missed
+    /// so it must reload the whole state instead.
+    reload: bool,
+}
+
+impl SinceResponse {
+    fn reload() -> Self {
+        Self {
+            events: vec![],
+            reload: true,
+        }
+    }
+}
+
+pub async fn since(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<Uuid>,
+    Query(payload): Query<SincePayload>,
+) -> RequestResult {
+    let request = SinceRequest { room_id, payload };
+    SinceHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct SinceHandler;
+
+#[async_trait]
+impl RequestHandler for SinceHandler {
+    type Payload = SinceRequest;
+
+    #[instrument(skip_all, fields(room_id, scope, classroom_id))]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload { room_id, payload }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let limit = std::cmp::min(payload.limit.unwrap_or(MAX_SINCE_LIMIT), MAX_SINCE_LIMIT);
+
+        if limit <= 0 {
+            return Err(anyhow!("'limit' must be positive")).error(AppErrorKind::InvalidPayload);
+        }
+
+        let room = helpers::find_room(context, room_id, helpers::RoomTimeRequirement::Any).await?;
+
+        // Authorize room events listing.
+        let classroom_id = room.classroom_id().to_string();
+        let object = AuthzObject::new(&["classrooms", &classroom_id]).into();
+
+        let authz_time = context
+            .authz()
+            .authorize_room(
+                &room,
+                reqp.as_account_id().to_owned(),
+                object,
+                "read".into(),
+            )
+            .await?;
+
+        let (redaction, redaction_authz_time) =
+            helpers::Redaction::for_agent(context, &room, reqp).await?;
+
+        let authz_time = authz_time + redaction_authz_time.unwrap_or_else(chrono::Duration::zero);
+        let mut conn = context.get_ro_conn().await?;
+
+        let seq = match (payload.seq, payload.event_id) {
+            (Some(seq), None) => Some(seq),
+            // `seq` of an event never changes so resuming after it doesn't skip anything,
+            // though events changed after it was last seen may be returned again.
+            (None, Some(event_id)) => {
+                let query = db::event::SeqQuery::new(room.id(), event_id);
+
+                context
+                    .metrics()
+                    .measure_query(QueryKey::EventSeqQuery, query.execute(&mut conn))
+                    .await
+                    .context("Failed to find event seq")
+                    .error(AppErrorKind::DbQueryFailed)?
+            }
+            _ => {
+                return Err(anyhow!("Either 'seq' or 'event_id' must be specified"))
+                    .error(AppErrorKind::InvalidPayload);
+            }
+        };
+
+        let response = match seq {
+            Some(seq) => {
+                // Fetch one extra event to find out whether the client has missed too much.
+                let query = db::event::SinceQuery::new(room.id(), seq, limit + 1);
+
+                let mut events = context
+                    .metrics()
+                    .measure_query(QueryKey::EventSinceQuery, query.execute(&mut conn))
+                    .await
+                    .context("Failed to list events since seq")
+                    .error(AppErrorKind::DbQueryFailed)?;
+
+                if events.len() as i64 > limit {
+                    SinceResponse::reload()
+                } else {
+                    helpers::open_events(context, &mut events)?;
+                    redaction.apply(&mut events);
+
+                    SinceResponse {
+                        events,
+                        reload: false,
+                    }
+                }
+            }
+            // The event is unknown or too old to have `seq`.
+            None => SinceResponse::reload(),
+        };
+
+        Ok(AppResponse::new(
+            ResponseStatus::OK,
+            response,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+const MAX_HISTORY_LIMIT: i64 = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct HistoryPayload {
+    set: String,
+    label: String,
+    /// `occurred_at` of the last revision seen to fetch the next page.
+    occurred_at: Option<i64>,
+    limit: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HistoryRequest {
+    room_id: Uuid,
+    #[serde(flatten)]
+    payload: HistoryPayload,
+}
+
+pub async fn history(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<Uuid>,
+    Query(payload): Query<HistoryPayload>,
+) -> RequestResult {
+    let request = HistoryRequest { room_id, payload };
+    HistoryHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct HistoryHandler;
+
+#[async_trait]
+impl RequestHandler for HistoryHandler {
+    type Payload = HistoryRequest;
+
+    #[instrument(skip_all, fields(room_id, scope, classroom_id))]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload { room_id, payload }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let limit = std::cmp::min(
+            payload.limit.unwrap_or(MAX_HISTORY_LIMIT),
+            MAX_HISTORY_LIMIT,
+        );
+
+        if limit <= 0 {
+            return Err(anyhow!("'limit' must be positive")).error(AppErrorKind::InvalidPayload);
+        }
+
+        let room = helpers::find_room(context, room_id, helpers::RoomTimeRequirement::Any).await?;
+
+        // Authorize room events listing.
+        let classroom_id = room.classroom_id().to_string();
+        let object = AuthzObject::new(&["classrooms", &classroom_id]).into();
+
+        let authz_time = context
+            .authz()
+            .authorize_room(
+                &room,
+                reqp.as_account_id().to_owned(),
+                object,
+                "read".into(),
+            )
+            .await?;
+
+        let (redaction, redaction_authz_time) =
+            helpers::Redaction::for_agent(context, &room, reqp).await?;
+
+        let authz_time = authz_time + redaction_authz_time.unwrap_or_else(chrono::Duration::zero);
+
+        let query = db::event::HistoryQuery::new(room.id(), payload.set, payload.label, limit)
+            .after_occurred_at(payload.occurred_at);
+
+        let mut events = {
+            let mut conn = context.get_ro_conn().await?;
+
+            context
+                .metrics()
+                .measure_query(QueryKey::EventHistoryQuery, query.execute(&mut conn))
+                .await
+                .context("Failed to list label history")
+                .error(AppErrorKind::DbQueryFailed)?
+        };
+
+        helpers::open_events(context, &mut events)?;
+        redaction.apply(&mut events);
+
+        Ok(AppResponse::new(
+            ResponseStatus::OK,
+            events,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use chrono::TimeZone;
+    use serde_json::json;
+
+    use crate::config::RedactionRule;
+    use crate::db::event::{Direction, Object as Event};
+    use crate::test_helpers::outgoing_envelope::{OutgoingEnvelope, OutgoingEnvelopeProperties};
+    use crate::test_helpers::prelude::*;
 
     use super::*;
 
-    ///////////////////////////////////////////////////////////////////////////
+    ///////////////////////////////////////////////////////////////////////////
+
+    #[tokio::test]
+    async fn create_event() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            // Create room and put the agent online.
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+            shared_helpers::insert_agent(&mut conn, agent.agent_id(), room.id()).await;
+            room
+        };
+
+        // Allow agent to create events of type `message` in the room.
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        let account_id = agent.account_id().to_string();
+
+        let object = vec![
+            "classrooms",
+            &classroom_id,
+            "pinned",
+            "message",
+            "authors",
+            &account_id,
+        ];
+
+        authz.allow(agent.account_id(), object, "create");
+
+        // Make event.create request.
+        let mut context = TestContext::new(db, authz);
+
+        let payload = CreateRequest {
+            room_id: room.id(),
+            payload: CreatePayload {
+                kind: String::from("message"),
+                set: Some(String::from("messages")),
+                label: Some(String::from("message-1")),
+                attribute: Some(String::from("pinned")),
+                attributes: vec![],
+                data: json!({ "text": "hello" }),
+                is_claim: false,
+                is_persistent: true,
+                removed: false,
+                content_encrypted: false,
+                key_id: None,
+                skip_broadcast_if_empty: false,
+                expected_last_occurred_at: None,
+            },
+        };
+
+        let messages = handle_request::<CreateHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Event creation failed");
+
+        assert_eq!(messages.len(), 2);
+
+        // Assert response.
+        let (event, respp, _) = find_response::<Event>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::CREATED);
+        assert_eq!(event.room_id(), room.id());
+        assert_eq!(event.kind(), "message");
+        assert_eq!(event.set(), "messages");
+        assert_eq!(event.label(), Some("message-1"));
+        assert_eq!(event.attribute(), Some("pinned"));
+        assert_eq!(event.data(), &json!({ "text": "hello" }));
+
+        // Assert notification.
+        let (event, evp, topic) = find_event::<Event>(messages.as_slice());
+        assert!(topic.ends_with(&format!("/rooms/{}/events", room.id())));
+        assert_eq!(evp.label(), "event.create");
+        assert_eq!(event.room_id(), room.id());
+        assert_eq!(event.kind(), "message");
+        assert_eq!(event.set(), "messages");
+        assert_eq!(event.label(), Some("message-1"));
+        assert_eq!(event.attribute(), Some("pinned"));
+        assert_eq!(event.data(), &json!({ "text": "hello" }));
+    }
+
+    #[tokio::test]
+    async fn create_event_calls_hooks() {
+        use crate::app::event_hooks::{EventHook, EventHooks};
+        use crate::db::room::Object as Room;
+
+        struct Forward(tokio::sync::mpsc::UnboundedSender<Uuid>);
+
+        #[async_trait]
+        impl EventHook for Forward {
+            fn name(&self) -> &'static str {
+                "forward"
+            }
+
+            async fn call(&self, _room: &Room, event: &Event) -> anyhow::Result<()> {
+                self.0.send(event.id())?;
+                Ok(())
+            }
+        }
+
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+            shared_helpers::insert_agent(&mut conn, agent.agent_id(), room.id()).await;
+            room
+        };
+
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        let account_id = agent.account_id().to_string();
+
+        let object = vec![
+            "classrooms",
+            &classroom_id,
+            "events",
+            "message",
+            "authors",
+            &account_id,
+        ];
+
+        authz.allow(agent.account_id(), object, "create");
+
+        let mut context = TestContext::new(db, authz);
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        context.set_event_hooks(EventHooks::new().register(Forward(tx)));
+
+        let payload = CreateRequest {
+            room_id: room.id(),
+            payload: CreatePayload {
+                kind: String::from("message"),
+                set: None,
+                label: None,
+                attribute: None,
+                attributes: vec![],
+                data: json!({ "text": "hello" }),
+                is_claim: false,
+                is_persistent: true,
+                removed: false,
+                content_encrypted: false,
+                key_id: None,
+                skip_broadcast_if_empty: false,
+                expected_last_occurred_at: None,
+            },
+        };
+
+        let messages = handle_request::<CreateHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Event creation failed");
+
+        let (event, _, _) = find_response::<Event>(messages.as_slice());
+
+        let hooked_id = tokio::time::timeout(std::time::Duration::from_secs(1), rx.recv())
+            .await
+            .expect("Hook not called")
+            .expect("Hook channel closed");
+
+        assert_eq!(hooked_id, event.id());
+    }
+
+    async fn create_message_with_verdict(
+        verdict: Verdict,
+    ) -> Result<Vec<OutgoingEnvelope>, AppError> {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+            shared_helpers::insert_agent(&mut conn, agent.agent_id(), room.id()).await;
+            room
+        };
+
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        let account_id = agent.account_id().to_string();
+
+        let object = vec![
+            "classrooms",
+            &classroom_id,
+            "events",
+            "message",
+            "authors",
+            &account_id,
+        ];
+
+        authz.allow(agent.account_id(), object, "create");
+
+        let mut context = TestContext::new(db, authz);
+        let content_filter = context.content_filter_mock();
+        content_filter.checkpoint();
+
+        content_filter
+            .expect_check()
+            .times(1)
+            .returning(move |_, _| Ok(verdict));
+
+        let payload = CreateRequest {
+            room_id: room.id(),
+            payload: CreatePayload {
+                kind: String::from("message"),
+                set: None,
+                label: None,
+                attribute: None,
+                attributes: vec![],
+                data: json!({ "text": "hello" }),
+                is_claim: false,
+                is_persistent: true,
+                removed: false,
+                content_encrypted: false,
+                key_id: None,
+                skip_broadcast_if_empty: false,
+                expected_last_occurred_at: None,
+            },
+        };
+
+        handle_request::<CreateHandler>(&mut context, &agent, payload).await
+    }
+
+    #[tokio::test]
+    async fn create_message_flagged_by_content_filter() {
+        let messages = create_message_with_verdict(Verdict::Flag)
+            .await
+            .expect("Event creation failed");
+
+        let (event, respp, _) = find_response::<Event>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::CREATED);
+        assert_eq!(event.attributes(), &[FLAGGED_ATTRIBUTE.to_owned()]);
+    }
+
+    #[tokio::test]
+    async fn create_message_rejected_by_content_filter() {
+        let err = create_message_with_verdict(Verdict::Reject)
+            .await
+            .expect_err("Unexpected success creating rejected message");
+
+        assert_eq!(err.status(), ResponseStatus::UNPROCESSABLE_ENTITY);
+        assert_eq!(err.kind(), "content_rejected");
+    }
+
+    #[test]
+    fn check_attachment_url() {
+        let mut config = AttachmentsConfig::default();
+
+        config.audiences.insert(
+            USR_AUDIENCE.to_owned(),
+            crate::config::AttachmentsAudience {
+                allowed_urls: vec!["https://storage.example.org/files/".to_owned()],
+                max_size: Some(1024),
+            },
+        );
+
+        let attachment =
+            |url: &str, size: i64| json!({ "url": url, "size": size, "mime": "image/png" });
+
+        check_attachment(
+            &config,
+            USR_AUDIENCE,
+            &attachment("https://storage.example.org/files/1.png", 1024),
+        )
+        .expect("Allowed attachment rejected");
+
+        for data in [
+            attachment("https://storage.example.org/files/1.png", 1025),
+            attachment("https://storage.example.org/other/1.png", 1),
+            attachment("https://storage.example.org/files-evil/1.png", 1),
+            attachment("https://storage.example.org.evil.com/files/1.png", 1),
+            attachment("http://storage.example.org/files/1.png", 1),
+            json!({ "url": "https://storage.example.org/files/1.png" }),
+        ] {
+            let err = check_attachment(&config, USR_AUDIENCE, &data)
+                .expect_err("Unexpected success checking attachment");
+
+            assert_eq!(err.kind(), "invalid_event");
+        }
+
+        let err = check_attachment(
+            &config,
+            USR_AUDIENCE,
+            &json!({
+                "url": "https://storage.example.org/files/1.png?token=secret",
+                "size": -1,
+                "mime": "image/png",
+            }),
+        )
+        .expect_err("Unexpected success checking attachment with negative size");
+
+        assert!(!err.detail().contains("secret"));
+
+        config.audiences.get_mut(USR_AUDIENCE).unwrap().allowed_urls =
+            vec!["https://storage.example.org/files".to_owned()];
+
+        check_attachment(
+            &config,
+            USR_AUDIENCE,
+            &attachment("https://storage.example.org/files/1.png", 1),
+        )
+        .expect("Allowed attachment rejected");
+
+        check_attachment(
+            &config,
+            USR_AUDIENCE,
+            &attachment("https://storage.example.org/files-evil/1.png", 1),
+        )
+        .expect_err("Unexpected success checking attachment outside of the prefix");
+
+        check_attachment(
+            &config,
+            "other.example.org",
+            &attachment("https://storage.example.org/files/1.png", 1),
+        )
+        .expect_err("Unexpected success checking attachment in another audience");
+    }
+
+    #[tokio::test]
+    async fn create_event_skipping_broadcast_if_empty() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let listener = TestAgent::new("web", "user456", USR_AUDIENCE);
+
+        // The agent creating events doesn't enter the room like import scripts do.
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        let account_id = agent.account_id().to_string();
+
+        let object = vec![
+            "classrooms",
+            &classroom_id,
+            "events",
+            "message",
+            "authors",
+            &account_id,
+        ];
+
+        authz.allow(agent.account_id(), object, "create");
+
+        let mut context = TestContext::new(db, authz);
+
+        let build_payload = || CreateRequest {
+            room_id: room.id(),
+            payload: CreatePayload {
+                kind: String::from("message"),
+                set: None,
+                label: None,
+                attribute: None,
+                attributes: vec![],
+                data: json!({ "text": "hello" }),
+                is_claim: false,
+                is_persistent: true,
+                removed: false,
+                content_encrypted: false,
+                key_id: None,
+                skip_broadcast_if_empty: true,
+                expected_last_occurred_at: None,
+            },
+        };
+
+        // Nobody is in the room so the event is only responded with.
+        let messages = handle_request::<CreateHandler>(&mut context, &agent, build_payload())
+            .await
+            .expect("Event creation failed");
+
+        assert_eq!(messages.len(), 1);
+        let (_, respp, _) = find_response::<Event>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::CREATED);
+
+        // Once somebody enters the room the event gets broadcasted again.
+        {
+            let mut conn = context.db().acquire().await.expect("Failed to get conn");
+            shared_helpers::insert_agent(&mut conn, listener.agent_id(), room.id()).await;
+        }
+
+        context.presence_cache().invalidate(room.id());
+
+        let messages = handle_request::<CreateHandler>(&mut context, &agent, build_payload())
+            .await
+            .expect("Event creation failed");
+
+        assert_eq!(messages.len(), 2);
+        let (_, evp, topic) = find_event::<Event>(messages.as_slice());
+        assert!(topic.ends_with(&format!("/rooms/{}/events", room.id())));
+        assert_eq!(evp.label(), "event.create");
+    }
+
+    #[tokio::test]
+    async fn create_event_with_expected_head() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let (room, head) = {
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+            shared_helpers::insert_agent(&mut conn, agent.agent_id(), room.id()).await;
+
+            let head = factory::Event::new()
+                .room_id(room.id())
+                .kind("widget")
+                .set("widgets")
+                .label("widget-1")
+                .data(&json!({ "state": 1 }))
+                .occurred_at(1000)
+                .created_by(agent.agent_id())
+                .insert(&mut conn)
+                .await;
+
+            (room, head)
+        };
+
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        let account_id = agent.account_id().to_string();
+
+        let object = vec![
+            "classrooms",
+            &classroom_id,
+            "events",
+            "widget",
+            "authors",
+            &account_id,
+        ];
+
+        authz.allow(agent.account_id(), object, "create");
+
+        let mut context = TestContext::new(db, authz);
+
+        let build_payload = |expected_last_occurred_at| CreateRequest {
+            room_id: room.id(),
+            payload: CreatePayload {
+                kind: String::from("widget"),
+                set: Some(String::from("widgets")),
+                label: Some(String::from("widget-1")),
+                attribute: None,
+                attributes: vec![],
+                data: json!({ "state": 2 }),
+                is_claim: false,
+                is_persistent: true,
+                removed: false,
+                content_encrypted: false,
+                key_id: None,
+                skip_broadcast_if_empty: false,
+                expected_last_occurred_at: Some(expected_last_occurred_at),
+            },
+        };
+
+        // The client has seen the latest event so the update goes through.
+        let messages = handle_request::<CreateHandler>(&mut context, &agent, build_payload(1000))
+            .await
+            .expect("Event creation failed");
+
+        let (event, respp, _) = find_response::<Event>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::CREATED);
+        assert_eq!(event.data(), &json!({ "state": 2 }));
+
+        // Another client still thinks the first event is the latest one.
+        let err = handle_request::<CreateHandler>(&mut context, &agent, build_payload(1000))
+            .await
+            .expect_err("Unexpected success on event creation");
+
+        assert_eq!(err.status(), ResponseStatus::CONFLICT);
+        assert_eq!(err.kind(), "event_conflict");
+
+        assert!(err.detail().contains(&event.id().to_string()));
+        assert!(!err.detail().contains(&head.id().to_string()));
+    }
+
+    #[tokio::test]
+    async fn exceed_payload_size() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            // Create room and put the agent online.
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+            shared_helpers::insert_agent(&mut conn, agent.agent_id(), room.id()).await;
+            room
+        };
+
+        // Allow agent to create events of type `message` in the room.
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        let account_id = agent.account_id().to_string();
+
+        let object = vec![
+            "classrooms",
+            &classroom_id,
+            "pinned",
+            "message",
+            "authors",
+            &account_id,
+        ];
+
+        authz.allow(agent.account_id(), object, "create");
+
+        // Make event.create request.
+        let mut context = TestContext::new_with_payload_size(db, authz, 10);
+
+        let payload = CreateRequest {
+            room_id: room.id(),
+            payload: CreatePayload {
+                kind: String::from("message"),
+                set: Some(String::from("messages")),
+                label: Some(String::from("message-1")),
+                attribute: Some(String::from("pinned")),
+                attributes: vec![],
+                data: json!({ "text": "hello" }),
+                is_claim: false,
+                is_persistent: true,
+                removed: false,
+                content_encrypted: false,
+                key_id: None,
+                skip_broadcast_if_empty: false,
+                expected_last_occurred_at: None,
+            },
+        };
+
+        handle_request::<CreateHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Event creation succeeded");
+    }
+
+    #[tokio::test]
+    async fn exceed_kind_data_size() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+            shared_helpers::insert_agent(&mut conn, agent.agent_id(), room.id()).await;
+            room
+        };
+
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        let account_id = agent.account_id().to_string();
+
+        let object = vec![
+            "classrooms",
+            &classroom_id,
+            "events",
+            "message",
+            "authors",
+            &account_id,
+        ];
+
+        authz.allow(agent.account_id(), object, "create");
+
+        let mut context = TestContext::new(db, authz);
+
+        context.update_config(|config| {
+            config
+                .constraint
+                .event_data_size
+                .insert("message".to_owned(), 10);
+        });
+
+        let payload = CreateRequest {
+            room_id: room.id(),
+            payload: CreatePayload {
+                kind: String::from("message"),
+                set: Some(String::from("messages")),
+                label: Some(String::from("message-1")),
+                attribute: None,
+                attributes: vec![],
+                data: json!({ "text": "hello" }),
+                is_claim: false,
+                is_persistent: true,
+                removed: false,
+                content_encrypted: false,
+                key_id: None,
+                skip_broadcast_if_empty: false,
+                expected_last_occurred_at: None,
+            },
+        };
+
+        let err = handle_request::<CreateHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Event creation succeeded");
+
+        assert_eq!(err.status(), ResponseStatus::PAYLOAD_TOO_LARGE);
+        assert_eq!(err.kind(), "payload_too_large");
+    }
+
+    #[tokio::test]
+    async fn create_locked_event_as_user() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            // Create room and put the agent online.
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+            shared_helpers::insert_agent(&mut conn, agent.agent_id(), room.id()).await;
+            room
+        };
+
+        // Allow agent to create events of type `message` in the room.
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        let account_id = agent.account_id().to_string();
+
+        let object = vec![
+            "classrooms",
+            &classroom_id,
+            "events",
+            "message",
+            "authors",
+            &account_id,
+        ];
+
+        authz.allow(agent.account_id(), object, "create");
+
+        // Make event.create request. It should succeed
+        let mut context = TestContext::new(db.clone(), authz);
+
+        let payload = CreateRequest {
+            room_id: room.id(),
+            payload: CreatePayload {
+                kind: String::from("message"),
+                set: Some(String::from("messages")),
+                label: Some(String::from("message-1")),
+                attribute: None,
+                attributes: vec![],
+                data: json!({ "text": "hello" }),
+                is_claim: false,
+                is_persistent: true,
+                removed: false,
+                content_encrypted: false,
+                key_id: None,
+                skip_broadcast_if_empty: false,
+                expected_last_occurred_at: None,
+            },
+        };
+
+        handle_request::<CreateHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Event creation failed");
+
+        // Lock messages for users
+        {
+            let mut m = HashMap::new();
+            m.insert("message".into(), true);
+            let q = db::room::UpdateQuery::new(room.id()).locked_types(m);
+            let mut conn = db.get_conn().await;
+            q.execute(&mut conn).await.expect("Failed to lock type");
+        }
+
+        // Make event.create request. Now it should fail since we locked kind='message' events
+        let payload = CreateRequest {
+            room_id: room.id(),
+            payload: CreatePayload {
+                kind: String::from("message"),
+                set: Some(String::from("messages")),
+                label: Some(String::from("message-2")),
+                attribute: None,
+                attributes: vec![],
+                data: json!({ "text": "locked chat hello" }),
+                is_claim: false,
+                is_persistent: true,
+                removed: false,
+                content_encrypted: false,
+                key_id: None,
+                skip_broadcast_if_empty: false,
+                expected_last_occurred_at: None,
+            },
+        };
+
+        handle_request::<CreateHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Event creation succeeded");
+    }
+
+    #[tokio::test]
+    async fn create_locked_event_as_room_updater() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            // Create room and put the agent online.
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+            shared_helpers::insert_agent(&mut conn, agent.agent_id(), room.id()).await;
+            room
+        };
+
+        // Allow agent to create events of type `message` in the room.
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        let account_id = agent.account_id().to_string();
+
+        let object = vec![
+            "classrooms",
+            &classroom_id,
+            "events",
+            "message",
+            "authors",
+            &account_id,
+        ];
+
+        authz.allow(agent.account_id(), object, "create");
+
+        let object = vec!["classrooms", &classroom_id];
+        authz.allow(agent.account_id(), object, "update");
+
+        // Make event.create request.
+        let mut context = TestContext::new(db.clone(), authz);
+
+        let payload = CreateRequest {
+            room_id: room.id(),
+            payload: CreatePayload {
+                kind: String::from("message"),
+                set: Some(String::from("messages")),
+                label: Some(String::from("message-1")),
+                attribute: None,
+                attributes: vec![],
+                data: json!({ "text": "hello" }),
+                is_claim: false,
+                is_persistent: true,
+                removed: false,
+                content_encrypted: false,
+                key_id: None,
+                skip_broadcast_if_empty: false,
+                expected_last_occurred_at: None,
+            },
+        };
+
+        handle_request::<CreateHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Event creation failed");
+
+        // Lock messages for users
+        {
+            let mut m = HashMap::new();
+            m.insert("message".into(), true);
+            let q = db::room::UpdateQuery::new(room.id()).locked_types(m);
+            let mut conn = db.get_conn().await;
+            q.execute(&mut conn).await.expect("Failed to lock type");
+        }
+
+        let payload = CreateRequest {
+            room_id: room.id(),
+            payload: CreatePayload {
+                kind: String::from("message"),
+                set: Some(String::from("messages")),
+                label: Some(String::from("message-2")),
+                attribute: None,
+                attributes: vec![],
+                data: json!({ "text": "locked chat hello" }),
+                is_claim: false,
+                is_persistent: true,
+                removed: false,
+                content_encrypted: false,
+                key_id: None,
+                skip_broadcast_if_empty: false,
+                expected_last_occurred_at: None,
+            },
+        };
+
+        handle_request::<CreateHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Event creation failed");
+    }
+
+    #[tokio::test]
+    async fn create_next_event() {
+        let db = TestDb::new().await;
+        let original_author = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let agent = TestAgent::new("web", "moderator", USR_AUDIENCE);
+
+        let room = {
+            // Create room.
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+
+            // Add an event to the room.
+            factory::Event::new()
+                .room_id(room.id())
+                .kind("message")
+                .set("messages")
+                .label("message-1")
+                .data(&json!({ "text": "original text" }))
+                .occurred_at(1_000_000_000)
+                .created_by(&original_author.agent_id())
+                .insert(&mut conn)
+                .await;
+
+            // Put the agent online.
+            shared_helpers::insert_agent(&mut conn, agent.agent_id(), room.id()).await;
+            room
+        };
+
+        // Allow agent to create events of type `message` in the room.
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+
+        // Should authorize with the author of the original event.
+        let account_id = original_author.agent_id().as_account_id().to_string();
+
+        let object = vec![
+            "classrooms",
+            &classroom_id,
+            "events",
+            "message",
+            "authors",
+            &account_id,
+        ];
+
+        authz.allow(agent.account_id(), object, "create");
+
+        // Make event.create request with the same set/label as existing event.
+        let mut context = TestContext::new(db, authz);
+
+        let payload = CreateRequest {
+            room_id: room.id(),
+            payload: CreatePayload {
+                kind: String::from("message"),
+                set: Some(String::from("messages")),
+                label: Some(String::from("message-1")),
+                attribute: None,
+                attributes: vec![],
+                data: json!({ "text": "modified text" }),
+                is_claim: false,
+                is_persistent: true,
+                removed: false,
+                content_encrypted: false,
+                key_id: None,
+                skip_broadcast_if_empty: false,
+                expected_last_occurred_at: None,
+            },
+        };
+
+        let messages = handle_request::<CreateHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Event creation failed");
+
+        // Assert response.
+        let (event, respp, _) = find_response::<Event>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::CREATED);
+        assert_eq!(event.created_by(), agent.agent_id());
+    }
+
+    #[tokio::test]
+    async fn create_event_with_multiple_attributes() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+            shared_helpers::insert_agent(&mut conn, agent.agent_id(), room.id()).await;
+            room
+        };
+
+        // Allow agent to create events with both attributes.
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        let account_id = agent.account_id().to_string();
+
+        for attribute in ["pinned", "important"] {
+            let object = vec![
+                "classrooms",
+                &classroom_id,
+                attribute,
+                "message",
+                "authors",
+                &account_id,
+            ];
+
+            authz.allow(agent.account_id(), object, "create");
+        }
+
+        // Make event.create request.
+        let mut context = TestContext::new(db, authz);
+
+        let payload = CreateRequest {
+            room_id: room.id(),
+            payload: CreatePayload {
+                kind: String::from("message"),
+                set: None,
+                label: None,
+                attribute: Some(String::from("pinned")),
+                attributes: vec![String::from("important"), String::from("pinned")],
+                data: json!({ "text": "hello" }),
+                is_claim: false,
+                is_persistent: true,
+                removed: false,
+                content_encrypted: false,
+                key_id: None,
+                skip_broadcast_if_empty: false,
+                expected_last_occurred_at: None,
+            },
+        };
+
+        let messages = handle_request::<CreateHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Event creation failed");
+
+        // Legacy attribute goes first and duplicates are dropped.
+        let (event, respp, _) = find_response::<Event>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::CREATED);
+        assert_eq!(event.attribute(), Some("pinned"));
+        assert_eq!(event.attributes(), ["pinned", "important"]);
+    }
+
+    #[tokio::test]
+    async fn create_claim() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            // Create room and put the agent online.
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+            shared_helpers::insert_agent(&mut conn, agent.agent_id(), room.id()).await;
+            room
+        };
+
+        // Allow agent to create claims of type `block` in the room.
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        let account_id = agent.account_id().to_string();
+        let object = vec![
+            "classrooms",
+            &classroom_id,
+            "claims",
+            "block",
+            "authors",
+            &account_id,
+        ];
+        authz.allow(agent.account_id(), object, "create");
+
+        // Make event.create request.
+        let mut context = TestContext::new(db, authz);
+
+        let payload = CreateRequest {
+            room_id: room.id(),
+            payload: CreatePayload {
+                kind: String::from("block"),
+                set: Some(String::from("blocks")),
+                label: Some(String::from("user-1")),
+                attribute: None,
+                attributes: vec![],
+                data: json!({ "blocked": true }),
+                is_claim: true,
+                is_persistent: true,
+                removed: false,
+                content_encrypted: false,
+                key_id: None,
+                skip_broadcast_if_empty: false,
+                expected_last_occurred_at: None,
+            },
+        };
+
+        let messages = handle_request::<CreateHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Event creation failed");
+
+        assert_eq!(messages.len(), 3);
+
+        // Assert response.
+        let (event, respp, _) = find_response::<Event>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::CREATED);
+        assert_eq!(event.room_id(), room.id());
+        assert_eq!(event.kind(), "block");
+        assert_eq!(event.set(), "blocks");
+        assert_eq!(event.label(), Some("user-1"));
+        assert_eq!(event.data(), &json!({ "blocked": true }));
+
+        // Assert tenant & room notifications.
+        let mut has_tenant_notification = false;
+        let mut has_room_notification = false;
+
+        for message in messages {
+            if let OutgoingEnvelopeProperties::Event(evp) = message.properties() {
+                let topic = message.topic();
+
+                if topic.ends_with(&format!("/audiences/{}/events", room.audience())) {
+                    has_tenant_notification = true;
+                }
+
+                if topic.ends_with(&format!("/rooms/{}/events", room.id())) {
+                    has_room_notification = true;
+                }
+
+                assert_eq!(evp.label(), "event.create");
+
+                let event = message.payload::<Event>();
+                assert_eq!(event.room_id(), room.id());
+                assert_eq!(event.kind(), "block");
+                assert_eq!(event.set(), "blocks");
+                assert_eq!(event.label(), Some("user-1"));
+                assert_eq!(event.data(), &json!({ "blocked": true }));
+            }
+        }
+
+        assert_eq!(has_tenant_notification, true);
+        assert_eq!(has_room_notification, true);
+    }
+
+    #[tokio::test]
+    async fn create_transient_event() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            // Create room and put the agent online.
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+            shared_helpers::insert_agent(&mut conn, agent.agent_id(), room.id()).await;
+            room
+        };
+
+        // Allow agent to create events of type `message` in the room.
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        let account_id = agent.account_id().to_string();
+
+        let object = vec![
+            "classrooms",
+            &classroom_id,
+            "events",
+            "cursor",
+            "authors",
+            &account_id,
+        ];
+
+        authz.allow(agent.account_id(), object, "create");
+
+        // Make event.create request.
+        let mut context = TestContext::new(db, authz);
+
+        let data = json!({
+            "agent_id": agent.agent_id().to_string(),
+            "x": 123,
+            "y": 456,
+        });
+
+        let payload = CreateRequest {
+            room_id: room.id(),
+            payload: CreatePayload {
+                kind: String::from("cursor"),
+                set: None,
+                label: None,
+                attribute: None,
+                attributes: vec![],
+                data: data.clone(),
+                is_claim: false,
+                is_persistent: false,
+                removed: false,
+                content_encrypted: false,
+                key_id: None,
+                skip_broadcast_if_empty: false,
+                expected_last_occurred_at: None,
+            },
+        };
+
+        let messages = handle_request::<CreateHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Event creation failed");
+
+        assert_eq!(messages.len(), 2);
+
+        // Assert response.
+        let (event, respp, _) = find_response::<Event>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::CREATED);
+        assert_eq!(event.room_id(), room.id());
+        assert_eq!(event.kind(), "cursor");
+        assert_eq!(event.set(), "cursor");
+        assert_eq!(event.label(), None);
+        assert_eq!(event.data(), &data);
+
+        // Assert notification.
+        let (event, evp, topic) = find_event::<Event>(messages.as_slice());
+        assert!(topic.ends_with(&format!("/rooms/{}/events", room.id())));
+        assert_eq!(evp.label(), "event.create");
+        assert_eq!(event.room_id(), room.id());
+        assert_eq!(event.kind(), "cursor");
+        assert_eq!(event.set(), "cursor");
+        assert_eq!(event.label(), None);
+        assert_eq!(event.data(), &data);
+    }
+
+    #[tokio::test]
+    async fn create_encrypted_event() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+            shared_helpers::insert_agent(&mut conn, agent.agent_id(), room.id()).await;
+            room
+        };
+
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        let account_id = agent.account_id().to_string();
+
+        let object = vec![
+            "classrooms",
+            &classroom_id,
+            "events",
+            "draw",
+            "authors",
+            &account_id,
+        ];
+
+        authz.allow(agent.account_id(), object, "create");
+
+        let mut context = TestContext::new(db, authz);
+
+        // Draw events are not converted to the binary format when encrypted.
+        let payload = CreateRequest {
+            room_id: room.id(),
+            payload: CreatePayload {
+                kind: String::from("draw"),
+                set: None,
+                label: None,
+                attribute: None,
+                attributes: vec![],
+                data: json!("c2VjcmV0IGRyYXdpbmc="),
+                is_claim: false,
+                is_persistent: true,
+                removed: false,
+                content_encrypted: true,
+                key_id: Some(String::from("key-1")),
+                skip_broadcast_if_empty: false,
+                expected_last_occurred_at: None,
+            },
+        };
+
+        let messages = handle_request::<CreateHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Event creation failed");
+
+        let (event, respp, _) = find_response::<Event>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::CREATED);
+        assert!(event.content_encrypted());
+        assert_eq!(event.key_id(), Some("key-1"));
+        assert_eq!(event.data(), &json!("c2VjcmV0IGRyYXdpbmc="));
+
+        let (event, _, _) = find_event::<Event>(messages.as_slice());
+        assert!(event.content_encrypted());
+        assert_eq!(event.key_id(), Some("key-1"));
+
+        // The event is read back as is.
+        let mut conn = context.db().acquire().await.expect("Failed to get conn");
+
+        let events = db::event::ListQuery::new()
+            .room_id(room.id())
+            .execute(&mut conn)
+            .await
+            .expect("Failed to list events");
+
+        assert_eq!(events.len(), 1);
+        assert!(events[0].content_encrypted());
+        assert_eq!(events[0].key_id(), Some("key-1"));
+        assert_eq!(events[0].data(), &json!("c2VjcmV0IGRyYXdpbmc="));
+    }
+
+    #[tokio::test]
+    async fn create_event_encrypted_at_rest() {
+        use base64::Engine;
+
+        use crate::config::DataEncryptionConfig;
+        use crate::db::event::DataEncryption;
+
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+            shared_helpers::insert_agent(&mut conn, agent.agent_id(), room.id()).await;
+            room
+        };
+
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        let account_id = agent.account_id().to_string();
+
+        let object = vec![
+            "classrooms",
+            &classroom_id,
+            "events",
+            "private_note",
+            "authors",
+            &account_id,
+        ];
+
+        authz.allow(agent.account_id(), object.clone(), "create");
+        authz.allow(
+            agent.account_id(),
+            vec!["classrooms", &classroom_id],
+            "read",
+        );
+
+        let mut context = TestContext::new(db, authz);
+
+        let config = DataEncryptionConfig {
+            kinds: vec![String::from("private_note")],
+            key_id: String::from("k1"),
+            keys: [(
+                String::from("k1"),
+                base64::engine::general_purpose::STANDARD.encode([1; 32]),
+            )]
+            .into_iter()
+            .collect(),
+            allow_dump: false,
+        };
+
+        context.set_data_encryption(
+            DataEncryption::new(&config).expect("Failed to build data encryption"),
+        );
+
+        let build_payload = |content_encrypted: bool| CreateRequest {
+            room_id: room.id(),
+            payload: CreatePayload {
+                kind: String::from("private_note"),
+                set: None,
+                label: None,
+                attribute: None,
+                attributes: vec![],
+                data: match content_encrypted {
+                    true => json!("c2VjcmV0"),
+                    false => json!({ "text": "secret" }),
+                },
+                is_claim: false,
+                is_persistent: true,
+                removed: false,
+                content_encrypted,
+                key_id: None,
+                skip_broadcast_if_empty: false,
+                expected_last_occurred_at: None,
+            },
+        };
+
+        let messages = handle_request::<CreateHandler>(&mut context, &agent, build_payload(false))
+            .await
+            .expect("Event creation failed");
+
+        // Clients get plain data.
+        let (event, respp, _) = find_response::<Event>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::CREATED);
+        assert!(!event.content_encrypted());
+        assert_eq!(event.data(), &json!({ "text": "secret" }));
+
+        let (event, _, _) = find_event::<Event>(messages.as_slice());
+        assert!(!event.content_encrypted());
+        assert_eq!(event.data(), &json!({ "text": "secret" }));
+
+        // While it's stored encrypted.
+        {
+            let mut conn = context.db().acquire().await.expect("Failed to get conn");
+
+            let events = db::event::ListQuery::new()
+                .room_id(room.id())
+                .execute(&mut conn)
+                .await
+                .expect("Failed to list events");
+
+            assert_eq!(events.len(), 1);
+            assert!(events[0].content_encrypted());
+            assert_eq!(events[0].key_id(), Some("k1"));
+            assert!(events[0].data().is_string());
+            assert!(!events[0].data().to_string().contains("secret"));
+        }
+
+        let payload = ListRequest {
+            room_id: room.id(),
+            payload: ListPayload {
+                kind: Some(ListTypesFilter::Single(String::from("private_note"))),
+                set: None,
+                label: None,
+                label_prefix: None,
+                attribute: None,
+                last_occurred_at: None,
+                direction: Direction::Forward,
+                limit: None,
+                fields: None,
+                binary_data: false,
+                from_occurred_at: None,
+                to_occurred_at: None,
+                include_deleted: false,
+            },
+            if_none_match: None,
+        };
+
+        let messages = handle_request::<ListHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Events listing failed");
+
+        let (events, _, _) = find_response::<Vec<Event>>(messages.as_slice());
+        assert_eq!(events.len(), 1);
+        assert!(!events[0].content_encrypted());
+        assert_eq!(events[0].data(), &json!({ "text": "secret" }));
+
+        // Only the service encrypts events of the kind.
+        let err = handle_request::<CreateHandler>(&mut context, &agent, build_payload(true))
+            .await
+            .expect_err("Unexpected success creating client-side encrypted event");
+
+        assert_eq!(err.kind(), "invalid_event");
+    }
+
+    #[tokio::test]
+    async fn create_draw_event_with_unknown_shape() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+            shared_helpers::insert_agent(&mut conn, agent.agent_id(), room.id()).await;
+            room
+        };
+
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        let account_id = agent.account_id().to_string();
+
+        let object = vec![
+            "classrooms",
+            &classroom_id,
+            "events",
+            "draw",
+            "authors",
+            &account_id,
+        ];
+
+        authz.allow(agent.account_id(), object, "create");
+
+        let mut context = TestContext::new(db, authz);
+
+        let mut data = crate::db::event::CompactEvent::test_rect_event()
+            .into_json()
+            .unwrap();
+
+        data["type"] = json!("hexagon");
+
+        let payload = CreateRequest {
+            room_id: room.id(),
+            payload: CreatePayload {
+                kind: String::from("draw"),
+                set: None,
+                label: None,
+                attribute: None,
+                attributes: vec![],
+                data,
+                is_claim: false,
+                is_persistent: true,
+                removed: false,
+                content_encrypted: false,
+                key_id: None,
+                skip_broadcast_if_empty: false,
+                expected_last_occurred_at: None,
+            },
+        };
+
+        let err = handle_request::<CreateHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success creating draw event with unknown shape");
+
+        assert_eq!(err.status(), ResponseStatus::UNPROCESSABLE_ENTITY);
+        assert_eq!(err.kind(), "invalid_event");
+
+        let rejects = context
+            .metrics()
+            .draw_event_rejects
+            .with_label_values(&["unknown_shape"])
+            .get();
+
+        assert_eq!(rejects, 1);
+    }
+
+    #[tokio::test]
+    async fn create_encrypted_event_with_json_data() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+            shared_helpers::insert_agent(&mut conn, agent.agent_id(), room.id()).await;
+            room
+        };
+
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        let account_id = agent.account_id().to_string();
+
+        let object = vec![
+            "classrooms",
+            &classroom_id,
+            "events",
+            "message",
+            "authors",
+            &account_id,
+        ];
+
+        authz.allow(agent.account_id(), object, "create");
+
+        let mut context = TestContext::new(db, authz);
+
+        let payload = CreateRequest {
+            room_id: room.id(),
+            payload: CreatePayload {
+                kind: String::from("message"),
+                set: None,
+                label: None,
+                attribute: None,
+                attributes: vec![],
+                data: json!({ "text": "hello" }),
+                is_claim: false,
+                is_persistent: true,
+                removed: false,
+                content_encrypted: true,
+                key_id: None,
+                skip_broadcast_if_empty: false,
+                expected_last_occurred_at: None,
+            },
+        };
+
+        let err = handle_request::<CreateHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success creating encrypted event with JSON data");
+
+        assert_eq!(err.status(), ResponseStatus::UNPROCESSABLE_ENTITY);
+        assert_eq!(err.kind(), "invalid_event");
+    }
+
+    #[tokio::test]
+    async fn create_event_not_authorized() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            // Create room and put the agent online.
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+            shared_helpers::insert_agent(&mut conn, agent.agent_id(), room.id()).await;
+            room
+        };
+
+        // Make event.create request.
+        let mut context = TestContext::new(db, TestAuthz::new());
+
+        let payload = CreateRequest {
+            room_id: room.id(),
+            payload: CreatePayload {
+                kind: String::from("message"),
+                set: Some(String::from("messages")),
+                label: Some(String::from("message-1")),
+                attribute: None,
+                attributes: vec![],
+                data: json!({ "text": "hello" }),
+                is_claim: false,
+                is_persistent: true,
+                removed: false,
+                content_encrypted: false,
+                key_id: None,
+                skip_broadcast_if_empty: false,
+                expected_last_occurred_at: None,
+            },
+        };
+
+        let err = handle_request::<CreateHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success on event creation");
+
+        assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+    }
 
     #[tokio::test]
-    async fn create_event() {
+    async fn create_event_not_entered() {
         let db = TestDb::new().await;
         let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
 
         let room = {
-            // Create room and put the agent online.
+            // Create room.
             let mut conn = db.get_conn().await;
-            let room = shared_helpers::insert_room(&mut conn).await;
-            shared_helpers::insert_agent(&mut conn, agent.agent_id(), room.id()).await;
-            room
+            shared_helpers::insert_room(&mut conn).await
         };
 
         // Allow agent to create events of type `message` in the room.
@@ -472,7 +3141,7 @@ mod tests {
         let object = vec![
             "classrooms",
             &classroom_id,
-            "pinned",
+            "events",
             "message",
             "authors",
             &account_id,
@@ -489,11 +3158,16 @@ mod tests {
                 kind: String::from("message"),
                 set: Some(String::from("messages")),
                 label: Some(String::from("message-1")),
-                attribute: Some(String::from("pinned")),
+                attribute: None,
+                attributes: vec![],
                 data: json!({ "text": "hello" }),
                 is_claim: false,
                 is_persistent: true,
                 removed: false,
+                content_encrypted: false,
+                key_id: None,
+                skip_broadcast_if_empty: false,
+                expected_last_occurred_at: None,
             },
         };
 
@@ -502,38 +3176,17 @@ mod tests {
             .expect("Event creation failed");
 
         assert_eq!(messages.len(), 2);
-
-        // Assert response.
-        let (event, respp, _) = find_response::<Event>(messages.as_slice());
-        assert_eq!(respp.status(), ResponseStatus::CREATED);
-        assert_eq!(event.room_id(), room.id());
-        assert_eq!(event.kind(), "message");
-        assert_eq!(event.set(), "messages");
-        assert_eq!(event.label(), Some("message-1"));
-        assert_eq!(event.attribute(), Some("pinned"));
-        assert_eq!(event.data(), &json!({ "text": "hello" }));
-
-        // Assert notification.
-        let (event, evp, topic) = find_event::<Event>(messages.as_slice());
-        assert!(topic.ends_with(&format!("/rooms/{}/events", room.id())));
-        assert_eq!(evp.label(), "event.create");
-        assert_eq!(event.room_id(), room.id());
-        assert_eq!(event.kind(), "message");
-        assert_eq!(event.set(), "messages");
-        assert_eq!(event.label(), Some("message-1"));
-        assert_eq!(event.attribute(), Some("pinned"));
-        assert_eq!(event.data(), &json!({ "text": "hello" }));
     }
 
     #[tokio::test]
-    async fn exceed_payload_size() {
+    async fn create_event_closed_room() {
         let db = TestDb::new().await;
         let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
 
         let room = {
-            // Create room and put the agent online.
+            // Create closed room and put the agent online.
             let mut conn = db.get_conn().await;
-            let room = shared_helpers::insert_room(&mut conn).await;
+            let room = shared_helpers::insert_closed_room(&mut conn).await;
             shared_helpers::insert_agent(&mut conn, agent.agent_id(), room.id()).await;
             room
         };
@@ -546,7 +3199,7 @@ mod tests {
         let object = vec![
             "classrooms",
             &classroom_id,
-            "pinned",
+            "events",
             "message",
             "authors",
             &account_id,
@@ -555,7 +3208,7 @@ mod tests {
         authz.allow(agent.account_id(), object, "create");
 
         // Make event.create request.
-        let mut context = TestContext::new_with_payload_size(db, authz, 10);
+        let mut context = TestContext::new(db, authz);
 
         let payload = CreateRequest {
             room_id: room.id(),
@@ -563,112 +3216,166 @@ mod tests {
                 kind: String::from("message"),
                 set: Some(String::from("messages")),
                 label: Some(String::from("message-1")),
-                attribute: Some(String::from("pinned")),
+                attribute: None,
+                attributes: vec![],
                 data: json!({ "text": "hello" }),
                 is_claim: false,
                 is_persistent: true,
                 removed: false,
+                content_encrypted: false,
+                key_id: None,
+                skip_broadcast_if_empty: false,
+                expected_last_occurred_at: None,
             },
         };
 
-        handle_request::<CreateHandler>(&mut context, &agent, payload)
+        let err = handle_request::<CreateHandler>(&mut context, &agent, payload)
             .await
-            .expect_err("Event creation succeeded");
+            .expect_err("Unexpected success on event creation");
+
+        assert_eq!(err.status(), ResponseStatus::NOT_FOUND);
+        assert_eq!(err.kind(), "room_closed");
     }
 
     #[tokio::test]
-    async fn create_locked_event_as_user() {
+    async fn create_event_missing_room() {
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let mut context = TestContext::new(TestDb::new().await, TestAuthz::new());
+
+        let payload = CreateRequest {
+            room_id: Uuid::new_v4(),
+            payload: CreatePayload {
+                kind: String::from("message"),
+                set: Some(String::from("messages")),
+                label: Some(String::from("message-1")),
+                attribute: None,
+                attributes: vec![],
+                data: json!({ "text": "hello" }),
+                is_claim: false,
+                is_persistent: true,
+                removed: false,
+                content_encrypted: false,
+                key_id: None,
+                skip_broadcast_if_empty: false,
+                expected_last_occurred_at: None,
+            },
+        };
+
+        let err = handle_request::<CreateHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success on event creation");
+
+        assert_eq!(err.status(), ResponseStatus::NOT_FOUND);
+        assert_eq!(err.kind(), "room_not_found");
+    }
+
+    #[tokio::test]
+    async fn create_whiteboard_event_without_whiteboard_access() {
         let db = TestDb::new().await;
         let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
 
         let room = {
             // Create room and put the agent online.
             let mut conn = db.get_conn().await;
-            let room = shared_helpers::insert_room(&mut conn).await;
+            let room = shared_helpers::insert_validating_whiteboard_access_room(&mut conn).await;
             shared_helpers::insert_agent(&mut conn, agent.agent_id(), room.id()).await;
             room
         };
 
-        // Allow agent to create events of type `message` in the room.
+        // Allow agent to create events of type `draw` in the room.
         let mut authz = TestAuthz::new();
-        let classroom_id = room.classroom_id().to_string();
         let account_id = agent.account_id().to_string();
+        let classroom_id = room.classroom_id().to_string();
 
         let object = vec![
             "classrooms",
             &classroom_id,
             "events",
-            "message",
+            "draw",
             "authors",
             &account_id,
         ];
 
         authz.allow(agent.account_id(), object, "create");
 
-        // Make event.create request. It should succeed
         let mut context = TestContext::new(db.clone(), authz);
 
+        // Make event.create request. It should fail
         let payload = CreateRequest {
             room_id: room.id(),
             payload: CreatePayload {
-                kind: String::from("message"),
-                set: Some(String::from("messages")),
-                label: Some(String::from("message-1")),
+                kind: String::from("draw"),
+                set: Some(String::from("set")),
+                label: Some(String::from("label-1")),
                 attribute: None,
-                data: json!({ "text": "hello" }),
+                attributes: vec![],
+                data: json!({ "foo": "bar" }),
                 is_claim: false,
                 is_persistent: true,
                 removed: false,
+                content_encrypted: false,
+                key_id: None,
+                skip_broadcast_if_empty: false,
+                expected_last_occurred_at: None,
             },
         };
 
         handle_request::<CreateHandler>(&mut context, &agent, payload)
             .await
-            .expect("Event creation failed");
+            .expect_err("Event creation succeeded");
 
-        // Lock messages for users
+        // Update whiteboard access for the agent
         {
             let mut m = HashMap::new();
-            m.insert("message".into(), true);
-            let q = db::room::UpdateQuery::new(room.id()).locked_types(m);
+            m.insert(agent.account_id().to_owned(), true);
+            let q = db::room::UpdateQuery::new(room.id()).whiteboard_access(m);
             let mut conn = db.get_conn().await;
-            q.execute(&mut conn).await.expect("Failed to lock type");
+            q.execute(&mut conn)
+                .await
+                .expect("Failed to update whiteboard access");
         }
 
-        // Make event.create request. Now it should fail since we locked kind='message' events
+        // Make event.create request. Now it should succeed
         let payload = CreateRequest {
             room_id: room.id(),
             payload: CreatePayload {
-                kind: String::from("message"),
-                set: Some(String::from("messages")),
-                label: Some(String::from("message-2")),
+                kind: String::from("draw"),
+                set: Some(String::from("set")),
+                label: Some(String::from("label-2")),
                 attribute: None,
-                data: json!({ "text": "locked chat hello" }),
+                attributes: vec![],
+                data: crate::db::event::CompactEvent::test_rect_event()
+                    .into_json()
+                    .unwrap(),
                 is_claim: false,
                 is_persistent: true,
                 removed: false,
+                content_encrypted: false,
+                key_id: None,
+                skip_broadcast_if_empty: false,
+                expected_last_occurred_at: None,
             },
         };
 
         handle_request::<CreateHandler>(&mut context, &agent, payload)
             .await
-            .expect_err("Event creation succeeded");
+            .expect("Failed to create event");
     }
 
     #[tokio::test]
-    async fn create_locked_event_as_room_updater() {
+    async fn create_whiteboard_event_as_room_updater() {
         let db = TestDb::new().await;
         let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
 
         let room = {
             // Create room and put the agent online.
             let mut conn = db.get_conn().await;
-            let room = shared_helpers::insert_room(&mut conn).await;
+            let room = shared_helpers::insert_validating_whiteboard_access_room(&mut conn).await;
             shared_helpers::insert_agent(&mut conn, agent.agent_id(), room.id()).await;
             room
         };
 
-        // Allow agent to create events of type `message` in the room.
+        // Allow agent to create events of type `draw` in the room.
         let mut authz = TestAuthz::new();
         let classroom_id = room.classroom_id().to_string();
         let account_id = agent.account_id().to_string();
@@ -677,99 +3384,153 @@ mod tests {
             "classrooms",
             &classroom_id,
             "events",
-            "message",
+            "draw",
             "authors",
             &account_id,
         ];
 
         authz.allow(agent.account_id(), object, "create");
 
-        let object = vec!["classrooms", &classroom_id];
-        authz.allow(agent.account_id(), object, "update");
-
-        // Make event.create request.
-        let mut context = TestContext::new(db.clone(), authz);
+        let mut context = TestContext::new(db.clone(), authz.clone());
 
         let payload = CreateRequest {
             room_id: room.id(),
             payload: CreatePayload {
-                kind: String::from("message"),
-                set: Some(String::from("messages")),
-                label: Some(String::from("message-1")),
+                kind: String::from("draw"),
+                set: Some(String::from("set")),
+                label: Some(String::from("label-2")),
                 attribute: None,
-                data: json!({ "text": "hello" }),
+                attributes: vec![],
+                data: crate::db::event::CompactEvent::test_rect_event()
+                    .into_json()
+                    .unwrap(),
                 is_claim: false,
                 is_persistent: true,
                 removed: false,
+                content_encrypted: false,
+                key_id: None,
+                skip_broadcast_if_empty: false,
+                expected_last_occurred_at: None,
             },
         };
 
+        // This must fail since user has no room-update access and whiteboard access map is empty
+        handle_request::<CreateHandler>(&mut context, &agent, payload.clone())
+            .await
+            .expect_err("Event creation succeeded");
+
+        let object = vec!["classrooms", &classroom_id];
+        authz.allow(agent.account_id(), object, "update");
+        let mut context = TestContext::new(db.clone(), authz);
+
+        // This must succeed cause even though whiteboard access map is empty user is allowed to update the room
         handle_request::<CreateHandler>(&mut context, &agent, payload)
             .await
             .expect("Event creation failed");
+    }
 
-        // Lock messages for users
-        {
-            let mut m = HashMap::new();
-            m.insert("message".into(), true);
-            let q = db::room::UpdateQuery::new(room.id()).locked_types(m);
+    ///////////////////////////////////////////////////////////////////////////
+
+    #[tokio::test]
+    async fn import_events() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "migration", SVC_AUDIENCE);
+        let author = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
             let mut conn = db.get_conn().await;
-            q.execute(&mut conn).await.expect("Failed to lock type");
-        }
+            shared_helpers::insert_closed_room(&mut conn).await
+        };
 
-        let payload = CreateRequest {
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        authz.allow(
+            agent.account_id(),
+            vec!["classrooms", &classroom_id],
+            "import",
+        );
+
+        let mut context = TestContext::new(db, authz);
+        let created_at = Utc.timestamp_millis_opt(1_600_000_000_000).unwrap();
+
+        let payload = ImportRequest {
             room_id: room.id(),
-            payload: CreatePayload {
-                kind: String::from("message"),
-                set: Some(String::from("messages")),
-                label: Some(String::from("message-2")),
-                attribute: None,
-                data: json!({ "text": "locked chat hello" }),
-                is_claim: false,
-                is_persistent: true,
-                removed: false,
+            payload: ImportPayload {
+                events: vec![
+                    ImportEvent {
+                        kind: String::from("message"),
+                        set: Some(String::from("messages")),
+                        label: Some(String::from("message-1")),
+                        attributes: vec![],
+                        data: json!({ "text": "hello" }),
+                        occurred_at: 1000,
+                        created_by: author.agent_id().to_owned(),
+                        created_at,
+                        removed: false,
+                    },
+                    ImportEvent {
+                        kind: String::from("message"),
+                        set: Some(String::from("messages")),
+                        label: Some(String::from("message-2")),
+                        attributes: vec![String::from("pinned")],
+                        data: json!({ "text": "bye" }),
+                        occurred_at: 2000,
+                        created_by: author.agent_id().to_owned(),
+                        created_at,
+                        removed: false,
+                    },
+                ],
             },
         };
 
-        handle_request::<CreateHandler>(&mut context, &agent, payload)
+        let messages = handle_request::<ImportHandler>(&mut context, &agent, payload)
             .await
-            .expect("Event creation failed");
+            .expect("Events import failed");
+
+        // Imported events are not broadcasted.
+        assert_eq!(messages.len(), 1);
+        let (resp, respp, _) = find_response::<JsonValue>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::CREATED);
+        assert_eq!(resp, json!({ "imported": 2 }));
+
+        let mut conn = context.db().acquire().await.expect("Failed to get conn");
+
+        let events = db::event::ListQuery::new()
+            .room_id(room.id())
+            .execute(&mut conn)
+            .await
+            .expect("Failed to list events");
+
+        assert_eq!(events.len(), 2);
+
+        for event in &events {
+            assert_eq!(event.created_by(), author.agent_id());
+            assert_eq!(event.created_at(), created_at);
+        }
+
+        let event = events
+            .iter()
+            .find(|e| e.label() == Some("message-2"))
+            .expect("Imported event not found");
+
+        assert_eq!(event.occurred_at(), 2000);
+        assert_eq!(event.attribute(), Some("pinned"));
     }
 
     #[tokio::test]
-    async fn create_next_event() {
+    async fn import_events_not_authorized() {
         let db = TestDb::new().await;
-        let original_author = TestAgent::new("web", "user123", USR_AUDIENCE);
-        let agent = TestAgent::new("web", "moderator", USR_AUDIENCE);
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
 
         let room = {
-            // Create room.
             let mut conn = db.get_conn().await;
-            let room = shared_helpers::insert_room(&mut conn).await;
-
-            // Add an event to the room.
-            factory::Event::new()
-                .room_id(room.id())
-                .kind("message")
-                .set("messages")
-                .label("message-1")
-                .data(&json!({ "text": "original text" }))
-                .occurred_at(1_000_000_000)
-                .created_by(&original_author.agent_id())
-                .insert(&mut conn)
-                .await;
-
-            // Put the agent online.
-            shared_helpers::insert_agent(&mut conn, agent.agent_id(), room.id()).await;
-            room
+            shared_helpers::insert_room(&mut conn).await
         };
 
-        // Allow agent to create events of type `message` in the room.
+        // Those who can create events still can't import them.
         let mut authz = TestAuthz::new();
         let classroom_id = room.classroom_id().to_string();
-
-        // Should authorize with the author of the original event.
-        let account_id = original_author.agent_id().as_account_id().to_string();
+        let account_id = agent.account_id().to_string();
 
         let object = vec![
             "classrooms",
@@ -782,137 +3543,138 @@ mod tests {
 
         authz.allow(agent.account_id(), object, "create");
 
-        // Make event.create request with the same set/label as existing event.
         let mut context = TestContext::new(db, authz);
 
-        let payload = CreateRequest {
-            room_id: room.id(),
-            payload: CreatePayload {
-                kind: String::from("message"),
-                set: Some(String::from("messages")),
-                label: Some(String::from("message-1")),
-                attribute: None,
-                data: json!({ "text": "modified text" }),
-                is_claim: false,
-                is_persistent: true,
-                removed: false,
+        let payload = ImportRequest {
+            room_id: room.id(),
+            payload: ImportPayload {
+                events: vec![ImportEvent {
+                    kind: String::from("message"),
+                    set: None,
+                    label: None,
+                    attributes: vec![],
+                    data: json!({ "text": "hello" }),
+                    occurred_at: 1000,
+                    created_by: agent.agent_id().to_owned(),
+                    created_at: Utc::now(),
+                    removed: false,
+                }],
             },
         };
 
-        let messages = handle_request::<CreateHandler>(&mut context, &agent, payload)
+        let err = handle_request::<ImportHandler>(&mut context, &agent, payload)
             .await
-            .expect("Event creation failed");
+            .expect_err("Unexpected success on events import");
 
-        // Assert response.
-        let (event, respp, _) = find_response::<Event>(messages.as_slice());
-        assert_eq!(respp.status(), ResponseStatus::CREATED);
-        assert_eq!(event.created_by(), agent.agent_id());
+        assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
     }
 
     #[tokio::test]
-    async fn create_claim() {
+    async fn set_attribute() {
         let db = TestDb::new().await;
-        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let original_author = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let agent = TestAgent::new("web", "moderator", USR_AUDIENCE);
 
-        let room = {
-            // Create room and put the agent online.
+        let (room, event) = {
             let mut conn = db.get_conn().await;
             let room = shared_helpers::insert_room(&mut conn).await;
-            shared_helpers::insert_agent(&mut conn, agent.agent_id(), room.id()).await;
-            room
+
+            factory::Event::new()
+                .room_id(room.id())
+                .kind("message")
+                .set("messages")
+                .label("message-1")
+                .data(&json!({ "text": "original text" }))
+                .occurred_at(1_000_000_000)
+                .created_by(original_author.agent_id())
+                .insert(&mut conn)
+                .await;
+
+            let event = factory::Event::new()
+                .room_id(room.id())
+                .kind("message")
+                .set("messages")
+                .label("message-1")
+                .data(&json!({ "text": "modified text" }))
+                .occurred_at(2_000_000_000)
+                .created_by(original_author.agent_id())
+                .insert(&mut conn)
+                .await;
+
+            (room, event)
         };
 
-        // Allow agent to create claims of type `block` in the room.
+        // Allow agent to pin messages of the original author.
         let mut authz = TestAuthz::new();
         let classroom_id = room.classroom_id().to_string();
-        let account_id = agent.account_id().to_string();
+        let account_id = original_author.agent_id().as_account_id().to_string();
+
         let object = vec![
             "classrooms",
             &classroom_id,
-            "claims",
-            "block",
+            "pinned",
+            "message",
             "authors",
             &account_id,
         ];
+
         authz.allow(agent.account_id(), object, "create");
 
-        // Make event.create request.
+        // Make event.set_attribute request.
         let mut context = TestContext::new(db, authz);
 
-        let payload = CreateRequest {
+        let payload = SetAttributeRequest {
             room_id: room.id(),
-            payload: CreatePayload {
-                kind: String::from("block"),
-                set: Some(String::from("blocks")),
-                label: Some(String::from("user-1")),
-                attribute: None,
-                data: json!({ "blocked": true }),
-                is_claim: true,
-                is_persistent: true,
-                removed: false,
+            payload: SetAttributePayload {
+                set: String::from("messages"),
+                label: String::from("message-1"),
+                attribute: String::from("pinned"),
             },
         };
 
-        let messages = handle_request::<CreateHandler>(&mut context, &agent, payload)
+        let messages = handle_request::<SetAttributeHandler>(&mut context, &agent, payload)
             .await
-            .expect("Event creation failed");
-
-        assert_eq!(messages.len(), 3);
-
-        // Assert response.
-        let (event, respp, _) = find_response::<Event>(messages.as_slice());
-        assert_eq!(respp.status(), ResponseStatus::CREATED);
-        assert_eq!(event.room_id(), room.id());
-        assert_eq!(event.kind(), "block");
-        assert_eq!(event.set(), "blocks");
-        assert_eq!(event.label(), Some("user-1"));
-        assert_eq!(event.data(), &json!({ "blocked": true }));
-
-        // Assert tenant & room notifications.
-        let mut has_tenant_notification = false;
-        let mut has_room_notification = false;
-
-        for message in messages {
-            if let OutgoingEnvelopeProperties::Event(evp) = message.properties() {
-                let topic = message.topic();
-
-                if topic.ends_with(&format!("/audiences/{}/events", room.audience())) {
-                    has_tenant_notification = true;
-                }
-
-                if topic.ends_with(&format!("/rooms/{}/events", room.id())) {
-                    has_room_notification = true;
-                }
-
-                assert_eq!(evp.label(), "event.create");
+            .expect("Attribute update failed");
 
-                let event = message.payload::<Event>();
-                assert_eq!(event.room_id(), room.id());
-                assert_eq!(event.kind(), "block");
-                assert_eq!(event.set(), "blocks");
-                assert_eq!(event.label(), Some("user-1"));
-                assert_eq!(event.data(), &json!({ "blocked": true }));
-            }
-        }
+        // Assert the latest event got updated in place.
+        let (resp_event, respp, _) = find_response::<Event>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert_eq!(resp_event.id(), event.id());
+        assert_eq!(resp_event.attribute(), Some("pinned"));
+        assert_eq!(resp_event.data(), &json!({ "text": "modified text" }));
 
-        assert_eq!(has_tenant_notification, true);
-        assert_eq!(has_room_notification, true);
+        // Assert notification.
+        let (notification_event, evp, topic) = find_event::<Event>(messages.as_slice());
+        assert_eq!(evp.label(), "event.attribute_update");
+        assert!(topic.ends_with(&format!("/rooms/{}/events", room.id())));
+        assert_eq!(notification_event.attribute(), Some("pinned"));
     }
 
     #[tokio::test]
-    async fn create_transient_event() {
+    async fn clear_attribute() {
         let db = TestDb::new().await;
         let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
 
         let room = {
-            // Create room and put the agent online.
             let mut conn = db.get_conn().await;
             let room = shared_helpers::insert_room(&mut conn).await;
-            shared_helpers::insert_agent(&mut conn, agent.agent_id(), room.id()).await;
+
+            factory::Event::new()
+                .room_id(room.id())
+                .kind("message")
+                .set("messages")
+                .label("message-1")
+                .attribute("pinned")
+                .data(&json!({ "text": "text" }))
+                .occurred_at(1_000_000_000)
+                .created_by(agent.agent_id())
+                .insert(&mut conn)
+                .await;
+
             room
         };
 
-        // Allow agent to create events of type `message` in the room.
+        // Clearing requires permission on the attribute being cleared.
         let mut authz = TestAuthz::new();
         let classroom_id = room.classroom_id().to_string();
         let account_id = agent.account_id().to_string();
@@ -920,406 +3682,455 @@ mod tests {
         let object = vec![
             "classrooms",
             &classroom_id,
-            "events",
-            "cursor",
+            "pinned",
+            "message",
             "authors",
             &account_id,
         ];
 
         authz.allow(agent.account_id(), object, "create");
 
-        // Make event.create request.
+        // Make event.clear_attribute request.
         let mut context = TestContext::new(db, authz);
 
-        let data = json!({
-            "agent_id": agent.agent_id().to_string(),
-            "x": 123,
-            "y": 456,
-        });
-
-        let payload = CreateRequest {
+        let payload = ClearAttributeRequest {
             room_id: room.id(),
-            payload: CreatePayload {
-                kind: String::from("cursor"),
-                set: None,
-                label: None,
+            payload: ClearAttributePayload {
+                set: String::from("messages"),
+                label: String::from("message-1"),
                 attribute: None,
-                data: data.clone(),
-                is_claim: false,
-                is_persistent: false,
-                removed: false,
             },
         };
 
-        let messages = handle_request::<CreateHandler>(&mut context, &agent, payload)
+        let messages = handle_request::<ClearAttributeHandler>(&mut context, &agent, payload)
             .await
-            .expect("Event creation failed");
-
-        assert_eq!(messages.len(), 2);
+            .expect("Attribute clearing failed");
 
-        // Assert response.
         let (event, respp, _) = find_response::<Event>(messages.as_slice());
-        assert_eq!(respp.status(), ResponseStatus::CREATED);
-        assert_eq!(event.room_id(), room.id());
-        assert_eq!(event.kind(), "cursor");
-        assert_eq!(event.set(), "cursor");
-        assert_eq!(event.label(), None);
-        assert_eq!(event.data(), &data);
-
-        // Assert notification.
-        let (event, evp, topic) = find_event::<Event>(messages.as_slice());
-        assert!(topic.ends_with(&format!("/rooms/{}/events", room.id())));
-        assert_eq!(evp.label(), "event.create");
-        assert_eq!(event.room_id(), room.id());
-        assert_eq!(event.kind(), "cursor");
-        assert_eq!(event.set(), "cursor");
-        assert_eq!(event.label(), None);
-        assert_eq!(event.data(), &data);
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert_eq!(event.attribute(), None);
     }
 
     #[tokio::test]
-    async fn create_event_not_authorized() {
+    async fn set_attribute_missing_event() {
         let db = TestDb::new().await;
         let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
 
         let room = {
-            // Create room and put the agent online.
             let mut conn = db.get_conn().await;
-            let room = shared_helpers::insert_room(&mut conn).await;
-            shared_helpers::insert_agent(&mut conn, agent.agent_id(), room.id()).await;
-            room
+            shared_helpers::insert_room(&mut conn).await
         };
 
-        // Make event.create request.
         let mut context = TestContext::new(db, TestAuthz::new());
 
-        let payload = CreateRequest {
+        let payload = SetAttributeRequest {
             room_id: room.id(),
-            payload: CreatePayload {
-                kind: String::from("message"),
-                set: Some(String::from("messages")),
-                label: Some(String::from("message-1")),
-                attribute: None,
-                data: json!({ "text": "hello" }),
-                is_claim: false,
-                is_persistent: true,
-                removed: false,
+            payload: SetAttributePayload {
+                set: String::from("messages"),
+                label: String::from("message-1"),
+                attribute: String::from("pinned"),
             },
         };
 
-        let err = handle_request::<CreateHandler>(&mut context, &agent, payload)
+        let err = handle_request::<SetAttributeHandler>(&mut context, &agent, payload)
             .await
-            .expect_err("Unexpected success on event creation");
+            .expect_err("Unexpected success on attribute update");
 
-        assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+        assert_eq!(err.status(), ResponseStatus::NOT_FOUND);
+        assert_eq!(err.kind(), "event_not_found");
     }
 
     #[tokio::test]
-    async fn create_event_not_entered() {
+    async fn list_events() {
         let db = TestDb::new().await;
         let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
 
-        let room = {
+        let (room, db_events) = {
             // Create room.
             let mut conn = db.get_conn().await;
-            shared_helpers::insert_room(&mut conn).await
+            let room = shared_helpers::insert_room(&mut conn).await;
+
+            // Create events in the room.
+            let mut events = vec![];
+
+            for i in 1..4 {
+                let event = factory::Event::new()
+                    .room_id(room.id())
+                    .kind("message")
+                    .data(&json!({ "text": format!("message {}", i) }))
+                    .occurred_at(i * 1000)
+                    .created_by(agent.agent_id())
+                    .insert(&mut conn)
+                    .await;
+
+                events.push(event);
+            }
+
+            (room, events)
         };
 
-        // Allow agent to create events of type `message` in the room.
+        // Allow agent to list events in the room.
         let mut authz = TestAuthz::new();
         let classroom_id = room.classroom_id().to_string();
-        let account_id = agent.account_id().to_string();
+        let object = vec!["classrooms", &classroom_id];
+        authz.allow(agent.account_id(), object, "read");
 
-        let object = vec![
-            "classrooms",
-            &classroom_id,
-            "events",
-            "message",
-            "authors",
-            &account_id,
-        ];
+        // Make event.list request.
+        let mut context = TestContext::new(db, authz);
 
-        authz.allow(agent.account_id(), object, "create");
+        let payload = ListRequest {
+            room_id: room.id(),
+            payload: ListPayload {
+                kind: None,
+                set: None,
+                label: None,
+                label_prefix: None,
+                attribute: None,
+                last_occurred_at: None,
+                direction: Direction::Backward,
+                limit: Some(2),
+                fields: None,
+                binary_data: false,
+                from_occurred_at: None,
+                to_occurred_at: None,
+                include_deleted: false,
+            },
+            if_none_match: None,
+        };
 
-        // Make event.create request.
-        let mut context = TestContext::new(db, authz);
+        let messages = handle_request::<ListHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Events listing failed (page 1)");
 
-        let payload = CreateRequest {
+        // Assert last two events response.
+        let (events, respp, _) = find_response::<Vec<Event>>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].id(), db_events[2].id());
+        assert_eq!(events[1].id(), db_events[1].id());
+
+        // Request the next page.
+        let payload = ListRequest {
             room_id: room.id(),
-            payload: CreatePayload {
-                kind: String::from("message"),
-                set: Some(String::from("messages")),
-                label: Some(String::from("message-1")),
+            payload: ListPayload {
+                kind: None,
+                set: None,
+                label: None,
+                label_prefix: None,
                 attribute: None,
-                data: json!({ "text": "hello" }),
-                is_claim: false,
-                is_persistent: true,
-                removed: false,
+                last_occurred_at: Some(events[1].occurred_at()),
+                direction: Direction::Backward,
+                limit: Some(2),
+                fields: None,
+                binary_data: false,
+                from_occurred_at: None,
+                to_occurred_at: None,
+                include_deleted: false,
             },
+            if_none_match: None,
         };
 
-        let messages = handle_request::<CreateHandler>(&mut context, &agent, payload)
+        let messages = handle_request::<ListHandler>(&mut context, &agent, payload)
             .await
-            .expect("Event creation failed");
+            .expect("Events listing failed (page 2)");
 
-        assert_eq!(messages.len(), 2);
+        // Assert the first event.
+        let (events, respp, _) = find_response::<Vec<Event>>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].id(), db_events[0].id());
     }
 
     #[tokio::test]
-    async fn create_event_closed_room() {
+    async fn list_events_in_occurred_at_range() {
         let db = TestDb::new().await;
         let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
 
-        let room = {
-            // Create closed room and put the agent online.
+        let (room, db_events) = {
             let mut conn = db.get_conn().await;
-            let room = shared_helpers::insert_closed_room(&mut conn).await;
-            shared_helpers::insert_agent(&mut conn, agent.agent_id(), room.id()).await;
-            room
+            let room = shared_helpers::insert_room(&mut conn).await;
+            let mut events = vec![];
+
+            for i in 1..6 {
+                let event = factory::Event::new()
+                    .room_id(room.id())
+                    .kind("message")
+                    .data(&json!({ "text": format!("message {}", i) }))
+                    .occurred_at(i * 1000)
+                    .created_by(agent.agent_id())
+                    .insert(&mut conn)
+                    .await;
+
+                events.push(event);
+            }
+
+            (room, events)
         };
 
-        // Allow agent to create events of type `message` in the room.
         let mut authz = TestAuthz::new();
         let classroom_id = room.classroom_id().to_string();
-        let account_id = agent.account_id().to_string();
-
-        let object = vec![
-            "classrooms",
-            &classroom_id,
-            "events",
-            "message",
-            "authors",
-            &account_id,
-        ];
-
-        authz.allow(agent.account_id(), object, "create");
+        authz.allow(
+            agent.account_id(),
+            vec!["classrooms", &classroom_id],
+            "read",
+        );
 
-        // Make event.create request.
         let mut context = TestContext::new(db, authz);
 
-        let payload = CreateRequest {
+        // The lower bound is inclusive and the upper one is exclusive.
+        let payload = ListRequest {
             room_id: room.id(),
-            payload: CreatePayload {
-                kind: String::from("message"),
-                set: Some(String::from("messages")),
-                label: Some(String::from("message-1")),
+            payload: ListPayload {
+                kind: None,
+                set: None,
+                label: None,
+                label_prefix: None,
                 attribute: None,
-                data: json!({ "text": "hello" }),
-                is_claim: false,
-                is_persistent: true,
-                removed: false,
+                last_occurred_at: None,
+                from_occurred_at: Some(2000),
+                to_occurred_at: Some(4000),
+                direction: Direction::Forward,
+                limit: None,
+                fields: None,
+                binary_data: false,
+                include_deleted: false,
             },
+            if_none_match: None,
         };
 
-        let err = handle_request::<CreateHandler>(&mut context, &agent, payload)
+        let messages = handle_request::<ListHandler>(&mut context, &agent, payload)
             .await
-            .expect_err("Unexpected success on event creation");
+            .expect("Events listing failed");
 
-        assert_eq!(err.status(), ResponseStatus::NOT_FOUND);
-        assert_eq!(err.kind(), "room_closed");
+        let (events, respp, _) = find_response::<Vec<Event>>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].id(), db_events[1].id());
+        assert_eq!(events[1].id(), db_events[2].id());
     }
 
     #[tokio::test]
-    async fn create_event_missing_room() {
+    async fn list_deleted_events() {
+        let db = TestDb::new().await;
         let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
-        let mut context = TestContext::new(TestDb::new().await, TestAuthz::new());
+        let banned_agent = TestAgent::new("web", "user456", USR_AUDIENCE);
 
-        let payload = CreateRequest {
-            room_id: Uuid::new_v4(),
-            payload: CreatePayload {
-                kind: String::from("message"),
-                set: Some(String::from("messages")),
-                label: Some(String::from("message-1")),
-                attribute: None,
-                data: json!({ "text": "hello" }),
-                is_claim: false,
-                is_persistent: true,
-                removed: false,
-            },
-        };
+        let (room, deleted_event) = {
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
 
-        let err = handle_request::<CreateHandler>(&mut context, &agent, payload)
-            .await
-            .expect_err("Unexpected success on event creation");
+            factory::Event::new()
+                .room_id(room.id())
+                .kind("message")
+                .data(&json!({ "text": "hello" }))
+                .occurred_at(1000)
+                .created_by(agent.agent_id())
+                .insert(&mut conn)
+                .await;
 
-        assert_eq!(err.status(), ResponseStatus::NOT_FOUND);
-        assert_eq!(err.kind(), "room_not_found");
-    }
+            let deleted_event = factory::Event::new()
+                .room_id(room.id())
+                .kind("message")
+                .data(&json!({ "text": "spam" }))
+                .occurred_at(2000)
+                .created_by(&banned_agent.agent_id())
+                .insert(&mut conn)
+                .await;
 
-    #[tokio::test]
-    async fn create_whiteboard_event_without_whiteboard_access() {
-        let db = TestDb::new().await;
-        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            db::event::DeleteByAccountQuery::new(
+                room.id(),
+                banned_agent.account_id().to_owned(),
+                Utc::now() - chrono::Duration::hours(1),
+            )
+            .execute(&mut conn)
+            .await
+            .expect("Failed to delete events");
 
-        let room = {
-            // Create room and put the agent online.
-            let mut conn = db.get_conn().await;
-            let room = shared_helpers::insert_validating_whiteboard_access_room(&mut conn).await;
-            shared_helpers::insert_agent(&mut conn, agent.agent_id(), room.id()).await;
-            room
+            (room, deleted_event)
         };
 
-        // Allow agent to create events of type `draw` in the room.
+        let moderator = TestAgent::new("web", "moderator", USR_AUDIENCE);
         let mut authz = TestAuthz::new();
-        let account_id = agent.account_id().to_string();
         let classroom_id = room.classroom_id().to_string();
+        authz.allow(
+            agent.account_id(),
+            vec!["classrooms", &classroom_id],
+            "read",
+        );
+        authz.allow(
+            moderator.account_id(),
+            vec!["classrooms", &classroom_id],
+            "read",
+        );
+        authz.allow(
+            moderator.account_id(),
+            vec!["classrooms", &classroom_id],
+            "update",
+        );
 
-        let object = vec![
-            "classrooms",
-            &classroom_id,
-            "events",
-            "draw",
-            "authors",
-            &account_id,
-        ];
-
-        authz.allow(agent.account_id(), object, "create");
-
-        let mut context = TestContext::new(db.clone(), authz);
+        let mut context = TestContext::new(db, authz);
 
-        // Make event.create request. It should fail
-        let payload = CreateRequest {
+        let payload = || ListRequest {
             room_id: room.id(),
-            payload: CreatePayload {
-                kind: String::from("draw"),
-                set: Some(String::from("set")),
-                label: Some(String::from("label-1")),
+            payload: ListPayload {
+                kind: None,
+                set: None,
+                label: None,
+                label_prefix: None,
                 attribute: None,
-                data: json!({ "foo": "bar" }),
-                is_claim: false,
-                is_persistent: true,
-                removed: false,
+                last_occurred_at: None,
+                direction: Direction::Forward,
+                limit: None,
+                fields: None,
+                binary_data: false,
+                from_occurred_at: None,
+                to_occurred_at: None,
+                include_deleted: true,
             },
+            if_none_match: None,
         };
 
-        handle_request::<CreateHandler>(&mut context, &agent, payload)
+        // Deleted events are not available to regular readers.
+        let err = handle_request::<ListHandler>(&mut context, &agent, payload())
             .await
-            .expect_err("Event creation succeeded");
-
-        // Update whiteboard access for the agent
-        {
-            let mut m = HashMap::new();
-            m.insert(agent.account_id().to_owned(), true);
-            let q = db::room::UpdateQuery::new(room.id()).whiteboard_access(m);
-            let mut conn = db.get_conn().await;
-            q.execute(&mut conn)
-                .await
-                .expect("Failed to update whiteboard access");
-        }
+            .expect_err("Unexpected success listing deleted events");
 
-        // Make event.create request. Now it should succeed
-        let payload = CreateRequest {
-            room_id: room.id(),
-            payload: CreatePayload {
-                kind: String::from("draw"),
-                set: Some(String::from("set")),
-                label: Some(String::from("label-2")),
-                attribute: None,
-                data: crate::db::event::CompactEvent::test_rect_event()
-                    .into_json()
-                    .unwrap(),
-                is_claim: false,
-                is_persistent: true,
-                removed: false,
-            },
-        };
+        assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
 
-        handle_request::<CreateHandler>(&mut context, &agent, payload)
+        let messages = handle_request::<ListHandler>(&mut context, &moderator, payload())
             .await
-            .expect("Failed to create event");
-    }
+            .expect("Events listing failed");
 
+        let (events, respp, _) = find_response::<Vec<JsonValue>>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert_eq!(events.len(), 2);
+        assert!(events[0].get("deleted_at").is_none());
+        assert_eq!(events[1]["id"], json!(deleted_event.id()));
+        assert!(events[1]["deleted_at"].is_i64());
+    }
     #[tokio::test]
-    async fn create_whiteboard_event_as_room_updater() {
+    async fn list_events_filtered_by_kinds() {
         let db = TestDb::new().await;
         let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
 
         let room = {
-            // Create room and put the agent online.
+            // Create room.
             let mut conn = db.get_conn().await;
-            let room = shared_helpers::insert_validating_whiteboard_access_room(&mut conn).await;
-            shared_helpers::insert_agent(&mut conn, agent.agent_id(), room.id()).await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+
+            // Create events in the room.
+            for (i, s) in ["A", "B", "A", "C"].iter().enumerate() {
+                factory::Event::new()
+                    .room_id(room.id())
+                    .kind(s)
+                    .data(&json!({ "text": format!("message {}", i) }))
+                    .occurred_at(i as i64 * 1000)
+                    .created_by(agent.agent_id())
+                    .insert(&mut conn)
+                    .await;
+            }
+
             room
         };
 
-        // Allow agent to create events of type `draw` in the room.
+        // Allow agent to list events in the room.
         let mut authz = TestAuthz::new();
         let classroom_id = room.classroom_id().to_string();
-        let account_id = agent.account_id().to_string();
+        let object = vec!["classrooms", &classroom_id];
+        authz.allow(agent.account_id(), object, "read");
 
-        let object = vec![
-            "classrooms",
-            &classroom_id,
-            "events",
-            "draw",
-            "authors",
-            &account_id,
-        ];
+        // Make event.list request.
+        let mut context = TestContext::new(db, authz);
 
-        authz.allow(agent.account_id(), object, "create");
+        let payload = ListRequest {
+            room_id: room.id(),
+            payload: ListPayload {
+                kind: Some(ListTypesFilter::Single("B".to_string())),
+                set: None,
+                label: None,
+                label_prefix: None,
+                attribute: None,
+                last_occurred_at: None,
+                direction: Direction::Backward,
+                limit: None,
+                fields: None,
+                binary_data: false,
+                from_occurred_at: None,
+                to_occurred_at: None,
+                include_deleted: false,
+            },
+            if_none_match: None,
+        };
 
-        let mut context = TestContext::new(db.clone(), authz.clone());
+        let messages = handle_request::<ListHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Events listing failed");
 
-        let payload = CreateRequest {
+        // we have only two kind=B events
+        let (events, respp, _) = find_response::<Vec<Event>>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert_eq!(events.len(), 1);
+
+        let payload = ListRequest {
             room_id: room.id(),
-            payload: CreatePayload {
-                kind: String::from("draw"),
-                set: Some(String::from("set")),
-                label: Some(String::from("label-2")),
+            payload: ListPayload {
+                kind: Some(ListTypesFilter::Multiple(vec![
+                    "B".to_string(),
+                    "A".to_string(),
+                ])),
+                set: None,
+                label: None,
+                label_prefix: None,
                 attribute: None,
-                data: crate::db::event::CompactEvent::test_rect_event()
-                    .into_json()
-                    .unwrap(),
-                is_claim: false,
-                is_persistent: true,
-                removed: false,
+                last_occurred_at: None,
+                direction: Direction::Backward,
+                limit: None,
+                fields: None,
+                binary_data: false,
+                from_occurred_at: None,
+                to_occurred_at: None,
+                include_deleted: false,
             },
+            if_none_match: None,
         };
 
-        // This must fail since user has no room-update access and whiteboard access map is empty
-        handle_request::<CreateHandler>(&mut context, &agent, payload.clone())
+        let messages = handle_request::<ListHandler>(&mut context, &agent, payload)
             .await
-            .expect_err("Event creation succeeded");
-
-        let object = vec!["classrooms", &classroom_id];
-        authz.allow(agent.account_id(), object, "update");
-        let mut context = TestContext::new(db.clone(), authz);
+            .expect("Events listing failed");
 
-        // This must succeed cause even though whiteboard access map is empty user is allowed to update the room
-        handle_request::<CreateHandler>(&mut context, &agent, payload)
-            .await
-            .expect("Event creation failed");
+        // we have two kind=B events and one kind=A event
+        let (events, respp, _) = find_response::<Vec<Event>>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert_eq!(events.len(), 3);
     }
 
-    ///////////////////////////////////////////////////////////////////////////
-
     #[tokio::test]
-    async fn list_events() {
+    async fn list_events_filter_by_attribute() {
         let db = TestDb::new().await;
         let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
 
-        let (room, db_events) = {
+        let room = {
             // Create room.
             let mut conn = db.get_conn().await;
             let room = shared_helpers::insert_room(&mut conn).await;
 
             // Create events in the room.
-            let mut events = vec![];
-
-            for i in 1..4 {
-                let event = factory::Event::new()
+            for (i, attr) in [None, Some("pinned"), Some("other")].iter().enumerate() {
+                let mut factory = factory::Event::new()
                     .room_id(room.id())
                     .kind("message")
                     .data(&json!({ "text": format!("message {}", i) }))
-                    .occurred_at(i * 1000)
-                    .created_by(&agent.agent_id())
-                    .insert(&mut conn)
-                    .await;
+                    .occurred_at(i as i64 * 1000)
+                    .created_by(agent.agent_id());
 
-                events.push(event);
+                if let Some(attribute) = attr {
+                    factory = factory.attribute(attribute);
+                }
+
+                factory.insert(&mut conn).await;
             }
 
-            (room, events)
+            room
         };
 
         // Allow agent to list events in the room.
@@ -1337,67 +4148,179 @@ mod tests {
                 kind: None,
                 set: None,
                 label: None,
-                attribute: None,
+                label_prefix: None,
+                attribute: Some(String::from("pinned")),
                 last_occurred_at: None,
                 direction: Direction::Backward,
-                limit: Some(2),
+                limit: None,
+                fields: None,
+                binary_data: false,
+                from_occurred_at: None,
+                to_occurred_at: None,
+                include_deleted: false,
             },
+            if_none_match: None,
         };
 
         let messages = handle_request::<ListHandler>(&mut context, &agent, payload)
             .await
-            .expect("Events listing failed (page 1)");
+            .expect("Events listing failed");
 
-        // Assert last two events response.
+        // Expect only the event with the `pinned` attribute value.
         let (events, respp, _) = find_response::<Vec<Event>>(messages.as_slice());
         assert_eq!(respp.status(), ResponseStatus::OK);
-        assert_eq!(events.len(), 2);
-        assert_eq!(events[0].id(), db_events[2].id());
-        assert_eq!(events[1].id(), db_events[1].id());
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].attribute(), Some("pinned"));
+    }
+
+    #[tokio::test]
+    async fn list_events_filter_by_one_of_attributes() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+
+            factory::Event::new()
+                .room_id(room.id())
+                .kind("message")
+                .attribute("important")
+                .attribute("pinned")
+                .data(&json!({ "text": "message" }))
+                .occurred_at(1000)
+                .created_by(agent.agent_id())
+                .insert(&mut conn)
+                .await;
+
+            room
+        };
+
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        let object = vec!["classrooms", &classroom_id];
+        authz.allow(agent.account_id(), object, "read");
+
+        let mut context = TestContext::new(db, authz);
 
-        // Request the next page.
         let payload = ListRequest {
             room_id: room.id(),
             payload: ListPayload {
                 kind: None,
                 set: None,
                 label: None,
-                attribute: None,
-                last_occurred_at: Some(events[1].occurred_at()),
+                label_prefix: None,
+                attribute: Some(String::from("pinned")),
+                last_occurred_at: None,
                 direction: Direction::Backward,
-                limit: Some(2),
+                limit: None,
+                fields: None,
+                binary_data: false,
+                from_occurred_at: None,
+                to_occurred_at: None,
+                include_deleted: false,
             },
+            if_none_match: None,
         };
 
         let messages = handle_request::<ListHandler>(&mut context, &agent, payload)
             .await
-            .expect("Events listing failed (page 2)");
+            .expect("Events listing failed");
 
-        // Assert the first event.
-        let (events, respp, _) = find_response::<Vec<Event>>(messages.as_slice());
-        assert_eq!(respp.status(), ResponseStatus::OK);
+        let (events, _, _) = find_response::<Vec<Event>>(messages.as_slice());
         assert_eq!(events.len(), 1);
-        assert_eq!(events[0].id(), db_events[0].id());
+        assert_eq!(events[0].attributes(), ["important", "pinned"]);
     }
 
     #[tokio::test]
-    async fn list_events_filtered_by_kinds() {
+    async fn list_events_with_fields() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let (room, event) = {
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+
+            let event = factory::Event::new()
+                .room_id(room.id())
+                .kind("message")
+                .set("messages")
+                .label("message-1")
+                .data(&json!({ "text": "hello" }))
+                .occurred_at(1000)
+                .created_by(agent.agent_id())
+                .insert(&mut conn)
+                .await;
+
+            (room, event)
+        };
+
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        let object = vec!["classrooms", &classroom_id];
+        authz.allow(agent.account_id(), object, "read");
+
+        let mut context = TestContext::new(db, authz);
+
+        let payload = ListRequest {
+            room_id: room.id(),
+            payload: ListPayload {
+                kind: None,
+                set: None,
+                label: None,
+                label_prefix: None,
+                attribute: None,
+                last_occurred_at: None,
+                direction: Direction::Forward,
+                limit: None,
+                fields: Some("id, type,occurred_at".to_string()),
+                binary_data: false,
+                from_occurred_at: None,
+                to_occurred_at: None,
+                include_deleted: false,
+            },
+            if_none_match: None,
+        };
+
+        let messages = handle_request::<ListHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Events listing failed");
+
+        let (events, respp, _) = find_response::<Vec<JsonValue>>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+
+        assert_eq!(
+            events,
+            vec![json!({
+                "id": event.id(),
+                "type": "message",
+                "occurred_at": 1000,
+            })]
+        );
+    }
+
+    #[tokio::test]
+    async fn list_events_with_binary_data() {
         let db = TestDb::new().await;
         let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let draw_data = crate::db::event::CompactEvent::test_rect_event()
+            .into_json()
+            .unwrap();
 
         let room = {
-            // Create room.
             let mut conn = db.get_conn().await;
             let room = shared_helpers::insert_room(&mut conn).await;
 
-            // Create events in the room.
-            for (i, s) in ["A", "B", "A", "C"].iter().enumerate() {
+            for (kind, data, occurred_at) in [
+                ("draw", &draw_data, 1000),
+                ("message", &json!({ "text": "hello" }), 2000),
+            ] {
                 factory::Event::new()
                     .room_id(room.id())
-                    .kind(s)
-                    .data(&json!({ "text": format!("message {}", i) }))
-                    .occurred_at(i as i64 * 1000)
-                    .created_by(&agent.agent_id())
+                    .kind(kind)
+                    .data(data)
+                    .occurred_at(occurred_at)
+                    .created_by(agent.agent_id())
                     .insert(&mut conn)
                     .await;
             }
@@ -1405,100 +4328,202 @@ mod tests {
             room
         };
 
-        // Allow agent to list events in the room.
         let mut authz = TestAuthz::new();
         let classroom_id = room.classroom_id().to_string();
         let object = vec!["classrooms", &classroom_id];
         authz.allow(agent.account_id(), object, "read");
 
-        // Make event.list request.
         let mut context = TestContext::new(db, authz);
 
         let payload = ListRequest {
             room_id: room.id(),
             payload: ListPayload {
-                kind: Some(ListTypesFilter::Single("B".to_string())),
+                kind: None,
                 set: None,
                 label: None,
+                label_prefix: None,
                 attribute: None,
                 last_occurred_at: None,
-                direction: Direction::Backward,
+                direction: Direction::Forward,
                 limit: None,
+                fields: None,
+                binary_data: true,
+                from_occurred_at: None,
+                to_occurred_at: None,
+                include_deleted: false,
             },
+            if_none_match: None,
         };
 
         let messages = handle_request::<ListHandler>(&mut context, &agent, payload)
             .await
             .expect("Events listing failed");
 
-        // we have only two kind=B events
         let (events, respp, _) = find_response::<Vec<Event>>(messages.as_slice());
         assert_eq!(respp.status(), ResponseStatus::OK);
-        assert_eq!(events.len(), 1);
+        assert_eq!(events.len(), 2);
+
+        assert_eq!(events[0].data(), &JsonValue::Null);
+        let binary_data = events[0].binary_data().expect("Missing binary data");
+        assert_eq!(binary_data.decode(), draw_data);
+
+        assert_eq!(events[1].data(), &json!({ "text": "hello" }));
+        assert!(events[1].binary_data().is_none());
+    }
+
+    #[tokio::test]
+    async fn list_events_with_unknown_field() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut context = TestContext::new(db, TestAuthz::new());
 
         let payload = ListRequest {
             room_id: room.id(),
             payload: ListPayload {
-                kind: Some(ListTypesFilter::Multiple(vec![
-                    "B".to_string(),
-                    "A".to_string(),
-                ])),
+                kind: None,
                 set: None,
                 label: None,
+                label_prefix: None,
                 attribute: None,
                 last_occurred_at: None,
-                direction: Direction::Backward,
+                direction: Direction::Forward,
                 limit: None,
+                fields: Some("id,secret".to_string()),
+                binary_data: false,
+                from_occurred_at: None,
+                to_occurred_at: None,
+                include_deleted: false,
             },
+            if_none_match: None,
         };
 
-        let messages = handle_request::<ListHandler>(&mut context, &agent, payload)
+        let err = handle_request::<ListHandler>(&mut context, &agent, payload)
             .await
-            .expect("Events listing failed");
+            .expect_err("Unexpected success listing events with unknown field");
 
-        // we have two kind=B events and one kind=A event
-        let (events, respp, _) = find_response::<Vec<Event>>(messages.as_slice());
-        assert_eq!(respp.status(), ResponseStatus::OK);
-        assert_eq!(events.len(), 3);
+        assert_eq!(err.status(), ResponseStatus::BAD_REQUEST);
+        assert_eq!(err.kind(), "invalid_payload");
     }
 
     #[tokio::test]
-    async fn list_events_filter_by_attribute() {
+    async fn list_events_redacted() {
         let db = TestDb::new().await;
-        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let student = TestAgent::new("web", "student", USR_AUDIENCE);
+        let moderator = TestAgent::new("web", "moderator", USR_AUDIENCE);
 
         let room = {
-            // Create room.
             let mut conn = db.get_conn().await;
             let room = shared_helpers::insert_room(&mut conn).await;
 
-            // Create events in the room.
-            for (i, attr) in [None, Some("pinned"), Some("other")].iter().enumerate() {
-                let mut factory = factory::Event::new()
-                    .room_id(room.id())
-                    .kind("message")
-                    .data(&json!({ "text": format!("message {}", i) }))
-                    .occurred_at(i as i64 * 1000)
-                    .created_by(&agent.agent_id());
+            factory::Event::new()
+                .room_id(room.id())
+                .kind("message")
+                .set("messages")
+                .label("message-1")
+                .data(&json!({ "text": "hello", "notes": "noisy", "rating": 2 }))
+                .occurred_at(1000)
+                .created_by(moderator.agent_id())
+                .insert(&mut conn)
+                .await;
 
-                if let Some(attribute) = attr {
-                    factory = factory.attribute(attribute);
-                }
+            room
+        };
 
-                factory.insert(&mut conn).await;
-            }
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+
+        for agent in [&student, &moderator] {
+            authz.allow(
+                agent.account_id(),
+                vec!["classrooms", &classroom_id],
+                "read",
+            );
+        }
+
+        authz.allow(
+            moderator.account_id(),
+            vec!["classrooms", &classroom_id],
+            "update",
+        );
+
+        let mut context = TestContext::new(db, authz);
+
+        context.update_config(|config| {
+            config.redaction = vec![
+                RedactionRule {
+                    kind: "message".to_string(),
+                    path: "/notes".to_string(),
+                    mask: None,
+                },
+                RedactionRule {
+                    kind: "message".to_string(),
+                    path: "/rating".to_string(),
+                    mask: Some(json!("***")),
+                },
+            ];
+        });
+
+        let payload = || ListRequest {
+            room_id: room.id(),
+            payload: ListPayload {
+                kind: None,
+                set: None,
+                label: None,
+                label_prefix: None,
+                attribute: None,
+                last_occurred_at: None,
+                direction: Direction::Forward,
+                limit: None,
+                fields: None,
+                binary_data: false,
+                from_occurred_at: None,
+                to_occurred_at: None,
+                include_deleted: false,
+            },
+            if_none_match: None,
+        };
+
+        // The student doesn't see moderator notes.
+        let messages = handle_request::<ListHandler>(&mut context, &student, payload())
+            .await
+            .expect("Events listing failed");
+
+        let (events, _, _) = find_response::<Vec<Event>>(messages.as_slice());
+        assert_eq!(
+            events[0].data(),
+            &json!({ "text": "hello", "rating": "***" })
+        );
+
+        // The moderator sees everything.
+        let messages = handle_request::<ListHandler>(&mut context, &moderator, payload())
+            .await
+            .expect("Events listing failed");
+
+        let (events, _, _) = find_response::<Vec<Event>>(messages.as_slice());
+
+        assert_eq!(
+            events[0].data(),
+            &json!({ "text": "hello", "notes": "noisy", "rating": 2 })
+        );
+    }
+
+    #[tokio::test]
+    async fn list_events_not_authorized() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
 
-            room
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
         };
 
-        // Allow agent to list events in the room.
-        let mut authz = TestAuthz::new();
-        let classroom_id = room.classroom_id().to_string();
-        let object = vec!["classrooms", &classroom_id];
-        authz.allow(agent.account_id(), object, "read");
-
-        // Make event.list request.
-        let mut context = TestContext::new(db, authz);
+        let mut context = TestContext::new(db, TestAuthz::new());
 
         let payload = ListRequest {
             room_id: room.id(),
@@ -1506,52 +4531,102 @@ mod tests {
                 kind: None,
                 set: None,
                 label: None,
-                attribute: Some(String::from("pinned")),
+                label_prefix: None,
+                attribute: None,
                 last_occurred_at: None,
                 direction: Direction::Backward,
-                limit: None,
+                limit: Some(2),
+                fields: None,
+                binary_data: false,
+                from_occurred_at: None,
+                to_occurred_at: None,
+                include_deleted: false,
             },
+            if_none_match: None,
         };
 
-        let messages = handle_request::<ListHandler>(&mut context, &agent, payload)
+        let err = handle_request::<ListHandler>(&mut context, &agent, payload)
             .await
-            .expect("Events listing failed");
+            .expect_err("Unexpected success on events listing");
 
-        // Expect only the event with the `pinned` attribute value.
-        let (events, respp, _) = find_response::<Vec<Event>>(messages.as_slice());
-        assert_eq!(respp.status(), ResponseStatus::OK);
-        assert_eq!(events.len(), 1);
-        assert_eq!(events[0].attribute(), Some("pinned"));
+        assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
     }
 
     #[tokio::test]
-    async fn list_events_not_authorized() {
+    async fn list_events_shared() {
         let db = TestDb::new().await;
         let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
 
-        let room = {
+        let (room, other_room) = {
             let mut conn = db.get_conn().await;
-            shared_helpers::insert_room(&mut conn).await
+            let room = shared_helpers::insert_room(&mut conn).await;
+            let other_room = shared_helpers::insert_room(&mut conn).await;
+
+            factory::Event::new()
+                .room_id(room.id())
+                .kind("message")
+                .data(&json!({ "text": "hello" }))
+                .occurred_at(1000)
+                .created_by(agent.agent_id())
+                .insert(&mut conn)
+                .await;
+
+            (room, other_room)
         };
 
+        // Share tokens don't need the tenant's authorization.
         let mut context = TestContext::new(db, TestAuthz::new());
 
-        let payload = ListRequest {
-            room_id: room.id(),
+        let build_payload = |room_id, include_deleted| ListRequest {
+            room_id,
             payload: ListPayload {
                 kind: None,
                 set: None,
                 label: None,
+                label_prefix: None,
                 attribute: None,
                 last_occurred_at: None,
                 direction: Direction::Backward,
-                limit: Some(2),
+                limit: None,
+                fields: None,
+                binary_data: false,
+                from_occurred_at: None,
+                to_occurred_at: None,
+                include_deleted,
             },
+            if_none_match: None,
         };
 
-        let err = handle_request::<ListHandler>(&mut context, &agent, payload)
-            .await
-            .expect_err("Unexpected success on events listing");
+        let messages = handle_shared_request::<ListHandler>(
+            &mut context,
+            room.id(),
+            build_payload(room.id(), false),
+        )
+        .await
+        .expect("Events listing failed");
+
+        let (events, respp, _) = find_response::<Vec<Event>>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert_eq!(events.len(), 1);
+
+        // The token grants reading its room only.
+        let err = handle_shared_request::<ListHandler>(
+            &mut context,
+            room.id(),
+            build_payload(other_room.id(), false),
+        )
+        .await
+        .expect_err("Unexpected success on events listing");
+
+        assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+
+        let err = handle_shared_request::<ListHandler>(
+            &mut context,
+            room.id(),
+            build_payload(room.id(), true),
+        )
+        .await
+        .expect_err("Unexpected success on events listing");
 
         assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
     }
@@ -1567,11 +4642,18 @@ mod tests {
                 kind: None,
                 set: None,
                 label: None,
+                label_prefix: None,
                 attribute: None,
                 last_occurred_at: None,
                 direction: Direction::Backward,
                 limit: Some(2),
+                fields: None,
+                binary_data: false,
+                from_occurred_at: None,
+                to_occurred_at: None,
+                include_deleted: false,
             },
+            if_none_match: None,
         };
 
         let err = handle_request::<ListHandler>(&mut context, &agent, payload)
@@ -1644,4 +4726,379 @@ mod tests {
             Some(ListTypesFilter::Multiple(vec!["test".to_string()]))
         );
     }
+
+    ///////////////////////////////////////////////////////////////////////////
+
+    async fn insert_messages(
+        conn: &mut sqlx::PgConnection,
+        room: &db::room::Object,
+        agent: &TestAgent,
+        count: usize,
+    ) -> Vec<Event> {
+        let mut events = vec![];
+
+        for i in 0..count {
+            let event = factory::Event::new()
+                .room_id(room.id())
+                .kind("message")
+                .data(&json!({ "text": format!("message {}", i) }))
+                .occurred_at(1000 * (i as i64 + 1))
+                .created_by(agent.agent_id())
+                .insert(conn)
+                .await;
+
+            events.push(event);
+        }
+
+        events
+    }
+
+    /// `seq` assigned to the event once on commit as opposed to the provisional one returned on insert.
+    async fn committed_seq(conn: &mut sqlx::PgConnection, event: &Event) -> Option<i64> {
+        sqlx::query_scalar("SELECT seq FROM event WHERE id = $1")
+            .bind(event.id())
+            .fetch_one(conn)
+            .await
+            .expect("Failed to fetch event seq")
+    }
+
+    fn allow_read(authz: &mut TestAuthz, agent: &TestAgent, room: &db::room::Object) {
+        let classroom_id = room.classroom_id().to_string();
+        authz.allow(
+            agent.account_id(),
+            vec!["classrooms", &classroom_id],
+            "read",
+        );
+    }
+
+    #[tokio::test]
+    async fn list_events_since() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let (room, events, seq) = {
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+            let events = insert_messages(&mut conn, &room, &agent, 3).await;
+            let seq = committed_seq(&mut conn, &events[0]).await;
+            (room, events, seq)
+        };
+
+        let mut authz = TestAuthz::new();
+        allow_read(&mut authz, &agent, &room);
+        let mut context = TestContext::new(db, authz);
+
+        let by_seq = SincePayload {
+            seq,
+            event_id: None,
+            limit: None,
+        };
+
+        let by_event_id = SincePayload {
+            seq: None,
+            event_id: Some(events[0].id()),
+            limit: None,
+        };
+
+        for payload in [by_seq, by_event_id] {
+            let request = SinceRequest {
+                room_id: room.id(),
+                payload,
+            };
+
+            let messages = handle_request::<SinceHandler>(&mut context, &agent, request)
+                .await
+                .expect("Events listing since failed");
+
+            let (resp, respp, _) = find_response::<SinceResponse>(messages.as_slice());
+            assert_eq!(respp.status(), ResponseStatus::OK);
+            assert!(!resp.reload);
+
+            let ids = resp.events.iter().map(|e| e.id()).collect::<Vec<_>>();
+            assert_eq!(ids, vec![events[1].id(), events[2].id()]);
+        }
+    }
+
+    #[tokio::test]
+    async fn list_events_since_changed() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let (room, events, seq) = {
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+            let events = insert_messages(&mut conn, &room, &agent, 3).await;
+            let seq = committed_seq(&mut conn, &events[2]).await;
+
+            sqlx::query("UPDATE event SET deleted_at = NOW() WHERE id = $1")
+                .bind(events[0].id())
+                .execute(&mut conn)
+                .await
+                .expect("Failed to delete event");
+
+            sqlx::query("UPDATE event SET attributes = ARRAY['pinned'] WHERE id = $1")
+                .bind(events[1].id())
+                .execute(&mut conn)
+                .await
+                .expect("Failed to update event attributes");
+
+            (room, events, seq)
+        };
+
+        let mut authz = TestAuthz::new();
+        allow_read(&mut authz, &agent, &room);
+        let mut context = TestContext::new(db, authz);
+
+        // Changed events come after the last one created before the changes.
+        let request = SinceRequest {
+            room_id: room.id(),
+            payload: SincePayload {
+                seq,
+                event_id: None,
+                limit: None,
+            },
+        };
+
+        let messages = handle_request::<SinceHandler>(&mut context, &agent, request)
+            .await
+            .expect("Events listing since failed");
+
+        let (resp, _, _) = find_response::<SinceResponse>(messages.as_slice());
+        assert!(!resp.reload);
+
+        let ids = resp.events.iter().map(|e| e.id()).collect::<Vec<_>>();
+        assert_eq!(ids, vec![events[0].id(), events[1].id()]);
+        assert_eq!(resp.events[1].attributes(), &["pinned".to_owned()]);
+
+        // Deleted events are returned with `deleted_at` which isn't deserialized back.
+        let (resp, _, _) = find_response::<JsonValue>(messages.as_slice());
+        assert!(resp["events"][0]["deleted_at"].is_i64());
+        assert!(resp["events"][1].get("deleted_at").is_none());
+    }
+
+    #[tokio::test]
+    async fn list_events_since_changed_by_event_id() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let (room, events, seq) = {
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+            let events = insert_messages(&mut conn, &room, &agent, 2).await;
+            let seq = committed_seq(&mut conn, &events[0]).await;
+
+            // The first event the client has seen changes after the second one is created.
+            sqlx::query("UPDATE event SET attributes = ARRAY['pinned'] WHERE id = $1")
+                .bind(events[0].id())
+                .execute(&mut conn)
+                .await
+                .expect("Failed to update event attributes");
+
+            (room, events, seq)
+        };
+
+        {
+            // The change doesn't move the event itself.
+            let mut conn = db.get_conn().await;
+            assert_eq!(committed_seq(&mut conn, &events[0]).await, seq);
+        }
+
+        let mut authz = TestAuthz::new();
+        allow_read(&mut authz, &agent, &room);
+        let mut context = TestContext::new(db, authz);
+
+        let request = SinceRequest {
+            room_id: room.id(),
+            payload: SincePayload {
+                seq: None,
+                event_id: Some(events[0].id()),
+                limit: None,
+            },
+        };
+
+        let messages = handle_request::<SinceHandler>(&mut context, &agent, request)
+            .await
+            .expect("Events listing since failed");
+
+        let (resp, _, _) = find_response::<SinceResponse>(messages.as_slice());
+        assert!(!resp.reload);
+
+        let ids = resp.events.iter().map(|e| e.id()).collect::<Vec<_>>();
+        assert_eq!(ids, vec![events[1].id(), events[0].id()]);
+        assert_eq!(resp.events[1].attributes(), &["pinned".to_owned()]);
+        assert!(resp.events[1].seq() > resp.events[0].seq());
+    }
+
+    #[tokio::test]
+    async fn list_events_since_too_many_missed() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let (room, seq) = {
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+            let events = insert_messages(&mut conn, &room, &agent, 4).await;
+            let seq = committed_seq(&mut conn, &events[0]).await;
+            (room, seq)
+        };
+
+        let mut authz = TestAuthz::new();
+        allow_read(&mut authz, &agent, &room);
+        let mut context = TestContext::new(db, authz);
+
+        let request = SinceRequest {
+            room_id: room.id(),
+            payload: SincePayload {
+                seq,
+                event_id: None,
+                limit: Some(2),
+            },
+        };
+
+        let messages = handle_request::<SinceHandler>(&mut context, &agent, request)
+            .await
+            .expect("Events listing since failed");
+
+        let (resp, _, _) = find_response::<SinceResponse>(messages.as_slice());
+        assert!(resp.reload);
+        assert!(resp.events.is_empty());
+    }
+
+    #[tokio::test]
+    async fn list_events_since_unknown_event() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut authz = TestAuthz::new();
+        allow_read(&mut authz, &agent, &room);
+        let mut context = TestContext::new(db, authz);
+
+        let request = SinceRequest {
+            room_id: room.id(),
+            payload: SincePayload {
+                seq: None,
+                event_id: Some(Uuid::new_v4()),
+                limit: None,
+            },
+        };
+
+        let messages = handle_request::<SinceHandler>(&mut context, &agent, request)
+            .await
+            .expect("Events listing since failed");
+
+        let (resp, _, _) = find_response::<SinceResponse>(messages.as_slice());
+        assert!(resp.reload);
+    }
+
+    #[tokio::test]
+    async fn label_history() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let (room, events) = {
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+            let mut events = vec![];
+
+            // Revisions of the message get inserted out of order.
+            for (label, occurred_at) in [
+                ("message-1", 3000),
+                ("message-1", 1000),
+                ("message-2", 2000),
+                ("message-1", 2000),
+            ] {
+                let event = factory::Event::new()
+                    .room_id(room.id())
+                    .kind("message")
+                    .set("messages")
+                    .label(label)
+                    .data(&json!({ "text": occurred_at }))
+                    .occurred_at(occurred_at)
+                    .created_by(agent.agent_id())
+                    .insert(&mut conn)
+                    .await;
+
+                events.push(event);
+            }
+
+            (room, events)
+        };
+
+        let mut authz = TestAuthz::new();
+        allow_read(&mut authz, &agent, &room);
+        let mut context = TestContext::new(db, authz);
+
+        let request = HistoryRequest {
+            room_id: room.id(),
+            payload: HistoryPayload {
+                set: "messages".to_owned(),
+                label: "message-1".to_owned(),
+                occurred_at: None,
+                limit: Some(2),
+            },
+        };
+
+        let messages = handle_request::<HistoryHandler>(&mut context, &agent, request)
+            .await
+            .expect("Label history failed");
+
+        let (resp, respp, _) = find_response::<Vec<Event>>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+
+        let ids = resp.iter().map(|e| e.id()).collect::<Vec<_>>();
+        assert_eq!(ids, vec![events[1].id(), events[3].id()]);
+
+        // Next page.
+        let request = HistoryRequest {
+            room_id: room.id(),
+            payload: HistoryPayload {
+                set: "messages".to_owned(),
+                label: "message-1".to_owned(),
+                occurred_at: Some(2000),
+                limit: Some(2),
+            },
+        };
+
+        let messages = handle_request::<HistoryHandler>(&mut context, &agent, request)
+            .await
+            .expect("Label history failed");
+
+        let (resp, _, _) = find_response::<Vec<Event>>(messages.as_slice());
+        let ids = resp.iter().map(|e| e.id()).collect::<Vec<_>>();
+        assert_eq!(ids, vec![events[0].id()]);
+    }
+
+    #[tokio::test]
+    async fn label_history_not_authorized() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut context = TestContext::new(db, TestAuthz::new());
+
+        let request = HistoryRequest {
+            room_id: room.id(),
+            payload: HistoryPayload {
+                set: "messages".to_owned(),
+                label: "message-1".to_owned(),
+                occurred_at: None,
+                limit: None,
+            },
+        };
+
+        let err = handle_request::<HistoryHandler>(&mut context, &agent, request)
+            .await
+            .expect_err("Unexpected success on label history");
+
+        assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+    }
 }
@@ -0,0 +1,469 @@
+use anyhow::Context as AnyhowContext;
+use async_trait::async_trait;
+use axum::{
+    extract::{self, Path},
+    Json,
+};
+use serde_derive::{Deserialize, Serialize};
+use sqlx::postgres::PgConnection;
+use svc_agent::{mqtt::ResponseStatus, Addressable, AgentId};
+use svc_utils::extractors::AgentIdExtractor;
+use tracing::{field::display, instrument, Span};
+use uuid::Uuid;
+
+use crate::app::endpoint::prelude::*;
+use crate::db;
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// The room's queue of raised hands, the earliest first.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct HandQueue {
+    room_id: Uuid,
+    agents: Vec<db::hand_queue::Object>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListRequest {
+    room_id: Uuid,
+}
+
+pub async fn list(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<Uuid>,
+) -> RequestResult {
+    ListHandler::handle(
+        &mut ctx.start_message(),
+        ListRequest { room_id },
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct ListHandler;
+
+#[async_trait]
+impl RequestHandler for ListHandler {
+    type Payload = ListRequest;
+
+    #[instrument(skip_all, fields(room_id, scope, classroom_id))]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload { room_id }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let room = helpers::find_room(context, room_id, helpers::RoomTimeRequirement::Any).await?;
+
+        let classroom_id = room.classroom_id().to_string();
+        let object = AuthzObject::new(&["classrooms", &classroom_id]);
+        let authz_time = helpers::authorize_room(context, &room, reqp, object, "read").await?;
+
+        let mut conn = context.get_ro_conn().await?;
+        let queue = list_queue(context, &mut conn, room.id()).await?;
+
+        Ok(AppResponse::new(
+            ResponseStatus::OK,
+            queue,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct RaiseRequest {
+    room_id: Uuid,
+}
+
+pub async fn raise(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<Uuid>,
+) -> RequestResult {
+    RaiseHandler::handle(
+        &mut ctx.start_message(),
+        RaiseRequest { room_id },
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct RaiseHandler;
+
+#[async_trait]
+impl RequestHandler for RaiseHandler {
+    type Payload = RaiseRequest;
+
+    #[instrument(skip_all, fields(room_id, scope, classroom_id))]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload { room_id }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let room = helpers::find_room(context, room_id, helpers::RoomTimeRequirement::Open).await?;
+        let authz_time = authorize_hand(context, &room, reqp).await?;
+
+        let query = db::hand_queue::RaiseQuery::new(room.id(), reqp.as_agent_id().to_owned());
+        let mut conn = context.get_conn().await?;
+
+        let result = context
+            .metrics()
+            .measure_query(QueryKey::HandQueueRaiseQuery, query.execute(&mut conn))
+            .await
+            .context("Failed to raise hand")
+            .error(AppErrorKind::DbQueryFailed)?;
+
+        if !result.entered {
+            return Err(anyhow!("Agent has not entered the room"))
+                .error(AppErrorKind::AgentNotEnteredTheRoom);
+        }
+
+        let queue = list_queue(context, &mut conn, room.id()).await?;
+        respond(context, &room, queue, authz_time, result.raised)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Default, Deserialize)]
+pub struct LowerPayload {
+    /// Another agent to lower the hand of. It's for moderators.
+    agent_id: Option<AgentId>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LowerRequest {
+    room_id: Uuid,
+    #[serde(flatten)]
+    payload: LowerPayload,
+}
+
+pub async fn lower(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<Uuid>,
+    payload: Option<Json<LowerPayload>>,
+) -> RequestResult {
+    let payload = payload.map(|Json(payload)| payload).unwrap_or_default();
+    let request = LowerRequest { room_id, payload };
+
+    LowerHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct LowerHandler;
+
+#[async_trait]
+impl RequestHandler for LowerHandler {
+    type Payload = LowerRequest;
+
+    #[instrument(skip_all, fields(room_id, scope, classroom_id))]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload { room_id, payload }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let room = helpers::find_room(context, room_id, helpers::RoomTimeRequirement::Any).await?;
+
+        let (agent_id, authz_time) = match payload.agent_id {
+            Some(agent_id) if &agent_id != reqp.as_agent_id() => {
+                let object = AuthzObject::room(&room);
+                let authz_time =
+                    helpers::authorize_room(context, &room, reqp, object, "update").await?;
+
+                (agent_id, authz_time)
+            }
+            _ => {
+                let authz_time = authorize_hand(context, &room, reqp).await?;
+                (reqp.as_agent_id().to_owned(), authz_time)
+            }
+        };
+
+        let query = db::hand_queue::LowerQuery::new(room.id(), agent_id);
+        let mut conn = context.get_conn().await?;
+
+        let lowered = context
+            .metrics()
+            .measure_query(QueryKey::HandQueueLowerQuery, query.execute(&mut conn))
+            .await
+            .context("Failed to lower hand")
+            .error(AppErrorKind::DbQueryFailed)?;
+
+        let queue = list_queue(context, &mut conn, room.id()).await?;
+        respond(context, &room, queue, authz_time, lowered)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct PopRequest {
+    room_id: Uuid,
+}
+
+/// The hand taken out of the queue.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PopResult {
+    room_id: Uuid,
+    popped: db::hand_queue::Object,
+}
+
+pub async fn pop(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<Uuid>,
+) -> RequestResult {
+    PopHandler::handle(
+        &mut ctx.start_message(),
+        PopRequest { room_id },
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct PopHandler;
+
+#[async_trait]
+impl RequestHandler for PopHandler {
+    type Payload = PopRequest;
+
+    #[instrument(skip_all, fields(room_id, scope, classroom_id, popped_agent_id))]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload { room_id }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let room = helpers::find_room(context, room_id, helpers::RoomTimeRequirement::Open).await?;
+
+        // Moderators are those who can update the room.
+        let object = AuthzObject::room(&room);
+        let authz_time = helpers::authorize_room(context, &room, reqp, object, "update").await?;
+
+        let query = db::hand_queue::PopQuery::new(room.id());
+        let mut conn = context.get_conn().await?;
+
+        let popped = context
+            .metrics()
+            .measure_query(QueryKey::HandQueuePopQuery, query.execute(&mut conn))
+            .await
+            .context("Failed to pop hand queue")
+            .error(AppErrorKind::DbQueryFailed)?
+            .ok_or_else(|| anyhow!("No raised hands in the room"))
+            .error(AppErrorKind::HandQueueEmpty)?;
+
+        Span::current().record("popped_agent_id", &display(&popped.agent_id()));
+
+        let result = PopResult {
+            room_id: room.id(),
+            popped,
+        };
+
+        let queue = list_queue(context, &mut conn, room.id()).await?;
+        let path = context.config().room_topic(room.audience(), room.id());
+
+        let mut response = AppResponse::new(
+            ResponseStatus::OK,
+            result.clone(),
+            context.start_timestamp(),
+            Some(authz_time),
+        );
+
+        // The popped agent is given the floor, everyone else sees the shorter queue.
+        response.add_notification("hand_queue.pop", &path, result, context.start_timestamp())?;
+        response.add_notification("hand_queue.update", &path, queue, context.start_timestamp())?;
+        Ok(response)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Raising and lowering own hand is authorized like creating events so banned accounts can't
+/// draw attention.
+async fn authorize_hand<C: Context>(
+    context: &mut C,
+    room: &db::room::Object,
+    reqp: RequestParams<'_>,
+) -> Result<chrono::Duration, AppError> {
+    let classroom_id = room.classroom_id().to_string();
+    let account_id = reqp.as_account_id().to_string();
+
+    let object = AuthzObject::new(&[
+        "classrooms",
+        &classroom_id,
+        "events",
+        "hand",
+        "authors",
+        &account_id,
+    ]);
+
+    helpers::authorize_room(context, room, reqp, object, "create").await
+}
+
+async fn list_queue<C: Context>(
+    context: &C,
+    conn: &mut PgConnection,
+    room_id: Uuid,
+) -> Result<HandQueue, AppError> {
+    let query = db::hand_queue::ListQuery::new(room_id);
+
+    let agents = context
+        .metrics()
+        .measure_query(QueryKey::HandQueueListQuery, query.execute(conn))
+        .await
+        .context("Failed to list hand queue")
+        .error(AppErrorKind::DbQueryFailed)?;
+
+    Ok(HandQueue { room_id, agents })
+}
+
+/// Responds with the queue and notifies the room if it has changed.
+fn respond<C: Context>(
+    context: &C,
+    room: &db::room::Object,
+    queue: HandQueue,
+    authz_time: chrono::Duration,
+    is_changed: bool,
+) -> RequestResult {
+    let mut response = AppResponse::new(
+        ResponseStatus::OK,
+        queue.clone(),
+        context.start_timestamp(),
+        Some(authz_time),
+    );
+
+    if is_changed {
+        response.add_notification(
+            "hand_queue.update",
+            &context.config().room_topic(room.audience(), room.id()),
+            queue,
+            context.start_timestamp(),
+        )?;
+    }
+
+    Ok(response)
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use crate::test_helpers::prelude::*;
+
+    use super::*;
+
+    fn allow_hand(authz: &mut TestAuthz, agent: &TestAgent, room: &db::room::Object) {
+        let classroom_id = room.classroom_id().to_string();
+        let account_id = agent.account_id().to_string();
+
+        authz.allow(
+            agent.account_id(),
+            vec![
+                "classrooms",
+                &classroom_id,
+                "events",
+                "hand",
+                "authors",
+                &account_id,
+            ],
+            "create",
+        );
+    }
+
+    #[tokio::test]
+    async fn raise_and_pop_hands() {
+        let db = TestDb::new().await;
+        let first = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let second = TestAgent::new("web", "user456", USR_AUDIENCE);
+        let host = TestAgent::new("web", "admin", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+            shared_helpers::insert_agent(&mut conn, first.agent_id(), room.id()).await;
+            shared_helpers::insert_agent(&mut conn, second.agent_id(), room.id()).await;
+            room
+        };
+
+        let mut authz = TestAuthz::new();
+        allow_hand(&mut authz, &first, &room);
+        allow_hand(&mut authz, &second, &room);
+
+        authz.allow(
+            host.account_id(),
+            vec!["classrooms", &room.classroom_id().to_string()],
+            "update",
+        );
+
+        let mut context = TestContext::new(db, authz);
+
+        for agent in [&first, &second, &first] {
+            let request = RaiseRequest { room_id: room.id() };
+
+            handle_request::<RaiseHandler>(&mut context, agent, request)
+                .await
+                .expect("Hand raising failed");
+        }
+
+        let messages =
+            handle_request::<PopHandler>(&mut context, &host, PopRequest { room_id: room.id() })
+                .await
+                .expect("Hand queue pop failed");
+
+        // Raising again keeps the place in the queue.
+        let (result, _, _) = find_response::<PopResult>(messages.as_slice());
+        assert_eq!(&result.popped.agent_id(), &first.agent_id());
+
+        let messages =
+            handle_request::<PopHandler>(&mut context, &host, PopRequest { room_id: room.id() })
+                .await
+                .expect("Hand queue pop failed");
+
+        let (result, _, _) = find_response::<PopResult>(messages.as_slice());
+        assert_eq!(&result.popped.agent_id(), &second.agent_id());
+
+        let err =
+            handle_request::<PopHandler>(&mut context, &host, PopRequest { room_id: room.id() })
+                .await
+                .expect_err("Unexpected success popping empty hand queue");
+
+        assert_eq!(err.kind(), "hand_queue_empty");
+    }
+
+    #[tokio::test]
+    async fn raise_hand_not_entered() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut authz = TestAuthz::new();
+        allow_hand(&mut authz, &agent, &room);
+
+        let mut context = TestContext::new(db, authz);
+        let request = RaiseRequest { room_id: room.id() };
+
+        let err = handle_request::<RaiseHandler>(&mut context, &agent, request)
+            .await
+            .expect_err("Unexpected success raising hand out of the room");
+
+        assert_eq!(err.kind(), "agent_not_entered_the_room");
+    }
+}
@@ -1,17 +1,34 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
 use anyhow::Context as AnyhowContext;
 use chrono::{DateTime, Duration, Utc};
+use http::header::{HeaderMap, IF_NONE_MATCH};
 use serde::ser::Serialize;
+use serde_json::Value as JsonValue;
+use sqlx::postgres::{PgConnection, PgPool};
 use svc_agent::mqtt::{
     IncomingRequestProperties, OutgoingResponse, ResponseStatus, ShortTermTimingProperties,
 };
-use tracing::field::display;
+use svc_agent::AgentId;
+use tracing::{error, field::display, info_span, warn, Span};
 use uuid::Uuid;
 
+use svc_authn::Authenticable;
+
+use crate::app::endpoint::authz::AuthzObject;
+use crate::app::endpoint::RequestParams;
 use crate::app::error::{Error as AppError, ErrorExt, ErrorKind as AppErrorKind};
 use crate::app::message_handler::Message;
+use crate::app::presence::RoomPresence;
+use crate::app::sentry;
 use crate::app::API_VERSION;
+use crate::config::RedactionRule;
 use crate::db;
-use crate::{app::context::Context, metrics::QueryKey};
+use crate::{
+    app::context::Context,
+    metrics::{Metrics, QueryKey},
+};
 
 ////////////////////////////////////////////////////////////////////////////////
 
@@ -47,17 +64,28 @@ pub async fn find_room<C: Context>(
 ) -> Result<db::room::Object, AppError> {
     tracing::Span::current().record("room_id", &display(id));
 
-    let query = db::room::FindQuery::by_id(id);
-    let mut conn = context.get_ro_conn().await?;
+    let ttl = context.config().room_cache.ttl;
+    let cache = context.room_cache();
 
-    let room = context
-        .metrics()
-        .measure_query(QueryKey::RoomFindQuery, query.execute(&mut conn))
-        .await
-        .context("Failed to find room")
-        .error(AppErrorKind::DbQueryFailed)?
-        .context("Room not found")
-        .error(AppErrorKind::RoomNotFound)?;
+    let room = match cache.get(id, ttl) {
+        Some(room) => room,
+        None => {
+            let query = db::room::FindQuery::by_id(id);
+            let mut conn = context.get_ro_conn().await?;
+
+            let room = context
+                .metrics()
+                .measure_query(QueryKey::RoomFindQuery, query.execute(&mut conn))
+                .await
+                .context("Failed to find room")
+                .error(AppErrorKind::DbQueryFailed)?
+                .context("Room not found")
+                .error(AppErrorKind::RoomNotFound)?;
+
+            cache.insert(&room, ttl);
+            room
+        }
+    };
 
     add_room_logger_tags(&room);
 
@@ -84,7 +112,133 @@ pub async fn find_room<C: Context>(
     }
 }
 
+/// Finds any room of the classroom, using the room cache when possible.
+pub async fn find_room_by_classroom_id<C: Context>(
+    context: &mut C,
+    classroom_id: Uuid,
+) -> Result<db::room::Object, AppError> {
+    let ttl = context.config().room_cache.ttl;
+    let cache = context.room_cache();
+
+    if let Some(room) = cache.get_by_classroom_id(classroom_id, ttl) {
+        return Ok(room);
+    }
+
+    let query = db::room::FindQuery::by_classroom_id(classroom_id);
+    let mut conn = context.get_ro_conn().await?;
+
+    let room = context
+        .metrics()
+        .measure_query(QueryKey::RoomFindQuery, query.execute(&mut conn))
+        .await
+        .context("Failed to find room")
+        .error(AppErrorKind::DbQueryFailed)?
+        .context("Room not found")
+        .error(AppErrorKind::RoomNotFound)?;
+
+    cache.insert(&room, ttl);
+    Ok(room)
+}
+
+/// Authorizes the agent for the action on the room.
+///
+/// A share token grants `read` of its room only without asking the tenant.
+pub async fn authorize_room<C: Context>(
+    context: &mut C,
+    room: &db::room::Object,
+    reqp: RequestParams<'_>,
+    object: AuthzObject,
+    action: &str,
+) -> Result<Duration, AppError> {
+    match reqp.shared_room_id() {
+        Some(room_id) if room_id == room.id() && action == "read" => Ok(Duration::zero()),
+        Some(_) => Err(anyhow!("Share token doesn't grant '{}' access", action))
+            .error(AppErrorKind::AccessDenied),
+        None => context
+            .authz()
+            .authorize_room(
+                room,
+                reqp.as_account_id().to_owned(),
+                object.into(),
+                action.into(),
+            )
+            .await
+            .map_err(AppError::from),
+    }
+}
+
+/// Span for a background operation on the room so that its logs carry the same correlation
+/// fields as the request which triggered it. It's a child of the current span if any.
+pub fn operation_span(
+    kind: &'static str,
+    room: &db::room::Object,
+    agent_id: Option<&AgentId>,
+) -> Span {
+    info_span!(
+        "operation",
+        kind,
+        room_id = %room.id(),
+        classroom_id = %room.classroom_id(),
+        agent_id = agent_id.map(display),
+    )
+}
+
+/// Records an asynchronous task of the request to let clients who miss its notification
+/// get the outcome with `task.read`. The task is to be finished with `finish_task`.
+pub async fn start_task<C: Context>(
+    context: &mut C,
+    kind: &str,
+    room: &db::room::Object,
+    agent_id: &AgentId,
+) -> Result<db::task::Object, AppError> {
+    let query = db::task::InsertQuery::new(kind, room.id(), agent_id.to_owned());
+    let mut conn = context.get_conn().await?;
+
+    context
+        .metrics()
+        .measure_query(QueryKey::TaskInsertQuery, query.execute(&mut conn))
+        .await
+        .context("Failed to insert task")
+        .error(AppErrorKind::DbQueryFailed)
+}
+
+/// Saves the notification the task has finished with. The notification gets published
+/// anyway so failures are only logged.
+pub async fn finish_task(
+    db: &PgPool,
+    metrics: &Metrics,
+    task_id: Uuid,
+    status: db::task::Status,
+    notification: &impl Serialize,
+) {
+    let result = async {
+        let notification =
+            serde_json::to_value(notification).context("Failed to serialize notification")?;
+
+        let query = db::task::FinishQuery::new(task_id, status, notification);
+
+        let mut conn = db
+            .acquire()
+            .await
+            .context("Failed to acquire db connection")?;
+
+        metrics
+            .measure_query(QueryKey::TaskFinishQuery, query.execute(&mut conn))
+            .await
+            .context("Failed to update task")
+    }
+    .await;
+
+    match result {
+        Ok(Some(_)) => (),
+        Ok(None) => warn!(%task_id, "No task found to save the notification to"),
+        Err(err) => error!(%task_id, "Failed to save task notification: {:?}", err),
+    }
+}
+
 pub fn add_room_logger_tags(room: &db::room::Object) {
+    sentry::set_room_tags(room);
+
     let span = tracing::Span::current();
     span.record("room_id", &display(room.id()));
     span.record("classroom_id", &display(room.classroom_id()));
@@ -95,3 +249,384 @@ pub fn add_room_logger_tags(room: &db::room::Object) {
         }
     }
 }
+
+/// Tells whether the room has ready agents, using the presence cache when possible.
+pub async fn room_has_ready_agents<C: Context>(
+    context: &mut C,
+    room_id: Uuid,
+) -> Result<bool, AppError> {
+    let ttl = context.config().presence_check.cache_ttl;
+    let cache = context.presence_cache();
+
+    if let Some(has_ready_agents) = cache.get(room_id, ttl) {
+        return Ok(has_ready_agents);
+    }
+
+    let query = db::agent::ReadyExistsQuery::new(room_id);
+    let mut conn = context.get_ro_conn().await?;
+
+    let has_ready_agents = context
+        .metrics()
+        .measure_query(QueryKey::AgentReadyExistsQuery, query.execute(&mut conn))
+        .await
+        .context("Failed to check ready agents")
+        .error(AppErrorKind::DbQueryFailed)?;
+
+    cache.insert(room_id, has_ready_agents, ttl);
+    Ok(has_ready_agents)
+}
+
+/// Redis-backed room presence when it's enabled and the cache is configured.
+pub fn room_presence<C: Context>(context: &C) -> Option<RoomPresence> {
+    let config = context.config();
+
+    if !config.presence.enabled {
+        return None;
+    }
+
+    context
+        .redis_pool()
+        .clone()
+        .map(|pool| RoomPresence::new(pool, config.presence.ttl))
+}
+
+/// Lists ready agents of the room from the presence set when possible, falling back to the DB.
+pub async fn room_online_agents<C: Context>(
+    context: &mut C,
+    room_id: Uuid,
+) -> Result<Vec<AgentId>, AppError> {
+    let presence = room_presence(context);
+
+    if let Some(ref presence) = presence {
+        match presence.list(room_id).await {
+            Ok(Some(agent_ids)) => return Ok(agent_ids),
+            Ok(None) => (),
+            Err(err) => warn!("Failed to read room presence: {:?}", err),
+        }
+    }
+
+    let query = db::agent::ReadyListQuery::new(room_id);
+    let mut conn = context.get_ro_conn().await?;
+
+    let agent_ids = context
+        .metrics()
+        .measure_query(QueryKey::AgentReadyListQuery, query.execute(&mut conn))
+        .await
+        .context("Failed to list ready agents")
+        .error(AppErrorKind::DbQueryFailed)?;
+
+    if let Some(presence) = presence {
+        if let Err(err) = presence.load(room_id, &agent_ids).await {
+            warn!("Failed to load room presence: {:?}", err);
+        }
+    }
+
+    Ok(agent_ids)
+}
+
+/// Number of ready agents to put into enter and leave notifications.
+/// Missing when the presence is disabled so notifications don't cost a DB query.
+pub async fn room_online_count<C: Context>(context: &mut C, room_id: Uuid) -> Option<usize> {
+    let presence = room_presence(context)?;
+
+    match presence.count(room_id).await {
+        Ok(Some(count)) => Some(count),
+        Ok(None) => room_online_agents(context, room_id)
+            .await
+            .map(|agent_ids| agent_ids.len())
+            .ok(),
+        Err(err) => {
+            warn!("Failed to count room presence: {:?}", err);
+            None
+        }
+    }
+}
+
+/// Updates the room's presence set on enter or leave. Failures only make the set stale
+/// until it expires so they are logged and ignored.
+pub async fn update_room_presence<C: Context>(
+    context: &C,
+    room_id: Uuid,
+    agent_id: &AgentId,
+    online: bool,
+) {
+    if let Some(presence) = room_presence(context) {
+        let result = if online {
+            presence.enter(room_id, agent_id).await
+        } else {
+            presence.leave(room_id, agent_id).await
+        };
+
+        if let Err(err) = result {
+            warn!("Failed to update room presence: {:?}", err);
+        }
+    }
+}
+
+/// Room capacity, either its own or the configured default. `None` means it's unlimited.
+pub fn room_capacity<C: Context>(context: &C, room: &db::room::Object) -> Option<i32> {
+    room.capacity().or(context.config().room_capacity.default)
+}
+
+/// Admits up to `places` agents from the head of the room's queue and returns them.
+///
+/// Places are held for admitted agents until they repeat `room.enter`. Queued agents aren't
+/// subscribed to any topic of the room so they learn about admission by polling `room.enter`
+/// rather than with a notification which would have to go to the whole audience.
+///
+/// Must be called under the room capacity lock, see `room_capacity_lock`.
+pub async fn admit_queued_agents<C: Context>(
+    context: &C,
+    conn: &mut PgConnection,
+    room: &db::room::Object,
+    places: i64,
+) -> Result<Vec<AgentId>, AppError> {
+    if places <= 0 {
+        return Ok(vec![]);
+    }
+
+    let query = db::room_queue::AdmitQuery::new(room.id(), places);
+
+    context
+        .metrics()
+        .measure_query(QueryKey::RoomQueueAdmitQuery, query.execute(conn))
+        .await
+        .context("Failed to admit agents from the queue")
+        .error(AppErrorKind::DbQueryFailed)
+}
+
+/// Serializes capacity checks and queue admissions of the room until the transaction ends.
+pub async fn room_capacity_lock(conn: &mut PgConnection, room_id: Uuid) -> Result<(), AppError> {
+    db::advisory_lock::XactLockQuery::new(format!("room_capacity:{room_id}"))
+        .execute(conn)
+        .await
+        .context("Failed to lock room capacity")
+        .error(AppErrorKind::DbQueryFailed)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Builds a weak entity tag out of anything identifying the response contents.
+pub fn etag(value: impl Hash) -> String {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    format!("W/\"{:x}\"", hasher.finish())
+}
+
+/// Builds an entity tag for responses made of the room's events.
+///
+/// `shape` is anything else the response depends on like normalized request parameters
+/// and redaction so that a tag never matches a response to a different request.
+pub async fn room_events_etag<C: Context>(
+    context: &mut C,
+    room: &db::room::Object,
+    shape: impl Hash,
+) -> Result<String, AppError> {
+    let query = db::event::RoomVersionQuery::new(room.id());
+    let mut conn = context.get_ro_conn().await?;
+
+    let version = context
+        .metrics()
+        .measure_query(QueryKey::EventRoomVersionQuery, query.execute(&mut conn))
+        .await
+        .context("Failed to get room version")
+        .error(AppErrorKind::DbQueryFailed)?;
+
+    Ok(etag((room.id(), version, shape)))
+}
+
+/// Checks `If-None-Match` header value against the entity tag using weak comparison.
+pub fn etag_matches(if_none_match: Option<&str>, etag: &str) -> bool {
+    let strip = |tag: &str| tag.trim().trim_start_matches("W/").to_owned();
+
+    match if_none_match {
+        Some(value) if value.trim() == "*" => true,
+        Some(value) => value.split(',').any(|tag| strip(tag) == strip(etag)),
+        None => false,
+    }
+}
+
+pub fn if_none_match(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .map(ToOwned::to_owned)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+const EVENT_FIELDS: &[&str] = &[
+    "id",
+    "room_id",
+    "type",
+    "set",
+    "label",
+    "attribute",
+    "attributes",
+    "data",
+    "occurred_at",
+    "created_by",
+    "created_at",
+    "deleted_at",
+    "original_occurred_at",
+    "original_created_by",
+    "removed",
+    "moderation_status",
+    "seq",
+];
+
+/// Sparse fieldset of events parsed from a comma separated `fields` parameter.
+/// Fields are kept sorted without duplicates so that equal sets are equal regardless of the order.
+#[derive(Debug, Hash)]
+pub struct EventFields(Vec<String>);
+
+impl EventFields {
+    pub fn contains(&self, field: &str) -> bool {
+        self.0.iter().any(|f| f == field)
+    }
+
+    /// Removes fields which were not requested from a serialized event or a list of events.
+    /// `binary_data` goes along with `data`.
+    pub fn retain(&self, value: &mut JsonValue) {
+        match value {
+            JsonValue::Array(events) => events.iter_mut().for_each(|event| self.retain(event)),
+            JsonValue::Object(event) => event.retain(|key, _| {
+                self.contains(key) || (key == "binary_data" && self.contains("data"))
+            }),
+            _ => (),
+        }
+    }
+}
+
+impl std::str::FromStr for EventFields {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fields = vec![];
+
+        for field in s.split(',').map(str::trim).filter(|f| !f.is_empty()) {
+            if !EVENT_FIELDS.contains(&field) {
+                bail!("Unknown field = '{}'", field);
+            }
+
+            fields.push(field.to_owned());
+        }
+
+        if fields.is_empty() {
+            bail!("'fields' can't be empty");
+        }
+
+        fields.sort_unstable();
+        fields.dedup();
+
+        Ok(Self(fields))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Redaction rules applicable to the requesting agent.
+#[derive(Debug)]
+pub struct Redaction(Vec<RedactionRule>);
+
+impl Redaction {
+    /// Picks configured redaction rules unless the agent can update the room.
+    /// Returns the authorization time when it was required.
+    pub async fn for_agent<C: Context>(
+        context: &mut C,
+        room: &db::room::Object,
+        reqp: RequestParams<'_>,
+    ) -> Result<(Self, Option<Duration>), AppError> {
+        let rules = context.config().redaction.clone();
+
+        if rules.is_empty() {
+            return Ok((Self(rules), None));
+        }
+
+        let object = AuthzObject::room(room);
+
+        match authorize_room(context, room, reqp, object, "update").await {
+            Ok(duration) => Ok((Self(vec![]), Some(duration))),
+            Err(err) if err.error_kind() == AppErrorKind::AccessDenied => Ok((Self(rules), None)),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn apply(&self, events: &mut [db::event::Object]) {
+        if self.0.is_empty() {
+            return;
+        }
+
+        // Encrypted content can't be looked into.
+        for event in events.iter_mut().filter(|event| !event.content_encrypted()) {
+            let rules = self
+                .0
+                .iter()
+                .filter(|rule| rule.kind == event.kind())
+                .collect::<Vec<_>>();
+
+            for rule in rules {
+                event.redact(&rule.path, rule.mask.as_ref());
+            }
+        }
+    }
+}
+
+/// Decrypts `data` of the event if the service has encrypted it at rest.
+pub fn open_event<C: Context>(context: &C, event: &mut db::event::Object) -> Result<(), AppError> {
+    match context.data_encryption() {
+        Some(encryption) => encryption
+            .open(event)
+            .error(AppErrorKind::InternalServerError),
+        None => Ok(()),
+    }
+}
+
+/// Decrypts `data` of events the service has encrypted at rest. Call it before redaction.
+pub fn open_events<C: Context>(
+    context: &C,
+    events: &mut [db::event::Object],
+) -> Result<(), AppError> {
+    events
+        .iter_mut()
+        .try_for_each(|event| open_event(context, event))
+}
+
+/// Wraps an event insert query building error counting rejected draw events.
+pub fn invalid_event<C: Context>(context: &C, err: anyhow::Error) -> AppError {
+    if let Some(err) = err.downcast_ref::<db::event::SchemaError>() {
+        context.metrics().observe_draw_event_reject(err.reason());
+    }
+
+    AppError::new(AppErrorKind::InvalidEvent, err)
+}
+
+/// `occurred_at` of an event created in the room right now, nanoseconds since the room opening.
+pub fn occurred_at(room: &db::room::Object) -> Result<i64, AppError> {
+    match room.time().map(|t| t.start().to_owned()) {
+        Ok(opened_at) => Ok((Utc::now() - opened_at)
+            .num_nanoseconds()
+            .unwrap_or(i64::MAX)),
+        _ => Err(anyhow!("Invalid room time")).error(AppErrorKind::InvalidRoomTime),
+    }
+}
+
+/// Checks `data` of events created by dedicated endpoints like questions and polls.
+pub fn validate_object_data<C: Context>(
+    context: &C,
+    data: JsonValue,
+) -> Result<JsonValue, AppError> {
+    if !data.is_object() {
+        return Err(anyhow!("Data must be an object")).error(AppErrorKind::InvalidPayload);
+    }
+
+    if data.to_string().len() >= context.config().constraint.payload_size {
+        return Err(anyhow!("Payload size exceeded")).error(AppErrorKind::PayloadSizeExceeded);
+    }
+
+    Ok(data)
+}
@@ -8,7 +8,7 @@ use svc_agent::mqtt::{
 };
 
 use crate::app::context::Context;
-use crate::app::error::Error as AppError;
+use crate::app::error::{Error as AppError, ErrorKind, ErrorKindDescription};
 pub(self) use crate::app::message_handler::MessageStream;
 use crate::app::message_handler::{EventEnvelopeHandler, RequestEnvelopeHandler};
 
@@ -52,8 +52,11 @@ macro_rules! request_routes {
 
 // Request routes configuration: method => RequestHandler
 request_routes!(
+    "activity.read" => activity::ReadHandler,
+    "adjustment.read" => adjustment::ReadHandler,
     "agent.list" => agent::ListHandler,
     "agent.update" => agent::UpdateHandler,
+    "attachment.list" => attachment::ListHandler,
     "ban.list" => ban::ListHandler,
     "change.create" => change::CreateHandler,
     "change.delete" => change::DeleteHandler,
@@ -62,16 +65,61 @@ request_routes!(
     "edition.create" => edition::CreateHandler,
     "edition.list" => edition::ListHandler,
     "edition.delete" => edition::DeleteHandler,
+    "edition.summary" => edition::SummaryHandler,
+    "event.clear_attribute" => event::ClearAttributeHandler,
     "event.create" => event::CreateHandler,
+    "event.history" => event::HistoryHandler,
+    "event.import" => event::ImportHandler,
     "event.list" => event::ListHandler,
+    "event.set_attribute" => event::SetAttributeHandler,
+    "event.since" => event::SinceHandler,
+    "hand_queue.list" => hand_queue::ListHandler,
+    "hand_queue.lower" => hand_queue::LowerHandler,
+    "hand_queue.pop" => hand_queue::PopHandler,
+    "hand_queue.raise" => hand_queue::RaiseHandler,
+    "moderation.approve" => moderation::ApproveHandler,
+    "moderation.list" => moderation::ListHandler,
+    "moderation.reject" => moderation::RejectHandler,
+    "poll.close" => poll::CloseHandler,
+    "poll.create" => poll::CreateHandler,
+    "poll.results" => poll::ResultsHandler,
+    "poll.vote" => poll::VoteHandler,
+    "question.answer" => question::AnswerHandler,
+    "question.create" => question::CreateHandler,
+    "question.list" => question::ListHandler,
+    "question.upvote" => question::UpvoteHandler,
+    "retention.read" => retention::ReadHandler,
+    "retention.set" => retention::SetHandler,
     "room.adjust" => room::AdjustHandler,
+    "room.adjust_preview" => room::AdjustPreviewHandler,
+    "room.bootstrap" => room::BootstrapHandler,
     "room.create" => room::CreateHandler,
     "room.dump_events" => room::EventsDumpHandler,
     "room.enter" => room::EnterHandler,
+    "room.list" => room::ListHandler,
     "room.locked_types" => room::LockedTypesHandler,
+    "room.notify" => room::NotifyHandler,
+    "room.operation_status" => room::OperationStatusHandler,
+    "room.permissions" => room::PermissionsHandler,
+    "room.pinned_events" => room::PinnedEventsHandler,
+    "room.presence" => room::PresenceHandler,
     "room.read" => room::ReadHandler,
+    "room.replay" => room::ReplayHandler,
+    "room.schedule_lock" => room::ScheduleLockHandler,
+    "room.search" => room::SearchHandler,
+    "room.share" => room::ShareHandler,
+    "room.transcript" => room::TranscriptHandler,
     "room.update" => room::UpdateHandler,
+    "room.update_settings" => room::UpdateSettingsHandler,
+    "room.verify" => room::VerifyHandler,
+    "set.count_since" => set::CountSinceHandler,
+    "set.list" => set::ListHandler,
     "state.read" => state::ReadHandler,
+    "task.read" => task::ReadHandler,
+    "tenant_ban.create" => tenant_ban::CreateHandler,
+    "tenant_ban.delete" => tenant_ban::DeleteHandler,
+    "tenant_ban.list" => tenant_ban::ListHandler,
+    "whiteboard.pages" => whiteboard::PagesHandler,
     "system.vacuum" => system::VacuumHandler
 );
 
@@ -137,17 +185,30 @@ event_routes!(
 
 ///////////////////////////////////////////////////////////////////////////////
 
+pub mod activity;
+pub mod adjustment;
+pub mod admin;
 pub mod agent;
+pub mod attachment;
 pub mod authz;
 pub mod ban;
 pub mod change;
 pub mod edition;
 pub mod event;
+pub mod hand_queue;
 pub mod helpers;
+pub mod moderation;
+pub mod poll;
+pub mod question;
+pub mod retention;
 pub mod room;
+pub mod set;
 pub mod state;
 mod subscription;
 mod system;
+pub mod task;
+pub mod tenant_ban;
+pub mod whiteboard;
 
 pub(self) mod prelude {
     pub(super) use super::{
@@ -155,7 +216,9 @@ pub(self) mod prelude {
         RequestResult,
     };
     pub(super) use crate::app::endpoint::authz::AuthzObject;
-    pub(super) use crate::app::error::{Error as AppError, ErrorExt, ErrorKind as AppErrorKind};
+    pub(super) use crate::app::error::{
+        Error as AppError, ErrorExt, ErrorKind as AppErrorKind, ErrorPayload,
+    };
     pub(super) use crate::metrics::QueryKey;
 
     pub use crate::app::context::{AppContext, Context};
@@ -164,6 +227,17 @@ pub(self) mod prelude {
     pub(super) use svc_authn::Authenticable;
 }
 
+/// Lists all error kinds with their statuses for clients to map errors programmatically.
+#[utoipa::path(
+    get,
+    path = "/errors",
+    tag = "error",
+    responses((status = 200, description = "Error catalog", body = [ErrorKindDescription]))
+)]
+pub async fn list_errors() -> axum::Json<Vec<ErrorKindDescription>> {
+    axum::Json(ErrorKind::catalog())
+}
+
 pub async fn read_options() -> hyper::Response<hyper::Body> {
     hyper::Response::builder()
         .body(hyper::Body::empty())
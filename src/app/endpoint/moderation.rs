@@ -0,0 +1,529 @@
+use anyhow::Context as AnyhowContext;
+use async_trait::async_trait;
+use axum::{
+    extract::{self, Path, Query},
+    Json,
+};
+use serde_derive::Deserialize;
+use svc_agent::mqtt::ResponseStatus;
+use svc_utils::extractors::AgentIdExtractor;
+use tracing::{field::display, instrument, Span};
+use uuid::Uuid;
+
+use crate::app::endpoint::prelude::*;
+use crate::db;
+use crate::db::event::ModerationStatus;
+
+///////////////////////////////////////////////////////////////////////////////
+
+const MAX_LIMIT: usize = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct ListPayload {
+    last_occurred_at: Option<i64>,
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListRequest {
+    room_id: Uuid,
+    #[serde(flatten)]
+    payload: ListPayload,
+}
+
+pub async fn list(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<Uuid>,
+    Query(payload): Query<ListPayload>,
+) -> RequestResult {
+    let request = ListRequest { room_id, payload };
+    ListHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct ListHandler;
+
+#[async_trait]
+impl RequestHandler for ListHandler {
+    type Payload = ListRequest;
+
+    #[instrument(skip_all, fields(room_id, scope, classroom_id))]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload { room_id, payload }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let room = helpers::find_room(context, room_id, helpers::RoomTimeRequirement::Any).await?;
+        let authz_time = authorize_moderation(context, &room, reqp).await?;
+
+        // Retrieve pending events from the oldest to the newest.
+        let mut query = db::event::ListQuery::new()
+            .room_id(room.id())
+            .moderation_status(ModerationStatus::Pending)
+            .limit(std::cmp::min(payload.limit.unwrap_or(MAX_LIMIT), MAX_LIMIT));
+
+        if let Some(last_occurred_at) = payload.last_occurred_at {
+            query = query.last_occurred_at(last_occurred_at);
+        }
+
+        let mut events = {
+            let mut conn = context.get_ro_conn().await?;
+
+            context
+                .metrics()
+                .measure_query(QueryKey::EventListQuery, query.execute(&mut conn))
+                .await
+                .context("Failed to list pending events")
+                .error(AppErrorKind::DbQueryFailed)?
+        };
+
+        helpers::open_events(context, &mut events)?;
+
+        Ok(AppResponse::new(
+            ResponseStatus::OK,
+            events,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct ResolvePayload {
+    event_id: Uuid,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResolveRequest {
+    room_id: Uuid,
+    #[serde(flatten)]
+    payload: ResolvePayload,
+}
+
+pub async fn approve(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<Uuid>,
+    Json(payload): Json<ResolvePayload>,
+) -> RequestResult {
+    let request = ResolveRequest { room_id, payload };
+    ApproveHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct ApproveHandler;
+
+#[async_trait]
+impl RequestHandler for ApproveHandler {
+    type Payload = ResolveRequest;
+
+    #[instrument(skip_all, fields(room_id, scope, classroom_id, event_id))]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload { room_id, payload }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        // Approved events get into the room so it must be open like on event creation.
+        let room = helpers::find_room(context, room_id, helpers::RoomTimeRequirement::Open).await?;
+        let authz_time = authorize_moderation(context, &room, reqp).await?;
+
+        let event = resolve(context, &room, payload.event_id, ModerationStatus::Approved).await?;
+
+        let mut response = AppResponse::new(
+            ResponseStatus::OK,
+            event.clone(),
+            context.start_timestamp(),
+            Some(authz_time),
+        );
+
+        // Room subscribers see the event for the first time so it's the same as creation for them.
+        response.add_notification(
+            "event.create",
+            &context.config().room_topic(room.audience(), room.id()),
+            event,
+            context.start_timestamp(),
+        )?;
+
+        Ok(response)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+pub async fn reject(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<Uuid>,
+    Json(payload): Json<ResolvePayload>,
+) -> RequestResult {
+    let request = ResolveRequest { room_id, payload };
+    RejectHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct RejectHandler;
+
+#[async_trait]
+impl RequestHandler for RejectHandler {
+    type Payload = ResolveRequest;
+
+    #[instrument(skip_all, fields(room_id, scope, classroom_id, event_id))]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload { room_id, payload }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let room = helpers::find_room(context, room_id, helpers::RoomTimeRequirement::Any).await?;
+        let authz_time = authorize_moderation(context, &room, reqp).await?;
+
+        let event = resolve(context, &room, payload.event_id, ModerationStatus::Rejected).await?;
+
+        Ok(AppResponse::new(
+            ResponseStatus::OK,
+            event,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Moderators are those who can update the room.
+async fn authorize_moderation<C: Context>(
+    context: &mut C,
+    room: &db::room::Object,
+    reqp: RequestParams<'_>,
+) -> Result<chrono::Duration, AppError> {
+    let object = AuthzObject::room(room).into();
+
+    let authz_time = context
+        .authz()
+        .authorize_room(
+            room,
+            reqp.as_account_id().to_owned(),
+            object,
+            "update".into(),
+        )
+        .await?;
+
+    Ok(authz_time)
+}
+
+async fn resolve<C: Context>(
+    context: &mut C,
+    room: &db::room::Object,
+    event_id: Uuid,
+    moderation_status: ModerationStatus,
+) -> Result<db::event::Object, AppError> {
+    Span::current().record("event_id", display(event_id));
+
+    let query = db::event::ModerationUpdateQuery::new(room.id(), event_id, moderation_status);
+    let mut conn = context.get_conn().await?;
+
+    let mut event = context
+        .metrics()
+        .measure_query(
+            QueryKey::EventModerationUpdateQuery,
+            query.execute(&mut conn),
+        )
+        .await
+        .context("Failed to update event moderation status")
+        .error(AppErrorKind::DbQueryFailed)?
+        .ok_or_else(|| anyhow!("Pending event not found"))
+        .error(AppErrorKind::EventNotFound)?;
+
+    helpers::open_event(context, &mut event)?;
+    Ok(event)
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::db::event::Object as Event;
+    use crate::test_helpers::prelude::*;
+
+    use super::*;
+
+    async fn insert_moderated_room(db: &TestDb) -> db::room::Object {
+        let mut conn = db.get_conn().await;
+        let now = chrono::Utc::now();
+
+        factory::Room::new(Uuid::new_v4(), db::room::ClassType::Webinar)
+            .audience(USR_AUDIENCE)
+            .time((
+                std::ops::Bound::Included(now),
+                std::ops::Bound::Excluded(now + chrono::Duration::hours(1)),
+            ))
+            .moderated(true)
+            .insert(&mut conn)
+            .await
+    }
+
+    async fn insert_pending_event(
+        db: &TestDb,
+        room: &db::room::Object,
+        agent: &TestAgent,
+    ) -> Event {
+        let mut conn = db.get_conn().await;
+
+        factory::Event::new()
+            .room_id(room.id())
+            .kind("question")
+            .data(&json!({ "text": "hello" }))
+            .occurred_at(1000)
+            .created_by(agent.agent_id())
+            .moderation_status(ModerationStatus::Pending)
+            .insert(&mut conn)
+            .await
+    }
+
+    fn allow_moderation(authz: &mut TestAuthz, agent: &TestAgent, room: &db::room::Object) {
+        authz.allow(
+            agent.account_id(),
+            vec!["classrooms", &room.classroom_id().to_string()],
+            "update",
+        );
+    }
+
+    #[tokio::test]
+    async fn create_pending_event() {
+        let db = TestDb::new().await;
+        let room = insert_moderated_room(&db).await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        let author = agent.account_id().to_string();
+
+        let object = vec![
+            "classrooms",
+            &classroom_id,
+            "events",
+            "question",
+            "authors",
+            &author,
+        ];
+
+        authz.allow(agent.account_id(), object, "create");
+
+        // Make event.create request by a non-moderator.
+        let mut context = TestContext::new(db.clone(), authz);
+
+        let payload = crate::app::endpoint::event::CreateRequest {
+            room_id: room.id(),
+            payload: crate::app::endpoint::event::CreatePayload {
+                kind: String::from("question"),
+                set: None,
+                label: None,
+                attribute: None,
+                attributes: vec![],
+                data: json!({ "text": "hello" }),
+                is_claim: false,
+                is_persistent: true,
+                removed: false,
+                content_encrypted: false,
+                key_id: None,
+                skip_broadcast_if_empty: false,
+                expected_last_occurred_at: None,
+            },
+        };
+
+        let messages = handle_request::<crate::app::endpoint::event::CreateHandler>(
+            &mut context,
+            &agent,
+            payload,
+        )
+        .await
+        .expect("Event creation failed");
+
+        // The author gets the pending event but it's not broadcasted.
+        assert_eq!(messages.len(), 1);
+
+        let (event, respp, _) = find_response::<Event>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::CREATED);
+        assert_eq!(event.moderation_status(), ModerationStatus::Pending);
+
+        // The event is not listed until approved.
+        let mut conn = db.get_conn().await;
+
+        let events = db::event::ListQuery::new()
+            .room_id(room.id())
+            .execute(&mut conn)
+            .await
+            .expect("Failed to list events");
+
+        assert!(events.is_empty());
+    }
+
+    #[tokio::test]
+    async fn list_pending_events() {
+        let db = TestDb::new().await;
+        let room = insert_moderated_room(&db).await;
+        let user = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let moderator = TestAgent::new("web", "moderator", USR_AUDIENCE);
+        let event = insert_pending_event(&db, &room, &user).await;
+
+        let mut authz = TestAuthz::new();
+        allow_moderation(&mut authz, &moderator, &room);
+
+        let mut context = TestContext::new(db, authz);
+
+        let payload = ListRequest {
+            room_id: room.id(),
+            payload: ListPayload {
+                last_occurred_at: None,
+                limit: None,
+            },
+        };
+
+        let messages = handle_request::<ListHandler>(&mut context, &moderator, payload)
+            .await
+            .expect("Pending events listing failed");
+
+        let (events, respp, _) = find_response::<Vec<Event>>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].id(), event.id());
+    }
+
+    #[tokio::test]
+    async fn list_pending_events_not_authorized() {
+        let db = TestDb::new().await;
+        let room = insert_moderated_room(&db).await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let mut context = TestContext::new(db, TestAuthz::new());
+
+        let payload = ListRequest {
+            room_id: room.id(),
+            payload: ListPayload {
+                last_occurred_at: None,
+                limit: None,
+            },
+        };
+
+        let err = handle_request::<ListHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success on pending events listing");
+
+        assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn approve_event() {
+        let db = TestDb::new().await;
+        let room = insert_moderated_room(&db).await;
+        let user = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let moderator = TestAgent::new("web", "moderator", USR_AUDIENCE);
+        let event = insert_pending_event(&db, &room, &user).await;
+
+        let mut authz = TestAuthz::new();
+        allow_moderation(&mut authz, &moderator, &room);
+
+        let mut context = TestContext::new(db.clone(), authz);
+
+        let payload = ResolveRequest {
+            room_id: room.id(),
+            payload: ResolvePayload {
+                event_id: event.id(),
+            },
+        };
+
+        let messages = handle_request::<ApproveHandler>(&mut context, &moderator, payload)
+            .await
+            .expect("Event approval failed");
+
+        let (resp_event, respp, _) = find_response::<Event>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert_eq!(resp_event.moderation_status(), ModerationStatus::Approved);
+
+        // The event is broadcasted to the room as a new one.
+        let (room_event, evp, topic) = find_event::<Event>(messages.as_slice());
+        assert!(topic.ends_with(&format!("/rooms/{}/events", room.id())));
+        assert_eq!(evp.label(), "event.create");
+        assert_eq!(room_event.id(), event.id());
+
+        // Now it's listed.
+        let mut conn = db.get_conn().await;
+
+        let events = db::event::ListQuery::new()
+            .room_id(room.id())
+            .execute(&mut conn)
+            .await
+            .expect("Failed to list events");
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].id(), event.id());
+    }
+
+    #[tokio::test]
+    async fn reject_event() {
+        let db = TestDb::new().await;
+        let room = insert_moderated_room(&db).await;
+        let user = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let moderator = TestAgent::new("web", "moderator", USR_AUDIENCE);
+        let event = insert_pending_event(&db, &room, &user).await;
+
+        let mut authz = TestAuthz::new();
+        allow_moderation(&mut authz, &moderator, &room);
+
+        let mut context = TestContext::new(db.clone(), authz);
+
+        let payload = ResolveRequest {
+            room_id: room.id(),
+            payload: ResolvePayload {
+                event_id: event.id(),
+            },
+        };
+
+        let messages = handle_request::<RejectHandler>(&mut context, &moderator, payload)
+            .await
+            .expect("Event rejection failed");
+
+        assert_eq!(messages.len(), 1);
+
+        let (resp_event, respp, _) = find_response::<Event>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert_eq!(resp_event.moderation_status(), ModerationStatus::Rejected);
+
+        // Rejected event can't be approved afterwards.
+        let payload = ResolveRequest {
+            room_id: room.id(),
+            payload: ResolvePayload {
+                event_id: event.id(),
+            },
+        };
+
+        let err = handle_request::<ApproveHandler>(&mut context, &moderator, payload)
+            .await
+            .expect_err("Unexpected success on rejected event approval");
+
+        assert_eq!(err.status(), ResponseStatus::NOT_FOUND);
+        assert_eq!(err.kind(), "event_not_found");
+    }
+}
@@ -0,0 +1,646 @@
+use anyhow::Context as AnyhowContext;
+use async_trait::async_trait;
+use axum::{
+    extract::{self, Path},
+    Json,
+};
+use chrono::{Duration, Utc};
+use serde_derive::Deserialize;
+use serde_json::Value as JsonValue;
+use sqlx::Acquire;
+use svc_agent::{mqtt::ResponseStatus, Addressable};
+use svc_utils::extractors::AgentIdExtractor;
+use tracing::{field::display, instrument, Span};
+use uuid::Uuid;
+
+use crate::app::endpoint::prelude::*;
+use crate::db;
+use crate::db::event::EventKind;
+use crate::db::poll::{Object as Poll, Results};
+
+/// Set of `poll` events. Polls share identifiers with their events.
+const POLLS_SET: &str = "polls";
+
+/// Polls may last a day at most.
+const MAX_DURATION: u64 = 24 * 60 * 60;
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct CreatePayload {
+    /// The poll content, e.g. `{"question": "...", "options": ["...", "..."]}`.
+    data: JsonValue,
+    /// Number of options to vote for.
+    options: usize,
+    /// The correct option index for quizzes.
+    correct_option: Option<usize>,
+    /// Seconds to close the poll in. It stays open until closed explicitly otherwise.
+    duration: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateRequest {
+    room_id: Uuid,
+    #[serde(flatten)]
+    payload: CreatePayload,
+}
+
+pub async fn create(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<Uuid>,
+    Json(payload): Json<CreatePayload>,
+) -> RequestResult {
+    let request = CreateRequest { room_id, payload };
+    CreateHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct CreateHandler;
+
+#[async_trait]
+impl RequestHandler for CreateHandler {
+    type Payload = CreateRequest;
+
+    #[instrument(skip_all, fields(room_id, scope, classroom_id, poll_id))]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload { room_id, payload }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let room = helpers::find_room(context, room_id, helpers::RoomTimeRequirement::Open).await?;
+
+        // Polls are run by those who can update the room.
+        let object = AuthzObject::room(&room);
+        let authz_time = helpers::authorize_room(context, &room, reqp, object, "update").await?;
+
+        let CreatePayload {
+            data,
+            options,
+            correct_option,
+            duration,
+        } = payload;
+
+        let max_options = context.config().polls.max_options;
+
+        if options < 2 || options > max_options {
+            return Err(anyhow!("Polls must have 2 to {} options", max_options))
+                .error(AppErrorKind::InvalidPayload);
+        }
+
+        if matches!(correct_option, Some(correct_option) if correct_option >= options) {
+            return Err(anyhow!("'correct_option' is out of options"))
+                .error(AppErrorKind::InvalidPayload);
+        }
+
+        if matches!(duration, Some(duration) if duration == 0 || duration > MAX_DURATION) {
+            return Err(anyhow!("'duration' must be 1 to {} seconds", MAX_DURATION))
+                .error(AppErrorKind::InvalidPayload);
+        }
+
+        let data = helpers::validate_object_data(context, data)?;
+
+        let event_query = db::event::InsertQuery::new(
+            room.id(),
+            EventKind::Poll.into(),
+            data,
+            helpers::occurred_at(&room)?,
+            reqp.as_agent_id().to_owned(),
+            context.data_encryption().as_deref(),
+        )
+        .map_err(|err| helpers::invalid_event(context, err))?
+        .set(POLLS_SET.to_owned());
+
+        let mut conn = context.get_conn().await?;
+
+        let mut txn = conn
+            .begin()
+            .await
+            .context("Failed to acquire transaction")
+            .error(AppErrorKind::DbQueryFailed)?;
+
+        let event = context
+            .metrics()
+            .measure_query(QueryKey::EventInsertQuery, event_query.execute(&mut txn))
+            .await
+            .context("Failed to insert poll event")
+            .error(AppErrorKind::DbQueryFailed)?;
+
+        Span::current().record("poll_id", &display(event.id()));
+
+        let mut poll_query = db::poll::InsertQuery::new(event.id(), room.id(), options as i32);
+
+        if let Some(correct_option) = correct_option {
+            poll_query = poll_query.correct_option(correct_option as i32);
+        }
+
+        if let Some(duration) = duration {
+            poll_query = poll_query.closes_at(Utc::now() + Duration::seconds(duration as i64));
+        }
+
+        context
+            .metrics()
+            .measure_query(QueryKey::PollInsertQuery, poll_query.execute(&mut txn))
+            .await
+            .context("Failed to insert poll")
+            .error(AppErrorKind::DbQueryFailed)?;
+
+        let poll = find_poll(context, &mut txn, room.id(), event.id()).await?;
+
+        txn.commit()
+            .await
+            .context("Failed to commit transaction")
+            .error(AppErrorKind::DbQueryFailed)?;
+
+        context.event_hooks().dispatch(&room, &event);
+
+        let mut response = AppResponse::new(
+            ResponseStatus::CREATED,
+            poll.clone(),
+            context.start_timestamp(),
+            Some(authz_time),
+        );
+
+        response.add_notification(
+            "poll.create",
+            &context.config().room_topic(room.audience(), room.id()),
+            poll,
+            context.start_timestamp(),
+        )?;
+
+        Ok(response)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct VotePayload {
+    /// The option index.
+    option: usize,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VoteRequest {
+    room_id: Uuid,
+    id: Uuid,
+    #[serde(flatten)]
+    payload: VotePayload,
+}
+
+pub async fn vote(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path((room_id, id)): Path<(Uuid, Uuid)>,
+    Json(payload): Json<VotePayload>,
+) -> RequestResult {
+    let request = VoteRequest {
+        room_id,
+        id,
+        payload,
+    };
+
+    VoteHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct VoteHandler;
+
+#[async_trait]
+impl RequestHandler for VoteHandler {
+    type Payload = VoteRequest;
+
+    #[instrument(skip_all, fields(room_id, scope, classroom_id, poll_id))]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload {
+            room_id,
+            id,
+            payload,
+        }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        Span::current().record("poll_id", &display(id));
+
+        let room = helpers::find_room(context, room_id, helpers::RoomTimeRequirement::Open).await?;
+
+        // Votes are authorized like events so that banned accounts can't vote.
+        let classroom_id = room.classroom_id().to_string();
+        let account_id = reqp.as_account_id().to_string();
+
+        let object = AuthzObject::new(&[
+            "classrooms",
+            &classroom_id,
+            "events",
+            "poll_vote",
+            "authors",
+            &account_id,
+        ]);
+
+        let authz_time = helpers::authorize_room(context, &room, reqp, object, "create").await?;
+
+        let mut conn = context.get_conn().await?;
+        let poll = find_poll(context, &mut conn, room.id(), id).await?;
+
+        if !poll.is_open() {
+            return Err(anyhow!("The poll is closed")).error(AppErrorKind::PollClosed);
+        }
+
+        if payload.option >= poll.options() as usize {
+            return Err(anyhow!("'option' is out of the poll options"))
+                .error(AppErrorKind::InvalidPayload);
+        }
+
+        let query = db::poll::VoteQuery::new(
+            poll.id(),
+            reqp.as_account_id().to_owned(),
+            payload.option as i32,
+        );
+
+        // The first vote of the account stays so that retries don't inflate counters.
+        context
+            .metrics()
+            .measure_query(QueryKey::PollVoteQuery, query.execute(&mut conn))
+            .await
+            .context("Failed to vote")
+            .error(AppErrorKind::DbQueryFailed)?;
+
+        // Results are not broadcasted on each vote to avoid revealing them before the poll closes.
+        Ok(AppResponse::new(
+            ResponseStatus::OK,
+            poll,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct ResultsRequest {
+    room_id: Uuid,
+    id: Uuid,
+}
+
+pub async fn results(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path((room_id, id)): Path<(Uuid, Uuid)>,
+) -> RequestResult {
+    let request = ResultsRequest { room_id, id };
+    ResultsHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct ResultsHandler;
+
+#[async_trait]
+impl RequestHandler for ResultsHandler {
+    type Payload = ResultsRequest;
+
+    #[instrument(skip_all, fields(room_id, scope, classroom_id, poll_id))]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload { room_id, id }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        Span::current().record("poll_id", &display(id));
+
+        let room = helpers::find_room(context, room_id, helpers::RoomTimeRequirement::Any).await?;
+
+        let poll = {
+            let mut conn = context.get_ro_conn().await?;
+            find_poll(context, &mut conn, room.id(), id).await?
+        };
+
+        // Results of open polls are available to those running them only.
+        let authz_time = if poll.is_open() {
+            let object = AuthzObject::room(&room);
+            helpers::authorize_room(context, &room, reqp, object, "update").await?
+        } else {
+            let classroom_id = room.classroom_id().to_string();
+            let object = AuthzObject::new(&["classrooms", &classroom_id]);
+            helpers::authorize_room(context, &room, reqp, object, "read").await?
+        };
+
+        let mut conn = context.get_ro_conn().await?;
+        let results = find_results(context, &mut conn, poll.id()).await?;
+
+        Ok(AppResponse::new(
+            ResponseStatus::OK,
+            results,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct CloseRequest {
+    room_id: Uuid,
+    id: Uuid,
+}
+
+pub async fn close(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path((room_id, id)): Path<(Uuid, Uuid)>,
+) -> RequestResult {
+    let request = CloseRequest { room_id, id };
+    CloseHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct CloseHandler;
+
+#[async_trait]
+impl RequestHandler for CloseHandler {
+    type Payload = CloseRequest;
+
+    #[instrument(skip_all, fields(room_id, scope, classroom_id, poll_id))]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload { room_id, id }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        Span::current().record("poll_id", &display(id));
+
+        let room = helpers::find_room(context, room_id, helpers::RoomTimeRequirement::Any).await?;
+
+        let object = AuthzObject::room(&room);
+        let authz_time = helpers::authorize_room(context, &room, reqp, object, "update").await?;
+
+        let mut conn = context.get_conn().await?;
+        let poll = find_poll(context, &mut conn, room.id(), id).await?;
+        let query = db::poll::CloseQuery::new(room.id(), poll.id());
+
+        let is_closed_now = context
+            .metrics()
+            .measure_query(QueryKey::PollCloseQuery, query.execute(&mut conn))
+            .await
+            .context("Failed to close poll")
+            .error(AppErrorKind::DbQueryFailed)?;
+
+        let results = find_results(context, &mut conn, poll.id()).await?;
+
+        let mut response = AppResponse::new(
+            ResponseStatus::OK,
+            results.clone(),
+            context.start_timestamp(),
+            Some(authz_time),
+        );
+
+        // Closing again doesn't repeat the notification.
+        if is_closed_now {
+            response.add_notification(
+                "poll.close",
+                &context.config().room_topic(room.audience(), room.id()),
+                results,
+                context.start_timestamp(),
+            )?;
+        }
+
+        Ok(response)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+async fn find_poll<C: Context>(
+    context: &C,
+    conn: &mut sqlx::PgConnection,
+    room_id: Uuid,
+    id: Uuid,
+) -> Result<Poll, AppError> {
+    let query = db::poll::FindQuery::new(room_id, id);
+
+    context
+        .metrics()
+        .measure_query(QueryKey::PollFindQuery, query.execute(conn))
+        .await
+        .context("Failed to find poll")
+        .error(AppErrorKind::DbQueryFailed)?
+        .ok_or_else(|| anyhow!("Poll not found"))
+        .error(AppErrorKind::PollNotFound)
+}
+
+async fn find_results<C: Context>(
+    context: &C,
+    conn: &mut sqlx::PgConnection,
+    id: Uuid,
+) -> Result<Results, AppError> {
+    let query = db::poll::ResultsQuery::new(id);
+
+    context
+        .metrics()
+        .measure_query(QueryKey::PollResultsQuery, query.execute(conn))
+        .await
+        .context("Failed to get poll results")
+        .error(AppErrorKind::DbQueryFailed)?
+        .ok_or_else(|| anyhow!("Poll not found"))
+        .error(AppErrorKind::PollNotFound)
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::test_helpers::prelude::*;
+
+    use super::*;
+
+    fn allow_voting(authz: &mut TestAuthz, agent: &TestAgent, room: &db::room::Object) {
+        let classroom_id = room.classroom_id().to_string();
+        let account_id = agent.account_id().to_string();
+
+        authz.allow(
+            agent.account_id(),
+            vec![
+                "classrooms",
+                &classroom_id,
+                "events",
+                "poll_vote",
+                "authors",
+                &account_id,
+            ],
+            "create",
+        );
+
+        authz.allow(
+            agent.account_id(),
+            vec!["classrooms", &classroom_id],
+            "read",
+        );
+    }
+
+    async fn vote(context: &mut TestContext, agent: &TestAgent, room_id: Uuid, id: Uuid) {
+        let payload = VoteRequest {
+            room_id,
+            id,
+            payload: VotePayload { option: 1 },
+        };
+
+        handle_request::<VoteHandler>(context, agent, payload)
+            .await
+            .expect("Poll vote failed");
+    }
+
+    #[tokio::test]
+    async fn vote_and_close_poll() {
+        let db = TestDb::new().await;
+        let host = TestAgent::new("web", "admin", USR_AUDIENCE);
+        let voter = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        authz.allow(
+            host.account_id(),
+            vec!["classrooms", &classroom_id],
+            "update",
+        );
+        allow_voting(&mut authz, &voter, &room);
+
+        let mut context = TestContext::new(db, authz);
+
+        let payload = CreateRequest {
+            room_id: room.id(),
+            payload: CreatePayload {
+                data: json!({ "question": "2 + 2", "options": ["3", "4"] }),
+                options: 2,
+                correct_option: Some(1),
+                duration: None,
+            },
+        };
+
+        let messages = handle_request::<CreateHandler>(&mut context, &host, payload)
+            .await
+            .expect("Poll creation failed");
+
+        let (poll, respp, _) = find_response::<Poll>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::CREATED);
+
+        // Votes are counted once per account.
+        vote(&mut context, &voter, room.id(), poll.id()).await;
+        vote(&mut context, &voter, room.id(), poll.id()).await;
+
+        // Results of the open poll are not available to voters.
+        let request = ResultsRequest {
+            room_id: room.id(),
+            id: poll.id(),
+        };
+
+        let err = handle_request::<ResultsHandler>(&mut context, &voter, request)
+            .await
+            .expect_err("Unexpected success reading open poll results");
+
+        assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+
+        let request = CloseRequest {
+            room_id: room.id(),
+            id: poll.id(),
+        };
+
+        let messages = handle_request::<CloseHandler>(&mut context, &host, request)
+            .await
+            .expect("Poll closing failed");
+
+        let (results, _, _) = find_response::<Results>(messages.as_slice());
+        assert_eq!(results.votes(), &[0, 1]);
+        assert_eq!(results.correct_option(), Some(1));
+
+        let payload = VoteRequest {
+            room_id: room.id(),
+            id: poll.id(),
+            payload: VotePayload { option: 0 },
+        };
+
+        let err = handle_request::<VoteHandler>(&mut context, &voter, payload)
+            .await
+            .expect_err("Unexpected success voting in closed poll");
+
+        assert_eq!(err.kind(), "poll_closed");
+
+        let request = ResultsRequest {
+            room_id: room.id(),
+            id: poll.id(),
+        };
+
+        let messages = handle_request::<ResultsHandler>(&mut context, &voter, request)
+            .await
+            .expect("Poll results reading failed");
+
+        let (results, _, _) = find_response::<Results>(messages.as_slice());
+        assert_eq!(results.votes(), &[0, 1]);
+    }
+
+    #[tokio::test]
+    async fn create_poll_with_invalid_options() {
+        let db = TestDb::new().await;
+        let host = TestAgent::new("web", "admin", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        authz.allow(
+            host.account_id(),
+            vec!["classrooms", &classroom_id],
+            "update",
+        );
+
+        let mut context = TestContext::new(db, authz);
+
+        let payload = CreateRequest {
+            room_id: room.id(),
+            payload: CreatePayload {
+                data: json!({ "question": "2 + 2", "options": ["3", "4"] }),
+                options: 2,
+                correct_option: Some(2),
+                duration: None,
+            },
+        };
+
+        let err = handle_request::<CreateHandler>(&mut context, &host, payload)
+            .await
+            .expect_err("Unexpected success creating poll");
+
+        assert_eq!(err.status(), ResponseStatus::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,691 @@
+use anyhow::Context as AnyhowContext;
+use async_trait::async_trait;
+use axum::{
+    extract::{self, Path, RawQuery},
+    Json,
+};
+use serde_derive::Deserialize;
+use serde_json::Value as JsonValue;
+use sqlx::Acquire;
+use svc_agent::{mqtt::ResponseStatus, Addressable};
+use svc_utils::extractors::AgentIdExtractor;
+use tracing::{field::display, instrument, Span};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::app::endpoint::prelude::*;
+use crate::db;
+use crate::db::event::EventKind;
+use crate::db::question::{Object as Question, Sort};
+
+/// Set of `question` events. Questions share identifiers with their events.
+const QUESTIONS_SET: &str = "questions";
+/// Set of `answer` events labeled with identifiers of the questions they answer.
+const ANSWERS_SET: &str = "answers";
+
+const MAX_LIMIT: usize = 100;
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreatePayload {
+    /// The question content, e.g. `{"text": "..."}`.
+    #[schema(value_type = Object)]
+    data: JsonValue,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateRequest {
+    room_id: Uuid,
+    #[serde(flatten)]
+    payload: CreatePayload,
+}
+
+#[utoipa::path(
+    post,
+    path = "/rooms/{id}/questions",
+    tag = "question",
+    params(("id" = Uuid, Path, description = "Room identifier")),
+    request_body = inline(CreatePayload),
+    responses(
+        (status = 201, description = "Question created", body = Question),
+        (status = 403, description = "Not authorized", body = ErrorPayload),
+        (status = 404, description = "Room not found", body = ErrorPayload),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn create(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<Uuid>,
+    Json(payload): Json<CreatePayload>,
+) -> RequestResult {
+    let request = CreateRequest { room_id, payload };
+    CreateHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct CreateHandler;
+
+#[async_trait]
+impl RequestHandler for CreateHandler {
+    type Payload = CreateRequest;
+
+    #[instrument(skip_all, fields(room_id, scope, classroom_id, question_id))]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload { room_id, payload }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let room = helpers::find_room(context, room_id, helpers::RoomTimeRequirement::Open).await?;
+
+        // The same permission as for creating `question` events with event.create.
+        let classroom_id = room.classroom_id().to_string();
+        let account_id = reqp.as_account_id().to_string();
+
+        let object = AuthzObject::new(&[
+            "classrooms",
+            &classroom_id,
+            "events",
+            EventKind::Question.as_str(),
+            "authors",
+            &account_id,
+        ]);
+
+        let authz_time = helpers::authorize_room(context, &room, reqp, object, "create").await?;
+        let data = helpers::validate_object_data(context, payload.data)?;
+
+        let query = db::event::InsertQuery::new(
+            room.id(),
+            EventKind::Question.into(),
+            data,
+            helpers::occurred_at(&room)?,
+            reqp.as_agent_id().to_owned(),
+            context.data_encryption().as_deref(),
+        )
+        .map_err(|err| helpers::invalid_event(context, err))?
+        .set(QUESTIONS_SET.to_owned());
+
+        let mut conn = context.get_conn().await?;
+
+        let mut txn = conn
+            .begin()
+            .await
+            .context("Failed to acquire transaction")
+            .error(AppErrorKind::DbQueryFailed)?;
+
+        let event = context
+            .metrics()
+            .measure_query(QueryKey::EventInsertQuery, query.execute(&mut txn))
+            .await
+            .context("Failed to insert question event")
+            .error(AppErrorKind::DbQueryFailed)?;
+
+        Span::current().record("question_id", &display(event.id()));
+
+        let query = db::question::InsertQuery::new(event.id(), room.id());
+
+        context
+            .metrics()
+            .measure_query(QueryKey::QuestionInsertQuery, query.execute(&mut txn))
+            .await
+            .context("Failed to insert question")
+            .error(AppErrorKind::DbQueryFailed)?;
+
+        let question = find_question(context, &mut txn, room.id(), event.id()).await?;
+
+        txn.commit()
+            .await
+            .context("Failed to commit transaction")
+            .error(AppErrorKind::DbQueryFailed)?;
+
+        context.event_hooks().dispatch(&room, &event);
+
+        let mut response = AppResponse::new(
+            ResponseStatus::CREATED,
+            question.clone(),
+            context.start_timestamp(),
+            Some(authz_time),
+        );
+
+        response.add_notification(
+            "question.create",
+            &context.config().room_topic(room.audience(), room.id()),
+            question,
+            context.start_timestamp(),
+        )?;
+
+        Ok(response)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListPayload {
+    /// `votes` (default) for the most voted first or `created_at` for the latest first.
+    sort: Option<Sort>,
+    /// Only answered or only unanswered questions.
+    answered: Option<bool>,
+    /// Maximum number of questions, 100 at most.
+    limit: Option<usize>,
+    /// Number of questions to skip.
+    offset: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListRequest {
+    room_id: Uuid,
+    #[serde(flatten)]
+    payload: ListPayload,
+}
+
+#[utoipa::path(
+    get,
+    path = "/rooms/{id}/questions",
+    tag = "question",
+    params(("id" = Uuid, Path, description = "Room identifier"), ListPayload),
+    responses(
+        (status = 200, description = "Questions", body = [Question]),
+        (status = 403, description = "Not authorized", body = ErrorPayload),
+        (status = 404, description = "Room not found", body = ErrorPayload),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn list(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<Uuid>,
+    RawQuery(query): RawQuery,
+) -> RequestResult {
+    let payload = serde_qs::from_str(&query.unwrap_or_default())
+        .context("Failed to parse qs")
+        .error(AppErrorKind::InvalidQueryString)?;
+    let request = ListRequest { room_id, payload };
+    ListHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct ListHandler;
+
+#[async_trait]
+impl RequestHandler for ListHandler {
+    type Payload = ListRequest;
+
+    #[instrument(skip_all, fields(room_id, scope, classroom_id))]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload { room_id, payload }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let room = helpers::find_room(context, room_id, helpers::RoomTimeRequirement::Any).await?;
+
+        // The same permission as for listing events.
+        let classroom_id = room.classroom_id().to_string();
+        let object = AuthzObject::new(&["classrooms", &classroom_id]);
+        let authz_time = helpers::authorize_room(context, &room, reqp, object, "read").await?;
+
+        let limit = std::cmp::min(payload.limit.unwrap_or(MAX_LIMIT), MAX_LIMIT);
+
+        let mut query = db::question::ListQuery::new(room.id(), limit as i64)
+            .sort(payload.sort.unwrap_or_default())
+            .offset(payload.offset.unwrap_or(0) as i64);
+
+        if let Some(answered) = payload.answered {
+            query = query.answered(answered);
+        }
+
+        let mut conn = context.get_ro_conn().await?;
+
+        let questions = context
+            .metrics()
+            .measure_query(QueryKey::QuestionListQuery, query.execute(&mut conn))
+            .await
+            .context("Failed to list questions")
+            .error(AppErrorKind::DbQueryFailed)?;
+
+        Ok(AppResponse::new(
+            ResponseStatus::OK,
+            questions,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct UpvoteRequest {
+    room_id: Uuid,
+    id: Uuid,
+}
+
+pub async fn upvote(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path((room_id, id)): Path<(Uuid, Uuid)>,
+) -> RequestResult {
+    let request = UpvoteRequest { room_id, id };
+    UpvoteHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct UpvoteHandler;
+
+#[async_trait]
+impl RequestHandler for UpvoteHandler {
+    type Payload = UpvoteRequest;
+
+    #[instrument(skip_all, fields(room_id, scope, classroom_id, question_id))]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload { room_id, id }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        Span::current().record("question_id", &display(id));
+
+        let room = helpers::find_room(context, room_id, helpers::RoomTimeRequirement::Open).await?;
+
+        // Votes are authorized like events so that banned accounts can't vote.
+        let classroom_id = room.classroom_id().to_string();
+        let account_id = reqp.as_account_id().to_string();
+
+        let object = AuthzObject::new(&[
+            "classrooms",
+            &classroom_id,
+            "events",
+            "question_vote",
+            "authors",
+            &account_id,
+        ]);
+
+        let authz_time = helpers::authorize_room(context, &room, reqp, object, "create").await?;
+
+        let mut conn = context.get_conn().await?;
+        find_question(context, &mut conn, room.id(), id).await?;
+
+        let query = db::question::UpvoteQuery::new(id, reqp.as_account_id().to_owned());
+
+        // Voting again is a no-op so that retries don't inflate the counter.
+        let is_counted = context
+            .metrics()
+            .measure_query(QueryKey::QuestionUpvoteQuery, query.execute(&mut conn))
+            .await
+            .context("Failed to upvote question")
+            .error(AppErrorKind::DbQueryFailed)?;
+
+        let question = find_question(context, &mut conn, room.id(), id).await?;
+
+        let mut response = AppResponse::new(
+            ResponseStatus::OK,
+            question.clone(),
+            context.start_timestamp(),
+            Some(authz_time),
+        );
+
+        if is_counted {
+            response.add_notification(
+                "question.update",
+                &context.config().room_topic(room.audience(), room.id()),
+                question,
+                context.start_timestamp(),
+            )?;
+        }
+
+        Ok(response)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct AnswerPayload {
+    /// The answer content, e.g. `{"text": "..."}`.
+    data: JsonValue,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AnswerRequest {
+    room_id: Uuid,
+    id: Uuid,
+    #[serde(flatten)]
+    payload: AnswerPayload,
+}
+
+pub async fn answer(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path((room_id, id)): Path<(Uuid, Uuid)>,
+    Json(payload): Json<AnswerPayload>,
+) -> RequestResult {
+    let request = AnswerRequest {
+        room_id,
+        id,
+        payload,
+    };
+
+    AnswerHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct AnswerHandler;
+
+#[async_trait]
+impl RequestHandler for AnswerHandler {
+    type Payload = AnswerRequest;
+
+    #[instrument(skip_all, fields(room_id, scope, classroom_id, question_id))]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload {
+            room_id,
+            id,
+            payload,
+        }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        Span::current().record("question_id", &display(id));
+
+        let room = helpers::find_room(context, room_id, helpers::RoomTimeRequirement::Open).await?;
+
+        // Those who can update the room answer questions like they moderate events.
+        let object = AuthzObject::room(&room);
+        let authz_time = helpers::authorize_room(context, &room, reqp, object, "update").await?;
+
+        let data = helpers::validate_object_data(context, payload.data)?;
+
+        // Answering again adds a new version of the answer with the same label.
+        let query = db::event::InsertQuery::new(
+            room.id(),
+            EventKind::Answer.into(),
+            data,
+            helpers::occurred_at(&room)?,
+            reqp.as_agent_id().to_owned(),
+            context.data_encryption().as_deref(),
+        )
+        .map_err(|err| helpers::invalid_event(context, err))?
+        .set(ANSWERS_SET.to_owned())
+        .label(id.to_string());
+
+        let mut conn = context.get_conn().await?;
+
+        let mut txn = conn
+            .begin()
+            .await
+            .context("Failed to acquire transaction")
+            .error(AppErrorKind::DbQueryFailed)?;
+
+        find_question(context, &mut txn, room.id(), id).await?;
+
+        let event = context
+            .metrics()
+            .measure_query(QueryKey::EventInsertQuery, query.execute(&mut txn))
+            .await
+            .context("Failed to insert answer event")
+            .error(AppErrorKind::DbQueryFailed)?;
+
+        let query = db::question::AnswerQuery::new(id, event.id());
+
+        context
+            .metrics()
+            .measure_query(QueryKey::QuestionAnswerQuery, query.execute(&mut txn))
+            .await
+            .context("Failed to answer question")
+            .error(AppErrorKind::DbQueryFailed)?;
+
+        let question = find_question(context, &mut txn, room.id(), id).await?;
+
+        txn.commit()
+            .await
+            .context("Failed to commit transaction")
+            .error(AppErrorKind::DbQueryFailed)?;
+
+        context.event_hooks().dispatch(&room, &event);
+
+        let mut response = AppResponse::new(
+            ResponseStatus::OK,
+            question.clone(),
+            context.start_timestamp(),
+            Some(authz_time),
+        );
+
+        let topic = context.config().room_topic(room.audience(), room.id());
+        response.add_notification("event.create", &topic, event, context.start_timestamp())?;
+        response.add_notification(
+            "question.update",
+            &topic,
+            question,
+            context.start_timestamp(),
+        )?;
+        Ok(response)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+async fn find_question<C: Context>(
+    context: &C,
+    conn: &mut sqlx::PgConnection,
+    room_id: Uuid,
+    id: Uuid,
+) -> Result<Question, AppError> {
+    let query = db::question::FindQuery::new(room_id, id);
+
+    context
+        .metrics()
+        .measure_query(QueryKey::QuestionFindQuery, query.execute(conn))
+        .await
+        .context("Failed to find question")
+        .error(AppErrorKind::DbQueryFailed)?
+        .ok_or_else(|| anyhow!("Question not found"))
+        .error(AppErrorKind::QuestionNotFound)
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::test_helpers::prelude::*;
+
+    use super::*;
+
+    fn allow_asking(authz: &mut TestAuthz, agent: &TestAgent, room: &db::room::Object) {
+        let classroom_id = room.classroom_id().to_string();
+        let account_id = agent.account_id().to_string();
+
+        for kind in ["question", "question_vote"] {
+            authz.allow(
+                agent.account_id(),
+                vec![
+                    "classrooms",
+                    &classroom_id,
+                    "events",
+                    kind,
+                    "authors",
+                    &account_id,
+                ],
+                "create",
+            );
+        }
+    }
+
+    async fn create_question(context: &mut TestContext, agent: &TestAgent, room_id: Uuid) -> Uuid {
+        let payload = CreateRequest {
+            room_id,
+            payload: CreatePayload {
+                data: json!({ "text": "Why?" }),
+            },
+        };
+
+        let messages = handle_request::<CreateHandler>(context, agent, payload)
+            .await
+            .expect("Question creation failed");
+
+        let (question, respp, _) = find_response::<Question>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::CREATED);
+        question.id()
+    }
+
+    async fn upvote_question(
+        context: &mut TestContext,
+        agent: &TestAgent,
+        room_id: Uuid,
+        id: Uuid,
+    ) -> Question {
+        let messages =
+            handle_request::<UpvoteHandler>(context, agent, UpvoteRequest { room_id, id })
+                .await
+                .expect("Question upvote failed");
+
+        find_response::<Question>(messages.as_slice()).0
+    }
+
+    #[tokio::test]
+    async fn upvote_and_list_questions() {
+        let db = TestDb::new().await;
+        let asker = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let voter = TestAgent::new("web", "user456", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut authz = TestAuthz::new();
+        allow_asking(&mut authz, &asker, &room);
+        allow_asking(&mut authz, &voter, &room);
+
+        authz.allow(
+            voter.account_id(),
+            vec!["classrooms", &room.classroom_id().to_string()],
+            "read",
+        );
+
+        let mut context = TestContext::new(db, authz);
+        let first = create_question(&mut context, &asker, room.id()).await;
+        let second = create_question(&mut context, &asker, room.id()).await;
+
+        // Votes are counted once per account.
+        upvote_question(&mut context, &asker, room.id(), second).await;
+        upvote_question(&mut context, &voter, room.id(), second).await;
+        let question = upvote_question(&mut context, &voter, room.id(), second).await;
+        assert_eq!(question.votes(), 2);
+
+        let payload = ListRequest {
+            room_id: room.id(),
+            payload: ListPayload::default(),
+        };
+
+        let messages = handle_request::<ListHandler>(&mut context, &voter, payload)
+            .await
+            .expect("Questions listing failed");
+
+        let (questions, _, _) = find_response::<Vec<Question>>(messages.as_slice());
+        let ids = questions.iter().map(|q| q.id()).collect::<Vec<_>>();
+        assert_eq!(ids, vec![second, first]);
+    }
+
+    #[tokio::test]
+    async fn answer_question() {
+        let db = TestDb::new().await;
+        let asker = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let host = TestAgent::new("web", "admin", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut authz = TestAuthz::new();
+        allow_asking(&mut authz, &asker, &room);
+
+        authz.allow(
+            host.account_id(),
+            vec!["classrooms", &room.classroom_id().to_string()],
+            "update",
+        );
+
+        let mut context = TestContext::new(db, authz);
+        let id = create_question(&mut context, &asker, room.id()).await;
+
+        let payload = AnswerRequest {
+            room_id: room.id(),
+            id,
+            payload: AnswerPayload {
+                data: json!({ "text": "Because" }),
+            },
+        };
+
+        let messages = handle_request::<AnswerHandler>(&mut context, &host, payload)
+            .await
+            .expect("Question answer failed");
+
+        let (question, respp, _) = find_response::<Question>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert!(question.answer_id().is_some());
+    }
+
+    #[tokio::test]
+    async fn answer_missing_question() {
+        let db = TestDb::new().await;
+        let host = TestAgent::new("web", "admin", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut authz = TestAuthz::new();
+
+        authz.allow(
+            host.account_id(),
+            vec!["classrooms", &room.classroom_id().to_string()],
+            "update",
+        );
+
+        let mut context = TestContext::new(db, authz);
+
+        let payload = AnswerRequest {
+            room_id: room.id(),
+            id: Uuid::new_v4(),
+            payload: AnswerPayload {
+                data: json!({ "text": "Because" }),
+            },
+        };
+
+        let err = handle_request::<AnswerHandler>(&mut context, &host, payload)
+            .await
+            .expect_err("Unexpected success answering missing question");
+
+        assert_eq!(err.status(), ResponseStatus::NOT_FOUND);
+        assert_eq!(err.kind(), "question_not_found");
+    }
+}
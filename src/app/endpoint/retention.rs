@@ -0,0 +1,419 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use anyhow::Context as AnyhowContext;
+use async_trait::async_trait;
+use axum::{
+    extract::{self, Path},
+    Json,
+};
+use serde_derive::Deserialize;
+use sqlx::Acquire;
+use svc_agent::mqtt::ResponseStatus;
+use svc_authn::Authenticable;
+use svc_utils::extractors::AgentIdExtractor;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::app::context::Context;
+use crate::app::endpoint::prelude::*;
+use crate::db;
+use crate::db::retention_rule::Scope;
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Retention rules are set either for a room or for all rooms of an audience.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum RetentionScope {
+    Room { room_id: Uuid },
+    Audience { audience: String },
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReadRequest {
+    #[serde(flatten)]
+    scope: RetentionScope,
+}
+
+pub async fn read_room(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<Uuid>,
+) -> RequestResult {
+    let request = ReadRequest {
+        scope: RetentionScope::Room { room_id },
+    };
+    ReadHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub async fn read_audience(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(audience): Path<String>,
+) -> RequestResult {
+    let request = ReadRequest {
+        scope: RetentionScope::Audience { audience },
+    };
+    ReadHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct ReadHandler;
+
+#[async_trait]
+impl RequestHandler for ReadHandler {
+    type Payload = ReadRequest;
+
+    #[instrument(skip_all)]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload { scope }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let (scope, authz_time) = authorize(context, scope, "read", reqp).await?;
+
+        let rules = {
+            let query = db::retention_rule::ListQuery::new(scope);
+            let mut conn = context.get_ro_conn().await?;
+
+            context
+                .metrics()
+                .measure_query(QueryKey::RetentionRuleListQuery, query.execute(&mut conn))
+                .await
+                .context("Failed to list retention rules")
+                .error(AppErrorKind::DbQueryFailed)?
+        };
+
+        Ok(AppResponse::new(
+            ResponseStatus::OK,
+            rules,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct RulePayload {
+    kind: String,
+    /// Seconds to keep events of the kind.
+    max_lifetime: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetPayload {
+    rules: Vec<RulePayload>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetRequest {
+    #[serde(flatten)]
+    scope: RetentionScope,
+    #[serde(flatten)]
+    payload: SetPayload,
+}
+
+pub async fn set_room(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<Uuid>,
+    Json(payload): Json<SetPayload>,
+) -> RequestResult {
+    let request = SetRequest {
+        scope: RetentionScope::Room { room_id },
+        payload,
+    };
+    SetHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub async fn set_audience(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(audience): Path<String>,
+    Json(payload): Json<SetPayload>,
+) -> RequestResult {
+    let request = SetRequest {
+        scope: RetentionScope::Audience { audience },
+        payload,
+    };
+    SetHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct SetHandler;
+
+#[async_trait]
+impl RequestHandler for SetHandler {
+    type Payload = SetRequest;
+
+    #[instrument(skip_all)]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload { scope, payload }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let (scope, authz_time) = authorize(context, scope, "update", reqp).await?;
+
+        let mut kinds = HashSet::new();
+
+        for rule in &payload.rules {
+            if rule.max_lifetime <= 0 {
+                return Err(anyhow!("Retention max_lifetime must be positive"))
+                    .error(AppErrorKind::InvalidPayload);
+            }
+
+            if !kinds.insert(rule.kind.as_str()) {
+                return Err(anyhow!("Duplicate retention rule for kind '{}'", rule.kind))
+                    .error(AppErrorKind::InvalidPayload);
+            }
+        }
+
+        // Replace all the rules of the scope at once.
+        let rules = {
+            let mut conn = context.get_conn().await?;
+
+            let mut txn = conn
+                .begin()
+                .await
+                .context("Failed to acquire transaction")
+                .error(AppErrorKind::DbQueryFailed)?;
+
+            let query = db::retention_rule::DeleteQuery::new(scope.clone());
+
+            context
+                .metrics()
+                .measure_query(QueryKey::RetentionRuleDeleteQuery, query.execute(&mut txn))
+                .await
+                .context("Failed to delete retention rules")
+                .error(AppErrorKind::DbQueryFailed)?;
+
+            let mut rules = Vec::with_capacity(payload.rules.len());
+
+            for rule in payload.rules {
+                let query = db::retention_rule::InsertQuery::new(
+                    scope.clone(),
+                    rule.kind,
+                    rule.max_lifetime,
+                );
+
+                let rule = context
+                    .metrics()
+                    .measure_query(QueryKey::RetentionRuleInsertQuery, query.execute(&mut txn))
+                    .await
+                    .context("Failed to insert retention rule")
+                    .error(AppErrorKind::DbQueryFailed)?;
+
+                rules.push(rule);
+            }
+
+            txn.commit()
+                .await
+                .context("Failed to commit transaction")
+                .error(AppErrorKind::DbQueryFailed)?;
+
+            rules
+        };
+
+        Ok(AppResponse::new(
+            ResponseStatus::OK,
+            rules,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+async fn authorize<C: Context>(
+    context: &mut C,
+    scope: RetentionScope,
+    action: &str,
+    reqp: RequestParams<'_>,
+) -> Result<(Scope, chrono::Duration), AppError> {
+    let (audience, object, scope) = match scope {
+        RetentionScope::Room { room_id } => {
+            let room =
+                helpers::find_room(context, room_id, helpers::RoomTimeRequirement::Any).await?;
+
+            (
+                room.audience().to_owned(),
+                AuthzObject::room(&room),
+                Scope::Room(room.id()),
+            )
+        }
+        RetentionScope::Audience { audience } => (
+            audience.clone(),
+            AuthzObject::new(&["classrooms"]),
+            Scope::Audience(audience),
+        ),
+    };
+
+    let authz_time = context
+        .authz()
+        .authorize(
+            audience,
+            reqp.as_account_id().to_owned(),
+            object.into(),
+            action.into(),
+        )
+        .await?;
+
+    Ok((scope, authz_time))
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value as JsonValue};
+
+    use crate::test_helpers::prelude::*;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn set_and_read_room_rules() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "admin", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        let object = vec!["classrooms", &classroom_id];
+        authz.allow(agent.account_id(), object.clone(), "update");
+        authz.allow(agent.account_id(), object, "read");
+
+        let mut context = TestContext::new(db, authz);
+
+        let payload: SetRequest = serde_json::from_value(json!({
+            "room_id": room.id(),
+            "rules": [
+                { "kind": "message", "max_lifetime": 90 * 86400 },
+                { "kind": "draw", "max_lifetime": 7 * 86400 },
+            ],
+        }))
+        .expect("Failed to parse request");
+
+        let messages = handle_request::<SetHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Retention rules setting failed");
+
+        let (rules, respp, _) = find_response::<Vec<JsonValue>>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert_eq!(rules.len(), 2);
+
+        // Setting again replaces the rules.
+        let payload: SetRequest = serde_json::from_value(json!({
+            "room_id": room.id(),
+            "rules": [{ "kind": "draw", "max_lifetime": 86400 }],
+        }))
+        .expect("Failed to parse request");
+
+        handle_request::<SetHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Retention rules setting failed");
+
+        let payload = ReadRequest {
+            scope: RetentionScope::Room { room_id: room.id() },
+        };
+
+        let messages = handle_request::<ReadHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Retention rules reading failed");
+
+        let (rules, _, _) = find_response::<Vec<JsonValue>>(messages.as_slice());
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0]["kind"], "draw");
+        assert_eq!(rules[0]["max_lifetime"], 86400);
+        assert_eq!(rules[0]["room_id"], room.id().to_string());
+    }
+
+    #[tokio::test]
+    async fn set_audience_rules_not_authorized() {
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let mut context = TestContext::new(TestDb::new().await, TestAuthz::new());
+
+        let payload: SetRequest = serde_json::from_value(json!({
+            "audience": USR_AUDIENCE,
+            "rules": [{ "kind": "message", "max_lifetime": 86400 }],
+        }))
+        .expect("Failed to parse request");
+
+        let err = handle_request::<SetHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success on retention rules setting");
+
+        assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn set_duplicate_rules() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "admin", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        authz.allow(
+            agent.account_id(),
+            vec!["classrooms", &classroom_id],
+            "update",
+        );
+
+        let mut context = TestContext::new(db, authz);
+
+        let payload: SetRequest = serde_json::from_value(json!({
+            "room_id": room.id(),
+            "rules": [
+                { "kind": "message", "max_lifetime": 86400 },
+                { "kind": "message", "max_lifetime": 3600 },
+            ],
+        }))
+        .expect("Failed to parse request");
+
+        let err = handle_request::<SetHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success on retention rules setting");
+
+        assert_eq!(err.status(), ResponseStatus::BAD_REQUEST);
+        assert_eq!(err.kind(), "invalid_payload");
+    }
+}
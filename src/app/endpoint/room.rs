@@ -6,46 +6,74 @@ use anyhow::Context as AnyhowContext;
 use async_trait::async_trait;
 use axum::{
     extract::{self, Path},
+    http::HeaderMap,
     Json,
 };
 use chrono::{DateTime, Utc};
 use serde_derive::{Deserialize, Serialize};
 use serde_json::{json, Value as JsonValue};
-use sqlx::Acquire;
+use sqlx::{postgres::PgConnection, Acquire};
 use svc_agent::{
     mqtt::{OutgoingEvent, OutgoingEventProperties, ResponseStatus, ShortTermTimingProperties},
     AccountId, Addressable, AgentId,
 };
-use svc_error::Error as SvcError;
 use svc_utils::extractors::AgentIdExtractor;
-use tracing::{error, info, instrument};
+use tracing::{error, info, instrument, warn, Instrument};
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 use crate::app::endpoint::prelude::*;
 use crate::app::{
     context::{AppContext, Context},
     message_handler::Message,
+    webhook_client::Webhook,
 };
-use crate::db::adjustment::Segments;
+use crate::db::adjustment::{FinishQuery as AdjustmentFinishQuery, Segments};
 use crate::db::agent;
-use crate::db::room::{ClassType, InsertQuery, UpdateQuery};
+use crate::db::room::{ClassType, InsertQuery, Object as Room, UpdateQuery};
+use crate::db::room_queue;
 use crate::db::room_time::{BoundedDateTimeTuple, RoomTime};
+use crate::db::task::Status as TaskStatus;
+use crate::metrics::Metrics;
 use crate::{
-    app::operations::{adjust_room, AdjustOutput},
-    db::event::{insert_agent_action, AgentAction},
+    app::operations::{
+        adjust_room, adjust_room_preview, segments, AdjustOutput, AdjustPreview, RoomOperationLock,
+    },
+    db::event::{insert_agent_action, AgentAction, PinnedListQuery},
 };
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct CreateRequest {
     audience: String,
+    /// `[opened_at, closed_at)` in seconds, `closed_at` may be null for an unbounded room.
     #[serde(with = "crate::serde::ts_seconds_bound_tuple")]
+    #[schema(value_type = Vec<Option<i64>>)]
     time: BoundedDateTimeTuple,
+    #[schema(value_type = Option<Object>)]
     tags: Option<JsonValue>,
     preserve_history: Option<bool>,
     classroom_id: Uuid,
     kind: ClassType,
+    moderated: Option<bool>,
+    keep_open: Option<bool>,
+    /// Limit of agents in the room.
+    capacity: Option<i32>,
+    /// Audience of another tenant co-hosting the room.
+    secondary_audience: Option<String>,
 }
 
+#[utoipa::path(
+    post,
+    path = "/rooms",
+    tag = "room",
+    request_body = CreateRequest,
+    responses(
+        (status = 201, description = "Room created", body = inline(crate::db::room::Object)),
+        (status = 403, description = "Not authorized", body = ErrorPayload),
+        (status = 422, description = "Invalid room time", body = ErrorPayload),
+    ),
+    security(("bearer" = []))
+)]
 pub async fn create(
     ctx: extract::Extension<Arc<AppContext>>,
     AgentIdExtractor(agent_id): AgentIdExtractor,
@@ -98,6 +126,8 @@ impl RequestHandler for CreateHandler {
             }
         }
 
+        validate_capacity(payload.capacity)?;
+
         let object = AuthzObject::new(&["classrooms"]).into();
 
         // Authorize room creation on the tenant.
@@ -120,6 +150,13 @@ impl RequestHandler for CreateHandler {
                 payload.kind,
             );
 
+            // Defaults of the class type.
+            if let Some(defaults) = context.config().class_types.get(&payload.kind) {
+                query = query
+                    .locked_types(defaults.locked_types.clone())
+                    .settings(defaults.settings.clone());
+            }
+
             if let Some(tags) = payload.tags {
                 query = query.tags(tags);
             }
@@ -128,6 +165,18 @@ impl RequestHandler for CreateHandler {
                 query = query.preserve_history(preserve_history);
             }
 
+            if let Some(moderated) = payload.moderated {
+                query = query.moderated(moderated);
+            }
+
+            if let Some(keep_open) = payload.keep_open {
+                query = query.keep_open(keep_open);
+            }
+
+            query = query
+                .capacity(payload.capacity)
+                .secondary_audience(payload.secondary_audience);
+
             let mut conn = context.get_conn().await?;
 
             context
@@ -148,12 +197,16 @@ impl RequestHandler for CreateHandler {
             Some(authz_time),
         );
 
+        context
+            .webhook_client()
+            .send(&payload.audience, Webhook::new("room.create", &room));
+
         response.add_notification(
             "room.create",
-            &format!("audiences/{}/events", payload.audience),
+            &context.config().audience_topic(&payload.audience),
             room,
             context.start_timestamp(),
-        );
+        )?;
 
         Ok(response)
     }
@@ -164,14 +217,33 @@ impl RequestHandler for CreateHandler {
 #[derive(Debug, Deserialize)]
 pub struct ReadRequest {
     id: Uuid,
+    /// `If-None-Match` header of a conditional HTTP request.
+    #[serde(skip)]
+    if_none_match: Option<String>,
 }
 
+#[utoipa::path(
+    get,
+    path = "/rooms/{id}",
+    tag = "room",
+    params(("id" = Uuid, Path, description = "Room identifier")),
+    responses(
+        (status = 200, description = "Room", body = inline(crate::db::room::Object)),
+        (status = 304, description = "Room hasn't changed since `If-None-Match`"),
+        (status = 404, description = "Room not found", body = ErrorPayload),
+    ),
+    security(("bearer" = []))
+)]
 pub async fn read(
     ctx: extract::Extension<Arc<AppContext>>,
     AgentIdExtractor(agent_id): AgentIdExtractor,
     Path(room_id): Path<Uuid>,
+    headers: HeaderMap,
 ) -> RequestResult {
-    let request = ReadRequest { id: room_id };
+    let request = ReadRequest {
+        id: room_id,
+        if_none_match: helpers::if_none_match(&headers),
+    };
     ReadHandler::handle(
         &mut ctx.start_message(),
         request,
@@ -207,31 +279,141 @@ impl RequestHandler for ReadHandler {
 
         let authz_time = context
             .authz()
-            .authorize(
-                room.audience().into(),
+            .authorize_room(
+                &room,
                 reqp.as_account_id().to_owned(),
                 object,
                 "read".into(),
             )
             .await?;
 
+        let serialized_room = serde_json::to_string(&room)
+            .context("Failed to serialize room")
+            .error(AppErrorKind::SerializationFailed)?;
+
+        let etag = helpers::etag(serialized_room);
+
+        if helpers::etag_matches(payload.if_none_match.as_deref(), &etag) {
+            return Ok(AppResponse::not_modified(
+                etag,
+                context.start_timestamp(),
+                Some(authz_time),
+            ));
+        }
+
         Ok(AppResponse::new(
             ResponseStatus::OK,
             room,
             context.start_timestamp(),
             Some(authz_time),
-        ))
+        )
+        .etag(etag))
     }
 }
 
 ///////////////////////////////////////////////////////////////////////////////
 
 #[derive(Debug, Deserialize)]
+pub struct PinnedEventsRequest {
+    id: Uuid,
+}
+
+pub async fn pinned_events(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<Uuid>,
+) -> RequestResult {
+    let request = PinnedEventsRequest { id: room_id };
+    PinnedEventsHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct PinnedEventsHandler;
+
+#[async_trait]
+impl RequestHandler for PinnedEventsHandler {
+    type Payload = PinnedEventsRequest;
+
+    #[instrument(
+        skip_all,
+        fields(
+            room_id = %payload.id, scope, classroom_id
+        )
+    )]
+    async fn handle<C: Context>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let room =
+            helpers::find_room(context, payload.id, helpers::RoomTimeRequirement::Any).await?;
+
+        // Authorize room events reading on the tenant.
+        let object = AuthzObject::room(&room).into();
+
+        let authz_time = context
+            .authz()
+            .authorize_room(
+                &room,
+                reqp.as_account_id().to_owned(),
+                object,
+                "read".into(),
+            )
+            .await?;
+
+        let mut events = {
+            let query = PinnedListQuery::new(room.id());
+            let mut conn = context.get_ro_conn().await?;
+
+            context
+                .metrics()
+                .measure_query(QueryKey::EventPinnedListQuery, query.execute(&mut conn))
+                .await
+                .context("Failed to list pinned events")
+                .error(AppErrorKind::DbQueryFailed)?
+        };
+
+        helpers::open_events(context, &mut events)?;
+
+        Ok(AppResponse::new(
+            ResponseStatus::OK,
+            events,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+fn validate_capacity(capacity: Option<i32>) -> Result<(), AppError> {
+    match capacity {
+        Some(capacity) if capacity <= 0 => {
+            Err(anyhow!("Room capacity must be positive, got {}", capacity))
+                .error(AppErrorKind::InvalidPayload)
+        }
+        _ => Ok(()),
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct UpdatePayload {
     #[serde(default, with = "crate::serde::ts_seconds_option_bound_tuple")]
+    #[schema(value_type = Option<Vec<Option<i64>>>)]
     time: Option<BoundedDateTimeTuple>,
+    #[schema(value_type = Option<Object>)]
     tags: Option<JsonValue>,
     classroom_id: Option<Uuid>,
+    moderated: Option<bool>,
+    keep_open: Option<bool>,
+    capacity: Option<i32>,
+    secondary_audience: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -241,6 +423,19 @@ pub struct UpdateRequest {
     payload: UpdatePayload,
 }
 
+#[utoipa::path(
+    patch,
+    path = "/rooms/{id}",
+    tag = "room",
+    params(("id" = Uuid, Path, description = "Room identifier")),
+    request_body = UpdatePayload,
+    responses(
+        (status = 200, description = "Updated room", body = inline(crate::db::room::Object)),
+        (status = 404, description = "Room not found", body = ErrorPayload),
+        (status = 422, description = "Invalid room time", body = ErrorPayload),
+    ),
+    security(("bearer" = []))
+)]
 pub async fn update(
     ctx: extract::Extension<Arc<AppContext>>,
     AgentIdExtractor(agent_id): AgentIdExtractor,
@@ -273,6 +468,8 @@ impl RequestHandler for UpdateHandler {
         Self::Payload { id, payload }: Self::Payload,
         reqp: RequestParams<'_>,
     ) -> RequestResult {
+        validate_capacity(payload.capacity)?;
+
         let time_requirement = if payload.time.is_some() {
             // Forbid changing time of a closed room.
             helpers::RoomTimeRequirement::NotClosed
@@ -287,8 +484,8 @@ impl RequestHandler for UpdateHandler {
 
         let authz_time = context
             .authz()
-            .authorize(
-                room.audience().into(),
+            .authorize_room(
+                &room,
                 reqp.as_account_id().to_owned(),
                 object,
                 "update".into(),
@@ -318,7 +515,11 @@ impl RequestHandler for UpdateHandler {
             let query = UpdateQuery::new(room.id())
                 .time(time)
                 .tags(payload.tags)
-                .classroom_id(payload.classroom_id);
+                .classroom_id(payload.classroom_id)
+                .moderated(payload.moderated)
+                .keep_open(payload.keep_open)
+                .capacity(payload.capacity)
+                .secondary_audience(payload.secondary_audience);
 
             let mut conn = context.get_conn().await?;
 
@@ -330,6 +531,8 @@ impl RequestHandler for UpdateHandler {
                 .error(AppErrorKind::DbQueryFailed)?
         };
 
+        context.room_cache().invalidate(room.id());
+
         // Respond and broadcast to the audience topic.
         let mut response = AppResponse::new(
             ResponseStatus::OK,
@@ -340,18 +543,22 @@ impl RequestHandler for UpdateHandler {
 
         response.add_notification(
             "room.update",
-            &format!("audiences/{}/events", room.audience()),
+            &context.config().audience_topic(room.audience()),
             room.clone(),
             context.start_timestamp(),
-        );
+        )?;
 
         let append_closed_notification = || {
+            context
+                .webhook_client()
+                .send(room.audience(), Webhook::new("room.close", &room));
+
             response.add_notification(
                 "room.close",
-                &format!("rooms/{}/events", room.id()),
+                &context.config().room_topic(room.audience(), room.id()),
                 room,
                 context.start_timestamp(),
-            );
+            )
         };
 
         // Publish room closed notification
@@ -359,10 +566,10 @@ impl RequestHandler for UpdateHandler {
             if let Some(time) = payload.time {
                 match time.1 {
                     Bound::Included(t) if Utc::now() > t => {
-                        append_closed_notification();
+                        append_closed_notification()?;
                     }
                     Bound::Excluded(t) if Utc::now() >= t => {
-                        append_closed_notification();
+                        append_closed_notification()?;
                     }
                     _ => {}
                 }
@@ -392,6 +599,9 @@ pub struct RoomEnterEvent {
     agent_id: AgentId,
     banned: bool,
     agent: crate::db::agent::AgentWithBan,
+    /// Number of agents in the room when the presence is enabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    online: Option<usize>,
 }
 
 pub struct EnterHandler;
@@ -443,39 +653,85 @@ impl RequestHandler for EnterHandler {
 
         let authz_time = context
             .authz()
-            .authorize(
-                room.audience().into(),
+            .authorize_room(
+                &room,
                 reqp.as_account_id().to_owned(),
                 object,
                 "read".into(),
             )
             .await?;
 
-        // Register agent in `in_progress` state.
+        // Register agent in `in_progress` state unless the room is full.
         {
             let mut conn = context.get_conn().await?;
+
+            let mut txn = conn
+                .begin()
+                .await
+                .context("Failed to acquire transaction")
+                .error(AppErrorKind::DbQueryFailed)?;
+
+            if let Some(capacity) = helpers::room_capacity(context, &room) {
+                let waiting =
+                    check_capacity(context, &mut txn, &room, capacity, reqp.as_agent_id()).await?;
+
+                if let Some(position) = waiting {
+                    txn.commit()
+                        .await
+                        .context("Failed to commit transaction")
+                        .error(AppErrorKind::DbQueryFailed)?;
+
+                    let response = AppResponse::new(
+                        ResponseStatus::ACCEPTED,
+                        json!({ "queued": true, "position": position }),
+                        context.start_timestamp(),
+                        Some(authz_time),
+                    );
+
+                    return Ok(response);
+                }
+            }
+
             let query = agent::InsertQuery::new(reqp.as_agent_id().to_owned(), room.id());
 
             context
                 .metrics()
-                .measure_query(QueryKey::AgentInsertQuery, query.execute(&mut conn))
+                .measure_query(QueryKey::AgentInsertQuery, query.execute(&mut txn))
                 .await
                 .context("Failed to insert agent into room")
                 .error(AppErrorKind::DbQueryFailed)?;
-            context
-                .metrics()
-                .measure_query(
-                    QueryKey::EventInsertQuery,
-                    insert_agent_action(&room, AgentAction::Enter, reqp.as_agent_id(), &mut conn),
-                )
+
+            if room.settings().agent_actions() {
+                let data_encryption = context.data_encryption();
+
+                context
+                    .metrics()
+                    .measure_query(
+                        QueryKey::EventInsertQuery,
+                        insert_agent_action(
+                            &room,
+                            AgentAction::Enter,
+                            reqp.as_agent_id(),
+                            data_encryption.as_deref(),
+                            &mut txn,
+                        ),
+                    )
+                    .await
+                    .context("Failed to insert agent action")
+                    .error(AppErrorKind::DbQueryFailed)?;
+            }
+
+            txn.commit()
                 .await
-                .context("Failed to insert agent action")
+                .context("Failed to commit transaction")
                 .error(AppErrorKind::DbQueryFailed)?;
         }
 
+        let topic = context.config().room_topic(room.audience(), room.id());
+
         let req1 = context
             .broker_client()
-            .enter_room(room.id(), reqp.as_agent_id());
+            .enter_room(&topic, reqp.as_agent_id());
         let req2 = context
             .broker_client()
             .enter_broadcast_room(room.id(), reqp.as_agent_id());
@@ -502,6 +758,9 @@ impl RequestHandler for EnterHandler {
                 .context("Failed to put agent into 'ready' status")
                 .error(AppErrorKind::DbQueryFailed)?;
 
+            context.presence_cache().invalidate(room.id());
+            helpers::update_room_presence(context, room.id(), reqp.as_agent_id(), true).await;
+
             let query = agent::FindWithBanQuery::new(reqp.as_agent_id().clone(), room.id());
 
             context
@@ -515,6 +774,7 @@ impl RequestHandler for EnterHandler {
         };
 
         let banned = agent_with_ban.banned().unwrap_or(false);
+        let online = helpers::room_online_count(context, room.id()).await;
 
         // Send a response to the original `room.enter` request and a room-wide notification.
         let mut response = AppResponse::new(
@@ -526,20 +786,100 @@ impl RequestHandler for EnterHandler {
 
         response.add_notification(
             "room.enter",
-            &format!("rooms/{}/events", room.id()),
+            &context.config().room_topic(room.audience(), room.id()),
             RoomEnterEvent {
                 id: room.id(),
                 agent_id: reqp.as_agent_id().to_owned(),
                 agent: agent_with_ban,
                 banned,
+                online,
             },
             context.start_timestamp(),
-        );
+        )?;
 
         Ok(response)
     }
 }
 
+/// Lets the agent take a place in a room with limited capacity.
+///
+/// Returns the agent's position in the queue when it has to wait. Waiting agents repeat
+/// the request until they get admitted. Fails with `room_full` when the queue is disabled.
+async fn check_capacity<C: Context>(
+    context: &mut C,
+    conn: &mut PgConnection,
+    room: &Room,
+    capacity: i32,
+    agent_id: &AgentId,
+) -> Result<Option<i64>, AppError> {
+    let config = context.config().room_capacity.clone();
+    helpers::room_capacity_lock(conn, room.id()).await?;
+
+    let query =
+        room_queue::OccupancyQuery::new(room.id(), agent_id.to_owned(), config.admission_timeout);
+
+    let occupancy = context
+        .metrics()
+        .measure_query(QueryKey::RoomQueueOccupancyQuery, query.execute(conn))
+        .await
+        .context("Failed to count agents in the room")
+        .error(AppErrorKind::DbQueryFailed)?;
+
+    let free_places = occupancy.free_places(capacity);
+
+    // Those waiting in the queue go first.
+    if occupancy.admitted || (free_places > 0 && (!config.queue || occupancy.waiting == 0)) {
+        leave_queue(context, conn, room, agent_id).await?;
+        return Ok(None);
+    }
+
+    if !config.queue {
+        return Err(anyhow!(
+            "Room {} has reached its capacity of {} agents",
+            room.id(),
+            capacity
+        ))
+        .error(AppErrorKind::RoomFull);
+    }
+
+    let query = room_queue::EnqueueQuery::new(room.id(), agent_id.to_owned());
+
+    let position = context
+        .metrics()
+        .measure_query(QueryKey::RoomQueueEnqueueQuery, query.execute(conn))
+        .await
+        .context("Failed to put agent into the queue")
+        .error(AppErrorKind::DbQueryFailed)?;
+
+    // Places may be free while the queue isn't moving when admitted agents haven't come in time.
+    let admitted = helpers::admit_queued_agents(context, conn, room, free_places).await?;
+
+    if admitted.contains(agent_id) {
+        leave_queue(context, conn, room, agent_id).await?;
+        return Ok(None);
+    }
+
+    Ok(Some(position))
+}
+
+async fn leave_queue<C: Context>(
+    context: &mut C,
+    conn: &mut PgConnection,
+    room: &Room,
+    agent_id: &AgentId,
+) -> Result<(), AppError> {
+    let query = room_queue::DeleteQuery::new(room.id(), agent_id.to_owned());
+
+    context
+        .metrics()
+        .measure_query(QueryKey::RoomQueueDeleteQuery, query.execute(conn))
+        .await
+        .context("Failed to delete agent from the queue")
+        .error(AppErrorKind::DbQueryFailed)?;
+
+    Ok(())
+}
+
 ///////////////////////////////////////////////////////////////////////////////
 
 #[derive(Debug, Deserialize)]
@@ -594,8 +934,8 @@ impl RequestHandler for LockedTypesHandler {
 
         let authz_time = context
             .authz()
-            .authorize(
-                room.audience().into(),
+            .authorize_room(
+                &room,
                 reqp.as_account_id().to_owned(),
                 object,
                 "update".into(),
@@ -635,6 +975,8 @@ impl RequestHandler for LockedTypesHandler {
             room
         };
 
+        context.room_cache().invalidate(room.id());
+
         // Respond and broadcast to the audience topic.
         let mut response = AppResponse::new(
             ResponseStatus::OK,
@@ -645,10 +987,10 @@ impl RequestHandler for LockedTypesHandler {
 
         response.add_notification(
             "room.update",
-            &format!("rooms/{}/events", room.id()),
+            &context.config().room_topic(room.audience(), room.id()),
             room,
             context.start_timestamp(),
-        );
+        )?;
 
         Ok(response)
     }
@@ -715,8 +1057,8 @@ impl RequestHandler for WhiteboardAccessHandler {
 
         let authz_time = context
             .authz()
-            .authorize(
-                room.audience().into(),
+            .authorize_room(
+                &room,
                 reqp.as_account_id().to_owned(),
                 object,
                 "update".into(),
@@ -754,6 +1096,8 @@ impl RequestHandler for WhiteboardAccessHandler {
             room
         };
 
+        context.room_cache().invalidate(room.id());
+
         // Respond and broadcast to the audience topic.
         let mut response = AppResponse::new(
             ResponseStatus::OK,
@@ -764,10 +1108,10 @@ impl RequestHandler for WhiteboardAccessHandler {
 
         response.add_notification(
             "room.update",
-            &format!("rooms/{}/events", room.id()),
+            &context.config().room_topic(room.audience(), room.id()),
             room,
             context.start_timestamp(),
-        );
+        )?;
 
         Ok(response)
     }
@@ -831,78 +1175,137 @@ impl RequestHandler for AdjustHandler {
 
         let authz_time = context
             .authz()
-            .authorize(
-                room.audience().into(),
+            .authorize_room(
+                &room,
                 reqp.as_account_id().to_owned(),
                 object,
                 "update".into(),
             )
             .await?;
 
+        // Only one operation deriving rooms may run on the room at a time.
+        let lock = RoomOperationLock::try_acquire(context.background_db(), room.id())
+            .await
+            .context("Failed to lock room")
+            .error(AppErrorKind::DbQueryFailed)?
+            .ok_or_else(|| anyhow!("Another operation on the room is in progress"))
+            .error(AppErrorKind::OperationInProgress)?;
+
+        let task = helpers::start_task(context, "room.adjust", &room, reqp.as_agent_id()).await?;
+
         // Run asynchronous task for adjustment.
-        let db = context.db().to_owned();
+        let task_id = task.id();
+        let db = context.background_db().to_owned();
         let metrics = context.metrics();
-        let cfg = context.config().to_owned();
+        let cfg = context.config().adjust_for(room.kind());
+        let webhook_client = context.webhook_client();
+        let jobs = context.jobs();
+        let job_id = jobs.start("adjust", Some(room.id()));
+        let room_cache = context.room_cache();
+        let data_encryption = context.data_encryption();
+        let path = context.config().audience_topic(room.audience());
+        let span = helpers::operation_span("adjust", &room, Some(reqp.as_agent_id()));
+
+        let notification_future = tokio::task::spawn(
+            async move {
+                let operation_result = adjust_room(
+                    &db,
+                    &metrics,
+                    &room_cache,
+                    data_encryption.as_deref(),
+                    &room,
+                    payload.started_at,
+                    &payload.segments,
+                    payload.offset,
+                    cfg,
+                )
+                .await;
 
-        let notification_future = tokio::task::spawn(async move {
-            let operation_result = adjust_room(
-                &db,
-                &metrics,
-                &room,
-                payload.started_at,
-                &payload.segments,
-                payload.offset,
-                cfg.adjust,
-            )
-            .await;
-
-            // Handle result.
-            let result = match operation_result {
-                Ok(AdjustOutput {
-                    original_room,
-                    modified_room,
-                    modified_segments,
-                    cut_original_segments,
-                }) => {
-                    info!(class_id = %room.classroom_id(), "Adjustment job succeeded");
-                    RoomAdjustResult::Success {
-                        original_room_id: original_room.id(),
-                        modified_room_id: modified_room.id(),
+                if let Err(err) = lock.release().await {
+                    warn!("Failed to release room lock: {:?}", err);
+                }
+
+                jobs.finish(job_id, &operation_result);
+
+                // Handle result.
+                let result = match operation_result {
+                    Ok(AdjustOutput {
+                        original_room,
+                        modified_room,
                         modified_segments,
                         cut_original_segments,
+                    }) => {
+                        info!("Adjustment job succeeded");
+                        RoomAdjustResult::Success {
+                            original_room_id: original_room.id(),
+                            modified_room_id: modified_room.id(),
+                            modified_segments,
+                            cut_original_segments,
+                        }
                     }
-                }
-                Err(err) => {
-                    error!(class_id = %room.classroom_id(), "Room adjustment job failed: {:?}", err);
-                    let app_error = AppError::new(AppErrorKind::RoomAdjustTaskFailed, err);
-                    app_error.notify_sentry();
-                    RoomAdjustResult::Error {
-                        error: app_error.to_svc_error(),
+                    Err(err) => {
+                        error!("Room adjustment job failed: {:?}", err);
+                        let app_error = AppError::new(AppErrorKind::RoomAdjustTaskFailed, err);
+                        app_error.notify_sentry();
+                        RoomAdjustResult::Error {
+                            error: app_error.to_payload(),
+                        }
+                    }
+                };
+
+                // Persist the result to be able to get it without the notification.
+                let query = match result {
+                    RoomAdjustResult::Success {
+                        original_room_id,
+                        modified_room_id,
+                        ref modified_segments,
+                        ref cut_original_segments,
+                    } => AdjustmentFinishQuery::succeeded(
+                        id,
+                        original_room_id,
+                        modified_room_id,
+                        modified_segments.to_owned(),
+                        cut_original_segments.to_owned(),
+                    ),
+                    RoomAdjustResult::Error { ref error } => {
+                        AdjustmentFinishQuery::failed(id, json!(error))
                     }
+                };
+
+                if let Err(err) = finish_adjustment(&db, &metrics, query).await {
+                    error!("Failed to save room adjustment result: {:?}", err);
                 }
-            };
 
-            // Publish success/failure notification.
-            let notification = RoomAdjustNotification {
-                room_id: id,
-                status: result.status(),
-                tags: room.tags().map(|t| t.to_owned()),
-                result,
-            };
+                // Publish success/failure notification.
+                let notification = RoomAdjustNotification {
+                    room_id: id,
+                    status: result.status(),
+                    tags: room.tags().map(|t| t.to_owned()),
+                    result,
+                };
+
+                let task_status = match notification.result {
+                    RoomAdjustResult::Success { .. } => TaskStatus::Succeeded,
+                    RoomAdjustResult::Error { .. } => TaskStatus::Failed,
+                };
 
-            let timing = ShortTermTimingProperties::new(Utc::now());
-            let props = OutgoingEventProperties::new("room.adjust", timing);
-            let path = format!("audiences/{}/events", room.audience());
-            let event = OutgoingEvent::broadcast(notification, props, &path);
+                helpers::finish_task(&db, &metrics, task_id, task_status, &notification).await;
+                webhook_client.send(room.audience(), Webhook::new("room.adjust", &notification));
 
-            Box::new(event) as Message
-        });
+                let timing = ShortTermTimingProperties::new(Utc::now());
+                let props = OutgoingEventProperties::new("room.adjust", timing);
+                let event = OutgoingEvent::broadcast(notification, props, &path);
+
+                Box::new(event) as Message
+            }
+            .instrument(span),
+        );
 
         // Respond with 202.
         // The actual task result will be broadcasted to the room topic when finished.
         let mut response = AppResponse::new(
             ResponseStatus::ACCEPTED,
-            json!({}),
+            json!({ "task_id": task_id }),
             context.start_timestamp(),
             Some(authz_time),
         );
@@ -913,6 +1316,29 @@ impl RequestHandler for AdjustHandler {
     }
 }
 
+async fn finish_adjustment(
+    db: &sqlx::PgPool,
+    metrics: &Metrics,
+    query: AdjustmentFinishQuery,
+) -> anyhow::Result<()> {
+    let mut conn = db
+        .acquire()
+        .await
+        .context("Failed to acquire db connection")?;
+
+    let adjustment = metrics
+        .measure_query(QueryKey::AdjustmentFinishQuery, query.execute(&mut conn))
+        .await
+        .context("Failed to update adjustment")?;
+
+    // The adjustment fails before being saved if the room can't be adjusted at all.
+    if adjustment.is_none() {
+        warn!("No adjustment found to save the result to");
+    }
+
+    Ok(())
+}
+
 #[derive(Serialize)]
 struct RoomAdjustNotification {
     room_id: Uuid,
@@ -935,7 +1361,7 @@ enum RoomAdjustResult {
         cut_original_segments: Segments,
     },
     Error {
-        error: SvcError,
+        error: ErrorPayload,
     },
 }
 
@@ -950,7 +1376,127 @@ impl RoomAdjustResult {
 
 ///////////////////////////////////////////////////////////////////////////////
 
+#[derive(Debug, Deserialize)]
+pub struct AdjustPreviewPayload {
+    #[serde(with = "chrono::serde::ts_milliseconds")]
+    started_at: DateTime<Utc>,
+    #[serde(with = "crate::db::adjustment::serde::segments")]
+    segments: Segments,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AdjustPreviewRequest {
+    id: Uuid,
+    #[serde(flatten)]
+    payload: AdjustPreviewPayload,
+}
+
+#[derive(Serialize)]
+struct AdjustPreviewResponse {
+    #[serde(with = "crate::db::adjustment::serde::segments")]
+    modified_segments: Segments,
+    #[serde(with = "crate::db::adjustment::serde::segments")]
+    cut_original_segments: Segments,
+}
+
+pub async fn adjust_preview(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<Uuid>,
+    Json(payload): Json<AdjustPreviewPayload>,
+) -> RequestResult {
+    let request = AdjustPreviewRequest {
+        id: room_id,
+        payload,
+    };
+    AdjustPreviewHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct AdjustPreviewHandler;
+
+#[async_trait]
+impl RequestHandler for AdjustPreviewHandler {
+    type Payload = AdjustPreviewRequest;
+
+    #[instrument(skip_all, fields(room_id, scope, classroom_id))]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload { id, payload }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let room = helpers::find_room(context, id, helpers::RoomTimeRequirement::Any).await?;
+
+        // Previewing is allowed to those who can adjust the room.
+        let object = AuthzObject::room(&room).into();
+
+        let authz_time = context
+            .authz()
+            .authorize_room(
+                &room,
+                reqp.as_account_id().to_owned(),
+                object,
+                "update".into(),
+            )
+            .await?;
+
+        let parsed_segments = segments::parse(&payload.segments)
+            .context("Invalid segments")
+            .error(AppErrorKind::InvalidPayload)?;
+
+        if parsed_segments.is_empty() {
+            return Err(anyhow!("Missing segments")).error(AppErrorKind::InvalidPayload);
+        }
+
+        let AdjustPreview {
+            modified_segments,
+            cut_original_segments,
+        } = adjust_room_preview(
+            context.ro_db(),
+            &context.metrics(),
+            &room,
+            payload.started_at,
+            &payload.segments,
+            &context.config().adjust_for(room.kind()),
+        )
+        .await
+        .context("Failed to preview room adjustment")
+        .error(AppErrorKind::RoomAdjustTaskFailed)?;
+
+        Ok(AppResponse::new(
+            ResponseStatus::OK,
+            AdjustPreviewResponse {
+                modified_segments,
+                cut_original_segments,
+            },
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+pub use bootstrap::BootstrapHandler;
 pub use dump_events::EventsDumpHandler;
+pub use list::ListHandler;
+pub use notify::NotifyHandler;
+pub use operation_status::OperationStatusHandler;
+pub use permissions::PermissionsHandler;
+pub use presence::PresenceHandler;
+pub use replay::ReplayHandler;
+pub use schedule_lock::ScheduleLockHandler;
+pub use search::SearchHandler;
+pub use share::ShareHandler;
+pub use transcript::TranscriptHandler;
+pub use update_settings::UpdateSettingsHandler;
+pub use verify::VerifyHandler;
 
 ///////////////////////////////////////////////////////////////////////////////
 
@@ -992,6 +1538,10 @@ mod tests {
                 preserve_history: Some(false),
                 classroom_id: Uuid::new_v4(),
                 kind: ClassType::Minigroup,
+                moderated: None,
+                keep_open: None,
+                capacity: None,
+                secondary_audience: None,
             };
 
             let messages = handle_request::<CreateHandler>(&mut context, &agent, payload)
@@ -1015,6 +1565,104 @@ mod tests {
             assert_eq!(room.preserve_history(), false);
         }
 
+        #[tokio::test]
+        async fn create_room_sends_webhook() {
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut authz = TestAuthz::new();
+            authz.allow(agent.account_id(), vec!["classrooms"], "create");
+
+            let mut context = TestContext::new(TestDb::new().await, authz);
+            let classroom_id = Uuid::new_v4();
+
+            let webhook_client = context.webhook_client_mock();
+            webhook_client.checkpoint();
+
+            webhook_client
+                .expect_send()
+                .withf(move |audience, webhook| {
+                    audience == USR_AUDIENCE
+                        && webhook.event() == "room.create"
+                        && webhook.payload()["classroom_id"] == json!(classroom_id)
+                })
+                .times(1)
+                .return_const(());
+
+            let now = Utc::now().trunc_subsecs(0);
+
+            let payload = CreateRequest {
+                time: BoundedDateTimeTuple::from((
+                    Bound::Included(now + Duration::hours(1)),
+                    Bound::Excluded(now + Duration::hours(2)),
+                )),
+                audience: USR_AUDIENCE.to_owned(),
+                tags: None,
+                preserve_history: None,
+                classroom_id,
+                kind: ClassType::Minigroup,
+                moderated: None,
+                keep_open: None,
+                capacity: None,
+                secondary_audience: None,
+            };
+
+            handle_request::<CreateHandler>(&mut context, &agent, payload)
+                .await
+                .expect("Room creation failed");
+
+            context.webhook_client_mock().checkpoint();
+        }
+
+        #[tokio::test]
+        async fn create_room_with_class_type_defaults() {
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut authz = TestAuthz::new();
+            authz.allow(agent.account_id(), vec!["classrooms"], "create");
+
+            let mut context = TestContext::new(TestDb::new().await, authz);
+
+            context.update_config(|config| {
+                let settings = crate::db::room::Settings::default()
+                    .merge(&json!({ "whiteboard_access_validation": false }))
+                    .expect("Invalid settings");
+
+                config.class_types.insert(
+                    ClassType::Minigroup,
+                    crate::config::ClassTypeConfig {
+                        locked_types: [("message".to_owned(), true)].into_iter().collect(),
+                        settings,
+                        thinning: None,
+                    },
+                );
+            });
+
+            let now = Utc::now().trunc_subsecs(0);
+
+            let payload = CreateRequest {
+                time: BoundedDateTimeTuple::from((
+                    Bound::Included(now + Duration::hours(1)),
+                    Bound::Excluded(now + Duration::hours(2)),
+                )),
+                audience: USR_AUDIENCE.to_owned(),
+                tags: None,
+                preserve_history: None,
+                classroom_id: Uuid::new_v4(),
+                kind: ClassType::Minigroup,
+                moderated: None,
+                keep_open: None,
+                capacity: None,
+                secondary_audience: None,
+            };
+
+            let messages = handle_request::<CreateHandler>(&mut context, &agent, payload)
+                .await
+                .expect("Room creation failed");
+
+            let (room, respp, _) = find_response::<Room>(messages.as_slice());
+            assert_eq!(respp.status(), ResponseStatus::CREATED);
+            assert_eq!(room.locked_types().get("message"), Some(&true));
+            assert!(!room.validate_whiteboard_access());
+        }
+
         #[tokio::test]
         async fn create_room_unbounded() {
             // Allow agent to create rooms.
@@ -1037,6 +1685,10 @@ mod tests {
                 preserve_history: Some(false),
                 classroom_id: Uuid::new_v4(),
                 kind: ClassType::P2P,
+                moderated: None,
+                keep_open: None,
+                capacity: None,
+                secondary_audience: None,
             };
 
             let messages = handle_request::<CreateHandler>(&mut context, &agent, payload)
@@ -1083,6 +1735,10 @@ mod tests {
                 preserve_history: Some(false),
                 classroom_id: cid,
                 kind: ClassType::Webinar,
+                moderated: None,
+                keep_open: None,
+                capacity: None,
+                secondary_audience: None,
             };
 
             let messages = handle_request::<CreateHandler>(&mut context, &agent, payload)
@@ -1128,6 +1784,10 @@ mod tests {
                 preserve_history: None,
                 classroom_id: Uuid::new_v4(),
                 kind: ClassType::Minigroup,
+                moderated: None,
+                keep_open: None,
+                capacity: None,
+                secondary_audience: None,
             };
 
             let err = handle_request::<CreateHandler>(&mut context, &agent, payload)
@@ -1154,57 +1814,191 @@ mod tests {
                 preserve_history: None,
                 classroom_id: Uuid::new_v4(),
                 kind: ClassType::Webinar,
+                moderated: None,
+                keep_open: None,
+                capacity: None,
+                secondary_audience: None,
+            };
+
+            let err = handle_request::<CreateHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on room creation");
+
+            assert_eq!(err.status(), ResponseStatus::BAD_REQUEST);
+            assert_eq!(err.kind(), "invalid_room_time");
+        }
+    }
+
+    mod read {
+        use axum::response::IntoResponse;
+        use chrono::{Duration, SubsecRound};
+
+        use crate::db::room::Object as Room;
+        use crate::test_helpers::prelude::*;
+
+        use super::super::*;
+
+        #[tokio::test]
+        async fn read_room() {
+            let db = TestDb::new().await;
+
+            let room = {
+                // Create room.
+                let mut conn = db.get_conn().await;
+                shared_helpers::insert_room(&mut conn).await
+            };
+
+            // Allow agent to read the room.
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut authz = TestAuthz::new();
+            authz.allow(
+                agent.account_id(),
+                vec!["classrooms", &room.classroom_id().to_string()],
+                "read",
+            );
+
+            // Make room.read request.
+            let mut context = TestContext::new(db, authz);
+            let payload = ReadRequest {
+                id: room.id(),
+                if_none_match: None,
+            };
+
+            let messages = handle_request::<ReadHandler>(&mut context, &agent, payload)
+                .await
+                .expect("Room reading failed");
+
+            // Assert response.
+            let (resp_room, respp, _) = find_response::<Room>(messages.as_slice());
+            assert_eq!(respp.status(), ResponseStatus::OK);
+            assert_eq!(resp_room.audience(), room.audience());
+            assert_eq!(resp_room.time(), room.time());
+            assert_eq!(resp_room.tags(), room.tags());
+            assert_eq!(resp_room.preserve_history(), room.preserve_history());
+        }
+
+        #[tokio::test]
+        async fn read_room_not_modified() {
+            let db = TestDb::new().await;
+
+            let room = {
+                let mut conn = db.get_conn().await;
+                shared_helpers::insert_room(&mut conn).await
             };
 
-            let err = handle_request::<CreateHandler>(&mut context, &agent, payload)
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut authz = TestAuthz::new();
+            authz.allow(
+                agent.account_id(),
+                vec!["classrooms", &room.classroom_id().to_string()],
+                "read",
+            );
+
+            let mut context = TestContext::new(db, authz);
+            let reqp = RequestParams::Http {
+                agent_id: agent.agent_id(),
+            };
+
+            let payload = ReadRequest {
+                id: room.id(),
+                if_none_match: None,
+            };
+
+            let resp = ReadHandler::handle(&mut context, payload, reqp)
+                .await
+                .expect("Room reading failed")
+                .into_response();
+
+            assert_eq!(resp.status(), ResponseStatus::OK);
+
+            let etag = resp
+                .headers()
+                .get(http::header::ETAG)
+                .and_then(|v| v.to_str().ok())
+                .expect("Missing ETag")
+                .to_owned();
+
+            // Same tag means nothing changed.
+            let payload = ReadRequest {
+                id: room.id(),
+                if_none_match: Some(etag.clone()),
+            };
+
+            let resp = ReadHandler::handle(&mut context, payload, reqp)
+                .await
+                .expect("Room reading failed")
+                .into_response();
+
+            assert_eq!(resp.status(), ResponseStatus::NOT_MODIFIED);
+
+            // The room update changes the tag.
+            let mut conn = context.db().acquire().await.unwrap();
+
+            UpdateQuery::new(room.id())
+                .tags(Some(json!({ "webinar_id": "456" })))
+                .execute(&mut conn)
                 .await
-                .expect_err("Unexpected success on room creation");
+                .expect("Failed to update room");
 
-            assert_eq!(err.status(), ResponseStatus::BAD_REQUEST);
-            assert_eq!(err.kind(), "invalid_room_time");
-        }
-    }
+            let payload = ReadRequest {
+                id: room.id(),
+                if_none_match: Some(etag),
+            };
 
-    mod read {
-        use crate::db::room::Object as Room;
-        use crate::test_helpers::prelude::*;
+            let resp = ReadHandler::handle(&mut context, payload, reqp)
+                .await
+                .expect("Room reading failed")
+                .into_response();
 
-        use super::super::*;
+            assert_eq!(resp.status(), ResponseStatus::OK);
+        }
 
         #[tokio::test]
-        async fn read_room() {
+        async fn read_room_authorized_by_secondary_audience() {
             let db = TestDb::new().await;
+            let now = Utc::now().trunc_subsecs(0);
 
             let room = {
-                // Create room.
                 let mut conn = db.get_conn().await;
-                shared_helpers::insert_room(&mut conn).await
+
+                factory::Room::new(Uuid::new_v4(), ClassType::Webinar)
+                    .audience(USR_AUDIENCE)
+                    .secondary_audience("partner.example.org")
+                    .time((
+                        Bound::Included(now),
+                        Bound::Excluded(now + Duration::hours(1)),
+                    ))
+                    .insert(&mut conn)
+                    .await
             };
 
-            // Allow agent to read the room.
-            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            // Only the co-hosting tenant grants access.
+            let agent = TestAgent::new("web", "user123", "partner.example.org");
             let mut authz = TestAuthz::new();
+            authz.set_audience("partner.example.org");
             authz.allow(
                 agent.account_id(),
                 vec!["classrooms", &room.classroom_id().to_string()],
                 "read",
             );
 
-            // Make room.read request.
             let mut context = TestContext::new(db, authz);
-            let payload = ReadRequest { id: room.id() };
+            let payload = ReadRequest {
+                id: room.id(),
+                if_none_match: None,
+            };
 
             let messages = handle_request::<ReadHandler>(&mut context, &agent, payload)
                 .await
                 .expect("Room reading failed");
 
-            // Assert response.
             let (resp_room, respp, _) = find_response::<Room>(messages.as_slice());
             assert_eq!(respp.status(), ResponseStatus::OK);
-            assert_eq!(resp_room.audience(), room.audience());
-            assert_eq!(resp_room.time(), room.time());
-            assert_eq!(resp_room.tags(), room.tags());
-            assert_eq!(resp_room.preserve_history(), room.preserve_history());
+            assert_eq!(resp_room.secondary_audience(), Some("partner.example.org"));
+            assert_eq!(
+                resp_room.audiences().collect::<Vec<_>>(),
+                vec![USR_AUDIENCE, "partner.example.org"]
+            );
         }
 
         #[tokio::test]
@@ -1220,7 +2014,10 @@ mod tests {
 
             // Make room.read request.
             let mut context = TestContext::new(db, TestAuthz::new());
-            let payload = ReadRequest { id: room.id() };
+            let payload = ReadRequest {
+                id: room.id(),
+                if_none_match: None,
+            };
 
             let err = handle_request::<ReadHandler>(&mut context, &agent, payload)
                 .await
@@ -1233,7 +2030,10 @@ mod tests {
         async fn read_room_missing() {
             let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
             let mut context = TestContext::new(TestDb::new().await, TestAuthz::new());
-            let payload = ReadRequest { id: Uuid::new_v4() };
+            let payload = ReadRequest {
+                id: Uuid::new_v4(),
+                if_none_match: None,
+            };
 
             let err = handle_request::<ReadHandler>(&mut context, &agent, payload)
                 .await
@@ -1244,6 +2044,100 @@ mod tests {
         }
     }
 
+    mod pinned_events {
+        use serde_json::json;
+
+        use crate::db::event::Object as Event;
+        use crate::test_helpers::prelude::*;
+
+        use super::super::*;
+
+        #[tokio::test]
+        async fn pinned_events() {
+            let db = TestDb::new().await;
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+            let (room, pinned) = {
+                let mut conn = db.get_conn().await;
+                let room = shared_helpers::insert_room(&mut conn).await;
+
+                let event = |label: &str, occurred_at: i64| {
+                    factory::Event::new()
+                        .room_id(room.id())
+                        .kind("message")
+                        .set("messages")
+                        .label(label)
+                        .data(&json!({ "text": label }))
+                        .occurred_at(occurred_at)
+                        .created_by(agent.agent_id())
+                };
+
+                // Pinned.
+                let pinned = event("message-1", 1000)
+                    .attribute("pinned")
+                    .insert(&mut conn)
+                    .await;
+
+                // Unpinned by the next event.
+                event("message-2", 2000)
+                    .attribute("pinned")
+                    .insert(&mut conn)
+                    .await;
+
+                event("message-2", 3000).insert(&mut conn).await;
+
+                // Pinned but removed.
+                event("message-3", 4000)
+                    .attribute("pinned")
+                    .removed(true)
+                    .insert(&mut conn)
+                    .await;
+
+                (room, pinned)
+            };
+
+            let mut authz = TestAuthz::new();
+            authz.allow(
+                agent.account_id(),
+                vec!["classrooms", &room.classroom_id().to_string()],
+                "read",
+            );
+
+            // Make room.pinned_events request.
+            let mut context = TestContext::new(db, authz);
+            let payload = PinnedEventsRequest { id: room.id() };
+
+            let messages = handle_request::<PinnedEventsHandler>(&mut context, &agent, payload)
+                .await
+                .expect("Pinned events listing failed");
+
+            let (events, respp, _) = find_response::<Vec<Event>>(messages.as_slice());
+            assert_eq!(respp.status(), ResponseStatus::OK);
+            assert_eq!(events.len(), 1);
+            assert_eq!(events[0].id(), pinned.id());
+        }
+
+        #[tokio::test]
+        async fn pinned_events_not_authorized() {
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let db = TestDb::new().await;
+
+            let room = {
+                let mut conn = db.get_conn().await;
+                shared_helpers::insert_room(&mut conn).await
+            };
+
+            let mut context = TestContext::new(db, TestAuthz::new());
+            let payload = PinnedEventsRequest { id: room.id() };
+
+            let err = handle_request::<PinnedEventsHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on pinned events listing");
+
+            assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+        }
+    }
+
     mod update {
         use std::ops::Bound;
 
@@ -1301,6 +2195,10 @@ mod tests {
                     time: Some(time),
                     tags: Some(tags.clone()),
                     classroom_id: None,
+                    moderated: None,
+                    keep_open: None,
+                    capacity: None,
+                    secondary_audience: None,
                 },
             };
 
@@ -1360,6 +2258,10 @@ mod tests {
                     time: Some(time),
                     tags: None,
                     classroom_id: None,
+                    moderated: None,
+                    keep_open: None,
+                    capacity: None,
+                    secondary_audience: None,
                 },
             };
 
@@ -1423,6 +2325,10 @@ mod tests {
                     time: Some(time),
                     tags: None,
                     classroom_id: None,
+                    moderated: None,
+                    keep_open: None,
+                    capacity: None,
+                    secondary_audience: None,
                 },
             };
 
@@ -1504,6 +2410,10 @@ mod tests {
                     time: Some(time),
                     tags: None,
                     classroom_id: None,
+                    moderated: None,
+                    keep_open: None,
+                    capacity: None,
+                    secondary_audience: None,
                 },
             };
 
@@ -1534,6 +2444,10 @@ mod tests {
                     time: None,
                     tags: None,
                     classroom_id: None,
+                    moderated: None,
+                    keep_open: None,
+                    capacity: None,
+                    secondary_audience: None,
                 },
             };
 
@@ -1555,6 +2469,10 @@ mod tests {
                     time: None,
                     tags: None,
                     classroom_id: None,
+                    moderated: None,
+                    keep_open: None,
+                    capacity: None,
+                    secondary_audience: None,
                 },
             };
 
@@ -1591,6 +2509,10 @@ mod tests {
                     time: Some(time.into()),
                     tags: None,
                     classroom_id: None,
+                    moderated: None,
+                    keep_open: None,
+                    capacity: None,
+                    secondary_audience: None,
                 },
             };
 
@@ -1755,6 +2677,108 @@ mod tests {
             assert_eq!(err.status(), ResponseStatus::NOT_FOUND);
             assert_eq!(err.kind(), "room_closed");
         }
+
+        async fn insert_full_room(db: &TestDb) -> Room {
+            let mut conn = db.get_conn().await;
+            let now = Utc::now();
+
+            let room = factory::Room::new(Uuid::new_v4(), ClassType::Webinar)
+                .audience(USR_AUDIENCE)
+                .time((Bound::Included(now), Bound::Unbounded))
+                .capacity(1)
+                .insert(&mut conn)
+                .await;
+
+            let other = TestAgent::new("web", "user456", USR_AUDIENCE);
+            shared_helpers::insert_agent(&mut conn, other.agent_id(), room.id()).await;
+            room
+        }
+
+        #[tokio::test]
+        async fn enter_full_room() {
+            let db = TestDb::new().await;
+            let room = insert_full_room(&db).await;
+
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut authz = TestAuthz::new();
+            let classroom_id = room.classroom_id().to_string();
+            authz.allow(
+                agent.account_id(),
+                vec!["classrooms", &classroom_id],
+                "read",
+            );
+
+            let mut context = TestContext::new(db, authz);
+            let payload = EnterRequest { id: room.id() };
+
+            let err = handle_request::<EnterHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on room entering");
+
+            assert_eq!(err.status(), ResponseStatus::CONFLICT);
+            assert_eq!(err.kind(), "room_full");
+        }
+
+        #[tokio::test]
+        async fn enter_full_room_queued() {
+            let db = TestDb::new().await;
+            let room = insert_full_room(&db).await;
+
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut authz = TestAuthz::new();
+            let classroom_id = room.classroom_id().to_string();
+            authz.allow(
+                agent.account_id(),
+                vec!["classrooms", &classroom_id],
+                "read",
+            );
+
+            let mut context = TestContext::new(db, authz);
+            context.update_config(|config| config.room_capacity.queue = true);
+            let payload = EnterRequest { id: room.id() };
+
+            let messages = handle_request::<EnterHandler>(&mut context, &agent, payload)
+                .await
+                .expect("Room entrance failed");
+
+            // The agent waits in the queue without entering.
+            assert_eq!(messages.len(), 1);
+            let (payload, respp, _) = find_response::<JsonValue>(&messages);
+            assert_eq!(respp.status(), ResponseStatus::ACCEPTED);
+            assert_eq!(payload, json!({ "queued": true, "position": 1 }));
+
+            // Once the place frees up the agent gets in on the next attempt.
+            {
+                let mut conn = context.get_conn().await.expect("Failed to get conn");
+                let other = TestAgent::new("web", "user456", USR_AUDIENCE);
+
+                agent::DeleteQuery::new(other.agent_id().to_owned(), room.id())
+                    .execute(&mut conn)
+                    .await
+                    .expect("Failed to delete agent");
+            }
+
+            context
+                .broker_client_mock()
+                .expect_enter_room()
+                .with(mockall::predicate::always(), mockall::predicate::always())
+                .returning(move |_, _agent_id| Ok(CreateDeleteResponse::Ok));
+
+            context
+                .broker_client_mock()
+                .expect_enter_broadcast_room()
+                .with(mockall::predicate::always(), mockall::predicate::always())
+                .returning(move |_, _agent_id| Ok(CreateDeleteResponse::Ok));
+
+            let payload = EnterRequest { id: room.id() };
+
+            let messages = handle_request::<EnterHandler>(&mut context, &agent, payload)
+                .await
+                .expect("Room entrance failed");
+
+            let (_, respp, _) = find_response::<JsonValue>(&messages);
+            assert_eq!(respp.status(), ResponseStatus::OK);
+        }
     }
 
     mod adjust {
@@ -1817,6 +2841,121 @@ mod tests {
         }
     }
 
+    mod adjust_preview {
+        use crate::test_helpers::prelude::*;
+
+        use super::super::*;
+
+        #[tokio::test]
+        async fn adjust_preview() {
+            let db = TestDb::new().await;
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+            let room = {
+                let mut conn = db.get_conn().await;
+                let room = shared_helpers::insert_room(&mut conn).await;
+
+                for (occurred_at, value) in [(4_000_000_000, true), (6_000_000_000, false)] {
+                    factory::Event::new()
+                        .room_id(room.id())
+                        .kind("break")
+                        .set("break")
+                        .data(&json!({ "value": value }))
+                        .occurred_at(occurred_at)
+                        .created_by(agent.agent_id())
+                        .insert(&mut conn)
+                        .await;
+                }
+
+                room
+            };
+
+            let mut authz = TestAuthz::new();
+            authz.allow(
+                agent.account_id(),
+                vec!["classrooms", &room.classroom_id().to_string()],
+                "update",
+            );
+
+            let mut context = TestContext::new(db, authz);
+
+            let started_at = match room.time().map(|t| *t.start()) {
+                Ok(started_at) => started_at,
+                Err(err) => panic!("Invalid room time: {err}"),
+            };
+
+            let payload = AdjustPreviewRequest {
+                id: room.id(),
+                payload: AdjustPreviewPayload {
+                    started_at,
+                    segments: vec![(Bound::Included(0), Bound::Excluded(10000))].into(),
+                },
+            };
+
+            let messages = handle_request::<AdjustPreviewHandler>(&mut context, &agent, payload)
+                .await
+                .expect("Room adjustment preview failed");
+
+            let (resp, respp, _) = find_response::<JsonValue>(messages.as_slice());
+            assert_eq!(respp.status(), ResponseStatus::OK);
+
+            assert_eq!(
+                resp,
+                json!({
+                    "modified_segments": [[0, 4000], [6000, 10000]],
+                    "cut_original_segments": [[0, 4000], [6000, 10000]],
+                })
+            );
+
+            // No cut events get derived from the breaks.
+            let mut conn = context.get_conn().await.expect("Failed to get conn");
+
+            let cut_events = crate::db::event::ListQuery::new()
+                .room_id(room.id())
+                .kind("stream".to_string())
+                .execute(&mut conn)
+                .await
+                .expect("Failed to fetch cut events");
+
+            assert!(cut_events.is_empty());
+        }
+
+        #[tokio::test]
+        async fn adjust_preview_invalid_segments() {
+            let db = TestDb::new().await;
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+            let room = {
+                let mut conn = db.get_conn().await;
+                shared_helpers::insert_room(&mut conn).await
+            };
+
+            let mut authz = TestAuthz::new();
+            authz.allow(
+                agent.account_id(),
+                vec!["classrooms", &room.classroom_id().to_string()],
+                "update",
+            );
+
+            let mut context = TestContext::new(db, authz);
+
+            let payload = AdjustPreviewRequest {
+                id: room.id(),
+                payload: AdjustPreviewPayload {
+                    started_at: Utc::now(),
+                    segments: vec![].into(),
+                },
+            };
+
+            let err = handle_request::<AdjustPreviewHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on room adjustment preview");
+
+            assert_eq!(err.status(), ResponseStatus::BAD_REQUEST);
+            assert_eq!(err.kind(), "invalid_payload");
+        }
+    }
+
     mod locked_types {
         use crate::db::room::Object as Room;
         use crate::test_helpers::prelude::*;
@@ -2184,5 +3323,47 @@ mod tests {
     }
 }
 
-pub use dump_events::dump_events;
+pub use bootstrap::bootstrap;
+mod bootstrap;
+
+pub use dump_events::{dump, dump_events};
 mod dump_events;
+
+pub use list::list;
+mod list;
+
+pub use notify::notify;
+mod notify;
+
+pub use operation_status::operation_status;
+mod operation_status;
+
+pub use permissions::permissions;
+mod permissions;
+
+pub use presence::presence;
+mod presence;
+
+pub use replay::replay;
+mod replay;
+
+pub use restore::restore;
+mod restore;
+
+pub use schedule_lock::schedule_lock;
+mod schedule_lock;
+
+pub use search::search;
+mod search;
+
+pub use share::share;
+mod share;
+
+pub use transcript::{export_transcript_to_s3, transcript};
+mod transcript;
+
+pub use update_settings::update_settings;
+mod update_settings;
+
+pub use verify::verify;
+mod verify;
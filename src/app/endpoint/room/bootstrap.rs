@@ -0,0 +1,290 @@
+use anyhow::Context as AnyhowContext;
+use async_trait::async_trait;
+use axum::extract::RawQuery;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::{map::Map as JsonMap, Value as JsonValue};
+use svc_agent::mqtt::ResponseStatus;
+use tracing::{field::display, Span};
+use uuid::Uuid;
+
+use super::*;
+use crate::app::context::Context;
+use crate::app::endpoint::state::{
+    default_original_occurred_at, read_set_state, serialize_set_state, SetStateParams,
+};
+use crate::db;
+use crate::db::event::Object as Event;
+
+const MAX_SETS: usize = 10;
+const MAX_LIMIT_PER_SET: i64 = 100;
+const MAX_WINDOW: usize = 100;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct BootstrapPayload {
+    /// Sets to return the state of at the position.
+    #[serde(default)]
+    sets: Vec<String>,
+    /// Player position, `occurred_at` in nanoseconds.
+    #[serde(default)]
+    position: i64,
+    /// Number of events starting from the position to return.
+    window: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BootstrapRequest {
+    id: Uuid,
+    #[serde(flatten)]
+    payload: BootstrapPayload,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BootstrapResponse {
+    room: Room,
+    /// Set names mapped to their state like in `state.read`.
+    state: JsonMap<String, JsonValue>,
+    /// Events of the window starting from the position.
+    events: Vec<Event>,
+    pinned_events: Vec<Event>,
+}
+
+pub async fn bootstrap(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<Uuid>,
+    RawQuery(query): RawQuery,
+) -> RequestResult {
+    let payload = serde_qs::from_str(&query.unwrap_or_default())
+        .context("Failed to parse qs")
+        .error(AppErrorKind::InvalidQueryString)?;
+    let request = BootstrapRequest {
+        id: room_id,
+        payload,
+    };
+    BootstrapHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+/// Everything a player needs to open a recording in a single response instead of
+/// sequential `room.read`, `state.read`, `event.list` and `room.pinned_events` calls.
+pub struct BootstrapHandler;
+
+#[async_trait]
+impl RequestHandler for BootstrapHandler {
+    type Payload = BootstrapRequest;
+
+    #[instrument(skip_all, fields(room_id, scope, classroom_id))]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload { id, payload }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        Span::current().record("room_id", &display(id));
+
+        if payload.sets.len() > MAX_SETS {
+            return Err(anyhow!("too many 'sets'")).error(AppErrorKind::InvalidStateSets);
+        }
+
+        let room = helpers::find_room(context, id, helpers::RoomTimeRequirement::Any).await?;
+
+        // The same permission as for reading the room events separately.
+        let classroom_id = room.classroom_id().to_string();
+        let object = AuthzObject::new(&["classrooms", &classroom_id]);
+        let authz_time = helpers::authorize_room(context, &room, reqp, object, "read").await?;
+
+        // Moderator-only parts of event data are hidden from the others.
+        let (redaction, redaction_authz_time) =
+            helpers::Redaction::for_agent(context, &room, reqp).await?;
+
+        let authz_time = authz_time + redaction_authz_time.unwrap_or_else(chrono::Duration::zero);
+
+        let mut conn = context.get_ro_conn().await?;
+
+        // The state as of the position.
+        let params = SetStateParams {
+            original_occurred_at: default_original_occurred_at(&room)?,
+            occurred_at: Some(payload.position),
+            attribute: None,
+            limit: MAX_LIMIT_PER_SET,
+            without_data: false,
+            with_has_next: false,
+        };
+
+        let mut state = JsonMap::new();
+
+        for set in payload.sets.iter() {
+            let (mut set_state, _) =
+                read_set_state(context, &mut conn, room.id(), set, &params).await?;
+
+            redaction.apply(&mut set_state);
+            state.insert(set.to_owned(), serialize_set_state(set_state)?);
+        }
+
+        // The window of events to play from the position.
+        let mut events = {
+            let limit = std::cmp::min(payload.window.unwrap_or(MAX_WINDOW), MAX_WINDOW);
+
+            let query = db::event::ListQuery::new()
+                .room_id(room.id())
+                .occurred_at_from(payload.position)
+                .direction(db::event::Direction::Forward)
+                .limit(limit);
+
+            context
+                .metrics()
+                .measure_query(QueryKey::EventListQuery, query.execute(&mut conn))
+                .await
+                .context("Failed to list events")
+                .error(AppErrorKind::DbQueryFailed)?
+        };
+
+        helpers::open_events(context, &mut events)?;
+        redaction.apply(&mut events);
+
+        let mut pinned_events = context
+            .metrics()
+            .measure_query(
+                QueryKey::EventPinnedListQuery,
+                PinnedListQuery::new(room.id()).execute(&mut conn),
+            )
+            .await
+            .context("Failed to list pinned events")
+            .error(AppErrorKind::DbQueryFailed)?;
+
+        helpers::open_events(context, &mut pinned_events)?;
+        redaction.apply(&mut pinned_events);
+
+        Ok(AppResponse::new(
+            ResponseStatus::OK,
+            BootstrapResponse {
+                room,
+                state,
+                events,
+                pinned_events,
+            },
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_helpers::prelude::*;
+
+    #[tokio::test]
+    async fn bootstrap_room() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let (room, layout, pinned, next_message) = {
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+
+            let layout = factory::Event::new()
+                .room_id(room.id())
+                .kind("layout")
+                .set("layout")
+                .data(&json!({ "name": "presentation" }))
+                .occurred_at(1000)
+                .created_by(agent.agent_id())
+                .insert(&mut conn)
+                .await;
+
+            let message = |label: &str, occurred_at: i64| {
+                factory::Event::new()
+                    .room_id(room.id())
+                    .kind("message")
+                    .set("messages")
+                    .label(label)
+                    .data(&json!({ "text": label }))
+                    .occurred_at(occurred_at)
+                    .created_by(agent.agent_id())
+            };
+
+            let pinned = message("message-1", 2000)
+                .attribute("pinned")
+                .insert(&mut conn)
+                .await;
+
+            let next_message = message("message-2", 4000).insert(&mut conn).await;
+            (room, layout, pinned, next_message)
+        };
+
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        authz.allow(
+            agent.account_id(),
+            vec!["classrooms", &classroom_id],
+            "read",
+        );
+
+        let mut context = TestContext::new(db, authz);
+
+        let payload = BootstrapRequest {
+            id: room.id(),
+            payload: BootstrapPayload {
+                sets: vec![String::from("layout"), String::from("messages")],
+                position: 3000,
+                window: Some(10),
+            },
+        };
+
+        let messages = handle_request::<BootstrapHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Room bootstrap failed");
+
+        let (resp, respp, _) = find_response::<BootstrapResponse>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert_eq!(resp.room.id(), room.id());
+
+        // The state at the position doesn't include later events.
+        assert_eq!(resp.state["layout"]["id"], json!(layout.id()));
+        let messages = resp.state["messages"].as_array().expect("Expected array");
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0]["id"], json!(pinned.id()));
+
+        let ids = resp.events.iter().map(|e| e.id()).collect::<Vec<_>>();
+        assert_eq!(ids, vec![next_message.id()]);
+
+        let ids = resp
+            .pinned_events
+            .iter()
+            .map(|e| e.id())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![pinned.id()]);
+    }
+
+    #[tokio::test]
+    async fn bootstrap_room_not_authorized() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut context = TestContext::new(db, TestAuthz::new());
+
+        let payload = BootstrapRequest {
+            id: room.id(),
+            payload: BootstrapPayload::default(),
+        };
+
+        let err = handle_request::<BootstrapHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success bootstrapping room without authorization");
+
+        assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+    }
+}
@@ -1,22 +1,41 @@
 use async_trait::async_trait;
+use axum::{
+    body::{Bytes, StreamBody},
+    response::{IntoResponse, Response},
+};
 use chrono::Utc;
+use futures::stream;
+use http::header::CONTENT_TYPE;
 use serde_derive::{Deserialize, Serialize};
 use serde_json::{json, Value as JsonValue};
 use svc_agent::mqtt::{
     OutgoingEvent, OutgoingEventProperties, ResponseStatus, ShortTermTimingProperties,
 };
-use svc_error::Error as SvcError;
-use tracing::error;
+use tracing::{error, Instrument};
 use uuid::Uuid;
 
 use super::*;
 use crate::app::context::Context;
 use crate::app::message_handler::Message;
-use crate::app::operations::dump_events_to_s3;
+use crate::app::operations::{dump_events_to_s3, DumpDestination, DumpFormat, DumpLocation};
+use crate::db::event::{ListQuery as EventListQuery, Object as Event};
+use crate::db::room::Object as Room;
+use crate::db::task::Status as TaskStatus;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct EventsDumpPayload {
+    /// Dump to the destination instead of the default bucket.
+    #[serde(default)]
+    destination: Option<DumpDestination>,
+    #[serde(default)]
+    format: DumpFormat,
+}
 
 #[derive(Debug, Deserialize)]
 pub struct EventsDumpRequest {
     id: Uuid,
+    #[serde(flatten)]
+    payload: EventsDumpPayload,
 }
 
 #[derive(Serialize)]
@@ -30,8 +49,14 @@ struct EventsDumpNotification {
 #[derive(Serialize)]
 #[serde(untagged)]
 enum EventsDumpResult {
-    Success { room_id: Uuid, s3_uri: String },
-    Error { error: SvcError },
+    Success {
+        room_id: Uuid,
+        #[serde(flatten)]
+        location: DumpLocation,
+    },
+    Error {
+        error: ErrorPayload,
+    },
 }
 
 impl EventsDumpResult {
@@ -47,8 +72,22 @@ pub async fn dump_events(
     ctx: extract::Extension<Arc<AppContext>>,
     AgentIdExtractor(agent_id): AgentIdExtractor,
     Path(room_id): Path<Uuid>,
+    body: Bytes,
 ) -> RequestResult {
-    let request = EventsDumpRequest { id: room_id };
+    // The body is optional to dump to the default bucket.
+    let payload = if body.is_empty() {
+        EventsDumpPayload::default()
+    } else {
+        serde_json::from_slice(&body)
+            .context("Invalid payload")
+            .error(AppErrorKind::InvalidPayload)?
+    };
+
+    let request = EventsDumpRequest {
+        id: room_id,
+        payload,
+    };
+
     EventsDumpHandler::handle(
         &mut ctx.start_message(),
         request,
@@ -67,51 +106,101 @@ impl RequestHandler for EventsDumpHandler {
 
     async fn handle<C: Context>(
         context: &mut C,
-        payload: Self::Payload,
+        Self::Payload { id, payload }: Self::Payload,
         reqp: RequestParams<'_>,
     ) -> RequestResult {
-        let room =
-            helpers::find_room(context, payload.id, helpers::RoomTimeRequirement::Any).await?;
+        let room = helpers::find_room(context, id, helpers::RoomTimeRequirement::Any).await?;
 
         let object = AuthzObject::new(&["classrooms"]).into();
 
         // Authorize room.
         let authz_time = context
             .authz()
-            .authorize(
-                room.audience().to_owned(),
+            .authorize_room(
+                &room,
                 reqp.as_account_id().to_owned(),
                 object,
                 "dump_events".into(),
             )
             .await?;
 
-        let db = context.db().to_owned();
-        let metrics = context.metrics();
+        let destination = payload.destination;
+
+        if let Some(ref destination) = destination {
+            let allowed = context
+                .config()
+                .dump
+                .destinations
+                .get(room.audience())
+                .is_some_and(|allowed| destination.is_allowed(allowed));
+
+            if !allowed {
+                return Err(anyhow!(
+                    "Dump destination is not allowed for audience = '{}'",
+                    room.audience()
+                ))
+                .error(AppErrorKind::AccessDenied);
+            }
+        }
 
-        let s3_client = context
-            .s3_client()
-            .ok_or_else(|| {
-                error!("DumpEvents called with no s3client in context");
-                anyhow!("No S3Client")
-            })
-            .error(AppErrorKind::NoS3Client)?;
+        start_s3_dump(context, room, destination, payload.format, reqp, authz_time).await
+    }
+}
 
-        let notification_future = tokio::task::spawn(async move {
-            let result = dump_events_to_s3(&db, &metrics, s3_client, &room).await;
+/// Spawns the dump of `room` events to S3 or the `destination` and responds with 202.
+/// The result gets broadcasted as `room.dump_events` notification when finished.
+async fn start_s3_dump<C: Context>(
+    context: &mut C,
+    room: Room,
+    destination: Option<DumpDestination>,
+    format: DumpFormat,
+    reqp: RequestParams<'_>,
+    authz_time: chrono::Duration,
+) -> RequestResult {
+    let db = context.background_db().to_owned();
+    let metrics = context.metrics();
+    let data_encryption = context.data_encryption();
+    let s3_client = context.s3_client();
+
+    // Upload urls don't need S3 credentials.
+    if s3_client.is_none() && !matches!(destination, Some(DumpDestination::UploadUrl { .. })) {
+        error!("DumpEvents called with no s3client in context");
+        return Err(anyhow!("No S3Client")).error(AppErrorKind::NoS3Client);
+    }
+
+    let task = helpers::start_task(context, "room.dump_events", &room, reqp.as_agent_id()).await?;
+    let task_id = task.id();
+    let jobs = context.jobs();
+    let job_id = jobs.start("dump_events", Some(room.id()));
+    let span = helpers::operation_span("dump_events", &room, Some(reqp.as_agent_id()));
+    let path = context.config().audience_topic(room.audience());
+
+    let notification_future = tokio::task::spawn(
+        async move {
+            let result = dump_events_to_s3(
+                &db,
+                &metrics,
+                data_encryption.as_deref(),
+                s3_client,
+                &room,
+                destination,
+                format,
+            )
+            .await;
+            jobs.finish(job_id, &result);
 
             // Handle result.
             let result = match result {
-                Ok(s3_uri) => EventsDumpResult::Success {
+                Ok(location) => EventsDumpResult::Success {
                     room_id: room.id(),
-                    s3_uri,
+                    location,
                 },
                 Err(err) => {
                     error!("Events dump job failed: {:?}", err);
                     let app_error = AppError::new(AppErrorKind::EditionCommitTaskFailed, err);
                     app_error.notify_sentry();
                     EventsDumpResult::Error {
-                        error: app_error.to_svc_error(),
+                        error: app_error.to_payload(),
                     }
                 }
             };
@@ -123,25 +212,123 @@ impl RequestHandler for EventsDumpHandler {
                 result,
             };
 
+            let task_status = match notification.result {
+                EventsDumpResult::Success { .. } => TaskStatus::Succeeded,
+                EventsDumpResult::Error { .. } => TaskStatus::Failed,
+            };
+
+            helpers::finish_task(&db, &metrics, task_id, task_status, &notification).await;
+
             let timing = ShortTermTimingProperties::new(Utc::now());
             let props = OutgoingEventProperties::new("room.dump_events", timing);
-            let path = format!("audiences/{}/events", room.audience());
             let event = OutgoingEvent::broadcast(notification, props, &path);
 
             Box::new(event) as Message
-        });
+        }
+        .instrument(span),
+    );
 
-        let mut response = AppResponse::new(
-            ResponseStatus::ACCEPTED,
-            json!({}),
-            context.start_timestamp(),
-            Some(authz_time),
-        );
+    let mut response = AppResponse::new(
+        ResponseStatus::ACCEPTED,
+        json!({ "task_id": task_id }),
+        context.start_timestamp(),
+        Some(authz_time),
+    );
+
+    response.add_async_task(notification_future);
+
+    Ok(response)
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Small rooms get dumped right away while large ones go to S3.
+enum RoomDump {
+    Events(Vec<Event>),
+    S3(AppResponse),
+}
+
+/// Responds with room events as NDJSON ordered by `occurred_at` if there are no more than
+/// `dump.http_max_events` of them. Otherwise starts dumping them to S3 like `dump_events`.
+pub async fn dump(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<Uuid>,
+) -> Result<Response, AppError> {
+    let reqp = RequestParams::Http {
+        agent_id: &agent_id,
+    };
+
+    match dump_room(&mut ctx.start_message(), room_id, reqp).await? {
+        RoomDump::Events(events) => {
+            let lines = stream::iter(events.into_iter().map(|event| {
+                serde_json::to_vec(&event).map(|mut line| {
+                    line.push(b'\n');
+                    line
+                })
+            }));
+
+            Ok((
+                [(CONTENT_TYPE, "application/x-ndjson")],
+                StreamBody::new(lines),
+            )
+                .into_response())
+        }
+        RoomDump::S3(response) => Ok(response.into_response()),
+    }
+}
 
-        response.add_async_task(notification_future);
+async fn dump_room<C: Context>(
+    context: &mut C,
+    room_id: Uuid,
+    reqp: RequestParams<'_>,
+) -> Result<RoomDump, AppError> {
+    let room = helpers::find_room(context, room_id, helpers::RoomTimeRequirement::Any).await?;
+
+    let object = AuthzObject::new(&["classrooms"]).into();
+
+    let authz_time = context
+        .authz()
+        .authorize_room(
+            &room,
+            reqp.as_account_id().to_owned(),
+            object,
+            "dump_events".into(),
+        )
+        .await?;
+
+    let max_events = context.config().dump.http_max_events;
+
+    // Fetch one extra event to find out whether the room is too large.
+    let mut events = {
+        let query = EventListQuery::new()
+            .room_id(room.id())
+            .limit(max_events + 1);
+
+        let mut conn = context.get_ro_conn().await?;
+
+        context
+            .metrics()
+            .measure_query(QueryKey::EventDumpQuery, query.execute(&mut conn))
+            .await
+            .context("Failed to fetch room events")
+            .error(AppErrorKind::DbQueryFailed)?
+    };
+
+    if events.len() > max_events {
+        return start_s3_dump(context, room, None, DumpFormat::Json, reqp, authz_time)
+            .await
+            .map(RoomDump::S3);
+    }
 
-        Ok(response)
+    // Encrypted kinds are left out unless they are allowed to be dumped.
+    if let Some(data_encryption) = context.data_encryption() {
+        events = data_encryption
+            .open_dumpable(events)
+            .error(AppErrorKind::InternalServerError)?;
     }
+
+    Ok(RoomDump::Events(events))
 }
 
 #[cfg(test)]
@@ -161,7 +348,10 @@ mod tests {
 
         let mut context = TestContext::new(db, TestAuthz::new());
 
-        let payload = EventsDumpRequest { id: room.id() };
+        let payload = EventsDumpRequest {
+            id: room.id(),
+            payload: EventsDumpPayload::default(),
+        };
 
         let err = handle_request::<EventsDumpHandler>(&mut context, &agent, payload)
             .await
@@ -175,7 +365,10 @@ mod tests {
         let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
         let mut context = TestContext::new(TestDb::new().await, TestAuthz::new());
 
-        let payload = EventsDumpRequest { id: Uuid::new_v4() };
+        let payload = EventsDumpRequest {
+            id: Uuid::new_v4(),
+            payload: EventsDumpPayload::default(),
+        };
 
         let err = handle_request::<EventsDumpHandler>(&mut context, &agent, payload)
             .await
@@ -199,7 +392,10 @@ mod tests {
 
         let mut context = TestContext::new(TestDb::new().await, authz);
 
-        let payload = EventsDumpRequest { id: room.id() };
+        let payload = EventsDumpRequest {
+            id: room.id(),
+            payload: EventsDumpPayload::default(),
+        };
 
         let err = handle_request::<EventsDumpHandler>(&mut context, &agent, payload)
             .await
@@ -224,14 +420,17 @@ mod tests {
         let mut context = TestContext::new(TestDb::new().await, authz);
         context.set_s3(shared_helpers::mock_s3());
 
-        let payload = EventsDumpRequest { id: room.id() };
+        let payload = EventsDumpRequest {
+            id: room.id(),
+            payload: EventsDumpPayload::default(),
+        };
 
         let messages = handle_request::<EventsDumpHandler>(&mut context, &agent, payload)
             .await
             .expect("Failed to dump room events");
 
         assert_eq!(messages.len(), 2);
-        let (_, respp, _) = find_response::<JsonValue>(messages.as_slice());
+        let (resp, respp, _) = find_response::<JsonValue>(messages.as_slice());
         let (ev, evp, _) = find_event::<JsonValue>(messages.as_slice());
         assert_eq!(respp.status(), ResponseStatus::ACCEPTED);
         assert_eq!(evp.label(), "room.dump_events");
@@ -253,5 +452,175 @@ mod tests {
             ))
             .as_deref()
         );
+
+        // The notification is saved to be read later.
+        let task_id = resp["task_id"]
+            .as_str()
+            .and_then(|id| id.parse().ok())
+            .expect("Missing task id");
+
+        let mut conn = context.db().acquire().await.expect("Failed to get conn");
+
+        let task = crate::db::task::FindQuery::new(task_id)
+            .execute(&mut conn)
+            .await
+            .expect("Failed to find task")
+            .expect("Task not found");
+
+        assert_eq!(task.status(), TaskStatus::Succeeded);
+        assert_eq!(task.notification(), Some(&ev));
+    }
+
+    #[tokio::test]
+    async fn dump_events_to_destination() {
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let db = TestDb::new().await;
+        let mut authz = TestAuthz::new();
+        authz.allow(agent.account_id(), vec!["classrooms"], "dump_events");
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut context = TestContext::new(db, authz);
+        context.set_s3(shared_helpers::mock_s3());
+
+        let build_payload = |format| EventsDumpRequest {
+            id: room.id(),
+            payload: EventsDumpPayload {
+                destination: Some(DumpDestination::S3 {
+                    bucket: "tenant-exports".to_owned(),
+                    prefix: Some("rooms/".to_owned()),
+                }),
+                format,
+            },
+        };
+
+        // The bucket is not allowed yet.
+        let payload = build_payload(DumpFormat::Json);
+
+        let err = handle_request::<EventsDumpHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success on room dump");
+
+        assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+
+        context.update_config(|config| {
+            let allowed = config
+                .dump
+                .destinations
+                .entry(USR_AUDIENCE.to_owned())
+                .or_default();
+
+            allowed.buckets.push("tenant-exports".to_owned());
+        });
+
+        let payload = build_payload(DumpFormat::Json);
+
+        let messages = handle_request::<EventsDumpHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Failed to dump room events");
+
+        let (ev, _, _) = find_event::<JsonValue>(messages.as_slice());
+        assert_eq!(
+            ev["result"]["s3_uri"],
+            format!("s3://tenant-exports/rooms/{}.json", room.id())
+        );
+
+        // Binary snapshot.
+        let payload = build_payload(DumpFormat::Binary);
+
+        let messages = handle_request::<EventsDumpHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Failed to dump room events");
+
+        let (ev, _, _) = find_event::<JsonValue>(messages.as_slice());
+        assert_eq!(
+            ev["result"]["s3_uri"],
+            format!("s3://tenant-exports/rooms/{}.bin", room.id())
+        );
+    }
+
+    #[tokio::test]
+    async fn dump_small_room() {
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let db = TestDb::new().await;
+        let mut authz = TestAuthz::new();
+        authz.allow(agent.account_id(), vec!["classrooms"], "dump_events");
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+
+            for (occurred_at, message) in [(2000, "m2"), (1000, "m1")] {
+                factory::Event::new()
+                    .room_id(room.id())
+                    .kind("message")
+                    .data(&json!({ "message": message }))
+                    .occurred_at(occurred_at)
+                    .created_by(agent.agent_id())
+                    .insert(&mut conn)
+                    .await;
+            }
+
+            room
+        };
+
+        let mut context = TestContext::new(db, authz);
+
+        let reqp = RequestParams::Http {
+            agent_id: agent.agent_id(),
+        };
+
+        let events = match dump_room(&mut context, room.id(), reqp).await {
+            Ok(RoomDump::Events(events)) => events,
+            Ok(RoomDump::S3(_)) => panic!("Unexpected dump to S3"),
+            Err(err) => panic!("Failed to dump room events: {:?}", err),
+        };
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].data()["message"], "m1");
+        assert_eq!(events[1].data()["message"], "m2");
+    }
+
+    #[tokio::test]
+    async fn dump_large_room_to_s3() {
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let db = TestDb::new().await;
+        let mut authz = TestAuthz::new();
+        authz.allow(agent.account_id(), vec!["classrooms"], "dump_events");
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+
+            for occurred_at in [1000, 2000] {
+                factory::Event::new()
+                    .room_id(room.id())
+                    .kind("message")
+                    .data(&json!({ "message": "text" }))
+                    .occurred_at(occurred_at)
+                    .created_by(agent.agent_id())
+                    .insert(&mut conn)
+                    .await;
+            }
+
+            room
+        };
+
+        let mut context = TestContext::new(db, authz);
+        context.update_config(|config| config.dump.http_max_events = 1);
+        context.set_s3(shared_helpers::mock_s3());
+
+        let reqp = RequestParams::Http {
+            agent_id: agent.agent_id(),
+        };
+
+        match dump_room(&mut context, room.id(), reqp).await {
+            Ok(RoomDump::S3(_)) => (),
+            Ok(RoomDump::Events(_)) => panic!("Unexpected dump over HTTP"),
+            Err(err) => panic!("Failed to dump room events: {:?}", err),
+        }
     }
 }
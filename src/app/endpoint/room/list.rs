@@ -0,0 +1,264 @@
+use std::ops::Bound;
+
+use async_trait::async_trait;
+use axum::extract::Query;
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+use svc_agent::mqtt::ResponseStatus;
+use uuid::Uuid;
+
+use super::*;
+use crate::app::context::Context;
+use crate::db;
+
+const MAX_LIMIT: usize = 100;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ListPayload {
+    classroom_id: Option<Uuid>,
+    open: Option<bool>,
+    closed: Option<bool>,
+    /// Lists rooms created at or after the time.
+    #[serde(default, with = "chrono::serde::ts_seconds_option")]
+    created_from: Option<DateTime<Utc>>,
+    /// Lists rooms created before the time.
+    #[serde(default, with = "chrono::serde::ts_seconds_option")]
+    created_to: Option<DateTime<Utc>>,
+    cursor: Option<Uuid>,
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListRequest {
+    audience: String,
+    #[serde(flatten)]
+    payload: ListPayload,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ListResponse {
+    rooms: Vec<Room>,
+    /// Id of the last room to pass as `cursor` for the next page.
+    next_cursor: Option<Uuid>,
+}
+
+pub async fn list(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(audience): Path<String>,
+    Query(payload): Query<ListPayload>,
+) -> RequestResult {
+    let request = ListRequest { audience, payload };
+    ListHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct ListHandler;
+
+#[async_trait]
+impl RequestHandler for ListHandler {
+    type Payload = ListRequest;
+
+    #[instrument(skip_all, fields(audience))]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload { audience, payload }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let object = AuthzObject::new(&["classrooms"]).into();
+
+        let authz_time = context
+            .authz()
+            .authorize(
+                audience.clone(),
+                reqp.as_account_id().to_owned(),
+                object,
+                "list".into(),
+            )
+            .await?;
+
+        let limit = std::cmp::min(payload.limit.unwrap_or(MAX_LIMIT), MAX_LIMIT);
+
+        let rooms = {
+            let mut query = db::room::ListQuery::new(audience, 0, limit);
+
+            if let Some(classroom_id) = payload.classroom_id {
+                query = query.classroom_id(classroom_id);
+            }
+
+            if let Some(open) = payload.open {
+                query = query.open(open);
+            }
+
+            if let Some(closed) = payload.closed {
+                query = query.closed(closed);
+            }
+
+            if payload.created_from.is_some() || payload.created_to.is_some() {
+                let created_at = (
+                    payload
+                        .created_from
+                        .map_or(Bound::Unbounded, Bound::Included),
+                    payload.created_to.map_or(Bound::Unbounded, Bound::Excluded),
+                );
+
+                query = query.created_at(created_at);
+            }
+
+            if let Some(cursor) = payload.cursor {
+                query = query.cursor(cursor);
+            }
+
+            let mut conn = context.get_ro_conn().await?;
+
+            context
+                .metrics()
+                .measure_query(QueryKey::RoomListQuery, query.execute(&mut conn))
+                .await
+                .context("Failed to list rooms")
+                .error(AppErrorKind::DbQueryFailed)?
+        };
+
+        let next_cursor = match rooms.last() {
+            Some(room) if rooms.len() >= limit => Some(room.id()),
+            _ => None,
+        };
+
+        Ok(AppResponse::new(
+            ResponseStatus::OK,
+            ListResponse { rooms, next_cursor },
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+    use crate::test_helpers::prelude::*;
+
+    fn list_request(classroom_id: Uuid) -> ListRequest {
+        ListRequest {
+            audience: USR_AUDIENCE.to_owned(),
+            payload: ListPayload {
+                classroom_id: Some(classroom_id),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn list_rooms() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let classroom_id = Uuid::new_v4();
+        let now = Utc::now();
+
+        let (open_room, closed_room, upcoming_room) = {
+            let mut conn = db.get_conn().await;
+
+            let open_room = factory::Room::new(classroom_id, ClassType::Webinar)
+                .audience(USR_AUDIENCE)
+                .time((Bound::Included(now - Duration::hours(1)), Bound::Unbounded))
+                .insert(&mut conn)
+                .await;
+
+            let closed_room = factory::Room::new(classroom_id, ClassType::Webinar)
+                .audience(USR_AUDIENCE)
+                .time((
+                    Bound::Included(now - Duration::hours(2)),
+                    Bound::Excluded(now - Duration::hours(1)),
+                ))
+                .insert(&mut conn)
+                .await;
+
+            let upcoming_room = factory::Room::new(classroom_id, ClassType::Webinar)
+                .audience(USR_AUDIENCE)
+                .time((Bound::Included(now + Duration::hours(1)), Bound::Unbounded))
+                .insert(&mut conn)
+                .await;
+
+            (open_room, closed_room, upcoming_room)
+        };
+
+        let mut authz = TestAuthz::new();
+        authz.allow(agent.account_id(), vec!["classrooms"], "list");
+
+        let mut context = TestContext::new(db, authz);
+
+        // The first page.
+        let mut payload = list_request(classroom_id);
+        payload.payload.limit = Some(2);
+
+        let messages = handle_request::<ListHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Rooms listing failed");
+
+        let (resp, respp, _) = find_response::<ListResponse>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+
+        let ids = resp.rooms.iter().map(|r| r.id()).collect::<Vec<_>>();
+        assert_eq!(ids, vec![upcoming_room.id(), closed_room.id()]);
+        assert_eq!(resp.next_cursor, Some(closed_room.id()));
+
+        // The last page.
+        let mut payload = list_request(classroom_id);
+        payload.payload.limit = Some(2);
+        payload.payload.cursor = resp.next_cursor;
+
+        let messages = handle_request::<ListHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Rooms listing failed");
+
+        let (resp, _, _) = find_response::<ListResponse>(messages.as_slice());
+        let ids = resp.rooms.iter().map(|r| r.id()).collect::<Vec<_>>();
+        assert_eq!(ids, vec![open_room.id()]);
+        assert_eq!(resp.next_cursor, None);
+
+        // Closed rooms only.
+        let mut payload = list_request(classroom_id);
+        payload.payload.closed = Some(true);
+
+        let messages = handle_request::<ListHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Rooms listing failed");
+
+        let (resp, _, _) = find_response::<ListResponse>(messages.as_slice());
+        let ids = resp.rooms.iter().map(|r| r.id()).collect::<Vec<_>>();
+        assert_eq!(ids, vec![closed_room.id()]);
+
+        // Open rooms only.
+        let mut payload = list_request(classroom_id);
+        payload.payload.open = Some(true);
+
+        let messages = handle_request::<ListHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Rooms listing failed");
+
+        let (resp, _, _) = find_response::<ListResponse>(messages.as_slice());
+        let ids = resp.rooms.iter().map(|r| r.id()).collect::<Vec<_>>();
+        assert_eq!(ids, vec![open_room.id()]);
+    }
+
+    #[tokio::test]
+    async fn list_rooms_not_authorized() {
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let mut context = TestContext::new(TestDb::new().await, TestAuthz::new());
+        let payload = list_request(Uuid::new_v4());
+
+        let err = handle_request::<ListHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success listing rooms without authorization");
+
+        assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+    }
+}
@@ -0,0 +1,324 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use svc_agent::{mqtt::ResponseStatus, AgentId};
+use tracing::{field::display, instrument, Span};
+use uuid::Uuid;
+
+use super::*;
+use crate::app::context::Context;
+use crate::db;
+
+#[derive(Debug, Deserialize)]
+pub struct NotifyPayload {
+    #[serde(rename = "type")]
+    kind: String,
+    data: JsonValue,
+    #[serde(default)]
+    persist: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NotifyRequest {
+    id: Uuid,
+    #[serde(flatten)]
+    payload: NotifyPayload,
+}
+
+#[derive(Clone, Serialize)]
+struct RoomNotification {
+    room_id: Uuid,
+    #[serde(rename = "type")]
+    kind: String,
+    data: JsonValue,
+    created_by: AgentId,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    event_id: Option<Uuid>,
+}
+
+pub async fn notify(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<Uuid>,
+    Json(payload): Json<NotifyPayload>,
+) -> RequestResult {
+    let request = NotifyRequest {
+        id: room_id,
+        payload,
+    };
+    NotifyHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct NotifyHandler;
+
+#[async_trait]
+impl RequestHandler for NotifyHandler {
+    type Payload = NotifyRequest;
+
+    #[instrument(skip_all, fields(room_id, scope, classroom_id))]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload { id, payload }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let room = helpers::find_room(context, id, helpers::RoomTimeRequirement::Open).await?;
+
+        // Only those who can update the room are allowed to speak on behalf of it.
+        let object = AuthzObject::room(&room).into();
+
+        let authz_time = context
+            .authz()
+            .authorize_room(
+                &room,
+                reqp.as_account_id().to_owned(),
+                object,
+                "update".into(),
+            )
+            .await?;
+
+        let NotifyPayload {
+            kind,
+            data,
+            persist,
+        } = payload;
+
+        if data.to_string().len() >= context.config().constraint.payload_size {
+            return Err(anyhow!("Payload size exceeded")).error(AppErrorKind::PayloadSizeExceeded);
+        }
+
+        // Optionally keep the notification in the room history as an ordinary event.
+        let event_id = if persist {
+            let occurred_at = match room.time().map(|t| t.start().to_owned()) {
+                Ok(opened_at) => (Utc::now() - opened_at)
+                    .num_nanoseconds()
+                    .unwrap_or(i64::MAX),
+                _ => {
+                    return Err(anyhow!("Invalid room time")).error(AppErrorKind::InvalidRoomTime);
+                }
+            };
+
+            let query = db::event::InsertQuery::new(
+                room.id(),
+                kind.clone(),
+                data.clone(),
+                occurred_at,
+                reqp.as_agent_id().to_owned(),
+                context.data_encryption().as_deref(),
+            )
+            .map_err(|err| helpers::invalid_event(context, err))?;
+
+            let mut conn = context.get_conn().await?;
+
+            let event = context
+                .metrics()
+                .measure_query(QueryKey::EventInsertQuery, query.execute(&mut conn))
+                .await
+                .context("Failed to insert event")
+                .error(AppErrorKind::DbQueryFailed)?;
+
+            Span::current().record("event_id", display(event.id()));
+            Some(event.id())
+        } else {
+            None
+        };
+
+        let notification = RoomNotification {
+            room_id: room.id(),
+            kind,
+            data,
+            created_by: reqp.as_agent_id().to_owned(),
+            event_id,
+        };
+
+        let mut response = AppResponse::new(
+            ResponseStatus::OK,
+            notification.clone(),
+            context.start_timestamp(),
+            Some(authz_time),
+        );
+
+        response.add_notification(
+            "room.notify",
+            &context.config().room_topic(room.audience(), room.id()),
+            notification,
+            context.start_timestamp(),
+        )?;
+
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_helpers::prelude::*;
+
+    fn allow_update(authz: &mut TestAuthz, agent: &TestAgent, room: &db::room::Object) {
+        authz.allow(
+            agent.account_id(),
+            vec!["classrooms", &room.classroom_id().to_string()],
+            "update",
+        );
+    }
+
+    #[tokio::test]
+    async fn notify() {
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let db = TestDb::new().await;
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut authz = TestAuthz::new();
+        allow_update(&mut authz, &agent, &room);
+        let mut context = TestContext::new(db.clone(), authz);
+
+        let payload = NotifyRequest {
+            id: room.id(),
+            payload: NotifyPayload {
+                kind: "lesson_reminder".to_owned(),
+                data: json!({ "text": "5 minutes left" }),
+                persist: false,
+            },
+        };
+
+        let messages = handle_request::<NotifyHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Room notification failed");
+
+        let (_, respp, _) = find_response::<JsonValue>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+
+        let (notification, evp, topic) = find_event::<JsonValue>(messages.as_slice());
+        assert!(topic.ends_with(&format!("/rooms/{}/events", room.id())));
+        assert_eq!(evp.label(), "room.notify");
+        assert_eq!(notification["type"], "lesson_reminder");
+        assert_eq!(notification["data"], json!({ "text": "5 minutes left" }));
+        assert!(notification.get("event_id").is_none());
+
+        // Nothing is stored.
+        let mut conn = db.get_conn().await;
+
+        let events = db::event::ListQuery::new()
+            .room_id(room.id())
+            .execute(&mut conn)
+            .await
+            .expect("Failed to list events");
+
+        assert!(events.is_empty());
+    }
+
+    #[tokio::test]
+    async fn notify_persist() {
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let db = TestDb::new().await;
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut authz = TestAuthz::new();
+        allow_update(&mut authz, &agent, &room);
+        let mut context = TestContext::new(db.clone(), authz);
+
+        let payload = NotifyRequest {
+            id: room.id(),
+            payload: NotifyPayload {
+                kind: "lesson_reminder".to_owned(),
+                data: json!({ "text": "5 minutes left" }),
+                persist: true,
+            },
+        };
+
+        let messages = handle_request::<NotifyHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Room notification failed");
+
+        let (notification, _, _) = find_event::<JsonValue>(messages.as_slice());
+
+        let mut conn = db.get_conn().await;
+
+        let events = db::event::ListQuery::new()
+            .room_id(room.id())
+            .execute(&mut conn)
+            .await
+            .expect("Failed to list events");
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind(), "lesson_reminder");
+        assert_eq!(events[0].data(), &json!({ "text": "5 minutes left" }));
+        assert_eq!(notification["event_id"], events[0].id().to_string());
+    }
+
+    #[tokio::test]
+    async fn notify_payload_size_exceeded() {
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let db = TestDb::new().await;
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut authz = TestAuthz::new();
+        allow_update(&mut authz, &agent, &room);
+        let mut context = TestContext::new_with_payload_size(db, authz, 10);
+
+        let payload = NotifyRequest {
+            id: room.id(),
+            payload: NotifyPayload {
+                kind: "lesson_reminder".to_owned(),
+                data: json!({ "text": "5 minutes left" }),
+                persist: false,
+            },
+        };
+
+        let err = handle_request::<NotifyHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success on room notification");
+
+        assert_eq!(err.status(), ResponseStatus::UNPROCESSABLE_ENTITY);
+        assert_eq!(err.kind(), "payload_size_exceeded");
+    }
+
+    #[tokio::test]
+    async fn notify_not_authorized() {
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let db = TestDb::new().await;
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut context = TestContext::new(db, TestAuthz::new());
+
+        let payload = NotifyRequest {
+            id: room.id(),
+            payload: NotifyPayload {
+                kind: "lesson_reminder".to_owned(),
+                data: json!({}),
+                persist: false,
+            },
+        };
+
+        let err = handle_request::<NotifyHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success on room notification");
+
+        assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+    }
+}
@@ -0,0 +1,171 @@
+use async_trait::async_trait;
+use serde_derive::{Deserialize, Serialize};
+use svc_agent::mqtt::ResponseStatus;
+use uuid::Uuid;
+
+use super::*;
+use crate::app::context::Context;
+use crate::app::operations::RoomOperationLock;
+
+#[derive(Debug, Deserialize)]
+pub struct OperationStatusRequest {
+    id: Uuid,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct OperationStatusResponse {
+    in_progress: bool,
+}
+
+pub async fn operation_status(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<Uuid>,
+) -> RequestResult {
+    let request = OperationStatusRequest { id: room_id };
+    OperationStatusHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct OperationStatusHandler;
+
+#[async_trait]
+impl RequestHandler for OperationStatusHandler {
+    type Payload = OperationStatusRequest;
+
+    #[instrument(
+        skip_all,
+        fields(
+            room_id = %payload.id, scope, classroom_id
+        )
+    )]
+    async fn handle<C: Context>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let room =
+            helpers::find_room(context, payload.id, helpers::RoomTimeRequirement::Any).await?;
+
+        // The status is available to those who can adjust the room.
+        let object = AuthzObject::room(&room).into();
+
+        let authz_time = context
+            .authz()
+            .authorize_room(
+                &room,
+                reqp.as_account_id().to_owned(),
+                object,
+                "update".into(),
+            )
+            .await?;
+
+        let in_progress = RoomOperationLock::is_held(context.background_db(), room.id())
+            .await
+            .context("Failed to check room lock")
+            .error(AppErrorKind::DbQueryFailed)?;
+
+        Ok(AppResponse::new(
+            ResponseStatus::OK,
+            OperationStatusResponse { in_progress },
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::prelude::*;
+
+    #[tokio::test]
+    async fn operation_status() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut authz = TestAuthz::new();
+        authz.allow(
+            agent.account_id(),
+            vec!["classrooms", &room.classroom_id().to_string()],
+            "update",
+        );
+
+        let mut context = TestContext::new(db, authz);
+
+        let payload = OperationStatusRequest { id: room.id() };
+
+        let messages = handle_request::<OperationStatusHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Room operation status failed");
+
+        let (resp, respp, _) = find_response::<OperationStatusResponse>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert!(!resp.in_progress);
+
+        // Lock the room as if it's being adjusted.
+        let lock = RoomOperationLock::try_acquire(context.background_db(), room.id())
+            .await
+            .expect("Failed to lock room")
+            .expect("Room is already locked");
+
+        let payload = OperationStatusRequest { id: room.id() };
+
+        let messages = handle_request::<OperationStatusHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Room operation status failed");
+
+        let (resp, _, _) = find_response::<OperationStatusResponse>(messages.as_slice());
+        assert!(resp.in_progress);
+
+        // Another operation can't be started meanwhile.
+        let payload = AdjustRequest {
+            id: room.id(),
+            payload: AdjustPayload {
+                started_at: Utc::now(),
+                segments: vec![(Bound::Included(0), Bound::Excluded(1000))].into(),
+                offset: 0,
+            },
+        };
+
+        let err = handle_request::<AdjustHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success on room adjustment");
+
+        assert_eq!(err.status(), ResponseStatus::CONFLICT);
+        assert_eq!(err.kind(), "operation_in_progress");
+
+        lock.release().await.expect("Failed to release room lock");
+    }
+
+    #[tokio::test]
+    async fn operation_status_not_authorized() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut context = TestContext::new(db, TestAuthz::new());
+        let payload = OperationStatusRequest { id: room.id() };
+
+        let err = handle_request::<OperationStatusHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success on room operation status");
+
+        assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+    }
+}
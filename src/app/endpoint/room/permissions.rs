@@ -0,0 +1,324 @@
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+use axum::extract::RawQuery;
+use serde_derive::{Deserialize, Serialize};
+use svc_agent::mqtt::ResponseStatus;
+use uuid::Uuid;
+
+use super::*;
+use crate::app::context::Context;
+use crate::app::endpoint::authz::is_banned;
+use crate::app::endpoint::event::authorize_event;
+
+/// Kinds evaluated when the request doesn't list any, along with the locked ones.
+const DEFAULT_KINDS: &[&str] = &["message", "draw"];
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PermissionsPayload {
+    /// Event kinds to evaluate creation of.
+    #[serde(default)]
+    kinds: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PermissionsRequest {
+    id: Uuid,
+    #[serde(flatten)]
+    payload: PermissionsPayload,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Permissions {
+    room_id: Uuid,
+    /// Banned in the classroom or in the whole audience.
+    banned: bool,
+    /// May update the room, its locked types and whiteboard access.
+    update: bool,
+    /// May list bans of the room.
+    list_bans: bool,
+    /// May create events of each kind as their author.
+    create_events: BTreeMap<String, bool>,
+    /// Persistent events created await moderator's approval.
+    moderated: bool,
+}
+
+pub async fn permissions(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<Uuid>,
+    RawQuery(query): RawQuery,
+) -> RequestResult {
+    let payload = serde_qs::from_str(&query.unwrap_or_default())
+        .context("Failed to parse qs")
+        .error(AppErrorKind::InvalidQueryString)?;
+
+    let request = PermissionsRequest {
+        id: room_id,
+        payload,
+    };
+
+    PermissionsHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct PermissionsHandler;
+
+#[async_trait]
+impl RequestHandler for PermissionsHandler {
+    type Payload = PermissionsRequest;
+
+    #[instrument(skip_all, fields(room_id, scope, classroom_id))]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload { id, payload }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let room = helpers::find_room(context, id, helpers::RoomTimeRequirement::Any).await?;
+
+        let classroom_id = room.classroom_id().to_string();
+        let object = AuthzObject::new(&["classrooms", &classroom_id]);
+        let mut authz_time = helpers::authorize_room(context, &room, reqp, object, "read").await?;
+
+        let banned = {
+            let mut conn = context.get_ro_conn().await?;
+
+            context
+                .metrics()
+                .measure_query(
+                    QueryKey::BanFindQuery,
+                    is_banned(&mut conn, reqp.as_account_id(), room.classroom_id()),
+                )
+                .await
+                .context("Failed to check ban")
+                .error(AppErrorKind::DbQueryFailed)?
+        };
+
+        let update = {
+            let object = AuthzObject::room(&room);
+            let result = helpers::authorize_room(context, &room, reqp, object, "update").await;
+            allowed(result, &mut authz_time)?
+        };
+
+        let mut kinds = payload.kinds;
+
+        if kinds.is_empty() {
+            kinds.extend(DEFAULT_KINDS.iter().map(|kind| kind.to_string()));
+
+            kinds.extend(
+                room.locked_types()
+                    .iter()
+                    .filter(|(_, locked)| **locked)
+                    .map(|(kind, _)| kind.to_owned()),
+            );
+        }
+
+        let mut create_events = BTreeMap::new();
+
+        for kind in kinds {
+            if create_events.contains_key(&kind) {
+                continue;
+            }
+
+            // Events are created only in open rooms and by those not banned.
+            let is_allowed = if room.is_open() && !banned {
+                let author = reqp.as_account_id().to_string();
+                let result =
+                    authorize_event(context, &room, &kind, &author, &["events"], reqp).await;
+
+                allowed(result, &mut authz_time)?
+            } else {
+                false
+            };
+
+            create_events.insert(kind, is_allowed);
+        }
+
+        let permissions = Permissions {
+            room_id: room.id(),
+            banned,
+            update,
+            // Bans are listed only in open rooms.
+            list_bans: update && room.is_open(),
+            create_events,
+            moderated: room.moderated() && !update,
+        };
+
+        Ok(AppResponse::new(
+            ResponseStatus::OK,
+            permissions,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+/// Turns access denial into `false` keeping other authorization errors.
+fn allowed(
+    result: Result<chrono::Duration, AppError>,
+    authz_time: &mut chrono::Duration,
+) -> Result<bool, AppError> {
+    match result {
+        Ok(duration) => {
+            *authz_time = *authz_time + duration;
+            Ok(true)
+        }
+        Err(err) if err.error_kind() == AppErrorKind::AccessDenied => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::test_helpers::prelude::*;
+
+    #[tokio::test]
+    async fn permissions_of_user() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+
+            let mut locked_types = HashMap::new();
+            locked_types.insert("message".to_owned(), true);
+
+            UpdateQuery::new(room.id())
+                .locked_types(locked_types)
+                .execute(&mut conn)
+                .await
+                .expect("Failed to lock message type")
+        };
+
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        let account_id = agent.account_id().to_string();
+        authz.allow(
+            agent.account_id(),
+            vec!["classrooms", &classroom_id],
+            "read",
+        );
+
+        for kind in ["message", "draw"] {
+            authz.allow(
+                agent.account_id(),
+                vec![
+                    "classrooms",
+                    &classroom_id,
+                    "events",
+                    kind,
+                    "authors",
+                    &account_id,
+                ],
+                "create",
+            );
+        }
+
+        let mut context = TestContext::new(db, authz);
+
+        let payload = PermissionsRequest {
+            id: room.id(),
+            payload: PermissionsPayload {
+                kinds: vec!["message".into(), "draw".into(), "poll".into()],
+            },
+        };
+
+        let messages = handle_request::<PermissionsHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Room permissions failed");
+
+        let (resp, respp, _) = find_response::<Permissions>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert!(!resp.banned);
+        assert!(!resp.update);
+        assert!(!resp.list_bans);
+
+        // Locked messages require room update.
+        assert_eq!(resp.create_events.get("message"), Some(&false));
+        assert_eq!(resp.create_events.get("draw"), Some(&true));
+        assert_eq!(resp.create_events.get("poll"), Some(&false));
+    }
+
+    #[tokio::test]
+    async fn permissions_of_banned_user() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_unbounded_room(&mut conn).await;
+
+            factory::RoomBan::new(agent.agent_id(), room.id())
+                .insert(&mut conn)
+                .await;
+
+            room
+        };
+
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        authz.allow(
+            agent.account_id(),
+            vec!["classrooms", &classroom_id],
+            "read",
+        );
+        authz.allow(
+            agent.account_id(),
+            vec!["classrooms", &classroom_id],
+            "update",
+        );
+
+        let mut context = TestContext::new(db, authz);
+
+        let payload = PermissionsRequest {
+            id: room.id(),
+            payload: Default::default(),
+        };
+
+        let messages = handle_request::<PermissionsHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Room permissions failed");
+
+        let (resp, _, _) = find_response::<Permissions>(messages.as_slice());
+        assert!(resp.banned);
+        assert!(resp.update);
+        assert!(resp.list_bans);
+        assert!(!resp.moderated);
+        assert_eq!(resp.create_events.get("message"), Some(&false));
+        assert_eq!(resp.create_events.get("draw"), Some(&false));
+    }
+
+    #[tokio::test]
+    async fn permissions_not_authorized() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut context = TestContext::new(db, TestAuthz::new());
+
+        let payload = PermissionsRequest {
+            id: room.id(),
+            payload: Default::default(),
+        };
+
+        let err = handle_request::<PermissionsHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success on room permissions");
+
+        assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+    }
+}
@@ -0,0 +1,146 @@
+use async_trait::async_trait;
+use serde_derive::{Deserialize, Serialize};
+use svc_agent::mqtt::ResponseStatus;
+use uuid::Uuid;
+
+use super::*;
+use crate::app::context::Context;
+
+#[derive(Debug, Deserialize)]
+pub struct PresenceRequest {
+    id: Uuid,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PresenceResponse {
+    agents: Vec<AgentId>,
+}
+
+pub async fn presence(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<Uuid>,
+) -> RequestResult {
+    let request = PresenceRequest { id: room_id };
+    PresenceHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct PresenceHandler;
+
+#[async_trait]
+impl RequestHandler for PresenceHandler {
+    type Payload = PresenceRequest;
+
+    #[instrument(
+        skip_all,
+        fields(
+            room_id = %payload.id, scope, classroom_id
+        )
+    )]
+    async fn handle<C: Context>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let room =
+            helpers::find_room(context, payload.id, helpers::RoomTimeRequirement::Any).await?;
+
+        // Authorize room events listing.
+        let object = AuthzObject::new(&["classrooms", &room.classroom_id().to_string()]).into();
+
+        let authz_time = context
+            .authz()
+            .authorize_room(
+                &room,
+                reqp.as_account_id().to_owned(),
+                object,
+                "read".into(),
+            )
+            .await?;
+
+        let agents = helpers::room_online_agents(context, room.id()).await?;
+
+        Ok(AppResponse::new(
+            ResponseStatus::OK,
+            PresenceResponse { agents },
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::prelude::*;
+
+    #[tokio::test]
+    async fn presence() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let online_agent = TestAgent::new("web", "user456", USR_AUDIENCE);
+        let entering_agent = TestAgent::new("web", "user789", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+
+            shared_helpers::insert_agent(&mut conn, online_agent.agent_id(), room.id()).await;
+
+            factory::Agent::new()
+                .room_id(room.id())
+                .agent_id(entering_agent.agent_id().to_owned())
+                .status(agent::Status::InProgress)
+                .insert(&mut conn)
+                .await;
+
+            room
+        };
+
+        let mut authz = TestAuthz::new();
+        authz.allow(
+            agent.account_id(),
+            vec!["classrooms", &room.classroom_id().to_string()],
+            "read",
+        );
+
+        let mut context = TestContext::new(db, authz);
+        let payload = PresenceRequest { id: room.id() };
+
+        let messages = handle_request::<PresenceHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Room presence failed");
+
+        // Agents which haven't entered yet are not online.
+        let (resp, respp, _) = find_response::<PresenceResponse>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert_eq!(resp.agents, vec![online_agent.agent_id().to_owned()]);
+    }
+
+    #[tokio::test]
+    async fn presence_not_authorized() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut context = TestContext::new(db, TestAuthz::new());
+        let payload = PresenceRequest { id: room.id() };
+
+        let err = handle_request::<PresenceHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success on room presence");
+
+        assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+    }
+}
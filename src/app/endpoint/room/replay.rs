@@ -0,0 +1,352 @@
+use async_trait::async_trait;
+use axum::extract::RawQuery;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::{map::Map as JsonMap, Value as JsonValue};
+use svc_agent::mqtt::ResponseStatus;
+use uuid::Uuid;
+
+use super::*;
+use crate::app::context::Context;
+use crate::app::endpoint::state::serialize_set_state;
+use crate::db;
+use crate::db::event::{Direction, Object as Event};
+
+/// Default half-width of the events window around the position in milliseconds.
+const DEFAULT_WINDOW: i64 = 30_000;
+const MAX_WINDOW: i64 = 300_000;
+const MAX_EVENTS: usize = 500;
+const MAX_SETS: usize = 10;
+const MAX_LIMIT_PER_SET: i64 = 100;
+
+const NANOS_IN_MILLI: i64 = 1_000_000;
+
+#[derive(Debug, Deserialize)]
+pub struct ReplayPayload {
+    /// Playback position in milliseconds since the room opening.
+    position: i64,
+    window: Option<i64>,
+    #[serde(default)]
+    sets: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReplayRequest {
+    id: Uuid,
+    #[serde(flatten)]
+    payload: ReplayPayload,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ReplayResponse {
+    position: i64,
+    events: Vec<Event>,
+    has_next: bool,
+    state: JsonMap<String, JsonValue>,
+}
+
+pub async fn replay(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<Uuid>,
+    RawQuery(query): RawQuery,
+) -> RequestResult {
+    let payload = serde_qs::from_str(&query.unwrap_or_default())
+        .context("Failed to parse qs")
+        .error(AppErrorKind::InvalidQueryString)?;
+    let request = ReplayRequest {
+        id: room_id,
+        payload,
+    };
+    ReplayHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct ReplayHandler;
+
+#[async_trait]
+impl RequestHandler for ReplayHandler {
+    type Payload = ReplayRequest;
+
+    #[instrument(
+        skip_all,
+        fields(
+            room_id = %payload.id, scope, classroom_id
+        )
+    )]
+    async fn handle<C: Context>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let ReplayRequest { id, payload } = payload;
+
+        if payload.sets.len() > MAX_SETS {
+            return Err(anyhow!("too many 'sets'")).error(AppErrorKind::InvalidStateSets);
+        }
+
+        let window = payload.window.unwrap_or(DEFAULT_WINDOW);
+
+        if payload.position < 0 || !(0..=MAX_WINDOW).contains(&window) {
+            return Err(anyhow!("Invalid position or window")).error(AppErrorKind::InvalidPayload);
+        }
+
+        let room = helpers::find_room(context, id, helpers::RoomTimeRequirement::Any).await?;
+
+        // Only adjusted rooms have `occurred_at` matching the playback timeline.
+        if room.source_room_id().is_none() {
+            return Err(anyhow!("Room is not derived")).error(AppErrorKind::InvalidPayload);
+        }
+
+        let object = AuthzObject::room(&room).into();
+
+        let authz_time = context
+            .authz()
+            .authorize_room(
+                &room,
+                reqp.as_account_id().to_owned(),
+                object,
+                "read".into(),
+            )
+            .await?;
+
+        let (redaction, redaction_authz_time) =
+            helpers::Redaction::for_agent(context, &room, reqp).await?;
+
+        let authz_time = authz_time + redaction_authz_time.unwrap_or_else(chrono::Duration::zero);
+
+        let position = payload.position.saturating_mul(NANOS_IN_MILLI);
+        let window = window * NANOS_IN_MILLI;
+        let mut conn = context.get_ro_conn().await?;
+
+        // Fetch one extra event to find out whether the window is truncated.
+        let query = db::event::ListQuery::new()
+            .room_id(room.id())
+            .occurred_at_from(position.saturating_sub(window))
+            .occurred_at_to(position.saturating_add(window))
+            .direction(Direction::Forward)
+            .limit(MAX_EVENTS + 1);
+
+        let mut events = context
+            .metrics()
+            .measure_query(QueryKey::EventListQuery, query.execute(&mut conn))
+            .await
+            .context("Failed to list events")
+            .error(AppErrorKind::DbQueryFailed)?;
+
+        let has_next = events.len() > MAX_EVENTS;
+        events.truncate(MAX_EVENTS);
+        helpers::open_events(context, &mut events)?;
+        redaction.apply(&mut events);
+
+        // Reconstruct the state as the player has it right at the position.
+        let mut state = JsonMap::new();
+
+        let crdt = context.config().crdt.clone();
+
+        for set in payload.sets {
+            let mut set_state = if crdt.is_crdt_set(&set) {
+                let query = db::event::crdt::Query::new(room.id(), set.clone(), i64::MAX)
+                    .occurred_at(position.saturating_add(1));
+
+                let mut elements = context
+                    .metrics()
+                    .measure_query(QueryKey::CrdtStateQuery, query.execute(&mut conn))
+                    .await
+                    .context("Failed to get CRDT state")
+                    .error(AppErrorKind::DbQueryFailed)?;
+
+                elements.truncate(MAX_LIMIT_PER_SET as usize);
+                elements
+            } else {
+                let query = db::event::SetStateQuery::new(
+                    room.id(),
+                    set.clone(),
+                    i64::MAX,
+                    MAX_LIMIT_PER_SET,
+                )
+                .occurred_at(position.saturating_add(1));
+
+                context
+                    .metrics()
+                    .measure_query(QueryKey::StateQuery, query.execute(&mut conn))
+                    .await
+                    .context("Failed to get state")
+                    .error(AppErrorKind::DbQueryFailed)?
+            };
+
+            helpers::open_events(context, &mut set_state)?;
+            redaction.apply(&mut set_state);
+            state.insert(set, serialize_set_state(set_state)?);
+        }
+
+        let response = ReplayResponse {
+            position: payload.position,
+            events,
+            has_next,
+            state,
+        };
+
+        Ok(AppResponse::new(
+            ResponseStatus::OK,
+            response,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_helpers::prelude::*;
+
+    async fn insert_derived_room(conn: &mut sqlx::PgConnection) -> db::room::Object {
+        let source = shared_helpers::insert_room(conn).await;
+
+        factory::Room::new(source.classroom_id(), ClassType::Webinar)
+            .audience(USR_AUDIENCE)
+            .time((Bound::Included(Utc::now()), Bound::Unbounded))
+            .source_room_id(source.id())
+            .insert(conn)
+            .await
+    }
+
+    fn replay_request(room: &db::room::Object, position: i64, sets: &[&str]) -> ReplayRequest {
+        ReplayRequest {
+            id: room.id(),
+            payload: ReplayPayload {
+                position,
+                window: Some(10_000),
+                sets: sets.iter().map(|s| s.to_string()).collect(),
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn replay() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let (room, events) = {
+            let mut conn = db.get_conn().await;
+            let room = insert_derived_room(&mut conn).await;
+            let mut events = vec![];
+
+            // Layout changes at 5s and 25s, messages are at 10s, 20s and 45s.
+            for (kind, set, label, seconds) in [
+                ("layout", "layout", None, 5),
+                ("message", "messages", Some("message-1"), 10),
+                ("message", "messages", Some("message-2"), 20),
+                ("layout", "layout", None, 25),
+                ("message", "messages", Some("message-3"), 45),
+            ] {
+                let mut factory = factory::Event::new()
+                    .room_id(room.id())
+                    .kind(kind)
+                    .set(set)
+                    .data(&json!({ "seconds": seconds }))
+                    .occurred_at(seconds * 1_000_000_000)
+                    .created_by(agent.agent_id());
+
+                if let Some(label) = label {
+                    factory = factory.label(label);
+                }
+
+                events.push(factory.insert(&mut conn).await);
+            }
+
+            (room, events)
+        };
+
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        authz.allow(
+            agent.account_id(),
+            vec!["classrooms", &classroom_id],
+            "read",
+        );
+
+        let mut context = TestContext::new(db, authz);
+        let payload = replay_request(&room, 20_000, &["layout", "messages"]);
+
+        let messages = handle_request::<ReplayHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Room replay failed");
+
+        let (resp, respp, _) = find_response::<ReplayResponse>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert_eq!(resp.position, 20_000);
+        assert!(!resp.has_next);
+
+        // Events between 10s and 30s.
+        let event_ids = resp.events.iter().map(|e| e.id()).collect::<Vec<_>>();
+        let expected_ids = events[1..4].iter().map(|e| e.id()).collect::<Vec<_>>();
+        assert_eq!(event_ids, expected_ids);
+
+        // State at 20s.
+        assert_eq!(resp.state["layout"]["id"], json!(events[0].id()));
+
+        let messages = resp.state["messages"]
+            .as_array()
+            .expect("Messages state is not a collection");
+
+        assert_eq!(messages.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn replay_not_derived_room() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        authz.allow(
+            agent.account_id(),
+            vec!["classrooms", &classroom_id],
+            "read",
+        );
+
+        let mut context = TestContext::new(db, authz);
+        let payload = replay_request(&room, 0, &[]);
+
+        let err = handle_request::<ReplayHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success replaying not derived room");
+
+        assert_eq!(err.status(), ResponseStatus::BAD_REQUEST);
+        assert_eq!(err.kind(), "invalid_payload");
+    }
+
+    #[tokio::test]
+    async fn replay_not_authorized() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            insert_derived_room(&mut conn).await
+        };
+
+        let mut context = TestContext::new(db, TestAuthz::new());
+        let payload = replay_request(&room, 0, &[]);
+
+        let err = handle_request::<ReplayHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success replaying room without authorization");
+
+        assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+    }
+}
@@ -0,0 +1,239 @@
+use axum::body::Bytes;
+use serde_derive::{Deserialize, Serialize};
+use svc_agent::mqtt::ResponseStatus;
+use uuid::Uuid;
+
+use super::*;
+use crate::app::context::Context;
+use crate::app::operations::snapshot;
+use crate::db::event::InsertSnapshotQuery;
+
+/// Events inserted with a single statement.
+const INSERT_CHUNK_SIZE: usize = 1000;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RestoreResponse {
+    /// The room the snapshot was made of.
+    source_room_id: Uuid,
+    restored: usize,
+}
+
+/// Restores events of a binary snapshot made with `room.dump_events` into the room.
+/// The body is the snapshot as is.
+pub async fn restore(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<Uuid>,
+    body: Bytes,
+) -> RequestResult {
+    let reqp = RequestParams::Http {
+        agent_id: &agent_id,
+    };
+
+    restore_room(&mut ctx.start_message(), room_id, body, reqp).await
+}
+
+#[instrument(skip_all, fields(room_id, scope, classroom_id))]
+async fn restore_room<C: Context>(
+    context: &mut C,
+    room_id: Uuid,
+    snapshot: Bytes,
+    reqp: RequestParams<'_>,
+) -> RequestResult {
+    // History may be restored into closed rooms as well.
+    let room = helpers::find_room(context, room_id, helpers::RoomTimeRequirement::Any).await?;
+
+    // Restored events keep their authorship and timing like imported ones.
+    let object = AuthzObject::room(&room).into();
+
+    let authz_time = context
+        .authz()
+        .authorize_room(
+            &room,
+            reqp.as_account_id().to_owned(),
+            object,
+            "import".into(),
+        )
+        .await?;
+
+    let (manifest, events) = tokio::task::spawn_blocking(move || snapshot::decode(&snapshot))
+        .await
+        .context("Snapshot decoding task panicked")
+        .error(AppErrorKind::InternalServerError)?
+        .context("Invalid snapshot")
+        .error(AppErrorKind::InvalidPayload)?;
+
+    let data_encryption = context.data_encryption();
+
+    // Restore all or nothing so that a failed restore may be simply retried.
+    {
+        let mut conn = context.get_conn().await?;
+
+        let mut txn = conn
+            .begin()
+            .await
+            .context("Failed to acquire transaction")
+            .error(AppErrorKind::DbQueryFailed)?;
+
+        for chunk in events.chunks(INSERT_CHUNK_SIZE) {
+            let query = InsertSnapshotQuery::new(room.id(), chunk, data_encryption.as_deref());
+
+            context
+                .metrics()
+                .measure_query(QueryKey::EventInsertSnapshotQuery, query.execute(&mut txn))
+                .await
+                .context("Failed to insert events")
+                .error(AppErrorKind::DbQueryFailed)?;
+        }
+
+        txn.commit()
+            .await
+            .context("Failed to commit transaction")
+            .error(AppErrorKind::DbQueryFailed)?;
+    }
+
+    info!(
+        source_room_id = %manifest.room.id,
+        restored = events.len(),
+        "Room restored from snapshot by {}",
+        reqp.as_agent_id()
+    );
+
+    // Restored history is not broadcasted, clients get it on reading the room.
+    Ok(AppResponse::new(
+        ResponseStatus::CREATED,
+        RestoreResponse {
+            source_room_id: manifest.room.id,
+            restored: events.len(),
+        },
+        context.start_timestamp(),
+        Some(authz_time),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::db::event::{CompactEvent, ListQuery as EventListQuery};
+    use crate::test_helpers::prelude::*;
+
+    #[tokio::test]
+    async fn restore_room_snapshot() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let (source_room, room, snapshot) = {
+            let mut conn = db.get_conn().await;
+            let source_room = shared_helpers::insert_room(&mut conn).await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+
+            factory::Event::new()
+                .room_id(source_room.id())
+                .kind("message")
+                .data(&json!({ "message": "hello" }))
+                .occurred_at(1000)
+                .created_by(agent.agent_id())
+                .insert(&mut conn)
+                .await;
+
+            factory::Event::new()
+                .room_id(source_room.id())
+                .kind("draw")
+                .data(
+                    &CompactEvent::test_rect_event()
+                        .into_json()
+                        .expect("Failed to convert into json"),
+                )
+                .occurred_at(2000)
+                .created_by(agent.agent_id())
+                .insert(&mut conn)
+                .await;
+
+            let events = EventListQuery::new()
+                .room_id(source_room.id())
+                .with_binary_data()
+                .execute(&mut conn)
+                .await
+                .expect("Failed to list events");
+
+            let snapshot = snapshot::encode(&source_room, events).expect("Failed to encode");
+            (source_room, room, snapshot)
+        };
+
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        authz.allow(
+            agent.account_id(),
+            vec!["classrooms", &classroom_id],
+            "import",
+        );
+
+        let mut context = TestContext::new(db, authz);
+
+        let reqp = RequestParams::Http {
+            agent_id: agent.agent_id(),
+        };
+
+        restore_room(&mut context, room.id(), Bytes::from(snapshot), reqp)
+            .await
+            .expect("Failed to restore room");
+
+        let mut conn = context.db().acquire().await.expect("Failed to get conn");
+
+        let source_events = EventListQuery::new()
+            .room_id(source_room.id())
+            .execute(&mut conn)
+            .await
+            .expect("Failed to list events");
+
+        let events = EventListQuery::new()
+            .room_id(room.id())
+            .execute(&mut conn)
+            .await
+            .expect("Failed to list events");
+
+        assert_eq!(events.len(), 2);
+
+        for (event, source_event) in events.iter().zip(source_events.iter()) {
+            assert_eq!(event.kind(), source_event.kind());
+            assert_eq!(event.data(), source_event.data());
+            assert_eq!(event.occurred_at(), source_event.occurred_at());
+            assert_eq!(event.created_by(), source_event.created_by());
+        }
+    }
+
+    #[tokio::test]
+    async fn restore_invalid_snapshot() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        authz.allow(
+            agent.account_id(),
+            vec!["classrooms", &classroom_id],
+            "import",
+        );
+
+        let mut context = TestContext::new(db, authz);
+
+        let reqp = RequestParams::Http {
+            agent_id: agent.agent_id(),
+        };
+
+        let err = match restore_room(&mut context, room.id(), Bytes::from("{}"), reqp).await {
+            Ok(_) => panic!("Unexpected success restoring room"),
+            Err(err) => err,
+        };
+
+        assert_eq!(err.status(), ResponseStatus::BAD_REQUEST);
+        assert_eq!(err.kind(), "invalid_payload");
+    }
+}
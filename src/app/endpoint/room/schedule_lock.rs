@@ -0,0 +1,260 @@
+use async_trait::async_trait;
+use chrono::{serde::ts_seconds_option, DateTime, Utc};
+use serde_derive::Deserialize;
+use svc_agent::mqtt::ResponseStatus;
+use tracing::{field::display, instrument, Span};
+use uuid::Uuid;
+
+use super::*;
+use crate::app::context::Context;
+use crate::db::room_lock_schedule::InsertQuery;
+
+#[derive(Debug, Deserialize)]
+pub struct ScheduleLockPayload {
+    /// Locked types to merge into the current ones like `room.locked_types` does.
+    locked_types: HashMap<String, bool>,
+    /// Unix time in seconds to apply the change at.
+    #[serde(default, with = "ts_seconds_option")]
+    apply_at: Option<DateTime<Utc>>,
+    /// Seconds before the room closes to apply the change at.
+    before_close: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScheduleLockRequest {
+    id: Uuid,
+    #[serde(flatten)]
+    payload: ScheduleLockPayload,
+}
+
+pub async fn schedule_lock(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<Uuid>,
+    Json(payload): Json<ScheduleLockPayload>,
+) -> RequestResult {
+    let request = ScheduleLockRequest {
+        id: room_id,
+        payload,
+    };
+    ScheduleLockHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct ScheduleLockHandler;
+
+#[async_trait]
+impl RequestHandler for ScheduleLockHandler {
+    type Payload = ScheduleLockRequest;
+
+    #[instrument(skip_all, fields(room_id, scope, classroom_id))]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload { id, payload }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        Span::current().record("room_id", &display(id));
+
+        if payload.locked_types.is_empty() {
+            return Err(anyhow!("'locked_types' must not be empty"))
+                .error(AppErrorKind::InvalidPayload);
+        }
+
+        let room = helpers::find_room(context, id, helpers::RoomTimeRequirement::NotClosed).await?;
+
+        let object = AuthzObject::room(&room).into();
+
+        let authz_time = context
+            .authz()
+            .authorize_room(
+                &room,
+                reqp.as_account_id().to_owned(),
+                object,
+                "update".into(),
+            )
+            .await?;
+
+        let agent_id = reqp.as_agent_id().to_owned();
+
+        let query = match (payload.apply_at, payload.before_close) {
+            (Some(apply_at), None) => {
+                InsertQuery::at(room.id(), payload.locked_types, apply_at, agent_id)
+            }
+            (None, Some(before_close)) if before_close >= 0 => {
+                InsertQuery::before_close(room.id(), payload.locked_types, before_close, agent_id)
+            }
+            (None, Some(_)) => {
+                return Err(anyhow!("'before_close' must not be negative"))
+                    .error(AppErrorKind::InvalidPayload);
+            }
+            _ => {
+                return Err(anyhow!(
+                    "Either 'apply_at' or 'before_close' must be specified"
+                ))
+                .error(AppErrorKind::InvalidPayload);
+            }
+        };
+
+        let schedule = {
+            let mut conn = context.get_conn().await?;
+
+            context
+                .metrics()
+                .measure_query(
+                    QueryKey::RoomLockScheduleInsertQuery,
+                    query.execute(&mut conn),
+                )
+                .await
+                .context("Failed to insert room lock schedule")
+                .error(AppErrorKind::DbQueryFailed)?
+        };
+
+        Ok(AppResponse::new(
+            ResponseStatus::CREATED,
+            schedule,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+    use crate::db::room::Object as Room;
+    use crate::db::room_lock_schedule::Object as Schedule;
+    use crate::test_helpers::prelude::*;
+
+    fn allow_update(authz: &mut TestAuthz, agent: &TestAgent, room: &Room) {
+        let classroom_id = room.classroom_id().to_string();
+        authz.allow(
+            agent.account_id(),
+            vec!["classrooms", &classroom_id],
+            "update",
+        );
+    }
+
+    #[tokio::test]
+    async fn schedule_lock() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut authz = TestAuthz::new();
+        allow_update(&mut authz, &agent, &room);
+        let mut context = TestContext::new(db, authz);
+
+        let payload = ScheduleLockRequest {
+            id: room.id(),
+            payload: ScheduleLockPayload {
+                locked_types: [("message".to_owned(), true)].into_iter().collect(),
+                apply_at: None,
+                before_close: Some(300),
+            },
+        };
+
+        let messages = handle_request::<ScheduleLockHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Room lock scheduling failed");
+
+        let (schedule, respp, _) = find_response::<Schedule>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::CREATED);
+        assert_eq!(schedule.room_id(), room.id());
+        assert_eq!(schedule.locked_types().get("message"), Some(&true));
+
+        // Nothing changes in the room until the schedule is due.
+        assert_eq!(messages.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn schedule_lock_invalid() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut authz = TestAuthz::new();
+        allow_update(&mut authz, &agent, &room);
+        let mut context = TestContext::new(db, authz);
+
+        let message = || [("message".to_owned(), true)].into_iter().collect();
+
+        for payload in [
+            ScheduleLockPayload {
+                locked_types: HashMap::new(),
+                apply_at: None,
+                before_close: Some(300),
+            },
+            ScheduleLockPayload {
+                locked_types: message(),
+                apply_at: None,
+                before_close: None,
+            },
+            ScheduleLockPayload {
+                locked_types: message(),
+                apply_at: Some(Utc::now() + Duration::minutes(5)),
+                before_close: Some(300),
+            },
+            ScheduleLockPayload {
+                locked_types: message(),
+                apply_at: None,
+                before_close: Some(-1),
+            },
+        ] {
+            let payload = ScheduleLockRequest {
+                id: room.id(),
+                payload,
+            };
+
+            let err = handle_request::<ScheduleLockHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on room lock scheduling");
+
+            assert_eq!(err.status(), ResponseStatus::BAD_REQUEST);
+            assert_eq!(err.kind(), "invalid_payload");
+        }
+    }
+
+    #[tokio::test]
+    async fn schedule_lock_not_authorized() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut context = TestContext::new(db, TestAuthz::new());
+
+        let payload = ScheduleLockRequest {
+            id: room.id(),
+            payload: ScheduleLockPayload {
+                locked_types: [("message".to_owned(), true)].into_iter().collect(),
+                apply_at: Some(Utc::now()),
+                before_close: None,
+            },
+        };
+
+        let err = handle_request::<ScheduleLockHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success on room lock scheduling");
+
+        assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+    }
+}
@@ -0,0 +1,202 @@
+use async_trait::async_trait;
+use serde_derive::Deserialize;
+use serde_json::Value as JsonValue;
+use svc_agent::mqtt::ResponseStatus;
+use uuid::Uuid;
+
+use super::*;
+use crate::app::context::Context;
+use crate::db;
+use crate::db::room_time::BoundedDateTimeTuple;
+
+const MAX_LIMIT: usize = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct SearchPayload {
+    classroom_id: Option<Uuid>,
+    tags: Option<JsonValue>,
+    open: Option<bool>,
+    #[serde(default, with = "crate::serde::ts_seconds_option_bound_tuple")]
+    created_at: Option<BoundedDateTimeTuple>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SearchRequest {
+    audience: String,
+    #[serde(flatten)]
+    payload: SearchPayload,
+}
+
+pub async fn search(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(audience): Path<String>,
+    Json(payload): Json<SearchPayload>,
+) -> RequestResult {
+    let request = SearchRequest { audience, payload };
+    SearchHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct SearchHandler;
+
+#[async_trait]
+impl RequestHandler for SearchHandler {
+    type Payload = SearchRequest;
+
+    #[instrument(skip_all, fields(audience))]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload { audience, payload }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        if matches!(payload.tags, Some(ref tags) if !tags.is_object()) {
+            return Err(anyhow!("'tags' must be an object")).error(AppErrorKind::InvalidPayload);
+        }
+
+        let object = AuthzObject::new(&["classrooms"]).into();
+
+        let authz_time = context
+            .authz()
+            .authorize(
+                audience.clone(),
+                reqp.as_account_id().to_owned(),
+                object,
+                "list".into(),
+            )
+            .await?;
+
+        let rooms = {
+            let mut query = db::room::SearchQuery::new(
+                audience,
+                payload.offset.unwrap_or(0),
+                std::cmp::min(payload.limit.unwrap_or(MAX_LIMIT), MAX_LIMIT),
+            );
+
+            if let Some(classroom_id) = payload.classroom_id {
+                query = query.classroom_id(classroom_id);
+            }
+
+            if let Some(tags) = payload.tags {
+                query = query.tags(tags);
+            }
+
+            if let Some(open) = payload.open {
+                query = query.open(open);
+            }
+
+            if let Some(created_at) = payload.created_at {
+                query = query.created_at(created_at);
+            }
+
+            let mut conn = context.get_ro_conn().await?;
+
+            context
+                .metrics()
+                .measure_query(QueryKey::RoomSearchQuery, query.execute(&mut conn))
+                .await
+                .context("Failed to search rooms")
+                .error(AppErrorKind::DbQueryFailed)?
+        };
+
+        Ok(AppResponse::new(
+            ResponseStatus::OK,
+            rooms,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::db::room::Object as Room;
+    use crate::test_helpers::prelude::*;
+
+    fn search_request(tags: Option<JsonValue>) -> SearchRequest {
+        SearchRequest {
+            audience: USR_AUDIENCE.to_owned(),
+            payload: SearchPayload {
+                classroom_id: None,
+                tags,
+                open: None,
+                created_at: None,
+                offset: None,
+                limit: None,
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn search_rooms_by_tags() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let webinar_id = Uuid::new_v4().to_string();
+
+        let room = {
+            let mut conn = db.get_conn().await;
+
+            let room = factory::Room::new(Uuid::new_v4(), ClassType::Webinar)
+                .audience(USR_AUDIENCE)
+                .time((Bound::Included(Utc::now()), Bound::Unbounded))
+                .tags(&json!({ "webinar_id": webinar_id, "subject": "math" }))
+                .insert(&mut conn)
+                .await;
+
+            shared_helpers::insert_room(&mut conn).await;
+            room
+        };
+
+        let mut authz = TestAuthz::new();
+        authz.allow(agent.account_id(), vec!["classrooms"], "list");
+
+        let mut context = TestContext::new(db, authz);
+        let payload = search_request(Some(json!({ "webinar_id": webinar_id })));
+
+        let messages = handle_request::<SearchHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Rooms search failed");
+
+        let (rooms, respp, _) = find_response::<Vec<Room>>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert_eq!(rooms.len(), 1);
+        assert_eq!(rooms[0].id(), room.id());
+    }
+
+    #[tokio::test]
+    async fn search_rooms_with_invalid_tags() {
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let mut context = TestContext::new(TestDb::new().await, TestAuthz::new());
+        let payload = search_request(Some(json!("webinar")));
+
+        let err = handle_request::<SearchHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success searching rooms with invalid tags");
+
+        assert_eq!(err.status(), ResponseStatus::BAD_REQUEST);
+        assert_eq!(err.kind(), "invalid_payload");
+    }
+
+    #[tokio::test]
+    async fn search_rooms_not_authorized() {
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let mut context = TestContext::new(TestDb::new().await, TestAuthz::new());
+
+        let err = handle_request::<SearchHandler>(&mut context, &agent, search_request(None))
+            .await
+            .expect_err("Unexpected success searching rooms without authorization");
+
+        assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+    }
+}
@@ -0,0 +1,252 @@
+use std::time::Duration as StdDuration;
+
+use async_trait::async_trait;
+use chrono::{serde::ts_seconds, DateTime, Duration, Utc};
+use serde_derive::{Deserialize, Serialize};
+use svc_agent::mqtt::ResponseStatus;
+use tracing::{field::display, instrument, Span};
+use uuid::Uuid;
+
+use super::*;
+use crate::app::context::Context;
+use crate::app::share_token::ShareToken;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct SharePayload {
+    /// Token lifetime in seconds, `share.default_ttl` from the config when missing.
+    ttl: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ShareRequest {
+    id: Uuid,
+    #[serde(flatten)]
+    payload: SharePayload,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ShareResponse {
+    token: String,
+    #[serde(with = "ts_seconds")]
+    expires_at: DateTime<Utc>,
+}
+
+pub async fn share(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<Uuid>,
+    Json(payload): Json<SharePayload>,
+) -> RequestResult {
+    let request = ShareRequest {
+        id: room_id,
+        payload,
+    };
+    ShareHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct ShareHandler;
+
+#[async_trait]
+impl RequestHandler for ShareHandler {
+    type Payload = ShareRequest;
+
+    #[instrument(skip_all, fields(room_id, scope, classroom_id))]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload { id, payload }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        Span::current().record("room_id", &display(id));
+
+        let config = context.config();
+
+        let share = config
+            .share
+            .as_ref()
+            .context("Room sharing is not configured")
+            .error(AppErrorKind::SharingDisabled)?;
+
+        let ttl = payload
+            .ttl
+            .map(StdDuration::from_secs)
+            .unwrap_or(share.default_ttl);
+
+        if ttl.is_zero() || ttl > share.max_ttl {
+            return Err(anyhow!(
+                "Token lifetime must be positive and not exceed {} seconds",
+                share.max_ttl.as_secs()
+            ))
+            .error(AppErrorKind::InvalidPayload);
+        }
+
+        let room = helpers::find_room(context, id, helpers::RoomTimeRequirement::Any).await?;
+
+        // Sharing the room publicly is up to those who manage it.
+        let object = AuthzObject::room(&room).into();
+
+        let authz_time = context
+            .authz()
+            .authorize_room(
+                &room,
+                reqp.as_account_id().to_owned(),
+                object,
+                "update".into(),
+            )
+            .await?;
+
+        let ttl = Duration::from_std(ttl)
+            .context("Invalid token lifetime")
+            .error(AppErrorKind::InvalidPayload)?;
+
+        let token = ShareToken::new(room.id(), context.start_timestamp() + ttl);
+
+        info!(
+            expires_at = %token.expires_at(),
+            "Room shared by {}",
+            reqp.as_agent_id()
+        );
+
+        let response = ShareResponse {
+            token: token.sign(&share.secret),
+            expires_at: token.expires_at(),
+        };
+
+        Ok(AppResponse::new(
+            ResponseStatus::OK,
+            response,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ShareConfig;
+    use crate::test_helpers::prelude::*;
+
+    const SECRET: &str = "secret";
+
+    fn enable_sharing(context: &TestContext) {
+        context.update_config(|config| {
+            config.share = Some(ShareConfig {
+                secret: SECRET.to_owned(),
+                default_ttl: StdDuration::from_secs(3600),
+                max_ttl: StdDuration::from_secs(24 * 3600),
+            })
+        });
+    }
+
+    #[tokio::test]
+    async fn share() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        authz.allow(
+            agent.account_id(),
+            vec!["classrooms", &classroom_id],
+            "update",
+        );
+
+        let mut context = TestContext::new(db, authz);
+        enable_sharing(&context);
+
+        let payload = ShareRequest {
+            id: room.id(),
+            payload: SharePayload::default(),
+        };
+
+        let messages = handle_request::<ShareHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Room sharing failed");
+
+        let (resp, respp, _) = find_response::<ShareResponse>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+
+        let token = ShareToken::verify(&resp.token, SECRET, Utc::now())
+            .expect("Failed to verify share token");
+
+        assert_eq!(token.room_id(), room.id());
+        assert_eq!(token.expires_at(), resp.expires_at);
+        assert!(resp.expires_at > Utc::now() + Duration::minutes(59));
+
+        // Lifetime is limited.
+        let payload = ShareRequest {
+            id: room.id(),
+            payload: SharePayload {
+                ttl: Some(48 * 3600),
+            },
+        };
+
+        let err = handle_request::<ShareHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success on room sharing");
+
+        assert_eq!(err.status(), ResponseStatus::BAD_REQUEST);
+        assert_eq!(err.kind(), "invalid_payload");
+    }
+
+    #[tokio::test]
+    async fn share_not_authorized() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut context = TestContext::new(db, TestAuthz::new());
+        enable_sharing(&context);
+
+        let payload = ShareRequest {
+            id: room.id(),
+            payload: SharePayload::default(),
+        };
+
+        let err = handle_request::<ShareHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success on room sharing");
+
+        assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn share_disabled() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut context = TestContext::new(db, TestAuthz::new());
+
+        let payload = ShareRequest {
+            id: room.id(),
+            payload: SharePayload::default(),
+        };
+
+        let err = handle_request::<ShareHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success on room sharing");
+
+        assert_eq!(err.kind(), "sharing_disabled");
+    }
+}
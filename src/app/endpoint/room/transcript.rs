@@ -0,0 +1,393 @@
+use async_trait::async_trait;
+use axum::extract::RawQuery;
+use chrono::Utc;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+use svc_agent::mqtt::{
+    OutgoingEvent, OutgoingEventProperties, ResponseStatus, ShortTermTimingProperties,
+};
+use tracing::{error, Instrument};
+use uuid::Uuid;
+
+use super::*;
+use crate::app::context::Context;
+use crate::app::message_handler::Message;
+use crate::app::operations::export_transcript;
+use crate::db::event::TranscriptQuery;
+use crate::db::room::Object as Room;
+use crate::db::task::Status as TaskStatus;
+
+const MAX_LIMIT: usize = 100;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct TranscriptPayload {
+    /// `occurred_at` of the last message of the previous page.
+    after: Option<i64>,
+    /// Maximum number of messages, 100 at most.
+    limit: Option<usize>,
+    /// Export the whole transcript to S3 instead of responding with a page.
+    #[serde(default)]
+    export: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TranscriptRequest {
+    id: Uuid,
+    #[serde(flatten)]
+    payload: TranscriptPayload,
+}
+
+#[derive(Serialize)]
+struct TranscriptNotification {
+    status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tags: Option<JsonValue>,
+    result: TranscriptResult,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum TranscriptResult {
+    Success { room_id: Uuid, s3_uri: String },
+    Error { error: ErrorPayload },
+}
+
+impl TranscriptResult {
+    fn status(&self) -> &'static str {
+        match self {
+            Self::Success { .. } => "success",
+            Self::Error { .. } => "error",
+        }
+    }
+}
+
+pub async fn transcript(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<Uuid>,
+    RawQuery(query): RawQuery,
+) -> RequestResult {
+    let payload = serde_qs::from_str(&query.unwrap_or_default())
+        .context("Failed to parse qs")
+        .error(AppErrorKind::InvalidQueryString)?;
+
+    let request = TranscriptRequest {
+        id: room_id,
+        payload,
+    };
+
+    TranscriptHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub async fn export_transcript_to_s3(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<Uuid>,
+) -> RequestResult {
+    let request = TranscriptRequest {
+        id: room_id,
+        payload: TranscriptPayload {
+            export: true,
+            ..Default::default()
+        },
+    };
+
+    TranscriptHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct TranscriptHandler;
+
+#[async_trait]
+impl RequestHandler for TranscriptHandler {
+    type Payload = TranscriptRequest;
+
+    #[instrument(skip_all, fields(room_id, scope, classroom_id))]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload { id, payload }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let room = helpers::find_room(context, id, helpers::RoomTimeRequirement::Any).await?;
+
+        if payload.export {
+            // Exporting is authorized like dumping events since both go to the tenant's bucket.
+            let object = AuthzObject::new(&["classrooms"]).into();
+
+            let authz_time = context
+                .authz()
+                .authorize_room(
+                    &room,
+                    reqp.as_account_id().to_owned(),
+                    object,
+                    "dump_events".into(),
+                )
+                .await?;
+
+            return start_export(context, room, reqp, authz_time).await;
+        }
+
+        // The same permission as for listing events.
+        let classroom_id = room.classroom_id().to_string();
+        let object = AuthzObject::new(&["classrooms", &classroom_id]);
+        let authz_time = helpers::authorize_room(context, &room, reqp, object, "read").await?;
+
+        let limit = std::cmp::min(payload.limit.unwrap_or(MAX_LIMIT), MAX_LIMIT);
+        let mut query = TranscriptQuery::new(room.id(), limit as i64);
+
+        if let Some(after) = payload.after {
+            query = query.after(after);
+        }
+
+        let data_encryption = context.data_encryption();
+
+        if let Some(ref data_encryption) = data_encryption {
+            query = query.decrypt(data_encryption);
+        }
+
+        let mut conn = context.get_ro_conn().await?;
+
+        let messages = context
+            .metrics()
+            .measure_query(QueryKey::EventTranscriptQuery, query.execute(&mut conn))
+            .await
+            .context("Failed to load room transcript")
+            .error(AppErrorKind::DbQueryFailed)?;
+
+        Ok(AppResponse::new(
+            ResponseStatus::OK,
+            messages,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+/// Spawns the transcript export to S3 and responds with 202.
+/// The result gets broadcasted as `room.transcript` notification when finished.
+async fn start_export<C: Context>(
+    context: &mut C,
+    room: Room,
+    reqp: RequestParams<'_>,
+    authz_time: chrono::Duration,
+) -> RequestResult {
+    let s3_client = context
+        .s3_client()
+        .ok_or_else(|| anyhow!("No S3Client"))
+        .error(AppErrorKind::NoS3Client)?;
+
+    let db = context.background_db().to_owned();
+    let metrics = context.metrics();
+    let data_encryption = context.data_encryption();
+    let task = helpers::start_task(context, "room.transcript", &room, reqp.as_agent_id()).await?;
+    let task_id = task.id();
+    let jobs = context.jobs();
+    let job_id = jobs.start("transcript", Some(room.id()));
+    let span = helpers::operation_span("transcript", &room, Some(reqp.as_agent_id()));
+    let path = context.config().audience_topic(room.audience());
+
+    let notification_future = tokio::task::spawn(
+        async move {
+            let result =
+                export_transcript(&db, &metrics, data_encryption.as_deref(), s3_client, &room)
+                    .await;
+            jobs.finish(job_id, &result);
+
+            let result = match result {
+                Ok(s3_uri) => TranscriptResult::Success {
+                    room_id: room.id(),
+                    s3_uri,
+                },
+                Err(err) => {
+                    error!("Transcript export job failed: {:?}", err);
+                    let app_error = AppError::new(AppErrorKind::S3UploadFailed, err);
+                    app_error.notify_sentry();
+                    TranscriptResult::Error {
+                        error: app_error.to_payload(),
+                    }
+                }
+            };
+
+            let notification = TranscriptNotification {
+                status: result.status(),
+                tags: room.tags().map(|t| t.to_owned()),
+                result,
+            };
+
+            let task_status = match notification.result {
+                TranscriptResult::Success { .. } => TaskStatus::Succeeded,
+                TranscriptResult::Error { .. } => TaskStatus::Failed,
+            };
+
+            helpers::finish_task(&db, &metrics, task_id, task_status, &notification).await;
+
+            let timing = ShortTermTimingProperties::new(Utc::now());
+            let props = OutgoingEventProperties::new("room.transcript", timing);
+            let event = OutgoingEvent::broadcast(notification, props, &path);
+
+            Box::new(event) as Message
+        }
+        .instrument(span),
+    );
+
+    let mut response = AppResponse::new(
+        ResponseStatus::ACCEPTED,
+        json!({ "task_id": task_id }),
+        context.start_timestamp(),
+        Some(authz_time),
+    );
+
+    response.add_async_task(notification_future);
+
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::event::TranscriptEntry;
+    use crate::test_helpers::prelude::*;
+
+    #[tokio::test]
+    async fn read_transcript() {
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let db = TestDb::new().await;
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+
+            let message = |label: &str, text: &str, occurred_at: i64| {
+                factory::Event::new()
+                    .room_id(room.id())
+                    .kind("message")
+                    .set("messages")
+                    .label(label)
+                    .data(&json!({ "text": text }))
+                    .occurred_at(occurred_at)
+                    .created_by(agent.agent_id())
+            };
+
+            message("m1", "first", 1_000_000_000)
+                .insert(&mut conn)
+                .await;
+            message("m2", "second", 2_000_000_000)
+                .insert(&mut conn)
+                .await;
+            message("m3", "third", 3_000_000_000)
+                .insert(&mut conn)
+                .await;
+
+            // Edited message keeps its place.
+            message("m1", "first edited", 4_000_000_000)
+                .insert(&mut conn)
+                .await;
+
+            message("m2", "", 5_000_000_000)
+                .removed(true)
+                .insert(&mut conn)
+                .await;
+
+            room
+        };
+
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        authz.allow(
+            agent.account_id(),
+            vec!["classrooms", &classroom_id],
+            "read",
+        );
+
+        let mut context = TestContext::new(db, authz);
+
+        let payload = TranscriptRequest {
+            id: room.id(),
+            payload: TranscriptPayload {
+                limit: Some(1),
+                ..Default::default()
+            },
+        };
+
+        let messages = handle_request::<TranscriptHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Transcript reading failed");
+
+        let (entries, respp, _) = find_response::<Vec<TranscriptEntry>>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].data()["text"], "first edited");
+
+        // The next page skips the removed message.
+        let payload = TranscriptRequest {
+            id: room.id(),
+            payload: TranscriptPayload {
+                after: Some(entries[0].occurred_at()),
+                ..Default::default()
+            },
+        };
+
+        let messages = handle_request::<TranscriptHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Transcript reading failed");
+
+        let (entries, _, _) = find_response::<Vec<TranscriptEntry>>(messages.as_slice());
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].data()["text"], "third");
+    }
+
+    #[tokio::test]
+    async fn export_transcript() {
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let db = TestDb::new().await;
+        let mut authz = TestAuthz::new();
+        authz.allow(agent.account_id(), vec!["classrooms"], "dump_events");
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut context = TestContext::new(db, authz);
+        context.set_s3(shared_helpers::mock_s3());
+
+        let payload = TranscriptRequest {
+            id: room.id(),
+            payload: TranscriptPayload {
+                export: true,
+                ..Default::default()
+            },
+        };
+
+        let messages = handle_request::<TranscriptHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Transcript export failed");
+
+        let (_, respp, _) = find_response::<JsonValue>(messages.as_slice());
+        let (ev, evp, _) = find_event::<JsonValue>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::ACCEPTED);
+        assert_eq!(evp.label(), "room.transcript");
+        assert_eq!(
+            ev["result"]["s3_uri"],
+            format!(
+                "s3://eventsdump.{}.{}/{}.transcript.json",
+                room.kind(),
+                room.audience(),
+                room.id()
+            )
+        );
+    }
+}
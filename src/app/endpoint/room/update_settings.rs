@@ -0,0 +1,239 @@
+use async_trait::async_trait;
+use serde_derive::Deserialize;
+use serde_json::Value as JsonValue;
+use svc_agent::mqtt::ResponseStatus;
+use tracing::{field::display, instrument, Span};
+use uuid::Uuid;
+
+use super::*;
+use crate::app::context::Context;
+use crate::db::room::UpdateSettingsQuery;
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateSettingsPayload {
+    /// JSON merge patch of the room settings.
+    settings: JsonValue,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateSettingsRequest {
+    id: Uuid,
+    #[serde(flatten)]
+    payload: UpdateSettingsPayload,
+}
+
+pub async fn update_settings(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<Uuid>,
+    Json(payload): Json<UpdateSettingsPayload>,
+) -> RequestResult {
+    let request = UpdateSettingsRequest {
+        id: room_id,
+        payload,
+    };
+    UpdateSettingsHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct UpdateSettingsHandler;
+
+#[async_trait]
+impl RequestHandler for UpdateSettingsHandler {
+    type Payload = UpdateSettingsRequest;
+
+    #[instrument(skip_all, fields(room_id, scope, classroom_id))]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload { id, payload }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        Span::current().record("room_id", &display(id));
+
+        let room = helpers::find_room(context, id, helpers::RoomTimeRequirement::Any).await?;
+
+        let object = AuthzObject::room(&room).into();
+
+        let authz_time = context
+            .authz()
+            .authorize_room(
+                &room,
+                reqp.as_account_id().to_owned(),
+                object,
+                "update".into(),
+            )
+            .await?;
+
+        // The patch itself is applied in the DB so that concurrent updates of different
+        // settings don't overwrite each other.
+        room.settings()
+            .merge(&payload.settings)
+            .error(AppErrorKind::InvalidPayload)?;
+
+        let room = {
+            let query = UpdateSettingsQuery::new(room.id(), payload.settings);
+            let mut conn = context.get_conn().await?;
+
+            context
+                .metrics()
+                .measure_query(QueryKey::RoomUpdateSettingsQuery, query.execute(&mut conn))
+                .await
+                .context("Failed to update room settings")
+                .error(AppErrorKind::DbQueryFailed)?
+        };
+
+        context.room_cache().invalidate(room.id());
+
+        let mut response = AppResponse::new(
+            ResponseStatus::OK,
+            room.clone(),
+            context.start_timestamp(),
+            Some(authz_time),
+        );
+
+        response.add_notification(
+            "room.update",
+            &context.config().audience_topic(room.audience()),
+            room,
+            context.start_timestamp(),
+        )?;
+
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::db::room::{Object as Room, Settings};
+    use crate::test_helpers::prelude::*;
+
+    fn allow_update(authz: &mut TestAuthz, agent: &TestAgent, room: &Room) {
+        let classroom_id = room.classroom_id().to_string();
+        authz.allow(
+            agent.account_id(),
+            vec!["classrooms", &classroom_id],
+            "update",
+        );
+    }
+
+    #[tokio::test]
+    async fn update_settings() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        assert!(room.settings().agent_actions());
+
+        let mut authz = TestAuthz::new();
+        allow_update(&mut authz, &agent, &room);
+        let mut context = TestContext::new(db, authz);
+
+        let payload = UpdateSettingsRequest {
+            id: room.id(),
+            payload: UpdateSettingsPayload {
+                settings: json!({ "agent_actions": false }),
+            },
+        };
+
+        let messages = handle_request::<UpdateSettingsHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Room settings update failed");
+
+        let (resp_room, respp, _) = find_response::<Room>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert!(!resp_room.settings().agent_actions());
+
+        let (evp_room, evp, topic) = find_event::<Room>(messages.as_slice());
+        assert_eq!(evp.label(), "room.update");
+        assert!(topic.ends_with(&format!("/audiences/{USR_AUDIENCE}/events")));
+        assert!(!evp_room.settings().agent_actions());
+
+        // `null` resets the setting to its default.
+        let payload = UpdateSettingsRequest {
+            id: room.id(),
+            payload: UpdateSettingsPayload {
+                settings: json!({ "agent_actions": null }),
+            },
+        };
+
+        let messages = handle_request::<UpdateSettingsHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Room settings update failed");
+
+        let (resp_room, _, _) = find_response::<Room>(messages.as_slice());
+        assert_eq!(resp_room.settings(), &Settings::default());
+        assert!(resp_room.settings().agent_actions());
+    }
+
+    #[tokio::test]
+    async fn update_settings_invalid() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut authz = TestAuthz::new();
+        allow_update(&mut authz, &agent, &room);
+        let mut context = TestContext::new(db, authz);
+
+        for settings in [
+            json!({ "agent_actions": "no" }),
+            json!({ "unknown_setting": true }),
+            json!([]),
+        ] {
+            let payload = UpdateSettingsRequest {
+                id: room.id(),
+                payload: UpdateSettingsPayload { settings },
+            };
+
+            let err = handle_request::<UpdateSettingsHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on room settings update");
+
+            assert_eq!(err.status(), ResponseStatus::BAD_REQUEST);
+            assert_eq!(err.kind(), "invalid_payload");
+        }
+    }
+
+    #[tokio::test]
+    async fn update_settings_not_authorized() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut context = TestContext::new(db, TestAuthz::new());
+
+        let payload = UpdateSettingsRequest {
+            id: room.id(),
+            payload: UpdateSettingsPayload {
+                settings: json!({ "agent_actions": false }),
+            },
+        };
+
+        let err = handle_request::<UpdateSettingsHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success on room settings update");
+
+        assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+    }
+}
@@ -0,0 +1,293 @@
+use async_trait::async_trait;
+use serde_derive::{Deserialize, Serialize};
+use svc_agent::mqtt::ResponseStatus;
+use uuid::Uuid;
+
+use super::*;
+use crate::app::context::Context;
+use crate::db;
+use crate::db::event::{Checksum, Diff};
+
+/// Max number of reported discrepancies of each kind.
+const MAX_DISCREPANCIES: i64 = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyRequest {
+    id: Uuid,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RoomChecksum {
+    room_id: Uuid,
+    #[serde(flatten)]
+    checksum: Checksum,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct VerifyResponse {
+    room: RoomChecksum,
+    source_room: RoomChecksum,
+    #[serde(flatten)]
+    diff: Diff,
+}
+
+pub async fn verify(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<Uuid>,
+) -> RequestResult {
+    let request = VerifyRequest { id: room_id };
+    VerifyHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct VerifyHandler;
+
+#[async_trait]
+impl RequestHandler for VerifyHandler {
+    type Payload = VerifyRequest;
+
+    #[instrument(
+        skip_all,
+        fields(
+            room_id = %payload.id, scope, classroom_id
+        )
+    )]
+    async fn handle<C: Context>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let room =
+            helpers::find_room(context, payload.id, helpers::RoomTimeRequirement::Any).await?;
+
+        let source_room_id = match room.source_room_id() {
+            Some(source_room_id) => source_room_id,
+            None => return Err(anyhow!("Room is not derived")).error(AppErrorKind::InvalidPayload),
+        };
+
+        // Auditing is allowed to those who can adjust the room.
+        let object = AuthzObject::room(&room).into();
+
+        let authz_time = context
+            .authz()
+            .authorize_room(
+                &room,
+                reqp.as_account_id().to_owned(),
+                object,
+                "update".into(),
+            )
+            .await?;
+
+        let mut conn = context.get_ro_conn().await?;
+        let mut checksums = Vec::with_capacity(2);
+
+        for room_id in [room.id(), source_room_id] {
+            let query = db::event::ChecksumQuery::new(room_id);
+
+            let checksum = context
+                .metrics()
+                .measure_query(QueryKey::EventChecksumQuery, query.execute(&mut conn))
+                .await
+                .context("Failed to calculate room checksum")
+                .error(AppErrorKind::DbQueryFailed)?;
+
+            checksums.push(RoomChecksum { room_id, checksum });
+        }
+
+        let diff = {
+            let query = db::event::DiffQuery::new(source_room_id, room.id(), MAX_DISCREPANCIES);
+
+            context
+                .metrics()
+                .measure_query(QueryKey::EventDiffQuery, query.execute(&mut conn))
+                .await
+                .context("Failed to compare room events with the source room")
+                .error(AppErrorKind::DbQueryFailed)?
+        };
+
+        let source_room = checksums.pop().expect("Missing source room checksum");
+        let room = checksums.pop().expect("Missing room checksum");
+
+        Ok(AppResponse::new(
+            ResponseStatus::OK,
+            VerifyResponse {
+                room,
+                source_room,
+                diff,
+            },
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::db::event::Object as Event;
+    use crate::test_helpers::prelude::*;
+
+    async fn clone_event(
+        conn: &mut sqlx::PgConnection,
+        room: &db::room::Object,
+        event: &Event,
+        data: &JsonValue,
+    ) -> Event {
+        db::event::InsertQuery::new(
+            room.id(),
+            event.kind().to_owned(),
+            data.to_owned(),
+            event.occurred_at() + 1_000_000_000,
+            event.created_by().to_owned(),
+            None,
+        )
+        .expect("Failed to build event insert query")
+        .set(event.set().to_owned())
+        .created_at(event.created_at())
+        .execute(conn)
+        .await
+        .expect("Failed to insert event")
+    }
+
+    #[tokio::test]
+    async fn verify_derived_room() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let (room, missing, mutated, extra) = {
+            let mut conn = db.get_conn().await;
+            let source = shared_helpers::insert_room(&mut conn).await;
+
+            let room = factory::Room::new(source.classroom_id(), ClassType::Webinar)
+                .audience(USR_AUDIENCE)
+                .time((Bound::Included(Utc::now()), Bound::Unbounded))
+                .source_room_id(source.id())
+                .insert(&mut conn)
+                .await;
+
+            let mut events = vec![];
+
+            for (kind, occurred_at) in [("message", 1000), ("layout", 2000), ("stream", 3000)] {
+                let event = factory::Event::new()
+                    .room_id(source.id())
+                    .kind(kind)
+                    .set(kind)
+                    .data(&json!({ "occurred_at": occurred_at }))
+                    .occurred_at(occurred_at)
+                    .created_by(agent.agent_id())
+                    .insert(&mut conn)
+                    .await;
+
+                events.push(event);
+            }
+
+            // The first event is cloned faithfully, the second one is mutated and the third one is lost.
+            clone_event(&mut conn, &room, &events[0], events[0].data()).await;
+            let mutated = clone_event(&mut conn, &room, &events[1], &json!({ "foo": "bar" })).await;
+
+            let extra = factory::Event::new()
+                .room_id(room.id())
+                .kind("message")
+                .set("message")
+                .data(&json!({ "text": "extra" }))
+                .occurred_at(5000)
+                .created_by(agent.agent_id())
+                .insert(&mut conn)
+                .await;
+
+            (room, events[2].clone(), mutated, extra)
+        };
+
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        authz.allow(
+            agent.account_id(),
+            vec!["classrooms", &classroom_id],
+            "update",
+        );
+
+        let mut context = TestContext::new(db, authz);
+        let payload = VerifyRequest { id: room.id() };
+
+        let messages = handle_request::<VerifyHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Room verification failed");
+
+        let (resp, respp, _) = find_response::<JsonValue>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert_eq!(resp["room"]["room_id"], json!(room.id()));
+        assert_eq!(resp["room"]["events_count"], 3);
+        assert_eq!(resp["source_room"]["events_count"], 3);
+        assert_ne!(resp["room"]["checksum"], resp["source_room"]["checksum"]);
+
+        assert_eq!(resp["missing"]["total"], 1);
+        assert_eq!(
+            resp["missing"]["events"][0]["source_event_id"],
+            json!(missing.id())
+        );
+        assert_eq!(resp["mutated"]["total"], 1);
+        assert_eq!(
+            resp["mutated"]["events"][0]["event_id"],
+            json!(mutated.id())
+        );
+        assert_eq!(resp["extra"]["total"], 1);
+        assert_eq!(resp["extra"]["events"][0]["event_id"], json!(extra.id()));
+    }
+
+    #[tokio::test]
+    async fn verify_not_derived_room() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut context = TestContext::new(db, TestAuthz::new());
+        let payload = VerifyRequest { id: room.id() };
+
+        let err = handle_request::<VerifyHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success verifying not derived room");
+
+        assert_eq!(err.status(), ResponseStatus::BAD_REQUEST);
+        assert_eq!(err.kind(), "invalid_payload");
+    }
+
+    #[tokio::test]
+    async fn verify_not_authorized() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            let source = shared_helpers::insert_room(&mut conn).await;
+
+            factory::Room::new(source.classroom_id(), ClassType::Webinar)
+                .audience(USR_AUDIENCE)
+                .time((Bound::Included(Utc::now()), Bound::Unbounded))
+                .source_room_id(source.id())
+                .insert(&mut conn)
+                .await
+        };
+
+        let mut context = TestContext::new(db, TestAuthz::new());
+        let payload = VerifyRequest { id: room.id() };
+
+        let err = handle_request::<VerifyHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success verifying room without authorization");
+
+        assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+    }
+}
@@ -0,0 +1,561 @@
+use std::sync::Arc;
+
+use anyhow::Context as AnyhowContext;
+use async_trait::async_trait;
+use axum::extract::{self, Path, Query};
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+use svc_agent::mqtt::ResponseStatus;
+use svc_utils::extractors::AgentIdExtractor;
+use tracing::{field::display, instrument, Span};
+use uuid::Uuid;
+
+use crate::app::context::Context;
+use crate::app::endpoint::prelude::*;
+use crate::db;
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct CountSincePayload {
+    /// `occurred_at` of the latest event the client has seen.
+    occurred_at: Option<i64>,
+    /// `seq` of the latest event the client has seen.
+    seq: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CountSinceRequest {
+    room_id: Uuid,
+    set: String,
+    #[serde(flatten)]
+    payload: CountSincePayload,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CountSinceResponse {
+    count: i64,
+}
+
+pub async fn count_since(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path((room_id, set)): Path<(Uuid, String)>,
+    Query(payload): Query<CountSincePayload>,
+) -> RequestResult {
+    let request = CountSinceRequest {
+        room_id,
+        set,
+        payload,
+    };
+    CountSinceHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct CountSinceHandler;
+
+#[async_trait]
+impl RequestHandler for CountSinceHandler {
+    type Payload = CountSinceRequest;
+
+    #[instrument(skip_all, fields(room_id, scope, classroom_id))]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload {
+            room_id,
+            set,
+            payload,
+        }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        Span::current().record("room_id", &display(room_id));
+
+        let query = db::event::CountSinceQuery::new(room_id, set);
+
+        let query = match (payload.occurred_at, payload.seq) {
+            (Some(occurred_at), None) => query.occurred_at(occurred_at),
+            (None, Some(seq)) => query.seq(seq),
+            _ => {
+                return Err(anyhow!("Either 'occurred_at' or 'seq' must be specified"))
+                    .error(AppErrorKind::InvalidPayload);
+            }
+        };
+
+        let room = helpers::find_room(context, room_id, helpers::RoomTimeRequirement::Any).await?;
+
+        // Authorize room events listing.
+        let classroom_id = room.classroom_id().to_string();
+        let object = AuthzObject::new(&["classrooms", &classroom_id]).into();
+
+        let authz_time = context
+            .authz()
+            .authorize_room(
+                &room,
+                reqp.as_account_id().to_owned(),
+                object,
+                "read".into(),
+            )
+            .await?;
+
+        let count = {
+            let mut conn = context.get_ro_conn().await?;
+
+            context
+                .metrics()
+                .measure_query(QueryKey::EventCountSinceQuery, query.execute(&mut conn))
+                .await
+                .context("Failed to count set events")
+                .error(AppErrorKind::DbQueryFailed)?
+        };
+
+        Ok(AppResponse::new(
+            ResponseStatus::OK,
+            CountSinceResponse { count },
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct ListRequest {
+    room_id: Uuid,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SetHead {
+    set: String,
+    /// Number of labels in the set, events without a label are counted on their own.
+    label_count: i64,
+    /// Creation time of the latest event in the set.
+    #[serde(with = "chrono::serde::ts_milliseconds")]
+    updated_at: DateTime<Utc>,
+}
+
+impl From<db::set_head::Object> for SetHead {
+    fn from(object: db::set_head::Object) -> Self {
+        Self {
+            set: object.set().to_owned(),
+            label_count: object.label_count(),
+            updated_at: object.updated_at(),
+        }
+    }
+}
+
+pub async fn list(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<Uuid>,
+) -> RequestResult {
+    ListHandler::handle(
+        &mut ctx.start_message(),
+        ListRequest { room_id },
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct ListHandler;
+
+#[async_trait]
+impl RequestHandler for ListHandler {
+    type Payload = ListRequest;
+
+    #[instrument(skip_all, fields(room_id, scope, classroom_id))]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload { room_id }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        Span::current().record("room_id", &display(room_id));
+
+        let room = helpers::find_room(context, room_id, helpers::RoomTimeRequirement::Any).await?;
+
+        // Authorize room events listing.
+        let classroom_id = room.classroom_id().to_string();
+        let object = AuthzObject::new(&["classrooms", &classroom_id]).into();
+
+        let authz_time = context
+            .authz()
+            .authorize_room(
+                &room,
+                reqp.as_account_id().to_owned(),
+                object,
+                "read".into(),
+            )
+            .await?;
+
+        let sets = {
+            let mut conn = context.get_ro_conn().await?;
+            let query = db::set_head::ListQuery::new(room_id);
+
+            context
+                .metrics()
+                .measure_query(QueryKey::SetHeadListQuery, query.execute(&mut conn))
+                .await
+                .context("Failed to list sets")
+                .error(AppErrorKind::DbQueryFailed)?
+        };
+
+        let sets = sets.into_iter().map(SetHead::from).collect::<Vec<_>>();
+
+        Ok(AppResponse::new(
+            ResponseStatus::OK,
+            sets,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::db::event::ModerationStatus;
+    use crate::test_helpers::prelude::*;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn count_since() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let (room, seq) = {
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+            let mut events = vec![];
+
+            for (label, occurred_at, removed) in [
+                ("message-1", 1000, false),
+                ("message-2", 2000, false),
+                // An edition of the second message.
+                ("message-2", 3000, false),
+                ("message-3", 4000, false),
+                // Removal of the first message.
+                ("message-1", 5000, true),
+            ] {
+                let event = factory::Event::new()
+                    .room_id(room.id())
+                    .kind("message")
+                    .set("messages")
+                    .label(label)
+                    .data(&json!({ "text": label }))
+                    .occurred_at(occurred_at)
+                    .created_by(&agent.agent_id())
+                    .removed(removed)
+                    .insert(&mut conn)
+                    .await;
+
+                events.push(event);
+            }
+
+            // Neither other sets nor pending events are counted.
+            factory::Event::new()
+                .room_id(room.id())
+                .kind("message")
+                .set("reactions")
+                .data(&json!({}))
+                .occurred_at(6000)
+                .created_by(&agent.agent_id())
+                .insert(&mut conn)
+                .await;
+
+            factory::Event::new()
+                .room_id(room.id())
+                .kind("message")
+                .set("messages")
+                .label("message-4")
+                .data(&json!({}))
+                .occurred_at(7000)
+                .created_by(&agent.agent_id())
+                .moderation_status(ModerationStatus::Pending)
+                .insert(&mut conn)
+                .await;
+
+            // The final `seq` is assigned on commit so the one returned on insert won't do.
+            let seq: Option<i64> = sqlx::query_scalar("SELECT seq FROM event WHERE id = $1")
+                .bind(events[2].id())
+                .fetch_one(&mut conn)
+                .await
+                .expect("Failed to fetch event seq");
+
+            (room, seq)
+        };
+
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        authz.allow(
+            agent.account_id(),
+            vec!["classrooms", &classroom_id],
+            "read",
+        );
+
+        let mut context = TestContext::new(db, authz);
+
+        let payload = CountSinceRequest {
+            room_id: room.id(),
+            set: "messages".to_owned(),
+            payload: CountSincePayload {
+                occurred_at: Some(1000),
+                seq: None,
+            },
+        };
+
+        let messages = handle_request::<CountSinceHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Set count failed");
+
+        let (resp, respp, _) = find_response::<CountSinceResponse>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert_eq!(resp.count, 2);
+
+        let payload = CountSinceRequest {
+            room_id: room.id(),
+            set: "messages".to_owned(),
+            payload: CountSincePayload {
+                occurred_at: None,
+                seq,
+            },
+        };
+
+        let messages = handle_request::<CountSinceHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Set count failed");
+
+        let (resp, _, _) = find_response::<CountSinceResponse>(messages.as_slice());
+        assert_eq!(resp.count, 1);
+    }
+
+    #[tokio::test]
+    async fn count_since_without_bound() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut context = TestContext::new(db, TestAuthz::new());
+
+        let payload = CountSinceRequest {
+            room_id: room.id(),
+            set: "messages".to_owned(),
+            payload: CountSincePayload {
+                occurred_at: None,
+                seq: None,
+            },
+        };
+
+        let err = handle_request::<CountSinceHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success on set count");
+
+        assert_eq!(err.status(), ResponseStatus::BAD_REQUEST);
+        assert_eq!(err.kind(), "invalid_payload");
+    }
+
+    #[tokio::test]
+    async fn count_since_not_authorized() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut context = TestContext::new(db, TestAuthz::new());
+
+        let payload = CountSinceRequest {
+            room_id: room.id(),
+            set: "messages".to_owned(),
+            payload: CountSincePayload {
+                occurred_at: Some(0),
+                seq: None,
+            },
+        };
+
+        let err = handle_request::<CountSinceHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success on set count");
+
+        assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn list() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let (room, pending) = {
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+
+            for (set, label, occurred_at) in [
+                ("messages", Some("message-1"), 1000),
+                // An edition of the first message.
+                ("messages", Some("message-1"), 2000),
+                ("messages", Some("message-2"), 3000),
+                ("reactions", None, 4000),
+                ("reactions", None, 5000),
+            ] {
+                let mut event = factory::Event::new()
+                    .room_id(room.id())
+                    .kind("message")
+                    .set(set)
+                    .data(&json!({}))
+                    .occurred_at(occurred_at)
+                    .created_by(&agent.agent_id());
+
+                if let Some(label) = label {
+                    event = event.label(label);
+                }
+
+                event.insert(&mut conn).await;
+            }
+
+            let pending = factory::Event::new()
+                .room_id(room.id())
+                .kind("message")
+                .set("messages")
+                .label("message-3")
+                .data(&json!({}))
+                .occurred_at(6000)
+                .created_by(&agent.agent_id())
+                .moderation_status(ModerationStatus::Pending)
+                .insert(&mut conn)
+                .await;
+
+            (room, pending)
+        };
+
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        authz.allow(
+            agent.account_id(),
+            vec!["classrooms", &classroom_id],
+            "read",
+        );
+
+        let mut context = TestContext::new(db.clone(), authz);
+        let payload = ListRequest { room_id: room.id() };
+
+        let messages = handle_request::<ListHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Set listing failed");
+
+        let (resp, respp, _) = find_response::<Vec<SetHead>>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+
+        let counts = resp
+            .iter()
+            .map(|head| (head.set.as_str(), head.label_count))
+            .collect::<Vec<_>>();
+
+        // Pending events aren't counted.
+        assert_eq!(counts, vec![("messages", 2), ("reactions", 2)]);
+
+        // Until they are approved.
+        {
+            let mut conn = db.get_conn().await;
+
+            db::event::ModerationUpdateQuery::new(
+                room.id(),
+                pending.id(),
+                ModerationStatus::Approved,
+            )
+            .execute(&mut conn)
+            .await
+            .expect("Failed to approve event");
+        }
+
+        let payload = ListRequest { room_id: room.id() };
+
+        let messages = handle_request::<ListHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Set listing failed");
+
+        let (resp, _, _) = find_response::<Vec<SetHead>>(messages.as_slice());
+        assert_eq!(resp[0].label_count, 3);
+
+        assert_eq!(
+            resp[0].updated_at.timestamp_millis(),
+            pending.created_at().timestamp_millis()
+        );
+
+        // Neither removed labels nor deleted events are counted.
+        {
+            let mut conn = db.get_conn().await;
+
+            factory::Event::new()
+                .room_id(room.id())
+                .kind("message")
+                .set("messages")
+                .label("message-2")
+                .data(&json!({}))
+                .occurred_at(7000)
+                .created_by(agent.agent_id())
+                .removed(true)
+                .insert(&mut conn)
+                .await;
+
+            sqlx::query(
+                "UPDATE event SET deleted_at = NOW() WHERE room_id = $1 AND set = 'reactions' AND occurred_at = 5000",
+            )
+            .bind(room.id())
+            .execute(&mut conn)
+            .await
+            .expect("Failed to delete event");
+        }
+
+        let payload = ListRequest { room_id: room.id() };
+
+        let messages = handle_request::<ListHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Set listing failed");
+
+        let (resp, _, _) = find_response::<Vec<SetHead>>(messages.as_slice());
+
+        let counts = resp
+            .iter()
+            .map(|head| (head.set.as_str(), head.label_count))
+            .collect::<Vec<_>>();
+
+        assert_eq!(counts, vec![("messages", 2), ("reactions", 1)]);
+    }
+
+    #[tokio::test]
+    async fn list_not_authorized() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut context = TestContext::new(db, TestAuthz::new());
+        let payload = ListRequest { room_id: room.id() };
+
+        let err = handle_request::<ListHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success on set listing");
+
+        assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+    }
+}
@@ -2,16 +2,22 @@ use std::ops::Bound;
 
 use anyhow::Context as AnyhowContext;
 use async_trait::async_trait;
-use axum::extract::{self, Path, RawQuery};
+use axum::{
+    extract::{self, Path, RawQuery},
+    http::HeaderMap,
+};
 use serde_derive::Deserialize;
 use serde_json::{map::Map as JsonMap, Value as JsonValue};
+use sqlx::postgres::PgConnection;
 use svc_agent::mqtt::ResponseStatus;
 use svc_utils::extractors::AgentIdExtractor;
 use tracing::{field::display, instrument, Span};
+use utoipa::IntoParams;
 use uuid::Uuid;
 
-use crate::app::context::Context;
+use crate::app::context::{Context, GlobalContext};
 use crate::app::endpoint::prelude::*;
+use crate::app::share_token::{ShareToken, ShareTokenExtractor};
 use crate::db;
 
 ///////////////////////////////////////////////////////////////////////////////
@@ -19,13 +25,16 @@ use crate::db;
 const MAX_SETS: usize = 10;
 const MAX_LIMIT_PER_SET: i64 = 100;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct ReadPayload {
     sets: Vec<String>,
     attribute: Option<String>,
     occurred_at: Option<i64>,
     original_occurred_at: Option<i64>,
     limit: Option<i64>,
+    /// Comma separated list of event fields to return.
+    fields: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -33,18 +42,39 @@ pub struct ReadRequest {
     room_id: Uuid,
     #[serde(flatten)]
     payload: ReadPayload,
+    /// `If-None-Match` header of a conditional HTTP request.
+    #[serde(skip)]
+    if_none_match: Option<String>,
 }
 
+#[utoipa::path(
+    get,
+    path = "/rooms/{id}/state",
+    tag = "state",
+    params(("id" = Uuid, Path, description = "Room identifier"), ReadPayload),
+    responses(
+        (status = 200, description = "Set names mapped to their events, with `has_next` for a single set"),
+        (status = 304, description = "State hasn't changed since `If-None-Match`"),
+        (status = 404, description = "Room not found", body = ErrorPayload),
+        (status = 422, description = "Invalid sets", body = ErrorPayload),
+    ),
+    security(("bearer" = []))
+)]
 pub async fn read(
     ctx: extract::Extension<Arc<AppContext>>,
     AgentIdExtractor(agent_id): AgentIdExtractor,
     Path(room_id): Path<Uuid>,
     RawQuery(query): RawQuery,
+    headers: HeaderMap,
 ) -> RequestResult {
     let payload = serde_qs::from_str(&query.unwrap_or_default())
         .context("Failed to parse qs")
         .error(AppErrorKind::InvalidQueryString)?;
-    let request = ReadRequest { room_id, payload };
+    let request = ReadRequest {
+        room_id,
+        payload,
+        if_none_match: helpers::if_none_match(&headers),
+    };
     ReadHandler::handle(
         &mut ctx.start_message(),
         request,
@@ -55,6 +85,34 @@ pub async fn read(
     .await
 }
 
+/// `state.read` for viewers with a share token of the room instead of authentication.
+pub async fn shared_read(
+    ctx: extract::Extension<Arc<AppContext>>,
+    ShareTokenExtractor(token): ShareTokenExtractor,
+    Path(room_id): Path<Uuid>,
+    RawQuery(query): RawQuery,
+    headers: HeaderMap,
+) -> RequestResult {
+    let payload = serde_qs::from_str(&query.unwrap_or_default())
+        .context("Failed to parse qs")
+        .error(AppErrorKind::InvalidQueryString)?;
+    let agent_id = ShareToken::viewer_agent_id(ctx.agent_id().as_account_id());
+    let request = ReadRequest {
+        room_id,
+        payload,
+        if_none_match: helpers::if_none_match(&headers),
+    };
+    ReadHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Shared {
+            agent_id: &agent_id,
+            room_id: token.room_id(),
+        },
+    )
+    .await
+}
+
 pub struct ReadHandler;
 
 #[async_trait]
@@ -64,7 +122,11 @@ impl RequestHandler for ReadHandler {
     #[instrument(skip_all, fields(room_id, scope, classroom_id))]
     async fn handle<C: Context>(
         context: &mut C,
-        Self::Payload { room_id, payload }: Self::Payload,
+        Self::Payload {
+            room_id,
+            payload,
+            if_none_match,
+        }: Self::Payload,
         reqp: RequestParams<'_>,
     ) -> RequestResult {
         Span::current().record("room_id", &display(room_id));
@@ -80,6 +142,13 @@ impl RequestHandler for ReadHandler {
             return Err(err).error(AppErrorKind::InvalidStateSets);
         }
 
+        let fields = payload
+            .fields
+            .as_deref()
+            .map(|fields| fields.parse::<helpers::EventFields>())
+            .transpose()
+            .error(AppErrorKind::InvalidPayload)?;
+
         // Choose limit.
         let limit = std::cmp::min(
             payload.limit.unwrap_or(MAX_LIMIT_PER_SET),
@@ -91,90 +160,78 @@ impl RequestHandler for ReadHandler {
 
         // Authorize room events listing.
         let classroom_id = room.classroom_id().to_string();
-        let object = AuthzObject::new(&["classrooms", &classroom_id]).into();
-
-        let authz_time = context
-            .authz()
-            .authorize(
-                room.audience().into(),
-                reqp.as_account_id().to_owned(),
-                object,
-                "read".into(),
-            )
-            .await?;
-
-        // Default `occurred_at`: closing time of the room.
-        let time = room.time().map(|t| t.into());
-        let original_occurred_at = if let Some(original_occurred_at) = payload.original_occurred_at
-        {
-            original_occurred_at
-        } else if let Ok((_, Bound::Unbounded)) = time {
-            std::i64::MAX
-        } else if let Ok((Bound::Included(open), Bound::Excluded(close))) = time {
-            (close - open)
-                .num_nanoseconds()
-                .map(|n| n + 1)
-                .unwrap_or(std::i64::MAX)
-        } else {
-            return Err(anyhow!("Bad room time")).error(AppErrorKind::InvalidRoomTime);
+        let object = AuthzObject::new(&["classrooms", &classroom_id]);
+        let authz_time = helpers::authorize_room(context, &room, reqp, object, "read").await?;
+
+        // Moderator-only parts of event data are hidden from the others.
+        let (redaction, redaction_authz_time) =
+            helpers::Redaction::for_agent(context, &room, reqp).await?;
+
+        let authz_time = authz_time + redaction_authz_time.unwrap_or_else(chrono::Duration::zero);
+
+        let original_occurred_at = match payload.original_occurred_at {
+            Some(original_occurred_at) => original_occurred_at,
+            None => default_original_occurred_at(&room)?,
         };
 
+        // The same events may be shaped differently by the parameters and redaction
+        // so they get into the tag along with the room version.
+        let shape = (
+            &payload.sets,
+            &payload.attribute,
+            payload.occurred_at,
+            original_occurred_at,
+            limit,
+            &fields,
+            !redaction.is_empty(),
+        );
+
+        let etag = helpers::room_events_etag(context, &room, shape).await?;
+
+        if helpers::etag_matches(if_none_match.as_deref(), &etag) {
+            return Ok(AppResponse::not_modified(
+                etag,
+                context.start_timestamp(),
+                Some(authz_time),
+            ));
+        }
+
         // Retrieve state for each set from the DB and put them into a map.
         let mut state = JsonMap::new();
         let mut conn = context.get_ro_conn().await?;
 
+        let params = SetStateParams {
+            original_occurred_at,
+            occurred_at: payload.occurred_at,
+            attribute: payload.attribute.as_deref(),
+            limit,
+            // Skip fetching and decoding heavy event data when it's not requested.
+            without_data: matches!(fields, Some(ref fields) if !fields.contains("data")),
+            // `has_next` pagination flag is added for a single set only.
+            with_has_next: payload.sets.len() == 1,
+        };
+
         for set in payload.sets.iter() {
             Span::current().record("set", set.as_str());
 
-            // Build a query for the particular set state.
-            let mut query =
-                db::event::SetStateQuery::new(room.id(), set.clone(), original_occurred_at, limit);
+            let (mut set_state, has_next) =
+                read_set_state(context, &mut conn, room.id(), set, &params).await?;
 
-            if let Some(ref attribute) = payload.attribute {
-                query = query.attribute(attribute);
-            }
-
-            if let Some(occurred_at) = payload.occurred_at {
-                query = query.occurred_at(occurred_at);
-            }
-
-            // If it is the only set specified at first execute a total count query and
-            // add `has_next` pagination flag to the state.
-            if payload.sets.len() == 1 {
-                let total_count = context
-                    .metrics()
-                    .measure_query(QueryKey::StateTotalCountQuery, query.total_count(&mut conn))
-                    .await
-                    .context("Failed to get state total count")
-                    .error(AppErrorKind::DbQueryFailed)?;
-
-                let has_next = total_count > limit;
+            if let Some(has_next) = has_next {
                 state.insert(String::from("has_next"), JsonValue::Bool(has_next));
             }
 
-            // Limit the query and retrieve the state.
-            let set_state = context
-                .metrics()
-                .measure_query(QueryKey::StateQuery, query.execute(&mut conn))
-                .await
-                .context("Failed to get state")
-                .error(AppErrorKind::DbQueryFailed)?;
+            redaction.apply(&mut set_state);
 
             // Serialize to JSON and add to the state map.
-            let serialized_set_state = serde_json::to_value(set_state)
-                .context("Failed to serialize state")
-                .error(AppErrorKind::SerializationFailed)?;
-
-            match serialized_set_state.as_array().and_then(|a| a.first()) {
-                Some(event) if event.get("label").is_none() => {
-                    // The first event has no label => simple set with a single event…
-                    state.insert(set.to_owned(), event.to_owned());
-                }
-                _ => {
-                    // …or it's a collection.
-                    state.insert(set.to_owned(), serialized_set_state);
-                }
+            let mut set_state = serialize_set_state(set_state)?;
+
+            // Drop fields only after the set kind is determined by the label.
+            if let Some(ref fields) = fields {
+                fields.retain(&mut set_state);
             }
+
+            state.insert(set.to_owned(), set_state);
         }
 
         // Respond with state.
@@ -183,14 +240,147 @@ impl RequestHandler for ReadHandler {
             JsonValue::Object(state),
             context.start_timestamp(),
             Some(authz_time),
-        ))
+        )
+        .etag(etag))
+    }
+}
+
+/// Default `original_occurred_at` of the state: closing time of the room.
+pub(crate) fn default_original_occurred_at(room: &db::room::Object) -> Result<i64, AppError> {
+    let time = room.time().map(|t| t.into());
+
+    if let Ok((_, Bound::Unbounded)) = time {
+        Ok(std::i64::MAX)
+    } else if let Ok((Bound::Included(open), Bound::Excluded(close))) = time {
+        Ok((close - open)
+            .num_nanoseconds()
+            .map(|n| n + 1)
+            .unwrap_or(std::i64::MAX))
+    } else {
+        Err(anyhow!("Bad room time")).error(AppErrorKind::InvalidRoomTime)
     }
 }
 
+/// Parameters of a single set state reading shared by `state.read` and `room.bootstrap`.
+pub(crate) struct SetStateParams<'a> {
+    pub original_occurred_at: i64,
+    pub occurred_at: Option<i64>,
+    pub attribute: Option<&'a str>,
+    pub limit: i64,
+    pub without_data: bool,
+    /// Whether to find out if there are more elements than the limit.
+    pub with_has_next: bool,
+}
+
+/// Reads the set state along with `has_next` flag when it's requested.
+pub(crate) async fn read_set_state<C: Context>(
+    context: &mut C,
+    conn: &mut PgConnection,
+    room_id: Uuid,
+    set: &str,
+    params: &SetStateParams<'_>,
+) -> Result<(Vec<db::event::Object>, Option<bool>), AppError> {
+    if context.config().crdt.is_crdt_set(set) {
+        // Deltas are merged into elements in the app so the limit is applied afterwards.
+        let mut query =
+            db::event::crdt::Query::new(room_id, set.to_owned(), params.original_occurred_at);
+
+        if let Some(attribute) = params.attribute {
+            query = query.attribute(attribute);
+        }
+
+        if let Some(occurred_at) = params.occurred_at {
+            query = query.occurred_at(occurred_at);
+        }
+
+        let mut elements = context
+            .metrics()
+            .measure_query(QueryKey::CrdtStateQuery, query.execute(conn))
+            .await
+            .context("Failed to get CRDT state")
+            .error(AppErrorKind::DbQueryFailed)?;
+
+        let has_next = params
+            .with_has_next
+            .then(|| elements.len() as i64 > params.limit);
+
+        elements.truncate(params.limit.max(0) as usize);
+        return Ok((elements, has_next));
+    }
+
+    // Build a query for the particular set state.
+    let mut query = db::event::SetStateQuery::new(
+        room_id,
+        set.to_owned(),
+        params.original_occurred_at,
+        params.limit,
+    );
+
+    if let Some(attribute) = params.attribute {
+        query = query.attribute(attribute);
+    }
+
+    if let Some(occurred_at) = params.occurred_at {
+        query = query.occurred_at(occurred_at);
+    }
+
+    if params.without_data {
+        query = query.without_data();
+    }
+
+    // At first execute a total count query to tell whether there're more elements.
+    let has_next = if params.with_has_next {
+        let total_count = context
+            .metrics()
+            .measure_query(QueryKey::StateTotalCountQuery, query.total_count(conn))
+            .await
+            .context("Failed to get state total count")
+            .error(AppErrorKind::DbQueryFailed)?;
+
+        Some(total_count > params.limit)
+    } else {
+        None
+    };
+
+    // Limit the query and retrieve the state.
+    let mut set_state = context
+        .metrics()
+        .measure_query(QueryKey::StateQuery, query.execute(conn))
+        .await
+        .context("Failed to get state")
+        .error(AppErrorKind::DbQueryFailed)?;
+
+    helpers::open_events(context, &mut set_state)?;
+    Ok((set_state, has_next))
+}
+
+/// Serializes a set state either as a single event for simple sets or as a collection.
+pub(crate) fn serialize_set_state(
+    set_state: Vec<db::event::Object>,
+) -> Result<JsonValue, AppError> {
+    let serialized_set_state = serde_json::to_value(set_state)
+        .context("Failed to serialize state")
+        .error(AppErrorKind::SerializationFailed)?;
+
+    let set_state = match serialized_set_state.as_array().and_then(|a| a.first()) {
+        Some(event) if event.get("label").is_none() => {
+            // The first event has no label => simple set with a single event…
+            event.to_owned()
+        }
+        _ => {
+            // …or it's a collection.
+            serialized_set_state
+        }
+    };
+
+    Ok(set_state)
+}
+
 ///////////////////////////////////////////////////////////////////////////////
 
 #[cfg(test)]
 mod tests {
+    use axum::response::IntoResponse;
     use serde_derive::Deserialize;
     use serde_json::json;
 
@@ -259,7 +449,9 @@ mod tests {
                 occurred_at: None,
                 original_occurred_at: None,
                 limit: None,
+                fields: None,
             },
+            if_none_match: None,
         };
 
         let messages = handle_request::<ReadHandler>(&mut context, &agent, payload)
@@ -274,6 +466,197 @@ mod tests {
         assert_eq!(state.layout.id(), layout_event.id());
     }
 
+    #[tokio::test]
+    async fn read_state_with_fields() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let (room, message_event, layout_event) = {
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+
+            let message_event = factory::Event::new()
+                .room_id(room.id())
+                .kind("message")
+                .set("messages")
+                .label("message-1")
+                .data(&json!({ "text": "hello" }))
+                .occurred_at(1000)
+                .created_by(agent.agent_id())
+                .insert(&mut conn)
+                .await;
+
+            let layout_event = factory::Event::new()
+                .room_id(room.id())
+                .kind("layout")
+                .set("layout")
+                .data(&json!({ "name": "presentation" }))
+                .occurred_at(2000)
+                .created_by(agent.agent_id())
+                .insert(&mut conn)
+                .await;
+
+            (room, message_event, layout_event)
+        };
+
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        let object = vec!["classrooms", &classroom_id];
+        authz.allow(agent.account_id(), object, "read");
+
+        let mut context = TestContext::new(db, authz);
+
+        let payload = ReadRequest {
+            room_id: room.id(),
+            payload: ReadPayload {
+                sets: vec![String::from("messages"), String::from("layout")],
+                attribute: None,
+                occurred_at: None,
+                original_occurred_at: None,
+                limit: None,
+                fields: Some(String::from("id,type")),
+            },
+            if_none_match: None,
+        };
+
+        let messages = handle_request::<ReadHandler>(&mut context, &agent, payload)
+            .await
+            .expect("State reading failed");
+
+        // Labelless sets are still returned as a single event.
+        let (state, respp, _) = find_response::<JsonValue>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+
+        assert_eq!(
+            state,
+            json!({
+                "messages": [{ "id": message_event.id(), "type": "message" }],
+                "layout": { "id": layout_event.id(), "type": "layout" },
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn read_state_not_modified() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+
+            factory::Event::new()
+                .room_id(room.id())
+                .kind("message")
+                .set("messages")
+                .label("message-1")
+                .data(&json!({ "text": "hello" }))
+                .occurred_at(1000)
+                .created_by(agent.agent_id())
+                .insert(&mut conn)
+                .await;
+
+            room
+        };
+
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        let object = vec!["classrooms", &classroom_id];
+        authz.allow(agent.account_id(), object, "read");
+
+        let mut context = TestContext::new(db, authz);
+        let reqp = RequestParams::Http {
+            agent_id: agent.agent_id(),
+        };
+
+        let read = |if_none_match: Option<String>| ReadRequest {
+            room_id: room.id(),
+            payload: ReadPayload {
+                sets: vec![String::from("messages")],
+                attribute: None,
+                occurred_at: None,
+                original_occurred_at: None,
+                limit: None,
+                fields: None,
+            },
+            if_none_match,
+        };
+
+        let resp = ReadHandler::handle(&mut context, read(None), reqp)
+            .await
+            .expect("State reading failed")
+            .into_response();
+
+        assert_eq!(resp.status(), ResponseStatus::OK);
+
+        let etag = resp
+            .headers()
+            .get(http::header::ETAG)
+            .and_then(|v| v.to_str().ok())
+            .expect("Missing ETag")
+            .to_owned();
+
+        let resp = ReadHandler::handle(&mut context, read(Some(etag.clone())), reqp)
+            .await
+            .expect("State reading failed")
+            .into_response();
+
+        assert_eq!(resp.status(), ResponseStatus::NOT_MODIFIED);
+
+        // The tag doesn't match differently shaped state of the same events.
+        let mut request = read(Some(etag.clone()));
+        request.payload.fields = Some(String::from("id,label"));
+
+        let resp = ReadHandler::handle(&mut context, request, reqp)
+            .await
+            .expect("State reading failed")
+            .into_response();
+
+        assert_eq!(resp.status(), ResponseStatus::OK);
+
+        // A new event changes the tag.
+        let mut conn = context.db().acquire().await.unwrap();
+
+        let event = factory::Event::new()
+            .room_id(room.id())
+            .kind("message")
+            .set("messages")
+            .label("message-2")
+            .data(&json!({ "text": "world" }))
+            .occurred_at(2000)
+            .created_by(agent.agent_id())
+            .insert(&mut conn)
+            .await;
+
+        let resp = ReadHandler::handle(&mut context, read(Some(etag)), reqp)
+            .await
+            .expect("State reading failed")
+            .into_response();
+
+        assert_eq!(resp.status(), ResponseStatus::OK);
+
+        let etag = resp
+            .headers()
+            .get(http::header::ETAG)
+            .and_then(|v| v.to_str().ok())
+            .expect("Missing ETag")
+            .to_owned();
+
+        // So does an in-place change of an existing event.
+        db::event::AttributeUpdateQuery::new(event.id())
+            .add(String::from("pinned"))
+            .execute(&mut conn)
+            .await
+            .expect("Failed to update event attributes");
+
+        let resp = ReadHandler::handle(&mut context, read(Some(etag)), reqp)
+            .await
+            .expect("State reading failed")
+            .into_response();
+
+        assert_eq!(resp.status(), ResponseStatus::OK);
+    }
+
     #[derive(Deserialize)]
     struct CollectionState {
         messages: Vec<Event>,
@@ -330,7 +713,9 @@ mod tests {
                 occurred_at: Some(2001),
                 original_occurred_at: None,
                 limit: Some(2),
+                fields: None,
             },
+            if_none_match: None,
         };
 
         let messages = handle_request::<ReadHandler>(&mut context, &agent, payload)
@@ -354,7 +739,9 @@ mod tests {
                 occurred_at: Some(1),
                 original_occurred_at: Some(state.messages[1].original_occurred_at()),
                 limit: Some(2),
+                fields: None,
             },
+            if_none_match: None,
         };
 
         let messages = handle_request::<ReadHandler>(&mut context, &agent, payload)
@@ -422,7 +809,9 @@ mod tests {
                 occurred_at: None,
                 original_occurred_at: None,
                 limit: None,
+                fields: None,
             },
+            if_none_match: None,
         };
 
         let messages = handle_request::<ReadHandler>(&mut context, &agent, payload)
@@ -486,7 +875,9 @@ mod tests {
                 occurred_at: Some(2001),
                 original_occurred_at: None,
                 limit: Some(2),
+                fields: None,
             },
+            if_none_match: None,
         };
 
         let messages = handle_request::<ReadHandler>(&mut context, &agent, payload)
@@ -510,7 +901,9 @@ mod tests {
                 occurred_at: Some(1),
                 original_occurred_at: Some(state.messages[1].original_occurred_at()),
                 limit: Some(2),
+                fields: None,
             },
+            if_none_match: None,
         };
 
         let messages = handle_request::<ReadHandler>(&mut context, &agent, payload)
@@ -606,7 +999,9 @@ mod tests {
                 occurred_at: None,
                 original_occurred_at: None,
                 limit: None,
+                fields: None,
             },
+            if_none_match: None,
         };
 
         let messages = handle_request::<ReadHandler>(&mut context, &agent, payload)
@@ -620,6 +1015,77 @@ mod tests {
         assert_eq!(state.messages[0].id(), pinned_message.id());
     }
 
+    #[tokio::test]
+    async fn read_state_crdt_set() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let (room, last_delta) = {
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+
+            let deltas = [
+                (1000, json!({ "x": 1, "y": 1, "color": "red" })),
+                (2000, json!({ "x": 2, "color": null })),
+                (3000, json!({ "y": 3 })),
+            ];
+
+            let mut last_delta = None;
+
+            for (occurred_at, data) in deltas {
+                let event = factory::Event::new()
+                    .room_id(room.id())
+                    .kind("object")
+                    .set("objects")
+                    .label("object-1")
+                    .data(&data)
+                    .occurred_at(occurred_at)
+                    .created_by(agent.agent_id())
+                    .insert(&mut conn)
+                    .await;
+
+                last_delta = Some(event);
+            }
+
+            (room, last_delta.expect("No deltas inserted"))
+        };
+
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        let object = vec!["classrooms", &classroom_id];
+        authz.allow(agent.account_id(), object, "read");
+
+        let mut context = TestContext::new(db, authz);
+        context.update_config(|config| config.crdt.sets = vec![String::from("objects")]);
+
+        let payload = ReadRequest {
+            room_id: room.id(),
+            payload: ReadPayload {
+                sets: vec![String::from("objects")],
+                attribute: None,
+                occurred_at: None,
+                original_occurred_at: None,
+                limit: None,
+                fields: None,
+            },
+            if_none_match: None,
+        };
+
+        let messages = handle_request::<ReadHandler>(&mut context, &agent, payload)
+            .await
+            .expect("State reading failed");
+
+        // The deltas are merged into a single element.
+        let (state, respp, _) = find_response::<JsonValue>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+
+        let objects = state["objects"].as_array().expect("Expected a collection");
+        assert_eq!(objects.len(), 1);
+        assert_eq!(objects[0]["id"], json!(last_delta.id()));
+        assert_eq!(objects[0]["data"], json!({ "x": 2, "y": 3 }));
+        assert_eq!(state["has_next"], json!(false));
+    }
+
     #[tokio::test]
     async fn read_state_not_authorized() {
         let db = TestDb::new().await;
@@ -640,7 +1106,9 @@ mod tests {
                 occurred_at: None,
                 original_occurred_at: None,
                 limit: None,
+                fields: None,
             },
+            if_none_match: None,
         };
 
         let err = handle_request::<ReadHandler>(&mut context, &agent, payload)
@@ -663,7 +1131,9 @@ mod tests {
                 occurred_at: None,
                 original_occurred_at: None,
                 limit: None,
+                fields: None,
             },
+            if_none_match: None,
         };
 
         let err = handle_request::<ReadHandler>(&mut context, &agent, payload)
@@ -2,8 +2,9 @@ use std::result::Result as StdResult;
 
 use anyhow::Context as AnyhowContext;
 use async_trait::async_trait;
-use futures::{future, stream};
+use futures::stream;
 use serde_derive::{Deserialize, Serialize};
+use sqlx::{postgres::PgConnection, Acquire};
 use svc_agent::{
     mqtt::{
         IncomingEventProperties, IncomingRequestProperties, OutgoingEvent,
@@ -14,12 +15,13 @@ use svc_agent::{
 use tracing::{field::display, instrument, warn, Span};
 use uuid::Uuid;
 
-use crate::db::agent;
+use crate::config::Config;
+use crate::db::{agent, room_queue};
+use crate::{app::endpoint::prelude::*, db::room};
 use crate::{
-    app::context::Context,
+    app::{context::Context, message_handler::Message},
     db::event::{insert_agent_action, AgentAction},
 };
-use crate::{app::endpoint::prelude::*, db::room};
 
 ///////////////////////////////////////////////////////////////////////////////
 
@@ -42,6 +44,9 @@ pub struct RoomEnterEvent {
 pub struct RoomLeaveEvent {
     id: Uuid,
     agent_id: AgentId,
+    /// Number of agents in the room when the presence is enabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    online: Option<usize>,
 }
 
 ///////////////////////////////////////////////////////////////////////////////
@@ -74,7 +79,7 @@ impl EventHandler for DeleteEventHandler {
         }
 
         // Delete agent from the DB.
-        let room_id = try_room_id(&payload.object)?;
+        let room_id = try_room_id(&context.config(), &payload.object)?;
         Span::current().record("room_id", &display(room_id));
 
         let row_count = {
@@ -93,6 +98,10 @@ impl EventHandler for DeleteEventHandler {
         if row_count != 1 {
             return Ok(Box::new(stream::empty()));
         }
+
+        helpers::update_room_presence(context, room_id, &payload.subject, false).await;
+        let online = helpers::room_online_count(context, room_id).await;
+
         let mut conn = context.get_conn().await?;
         let room = room::FindQuery::by_id(room_id)
             .execute(&mut conn)
@@ -100,33 +109,89 @@ impl EventHandler for DeleteEventHandler {
             .context("Failed to find room")
             .error(AppErrorKind::DbQueryFailed)?;
         if let Some(room) = room {
-            context
-                .metrics()
-                .measure_query(
-                    QueryKey::EventInsertQuery,
-                    insert_agent_action(&room, AgentAction::Left, &payload.subject, &mut conn),
-                )
-                .await
-                .context("Failed to insert agent action")
-                .error(AppErrorKind::DbQueryFailed)?;
+            if room.settings().agent_actions() {
+                let data_encryption = context.data_encryption();
+
+                context
+                    .metrics()
+                    .measure_query(
+                        QueryKey::EventInsertQuery,
+                        insert_agent_action(
+                            &room,
+                            AgentAction::Left,
+                            &payload.subject,
+                            data_encryption.as_deref(),
+                            &mut conn,
+                        ),
+                    )
+                    .await
+                    .context("Failed to insert agent action")
+                    .error(AppErrorKind::DbQueryFailed)?;
+            }
+
+            // Let waiting agents take the freed place.
+            if let Some(capacity) = helpers::room_capacity(context, &room) {
+                if context.config().room_capacity.queue {
+                    admit_queued_agents(context, &mut conn, &room, capacity, &payload.subject)
+                        .await?;
+                }
+            }
         }
 
         // Send broadcast notification that the agent has left the room.
         let outgoing_event_payload = RoomLeaveEvent {
             id: room_id,
             agent_id: payload.subject,
+            online,
         };
 
         let start_timestamp = context.start_timestamp();
         let short_term_timing = ShortTermTimingProperties::until_now(start_timestamp);
         let props = evp.to_event("room.leave", short_term_timing);
-        let to_uri = format!("rooms/{room_id}/events");
+        // The room topic the agent was subscribed to.
+        let to_uri = payload.object.join("/");
         let outgoing_event = OutgoingEvent::broadcast(outgoing_event_payload, props, &to_uri);
-        let boxed_event = Box::new(outgoing_event) as Box<_>;
-        Ok(Box::new(stream::once(future::ready(boxed_event))))
+        let boxed_event = Box::new(outgoing_event) as Message;
+        Ok(Box::new(stream::iter(std::iter::once(boxed_event))))
     }
 }
 
+async fn admit_queued_agents<C: Context>(
+    context: &mut C,
+    conn: &mut PgConnection,
+    room: &room::Object,
+    capacity: i32,
+    left_agent_id: &AgentId,
+) -> StdResult<(), AppError> {
+    let mut txn = conn
+        .begin()
+        .await
+        .context("Failed to acquire transaction")
+        .error(AppErrorKind::DbQueryFailed)?;
+
+    helpers::room_capacity_lock(&mut txn, room.id()).await?;
+
+    let query = room_queue::OccupancyQuery::new(
+        room.id(),
+        left_agent_id.to_owned(),
+        context.config().room_capacity.admission_timeout,
+    );
+
+    let occupancy = context
+        .metrics()
+        .measure_query(QueryKey::RoomQueueOccupancyQuery, query.execute(&mut txn))
+        .await
+        .context("Failed to count agents in the room")
+        .error(AppErrorKind::DbQueryFailed)?;
+
+    helpers::admit_queued_agents(context, &mut txn, room, occupancy.free_places(capacity)).await?;
+
+    txn.commit()
+        .await
+        .context("Failed to commit transaction")
+        .error(AppErrorKind::DbQueryFailed)
+}
+
 ///////////////////////////////////////////////////////////////////////////////
 
 pub struct BroadcastDeleteEventHandler;
@@ -150,7 +215,7 @@ impl EventHandler for BroadcastDeleteEventHandler {
             )).error(AppErrorKind::AccessDenied);
         }
 
-        let room_id = try_room_id(&payload.object)?;
+        let room_id = try_room_id(&context.config(), &payload.object)?;
         Span::current().record("room_id", &display(room_id));
 
         warn!("Broadcast subscription deleted by event");
@@ -161,17 +226,17 @@ impl EventHandler for BroadcastDeleteEventHandler {
 
 ///////////////////////////////////////////////////////////////////////////////
 
-fn try_room_id(object: &[String]) -> StdResult<Uuid, AppError> {
-    let object: Vec<&str> = object.iter().map(AsRef::as_ref).collect();
-
-    match object.as_slice() {
-        ["rooms", room_id, "events"] => Uuid::parse_str(room_id).context("UUID parse error"),
-        _ => Err(anyhow!(
-            "Bad 'object' format; expected [\"room\", <ROOM_ID>, \"events\"], got: {:?}",
-            object
-        )),
-    }
-    .error(AppErrorKind::InvalidSubscriptionObject)
+/// Parses the room out of the topic of a broadcast subscription of any audience.
+fn try_room_id(config: &Config, object: &[String]) -> StdResult<Uuid, AppError> {
+    config
+        .topic_room_id(&object.join("/"))
+        .ok_or_else(|| {
+            anyhow!(
+                "Bad 'object' format; expected a room topic like [\"rooms\", <ROOM_ID>, \"events\"], got: {:?}",
+                object
+            )
+        })
+        .error(AppErrorKind::InvalidSubscriptionObject)
 }
 
 ///////////////////////////////////////////////////////////////////////////////
@@ -179,7 +244,12 @@ fn try_room_id(object: &[String]) -> StdResult<Uuid, AppError> {
 #[cfg(test)]
 mod tests {
     mod delete_event {
+        use std::ops::Bound;
+
+        use serde_json::Value as JsonValue;
+
         use crate::db::agent::ListQuery as AgentListQuery;
+        use crate::db::room::ClassType;
         use crate::test_helpers::prelude::*;
 
         use super::super::*;
@@ -206,8 +276,8 @@ mod tests {
                 object: vec!["rooms".to_string(), room_id, "events".to_string()],
             };
 
-            let broker_account_label = context.config().broker_id.label();
-            let broker = TestAgent::new("alpha", broker_account_label, SVC_AUDIENCE);
+            let broker_id = context.config().broker_id.to_owned();
+            let broker = TestAgent::new("alpha", broker_id.label(), SVC_AUDIENCE);
 
             let messages = handle_event::<DeleteEventHandler>(&mut context, &broker, payload)
                 .await
@@ -236,6 +306,75 @@ mod tests {
             assert_eq!(db_agents.len(), 0);
         }
 
+        #[tokio::test]
+        async fn delete_subscription_admits_queued_agent() {
+            let db = TestDb::new().await;
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let queued_agent = TestAgent::new("web", "user456", USR_AUDIENCE);
+
+            let room = {
+                // Create a room for a single agent with another one waiting in the queue.
+                let mut conn = db.get_conn().await;
+                let now = chrono::Utc::now();
+
+                let room = factory::Room::new(Uuid::new_v4(), ClassType::Webinar)
+                    .audience(USR_AUDIENCE)
+                    .time((Bound::Included(now), Bound::Unbounded))
+                    .capacity(1)
+                    .insert(&mut conn)
+                    .await;
+
+                shared_helpers::insert_agent(&mut conn, agent.agent_id(), room.id()).await;
+
+                room_queue::EnqueueQuery::new(room.id(), queued_agent.agent_id().to_owned())
+                    .execute(&mut conn)
+                    .await
+                    .expect("Failed to put agent into the queue");
+
+                room
+            };
+
+            // Send subscription.delete event.
+            let mut context = TestContext::new(db, TestAuthz::new());
+            context.update_config(|config| config.room_capacity.queue = true);
+
+            let payload = DeleteEventPayload {
+                subject: agent.agent_id().to_owned(),
+                object: vec![
+                    "rooms".to_string(),
+                    room.id().to_string(),
+                    "events".to_string(),
+                ],
+            };
+
+            let broker_id = context.config().broker_id.to_owned();
+            let broker = TestAgent::new("alpha", broker_id.label(), SVC_AUDIENCE);
+
+            let messages = handle_event::<DeleteEventHandler>(&mut context, &broker, payload)
+                .await
+                .expect("Subscription deletion failed");
+
+            // Admission isn't announced to the audience.
+            assert!(find_event_by_predicate::<JsonValue, _>(&messages, |evp| {
+                evp.label() == "room.admit"
+            })
+            .is_none());
+
+            // The place is held for the queued agent until it repeats `room.enter`.
+            let mut conn = context.get_conn().await.expect("Failed to get conn");
+
+            let occupancy = room_queue::OccupancyQuery::new(
+                room.id(),
+                queued_agent.agent_id().to_owned(),
+                context.config().room_capacity.admission_timeout,
+            )
+            .execute(&mut conn)
+            .await
+            .expect("Failed to get room occupancy");
+
+            assert!(occupancy.admitted);
+        }
+
         #[tokio::test]
         async fn delete_subscription_missing_agent() {
             let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
@@ -254,8 +393,8 @@ mod tests {
                 object: vec!["rooms".to_string(), room_id, "events".to_string()],
             };
 
-            let broker_account_label = context.config().broker_id.label();
-            let broker = TestAgent::new("alpha", broker_account_label, SVC_AUDIENCE);
+            let broker_id = context.config().broker_id.to_owned();
+            let broker = TestAgent::new("alpha", broker_id.label(), SVC_AUDIENCE);
 
             let messages = handle_event::<DeleteEventHandler>(&mut context, &broker, payload)
                 .await
@@ -2,15 +2,19 @@ use async_trait::async_trait;
 use serde_derive::Deserialize;
 use serde_json::json;
 use svc_agent::mqtt::ResponseStatus;
-use svc_error::extension::sentry;
-use tracing::{error, warn};
+use tracing::error;
 
 use crate::app::context::Context;
 use crate::app::endpoint::prelude::*;
-use crate::app::operations::vacuum;
+use crate::app::operations::{vacuum, vacuum_dry_run};
+use crate::app::sentry;
 
 #[derive(Debug, Deserialize)]
-pub struct VacuumRequest {}
+pub struct VacuumRequest {
+    /// Only count events to be deleted without deleting them.
+    #[serde(default)]
+    dry_run: bool,
+}
 
 pub struct VacuumHandler;
 
@@ -20,7 +24,7 @@ impl RequestHandler for VacuumHandler {
 
     async fn handle<C: Context>(
         context: &mut C,
-        _payload: Self::Payload,
+        payload: Self::Payload,
         reqp: RequestParams<'_>,
     ) -> RequestResult {
         // Authz: only trusted subjects.
@@ -34,29 +38,55 @@ impl RequestHandler for VacuumHandler {
             )
             .await?;
 
-        // Run vacuum operation asynchronously.
-        let db = context.db().to_owned();
-        let metrics = context.metrics();
-        let config = context.config().vacuum.to_owned();
-
-        tokio::task::spawn(async move {
-            if let Err(err) = vacuum(&db, &metrics, &config).await {
-                error!("Vacuum failed: {:?}", err);
-
-                sentry::send(Arc::new(err)).unwrap_or_else(|err| {
-                    warn!("Error sending error to Sentry: {:?}", err);
-                });
-            }
-        });
-
-        // Return empty 202 response.
-        Ok(AppResponse::new(
-            ResponseStatus::ACCEPTED,
-            json!({}),
+        start_vacuum(context, payload.dry_run, authz_time).await
+    }
+}
+
+/// Responds with vacuum stats in case of `dry_run` or runs vacuum asynchronously.
+pub(crate) async fn start_vacuum<C: Context>(
+    context: &mut C,
+    dry_run: bool,
+    authz_time: chrono::Duration,
+) -> RequestResult {
+    let db = context.background_db().to_owned();
+    let metrics = context.metrics();
+    let config = context.config().vacuum.to_owned();
+
+    if dry_run {
+        let stats = vacuum_dry_run(&db, &metrics, &config)
+            .await
+            .error(AppErrorKind::DbQueryFailed)?;
+
+        return Ok(AppResponse::new(
+            ResponseStatus::OK,
+            json!(stats),
             context.start_timestamp(),
             Some(authz_time),
-        ))
+        ));
     }
+
+    // Run vacuum operation asynchronously.
+    let jobs = context.jobs();
+    let job_id = jobs.start("vacuum", None);
+
+    tokio::task::spawn(async move {
+        let result = vacuum(&db, &metrics, &config).await;
+        jobs.finish(job_id, &result);
+
+        if let Err(err) = result {
+            error!("Vacuum failed: {:?}", err);
+
+            sentry::send(&err, &[]);
+        }
+    });
+
+    // Return empty 202 response.
+    Ok(AppResponse::new(
+        ResponseStatus::ACCEPTED,
+        json!({}),
+        context.start_timestamp(),
+        Some(authz_time),
+    ))
 }
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -81,7 +111,7 @@ mod tests {
 
             // Make system.vacuum request.
             let mut context = TestContext::new(TestDb::new().await, authz);
-            let payload = VacuumRequest {};
+            let payload = VacuumRequest { dry_run: false };
 
             let messages = handle_request::<VacuumHandler>(&mut context, &agent, payload)
                 .await
@@ -92,11 +122,33 @@ mod tests {
             assert_eq!(payload, json!({}));
         }
 
+        #[tokio::test]
+        async fn vacuum_dry_run() {
+            let mut authz = TestAuthz::new();
+            authz.set_audience(SVC_AUDIENCE);
+
+            let agent = TestAgent::new("alpha", "cron", SVC_AUDIENCE);
+            authz.allow(agent.account_id(), vec!["system"], "update");
+
+            let mut context = TestContext::new(TestDb::new().await, authz);
+            let payload = VacuumRequest { dry_run: true };
+
+            let messages = handle_request::<VacuumHandler>(&mut context, &agent, payload)
+                .await
+                .expect("System vacuum dry run failed");
+
+            let (payload, respp, _) = find_response::<JsonValue>(messages.as_slice());
+            assert_eq!(respp.status(), ResponseStatus::OK);
+            assert!(payload["too_deep_history"].is_i64());
+            assert!(payload["too_old_history"].is_i64());
+            assert!(payload["too_old_deleted_labels"].is_i64());
+        }
+
         #[tokio::test]
         async fn vacuum_unauthorized() {
             let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
             let mut context = TestContext::new(TestDb::new().await, TestAuthz::new());
-            let payload = VacuumRequest {};
+            let payload = VacuumRequest { dry_run: false };
 
             let err = handle_request::<VacuumHandler>(&mut context, &agent, payload)
                 .await
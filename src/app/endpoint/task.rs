@@ -0,0 +1,203 @@
+use std::sync::Arc;
+
+use anyhow::Context as AnyhowContext;
+use async_trait::async_trait;
+use axum::extract::{self, Path};
+use serde_derive::Deserialize;
+use svc_agent::mqtt::ResponseStatus;
+use svc_utils::extractors::AgentIdExtractor;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::app::context::{AppContext, Context};
+use crate::app::endpoint::prelude::*;
+use crate::db;
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct ReadRequest {
+    id: Uuid,
+}
+
+pub async fn read(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(id): Path<Uuid>,
+) -> RequestResult {
+    let request = ReadRequest { id };
+    ReadHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct ReadHandler;
+
+#[async_trait]
+impl RequestHandler for ReadHandler {
+    type Payload = ReadRequest;
+
+    #[instrument(skip_all, fields(task_id = %payload.id, room_id, scope, classroom_id))]
+    async fn handle<C: Context>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let task = {
+            let query = db::task::FindQuery::new(payload.id);
+            let mut conn = context.get_ro_conn().await?;
+
+            context
+                .metrics()
+                .measure_query(QueryKey::TaskFindQuery, query.execute(&mut conn))
+                .await
+                .context("Failed to find task")
+                .error(AppErrorKind::DbQueryFailed)?
+                .ok_or_else(|| anyhow!("Task not found"))
+                .error(AppErrorKind::TaskNotFound)?
+        };
+
+        let room =
+            helpers::find_room(context, task.room_id(), helpers::RoomTimeRequirement::Any).await?;
+
+        let object = AuthzObject::room(&room).into();
+
+        let authz_time = context
+            .authz()
+            .authorize_room(
+                &room,
+                reqp.as_account_id().to_owned(),
+                object,
+                "read".into(),
+            )
+            .await?;
+
+        Ok(AppResponse::new(
+            ResponseStatus::OK,
+            task,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value as JsonValue};
+
+    use crate::db::task::{FinishQuery, InsertQuery, Status};
+    use crate::test_helpers::prelude::*;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn read_task() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let (room, task) = {
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+
+            let task = InsertQuery::new("room.adjust", room.id(), agent.agent_id().to_owned())
+                .execute(&mut conn)
+                .await
+                .expect("Failed to insert task");
+
+            (room, task)
+        };
+
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        authz.allow(
+            agent.account_id(),
+            vec!["classrooms", &classroom_id],
+            "read",
+        );
+
+        let mut context = TestContext::new(db, authz);
+
+        // Still running.
+        let payload = ReadRequest { id: task.id() };
+
+        let messages = handle_request::<ReadHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Task reading failed");
+
+        let (resp, respp, _) = find_response::<JsonValue>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert_eq!(resp["kind"], "room.adjust");
+        assert_eq!(resp["status"], "running");
+        assert!(resp.get("notification").is_none());
+
+        // Finished.
+        {
+            let mut conn = context.db().acquire().await.expect("Failed to get conn");
+            let notification = json!({ "room_id": room.id(), "status": "success" });
+
+            FinishQuery::new(task.id(), Status::Succeeded, notification)
+                .execute(&mut conn)
+                .await
+                .expect("Failed to finish task")
+                .expect("Task not found");
+        }
+
+        let payload = ReadRequest { id: task.id() };
+
+        let messages = handle_request::<ReadHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Task reading failed");
+
+        let (resp, _, _) = find_response::<JsonValue>(messages.as_slice());
+        assert_eq!(resp["status"], "succeeded");
+        assert_eq!(resp["notification"]["status"], "success");
+        assert!(resp.get("finished_at").is_some());
+    }
+
+    #[tokio::test]
+    async fn read_missing_task() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let mut context = TestContext::new(db, TestAuthz::new());
+        let payload = ReadRequest { id: Uuid::new_v4() };
+
+        let err = handle_request::<ReadHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success reading missing task");
+
+        assert_eq!(err.status(), ResponseStatus::NOT_FOUND);
+        assert_eq!(err.kind(), "task_not_found");
+    }
+
+    #[tokio::test]
+    async fn read_task_not_authorized() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let task = {
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+
+            InsertQuery::new("room.adjust", room.id(), agent.agent_id().to_owned())
+                .execute(&mut conn)
+                .await
+                .expect("Failed to insert task")
+        };
+
+        let mut context = TestContext::new(db, TestAuthz::new());
+        let payload = ReadRequest { id: task.id() };
+
+        let err = handle_request::<ReadHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success reading task without authorization");
+
+        assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+    }
+}
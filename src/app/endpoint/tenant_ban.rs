@@ -0,0 +1,435 @@
+use std::sync::Arc;
+
+use anyhow::Context as AnyhowContext;
+use async_trait::async_trait;
+use axum::{
+    extract::{self, Path},
+    Json,
+};
+use serde_derive::{Deserialize, Serialize};
+use serde_json::json;
+use svc_agent::{mqtt::ResponseStatus, AccountId};
+use svc_authn::Authenticable;
+use svc_utils::extractors::AgentIdExtractor;
+use tracing::instrument;
+
+use crate::app::context::Context;
+use crate::app::endpoint::prelude::*;
+use crate::db;
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct CreatePayload {
+    account_id: AccountId,
+    reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateRequest {
+    audience: String,
+    #[serde(flatten)]
+    payload: CreatePayload,
+}
+
+pub async fn create(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(audience): Path<String>,
+    Json(payload): Json<CreatePayload>,
+) -> RequestResult {
+    let request = CreateRequest { audience, payload };
+    CreateHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct CreateHandler;
+
+#[async_trait]
+impl RequestHandler for CreateHandler {
+    type Payload = CreateRequest;
+
+    #[instrument(skip_all, fields(audience))]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload { audience, payload }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let authz_time = authorize(context, &audience, reqp).await?;
+
+        let ban = {
+            let mut query = db::tenant_ban::InsertQuery::new(
+                audience.clone(),
+                payload.account_id.clone(),
+                reqp.as_account_id().to_owned(),
+            );
+
+            if let Some(ref reason) = payload.reason {
+                query.reason(reason);
+            }
+
+            let mut conn = context.get_conn().await?;
+
+            context
+                .metrics()
+                .measure_query(QueryKey::TenantBanInsertQuery, query.execute(&mut conn))
+                .await
+                .context("Failed to insert tenant ban")
+                .error(AppErrorKind::DbQueryFailed)?
+        };
+
+        // Don't let cached decisions outlive the ban.
+        context.authz().invalidate(&payload.account_id);
+
+        let mut response = AppResponse::new(
+            ResponseStatus::OK,
+            json!(ban),
+            context.start_timestamp(),
+            Some(authz_time),
+        );
+
+        response.add_notification(
+            "tenant_ban.create",
+            &context.config().audience_topic(&audience),
+            ban,
+            context.start_timestamp(),
+        )?;
+
+        Ok(response)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct ListRequest {
+    audience: String,
+}
+
+pub async fn list(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(audience): Path<String>,
+) -> RequestResult {
+    let request = ListRequest { audience };
+    ListHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct ListHandler;
+
+#[async_trait]
+impl RequestHandler for ListHandler {
+    type Payload = ListRequest;
+
+    #[instrument(skip_all, fields(audience))]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload { audience }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let authz_time = authorize(context, &audience, reqp).await?;
+
+        let bans = {
+            let query = db::tenant_ban::ListQuery::new(audience);
+            let mut conn = context.get_ro_conn().await?;
+
+            context
+                .metrics()
+                .measure_query(QueryKey::TenantBanListQuery, query.execute(&mut conn))
+                .await
+                .context("Failed to list tenant bans")
+                .error(AppErrorKind::DbQueryFailed)?
+        };
+
+        Ok(AppResponse::new(
+            ResponseStatus::OK,
+            bans,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteRequest {
+    audience: String,
+    account_id: AccountId,
+}
+
+#[derive(Serialize)]
+struct DeleteNotification {
+    audience: String,
+    account_id: AccountId,
+}
+
+pub async fn delete(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path((audience, account_id)): Path<(String, AccountId)>,
+) -> RequestResult {
+    let request = DeleteRequest {
+        audience,
+        account_id,
+    };
+    DeleteHandler::handle(
+        &mut ctx.start_message(),
+        request,
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct DeleteHandler;
+
+#[async_trait]
+impl RequestHandler for DeleteHandler {
+    type Payload = DeleteRequest;
+
+    #[instrument(skip_all, fields(audience))]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload {
+            audience,
+            account_id,
+        }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let authz_time = authorize(context, &audience, reqp).await?;
+
+        {
+            let query = db::tenant_ban::DeleteQuery::new(audience.clone(), account_id.clone());
+            let mut conn = context.get_conn().await?;
+
+            context
+                .metrics()
+                .measure_query(QueryKey::TenantBanDeleteQuery, query.execute(&mut conn))
+                .await
+                .context("Failed to delete tenant ban")
+                .error(AppErrorKind::DbQueryFailed)?;
+        }
+
+        let mut response = AppResponse::new(
+            ResponseStatus::OK,
+            json!({}),
+            context.start_timestamp(),
+            Some(authz_time),
+        );
+
+        let notification = DeleteNotification {
+            audience: audience.clone(),
+            account_id,
+        };
+
+        response.add_notification(
+            "tenant_ban.delete",
+            &context.config().audience_topic(&audience),
+            notification,
+            context.start_timestamp(),
+        )?;
+
+        Ok(response)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+async fn authorize<C: Context>(
+    context: &mut C,
+    audience: &str,
+    reqp: RequestParams<'_>,
+) -> Result<chrono::Duration, AppError> {
+    let object = AuthzObject::new(&["classrooms"]).into();
+
+    let authz_time = context
+        .authz()
+        .authorize(
+            audience.to_owned(),
+            reqp.as_account_id().to_owned(),
+            object,
+            "ban".into(),
+        )
+        .await?;
+
+    Ok(authz_time)
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Bound;
+
+    use chrono::Utc;
+    use serde_json::Value as JsonValue;
+    use uuid::Uuid;
+
+    use crate::db::room::ClassType;
+    use crate::test_helpers::prelude::*;
+
+    use super::*;
+
+    // Each test bans in its own audience not to affect other tests.
+    fn random_audience() -> String {
+        format!("{}.example.org", Uuid::new_v4())
+    }
+
+    #[tokio::test]
+    async fn create_tenant_ban() {
+        let db = TestDb::new().await;
+        let audience = random_audience();
+        let admin = TestAgent::new("web", "admin", USR_AUDIENCE);
+        let banned_agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+
+            factory::Room::new(Uuid::new_v4(), ClassType::Webinar)
+                .audience(&audience)
+                .time((Bound::Included(Utc::now()), Bound::Unbounded))
+                .insert(&mut conn)
+                .await
+        };
+
+        let mut authz = TestAuthz::new();
+        authz.set_audience(&audience);
+        authz.allow(admin.account_id(), vec!["classrooms"], "ban");
+
+        let mut context = TestContext::new(db.clone(), authz);
+
+        let payload = CreateRequest {
+            audience: audience.clone(),
+            payload: CreatePayload {
+                account_id: banned_agent.account_id().to_owned(),
+                reason: Some("spam".to_owned()),
+            },
+        };
+
+        let messages = handle_request::<CreateHandler>(&mut context, &admin, payload)
+            .await
+            .expect("Tenant ban creation failed");
+
+        let (ban, respp, _) = find_response::<JsonValue>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert_eq!(ban["account_id"], banned_agent.account_id().to_string());
+        assert_eq!(ban["reason"], "spam");
+
+        let (_, evp, topic) = find_event::<JsonValue>(messages.as_slice());
+        assert_eq!(evp.label(), "tenant_ban.create");
+        assert!(topic.ends_with(&format!("/audiences/{}/events", audience)));
+
+        // The account is banned in any classroom of the audience.
+        let mut conn = db.get_conn().await;
+
+        let is_banned = crate::app::endpoint::authz::is_banned(
+            &mut conn,
+            banned_agent.account_id(),
+            room.classroom_id(),
+        )
+        .await
+        .expect("Failed to check ban");
+
+        assert!(is_banned);
+    }
+
+    #[tokio::test]
+    async fn create_tenant_ban_not_authorized() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let mut context = TestContext::new(db, TestAuthz::new());
+
+        let payload = CreateRequest {
+            audience: random_audience(),
+            payload: CreatePayload {
+                account_id: agent.account_id().to_owned(),
+                reason: None,
+            },
+        };
+
+        let err = handle_request::<CreateHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success on tenant ban creation");
+
+        assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn list_and_delete_tenant_bans() {
+        let db = TestDb::new().await;
+        let audience = random_audience();
+        let admin = TestAgent::new("web", "admin", USR_AUDIENCE);
+        let banned_agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        {
+            let mut conn = db.get_conn().await;
+
+            db::tenant_ban::InsertQuery::new(
+                audience.clone(),
+                banned_agent.account_id().to_owned(),
+                admin.account_id().to_owned(),
+            )
+            .execute(&mut conn)
+            .await
+            .expect("Failed to insert tenant ban");
+        }
+
+        let mut authz = TestAuthz::new();
+        authz.set_audience(&audience);
+        authz.allow(admin.account_id(), vec!["classrooms"], "ban");
+
+        let mut context = TestContext::new(db, authz);
+
+        // List bans.
+        let payload = ListRequest {
+            audience: audience.clone(),
+        };
+
+        let messages = handle_request::<ListHandler>(&mut context, &admin, payload)
+            .await
+            .expect("Tenant bans listing failed");
+
+        let (bans, respp, _) = find_response::<Vec<JsonValue>>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert_eq!(bans.len(), 1);
+        assert_eq!(bans[0]["account_id"], banned_agent.account_id().to_string());
+
+        // Delete the ban.
+        let payload = DeleteRequest {
+            audience: audience.clone(),
+            account_id: banned_agent.account_id().to_owned(),
+        };
+
+        let messages = handle_request::<DeleteHandler>(&mut context, &admin, payload)
+            .await
+            .expect("Tenant ban deletion failed");
+
+        let (_, evp, _) = find_event::<JsonValue>(messages.as_slice());
+        assert_eq!(evp.label(), "tenant_ban.delete");
+
+        // No bans left.
+        let payload = ListRequest { audience };
+
+        let messages = handle_request::<ListHandler>(&mut context, &admin, payload)
+            .await
+            .expect("Tenant bans listing failed");
+
+        let (bans, _, _) = find_response::<Vec<JsonValue>>(messages.as_slice());
+        assert!(bans.is_empty());
+    }
+}
@@ -0,0 +1,169 @@
+use anyhow::Context as AnyhowContext;
+use async_trait::async_trait;
+use axum::extract::{self, Path};
+use serde_derive::Deserialize;
+use svc_agent::mqtt::ResponseStatus;
+use svc_utils::extractors::AgentIdExtractor;
+use tracing::{field::display, instrument, Span};
+use uuid::Uuid;
+
+use crate::app::endpoint::prelude::*;
+use crate::db;
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct PagesRequest {
+    room_id: Uuid,
+}
+
+#[utoipa::path(
+    get,
+    path = "/rooms/{id}/whiteboard/pages",
+    tag = "whiteboard",
+    params(("id" = Uuid, Path, description = "Room identifier")),
+    responses(
+        (status = 200, description = "Whiteboard pages in order", body = [crate::db::whiteboard_page::Object]),
+        (status = 403, description = "Not authorized", body = ErrorPayload),
+        (status = 404, description = "Room not found", body = ErrorPayload),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn pages(
+    ctx: extract::Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<Uuid>,
+) -> RequestResult {
+    PagesHandler::handle(
+        &mut ctx.start_message(),
+        PagesRequest { room_id },
+        RequestParams::Http {
+            agent_id: &agent_id,
+        },
+    )
+    .await
+}
+
+pub struct PagesHandler;
+
+#[async_trait]
+impl RequestHandler for PagesHandler {
+    type Payload = PagesRequest;
+
+    #[instrument(skip_all, fields(room_id, scope, classroom_id))]
+    async fn handle<C: Context>(
+        context: &mut C,
+        Self::Payload { room_id }: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        Span::current().record("room_id", &display(room_id));
+
+        let room = helpers::find_room(context, room_id, helpers::RoomTimeRequirement::Any).await?;
+
+        // The same permission as for listing events.
+        let classroom_id = room.classroom_id().to_string();
+        let object = AuthzObject::new(&["classrooms", &classroom_id]);
+        let authz_time = helpers::authorize_room(context, &room, reqp, object, "read").await?;
+
+        let query = db::whiteboard_page::ListQuery::new(room.id());
+        let mut conn = context.get_ro_conn().await?;
+
+        let pages = context
+            .metrics()
+            .measure_query(QueryKey::WhiteboardPageListQuery, query.execute(&mut conn))
+            .await
+            .context("Failed to list whiteboard pages")
+            .error(AppErrorKind::DbQueryFailed)?;
+
+        Ok(AppResponse::new(
+            ResponseStatus::OK,
+            pages,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::db::whiteboard_page::Object as Page;
+    use crate::test_helpers::prelude::*;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn list_pages() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+
+            let page = |label: &str, data: serde_json::Value, occurred_at: i64| {
+                factory::Event::new()
+                    .room_id(room.id())
+                    .kind("page")
+                    .set("pages")
+                    .label(label)
+                    .data(&data)
+                    .occurred_at(occurred_at)
+                    .created_by(agent.agent_id())
+            };
+
+            page("page1", json!({ "position": 1 }), 1000)
+                .insert(&mut conn)
+                .await;
+
+            page("page2", json!({ "position": 2 }), 2000)
+                .insert(&mut conn)
+                .await;
+
+            page("page3", json!({ "position": 3 }), 3000)
+                .insert(&mut conn)
+                .await;
+
+            // Move the last page to the top.
+            page("page3", json!({ "position": 0.5 }), 4000)
+                .insert(&mut conn)
+                .await;
+
+            // An earlier event doesn't override the latest one.
+            page("page3", json!({ "position": 10 }), 3500)
+                .insert(&mut conn)
+                .await;
+
+            page("page2", json!({}), 5000)
+                .removed(true)
+                .insert(&mut conn)
+                .await;
+
+            room
+        };
+
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        authz.allow(
+            agent.account_id(),
+            vec!["classrooms", &classroom_id],
+            "read",
+        );
+
+        let mut context = TestContext::new(db, authz);
+        let payload = PagesRequest { room_id: room.id() };
+
+        let messages = handle_request::<PagesHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Whiteboard pages listing failed");
+
+        let (pages, respp, _) = find_response::<Vec<Page>>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+
+        let labels = pages.iter().map(|page| page.label()).collect::<Vec<_>>();
+        assert_eq!(labels, vec!["page3", "page1"]);
+    }
+}
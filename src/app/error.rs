@@ -1,14 +1,23 @@
-use enum_iterator::Sequence;
+use enum_iterator::{all, Sequence};
+use serde_derive::Serialize;
 use std::fmt;
 use std::sync::Arc;
 
 use svc_agent::mqtt::ResponseStatus;
-use svc_error::{extension::sentry, Error as SvcError};
+use svc_error::Error as SvcError;
+use utoipa::{
+    openapi::{ObjectBuilder, RefOr, Schema, SchemaType},
+    ToSchema,
+};
+
+use crate::app::sentry;
 
 ////////////////////////////////////////////////////////////////////////////////
 
 struct ErrorKindProperties {
     status: ResponseStatus,
+    /// Whether retrying the same request is pointless.
+    is_permanent: bool,
     kind: &'static str,
     title: &'static str,
     is_notify_sentry: bool,
@@ -17,14 +26,19 @@ struct ErrorKindProperties {
 #[derive(Debug, Clone, Copy, Sequence, Hash, PartialEq, Eq)]
 pub enum ErrorKind {
     AccessDenied,
+    AdjustmentNotFound,
     AgentNotEnteredTheRoom,
+    AuthenticationFailed,
     AuthorizationFailed,
     BrokerRequestFailed,
     ChangeNotFound,
+    ConfigReloadFailed,
     DbConnAcquisitionFailed,
     DbQueryFailed,
     EditionCommitTaskFailed,
     EditionNotFound,
+    EventConflict,
+    EventNotFound,
     InternalServerError,
     InvalidPayload,
     InvalidQueryString,
@@ -34,22 +48,37 @@ pub enum ErrorKind {
     MessageHandlingFailed,
     MqttClientNotConnected,
     NoS3Client,
+    OperationInProgress,
     S3UploadFailed,
     StatsCollectionFailed,
     PublishFailed,
     RoomAdjustTaskFailed,
     RoomClosed,
+    RoomFull,
     RoomNotFound,
     SerializationFailed,
     TransientEventCreationFailed,
     UnknownMethod,
     WhiteboardAccessUpdateNotChecked,
     PayloadSizeExceeded,
+    PayloadTooLarge,
     InvalidEvent,
     NatsSubscriptionFailed,
     InternalNatsError,
     NatsMessageHandlingFailed,
     NatsPublishFailed,
+    MessageTooLarge,
+    MalformedMessage,
+    ServiceOverloaded,
+    InvalidShareToken,
+    SharingDisabled,
+    TaskNotFound,
+    ContentRejected,
+    ContentFilterUnavailable,
+    QuestionNotFound,
+    PollNotFound,
+    PollClosed,
+    HandQueueEmpty,
 }
 
 impl ErrorKind {
@@ -67,6 +96,40 @@ impl ErrorKind {
         let properties: ErrorKindProperties = self.into();
         properties.is_notify_sentry
     }
+
+    pub fn is_permanent(self) -> bool {
+        let properties: ErrorKindProperties = self.into();
+        properties.is_permanent
+    }
+
+    /// Descriptions of all error kinds ordered by kind.
+    pub fn catalog() -> Vec<ErrorKindDescription> {
+        let mut catalog = all::<ErrorKind>()
+            .map(|kind| {
+                let properties: ErrorKindProperties = kind.into();
+
+                ErrorKindDescription {
+                    kind: properties.kind,
+                    title: properties.title,
+                    status: properties.status.as_u16(),
+                    is_permanent: properties.is_permanent,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        catalog.sort_by_key(|description| description.kind);
+        catalog
+    }
+}
+
+/// An entry of the error catalog for clients to map error kinds programmatically.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorKindDescription {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    title: &'static str,
+    status: u16,
+    is_permanent: bool,
 }
 
 impl fmt::Display for ErrorKind {
@@ -81,208 +144,382 @@ impl From<ErrorKind> for ErrorKindProperties {
         match val {
             ErrorKind::AccessDenied => ErrorKindProperties {
                 status: ResponseStatus::FORBIDDEN,
+                is_permanent: true,
                 kind: "access_denied",
                 title: "Access denied",
                 is_notify_sentry: false,
             },
+            ErrorKind::AdjustmentNotFound => ErrorKindProperties {
+                status: ResponseStatus::NOT_FOUND,
+                is_permanent: true,
+                kind: "adjustment_not_found",
+                title: "Adjustment not found",
+                is_notify_sentry: false,
+            },
             ErrorKind::AgentNotEnteredTheRoom => ErrorKindProperties {
                 status: ResponseStatus::NOT_FOUND,
+                is_permanent: true,
                 kind: "agent_not_entered_the_room",
                 title: "Agent not entered the room",
                 is_notify_sentry: false,
             },
+            ErrorKind::AuthenticationFailed => ErrorKindProperties {
+                status: ResponseStatus::UNAUTHORIZED,
+                is_permanent: true,
+                kind: "authentication_failed",
+                title: "Authentication failed",
+                is_notify_sentry: false,
+            },
             ErrorKind::AuthorizationFailed => ErrorKindProperties {
                 status: ResponseStatus::UNPROCESSABLE_ENTITY,
+                is_permanent: false,
                 kind: "authorization_failed",
                 title: "Authorization failed",
                 is_notify_sentry: false,
             },
             ErrorKind::BrokerRequestFailed => ErrorKindProperties {
                 status: ResponseStatus::UNPROCESSABLE_ENTITY,
+                is_permanent: false,
                 kind: "broker_request_failed",
                 title: "Broker request failed",
                 is_notify_sentry: true,
             },
             ErrorKind::ChangeNotFound => ErrorKindProperties {
                 status: ResponseStatus::NOT_FOUND,
+                is_permanent: true,
                 kind: "change_not_found",
                 title: "Change not found",
                 is_notify_sentry: false,
             },
+            ErrorKind::ConfigReloadFailed => ErrorKindProperties {
+                status: ResponseStatus::UNPROCESSABLE_ENTITY,
+                is_permanent: false,
+                kind: "config_reload_failed",
+                title: "Config reload failed",
+                is_notify_sentry: false,
+            },
             ErrorKind::DbConnAcquisitionFailed => ErrorKindProperties {
                 status: ResponseStatus::UNPROCESSABLE_ENTITY,
+                is_permanent: false,
                 kind: "database_connection_acquisition_failed",
                 title: "Database connection acquisition failed",
                 is_notify_sentry: true,
             },
             ErrorKind::DbQueryFailed => ErrorKindProperties {
                 status: ResponseStatus::UNPROCESSABLE_ENTITY,
+                is_permanent: false,
                 kind: "database_query_failed",
                 title: "Database query failed",
                 is_notify_sentry: true,
             },
             ErrorKind::EditionCommitTaskFailed => ErrorKindProperties {
                 status: ResponseStatus::UNPROCESSABLE_ENTITY,
+                is_permanent: false,
                 kind: "edition_commit_task_failed",
                 title: "Edition commit task failed",
                 is_notify_sentry: true,
             },
             ErrorKind::EditionNotFound => ErrorKindProperties {
                 status: ResponseStatus::NOT_FOUND,
+                is_permanent: true,
                 kind: "edition_not_found",
                 title: "Edition not found",
                 is_notify_sentry: false,
             },
+            ErrorKind::EventConflict => ErrorKindProperties {
+                status: ResponseStatus::CONFLICT,
+                is_permanent: true,
+                kind: "event_conflict",
+                title: "Event conflict",
+                is_notify_sentry: false,
+            },
+            ErrorKind::EventNotFound => ErrorKindProperties {
+                status: ResponseStatus::NOT_FOUND,
+                is_permanent: true,
+                kind: "event_not_found",
+                title: "Event not found",
+                is_notify_sentry: false,
+            },
             ErrorKind::InvalidPayload => ErrorKindProperties {
                 status: ResponseStatus::BAD_REQUEST,
+                is_permanent: true,
                 kind: "invalid_payload",
                 title: "Invalid payload",
                 is_notify_sentry: false,
             },
             ErrorKind::InvalidQueryString => ErrorKindProperties {
                 status: ResponseStatus::BAD_REQUEST,
+                is_permanent: true,
                 kind: "invalid_query_string",
                 title: "Invalid query string",
                 is_notify_sentry: false,
             },
             ErrorKind::InvalidRoomTime => ErrorKindProperties {
                 status: ResponseStatus::BAD_REQUEST,
+                is_permanent: true,
                 kind: "invalid_room_time",
                 title: "Invalid room time",
                 is_notify_sentry: false,
             },
             ErrorKind::InvalidStateSets => ErrorKindProperties {
                 status: ResponseStatus::BAD_REQUEST,
+                is_permanent: true,
                 kind: "invalid_state_sets",
                 title: "Invalid state sets",
                 is_notify_sentry: false,
             },
             ErrorKind::InvalidSubscriptionObject => ErrorKindProperties {
                 status: ResponseStatus::BAD_REQUEST,
+                is_permanent: true,
                 kind: "invalid_subscription_object",
                 title: "Invalid subscription object",
                 is_notify_sentry: true,
             },
             ErrorKind::MessageHandlingFailed => ErrorKindProperties {
                 status: ResponseStatus::UNPROCESSABLE_ENTITY,
+                is_permanent: false,
                 kind: "message_handling_failed",
                 title: "Message handling failed",
                 is_notify_sentry: true,
             },
             ErrorKind::MqttClientNotConnected => ErrorKindProperties {
                 status: ResponseStatus::UNPROCESSABLE_ENTITY,
+                is_permanent: false,
                 kind: "mqtt_client_not_connected",
                 title: "Mqtt client not connected",
                 is_notify_sentry: false,
             },
             ErrorKind::NoS3Client => ErrorKindProperties {
                 status: ResponseStatus::NOT_IMPLEMENTED,
+                is_permanent: true,
                 kind: "no_s3_client",
                 title: "No s3 configuration, nowhere to dump events to",
                 is_notify_sentry: true,
             },
+            ErrorKind::OperationInProgress => ErrorKindProperties {
+                status: ResponseStatus::CONFLICT,
+                is_permanent: false,
+                kind: "operation_in_progress",
+                title: "Operation in progress",
+                is_notify_sentry: false,
+            },
             ErrorKind::S3UploadFailed => ErrorKindProperties {
                 status: ResponseStatus::INTERNAL_SERVER_ERROR,
+                is_permanent: false,
                 kind: "s3_upload_failed",
                 title: "S3 upload failed",
                 is_notify_sentry: true,
             },
             ErrorKind::SerializationFailed => ErrorKindProperties {
                 status: ResponseStatus::UNPROCESSABLE_ENTITY,
+                is_permanent: true,
                 kind: "serialization_failed",
                 title: "Serialization failed",
                 is_notify_sentry: true,
             },
             ErrorKind::StatsCollectionFailed => ErrorKindProperties {
                 status: ResponseStatus::UNPROCESSABLE_ENTITY,
+                is_permanent: false,
                 kind: "stats_collection_failed",
                 title: "Stats collection failed",
                 is_notify_sentry: true,
             },
             ErrorKind::PublishFailed => ErrorKindProperties {
                 status: ResponseStatus::UNPROCESSABLE_ENTITY,
+                is_permanent: false,
                 kind: "publish_failed",
                 title: "Publish failed",
                 is_notify_sentry: true,
             },
             ErrorKind::RoomAdjustTaskFailed => ErrorKindProperties {
                 status: ResponseStatus::UNPROCESSABLE_ENTITY,
+                is_permanent: false,
                 kind: "room_adjust_task_failed",
                 title: "Room adjust task failed",
                 is_notify_sentry: true,
             },
             ErrorKind::RoomClosed => ErrorKindProperties {
                 status: ResponseStatus::NOT_FOUND,
+                is_permanent: true,
                 kind: "room_closed",
                 title: "Room closed",
                 is_notify_sentry: false,
             },
+            ErrorKind::RoomFull => ErrorKindProperties {
+                status: ResponseStatus::CONFLICT,
+                is_permanent: false,
+                kind: "room_full",
+                title: "Room is full",
+                is_notify_sentry: false,
+            },
             ErrorKind::RoomNotFound => ErrorKindProperties {
                 status: ResponseStatus::NOT_FOUND,
+                is_permanent: true,
                 kind: "room_not_found",
                 title: "Room not found",
                 is_notify_sentry: false,
             },
             ErrorKind::TransientEventCreationFailed => ErrorKindProperties {
                 status: ResponseStatus::UNPROCESSABLE_ENTITY,
+                is_permanent: false,
                 kind: "transient_event_creation_failed",
                 title: "Transient event creation failed",
                 is_notify_sentry: true,
             },
             ErrorKind::UnknownMethod => ErrorKindProperties {
                 status: ResponseStatus::METHOD_NOT_ALLOWED,
+                is_permanent: true,
                 kind: "unknown_method",
                 title: "Unknown method",
                 is_notify_sentry: false,
             },
             ErrorKind::InternalServerError => ErrorKindProperties {
                 status: ResponseStatus::INTERNAL_SERVER_ERROR,
+                is_permanent: false,
                 kind: "internal_server_error",
                 title: "Internal server error",
                 is_notify_sentry: true,
             },
             ErrorKind::WhiteboardAccessUpdateNotChecked => ErrorKindProperties {
                 status: ResponseStatus::CONFLICT,
+                is_permanent: true,
                 kind: "useless_whiteboard_access_update",
                 title: "Whiteboard access change in room with universal whiteboard access (which doesnt make sense)",
                 is_notify_sentry: false,
             },
             ErrorKind::PayloadSizeExceeded => ErrorKindProperties {
                 status: ResponseStatus::UNPROCESSABLE_ENTITY,
+                is_permanent: true,
                 kind: "payload_size_exceeded",
                 title: "Payload size exceeded",
                 is_notify_sentry: false,
             },
+            ErrorKind::PayloadTooLarge => ErrorKindProperties {
+                status: ResponseStatus::PAYLOAD_TOO_LARGE,
+                is_permanent: true,
+                kind: "payload_too_large",
+                title: "Event data exceeds the size limit of its kind",
+                is_notify_sentry: false,
+            },
             ErrorKind::InvalidEvent => ErrorKindProperties {
                 status: ResponseStatus::UNPROCESSABLE_ENTITY,
+                is_permanent: true,
                 kind: "invalid_event",
                 title: "Invalid event",
                 is_notify_sentry: false
             },
             ErrorKind::NatsSubscriptionFailed => ErrorKindProperties {
                 status: ResponseStatus::UNPROCESSABLE_ENTITY,
+                is_permanent: false,
                 kind: "nats_subscription_failed",
                 title: "Nats subscription failed",
                 is_notify_sentry: true
             },
             ErrorKind::InternalNatsError => ErrorKindProperties {
                 status: ResponseStatus::FAILED_DEPENDENCY,
+                is_permanent: false,
                 kind: "internal_nats_error",
                 title: "Internal nats error",
                 is_notify_sentry: true
             },
             ErrorKind::NatsMessageHandlingFailed => ErrorKindProperties {
                 status: ResponseStatus::UNPROCESSABLE_ENTITY,
+                is_permanent: false,
                 kind: "nats_message_handling_failed",
                 title: "Nats message handling failed",
                 is_notify_sentry: true
             },
             ErrorKind::NatsPublishFailed => ErrorKindProperties {
                 status: ResponseStatus::UNPROCESSABLE_ENTITY,
+                is_permanent: false,
                 kind: "nats_publish_failed",
                 title: "Nats publish failed",
                 is_notify_sentry: true
             },
+            ErrorKind::MessageTooLarge => ErrorKindProperties {
+                status: ResponseStatus::PAYLOAD_TOO_LARGE,
+                is_permanent: true,
+                kind: "message_too_large",
+                title: "Message too large",
+                is_notify_sentry: false
+            },
+            ErrorKind::MalformedMessage => ErrorKindProperties {
+                status: ResponseStatus::UNPROCESSABLE_ENTITY,
+                is_permanent: true,
+                kind: "malformed_message",
+                title: "Malformed message",
+                is_notify_sentry: false
+            },
+            ErrorKind::ServiceOverloaded => ErrorKindProperties {
+                status: ResponseStatus::SERVICE_UNAVAILABLE,
+                is_permanent: false,
+                kind: "service_overloaded",
+                title: "Service overloaded",
+                is_notify_sentry: false
+            },
+            ErrorKind::InvalidShareToken => ErrorKindProperties {
+                status: ResponseStatus::UNAUTHORIZED,
+                is_permanent: true,
+                kind: "invalid_share_token",
+                title: "Share token is invalid or expired",
+                is_notify_sentry: false
+            },
+            ErrorKind::SharingDisabled => ErrorKindProperties {
+                status: ResponseStatus::NOT_IMPLEMENTED,
+                is_permanent: true,
+                kind: "sharing_disabled",
+                title: "Room sharing is not configured",
+                is_notify_sentry: false
+            },
+            ErrorKind::TaskNotFound => ErrorKindProperties {
+                status: ResponseStatus::NOT_FOUND,
+                is_permanent: true,
+                kind: "task_not_found",
+                title: "Task not found",
+                is_notify_sentry: false
+            },
+            ErrorKind::ContentRejected => ErrorKindProperties {
+                status: ResponseStatus::UNPROCESSABLE_ENTITY,
+                is_permanent: true,
+                kind: "content_rejected",
+                title: "Content rejected by the filter",
+                is_notify_sentry: false
+            },
+            ErrorKind::ContentFilterUnavailable => ErrorKindProperties {
+                status: ResponseStatus::SERVICE_UNAVAILABLE,
+                is_permanent: false,
+                kind: "content_filter_unavailable",
+                title: "Content filter unavailable",
+                is_notify_sentry: true
+            },
+            ErrorKind::QuestionNotFound => ErrorKindProperties {
+                status: ResponseStatus::NOT_FOUND,
+                is_permanent: true,
+                kind: "question_not_found",
+                title: "Question not found",
+                is_notify_sentry: false,
+            },
+            ErrorKind::PollNotFound => ErrorKindProperties {
+                status: ResponseStatus::NOT_FOUND,
+                is_permanent: true,
+                kind: "poll_not_found",
+                title: "Poll not found",
+                is_notify_sentry: false,
+            },
+            ErrorKind::PollClosed => ErrorKindProperties {
+                status: ResponseStatus::UNPROCESSABLE_ENTITY,
+                is_permanent: true,
+                kind: "poll_closed",
+                title: "Poll closed",
+                is_notify_sentry: false,
+            },
+            ErrorKind::HandQueueEmpty => ErrorKindProperties {
+                status: ResponseStatus::NOT_FOUND,
+                is_permanent: false,
+                kind: "hand_queue_empty",
+                title: "Hand queue empty",
+                is_notify_sentry: false,
+            },
         }
     }
 }
@@ -344,15 +581,32 @@ impl Error {
         e
     }
 
+    /// Builds the error payload for clients.
+    pub fn to_payload(&self) -> ErrorPayload {
+        ErrorPayload {
+            error: self.to_svc_error(),
+            status: self.status().as_u16(),
+            is_permanent: self.kind.is_permanent(),
+        }
+    }
+
+    /// Reports the error along with its tags and the request scope tags like `method`
+    /// and `room_id`. Errors of the same kind are throttled, the next reported one
+    /// gets the number of suppressed ones in `suppressed` tag.
     pub fn notify_sentry(&self) {
         if !self.kind.is_notify_sentry() {
             return;
         }
 
-        if let Some(e) = &self.err {
-            if let Err(e) = sentry::send(e.clone()) {
-                tracing::error!("Failed to send error to sentry, reason = {:?}", e);
-            }
+        if let Some(err) = &self.err {
+            let suppressed = match sentry::throttle(self.kind.kind()) {
+                Some(suppressed) => suppressed.to_string(),
+                None => return,
+            };
+
+            let mut tags = vec![("kind", self.kind()), ("suppressed", suppressed.as_str())];
+            tags.extend(self.tags.iter().map(|(k, v)| (k.as_str(), v.as_str())));
+            sentry::send(err, &tags);
         }
     }
 
@@ -364,6 +618,42 @@ impl Error {
     }
 }
 
+/// Problem details of an error sent to clients in responses and notifications.
+#[derive(Debug, Serialize)]
+pub struct ErrorPayload {
+    #[serde(flatten)]
+    error: SvcError,
+    status: u16,
+    is_permanent: bool,
+}
+
+/// Described by hand since the problem details fields come flattened from `SvcError`.
+impl<'s> ToSchema<'s> for ErrorPayload {
+    fn schema() -> (&'s str, RefOr<Schema>) {
+        let string = || ObjectBuilder::new().schema_type(SchemaType::String);
+
+        let schema = ObjectBuilder::new()
+            .property("type", string())
+            .required("type")
+            .property("title", string())
+            .required("title")
+            .property("detail", string())
+            .property(
+                "status",
+                ObjectBuilder::new().schema_type(SchemaType::Integer),
+            )
+            .required("status")
+            .property(
+                "is_permanent",
+                ObjectBuilder::new().schema_type(SchemaType::Boolean),
+            )
+            .required("is_permanent")
+            .into();
+
+        ("ErrorPayload", schema)
+    }
+}
+
 impl fmt::Debug for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Error")
@@ -409,3 +699,35 @@ impl ErrorKindExt for anyhow::Error {
         Error::new(kind, self)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn catalog_kinds_are_unique() {
+        let catalog = ErrorKind::catalog();
+        let kinds = catalog.iter().map(|d| d.kind).collect::<HashSet<_>>();
+        assert_eq!(kinds.len(), all::<ErrorKind>().count());
+    }
+
+    #[test]
+    fn payload() {
+        let err = Error::new(ErrorKind::RoomNotFound, anyhow::anyhow!("Room not found"));
+
+        assert_eq!(
+            serde_json::to_value(err.to_payload()).expect("Failed to serialize"),
+            json!({
+                "type": "room_not_found",
+                "title": "Room not found",
+                "detail": "Room not found",
+                "status": 404,
+                "is_permanent": true,
+            })
+        );
+    }
+}
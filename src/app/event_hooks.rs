@@ -0,0 +1,168 @@
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use futures::FutureExt;
+use tokio::task::JoinHandle;
+use tracing::error;
+
+use crate::app::sentry;
+use crate::db::event::{EventKind, Object as Event};
+use crate::db::room::Object as Room;
+use crate::metrics::Metrics;
+
+/// Reaction to events created with `event.create`, e.g. a projection or a counter.
+#[async_trait]
+pub trait EventHook: Send + Sync {
+    /// Used in logs to tell which hook has failed.
+    fn name(&self) -> &'static str;
+
+    async fn call(&self, room: &Room, event: &Event) -> Result<()>;
+}
+
+/// Hooks called after an event gets inserted so features reacting to new events don't have
+/// to be wired into the `event.create` handler one by one.
+#[derive(Clone, Default)]
+pub struct EventHooks {
+    hooks: Vec<Arc<dyn EventHook>>,
+}
+
+impl EventHooks {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn register(mut self, hook: impl EventHook + 'static) -> Self {
+        self.hooks.push(Arc::new(hook));
+        self
+    }
+
+    /// Calls the hooks in the order of registration in the background so they don't delay
+    /// the response. A hook failing or panicking doesn't prevent the next ones from running.
+    pub fn dispatch(&self, room: &Room, event: &Event) -> Option<JoinHandle<()>> {
+        if self.hooks.is_empty() {
+            return None;
+        }
+
+        let hooks = self.hooks.clone();
+        let room = room.to_owned();
+        let event = event.to_owned();
+
+        let handle = tokio::spawn(async move {
+            for hook in hooks {
+                let result = AssertUnwindSafe(hook.call(&room, &event))
+                    .catch_unwind()
+                    .await
+                    .unwrap_or_else(|_| Err(anyhow!("Hook panicked")));
+
+                if let Err(err) = result {
+                    let err = err.context(format!("Event hook '{}' failed", hook.name()));
+                    error!(event_id = %event.id(), "{:?}", err);
+                    sentry::send(&err, &[]);
+                }
+            }
+        });
+
+        Some(handle)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Counts created events by kind. Tenant-specific kinds are counted as `other`.
+pub struct CreatedEventsCounter {
+    metrics: Arc<Metrics>,
+}
+
+impl CreatedEventsCounter {
+    pub fn new(metrics: Arc<Metrics>) -> Self {
+        Self { metrics }
+    }
+}
+
+#[async_trait]
+impl EventHook for CreatedEventsCounter {
+    fn name(&self) -> &'static str {
+        "created_events_counter"
+    }
+
+    async fn call(&self, _room: &Room, event: &Event) -> Result<()> {
+        let kind = match EventKind::from(event.kind()) {
+            EventKind::Other(_) => "other",
+            _ => event.kind(),
+        };
+
+        self.metrics.observe_created_event(kind);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_helpers::prelude::*;
+
+    struct Failing;
+
+    #[async_trait]
+    impl EventHook for Failing {
+        fn name(&self) -> &'static str {
+            "failing"
+        }
+
+        async fn call(&self, _room: &Room, _event: &Event) -> Result<()> {
+            panic!("Failing hook");
+        }
+    }
+
+    struct Counting(Arc<AtomicUsize>);
+
+    #[async_trait]
+    impl EventHook for Counting {
+        fn name(&self) -> &'static str {
+            "counting"
+        }
+
+        async fn call(&self, _room: &Room, _event: &Event) -> Result<()> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn failing_hook_is_isolated() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let mut conn = db.get_conn().await;
+        let room = shared_helpers::insert_room(&mut conn).await;
+
+        let event = factory::Event::new()
+            .room_id(room.id())
+            .kind("message")
+            .data(&json!({ "text": "hello" }))
+            .occurred_at(1000)
+            .created_by(agent.agent_id())
+            .insert(&mut conn)
+            .await;
+
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let hooks = EventHooks::new()
+            .register(Failing)
+            .register(Counting(calls.clone()));
+
+        hooks
+            .dispatch(&room, &event)
+            .expect("Hooks not dispatched")
+            .await
+            .expect("Hooks task failed");
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(EventHooks::new().dispatch(&room, &event).is_none());
+    }
+}
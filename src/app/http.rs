@@ -4,8 +4,9 @@ use std::{
 };
 
 use axum::{
+    extract::{DefaultBodyLimit, MatchedPath},
     response::IntoResponse,
-    routing::{delete, get, post},
+    routing::{delete, get, patch, post},
     Extension, Json, Router,
 };
 
@@ -21,13 +22,21 @@ use svc_utils::middleware::MeteredRoute;
 use tower::{layer::layer_fn, Service, ServiceBuilder};
 use tower_http::cors::{Any, CorsLayer};
 use tracing::error;
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
 
 use crate::app::{
     message_handler::{publish_message, MessageStream},
-    service_utils,
+    redis_bridge, sentry, service_utils,
+    share_token::SHARE_TOKEN_HEADER,
 };
 
-use super::{context::AppContext, endpoint, error::Error as AppError};
+use super::{
+    context::{AppContext, GlobalContext},
+    endpoint,
+    error::Error as AppError,
+    openapi::ApiDoc,
+};
 
 pub fn build_router(
     context: Arc<AppContext>,
@@ -44,10 +53,14 @@ pub fn build_router(
             HeaderName::from_static("ulms-app-version"),
             HeaderName::from_static("ulms-app-label"),
             HeaderName::from_static("x-agent-label"),
+            HeaderName::from_static(SHARE_TOKEN_HEADER),
         ])
         .max_age(std::time::Duration::from_secs(3600))
         .allow_origin(Any);
 
+    // Snapshots are way larger than the other request bodies.
+    let restore_max_size = context.config().dump.restore_max_size;
+
     let middleware = ServiceBuilder::new()
         .layer(Extension(agent))
         .layer(Extension(Arc::new(authn)))
@@ -56,6 +69,10 @@ pub fn build_router(
         .layer(cors);
 
     let router = Router::new()
+        .metered_route(
+            "/errors",
+            get(endpoint::list_errors).options(endpoint::read_options),
+        )
         .metered_route("/rooms", post(endpoint::room::create))
         .metered_route(
             "/rooms/:id",
@@ -64,6 +81,10 @@ pub fn build_router(
                 .options(endpoint::read_options),
         )
         .metered_route("/rooms/:id/adjust", post(endpoint::room::adjust))
+        .metered_route(
+            "/rooms/:id/adjust/preview",
+            post(endpoint::room::adjust_preview),
+        )
         .metered_route(
             "/rooms/:id/enter",
             post(endpoint::room::enter).options(endpoint::read_options),
@@ -72,21 +93,124 @@ pub fn build_router(
             "/rooms/:id/locked_types",
             post(endpoint::room::locked_types).options(endpoint::read_options),
         )
+        .metered_route(
+            "/rooms/:id/locked_types/schedule",
+            post(endpoint::room::schedule_lock).options(endpoint::read_options),
+        )
+        .metered_route(
+            "/rooms/:id/share",
+            post(endpoint::room::share).options(endpoint::read_options),
+        )
         .metered_route(
             "/rooms/:id/whiteboard_access",
             post(endpoint::room::whiteboard_access).options(endpoint::read_options),
         )
         .metered_route("/rooms/:id/dump_events", post(endpoint::room::dump_events))
+        .metered_route(
+            "/rooms/:id/transcript",
+            get(endpoint::room::transcript)
+                .post(endpoint::room::export_transcript_to_s3)
+                .options(endpoint::read_options),
+        )
+        .metered_route(
+            "/rooms/:id/dump",
+            get(endpoint::room::dump).options(endpoint::read_options),
+        )
+        .metered_route(
+            "/rooms/:id/restore",
+            post(endpoint::room::restore)
+                .layer(DefaultBodyLimit::max(restore_max_size))
+                .options(endpoint::read_options),
+        )
+        .metered_route(
+            "/rooms/:id/notify",
+            post(endpoint::room::notify).options(endpoint::read_options),
+        )
+        .metered_route(
+            "/rooms/:id/replay",
+            get(endpoint::room::replay).options(endpoint::read_options),
+        )
+        .metered_route(
+            "/rooms/:id/verify",
+            get(endpoint::room::verify).options(endpoint::read_options),
+        )
+        .metered_route(
+            "/rooms/:id/operation",
+            get(endpoint::room::operation_status).options(endpoint::read_options),
+        )
+        .metered_route(
+            "/rooms/:id/settings",
+            patch(endpoint::room::update_settings).options(endpoint::read_options),
+        )
+        .metered_route(
+            "/rooms/:id/permissions",
+            get(endpoint::room::permissions).options(endpoint::read_options),
+        )
+        .metered_route(
+            "/rooms/:id/presence",
+            get(endpoint::room::presence).options(endpoint::read_options),
+        )
+        .metered_route(
+            "/rooms/:id/bootstrap",
+            get(endpoint::room::bootstrap).options(endpoint::read_options),
+        )
+        .metered_route(
+            "/rooms/:id/pinned_events",
+            get(endpoint::room::pinned_events).options(endpoint::read_options),
+        )
         .metered_route(
             "/rooms/:id/events",
             get(endpoint::event::list)
                 .post(endpoint::event::create)
                 .options(endpoint::read_options),
         )
+        .metered_route(
+            "/rooms/:id/events/history",
+            get(endpoint::event::history).options(endpoint::read_options),
+        )
+        .metered_route("/rooms/:id/events/import", post(endpoint::event::import))
+        .metered_route(
+            "/rooms/:id/events/since",
+            get(endpoint::event::since).options(endpoint::read_options),
+        )
+        .metered_route(
+            "/rooms/:id/events/attribute",
+            post(endpoint::event::set_attribute)
+                .delete(endpoint::event::clear_attribute)
+                .options(endpoint::read_options),
+        )
+        .metered_route(
+            "/rooms/:id/moderation",
+            get(endpoint::moderation::list).options(endpoint::read_options),
+        )
+        .metered_route(
+            "/rooms/:id/moderation/approve",
+            post(endpoint::moderation::approve).options(endpoint::read_options),
+        )
+        .metered_route(
+            "/rooms/:id/moderation/reject",
+            post(endpoint::moderation::reject).options(endpoint::read_options),
+        )
+        .metered_route(
+            "/rooms/:id/sets",
+            get(endpoint::set::list).options(endpoint::read_options),
+        )
+        .metered_route(
+            "/rooms/:id/sets/:set/count_since",
+            get(endpoint::set::count_since).options(endpoint::read_options),
+        )
         .metered_route(
             "/rooms/:id/state",
             get(endpoint::state::read).options(endpoint::read_options),
         )
+        .metered_route(
+            "/shared/rooms/:id/events",
+            get(endpoint::event::shared_list).options(endpoint::read_options),
+        )
+        .metered_route(
+            "/shared/rooms/:id/state",
+            get(endpoint::state::shared_read).options(endpoint::read_options),
+        )
         .metered_route(
             "/rooms/:id/agents",
             get(endpoint::agent::list)
@@ -103,6 +227,102 @@ pub fn build_router(
             "/rooms/:id/bans",
             get(endpoint::ban::list).options(endpoint::read_options),
         )
+        .metered_route(
+            "/rooms/:id/attachments",
+            get(endpoint::attachment::list).options(endpoint::read_options),
+        )
+        .metered_route(
+            "/rooms/:id/whiteboard/pages",
+            get(endpoint::whiteboard::pages).options(endpoint::read_options),
+        )
+        .metered_route(
+            "/rooms/:id/hand_queue",
+            get(endpoint::hand_queue::list).options(endpoint::read_options),
+        )
+        .metered_route(
+            "/rooms/:id/hand_queue/raise",
+            post(endpoint::hand_queue::raise).options(endpoint::read_options),
+        )
+        .metered_route(
+            "/rooms/:id/hand_queue/lower",
+            post(endpoint::hand_queue::lower).options(endpoint::read_options),
+        )
+        .metered_route(
+            "/rooms/:id/hand_queue/pop",
+            post(endpoint::hand_queue::pop).options(endpoint::read_options),
+        )
+        .metered_route(
+            "/rooms/:id/polls",
+            post(endpoint::poll::create).options(endpoint::read_options),
+        )
+        .metered_route(
+            "/rooms/:id/polls/:poll_id/vote",
+            post(endpoint::poll::vote).options(endpoint::read_options),
+        )
+        .metered_route(
+            "/rooms/:id/polls/:poll_id/results",
+            get(endpoint::poll::results).options(endpoint::read_options),
+        )
+        .metered_route(
+            "/rooms/:id/polls/:poll_id/close",
+            post(endpoint::poll::close).options(endpoint::read_options),
+        )
+        .metered_route(
+            "/rooms/:id/questions",
+            get(endpoint::question::list)
+                .post(endpoint::question::create)
+                .options(endpoint::read_options),
+        )
+        .metered_route(
+            "/rooms/:id/questions/:question_id/upvote",
+            post(endpoint::question::upvote).options(endpoint::read_options),
+        )
+        .metered_route(
+            "/rooms/:id/questions/:question_id/answer",
+            post(endpoint::question::answer).options(endpoint::read_options),
+        )
+        .metered_route(
+            "/classrooms/:classroom_id/activity",
+            get(endpoint::activity::read).options(endpoint::read_options),
+        )
+        .metered_route(
+            "/audiences/:audience/rooms",
+            get(endpoint::room::list).options(endpoint::read_options),
+        )
+        .metered_route(
+            "/audiences/:audience/rooms/search",
+            post(endpoint::room::search).options(endpoint::read_options),
+        )
+        .metered_route(
+            "/audiences/:audience/bans",
+            get(endpoint::tenant_ban::list)
+                .post(endpoint::tenant_ban::create)
+                .options(endpoint::read_options),
+        )
+        .metered_route(
+            "/audiences/:audience/bans/:account_id",
+            delete(endpoint::tenant_ban::delete).options(endpoint::read_options),
+        )
+        .metered_route(
+            "/rooms/:id/adjustment",
+            get(endpoint::adjustment::read).options(endpoint::read_options),
+        )
+        .metered_route(
+            "/tasks/:id",
+            get(endpoint::task::read).options(endpoint::read_options),
+        )
+        .metered_route(
+            "/rooms/:id/retention",
+            get(endpoint::retention::read_room)
+                .post(endpoint::retention::set_room)
+                .options(endpoint::read_options),
+        )
+        .metered_route(
+            "/audiences/:audience/retention",
+            get(endpoint::retention::read_audience)
+                .post(endpoint::retention::set_audience)
+                .options(endpoint::read_options),
+        )
         .metered_route(
             "/editions/:id",
             delete(endpoint::edition::delete).options(endpoint::read_options),
@@ -111,6 +331,10 @@ pub fn build_router(
             "/editions/:id/commit",
             post(endpoint::edition::commit).options(endpoint::read_options),
         )
+        .metered_route(
+            "/editions/:id/summary",
+            get(endpoint::edition::summary).options(endpoint::read_options),
+        )
         .metered_route(
             "/editions/:id/changes",
             get(endpoint::change::list)
@@ -121,16 +345,68 @@ pub fn build_router(
             "/changes/:id",
             delete(endpoint::change::delete).options(endpoint::read_options),
         )
+        .layer(middleware.clone());
+
+    let admin_router = Router::new()
+        .metered_route(
+            "/audiences/:audience/rooms",
+            get(endpoint::admin::list_rooms).options(endpoint::read_options),
+        )
+        .metered_route(
+            "/rooms/:id/close",
+            post(endpoint::admin::close_room).options(endpoint::read_options),
+        )
+        .metered_route(
+            "/nats/dead_letters",
+            get(endpoint::admin::list_dead_letters).options(endpoint::read_options),
+        )
+        .metered_route(
+            "/nats/dead_letters/requeue",
+            post(endpoint::admin::requeue_dead_letters).options(endpoint::read_options),
+        )
+        .metered_route(
+            "/vacuum",
+            post(endpoint::admin::vacuum).options(endpoint::read_options),
+        )
+        .metered_route(
+            "/draw_events/reencode",
+            post(endpoint::admin::reencode_draw_events).options(endpoint::read_options),
+        )
+        .metered_route(
+            "/set_heads/backfill",
+            post(endpoint::admin::backfill_set_heads).options(endpoint::read_options),
+        )
+        .metered_route(
+            "/accounts/:account_id/events/export",
+            post(endpoint::admin::export_account_events).options(endpoint::read_options),
+        )
+        .metered_route(
+            "/accounts/:account_id/events/erase",
+            post(endpoint::admin::erase_account_events).options(endpoint::read_options),
+        )
+        .metered_route(
+            "/jobs",
+            get(endpoint::admin::list_jobs).options(endpoint::read_options),
+        )
+        .metered_route(
+            "/config/reload",
+            post(endpoint::admin::reload_config).options(endpoint::read_options),
+        )
         .layer(middleware);
 
-    let routes = Router::new().nest("/api/v1", router);
+    let routes = Router::new()
+        .nest("/api/v1", router)
+        .nest("/admin/v1", admin_router);
 
     let pingz_router = Router::new().route(
         "/healthz",
         get(|| async { Response::builder().body(Body::from("pong")).unwrap() }),
     );
 
-    let routes = routes.merge(pingz_router);
+    let swagger_router =
+        SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::openapi());
+
+    let routes = routes.merge(pingz_router).merge(swagger_router);
 
     routes.layer(svc_utils::middleware::LogLayer::new())
 }
@@ -139,7 +415,7 @@ impl IntoResponse for AppError {
     fn into_response(self) -> axum::response::Response {
         self.notify_sentry();
 
-        let err = self.to_svc_error();
+        let err = self.to_payload();
 
         let mut r = (self.status(), Json(err)).into_response();
         r.extensions_mut().insert(self.error_kind());
@@ -176,13 +452,33 @@ where
 
         Box::pin(async move {
             let mut agent = req.extensions().get::<Agent>().cloned().unwrap();
-            let mut res: Response<ResBody> = inner.call(req).await?;
+            let context = req.extensions().get::<Arc<AppContext>>().cloned().unwrap();
+
+            let method = match req.extensions().get::<MatchedPath>() {
+                Some(path) => format!("{} {}", req.method(), path.as_str()),
+                None => format!("{} {}", req.method(), req.uri().path()),
+            };
 
-            if let Some(notifications) = res
+            // Sentry tags set while handling the request get attached to its errors only.
+            let mut res: Response<ResBody> = sentry::bind_hub(async move {
+                sentry::set_request_tags(&method);
+                inner.call(req).await
+            })
+            .await?;
+
+            if let Some(mut notifications) = res
                 .extensions_mut()
                 .remove::<service_utils::Notifications>()
             {
-                for notification in notifications {
+                redis_bridge::mirror(context.as_ref(), &notifications);
+
+                let config = context.config();
+
+                // HTTP clients get the response so they aren't notified about oversized ones.
+                notifications.limit_size(&config.notification_size, &context.metrics());
+
+                for notification in notifications.into_messages(&config.notification_batching, None)
+                {
                     if let Err(err) = publish_message(&mut agent, notification) {
                         error!("Failed to publish message, err = {:?}", err);
                     }
@@ -0,0 +1,170 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use uuid::Uuid;
+
+/// How many jobs to remember including finished ones.
+const JOBS_CAPACITY: usize = 100;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Running,
+    Succeeded,
+    Failed,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Job {
+    id: u64,
+    kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    room_id: Option<Uuid>,
+    status: JobStatus,
+    started_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    finished_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    /// The latest progress reported by the job, e.g. processed counts.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    progress: Option<JsonValue>,
+}
+
+#[cfg(test)]
+impl Job {
+    pub fn kind(&self) -> &str {
+        self.kind
+    }
+
+    pub fn status(&self) -> JobStatus {
+        self.status
+    }
+}
+
+/// Recent background jobs of this replica like room adjustments or vacuum.
+pub struct JobRegistry {
+    jobs: Mutex<VecDeque<Job>>,
+    next_id: AtomicU64,
+}
+
+impl JobRegistry {
+    pub fn new() -> Self {
+        Self {
+            jobs: Mutex::new(VecDeque::new()),
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    /// Registers a running job and returns its id to pass to `finish`.
+    pub fn start(&self, kind: &'static str, room_id: Option<Uuid>) -> u64 {
+        let job = Job {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            kind,
+            room_id,
+            status: JobStatus::Running,
+            started_at: Utc::now(),
+            finished_at: None,
+            error: None,
+            progress: None,
+        };
+
+        let id = job.id;
+        let mut jobs = self.jobs.lock();
+
+        if jobs.len() >= JOBS_CAPACITY {
+            // Forget the oldest finished job. Running ones are kept until they finish.
+            if let Some(idx) = jobs.iter().position(|j| j.status != JobStatus::Running) {
+                jobs.remove(idx);
+            }
+        }
+
+        jobs.push_back(job);
+        id
+    }
+
+    /// Replaces the progress of a running job shown in the listing.
+    pub fn report<P: Serialize>(&self, id: u64, progress: &P) {
+        let progress = serde_json::to_value(progress).ok();
+        let mut jobs = self.jobs.lock();
+
+        if let Some(job) = jobs.iter_mut().find(|j| j.id == id) {
+            job.progress = progress;
+        }
+    }
+
+    pub fn finish<T, E: std::fmt::Debug>(&self, id: u64, result: &Result<T, E>) {
+        let mut jobs = self.jobs.lock();
+
+        if let Some(job) = jobs.iter_mut().find(|j| j.id == id) {
+            job.finished_at = Some(Utc::now());
+
+            match result {
+                Ok(_) => job.status = JobStatus::Succeeded,
+                Err(err) => {
+                    job.status = JobStatus::Failed;
+                    job.error = Some(format!("{:?}", err));
+                }
+            }
+        }
+    }
+
+    /// Returns jobs from the most recent one.
+    pub fn list(&self) -> Vec<Job> {
+        self.jobs.lock().iter().rev().cloned().collect()
+    }
+}
+
+impl Default for JobRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn track_job() {
+        let registry = JobRegistry::new();
+        let ok_id = registry.start("adjust", Some(Uuid::new_v4()));
+        let err_id = registry.start("vacuum", None);
+
+        registry.report(ok_id, &serde_json::json!({ "processed": 1 }));
+        registry.finish(ok_id, &Ok::<_, String>(()));
+        registry.finish(err_id, &Err::<(), _>("boom"));
+
+        let jobs = registry.list();
+        assert_eq!(jobs.len(), 2);
+        assert_eq!(jobs[0].kind(), "vacuum");
+        assert_eq!(jobs[0].status(), JobStatus::Failed);
+        assert_eq!(jobs[0].error.as_deref(), Some("\"boom\""));
+        assert_eq!(jobs[1].status(), JobStatus::Succeeded);
+        assert_eq!(
+            jobs[1].progress,
+            Some(serde_json::json!({ "processed": 1 }))
+        );
+    }
+
+    #[test]
+    fn forget_oldest_finished_job() {
+        let registry = JobRegistry::new();
+        let running_id = registry.start("adjust", None);
+
+        for _ in 0..JOBS_CAPACITY {
+            let id = registry.start("vacuum", None);
+            registry.finish(id, &Ok::<_, String>(()));
+        }
+
+        let jobs = registry.list();
+        assert_eq!(jobs.len(), JOBS_CAPACITY);
+        assert!(jobs.iter().any(|j| j.id == running_id));
+    }
+}
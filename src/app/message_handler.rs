@@ -23,6 +23,8 @@ use crate::app::{
     service_utils::RequestParams,
 };
 use crate::app::{endpoint, API_VERSION};
+use crate::app::{redis_bridge, sentry};
+use crate::config::MessageConstraint;
 
 ////////////////////////////////////////////////////////////////////////////////
 
@@ -55,17 +57,38 @@ impl<C: GlobalContext + Sync> MessageHandler<C> {
     }
 
     pub async fn handle(&self, message: &Result<IncomingMessage<String>, String>) {
-        let mut msg_context = AppMessageContext::new(&self.global_context, Utc::now());
-
-        match message {
-            Ok(ref msg) => {
-                if let Err(err) = self.handle_message(&mut msg_context, msg).await {
-                    let err = format!("{err:?}");
-                    Self::report_error(message, &err).await;
+        // Sentry tags set while handling the message get attached to its errors only.
+        sentry::bind_hub(async {
+            let mut msg_context = AppMessageContext::new(&self.global_context, Utc::now());
+
+            match message {
+                Ok(ref msg) => {
+                    if let Err(err) = self.handle_message(&mut msg_context, msg).await {
+                        let err = format!("{err:?}");
+                        Self::report_error(message, &err).await;
+                    }
+                }
+                Err(e) => {
+                    Self::report_error(message, e).await;
                 }
             }
-            Err(e) => {
-                Self::report_error(message, e).await;
+        })
+        .await
+    }
+
+    /// Responds to a request with an error without handling it when there are too many
+    /// pending messages. Events are just dropped.
+    pub fn reject(&self, message: &Result<IncomingMessage<String>, String>) {
+        if let Ok(IncomingMessage::Request(req)) = message {
+            let err = anyhow!("Too many pending messages");
+            let app_error = AppError::new(AppErrorKind::ServiceOverloaded, err);
+            let reqp = req.properties();
+            let timing = ShortTermTimingProperties::new(Utc::now());
+            let props = reqp.to_response(app_error.status(), timing);
+            let resp = OutgoingResponse::unicast(app_error.to_payload(), props, reqp, API_VERSION);
+
+            if let Err(err) = publish_message(&mut self.agent.clone(), Box::new(resp)) {
+                error!("Failed to reject a message: {:?}", err);
             }
         }
     }
@@ -86,9 +109,33 @@ impl<C: GlobalContext + Sync> MessageHandler<C> {
         msg_context: &mut AppMessageContext<'_, C>,
         message: &IncomingMessage<String>,
     ) -> Result<(), AppError> {
+        let constraint = msg_context.config().constraint.message.clone();
+
         match message {
-            IncomingMessage::Request(req) => self.handle_request(msg_context, req).await,
-            IncomingMessage::Event(ev) => self.handle_event(msg_context, ev).await,
+            IncomingMessage::Request(req) => match validate_payload(req.payload(), &constraint) {
+                Ok(()) => self.handle_request(msg_context, req).await,
+                Err(app_error) => {
+                    warn!(
+                        method = req.properties().method(),
+                        "Rejected request: {:?}", app_error
+                    );
+
+                    let stream =
+                        error_response(app_error, req.properties(), msg_context.start_timestamp());
+
+                    self.publish_outgoing_messages(stream).await
+                }
+            },
+            IncomingMessage::Event(ev) => match validate_payload(ev.payload(), &constraint) {
+                Ok(()) => self.handle_event(msg_context, ev).await,
+                Err(app_error) => {
+                    warn!(
+                        label = ev.properties().label(),
+                        "Dropped event: {:?}", app_error
+                    );
+                    Ok(())
+                }
+            },
             IncomingMessage::Response(resp) => self.handle_response(msg_context, resp).await,
         }
     }
@@ -107,6 +154,8 @@ impl<C: GlobalContext + Sync> MessageHandler<C> {
         msg_context: &mut AppMessageContext<'_, C>,
         request: &IncomingRequest<String>,
     ) -> Result<(), AppError> {
+        sentry::set_request_tags(request.properties().method());
+
         let outgoing_message_stream = endpoint::route_request(msg_context, request)
             .await
             .unwrap_or_else(|| {
@@ -202,12 +251,68 @@ fn error_response(
 ) -> MessageStream {
     let timing = ShortTermTimingProperties::until_now(start_timestamp);
     let props = reqp.to_response(err.status(), timing);
-    let e = err.to_svc_error();
+    let e = err.to_payload();
     let resp = OutgoingResponse::unicast(e, props, reqp, API_VERSION);
 
     Box::new(stream::once(future::ready(Box::new(resp) as Message)))
 }
 
+/// Checks the payload before parsing so that malformed giant messages get rejected
+/// without building a JSON tree for them.
+fn validate_payload(payload: &str, constraint: &MessageConstraint) -> Result<(), AppError> {
+    if payload.len() > constraint.max_size {
+        return Err(anyhow!(
+            "Payload size {} exceeds {} bytes",
+            payload.len(),
+            constraint.max_size
+        ))
+        .error(AppErrorKind::MessageTooLarge);
+    }
+
+    let bytes = payload.as_bytes();
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut idx = 0;
+
+    while idx < bytes.len() {
+        match (in_string, bytes[idx]) {
+            // Postgres can't store NUL characters neither in text nor in jsonb.
+            (_, b'\0') => {
+                return Err(anyhow!("Payload contains NUL character"))
+                    .error(AppErrorKind::MalformedMessage)
+            }
+            (true, b'\\') => {
+                if bytes[idx + 1..].starts_with(b"u0000") {
+                    return Err(anyhow!("Payload contains NUL character"))
+                        .error(AppErrorKind::MalformedMessage);
+                }
+
+                // Skip the escaped character.
+                idx += 1;
+            }
+            (true, b'"') => in_string = false,
+            (false, b'"') => in_string = true,
+            (false, b'{' | b'[') => {
+                depth += 1;
+
+                if depth > constraint.max_depth {
+                    return Err(anyhow!(
+                        "Payload nesting exceeds {} levels",
+                        constraint.max_depth
+                    ))
+                    .error(AppErrorKind::MalformedMessage);
+                }
+            }
+            (false, b'}' | b']') => depth = depth.saturating_sub(1),
+            _ => (),
+        }
+
+        idx += 1;
+    }
+
+    Ok(())
+}
+
 pub fn publish_message(agent: &mut Agent, message: Message) -> Result<(), AppError> {
     agent
         .publish_publishable(message)
@@ -255,9 +360,25 @@ impl<'async_trait, H: 'async_trait + Sync + endpoint::RequestHandler>
                 Ok(payload) => {
                     let app_result =
                         H::handle(context, payload, RequestParams::MqttParams(reqp)).await;
-                    context.metrics().observe_app_result(&app_result);
+                    context
+                        .metrics()
+                        .observe_app_result(reqp.method(), &app_result);
+                    if let Ok(response) = &app_result {
+                        redis_bridge::mirror(&*context, response.notifications());
+                    }
+
+                    let config = context.config();
+                    let metrics = context.metrics();
                     app_result
-                        .and_then(|r| r.into_mqtt_messages(reqp))
+                        .and_then(|mut r| {
+                            r.limit_notification_size(
+                                &config.notification_size,
+                                &metrics,
+                                reqp.as_agent_id(),
+                            );
+
+                            r.into_mqtt_messages(reqp, &config.notification_batching)
+                        })
                         .unwrap_or_else(|app_error| {
                         error!(err = ?app_error, status = app_error.status().as_u16(), kind = app_error.kind(), "Failed to handle request");
 
@@ -380,3 +501,50 @@ impl<'async_trait, H: 'async_trait + endpoint::EventHandler> EventEnvelopeHandle
         Box::pin(handle_envelope::<H, C>(context, event))
     }
 }
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use svc_agent::mqtt::ResponseStatus;
+
+    use super::*;
+
+    fn constraint() -> MessageConstraint {
+        MessageConstraint {
+            max_size: 64,
+            max_depth: 3,
+        }
+    }
+
+    #[test]
+    fn validate_valid_payload() {
+        let payload = r#"{"a":[{"b":"[[[{{{"}],"c":"\\\"]]]"}"#;
+        assert!(validate_payload(payload, &constraint()).is_ok());
+    }
+
+    #[test]
+    fn validate_too_large_payload() {
+        let payload = format!(r#"{{"a":"{}"}}"#, "x".repeat(64));
+        let err = validate_payload(&payload, &constraint()).expect_err("Unexpected success");
+        assert_eq!(err.status(), ResponseStatus::PAYLOAD_TOO_LARGE);
+        assert_eq!(err.kind(), "message_too_large");
+    }
+
+    #[test]
+    fn validate_too_deep_payload() {
+        let err =
+            validate_payload(r#"{"a":[[{}]]}"#, &constraint()).expect_err("Unexpected success");
+        assert_eq!(err.status(), ResponseStatus::UNPROCESSABLE_ENTITY);
+        assert_eq!(err.kind(), "malformed_message");
+
+        assert!(validate_payload(r#"{"a":[[]],"b":[[]]}"#, &constraint()).is_ok());
+    }
+
+    #[test]
+    fn validate_nul_payload() {
+        assert!(validate_payload(r#"{"a":"\u0000"}"#, &constraint()).is_err());
+        assert!(validate_payload("{\"a\":\"\0\"}", &constraint()).is_err());
+        assert!(validate_payload(r#"{"a":"\\u0000"}"#, &constraint()).is_ok());
+    }
+}
@@ -9,9 +9,11 @@ use svc_agent::mqtt::{Agent, AgentBuilder, AgentNotification, ConnectionMode, Qo
 use svc_agent::{request::Dispatcher, AgentId, Authenticable, SharedGroup, Subscription};
 use svc_authn::token::jws_compact;
 use svc_authz::cache::{AuthzCache, ConnectionPool as RedisConnectionPool};
-use svc_error::extension::sentry as svc_sentry;
-use tokio::{sync::mpsc, task};
-use tracing::{error, info, warn};
+use tokio::{
+    sync::{mpsc, Semaphore},
+    task,
+};
+use tracing::{error, info};
 
 use crate::app::broker_client::{BrokerClient, HttpBrokerClient};
 use crate::app::http::build_router;
@@ -19,8 +21,10 @@ use crate::{app::context::GlobalContext, metrics::Metrics};
 use crate::{
     authz::Authz,
     config::{self, Config},
+    db::event::DataEncryption,
 };
 use context::AppContextBuilder;
+use event_hooks::{CreatedEventsCounter, EventHooks};
 use message_handler::MessageHandler;
 
 pub const API_VERSION: &str = "v1";
@@ -68,25 +72,42 @@ pub async fn run(
     .context("Error converting authz config to clients")?;
 
     // Sentry
-    let _j = if let Some(ref sentry_config) = config.sentry {
+    let _sentry_guard = config.sentry.as_ref().map(|sentry_config| {
         let mut sentry_config = sentry_config.clone();
-        sentry_config.release = sentry::release_name!();
-        Some(svc_error::extension::sentry::init(&sentry_config))
-    } else {
-        None
-    };
+        sentry_config.release = ::sentry::release_name!();
+        sentry::init(&sentry_config)
+    });
 
     // Subscribe to topics
     subscribe(&mut agent, &agent_id)?;
 
     let registry = Registry::new();
-    let metrics = Arc::new(Metrics::new(&registry)?);
+    let request_buckets = config
+        .metrics
+        .as_ref()
+        .and_then(|metrics| metrics.request_duration_buckets.clone());
+    let metrics = Arc::new(Metrics::with_request_buckets(&registry, request_buckets)?);
 
     // Context
-    let authz = Authz::new(authz, metrics.clone());
+    let authz = match config.authz_decision_cache {
+        Some(ref cache_config) => Authz::new(authz, metrics.clone()).decision_cache(cache_config),
+        None => Authz::new(authz, metrics.clone()),
+    };
     let queue_counter = agent.get_queue_counter();
     let dispatcher = Arc::new(Dispatcher::new(&agent));
     let broker_client = build_broker_client(&config, &token);
+    let mut sampled_pools = vec![("rw", db.clone())];
+    sampled_pools.extend(ro_db.iter().map(|db| ("ro", db.clone())));
+
+    let background_db = match config.background_db {
+        Some(ref background_db) => {
+            let pool = crate::db::create_sibling_pool(&db, background_db.pool_size).await;
+            sampled_pools.push(("bg", pool.clone()));
+            Some(pool)
+        }
+        None => None,
+    };
+
     let context_builder = AppContextBuilder::new(config.clone(), authz, db, broker_client);
 
     let context_builder = match ro_db {
@@ -94,12 +115,30 @@ pub async fn run(
         None => context_builder,
     };
 
+    let context_builder = match background_db {
+        Some(db) => context_builder.background_db(db),
+        None => context_builder,
+    };
+
     let context_builder = match redis_pool {
         Some(pool) => context_builder.redis_pool(pool),
         None => context_builder,
     };
 
-    let context = context_builder.queue_counter(queue_counter).build(metrics);
+    let context_builder = match config.data_encryption {
+        Some(ref data_encryption) => context_builder.data_encryption(
+            DataEncryption::new(data_encryption).context("Failed to configure data encryption")?,
+        ),
+        None => context_builder,
+    };
+
+    // Hooks run in the order of registration.
+    let event_hooks = EventHooks::new().register(CreatedEventsCounter::new(metrics.clone()));
+
+    let context = context_builder
+        .queue_counter(queue_counter)
+        .event_hooks(event_hooks)
+        .build(metrics);
 
     let metrics_task = config.metrics.as_ref().map(|metrics| {
         svc_utils::metrics::MetricsServer::new_with_registry(registry, metrics.http.bind_address)
@@ -120,8 +159,35 @@ pub async fn run(
             }),
     );
 
+    let nats_responder = match config.nats.clone().zip(config.nats_responder.clone()) {
+        Some((nats_cfg, nats_responder_cfg)) => {
+            let nats_responder = nats_responder::run(
+                ctx.clone(),
+                nats_cfg,
+                nats_responder_cfg,
+                graceful_rx.clone(),
+            )
+            .await
+            .context("nats responder")?;
+            info!("Nats responder started");
+
+            Some(nats_responder)
+        }
+        None => None,
+    };
+
     let nats_consumer = match config.nats.zip(config.nats_consumer) {
-        Some((nats_cfg, nats_consumer_cfg)) => {
+        Some((mut nats_cfg, nats_consumer_cfg)) => {
+            let shard = nats_consumer::Shard::new(nats_consumer_cfg.sharding.as_ref())
+                .context("nats consumer shard")?;
+
+            // Each replica pulls all the messages with its own consumer.
+            if nats_consumer_cfg.sharding.is_some() {
+                if let Some(subscribe) = nats_cfg.subscribe.as_mut() {
+                    subscribe.consumer = format!("{}-{}", subscribe.consumer, shard.ordinal());
+                }
+            }
+
             let nats_client = svc_nats_client::Client::new(nats_cfg)
                 .await
                 .context("nats client")?;
@@ -131,17 +197,60 @@ pub async fn run(
                 ctx.clone(),
                 nats_client,
                 nats_consumer_cfg,
+                shard,
                 graceful_rx.clone(),
             )
             .await
             .context("nats consumer")?;
-            info!("Nats consumer started");
+            info!(?shard, "Nats consumer started");
 
             Some(nats_consumer)
         }
         None => None,
     };
 
+    let vacuum_scheduler = vacuum_scheduler::run(
+        ctx.background_db().to_owned(),
+        metrics.clone(),
+        ctx.jobs(),
+        config.vacuum.clone(),
+        graceful_rx.clone(),
+    )?;
+
+    if vacuum_scheduler.is_some() {
+        info!("Vacuum scheduler started");
+    }
+
+    let edition_commit_resumer =
+        edition_commit_resumer::run(ctx.clone(), agent.clone(), graceful_rx.clone());
+
+    let config_reloader = config_reloader::run(ctx.clone(), graceful_rx.clone())?;
+
+    let room_auto_closer = room_auto_closer::run(ctx.clone(), agent.clone(), graceful_rx.clone());
+
+    if room_auto_closer.is_some() {
+        info!("Room auto closer started");
+    }
+
+    let room_inactivity_monitor = room_inactivity_monitor::run(ctx.clone(), graceful_rx.clone());
+
+    if room_inactivity_monitor.is_some() {
+        info!("Room inactivity monitor started");
+    }
+
+    let derived_room_cleaner = derived_room_cleaner::run(ctx.clone(), graceful_rx.clone());
+
+    if derived_room_cleaner.is_some() {
+        info!("Derived room cleaner started");
+    }
+
+    let room_lock_scheduler =
+        room_lock_scheduler::run(ctx.clone(), agent.clone(), graceful_rx.clone());
+
+    let poll_closer = poll_closer::run(ctx.clone(), agent.clone(), graceful_rx.clone());
+
+    let db_pool_sampler = db_pool_sampler::run(metrics.clone(), sampled_pools, graceful_rx.clone());
+
     // Message handler
     let message_handler = Arc::new(MessageHandler::new(agent.clone(), context, dispatcher));
 
@@ -149,7 +258,12 @@ pub async fn run(
     let mut signals_stream = signal_hook_tokio::Signals::new(TERM_SIGNALS)?.fuse();
     let signals = signals_stream.next();
 
-    let main_loop_task = task::spawn(main_loop(rx, message_handler.clone(), metrics.clone()));
+    let main_loop_task = task::spawn(main_loop(
+        rx,
+        message_handler.clone(),
+        metrics.clone(),
+        config.mqtt_queue.clone(),
+    ));
     let _ = futures::future::select(signals, main_loop_task).await;
     unsubscribe(&mut agent, &agent_id)?;
 
@@ -161,6 +275,56 @@ pub async fn run(
         }
     }
 
+    if let Some(responder) = nats_responder {
+        if let Err(err) = responder.await {
+            error!(%err, "failed to await nats responder completion");
+        }
+    }
+
+    if let Some(scheduler) = vacuum_scheduler {
+        if let Err(err) = scheduler.await {
+            error!(%err, "failed to await vacuum scheduler completion");
+        }
+    }
+
+    if let Err(err) = edition_commit_resumer.await {
+        error!(%err, "failed to await edition commit resumer completion");
+    }
+
+    if let Err(err) = config_reloader.await {
+        error!(%err, "failed to await config reloader completion");
+    }
+
+    if let Some(closer) = room_auto_closer {
+        if let Err(err) = closer.await {
+            error!(%err, "failed to await room auto closer completion");
+        }
+    }
+
+    if let Some(monitor) = room_inactivity_monitor {
+        if let Err(err) = monitor.await {
+            error!(%err, "failed to await room inactivity monitor completion");
+        }
+    }
+
+    if let Some(cleaner) = derived_room_cleaner {
+        if let Err(err) = cleaner.await {
+            error!(%err, "failed to await derived room cleaner completion");
+        }
+    }
+
+    if let Err(err) = room_lock_scheduler.await {
+        error!(%err, "failed to await room lock scheduler completion");
+    }
+
+    if let Err(err) = poll_closer.await {
+        error!(%err, "failed to await poll closer completion");
+    }
+
+    if let Err(err) = db_pool_sampler.await {
+        error!(%err, "failed to await db pool sampler completion");
+    }
+
     if let Some(metrics_task) = metrics_task {
         metrics_task.shutdown().await;
     }
@@ -182,18 +346,67 @@ async fn main_loop(
     mut mq_rx: mpsc::UnboundedReceiver<AgentNotification>,
     message_handler: Arc<MessageHandler<context::AppContext>>,
     metrics: Arc<Metrics>,
+    config: config::QueueConfig,
 ) {
+    // Bounds the number of messages handled at the same time not to exhaust the DB pool.
+    let semaphore = Arc::new(Semaphore::new(config.max_concurrency));
+
     loop {
         if let Some(message) = mq_rx.recv().await {
             let message_handler = message_handler.clone();
-            let request_started = metrics.clone().request_started();
             let metrics = metrics.clone();
+
+            if let AgentNotification::Message(message, _) = message {
+                metrics.total_requests.inc();
+
+                let permit = match semaphore.clone().try_acquire_owned() {
+                    Ok(permit) => Some(permit),
+                    Err(_)
+                        if (metrics.mqtt_pending_messages.get() as usize) < config.max_pending =>
+                    {
+                        None
+                    }
+                    Err(_) => {
+                        metrics.mqtt_rejected_messages.inc();
+                        message_handler.reject(&message);
+                        continue;
+                    }
+                };
+
+                let semaphore = semaphore.clone();
+
+                if permit.is_none() {
+                    metrics.mqtt_pending_messages.inc();
+                }
+
+                task::spawn(async move {
+                    let _permit = match permit {
+                        Some(permit) => permit,
+                        None => {
+                            let permit = semaphore.acquire_owned().await;
+                            metrics.mqtt_pending_messages.dec();
+
+                            match permit {
+                                Ok(permit) => permit,
+                                // The semaphore is never closed.
+                                Err(_) => return,
+                            }
+                        }
+                    };
+
+                    let request_started = metrics.clone().request_started();
+                    message_handler.handle(&message).await;
+                    drop(request_started);
+                });
+
+                continue;
+            }
+
+            let request_started = metrics.clone().request_started();
             task::spawn(async move {
                 match message {
-                    AgentNotification::Message(ref message, _) => {
-                        metrics.total_requests.inc();
-                        message_handler.handle(message).await;
-                    }
+                    // Handled above.
+                    AgentNotification::Message(_, _) => (),
                     AgentNotification::Disconnect => {
                         metrics.mqtt_disconnect.inc();
                         error!("Disconnected from broker")
@@ -271,8 +484,7 @@ fn resubscribe(agent: &mut Agent, agent_id: &AgentId) {
         let err = err.context("Failed to resubscribe after reconnection");
         error!("{:?}", err);
 
-        svc_sentry::send(Arc::new(err))
-            .unwrap_or_else(|err| warn!("Error sending error to Sentry: {:?}", err));
+        sentry::send(&err, &[]);
     }
 }
 
@@ -288,12 +500,33 @@ fn build_broker_client(config: &Config, token: &str) -> Arc<dyn BrokerClient> {
 }
 
 pub mod broker_client;
+pub mod config_reloader;
+pub mod content_filter;
 pub mod context;
+pub mod db_pool_sampler;
+pub mod derived_room_cleaner;
+pub mod edition_commit_resumer;
 pub mod endpoint;
 pub mod error;
+pub mod event_hooks;
 pub mod http;
+pub mod jobs;
 pub mod message_handler;
 pub mod nats_consumer;
+pub mod nats_responder;
+pub mod openapi;
 pub mod operations;
+pub mod poll_closer;
+pub mod presence;
+pub mod presence_cache;
+pub mod redis_bridge;
+pub mod room_auto_closer;
+pub mod room_cache;
+pub mod room_inactivity_monitor;
+pub mod room_lock_scheduler;
 pub mod s3_client;
+pub mod sentry;
 pub mod service_utils;
+pub mod share_token;
+pub mod vacuum_scheduler;
+pub mod webhook_client;
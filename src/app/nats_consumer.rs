@@ -4,26 +4,54 @@ use crate::{
         error::{Error as AppError, ErrorKind, ErrorKindExt},
     },
     config, db,
+    db::event::DataEncryption,
 };
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use chrono::{DateTime, TimeZone, Utc};
 use futures_util::StreamExt;
 use serde_json::json;
+use sqlx::{postgres::PgConnection, Acquire};
 use std::{str::FromStr, sync::Arc, time::Duration};
+use svc_agent::AgentId;
 use svc_conference_events::{Event, EventV1};
 use svc_nats_client::{
     AckKind as NatsAckKind, Client, Message, MessageStream, NatsClient, Subject, SubscribeError,
 };
-use tokio::{sync::watch, task::JoinHandle, time::Instant};
+use tokio::{
+    sync::{mpsc, watch},
+    task::JoinHandle,
+    time::Instant,
+};
 use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// Capacity of a worker's queue. The stream isn't polled while the queue is full.
+const WORKER_QUEUE_SIZE: usize = 64;
 
 pub async fn run(
     ctx: Arc<dyn GlobalContext + Send>,
     nats_client: Client,
     nats_consumer_config: config::NatsConsumer,
+    shard: Shard,
     shutdown_rx: watch::Receiver<()>,
 ) -> Result<JoinHandle<Result<(), SubscribeError>>> {
     let handle = tokio::spawn(async move {
+        let (queues, workers): (Vec<_>, Vec<_>) = (0..shard.workers)
+            .map(|_| {
+                let (tx, rx) = mpsc::channel(WORKER_QUEUE_SIZE);
+
+                let worker = tokio::spawn(run_worker(
+                    ctx.clone(),
+                    nats_client.clone(),
+                    nats_consumer_config.clone(),
+                    rx,
+                    shutdown_rx.clone(),
+                ));
+
+                (tx, worker)
+            })
+            .unzip();
+
         // In case of subscription errors we don't want to spam sentry
         let mut sentry_last_sent = Instant::now() - nats_consumer_config.suspend_sentry_interval;
 
@@ -47,14 +75,8 @@ pub async fn run(
             };
 
             // Run the loop of getting messages from the stream
-            let reason = handle_stream(
-                ctx.as_ref(),
-                &nats_client,
-                &nats_consumer_config,
-                messages,
-                shutdown_rx.clone(),
-            )
-            .await;
+            let reason =
+                handle_stream(ctx.as_ref(), &shard, &queues, messages, shutdown_rx.clone()).await;
 
             match reason {
                 CompletionReason::Shutdown => {
@@ -80,6 +102,14 @@ pub async fn run(
             }
         }
 
+        drop(queues);
+
+        for worker in workers {
+            if let Err(err) = worker.await {
+                error!(%err, "failed to await nats worker completion");
+            }
+        }
+
         Ok::<_, SubscribeError>(())
     });
 
@@ -91,25 +121,15 @@ enum CompletionReason {
     StreamClosed,
 }
 
+/// Dispatches messages of the replica's classrooms to workers and acks the others.
 async fn handle_stream(
     ctx: &dyn GlobalContext,
-    nats_client: &Client,
-    nats_consumer_config: &config::NatsConsumer,
+    shard: &Shard,
+    queues: &[mpsc::Sender<Message>],
     mut messages: MessageStream,
     mut shutdown_rx: watch::Receiver<()>,
 ) -> CompletionReason {
-    let mut retry_count = 0;
-    let mut suspend_interval: Option<Duration> = None;
-
     loop {
-        if let Some(interval) = suspend_interval.take() {
-            warn!(
-                "nats consumer suspenses the processing of nats messages on {} seconds",
-                interval.as_secs()
-            );
-            tokio::time::sleep(interval).await;
-        }
-
         tokio::select! {
             result = messages.next() => {
                 let message = match result {
@@ -139,43 +159,29 @@ async fn handle_stream(
                     message.subject, message.payload, message.headers
                 );
 
-                let result = handle_message(ctx, &message).await;
-                match result {
-                    Ok(_) => {
-                        retry_count = 0;
+                let metrics = ctx.metrics();
+                let subject = subject_pattern(&message.subject);
+                metrics.observe_nats_message(&subject, "received");
 
-                        if let Err(err) = message.ack().await {
-                            anyhow!(err)
-                                .context("nats ack error")
-                                .kind(ErrorKind::NatsPublishFailed)
-                                .log()
-                                .notify_sentry();
-                        }
-                    }
-                    Err(HandleMessageError::DbConnAcquisitionFailed(err)) => {
-                        err.log().notify_sentry();
+                let worker = match Subject::from_str(&message.subject) {
+                    Ok(s) => shard.worker(s.classroom_id()),
+                    // The worker terminates the message as it fails to parse it anyway.
+                    Err(_) => Some(0),
+                };
 
-                        if let Err(err) = message.ack_with(NatsAckKind::Nak(None)).await {
-                            anyhow!(err)
-                                .context("nats nack error")
-                                .kind(ErrorKind::NatsPublishFailed)
-                                .log()
-                                .notify_sentry();
+                match worker {
+                    Some(worker) => {
+                        // Workers stop only on shutdown.
+                        if queues[worker].send(message).await.is_err() {
+                            return CompletionReason::Shutdown;
                         }
-
-                        retry_count += 1;
-                        let interval = next_suspend_interval(retry_count, nats_consumer_config);
-                        suspend_interval = Some(interval);
                     }
-                    Err(HandleMessageError::Other(err)) => {
-                        err
-                            .kind(ErrorKind::NatsMessageHandlingFailed)
-                            .log()
-                            .notify_sentry();
+                    None => {
+                        metrics.observe_nats_message(&subject, "skipped");
 
-                        if let Err(err) = nats_client.terminate(message).await {
+                        if let Err(err) = message.ack().await {
                             anyhow!(err)
-                                .context("failed to handle nats message")
+                                .context("nats ack error")
                                 .kind(ErrorKind::NatsPublishFailed)
                                 .log()
                                 .notify_sentry();
@@ -191,6 +197,175 @@ async fn handle_stream(
     }
 }
 
+/// Handles messages from the queue one by one.
+async fn run_worker(
+    ctx: Arc<dyn GlobalContext + Send>,
+    nats_client: Client,
+    nats_consumer_config: config::NatsConsumer,
+    mut queue: mpsc::Receiver<Message>,
+    mut shutdown_rx: watch::Receiver<()>,
+) {
+    let mut retry_count = 0;
+    let mut suspend_interval: Option<Duration> = None;
+
+    loop {
+        if let Some(interval) = suspend_interval.take() {
+            warn!(
+                "nats consumer suspenses the processing of nats messages on {} seconds",
+                interval.as_secs()
+            );
+            tokio::time::sleep(interval).await;
+        }
+
+        let message = tokio::select! {
+            message = queue.recv() => match message {
+                Some(message) => message,
+                None => break,
+            },
+            // Graceful shutdown. Unacked messages left in the queue get redelivered.
+            _ = shutdown_rx.changed() => break,
+        };
+
+        let metrics = ctx.metrics();
+        let subject = subject_pattern(&message.subject);
+
+        let result = handle_message(ctx.as_ref(), &message, &subject).await;
+        match result {
+            Ok(_) => {
+                metrics.observe_nats_message(&subject, "ok");
+                retry_count = 0;
+
+                if let Err(err) = message.ack().await {
+                    anyhow!(err)
+                        .context("nats ack error")
+                        .kind(ErrorKind::NatsPublishFailed)
+                        .log()
+                        .notify_sentry();
+                }
+            }
+            Err(HandleMessageError::DbConnAcquisitionFailed(err)) => {
+                metrics.observe_nats_message(&subject, "transient_failure");
+                err.log().notify_sentry();
+
+                if let Err(err) = message.ack_with(NatsAckKind::Nak(None)).await {
+                    anyhow!(err)
+                        .context("nats nack error")
+                        .kind(ErrorKind::NatsPublishFailed)
+                        .log()
+                        .notify_sentry();
+                }
+
+                retry_count += 1;
+                let interval = next_suspend_interval(retry_count, &nats_consumer_config);
+                suspend_interval = Some(interval);
+            }
+            Err(HandleMessageError::Other(err)) => {
+                metrics.observe_nats_message(&subject, "permanent_failure");
+                err.kind(ErrorKind::NatsMessageHandlingFailed)
+                    .log()
+                    .notify_sentry();
+
+                if let Err(err) = nats_client.terminate(message).await {
+                    anyhow!(err)
+                        .context("failed to handle nats message")
+                        .kind(ErrorKind::NatsPublishFailed)
+                        .log()
+                        .notify_sentry();
+                }
+            }
+        }
+    }
+}
+
+/// Classrooms the replica handles messages of and their split between its workers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Shard {
+    replicas: u32,
+    ordinal: u32,
+    workers: usize,
+}
+
+impl Shard {
+    /// A single replica with a single worker handles all the messages without the config.
+    pub fn new(config: Option<&config::NatsShardingConfig>) -> Result<Self> {
+        let config = match config {
+            Some(config) => config,
+            None => {
+                return Ok(Self {
+                    replicas: 1,
+                    ordinal: 0,
+                    workers: 1,
+                })
+            }
+        };
+
+        let ordinal = match config.ordinal {
+            Some(ordinal) => ordinal,
+            None => {
+                let hostname =
+                    std::env::var("HOSTNAME").context("missing HOSTNAME to get ordinal from")?;
+
+                hostname_ordinal(&hostname)
+                    .with_context(|| format!("no ordinal in hostname: {}", hostname))?
+            }
+        };
+
+        if ordinal >= config.replicas {
+            bail!(
+                "replica ordinal {} is out of range for {} replicas",
+                ordinal,
+                config.replicas
+            );
+        }
+
+        if config.workers == 0 {
+            bail!("nats consumer needs at least one worker");
+        }
+
+        Ok(Self {
+            replicas: config.replicas,
+            ordinal,
+            workers: config.workers,
+        })
+    }
+
+    pub fn ordinal(&self) -> u32 {
+        self.ordinal
+    }
+
+    /// Returns the worker to handle messages of the classroom with
+    /// or `None` if they belong to another replica.
+    fn worker(&self, classroom_id: Uuid) -> Option<usize> {
+        // Classroom ids are random so their bits are spread evenly.
+        let hash = classroom_id.as_u128();
+        let replicas = u128::from(self.replicas);
+
+        if hash % replicas != u128::from(self.ordinal) {
+            return None;
+        }
+
+        Some(((hash / replicas) % self.workers as u128) as usize)
+    }
+}
+
+/// Parses the ordinal of a StatefulSet pod from its hostname, e.g. `event-2`.
+fn hostname_ordinal(hostname: &str) -> Option<u32> {
+    hostname.rsplit_once('-')?.1.parse().ok()
+}
+
+/// Replaces ids in the subject with `*` to keep metric labels bounded,
+/// e.g. `classrooms.<uuid>.video_group` becomes `classrooms.*.video_group`.
+fn subject_pattern(subject: &str) -> String {
+    subject
+        .split('.')
+        .map(|token| match Uuid::parse_str(token) {
+            Ok(_) => "*",
+            Err(_) => token,
+        })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
 fn next_suspend_interval(
     retry_count: u32,
     nats_consumer_config: &config::NatsConsumer,
@@ -203,7 +378,7 @@ fn next_suspend_interval(
     Duration::from_secs(seconds)
 }
 
-enum HandleMessageError {
+pub enum HandleMessageError {
     DbConnAcquisitionFailed(AppError),
     Other(anyhow::Error),
 }
@@ -217,85 +392,288 @@ impl From<anyhow::Error> for HandleMessageError {
 async fn handle_message(
     ctx: &dyn GlobalContext,
     message: &Message,
+    subject_pattern: &str,
 ) -> Result<(), HandleMessageError> {
-    let subject = Subject::from_str(&message.subject).context("parse nats subject")?;
-    let entity_type = subject.entity_type();
+    let event = NatsEvent::parse(message)?;
 
-    let event =
-        serde_json::from_slice::<Event>(message.payload.as_ref()).context("parse nats payload")?;
+    ctx.metrics()
+        .observe_nats_lag(subject_pattern, Utc::now() - event.created_at);
 
-    let (label, created_at) = match event {
-        Event::V1(EventV1::VideoGroup(e)) => (e.as_label().to_owned(), e.created_at()),
-    };
+    let result = apply_event(ctx, &event).await;
 
-    let classroom_id = subject.classroom_id();
-    let room = {
-        let mut conn = ctx
-            .get_conn()
-            .await
-            .map_err(HandleMessageError::DbConnAcquisitionFailed)?;
+    // Keep the event to requeue it when the cause is fixed, e.g. the room gets created.
+    if let Err(HandleMessageError::Other(ref err)) = result {
+        if let Err(err) = insert_dead_letter(ctx, &message.subject, &event, err).await {
+            error!("failed to store nats dead letter: {:?}", err);
+        }
+    }
 
-        db::room::FindQuery::by_classroom_id(classroom_id)
-            .execute(&mut conn)
-            .await
-            .context("find room by classroom_id")?
-            .ok_or(HandleMessageError::Other(anyhow!(
-                "failed to get room by classroom_id: {}",
-                classroom_id
-            )))?
-    };
-
-    let headers = svc_nats_client::Headers::try_from(message.headers.clone().unwrap_or_default())
-        .context("parse nats headers")?;
-    let agent_id = headers.sender_id();
-    let entity_event_id = headers.event_id().sequence_id();
-
-    let created_at: DateTime<Utc> = Utc.timestamp_nanos(created_at);
-    let occurred_at = room
-        .time()
-        .map(|t| {
-            (created_at - t.start().to_owned())
-                .num_nanoseconds()
-                .unwrap_or(i64::MAX)
+    result
+}
+
+/// The data of a NATS message needed to create an event.
+#[derive(Debug)]
+pub struct NatsEvent {
+    classroom_id: Uuid,
+    entity_type: String,
+    entity_event_id: i64,
+    label: String,
+    agent_id: AgentId,
+    created_at: DateTime<Utc>,
+}
+
+impl NatsEvent {
+    fn parse(message: &Message) -> Result<Self> {
+        let subject = Subject::from_str(&message.subject).context("parse nats subject")?;
+
+        let event = serde_json::from_slice::<Event>(message.payload.as_ref())
+            .context("parse nats payload")?;
+
+        let (label, created_at) = match event {
+            Event::V1(EventV1::VideoGroup(e)) => (e.as_label().to_owned(), e.created_at()),
+        };
+
+        let headers =
+            svc_nats_client::Headers::try_from(message.headers.clone().unwrap_or_default())
+                .context("parse nats headers")?;
+
+        Ok(Self {
+            classroom_id: subject.classroom_id(),
+            entity_type: subject.entity_type().to_owned(),
+            entity_event_id: headers.event_id().sequence_id(),
+            label,
+            agent_id: headers.sender_id().to_owned(),
+            created_at: Utc.timestamp_nanos(created_at),
         })
-        .map_err(|_| HandleMessageError::Other(anyhow!("invalid room time")))?;
+    }
+}
 
+impl From<&db::nats_dead_letter::Object> for NatsEvent {
+    fn from(dead_letter: &db::nats_dead_letter::Object) -> Self {
+        Self {
+            classroom_id: dead_letter.classroom_id(),
+            entity_type: dead_letter.entity_type().to_owned(),
+            entity_event_id: dead_letter.entity_event_id(),
+            label: dead_letter.label().to_owned(),
+            agent_id: dead_letter.created_by().to_owned(),
+            created_at: dead_letter.event_created_at(),
+        }
+    }
+}
+
+/// Applies the entity event unless it was applied before.
+///
+/// Handlers run in a transaction after claiming the entity event, so duplicates
+/// are skipped whatever the handler does.
+pub async fn apply_event(
+    ctx: &dyn GlobalContext,
+    event: &NatsEvent,
+) -> Result<(), HandleMessageError> {
     let mut conn = ctx
         .get_conn()
         .await
         .map_err(HandleMessageError::DbConnAcquisitionFailed)?;
 
-    let result = db::event::InsertQuery::new(
+    let mut txn = conn.begin().await.context("begin transaction")?;
+
+    let claimed = db::event::ClaimEntityEventQuery::new(&event.entity_type, event.entity_event_id)
+        .execute(&mut txn)
+        .await
+        .context("claim entity event")?;
+
+    if !claimed {
+        warn!(
+            "duplicate nats message, entity_type: {:?}, entity_event_id: {:?}",
+            event.entity_type, event.entity_event_id
+        );
+
+        return Ok(());
+    }
+
+    create_event(&mut txn, event, ctx.data_encryption().as_deref()).await?;
+
+    txn.commit().await.context("commit transaction")?;
+    Ok(())
+}
+
+/// Creates an event in the classroom room.
+async fn create_event(
+    conn: &mut PgConnection,
+    event: &NatsEvent,
+    encryption: Option<&DataEncryption>,
+) -> Result<()> {
+    let entity_type = event.entity_type.as_str();
+    let classroom_id = event.classroom_id;
+
+    let room = db::room::FindQuery::by_classroom_id(classroom_id)
+        .execute(conn)
+        .await
+        .context("find room by classroom_id")?
+        .ok_or_else(|| anyhow!("failed to get room by classroom_id: {}", classroom_id))?;
+
+    let occurred_at = room
+        .time()
+        .map(|t| {
+            (event.created_at - t.start().to_owned())
+                .num_nanoseconds()
+                .unwrap_or(i64::MAX)
+        })
+        .map_err(|_| anyhow!("invalid room time"))?;
+
+    db::event::InsertQuery::new(
         room.id(),
         entity_type.to_string(),
-        json!({ entity_type: label }),
+        json!({ entity_type: event.label }),
         occurred_at,
-        agent_id.to_owned(),
+        event.agent_id.to_owned(),
+        encryption,
     )
     .context("invalid event data")?
     .entity_type(entity_type.to_string())
-    .entity_event_id(entity_event_id)
+    .entity_event_id(event.entity_event_id)
+    .execute(conn)
+    .await
+    .context("failed to create event from nats")?;
+
+    Ok(())
+}
+
+async fn insert_dead_letter(
+    ctx: &dyn GlobalContext,
+    subject: &str,
+    event: &NatsEvent,
+    err: &anyhow::Error,
+) -> Result<()> {
+    let mut conn = ctx.get_conn().await.map_err(|err| anyhow!("{:?}", err))?;
+
+    db::nats_dead_letter::InsertQuery::new(
+        subject.to_owned(),
+        event.classroom_id,
+        event.entity_type.to_owned(),
+        event.entity_event_id,
+        event.label.to_owned(),
+        event.agent_id.to_owned(),
+        event.created_at,
+    )
+    .error(format!("{:#}", err))
     .execute(&mut conn)
-    .await;
+    .await
+    .context("insert nats dead letter")?;
 
-    if let Err(sqlx::Error::Database(ref err)) = result {
-        if let Some("uniq_entity_type_entity_event_id") = err.constraint() {
-            warn!(
-                "duplicate nats message, entity_type: {:?}, entity_event_id: {:?}",
-                entity_type.to_string(),
-                entity_event_id
-            );
+    Ok(())
+}
 
-            return Ok(());
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::room::ClassType;
+    use crate::test_helpers::prelude::*;
+    use std::ops::Bound;
+
+    #[tokio::test]
+    async fn apply_event_once() {
+        let db = TestDb::new().await;
+        let sender = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let classroom_id = Uuid::new_v4();
+
+        let room = {
+            let mut conn = db.get_conn().await;
+
+            factory::Room::new(classroom_id, ClassType::Webinar)
+                .audience(USR_AUDIENCE)
+                .time((Bound::Included(Utc::now()), Bound::Unbounded))
+                .insert(&mut conn)
+                .await
         };
+
+        let context = TestContext::new(db.clone(), TestAuthz::new());
+
+        let event = NatsEvent {
+            classroom_id,
+            entity_type: "video_group".to_owned(),
+            entity_event_id: rand::random::<i64>().abs(),
+            label: "created".to_owned(),
+            agent_id: sender.agent_id().to_owned(),
+            created_at: Utc::now(),
+        };
+
+        // The message is delivered twice.
+        for _ in 0..2 {
+            assert!(apply_event(&context, &event).await.is_ok());
+        }
+
+        let mut conn = db.get_conn().await;
+
+        let events = db::event::ListQuery::new()
+            .room_id(room.id())
+            .kind("video_group".to_owned())
+            .execute(&mut conn)
+            .await
+            .expect("Failed to list events");
+
+        assert_eq!(events.len(), 1);
     }
 
-    if let Err(err) = result {
-        return Err(HandleMessageError::Other(anyhow!(
-            "failed to create event from nats: {}",
-            err
-        )));
+    #[test]
+    fn subject_pattern_hides_ids() {
+        let classroom_id = Uuid::new_v4();
+        let subject = format!("classrooms.{}.video_group", classroom_id);
+        assert_eq!(subject_pattern(&subject), "classrooms.*.video_group");
+        assert_eq!(subject_pattern("agents.video_group"), "agents.video_group");
     }
 
-    Ok(())
+    #[test]
+    fn shard_splits_classrooms() {
+        let shards = (0..3)
+            .map(|ordinal| {
+                let config = config::NatsShardingConfig {
+                    replicas: 3,
+                    ordinal: Some(ordinal),
+                    workers: 2,
+                };
+
+                Shard::new(Some(&config)).expect("Failed to build shard")
+            })
+            .collect::<Vec<_>>();
+
+        for _ in 0..100 {
+            let classroom_id = Uuid::new_v4();
+
+            let workers = shards
+                .iter()
+                .filter_map(|shard| shard.worker(classroom_id))
+                .collect::<Vec<_>>();
+
+            // Exactly one replica handles the classroom and always with the same worker.
+            assert_eq!(workers.len(), 1);
+            assert!(workers[0] < 2);
+
+            let shard = shards
+                .iter()
+                .find(|shard| shard.worker(classroom_id).is_some())
+                .unwrap();
+
+            assert_eq!(shard.worker(classroom_id), Some(workers[0]));
+        }
+
+        // Everything goes to the single worker without sharding.
+        let shard = Shard::new(None).expect("Failed to build shard");
+        assert_eq!(shard.worker(Uuid::new_v4()), Some(0));
+
+        let config = config::NatsShardingConfig {
+            replicas: 3,
+            ordinal: Some(3),
+            workers: 1,
+        };
+
+        assert!(Shard::new(Some(&config)).is_err());
+    }
+
+    #[test]
+    fn hostname_ordinal_parsing() {
+        assert_eq!(hostname_ordinal("event-2"), Some(2));
+        assert_eq!(hostname_ordinal("event-api-10"), Some(10));
+        assert_eq!(hostname_ordinal("event"), None);
+        assert_eq!(hostname_ordinal("event-abc"), None);
+    }
 }
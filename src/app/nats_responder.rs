@@ -0,0 +1,368 @@
+use std::sync::Arc;
+
+use anyhow::{Context as AnyhowContext, Result};
+use async_nats::{HeaderMap, Message};
+use futures_util::StreamExt;
+use http::StatusCode;
+use serde::de::DeserializeOwned;
+use serde_json::Value as JsonValue;
+use svc_agent::{AccountId, AgentId};
+use svc_authn::{jose::ConfigMap, token::jws_compact::extract::decode_jws_compact_with_config};
+use tokio::{
+    sync::{watch, Semaphore},
+    task::JoinHandle,
+};
+use tracing::{error, info, info_span, warn, Instrument};
+
+use crate::app::{
+    context::{AppContext, Context, GlobalContext},
+    endpoint::{room, state, RequestHandler, RequestResult},
+    error::{Error as AppError, ErrorExt, ErrorKind as AppErrorKind},
+    service_utils::RequestParams,
+};
+use crate::config;
+
+/// `Bearer <token>` of the account on whose behalf the request is made, the same as in HTTP requests.
+/// Any publisher on the subject gets here so the sender can't be trusted without the token.
+const AUTHORIZATION: &str = "Authorization";
+
+/// Label of the agent of the authenticated account.
+const AGENT_LABEL: &str = "Agent-Label";
+const DEFAULT_AGENT_LABEL: &str = "nats";
+
+/// HTTP status code of the reply.
+const RESPONSE_STATUS: &str = "Response-Status";
+
+/// Subscribes to NATS requests and replies to them until the shutdown.
+pub async fn run(
+    ctx: Arc<AppContext>,
+    nats_config: svc_nats_client::Config,
+    config: config::NatsResponder,
+    mut shutdown_rx: watch::Receiver<()>,
+) -> Result<JoinHandle<()>> {
+    let client = async_nats::ConnectOptions::with_credentials_file(nats_config.creds.into())
+        .await
+        .context("nats credentials")?
+        .connect(&nats_config.url)
+        .await
+        .context("nats connect")?;
+
+    let subject = format!("{}.>", config.subject_prefix);
+
+    let mut requests = client
+        .queue_subscribe(subject, config.queue_group.clone())
+        .await
+        .context("nats subscribe")?;
+
+    // Bounds the number of requests handled at the same time like `main_loop` does for MQTT.
+    let semaphore = Arc::new(Semaphore::new(config.queue.max_concurrency));
+    let metrics = ctx.metrics();
+
+    let handle = tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                message = requests.next() => match message {
+                    Some(message) => {
+                        let prefix = config.subject_prefix.as_str();
+
+                        let method = message
+                            .subject
+                            .strip_prefix(prefix)
+                            .and_then(|s| s.strip_prefix('.'))
+                            .unwrap_or_default()
+                            .to_owned();
+
+                        let permit = match semaphore.clone().try_acquire_owned() {
+                            Ok(permit) => Some(permit),
+                            Err(_)
+                                if (metrics.nats_pending_requests.get() as usize)
+                                    < config.queue.max_pending =>
+                            {
+                                None
+                            }
+                            Err(_) => {
+                                metrics.nats_rejected_requests.inc();
+                                reject(&client, message).await;
+                                continue;
+                            }
+                        };
+
+                        if permit.is_none() {
+                            metrics.nats_pending_requests.inc();
+                        }
+
+                        let semaphore = semaphore.clone();
+                        let metrics = metrics.clone();
+                        let span = info_span!("nats_request", %method);
+                        let task = respond(ctx.clone(), client.clone(), method, message);
+
+                        tokio::spawn(
+                            async move {
+                                let _permit = match permit {
+                                    Some(permit) => permit,
+                                    None => {
+                                        let permit = semaphore.acquire_owned().await;
+                                        metrics.nats_pending_requests.dec();
+
+                                        match permit {
+                                            Ok(permit) => permit,
+                                            // The semaphore is never closed.
+                                            Err(_) => return,
+                                        }
+                                    }
+                                };
+
+                                task.await
+                            }
+                            .instrument(span),
+                        );
+                    }
+                    None => {
+                        error!("Nats requests subscription closed");
+                        break;
+                    }
+                },
+                _ = shutdown_rx.changed() => {
+                    if let Err(err) = requests.unsubscribe().await {
+                        warn!(%err, "Failed to unsubscribe from nats requests");
+                    }
+
+                    info!("Nats responder stopped");
+                    break;
+                }
+            }
+        }
+    });
+
+    Ok(handle)
+}
+
+async fn respond(
+    ctx: Arc<AppContext>,
+    client: async_nats::Client,
+    method: String,
+    message: Message,
+) {
+    let reply = match message.reply {
+        Some(ref reply) => reply.to_owned(),
+        None => {
+            warn!(subject = %message.subject, "Nats request without reply subject");
+            return;
+        }
+    };
+
+    let result = match sender_agent_id(&ctx.config().authn, message.headers.as_ref()) {
+        Ok(agent_id) => {
+            let mut context = ctx.start_message();
+            handle_request(&mut context, &method, &agent_id, &message.payload).await
+        }
+        Err(err) => Err(err),
+    };
+
+    let (status, payload) = match result {
+        Ok(response) => response.into_reply(),
+        Err(err) => {
+            err.notify_sentry();
+
+            let payload = serde_json::to_value(err.to_payload()).unwrap_or_default();
+            (err.status(), payload)
+        }
+    };
+
+    publish_reply(&client, reply, status, &payload).await;
+}
+
+/// Replies with an error without handling the request when there are too many pending ones.
+async fn reject(client: &async_nats::Client, message: Message) {
+    let reply = match message.reply {
+        Some(reply) => reply,
+        None => return,
+    };
+
+    let err = AppError::new(
+        AppErrorKind::ServiceOverloaded,
+        anyhow!("Too many pending requests"),
+    );
+
+    let payload = serde_json::to_value(err.to_payload()).unwrap_or_default();
+    publish_reply(client, reply, err.status(), &payload).await;
+}
+
+async fn publish_reply(
+    client: &async_nats::Client,
+    reply: String,
+    status: StatusCode,
+    payload: &JsonValue,
+) {
+    let payload = match serde_json::to_vec(payload) {
+        Ok(payload) => payload,
+        Err(err) => {
+            error!(%err, "Failed to serialize nats reply");
+            return;
+        }
+    };
+
+    let mut headers = HeaderMap::new();
+    headers.insert(RESPONSE_STATUS, status.as_str());
+
+    if let Err(err) = client
+        .publish_with_headers(reply, headers, payload.into())
+        .await
+    {
+        error!(%err, "Failed to publish nats reply");
+    }
+}
+
+fn sender_agent_id(authn: &ConfigMap, headers: Option<&HeaderMap>) -> Result<AgentId, AppError> {
+    let token = headers
+        .and_then(|headers| headers.get(AUTHORIZATION))
+        .and_then(|value| value.as_str().strip_prefix("Bearer "))
+        .context("Missing authorization header")
+        .error(AppErrorKind::AuthenticationFailed)?;
+
+    let claims = decode_jws_compact_with_config::<String>(token, authn)
+        .context("Invalid access token")
+        .error(AppErrorKind::AuthenticationFailed)?
+        .claims;
+
+    let account_id = AccountId::new(claims.subject(), claims.audience());
+
+    let label = headers
+        .and_then(|headers| headers.get(AGENT_LABEL))
+        .map(|value| value.as_str())
+        .unwrap_or(DEFAULT_AGENT_LABEL);
+
+    Ok(AgentId::new(label, account_id))
+}
+
+/// Dispatches the request to the handler of the method. The payload is the same as in MQTT.
+async fn handle_request<C: Context>(
+    context: &mut C,
+    method: &str,
+    agent_id: &AgentId,
+    payload: &[u8],
+) -> RequestResult {
+    let reqp = RequestParams::Http { agent_id };
+
+    match method {
+        "room.read" => room::ReadHandler::handle(context, parse(payload)?, reqp).await,
+        "state.read" => state::ReadHandler::handle(context, parse(payload)?, reqp).await,
+        _ => Err(anyhow!("Unknown method '{}'", method)).error(AppErrorKind::UnknownMethod),
+    }
+}
+
+fn parse<T: DeserializeOwned>(payload: &[u8]) -> Result<T, AppError> {
+    serde_json::from_slice(payload)
+        .context("Failed to parse payload")
+        .error(AppErrorKind::InvalidPayload)
+}
+
+#[cfg(test)]
+mod tests {
+    use http::StatusCode;
+    use serde_json::{json, Value as JsonValue};
+
+    use super::*;
+    use crate::test_helpers::prelude::{
+        shared_helpers, TestAgent, TestAuthz, TestContext, TestDb, SVC_AUDIENCE, USR_AUDIENCE,
+    };
+
+    #[test]
+    fn authenticate_sender() {
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let authn = serde_json::from_value::<ConfigMap>(json!({
+            SVC_AUDIENCE: {
+                "audience": [USR_AUDIENCE],
+                "algorithm": "ES256",
+                "key": "data/keys/svc.public_key.p8.der.sample",
+            },
+        }))
+        .expect("Failed to parse authn config");
+
+        let token = svc_authn::token::jws_compact::TokenBuilder::new()
+            .issuer(SVC_AUDIENCE)
+            .subject(agent.agent_id())
+            .key(
+                svc_authn::jose::Algorithm::ES256,
+                &std::fs::read("data/keys/svc.private_key.p8.der.sample").unwrap(),
+            )
+            .build()
+            .expect("Failed to build access token");
+
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, format!("Bearer {}", token).as_str());
+        headers.insert(AGENT_LABEL, "web");
+
+        let agent_id = sender_agent_id(&authn, Some(&headers)).expect("Authentication failed");
+        assert_eq!(&agent_id, agent.agent_id());
+
+        // The identity can't be just claimed.
+        let mut headers = HeaderMap::new();
+        headers.insert("Sender-Agent-Id", agent.agent_id().to_string().as_str());
+
+        let err = sender_agent_id(&authn, Some(&headers)).expect_err("Unexpected authentication");
+        assert_eq!(err.status(), StatusCode::UNAUTHORIZED);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, "Bearer garbage");
+
+        let err = sender_agent_id(&authn, Some(&headers)).expect_err("Unexpected authentication");
+        assert_eq!(err.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn read_room() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "service", SVC_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        authz.allow(
+            agent.account_id(),
+            vec!["classrooms", &classroom_id],
+            "read",
+        );
+
+        let mut context = TestContext::new(db, authz);
+        let payload = serde_json::to_vec(&json!({ "id": room.id() })).unwrap();
+
+        let (status, payload) =
+            handle_request(&mut context, "room.read", agent.agent_id(), &payload)
+                .await
+                .expect("Room reading failed")
+                .into_reply();
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(payload["id"], JsonValue::String(room.id().to_string()));
+    }
+
+    #[tokio::test]
+    async fn read_room_not_authorized() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "service", SVC_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut context = TestContext::new(db, TestAuthz::new());
+        let payload = serde_json::to_vec(&json!({ "id": room.id() })).unwrap();
+
+        match handle_request(&mut context, "room.read", agent.agent_id(), &payload).await {
+            Ok(_) => panic!("Unexpected success on room reading"),
+            Err(err) => assert_eq!(err.status(), StatusCode::FORBIDDEN),
+        }
+
+        match handle_request(&mut context, "room.delete", agent.agent_id(), &payload).await {
+            Ok(_) => panic!("Unexpected success on unknown method"),
+            Err(err) => assert_eq!(err.status(), StatusCode::METHOD_NOT_ALLOWED),
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use utoipa::{
+    openapi::{
+        security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+        OpenApi as OpenApiDocument,
+    },
+    Modify, OpenApi,
+};
+
+use crate::app::{endpoint, error};
+use crate::db;
+
+/// OpenAPI document of the HTTP API served along with Swagger UI.
+///
+/// Routes are added here as they get annotated with `utoipa::path`,
+/// the rest are described in the docs only.
+#[derive(OpenApi)]
+#[openapi(
+    servers((url = "/api/v1")),
+    paths(
+        endpoint::list_errors,
+        endpoint::room::create,
+        endpoint::room::read,
+        endpoint::room::update,
+        endpoint::event::create,
+        endpoint::event::list,
+        endpoint::state::read,
+        endpoint::agent::list,
+        endpoint::activity::read,
+        endpoint::attachment::list,
+        endpoint::question::create,
+        endpoint::question::list,
+        endpoint::whiteboard::pages,
+    ),
+    components(schemas(
+        error::ErrorKindDescription,
+        error::ErrorPayload,
+        db::room::Object,
+        db::room::ClassType,
+        db::event::Object,
+        db::event::BinaryData,
+        db::event::Direction,
+        db::event::ModerationStatus,
+        db::agent::AgentWithBan,
+        db::agent::Status,
+        db::attachment::Object,
+        db::question::Object,
+        db::question::Sort,
+        db::whiteboard_page::Object,
+        crate::serde::attributes::MaybeLegacyAttributes,
+        endpoint::room::CreateRequest,
+        endpoint::room::UpdatePayload,
+        endpoint::activity::RoomActivity,
+        endpoint::activity::HourActivity,
+    )),
+    modifiers(&BearerAuth)
+)]
+pub struct ApiDoc;
+
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut OpenApiDocument) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "bearer",
+                SecurityScheme::Http(
+                    HttpBuilder::new()
+                        .scheme(HttpAuthScheme::Bearer)
+                        .bearer_format("JWT")
+                        .build(),
+                ),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod client_types;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn document_lists_annotated_routes() {
+        let document = serde_json::to_value(ApiDoc::openapi()).expect("Failed to serialize");
+
+        for path in [
+            "/errors",
+            "/rooms/{id}",
+            "/rooms/{id}/events",
+            "/rooms/{id}/state",
+        ] {
+            assert!(document["paths"][path].is_object(), "{path} is missing");
+        }
+
+        assert!(document["components"]["schemas"]["Room"].is_object());
+        assert!(document["components"]["schemas"]["Event"].is_object());
+        assert!(document["components"]["securitySchemes"]["bearer"].is_object());
+    }
+
+    #[test]
+    fn client_types_are_up_to_date() {
+        let document = serde_json::to_value(ApiDoc::openapi()).expect("Failed to serialize");
+        let rendered = client_types::render(&document);
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/client/src/types/generated.rs");
+
+        if std::env::var_os("UPDATE_CLIENT_TYPES").is_some() {
+            std::fs::write(path, &rendered).expect("Failed to write client types");
+        }
+
+        let current = std::fs::read_to_string(path).expect("Failed to read client types");
+
+        assert!(
+            current == rendered,
+            "Client types don't match the endpoint types, run the test with UPDATE_CLIENT_TYPES=1"
+        );
+    }
+}
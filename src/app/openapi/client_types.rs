@@ -0,0 +1,333 @@
+//! Renders payload and response types of the `event-client` crate from the OpenAPI document
+//! so that they follow the endpoint types instead of being copied by hand.
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde_json::{json, Value as JsonValue};
+
+pub const HEADER: &str = "\
+// @generated from the OpenAPI document of the service by its `client_types_are_up_to_date` test.
+// Don't edit by hand, run `UPDATE_CLIENT_TYPES=1 cargo test client_types_are_up_to_date` instead.
+";
+
+enum Source {
+    /// A component schema.
+    Schema(&'static str),
+    /// JSON body of the operation.
+    Body(&'static str, &'static str),
+    /// Query parameters of the operation.
+    Query(&'static str, &'static str),
+}
+
+/// Client types in the order they're rendered along with where they come from.
+const TYPES: &[(&str, Source)] = &[
+    ("Room", Source::Schema("Room")),
+    ("ClassType", Source::Schema("ClassType")),
+    ("CreateRoomPayload", Source::Body("/rooms", "post")),
+    ("UpdateRoomPayload", Source::Body("/rooms/{id}", "patch")),
+    ("Event", Source::Schema("Event")),
+    ("BinaryData", Source::Schema("BinaryData")),
+    ("ModerationStatus", Source::Schema("ModerationStatus")),
+    (
+        "CreateEventPayload",
+        Source::Body("/rooms/{id}/events", "post"),
+    ),
+    (
+        "ListEventsPayload",
+        Source::Query("/rooms/{id}/events", "get"),
+    ),
+    ("Direction", Source::Schema("Direction")),
+    (
+        "ReadStatePayload",
+        Source::Query("/rooms/{id}/state", "get"),
+    ),
+    ("Agent", Source::Schema("AgentWithBan")),
+    (
+        "ListAgentsPayload",
+        Source::Query("/rooms/{id}/agents", "get"),
+    ),
+];
+
+pub fn render(document: &JsonValue) -> String {
+    let renderer = Renderer::new(document);
+
+    let mut out = String::from(HEADER);
+    out.push_str("\nuse std::collections::HashMap;\n\n");
+    out.push_str("use serde::{Deserialize, Serialize};\n");
+    out.push_str("use serde_json::Value as JsonValue;\n");
+    out.push_str("use uuid::Uuid;\n");
+
+    for (name, source) in TYPES {
+        let schema = match source {
+            Source::Schema(schema) => renderer.component(schema).to_owned(),
+            Source::Body(path, method) => {
+                let body = &document["paths"][path][method]["requestBody"]["content"]
+                    ["application/json"]["schema"];
+
+                renderer.resolve(body).to_owned()
+            }
+            Source::Query(path, method) => query_schema(document, path, method),
+        };
+
+        out.push('\n');
+        out.push_str(&renderer.render_type(name, &schema));
+    }
+
+    out
+}
+
+/// Collects query parameters of the operation into an object schema.
+fn query_schema(document: &JsonValue, path: &str, method: &str) -> JsonValue {
+    let parameters = document["paths"][path][method]["parameters"]
+        .as_array()
+        .unwrap_or_else(|| panic!("No parameters of {method} {path}"));
+
+    let mut properties = serde_json::Map::new();
+    let mut required = vec![];
+
+    for parameter in parameters.iter().filter(|p| p["in"] == "query") {
+        let name = parameter["name"].as_str().expect("Parameter without name");
+        let mut schema = parameter["schema"].clone();
+
+        if let Some(description) = parameter.get("description") {
+            schema["description"] = description.to_owned();
+        }
+
+        if parameter["required"] == true {
+            required.push(name.to_owned());
+        }
+
+        properties.insert(name.to_owned(), schema);
+    }
+
+    json!({ "type": "object", "properties": properties, "required": required })
+}
+
+struct Renderer<'a> {
+    document: &'a JsonValue,
+    /// Component schema names mapped to the client types rendered from them.
+    names: HashMap<String, &'static str>,
+}
+
+impl<'a> Renderer<'a> {
+    fn new(document: &'a JsonValue) -> Self {
+        let mut names = HashMap::new();
+
+        for (name, source) in TYPES {
+            let component = match source {
+                Source::Schema(schema) => Some(schema.to_string()),
+                Source::Body(path, method) => document["paths"][path][method]["requestBody"]
+                    ["content"]["application/json"]["schema"]["$ref"]
+                    .as_str()
+                    .map(ref_name),
+                Source::Query(..) => None,
+            };
+
+            if let Some(component) = component {
+                names.insert(component, *name);
+            }
+        }
+
+        Self { document, names }
+    }
+
+    fn component(&self, name: &str) -> &'a JsonValue {
+        let schema = &self.document["components"]["schemas"][name];
+        assert!(schema.is_object(), "Schema {name} is missing");
+        schema
+    }
+
+    fn resolve<'s>(&self, schema: &'s JsonValue) -> &'s JsonValue
+    where
+        'a: 's,
+    {
+        match schema["$ref"].as_str() {
+            Some(reference) => self.component(&ref_name(reference)),
+            None => schema,
+        }
+    }
+
+    fn render_type(&self, name: &str, schema: &JsonValue) -> String {
+        let mut out = doc_comment(schema, "");
+
+        match schema["enum"].as_array() {
+            Some(values) => {
+                out.push_str(
+                    "#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]\n",
+                );
+                out.push_str(&format!("pub enum {name} {{\n"));
+
+                for value in values {
+                    let value = value.as_str().expect("Non-string enum value");
+                    let variant = pascal_case(value);
+
+                    if variant != value {
+                        out.push_str(&format!("    #[serde(rename = \"{value}\")]\n"));
+                    }
+
+                    out.push_str(&format!("    {variant},\n"));
+                }
+            }
+            None => {
+                let (properties, required) = self.object_properties(schema);
+                // Defaults of the service may differ from the type ones so only payloads with
+                // all fields optional get `Default`.
+                let default = if required.is_empty() { "Default, " } else { "" };
+
+                out.push_str(&format!(
+                    "#[derive(Clone, Debug, {default}Deserialize, Serialize)]\n"
+                ));
+                out.push_str(&format!("pub struct {name} {{\n"));
+
+                for (name, schema) in &properties {
+                    out.push_str(&self.render_field(name, schema, required.contains(name)));
+                }
+            }
+        }
+
+        out.push_str("}\n");
+        out
+    }
+
+    /// Properties of the object merged over `allOf` parts along with the required ones.
+    fn object_properties(&self, schema: &JsonValue) -> (BTreeMap<String, JsonValue>, Vec<String>) {
+        let mut properties = BTreeMap::new();
+        let mut required = vec![];
+
+        let parts = match schema["allOf"].as_array() {
+            Some(parts) => parts.iter().map(|part| self.resolve(part)).collect(),
+            None => vec![schema],
+        };
+
+        for part in parts {
+            if let Some(part_properties) = part["properties"].as_object() {
+                for (name, schema) in part_properties {
+                    properties.insert(name.to_owned(), schema.to_owned());
+                }
+            }
+
+            if let Some(part_required) = part["required"].as_array() {
+                required.extend(
+                    part_required
+                        .iter()
+                        .filter_map(|r| r.as_str())
+                        .map(String::from),
+                );
+            }
+        }
+
+        (properties, required)
+    }
+
+    fn render_field(&self, name: &str, schema: &JsonValue, required: bool) -> String {
+        let mut out = doc_comment(schema, "    ");
+        let (ty, defaultable) = self.rust_type(schema);
+        let nullable = schema["nullable"] == true;
+
+        let mut serde = vec![];
+
+        let ident = match name {
+            "type" => {
+                serde.push("rename = \"type\"".to_owned());
+                "kind"
+            }
+            _ => name,
+        };
+
+        let ty = if required && !nullable {
+            ty
+        } else if defaultable && !nullable {
+            serde.push("default".to_owned());
+            ty
+        } else {
+            serde.push("default, skip_serializing_if = \"Option::is_none\"".to_owned());
+            format!("Option<{ty}>")
+        };
+
+        if !serde.is_empty() {
+            out.push_str(&format!("    #[serde({})]\n", serde.join(", ")));
+        }
+
+        out.push_str(&format!("    pub {ident}: {ty},\n"));
+        out
+    }
+
+    /// Rust type of the schema and whether it has a default value.
+    fn rust_type(&self, schema: &JsonValue) -> (String, bool) {
+        if let Some(reference) = schema["$ref"].as_str() {
+            let component = ref_name(reference);
+
+            let name = self
+                .names
+                .get(&component)
+                .unwrap_or_else(|| panic!("Schema {component} isn't rendered to a client type"));
+
+            return (name.to_string(), false);
+        }
+
+        if let Some([part]) = schema["allOf"].as_array().map(Vec::as_slice) {
+            return self.rust_type(part);
+        }
+
+        match (schema["type"].as_str(), schema["format"].as_str()) {
+            (Some("string"), Some("uuid")) => ("Uuid".to_owned(), false),
+            (Some("string"), _) => ("String".to_owned(), true),
+            (Some("integer"), Some("int32")) => ("i32".to_owned(), true),
+            (Some("integer"), Some("int64")) => ("i64".to_owned(), true),
+            (Some("integer"), _) if schema["minimum"] == 0 => ("usize".to_owned(), true),
+            (Some("integer"), _) => ("i64".to_owned(), true),
+            (Some("number"), _) => ("f64".to_owned(), true),
+            (Some("boolean"), _) => ("bool".to_owned(), true),
+            (Some("array"), _) => {
+                let items = &schema["items"];
+                let (ty, _) = self.rust_type(items);
+
+                match items["nullable"] == true {
+                    true => (format!("Vec<Option<{ty}>>"), true),
+                    false => (format!("Vec<{ty}>"), true),
+                }
+            }
+            (Some("object"), _) => match schema.get("additionalProperties") {
+                Some(values) => {
+                    let (ty, _) = self.rust_type(values);
+                    (format!("HashMap<String, {ty}>"), true)
+                }
+                None => ("JsonValue".to_owned(), true),
+            },
+            (ty, _) => panic!("Unsupported schema type {ty:?}"),
+        }
+    }
+}
+
+/// `#/components/schemas/db.event.Direction` refers to `Direction` schema.
+fn ref_name(reference: &str) -> String {
+    let name = reference.trim_start_matches("#/components/schemas/");
+    name.rsplit('.').next().unwrap_or(name).to_owned()
+}
+
+fn doc_comment(schema: &JsonValue, indent: &str) -> String {
+    match schema["description"].as_str() {
+        Some(description) => description
+            .lines()
+            .map(|line| match line {
+                "" => format!("{indent}///\n"),
+                line => format!("{indent}/// {line}\n"),
+            })
+            .collect(),
+        None => String::new(),
+    }
+}
+
+fn pascal_case(value: &str) -> String {
+    value
+        .split('_')
+        .map(|part| {
+            let mut chars = part.chars();
+
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
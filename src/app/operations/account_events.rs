@@ -0,0 +1,328 @@
+use anyhow::{Context, Result};
+use chrono::Utc;
+use rusoto_s3::PutObjectRequest;
+use serde_derive::Serialize;
+use sqlx::postgres::PgPool as Db;
+use svc_agent::{AccountId, AgentId};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::app::s3_client::S3Client;
+use crate::db::event::{AccountListQuery, AnonymizeAccountQuery};
+use crate::metrics::{Metrics, QueryKey};
+
+const BATCH_SIZE: i64 = 1000;
+const EVENTS_EXPORT_BUCKET: &str = "eventsexport";
+/// Label of the agent erased accounts are replaced with.
+const ANONYMOUS_LABEL: &str = "anonymous";
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ExportStats {
+    pub exported: u64,
+    /// Where the export is uploaded to once it's finished.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub s3_uri: Option<String>,
+}
+
+/// Uploads all events created by the account in rooms of the audience to S3 as NDJSON,
+/// one event per line in `id` order. Deleted events are exported too.
+///
+/// The object is `s3://eventsexport.{audience}/{account_id}/{timestamp}.ndjson`.
+/// `report` is called with the running totals after each batch.
+pub async fn export(
+    db: &Db,
+    metrics: &Metrics,
+    s3_client: S3Client,
+    account_id: &AccountId,
+    audience: &str,
+    report: impl Fn(&ExportStats),
+) -> Result<ExportStats> {
+    let mut stats = ExportStats::default();
+    let mut body = Vec::new();
+    let mut after_id: Option<Uuid> = None;
+
+    loop {
+        let mut conn = db
+            .acquire()
+            .await
+            .context("Failed to acquire db connection")?;
+
+        let query = AccountListQuery::new(
+            account_id.to_owned(),
+            audience.to_owned(),
+            after_id,
+            BATCH_SIZE,
+        );
+
+        let events = metrics
+            .measure_query(QueryKey::EventAccountListQuery, query.execute(&mut conn))
+            .await
+            .context("Failed to list account events")?;
+
+        for event in &events {
+            serde_json::to_writer(&mut body, event).context("Failed to serialize event")?;
+            body.push(b'\n');
+        }
+
+        stats.exported += events.len() as u64;
+        after_id = events.last().map(|event| event.id());
+        report(&stats);
+
+        if (events.len() as i64) < BATCH_SIZE {
+            break;
+        }
+    }
+
+    let bucket = format!("{EVENTS_EXPORT_BUCKET}.{audience}");
+    let key = format!(
+        "{}/{}.ndjson",
+        account_id,
+        Utc::now().format("%Y%m%dT%H%M%SZ")
+    );
+
+    let request = PutObjectRequest {
+        bucket: bucket.clone(),
+        key: key.clone(),
+        body: Some(body.into()),
+        content_type: Some("application/x-ndjson".into()),
+        ..Default::default()
+    };
+
+    s3_client
+        .put_object(request)
+        .await
+        .map_err(|e| anyhow!("Failed to upload account events export, reason = {:?}", e))?;
+
+    stats.s3_uri = Some(format!("s3://{bucket}/{key}"));
+
+    info!(
+        %account_id,
+        exported = stats.exported,
+        "Account events export finished"
+    );
+
+    Ok(stats)
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct EraseStats {
+    pub erased: u64,
+}
+
+/// Replaces the account with an anonymous agent of its audience as the author of all its
+/// events in rooms of the audience and drops `author_fields` keys from their data.
+///
+/// Erasure is idempotent so an interrupted one may be simply restarted.
+pub async fn erase(
+    db: &Db,
+    metrics: &Metrics,
+    account_id: &AccountId,
+    audience: &str,
+    author_fields: &[String],
+    report: impl Fn(&EraseStats),
+) -> Result<EraseStats> {
+    let anonymous = AgentId::new(
+        ANONYMOUS_LABEL,
+        AccountId::new(ANONYMOUS_LABEL, account_id.audience()),
+    );
+
+    let mut stats = EraseStats::default();
+
+    loop {
+        let mut conn = db
+            .acquire()
+            .await
+            .context("Failed to acquire db connection")?;
+
+        let query = AnonymizeAccountQuery::new(
+            account_id.to_owned(),
+            audience.to_owned(),
+            anonymous.clone(),
+            author_fields,
+            BATCH_SIZE,
+        );
+
+        let erased = metrics
+            .measure_query(
+                QueryKey::EventAnonymizeAccountQuery,
+                query.execute(&mut conn),
+            )
+            .await
+            .context("Failed to anonymize account events")?;
+
+        stats.erased += erased;
+        report(&stats);
+
+        if (erased as i64) < BATCH_SIZE {
+            break;
+        }
+    }
+
+    info!(
+        %account_id,
+        erased = stats.erased,
+        "Account events erasure finished"
+    );
+
+    Ok(stats)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Bound;
+
+    use prometheus::Registry;
+    use serde_json::json;
+
+    use super::*;
+    use crate::db::event::{InsertQuery, ListQuery};
+    use crate::db::room::ClassType;
+    use crate::test_helpers::prelude::*;
+
+    #[tokio::test]
+    async fn erase_account_events() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let other_agent = TestAgent::new("web", "user456", USR_AUDIENCE);
+        let metrics = Metrics::new(&Registry::new()).unwrap();
+
+        // The database is shared by tests so rooms of a fresh audience only are erased.
+        let audience = format!("{}.example.org", Uuid::new_v4());
+
+        let room = {
+            let mut conn = db.get_conn().await;
+
+            let room = factory::Room::new(Uuid::new_v4(), ClassType::Webinar)
+                .audience(&audience)
+                .time((Bound::Included(Utc::now()), Bound::Unbounded))
+                .insert(&mut conn)
+                .await;
+
+            for agent in [&agent, &other_agent] {
+                factory::Event::new()
+                    .room_id(room.id())
+                    .kind("message")
+                    .data(&json!({ "text": "hello", "user_name": "John" }))
+                    .occurred_at(1000)
+                    .created_by(agent.agent_id())
+                    .insert(&mut conn)
+                    .await;
+            }
+
+            room
+        };
+
+        let stats = erase(
+            db.connection_pool(),
+            &metrics,
+            agent.account_id(),
+            &audience,
+            &["user_name".to_owned()],
+            |_| (),
+        )
+        .await
+        .expect("Failed to erase account events");
+
+        assert_eq!(stats.erased, 1);
+
+        let mut conn = db.get_conn().await;
+
+        let events = ListQuery::new()
+            .room_id(room.id())
+            .execute(&mut conn)
+            .await
+            .expect("Failed to list events");
+
+        let erased = events
+            .iter()
+            .find(|e| e.created_by().label() == ANONYMOUS_LABEL)
+            .expect("Anonymized event not found");
+
+        assert_eq!(erased.data(), &json!({ "text": "hello" }));
+
+        let untouched = events
+            .iter()
+            .find(|e| e.created_by() == other_agent.agent_id())
+            .expect("Other agent's event not found");
+
+        assert_eq!(untouched.data()["user_name"], "John");
+    }
+
+    #[tokio::test]
+    async fn erase_encrypted_account_events() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let metrics = Metrics::new(&Registry::new()).unwrap();
+        let audience = format!("{}.example.org", Uuid::new_v4());
+
+        let room = {
+            let mut conn = db.get_conn().await;
+
+            let room = factory::Room::new(Uuid::new_v4(), ClassType::Webinar)
+                .audience(&audience)
+                .time((Bound::Included(Utc::now()), Bound::Unbounded))
+                .insert(&mut conn)
+                .await;
+
+            // Encrypted data is a string which has no keys to drop.
+            InsertQuery::new_encrypted(
+                room.id(),
+                "message".to_owned(),
+                json!("ciphertext"),
+                1000,
+                agent.agent_id().to_owned(),
+                Some("key-1".to_owned()),
+                None,
+            )
+            .expect("Failed to build event insert query")
+            .execute(&mut conn)
+            .await
+            .expect("Failed to insert encrypted event");
+
+            factory::Event::new()
+                .room_id(room.id())
+                .kind("message")
+                .data(&json!({ "text": "hello", "user_name": "John" }))
+                .occurred_at(2000)
+                .created_by(agent.agent_id())
+                .insert(&mut conn)
+                .await;
+
+            room
+        };
+
+        let stats = erase(
+            db.connection_pool(),
+            &metrics,
+            agent.account_id(),
+            &audience,
+            &["user_name".to_owned()],
+            |_| (),
+        )
+        .await
+        .expect("Failed to erase account events");
+
+        assert_eq!(stats.erased, 2);
+
+        let mut conn = db.get_conn().await;
+
+        let events = ListQuery::new()
+            .room_id(room.id())
+            .execute(&mut conn)
+            .await
+            .expect("Failed to list events");
+
+        assert!(events
+            .iter()
+            .all(|e| e.created_by().label() == ANONYMOUS_LABEL));
+
+        let encrypted = events
+            .iter()
+            .find(|e| e.content_encrypted())
+            .expect("Encrypted event not found");
+
+        assert_eq!(encrypted.data(), &json!("ciphertext"));
+    }
+}
@@ -1,23 +1,36 @@
 use std::cmp;
+use std::collections::HashMap;
+use std::future::Future;
+use std::num::NonZeroU32;
 use std::ops::Bound;
-use std::time::Duration as StdDuration;
+use std::time::{Duration as StdDuration, Instant};
 
 use anyhow::{Context, Result};
 use chrono::{DateTime, Duration, Utc};
-use serde_json::json;
+use futures::{future, stream, StreamExt, TryStreamExt};
+use serde_json::{json, Value as JsonValue};
 use sqlx::{
     postgres::{PgConnection, PgPool as Db},
     Acquire,
 };
-use tracing::{info, instrument};
+use tracing::{info, instrument, warn};
+use uuid::Uuid;
 
 use crate::{
+    app::{
+        operations::segments::{self, NANOSECONDS_IN_MILLISECOND},
+        room_cache::RoomCache,
+    },
     config::AdjustConfig,
     db::{
-        adjustment::{InsertQuery as AdjustmentInsertQuery, Segments},
+        adjustment::{
+            ComparisonInsertQuery as AdjustmentComparisonInsertQuery,
+            InsertQuery as AdjustmentInsertQuery, Segments,
+        },
         event::{
-            DeleteQuery as EventDeleteQuery, InsertQuery as EventInsertQuery,
-            ListQuery as EventListQuery, Object as Event,
+            keep_provisional_seq, DataEncryption, DeleteQuery as EventDeleteQuery, EventKind,
+            InsertQuery as EventInsertQuery, ListQuery as EventListQuery, Object as Event,
+            ThinQuery as EventThinQuery,
         },
         room::{InsertQuery as RoomInsertQuery, Object as Room},
         room_time::RoomTimeBound,
@@ -25,8 +38,6 @@ use crate::{
     metrics::{Metrics, QueryKey},
 };
 
-pub const NANOSECONDS_IN_MILLISECOND: i64 = 1_000_000;
-
 ////////////////////////////////////////////////////////////////////////////////
 
 pub struct AdjustOutput {
@@ -40,6 +51,7 @@ pub struct AdjustOutput {
     pub cut_original_segments: Segments,
 }
 
+#[allow(clippy::too_many_arguments)]
 #[instrument(
     skip_all,
     fields(
@@ -52,6 +64,8 @@ pub struct AdjustOutput {
 pub async fn call(
     db: &Db,
     metrics: &Metrics,
+    room_cache: &RoomCache,
+    data_encryption: Option<&DataEncryption>,
     real_time_room: &Room,
     started_at: DateTime<Utc>,
     segments: &Segments,
@@ -62,15 +76,7 @@ pub async fn call(
     let start_timestamp = Utc::now();
 
     // Parse segments.
-    let bounded_offset_tuples: Vec<(Bound<i64>, Bound<i64>)> = segments.to_owned().into();
-    let mut parsed_segments = Vec::with_capacity(bounded_offset_tuples.len());
-
-    for segment in bounded_offset_tuples {
-        match segment {
-            (Bound::Included(start), Bound::Excluded(stop)) => parsed_segments.push((start, stop)),
-            segment => bail!("Invalid segment: {:?}", segment),
-        }
-    }
+    let parsed_segments = segments::parse(segments)?;
 
     // Create adjustment.
     let mut conn = db
@@ -108,6 +114,9 @@ pub async fn call(
                     real_time_room.id(),
                 )
             })?;
+
+        // Other handlers must see the room closed while the adjustment is still running.
+        room_cache.invalidate(real_time_room.id());
         new_time
     } else {
         time
@@ -126,21 +135,352 @@ pub async fn call(
             )
         })?;
 
+    // Get room opening time and duration.
+    let (room_opening, room_duration) = match real_time_room_new_time.end() {
+        RoomTimeBound::Excluded(stop) => {
+            let start = real_time_room_new_time.start();
+            (*start, stop.signed_duration_since(*start))
+        }
+        _ => bail!("invalid duration for room = '{}'", real_time_room.id()),
+    };
+
+    // The shadow pipeline derives cut events itself so it runs before they get inserted.
+    // Its failure doesn't fail the adjustment but gets recorded as a mismatch.
+    let shadow = if cfg.shadow {
+        let shadow_phase = preview_segments(
+            &mut conn,
+            metrics,
+            real_time_room.id(),
+            started_at,
+            &parsed_segments,
+            (room_opening, room_duration),
+            cfg.min_segment_length,
+        );
+
+        Some(measure_phase(metrics, "shadow", shadow_phase).await)
+    } else {
+        None
+    };
+
+    ///////////////////////////////////////////////////////////////////////////
+
+    measure_phase(
+        metrics,
+        "derive_cut_events",
+        insert_break_group_cuts(&mut conn, metrics, data_encryption, real_time_room),
+    )
+    .await?;
+
     ///////////////////////////////////////////////////////////////////////////
 
-    // Finds events and creates the stream events for them:
-    // break(value: true)           -> stream { cut: start }
-    // break(value: false)          -> stream { cut: stop }
-    // group(group: created)        -> stream { cut: start }
-    // group(group: deleted)        -> stream { cut: stop }
+    // Calculate RTC offset as the difference between event room opening and RTC start.
+    let rtc_offset = (started_at - room_opening).num_milliseconds();
+
+    // Convert segments to nanoseconds.
+    let nano_segments = segments::scale(
+        &segments::offset(&parsed_segments, rtc_offset),
+        NANOSECONDS_IN_MILLISECOND,
+    );
+
+    // Invert segments to gaps.
+    let min_segment_length = cfg.min_segment_length;
+    let segment_gaps = segments::invert(&nano_segments, room_duration, min_segment_length);
+
+    let parsed_segments_finish = parsed_segments.last().unwrap().1;
+
+    // Calculate total duration of initial segments.
+    let total_segments_millis = segments::total_length(&parsed_segments);
+
+    let total_segments_duration = Duration::milliseconds(total_segments_millis);
+
+    // The original room and cut original segments only depend on the real-time room,
+    // so they are built at the same time on separate connections.
+    let original_room_phase = async {
+        // Create original room with events shifted according to segments.
+        let original_room = create_room(
+            &mut conn,
+            metrics,
+            real_time_room,
+            started_at,
+            total_segments_duration,
+        )
+        .await?;
+
+        clone_events(
+            &mut conn,
+            db,
+            metrics,
+            &original_room,
+            &segment_gaps,
+            0,
+            &cfg,
+        )
+        .await?;
+
+        info!(original_room_id = %original_room.id(), "Created original room");
+
+        // Fetch shifted cut events and transform them to gaps.
+        let query = EventListQuery::new()
+            .room_id(original_room.id())
+            .kind(EventKind::Stream);
+
+        let cut_events = metrics
+            .measure_query(QueryKey::EventListQuery, query.execute(&mut conn))
+            .await
+            .with_context(|| {
+                format!(
+                    "failed to fetch cut events for room_id = '{}'",
+                    original_room.id()
+                )
+            })?;
+
+        let cut_gaps = cut_events_to_gaps(&cut_events)?;
+        Ok::<_, anyhow::Error>((original_room, cut_gaps))
+    };
+
+    let cut_original_segments_phase = async {
+        let mut conn = db
+            .acquire()
+            .await
+            .context("Failed to acquire db connection")?;
+
+        let query = EventListQuery::new()
+            .room_id(real_time_room.id())
+            .kind(EventKind::Stream);
+
+        let cut_events = metrics
+            .measure_query(QueryKey::EventListQuery, query.execute(&mut conn))
+            .await
+            .with_context(|| {
+                format!(
+                    "failed to fetch cut events for room_id = '{}'",
+                    real_time_room.id()
+                )
+            })?;
+
+        Ok::<_, anyhow::Error>(cut_original_segments(
+            &cut_events_to_gaps(&cut_events)?,
+            &nano_segments,
+            rtc_offset,
+            parsed_segments_finish,
+            min_segment_length,
+        ))
+    };
 
+    let ((original_room, cut_gaps), cut_original_segments) = tokio::try_join!(
+        measure_phase(metrics, "original_room", original_room_phase),
+        measure_phase(
+            metrics,
+            "cut_original_segments",
+            cut_original_segments_phase
+        ),
+    )?;
+
+    ///////////////////////////////////////////////////////////////////////////
+
+    let modified_room_phase = async {
+        // Create modified room with events shifted again according to cut events this time.
+        let modified_room = create_room(
+            &mut conn,
+            metrics,
+            &original_room,
+            started_at,
+            total_segments_duration,
+        )
+        .await?;
+
+        clone_events(
+            &mut conn,
+            db,
+            metrics,
+            &modified_room,
+            &cut_gaps,
+            offset * NANOSECONDS_IN_MILLISECOND,
+            &cfg,
+        )
+        .await?;
+
+        info!(modified_room_id = %modified_room.id(), "Created modified room");
+
+        // Delete cut events from the modified room.
+        let query = EventDeleteQuery::new(modified_room.id(), EventKind::Stream);
+
+        metrics
+            .measure_query(QueryKey::EventDeleteQuery, query.execute(&mut conn))
+            .await
+            .with_context(|| {
+                format!(
+                    "failed to delete cut events for room_id = '{}'",
+                    modified_room.id()
+                )
+            })?;
+
+        // Thin high-frequency events to make the modified room lighter to replay.
+        thin_events(&mut conn, metrics, &modified_room, &cfg.thinning).await?;
+
+        Ok::<_, anyhow::Error>(modified_room)
+    };
+
+    let modified_room = measure_phase(metrics, "modified_room", modified_room_phase).await?;
+
+    ///////////////////////////////////////////////////////////////////////////
+
+    // Calculate modified segments by inverting cut gaps limited by total initial segments duration.
+    let modified_segments = segments::to_millis(&segments::invert(
+        &cut_gaps,
+        total_segments_duration,
+        min_segment_length,
+    ));
+
+    ///////////////////////////////////////////////////////////////////////////
+
+    if let Some(shadow) = shadow {
+        let diff = shadow_diff(&modified_segments, &cut_original_segments, shadow);
+        record_shadow_diff(&mut conn, metrics, real_time_room.id(), diff).await;
+    }
+
+    ///////////////////////////////////////////////////////////////////////////
+
+    // Done.
+    info!(
+        duration_ms = (Utc::now() - start_timestamp).num_milliseconds(),
+        "Room adjustment task successfully finished",
+    );
+
+    Ok(AdjustOutput {
+        original_room,
+        modified_room,
+        modified_segments,
+        cut_original_segments,
+    })
+}
+
+/// Describes how segments of the shadow pipeline differ from the persisted ones.
+/// Nothing when they match.
+fn shadow_diff(
+    modified_segments: &Segments,
+    cut_original_segments: &Segments,
+    shadow: Result<AdjustPreview>,
+) -> Option<JsonValue> {
+    let shadow = match shadow {
+        Ok(shadow) => shadow,
+        Err(err) => return Some(json!({ "error": format!("{:#}", err) })),
+    };
+
+    let fields = [
+        (
+            "modified_segments",
+            modified_segments,
+            &shadow.modified_segments,
+        ),
+        (
+            "cut_original_segments",
+            cut_original_segments,
+            &shadow.cut_original_segments,
+        ),
+    ];
+
+    let mut diff = serde_json::Map::new();
+
+    for (field, persisted, shadow) in fields {
+        let persisted_bounds: Vec<(Bound<i64>, Bound<i64>)> = persisted.to_owned().into();
+        let shadow_bounds: Vec<(Bound<i64>, Bound<i64>)> = shadow.to_owned().into();
+
+        // Indexes of segments present in both but different.
+        let mismatched = persisted_bounds
+            .iter()
+            .zip(shadow_bounds.iter())
+            .enumerate()
+            .filter(|(_, (a, b))| a != b)
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+
+        if mismatched.is_empty() && persisted_bounds.len() == shadow_bounds.len() {
+            continue;
+        }
+
+        diff.insert(
+            field.to_owned(),
+            json!({
+                "persisted": persisted,
+                "shadow": shadow,
+                "mismatched": mismatched,
+                "count_delta": shadow_bounds.len() as i64 - persisted_bounds.len() as i64,
+            }),
+        );
+    }
+
+    if diff.is_empty() {
+        None
+    } else {
+        Some(JsonValue::Object(diff))
+    }
+}
+
+/// Counts the comparison and stores it. Failing to store it doesn't fail the adjustment.
+async fn record_shadow_diff(
+    conn: &mut PgConnection,
+    metrics: &Metrics,
+    room_id: Uuid,
+    diff: Option<JsonValue>,
+) {
+    let matched = diff.is_none();
+
+    if matched {
+        metrics.adjust_shadow_matched.inc();
+    } else {
+        metrics.adjust_shadow_mismatched.inc();
+        warn!(?diff, "Shadow adjustment pipeline disagrees");
+    }
+
+    let diff = diff.unwrap_or_else(|| json!({}));
+    let query = AdjustmentComparisonInsertQuery::new(room_id, matched, diff);
+
+    if let Err(err) = metrics
+        .measure_query(
+            QueryKey::AdjustmentComparisonInsertQuery,
+            query.execute(conn),
+        )
+        .await
+    {
+        warn!(%err, "Failed to record shadow adjustment comparison");
+    }
+}
+
+/// Awaits a phase of the adjustment recording its duration.
+async fn measure_phase<F: Future>(metrics: &Metrics, phase: &str, future: F) -> F::Output {
+    let start = Instant::now();
+    let output = future.await;
+    let elapsed = start.elapsed();
+
+    metrics.observe_adjust_phase(phase, elapsed);
+    info!(
+        phase,
+        duration_ms = elapsed.as_millis() as u64,
+        "Adjustment phase finished"
+    );
+
+    output
+}
+
+/// Finds events and creates the stream events for them:
+/// break(value: true)           -> stream { cut: start }
+/// break(value: false)          -> stream { cut: stop }
+/// group(group: created)        -> stream { cut: start }
+/// group(group: deleted)        -> stream { cut: stop }
+async fn insert_break_group_cuts(
+    conn: &mut PgConnection,
+    metrics: &Metrics,
+    data_encryption: Option<&DataEncryption>,
+    real_time_room: &Room,
+) -> Result<()> {
     // Finds break and group events
     let query = EventListQuery::new()
         .room_id(real_time_room.id())
-        .kinds(vec!["break".to_string(), "video_group".to_string()]);
+        .kinds([EventKind::Break, EventKind::VideoGroup]);
 
     let break_group_events = metrics
-        .measure_query(QueryKey::EventListQuery, query.execute(&mut conn))
+        .measure_query(QueryKey::EventListQuery, query.execute(&mut *conn))
         .await
         .with_context(|| {
             format!(
@@ -151,36 +491,18 @@ pub async fn call(
 
     let mut insert_queries = Vec::new();
     for event in break_group_events {
-        let data = if event.kind() == "break" {
-            let value = event.data().get("value").and_then(|v| v.as_bool());
-            match value {
-                Some(true) => {
-                    json!({"cut": "start"})
-                }
-                Some(false) => {
-                    json!({"cut": "stop"})
-                }
-                None => continue,
-            }
-        } else {
-            let value = event.data().get("video_group").and_then(|v| v.as_str());
-            match value {
-                Some("created") => {
-                    json!({"cut": "start"})
-                }
-                Some("deleted") => {
-                    json!({"cut": "stop"})
-                }
-                _ => continue,
-            }
+        let data = match break_group_cut(&event) {
+            Some(command) => json!({ "cut": command }),
+            None => continue,
         };
 
         let q = EventInsertQuery::new(
             real_time_room.id(),
-            "stream".to_string(),
+            EventKind::Stream.into(),
             data,
             event.occurred_at(),
             event.created_by().to_owned(),
+            data_encryption,
         )?;
 
         insert_queries.push(q);
@@ -207,180 +529,217 @@ pub async fn call(
         txn.commit().await.context("Failed to commit transaction")?;
     }
 
-    ///////////////////////////////////////////////////////////////////////////
-
-    // Get room opening time and duration.
-    let (room_opening, room_duration) = match real_time_room_new_time.end() {
-        RoomTimeBound::Excluded(stop) => {
-            let start = real_time_room_new_time.start();
-            (*start, stop.signed_duration_since(*start))
-        }
-        _ => bail!("invalid duration for room = '{}'", real_time_room.id()),
-    };
+    Ok(())
+}
 
-    // Calculate RTC offset as the difference between event room opening and RTC start.
-    let rtc_offset = (started_at - room_opening).num_milliseconds();
+/// Segments an adjustment would result in.
+pub struct AdjustPreview {
+    pub modified_segments: Segments,
+    pub cut_original_segments: Segments,
+}
 
-    // Convert segments to nanoseconds.
-    let nano_segments = parsed_segments
-        .iter()
-        .map(|(start, stop)| {
-            let nano_start = (start + rtc_offset) * NANOSECONDS_IN_MILLISECOND;
-            let nano_stop = (stop + rtc_offset) * NANOSECONDS_IN_MILLISECOND;
-            (nano_start, nano_stop)
-        })
-        .collect::<Vec<(i64, i64)>>();
+/// Runs the segment math of [`call`] without creating rooms or cloning events.
+///
+/// Cut events derived from breaks and video groups are taken into account as if they were
+/// inserted and an unbounded room is considered to be closed now.
+#[instrument(
+    skip_all,
+    fields(
+        source_room_id = %real_time_room.id(),
+        started_at = ?started_at,
+        segments = ?segments,
+    )
+)]
+pub async fn preview(
+    db: &Db,
+    metrics: &Metrics,
+    real_time_room: &Room,
+    started_at: DateTime<Utc>,
+    segments: &Segments,
+    cfg: &AdjustConfig,
+) -> Result<AdjustPreview> {
+    let parsed_segments = segments::parse(segments)?;
 
-    // Invert segments to gaps.
-    let min_segment_length = cfg.min_segment_length;
-    let segment_gaps = invert_segments(&nano_segments, room_duration, min_segment_length)?;
+    let time = real_time_room
+        .time()
+        .map_err(|e| anyhow!(e))
+        .context("Invalid room time")?;
 
-    let parsed_segments_finish = parsed_segments.last().unwrap().1;
+    let room_opening = *time.start();
 
-    // Calculate total duration of initial segments.
-    let total_segments_millis = parsed_segments
-        .into_iter()
-        .fold(0, |acc, (start, stop)| acc + (stop - start));
+    let room_duration = match time.end() {
+        RoomTimeBound::Excluded(stop) => stop.signed_duration_since(room_opening),
+        RoomTimeBound::Unbounded => Utc::now().signed_duration_since(room_opening),
+    };
 
-    let total_segments_duration = Duration::milliseconds(total_segments_millis);
+    let mut conn = db
+        .acquire()
+        .await
+        .context("Failed to acquire db connection")?;
 
-    // Create original room with events shifted according to segments.
-    let original_room = create_room(
+    preview_segments(
         &mut conn,
         metrics,
-        real_time_room,
+        real_time_room.id(),
         started_at,
-        total_segments_duration,
+        &parsed_segments,
+        (room_opening, room_duration),
+        cfg.min_segment_length,
     )
-    .await?;
+    .await
+}
 
-    clone_events(&mut conn, metrics, &original_room, &segment_gaps, 0).await?;
+/// Computes segments of the adjustment of the room opened and lasting for `room_time`
+/// from its events as they are now.
+async fn preview_segments(
+    conn: &mut PgConnection,
+    metrics: &Metrics,
+    real_time_room_id: Uuid,
+    started_at: DateTime<Utc>,
+    parsed_segments: &[(i64, i64)],
+    room_time: (DateTime<Utc>, Duration),
+    min_segment_length: StdDuration,
+) -> Result<AdjustPreview> {
+    let (room_opening, room_duration) = room_time;
 
-    ///////////////////////////////////////////////////////////////////////////
+    let parsed_segments_finish = match parsed_segments.last() {
+        Some((_, stop)) => *stop,
+        None => bail!("no segments to adjust room = '{}'", real_time_room_id),
+    };
 
-    // Fetch shifted cut events and transform them to gaps.
-    let query = EventListQuery::new()
-        .room_id(original_room.id())
-        .kind("stream".to_string());
+    let query = EventListQuery::new().room_id(real_time_room_id).kinds([
+        EventKind::Stream,
+        EventKind::Break,
+        EventKind::VideoGroup,
+    ]);
 
-    let cut_events = metrics
-        .measure_query(QueryKey::EventListQuery, query.execute(&mut conn))
+    let events = metrics
+        .measure_query(QueryKey::EventListQuery, query.execute(conn))
         .await
         .with_context(|| {
             format!(
-                "failed to fetch cut events for room_id = '{}'",
-                original_room.id()
+                "failed to fetch cut, break and video group events for room_id = '{}'",
+                real_time_room_id
             )
         })?;
 
-    let cut_gaps = cut_events_to_gaps(&cut_events)?;
+    // Derived cut events would be created after the existing ones.
+    let derived_at = Utc::now();
 
-    let cut_original_segments = {
-        let query = EventListQuery::new()
-            .room_id(real_time_room.id())
-            .kind("stream".to_string());
+    let mut cuts = events
+        .iter()
+        .filter(|event| event.kind() == EventKind::Stream)
+        .map(Cut::from)
+        .collect::<Vec<_>>();
 
-        let cut_events = metrics
-            .measure_query(QueryKey::EventListQuery, query.execute(&mut conn))
-            .await
-            .with_context(|| {
-                format!(
-                    "failed to fetch cut events for room_id = '{}'",
-                    real_time_room.id()
-                )
-            })?;
+    cuts.extend(
+        events
+            .iter()
+            .filter(|event| event.kind() != EventKind::Stream)
+            .filter_map(|event| {
+                break_group_cut(event).map(|command| Cut {
+                    id: event.id(),
+                    occurred_at: event.occurred_at(),
+                    created_at: derived_at,
+                    command: Some(command),
+                })
+            }),
+    );
 
-        let mut cut_g1 = cut_events_to_gaps(&cut_events)?;
-        cut_g1.iter_mut().for_each(|(a, b)| {
-            *a -= rtc_offset * NANOSECONDS_IN_MILLISECOND;
-            *b -= rtc_offset * NANOSECONDS_IN_MILLISECOND;
-        });
+    cuts.sort_by_key(|cut| (cut.occurred_at, cut.created_at));
 
-        let g1 = invert_segments(
-            &cut_g1,
-            Duration::milliseconds(parsed_segments_finish),
-            min_segment_length,
-        )?;
+    let rtc_offset = (started_at - room_opening).num_milliseconds();
 
-        let segments = nano_segments
-            .iter()
-            .map(|(a, b)| {
-                let a = *a - rtc_offset * NANOSECONDS_IN_MILLISECOND;
-                let b = *b - rtc_offset * NANOSECONDS_IN_MILLISECOND;
-                (a, b)
-            })
-            .collect::<Vec<_>>();
+    let nano_segments = segments::scale(
+        &segments::offset(parsed_segments, rtc_offset),
+        NANOSECONDS_IN_MILLISECOND,
+    );
 
-        intersect::intersect(&g1, &segments)
-            .into_iter()
-            .map(|(start, stop)| {
-                (
-                    Bound::Included(start / NANOSECONDS_IN_MILLISECOND),
-                    Bound::Excluded(stop / NANOSECONDS_IN_MILLISECOND),
-                )
-            })
-            .collect::<Vec<(Bound<i64>, Bound<i64>)>>()
-    };
+    let segment_gaps = segments::invert(&nano_segments, room_duration, min_segment_length);
+    let total_segments_duration = Duration::milliseconds(segments::total_length(parsed_segments));
 
-    // Create modified room with events shifted again according to cut events this time.
-    let modified_room = create_room(
-        &mut conn,
-        metrics,
-        &original_room,
-        started_at,
-        total_segments_duration,
-    )
-    .await?;
-    clone_events(
-        &mut conn,
-        metrics,
-        &modified_room,
-        &cut_gaps,
-        offset * NANOSECONDS_IN_MILLISECOND,
-    )
-    .await?;
+    let cut_original_segments = cut_original_segments(
+        &cut_commands_to_gaps(&cuts)?,
+        &nano_segments,
+        rtc_offset,
+        parsed_segments_finish,
+        min_segment_length,
+    );
 
-    // Delete cut events from the modified room.
-    let query = EventDeleteQuery::new(modified_room.id(), "stream");
+    // Shift cut events the same way they get cloned to the original room.
+    for cut in cuts.iter_mut() {
+        cut.occurred_at = shift_occurred_at(cut.occurred_at, &segment_gaps);
+    }
 
-    metrics
-        .measure_query(QueryKey::EventDeleteQuery, query.execute(&mut conn))
-        .await
-        .with_context(|| {
-            format!(
-                "failed to delete cut events for room_id = '{}'",
-                modified_room.id()
-            )
-        })?;
+    cuts.sort_by_key(|cut| (cut.occurred_at, cut.created_at));
 
-    ///////////////////////////////////////////////////////////////////////////
+    let modified_segments = segments::to_millis(&segments::invert(
+        &cut_commands_to_gaps(&cuts)?,
+        total_segments_duration,
+        min_segment_length,
+    ));
 
-    // Calculate modified segments by inverting cut gaps limited by total initial segments duration.
-    let modified_segments =
-        invert_segments(&cut_gaps, total_segments_duration, min_segment_length)?
-            .into_iter()
-            .map(|(start, stop)| {
-                (
-                    Bound::Included(cmp::max(start / NANOSECONDS_IN_MILLISECOND, 0)),
-                    Bound::Excluded(stop / NANOSECONDS_IN_MILLISECOND),
-                )
-            })
-            .collect::<Vec<(Bound<i64>, Bound<i64>)>>();
+    Ok(AdjustPreview {
+        modified_segments,
+        cut_original_segments,
+    })
+}
 
-    ///////////////////////////////////////////////////////////////////////////
+/// Returns the cut command of the stream event derived from a break or video group event.
+fn break_group_cut(event: &Event) -> Option<&'static str> {
+    if event.kind() == EventKind::Break {
+        match event.data().get("value").and_then(|v| v.as_bool()) {
+            Some(true) => Some("start"),
+            Some(false) => Some("stop"),
+            None => None,
+        }
+    } else {
+        match event.data().get("video_group").and_then(|v| v.as_str()) {
+            Some("created") => Some("start"),
+            Some("deleted") => Some("stop"),
+            _ => None,
+        }
+    }
+}
 
-    // Done.
-    info!(
-        duration_ms = (Utc::now() - start_timestamp).num_milliseconds(),
-        "Room adjustment task successfully finished",
+/// Applies cut gaps of the real-time room to the initial segments.
+fn cut_original_segments(
+    cut_gaps: &[(i64, i64)],
+    nano_segments: &[(i64, i64)],
+    rtc_offset: i64,
+    parsed_segments_finish: i64,
+    min_segment_length: StdDuration,
+) -> Segments {
+    let rtc_offset_nanos = rtc_offset * NANOSECONDS_IN_MILLISECOND;
+    let cut_g1 = segments::offset(cut_gaps, -rtc_offset_nanos);
+
+    let g1 = segments::invert(
+        &cut_g1,
+        Duration::milliseconds(parsed_segments_finish),
+        min_segment_length,
     );
 
-    Ok(AdjustOutput {
-        original_room,
-        modified_room,
-        modified_segments: Segments::from(modified_segments),
-        cut_original_segments: Segments::from(cut_original_segments),
-    })
+    let shifted_segments = segments::offset(nano_segments, -rtc_offset_nanos);
+    segments::to_millis(&segments::intersect(&g1, &shifted_segments))
+}
+
+/// Shifts `occurred_at` according to `gaps` the same way as the clone query does.
+fn shift_occurred_at(occurred_at: i64, gaps: &[(i64, i64)]) -> i64 {
+    let leading_gap = gaps.iter().find(|(start, _)| *start == 0);
+
+    if let Some((_, stop)) = leading_gap {
+        if occurred_at <= *stop {
+            return 0;
+        }
+    }
+
+    let cut: i64 = gaps
+        .iter()
+        .filter(|(start, _)| *start >= 0 && *start < occurred_at)
+        .map(|(start, stop)| cmp::min(*stop, occurred_at) - start)
+        .sum();
+
+    occurred_at - cut
 }
 
 /// Creates a derived room from the source room.
@@ -415,12 +774,49 @@ async fn create_room(
 
 /// Clones events from the source room of the `room` with shifting them according to `gaps` and
 /// adding `offset` (both in nanoseconds).
+///
+/// When `chunk_size` is set events get cloned in chunks of about that size with a separate
+/// statement for each chunk not to hold a single huge transaction on large rooms. Chunks are
+/// already committed when the future gets dropped in between so the task may be cancelled
+/// leaving the derived room partially filled.
+async fn thin_events(
+    conn: &mut PgConnection,
+    metrics: &Metrics,
+    room: &Room,
+    thinning: &HashMap<String, NonZeroU32>,
+) -> Result<()> {
+    for (kind, max_per_second) in thinning {
+        let query = EventThinQuery::new(room.id(), kind, i64::from(max_per_second.get()));
+
+        let deleted = metrics
+            .measure_query(QueryKey::EventThinQuery, query.execute(conn))
+            .await
+            .with_context(|| {
+                format!(
+                    "failed to thin {} events for room_id = '{}'",
+                    kind,
+                    room.id()
+                )
+            })?;
+
+        info!(kind, deleted, "Thinned events");
+    }
+
+    Ok(())
+}
+
+/// Clones events of the source room shifted by `gaps` and `offset`.
+///
+/// With `cfg.clone_chunk_size` set events are cloned in chunks, up to `cfg.clone_concurrency`
+/// of them at the same time on separate connections.
 async fn clone_events(
     conn: &mut PgConnection,
+    db: &Db,
     metrics: &Metrics,
     room: &Room,
     gaps: &[(i64, i64)],
     offset: i64,
+    cfg: &AdjustConfig,
 ) -> Result<()> {
     let source_room_id = match room.source_room_id() {
         Some(id) => id,
@@ -435,6 +831,192 @@ async fn clone_events(
         stops.push(*stop);
     }
 
+    let chunk_size = match cfg.clone_chunk_size {
+        Some(chunk_size) => chunk_size,
+        None => {
+            let cloned = clone_events_range(
+                conn,
+                metrics,
+                room.id(),
+                source_room_id,
+                (&starts, &stops),
+                offset,
+                None,
+            )
+            .await?;
+
+            metrics.adjust_cloned_events.inc_by(cloned);
+            return Ok(());
+        }
+    };
+
+    // Chunk bounds are taken so that events which get the same shifted `occurred_at`
+    // never end up in different chunks because they are monotonized together.
+    let query = sqlx::query!(
+        r#"
+        WITH
+            gap_starts AS (
+                SELECT start, ROW_NUMBER() OVER () AS row_number
+                FROM UNNEST($1::BIGINT[]) AS start
+            ),
+            gap_stops AS (
+                SELECT stop, ROW_NUMBER() OVER () AS row_number
+                FROM UNNEST($2::BIGINT[]) AS stop
+            ),
+            gaps AS (
+                SELECT start, stop
+                FROM gap_starts, gap_stops
+                WHERE gap_stops.row_number = gap_starts.row_number
+            ),
+            source AS (
+                SELECT
+                    occurred_at,
+                    (
+                        CASE occurred_at <= (SELECT stop FROM gaps WHERE start = 0)
+                        WHEN TRUE THEN 0
+                        ELSE occurred_at - (
+                            SELECT COALESCE(SUM(LEAST(stop, occurred_at) - start), 0)
+                            FROM gaps
+                            WHERE start < occurred_at
+                            AND   start >= 0
+                        )
+                        END
+                    ) AS shifted_at
+                FROM event
+                WHERE room_id = $3
+                AND   deleted_at IS NULL
+                AND   moderation_status = 'approved'
+            ),
+            groups AS (
+                SELECT
+                    shifted_at,
+                    MIN(occurred_at) AS min_occurred_at,
+                    MAX(occurred_at) AS max_occurred_at,
+                    COUNT(*) AS count
+                FROM source
+                GROUP BY shifted_at
+            ),
+            numbered AS (
+                SELECT
+                    min_occurred_at,
+                    max_occurred_at,
+                    (SUM(count) OVER (ORDER BY shifted_at) - count)::BIGINT / $4::BIGINT AS chunk
+                FROM groups
+            )
+        SELECT
+            MIN(min_occurred_at) AS "start!: i64",
+            MAX(max_occurred_at) AS "stop!: i64"
+        FROM numbered
+        GROUP BY chunk
+        ORDER BY chunk
+        "#,
+        starts.as_slice(),
+        stops.as_slice(),
+        source_room_id,
+        cmp::max(chunk_size, 1) as i64,
+    );
+
+    let ranges = metrics
+        .measure_query(
+            QueryKey::RoomAdjustCloneRangesQuery,
+            query.fetch_all(&mut *conn),
+        )
+        .await
+        .with_context(|| {
+            format!(
+                "failed to split events of room = '{}' into chunks",
+                source_room_id
+            )
+        })?;
+
+    let chunks_count = ranges.len();
+    let concurrency = cfg.clone_concurrency.unwrap_or(1);
+
+    if concurrency <= 1 {
+        for (idx, range) in ranges.into_iter().enumerate() {
+            let cloned = clone_events_range(
+                conn,
+                metrics,
+                room.id(),
+                source_room_id,
+                (&starts, &stops),
+                offset,
+                Some((range.start, range.stop)),
+            )
+            .await?;
+
+            observe_cloned_chunk(metrics, room, idx + 1, chunks_count, cloned);
+
+            // Let the runtime drop the task between chunks if it's being cancelled.
+            tokio::task::yield_now().await;
+        }
+
+        return Ok(());
+    }
+
+    // Chunks don't overlap so they may be cloned in any order.
+    stream::iter(ranges.into_iter().enumerate())
+        .map(|(idx, range)| {
+            let (starts, stops) = (&starts, &stops);
+
+            async move {
+                let mut conn = db
+                    .acquire()
+                    .await
+                    .context("Failed to acquire db connection")?;
+
+                let cloned = clone_events_range(
+                    &mut conn,
+                    metrics,
+                    room.id(),
+                    source_room_id,
+                    (starts, stops),
+                    offset,
+                    Some((range.start, range.stop)),
+                )
+                .await?;
+
+                observe_cloned_chunk(metrics, room, idx + 1, chunks_count, cloned);
+                Ok::<_, anyhow::Error>(())
+            }
+        })
+        .buffer_unordered(concurrency)
+        .try_for_each(|()| future::ready(Ok(())))
+        .await
+}
+
+fn observe_cloned_chunk(
+    metrics: &Metrics,
+    room: &Room,
+    chunk: usize,
+    chunks_count: usize,
+    cloned: u64,
+) {
+    metrics.adjust_cloned_events.inc_by(cloned);
+    metrics.adjust_cloned_chunks.inc();
+
+    info!(
+        room_id = %room.id(),
+        chunk,
+        chunks_count,
+        cloned,
+        "Cloned events chunk",
+    );
+}
+
+/// Clones events of the source room with `occurred_at` within the inclusive `range`
+/// or all of them when it's not set. Returns the number of cloned events.
+async fn clone_events_range(
+    conn: &mut PgConnection,
+    metrics: &Metrics,
+    room_id: Uuid,
+    source_room_id: Uuid,
+    (starts, stops): (&[i64], &[i64]),
+    offset: i64,
+    range: Option<(i64, i64)>,
+) -> Result<u64> {
+    let (range_start, range_stop) = range.unzip();
+
     let query = sqlx::query!(
         "
         WITH
@@ -451,7 +1033,7 @@ async fn clone_events(
                 FROM gap_starts, gap_stops
                 WHERE gap_stops.row_number = gap_starts.row_number
             )
-        INSERT INTO event (id, room_id, kind, set, label, data, binary_data, attribute, removed, occurred_at, created_by, created_at)
+        INSERT INTO event (id, room_id, kind, set, label, data, binary_data, content_encrypted, key_id, attributes, removed, occurred_at, created_by, created_at)
         SELECT
             id,
             room_id,
@@ -460,7 +1042,9 @@ async fn clone_events(
             label,
             data,
             binary_data,
-            attribute,
+            content_encrypted,
+            key_id,
+            attributes,
             removed,
             -- Monotonization
             -- cutstarts and cutstops are left as is to avoid skew
@@ -481,7 +1065,9 @@ async fn clone_events(
                 label,
                 data,
                 binary_data,
-                attribute,
+                content_encrypted,
+                key_id,
+                attributes,
                 removed,
                 (
                     CASE occurred_at <= (SELECT stop FROM gaps WHERE start = 0)
@@ -499,66 +1085,41 @@ async fn clone_events(
             FROM event
             WHERE room_id = $5
             AND   deleted_at IS NULL
+            AND   moderation_status = 'approved'
+            AND   ($6::BIGINT IS NULL OR occurred_at >= $6)
+            AND   ($7::BIGINT IS NULL OR occurred_at <= $7)
         ) AS sub
         ",
-        starts.as_slice(),
-        stops.as_slice(),
-        room.id(),
+        starts,
+        stops,
+        room_id,
         sqlx::types::BigDecimal::from(offset),
         source_room_id,
+        range_start,
+        range_stop,
     );
 
-    metrics
-        .measure_query(QueryKey::RoomAdjustCloneEventsQuery, query.execute(conn))
+    let mut txn = conn
+        .begin()
         .await
-        .map(|_| ())
-        .with_context(|| {
-            format!(
-                "failed to shift clone events from to room = '{}'",
-                room.id()
-            )
-        })
-}
+        .context("Failed to acquire transaction")?;
 
-/// Turns `segments` into gaps.
-pub fn invert_segments(
-    segments: &[(i64, i64)],
-    room_duration: Duration,
-    min_segment_length: StdDuration,
-) -> Result<Vec<(i64, i64)>> {
-    if segments.is_empty() {
-        let total_nanos = room_duration.num_nanoseconds().unwrap_or(std::i64::MAX);
-        return Ok(vec![(0, total_nanos)]);
-    }
-
-    let mut gaps = Vec::with_capacity(segments.len() + 2);
-
-    // A possible gap before the first segment.
-    if let Some((first_segment_start, _)) = segments.first() {
-        if *first_segment_start > 0 {
-            gaps.push((0, *first_segment_start));
-        }
-    }
-
-    // Gaps between segments.
-    for ((_, segment_stop), (next_segment_start, _)) in segments.iter().zip(&segments[1..]) {
-        gaps.push((*segment_stop, *next_segment_start));
-    }
-
-    // A possible gap after the last segment.
-    if let Some((_, last_segment_stop)) = segments.last() {
-        let room_duration_nanos = room_duration.num_nanoseconds().unwrap_or(std::i64::MAX);
+    // The room is new so there are no concurrent writers to order cloned events with.
+    keep_provisional_seq(&mut txn)
+        .await
+        .context("Failed to keep provisional seq")?;
 
-        // Don't create segments less than `min_segment_length`
-        if *last_segment_stop < room_duration_nanos
-            && StdDuration::from_nanos((room_duration_nanos - last_segment_stop) as u64)
-                .gt(&min_segment_length)
-        {
-            gaps.push((*last_segment_stop, room_duration_nanos));
-        }
-    }
+    let cloned = metrics
+        .measure_query(
+            QueryKey::RoomAdjustCloneEventsQuery,
+            query.execute(&mut txn),
+        )
+        .await
+        .map(|result| result.rows_affected())
+        .with_context(|| format!("failed to shift clone events from to room = '{}'", room_id))?;
 
-    Ok(gaps)
+    txn.commit().await.context("Failed to commit transaction")?;
+    Ok(cloned)
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -567,31 +1128,53 @@ enum CutEventsToGapsState {
     Stopped,
 }
 
+/// Cut-start/stop command of a stream event.
+struct Cut<'a> {
+    id: Uuid,
+    occurred_at: i64,
+    created_at: DateTime<Utc>,
+    command: Option<&'a str>,
+}
+
+impl<'a> From<&'a Event> for Cut<'a> {
+    fn from(event: &'a Event) -> Self {
+        Self {
+            id: event.id(),
+            occurred_at: event.occurred_at(),
+            created_at: event.created_at(),
+            command: event.data().get("cut").and_then(|v| v.as_str()),
+        }
+    }
+}
+
 /// Transforms cut-start/stop events ordered list to gaps list with a simple FSM.
 pub fn cut_events_to_gaps(cut_events: &[Event]) -> Result<Vec<(i64, i64)>> {
-    let mut gaps = Vec::with_capacity(cut_events.len());
-    let mut state: CutEventsToGapsState = CutEventsToGapsState::Started(0);
+    let cuts = cut_events.iter().map(Cut::from).collect::<Vec<_>>();
+    cut_commands_to_gaps(&cuts)
+}
 
-    for event in cut_events {
-        let command = event.data().get("cut").and_then(|v| v.as_str());
+fn cut_commands_to_gaps(cuts: &[Cut]) -> Result<Vec<(i64, i64)>> {
+    let mut gaps = Vec::with_capacity(cuts.len());
+    let mut state: CutEventsToGapsState = CutEventsToGapsState::Started(0);
 
-        match (command, state) {
+    for cut in cuts {
+        match (cut.command, state) {
             (Some("start"), CutEventsToGapsState::Started(_)) => {
-                state = CutEventsToGapsState::Started(event.occurred_at());
+                state = CutEventsToGapsState::Started(cut.occurred_at);
             }
             (Some("start"), CutEventsToGapsState::Stopped) => {
-                state = CutEventsToGapsState::Started(event.occurred_at());
+                state = CutEventsToGapsState::Started(cut.occurred_at);
             }
             (Some("stop"), CutEventsToGapsState::Started(start)) => {
-                gaps.push((start, event.occurred_at()));
+                gaps.push((start, cut.occurred_at));
                 state = CutEventsToGapsState::Stopped;
             }
             // if command is stop but we've already stopped - do nothing instead of failing
             (Some("stop"), CutEventsToGapsState::Stopped) => {}
             _ => bail!(
                 "invalid cut event, id = '{}', command = {:?}, state = {:?}",
-                event.id(),
-                command,
+                cut.id,
+                cut.command,
                 state
             ),
         }
@@ -599,17 +1182,17 @@ pub fn cut_events_to_gaps(cut_events: &[Event]) -> Result<Vec<(i64, i64)>> {
     Ok(gaps)
 }
 
-mod intersect;
-
 ///////////////////////////////////////////////////////////////////////////////
 
 #[cfg(test)]
 mod tests {
+    use std::num::NonZeroU32;
     use std::ops::Bound;
     use std::time::Duration as StdDuration;
 
-    use super::{call, AdjustOutput};
+    use super::{call, preview, shadow_diff, shift_occurred_at, AdjustOutput, AdjustPreview};
 
+    use crate::app::room_cache::RoomCache;
     use crate::config::AdjustConfig;
     use chrono::{DateTime, Duration, NaiveDateTime, Utc};
     use humantime::parse_duration as pd;
@@ -712,6 +1295,7 @@ mod tests {
                 data.clone(),
                 occurred_at,
                 created_by,
+                None,
             )
             .expect("Failed to create insert query")
             .created_at(opened_at + Duration::nanoseconds(occurred_at));
@@ -738,7 +1322,7 @@ mod tests {
                 data,
                 Some(|q: EventInsertQuery| {
                     if let Some(p) = attribute {
-                        q.attribute(p.to_owned())
+                        q.attributes(vec![p.to_owned()])
                     } else {
                         q
                     }
@@ -771,6 +1355,10 @@ mod tests {
                 state: TestCtxState::Initialized,
                 adjust_cfg: AdjustConfig {
                     min_segment_length: StdDuration::from_secs(1),
+                    clone_chunk_size: None,
+                    clone_concurrency: None,
+                    thinning: Default::default(),
+                    shadow: false,
                 },
             };
 
@@ -883,6 +1471,8 @@ mod tests {
             } = call(
                 &self.db.connection_pool(),
                 &self.metrics,
+                &RoomCache::new(),
+                None,
                 &self.room,
                 rtc_started_at,
                 segments,
@@ -1009,6 +1599,52 @@ mod tests {
         .await;
     }
 
+    // pointer events thinned to one per second of each label, the last ones are kept
+    // events of other kinds and the final state of each label must be left as is
+    #[tokio::test]
+    async fn adjust_room_test_thinning() {
+        let mut ctx = TestCtx::new(&[(1_000_000_000, "message", json!({"message": "m1"}))]).await;
+
+        {
+            let mut conn = ctx.db.get_conn().await;
+
+            for (occurred_at, label, x) in [
+                (2_100_000_000, "p1", 1),
+                (2_500_000_000, "p1", 2),
+                (2_600_000_000, "p2", 3),
+                (3_100_000_000, "p1", 4),
+                (3_200_000_000, "p1", 5),
+            ] {
+                ctx.create_event_f(
+                    &mut conn,
+                    occurred_at,
+                    "pointer",
+                    json!({ "x": x }),
+                    Some(|q: EventInsertQuery| q.label(label.to_owned())),
+                )
+                .await;
+            }
+        }
+
+        ctx.adjust_cfg
+            .thinning
+            .insert("pointer".to_owned(), NonZeroU32::new(1).unwrap());
+
+        ctx.set_segments(vec![(0, 20000)], ctx.opened_at, "0 seconds");
+
+        ctx.run().await;
+        ctx.events_asserts(
+            &[
+                (1_000_000_000, "message", json!({"message": "m1"})),
+                (2_500_000_000, "pointer", json!({"x": 2})),
+                (2_600_000_000, "pointer", json!({"x": 3})),
+                (3_200_000_000, "pointer", json!({"x": 5})),
+            ],
+            &[(0, 20000)],
+        )
+        .await;
+    }
+
     // single stream started as soon as room opened, 3s preroll offset
     // all events must be moved 3s to the right
     #[tokio::test]
@@ -1422,6 +2058,73 @@ mod tests {
         .await;
     }
 
+    // same as previous test but events are cloned in chunks
+    // monotonized events must not be split between chunks
+    #[tokio::test]
+    async fn adjust_room_test_15_chunked() {
+        let mut ctx = TestCtx::new(&[
+            (3_000_000_000, "message", json!({"message": "m1"})),
+            (18_000_000_000, "stream", json!({"cut": "start"})),
+            (19_000_000_000, "message", json!({"message": "m2"})),
+            (22_000_000_000, "message", json!({"message": "m3"})),
+            (29_000_000_000, "message", json!({"message": "m4"})),
+            (31_000_000_000, "stream", json!({"cut": "stop"})),
+            (33_000_000_000, "message", json!({"message": "m5"})),
+        ])
+        .await;
+
+        ctx.adjust_cfg.clone_chunk_size = Some(1);
+        ctx.set_segments(vec![(0, 20000), (28000, 34000)], ctx.opened_at, "3 seconds");
+
+        ctx.run().await;
+        ctx.events_asserts(
+            &[
+                (6_000_000_000, "message", json!({"message": "m1"})),
+                (21_000_000_000, "message", json!({"message": "m2"})),
+                (21_000_000_001, "message", json!({"message": "m3"})),
+                (21_000_000_002, "message", json!({"message": "m4"})),
+                (23_000_000_000, "message", json!({"message": "m5"})),
+            ],
+            &[(0, 18000), (23000, 26000)],
+        )
+        .await;
+
+        assert_eq!(ctx.metrics.adjust_cloned_events.get(), 14);
+    }
+
+    #[tokio::test]
+    async fn adjust_room_test_15_chunked_concurrently() {
+        let mut ctx = TestCtx::new(&[
+            (3_000_000_000, "message", json!({"message": "m1"})),
+            (18_000_000_000, "stream", json!({"cut": "start"})),
+            (19_000_000_000, "message", json!({"message": "m2"})),
+            (22_000_000_000, "message", json!({"message": "m3"})),
+            (29_000_000_000, "message", json!({"message": "m4"})),
+            (31_000_000_000, "stream", json!({"cut": "stop"})),
+            (33_000_000_000, "message", json!({"message": "m5"})),
+        ])
+        .await;
+
+        ctx.adjust_cfg.clone_chunk_size = Some(1);
+        ctx.adjust_cfg.clone_concurrency = Some(4);
+        ctx.set_segments(vec![(0, 20000), (28000, 34000)], ctx.opened_at, "3 seconds");
+
+        ctx.run().await;
+        ctx.events_asserts(
+            &[
+                (6_000_000_000, "message", json!({"message": "m1"})),
+                (21_000_000_000, "message", json!({"message": "m2"})),
+                (21_000_000_001, "message", json!({"message": "m3"})),
+                (21_000_000_002, "message", json!({"message": "m4"})),
+                (23_000_000_000, "message", json!({"message": "m5"})),
+            ],
+            &[(0, 18000), (23000, 26000)],
+        )
+        .await;
+
+        assert_eq!(ctx.metrics.adjust_cloned_events.get(), 14);
+    }
+
     // single stream started as soon as room opened, no preroll offset
     // single cut that ends after the stream end
     // message in cut must be moved to cut start
@@ -1614,6 +2317,156 @@ mod tests {
         ctx.assert_cut_original_segments(&[(3000, 10000), (13000, 20000)])
     }
 
+    #[tokio::test]
+    async fn adjust_room_preview() {
+        let ctx = TestCtx::new(&[
+            (1_000_000_000, "message", json!({"message": "m0"})),
+            (3_000_000_000, "break", json!({"value": false})),
+            (5_000_000_000, "message", json!({"message": "m1"})),
+            (10_000_000_000, "break", json!({"value": true})),
+            (12_000_000_000, "message", json!({"message": "m2"})),
+            (13_000_000_000, "break", json!({"value": false})),
+            (15_000_000_000, "message", json!({"message": "m3"})),
+        ])
+        .await;
+
+        let segments: Segments = vec![
+            (Bound::Included(0), Bound::Excluded(10000)),
+            (Bound::Included(13000), Bound::Excluded(20000)),
+        ]
+        .into();
+
+        let AdjustPreview {
+            modified_segments,
+            cut_original_segments,
+        } = preview(
+            &ctx.db.connection_pool(),
+            &ctx.metrics,
+            &ctx.room,
+            ctx.opened_at,
+            &segments,
+            &ctx.adjust_cfg,
+        )
+        .await
+        .expect("Room adjustment preview failed");
+
+        // Same segments as the adjustment itself results in.
+        let modified_segments: Vec<(Bound<i64>, Bound<i64>)> = modified_segments.into();
+        assert_eq!(
+            modified_segments.as_slice(),
+            &[
+                (Bound::Included(3000), Bound::Excluded(10000)),
+                (Bound::Included(10000), Bound::Excluded(17000))
+            ]
+        );
+
+        let cut_original_segments: Vec<(Bound<i64>, Bound<i64>)> = cut_original_segments.into();
+        assert_eq!(
+            cut_original_segments.as_slice(),
+            &[
+                (Bound::Included(3000), Bound::Excluded(10000)),
+                (Bound::Included(13000), Bound::Excluded(20000))
+            ]
+        );
+
+        // Neither cut events are created nor the room gets closed.
+        let mut conn = ctx.get_conn().await;
+
+        let cut_events = EventListQuery::new()
+            .room_id(ctx.room.id())
+            .kind("stream".to_string())
+            .execute(&mut conn)
+            .await
+            .expect("Failed to fetch cut events");
+
+        assert!(cut_events.is_empty());
+
+        let room = crate::db::room::FindQuery::by_id(ctx.room.id())
+            .execute(&mut conn)
+            .await
+            .expect("Failed to find room")
+            .expect("Room not found");
+
+        assert_eq!(
+            room.time().map(|t| t.end().to_owned()),
+            Ok(RoomTimeBound::Unbounded)
+        );
+    }
+
+    #[tokio::test]
+    async fn adjust_room_shadow() {
+        let mut ctx = TestCtx::new(&[
+            (1_000_000_000, "message", json!({"message": "m0"})),
+            (3_000_000_000, "break", json!({"value": false})),
+            (5_000_000_000, "message", json!({"message": "m1"})),
+            (10_000_000_000, "break", json!({"value": true})),
+            (12_000_000_000, "message", json!({"message": "m2"})),
+            (13_000_000_000, "break", json!({"value": false})),
+            (15_000_000_000, "message", json!({"message": "m3"})),
+        ])
+        .await;
+
+        ctx.adjust_cfg.shadow = true;
+        ctx.set_segments(vec![(0, 10000), (13000, 20000)], ctx.opened_at, "0 seconds");
+        ctx.run().await;
+
+        let mut conn = ctx.get_conn().await;
+
+        let (matched, diff): (bool, JsonValue) =
+            sqlx::query_as("SELECT matched, diff FROM adjustment_comparison WHERE room_id = $1")
+                .bind(ctx.room.id())
+                .fetch_one(&mut conn)
+                .await
+                .expect("Failed to fetch adjustment comparison");
+
+        assert!(matched);
+        assert_eq!(diff, json!({}));
+    }
+
+    #[test]
+    fn shadow_diff_of_segments() {
+        let segments: Segments = vec![
+            (Bound::Included(0), Bound::Excluded(10000)),
+            (Bound::Included(13000), Bound::Excluded(20000)),
+        ]
+        .into();
+
+        let shadow = AdjustPreview {
+            modified_segments: segments.clone(),
+            cut_original_segments: segments.clone(),
+        };
+
+        assert_eq!(shadow_diff(&segments, &segments, Ok(shadow)), None);
+
+        let shadow = AdjustPreview {
+            modified_segments: vec![(Bound::Included(0), Bound::Excluded(9000))].into(),
+            cut_original_segments: segments.clone(),
+        };
+
+        let diff = shadow_diff(&segments, &segments, Ok(shadow)).expect("No diff");
+        assert_eq!(diff["modified_segments"]["mismatched"], json!([0]));
+        assert_eq!(diff["modified_segments"]["count_delta"], json!(-1));
+        assert!(diff.get("cut_original_segments").is_none());
+
+        let diff = shadow_diff(&segments, &segments, Err(anyhow!("boom"))).expect("No diff");
+        assert_eq!(diff["error"], json!("boom"));
+    }
+
+    #[test]
+    fn shift_occurred_at_by_gaps() {
+        let gaps = [(0, 1000), (3000, 4000), (6000, 8000)];
+
+        assert_eq!(shift_occurred_at(500, &gaps), 0);
+        assert_eq!(shift_occurred_at(1000, &gaps), 0);
+        assert_eq!(shift_occurred_at(2000, &gaps), 1000);
+        assert_eq!(shift_occurred_at(3500, &gaps), 2000);
+        assert_eq!(shift_occurred_at(5000, &gaps), 3000);
+        assert_eq!(shift_occurred_at(9000, &gaps), 5000);
+
+        // Without a leading gap nothing collapses to zero.
+        assert_eq!(shift_occurred_at(500, &gaps[1..]), 500);
+    }
+
     #[tokio::test]
     async fn adjust_room_test_video_group_event_as_stream_cut() {
         let mut ctx = TestCtx::new(&[
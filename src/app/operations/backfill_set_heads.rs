@@ -0,0 +1,137 @@
+use std::time::Duration as StdDuration;
+
+use anyhow::{Context, Result};
+use serde_derive::Serialize;
+use sqlx::postgres::PgPool as Db;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{
+    db::set_head::BackfillQuery,
+    metrics::{Metrics, QueryKey},
+};
+
+const BATCH_SIZE: i64 = 100;
+/// Pause between batches not to load the database along with live traffic.
+const BATCH_INTERVAL: StdDuration = StdDuration::from_millis(100);
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct BackfillStats {
+    pub rooms: u64,
+    /// The last processed room to resume the job after.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_room_id: Option<Uuid>,
+}
+
+/// Fills `set_head` for rooms having events from before it was introduced in batches of rooms.
+///
+/// Rooms are taken in `id` order starting after `after_room_id` so an interrupted job may be
+/// resumed from the last reported id. Rerunning is safe since existing rows are only moved forward.
+/// `report` is called with the running totals after each batch.
+pub async fn call(
+    db: &Db,
+    metrics: &Metrics,
+    after_room_id: Option<Uuid>,
+    report: impl Fn(&BackfillStats),
+) -> Result<BackfillStats> {
+    let mut stats = BackfillStats {
+        last_room_id: after_room_id,
+        ..Default::default()
+    };
+
+    loop {
+        let mut conn = db
+            .acquire()
+            .await
+            .context("Failed to acquire db connection")?;
+
+        let query = BackfillQuery::new(stats.last_room_id, BATCH_SIZE);
+
+        let room_ids = metrics
+            .measure_query(QueryKey::SetHeadBackfillQuery, query.execute(&mut conn))
+            .await
+            .context("Failed to backfill set heads")?;
+
+        stats.rooms += room_ids.len() as u64;
+
+        if let Some(room_id) = room_ids.last() {
+            stats.last_room_id = Some(*room_id);
+        }
+
+        report(&stats);
+
+        if (room_ids.len() as i64) < BATCH_SIZE {
+            break;
+        }
+
+        drop(conn);
+        tokio::time::sleep(BATCH_INTERVAL).await;
+    }
+
+    info!("Set heads backfill finished, rooms = {}", stats.rooms);
+
+    Ok(stats)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use prometheus::Registry;
+    use serde_json::json;
+
+    use super::*;
+    use crate::db::set_head::ListQuery;
+    use crate::test_helpers::prelude::*;
+
+    #[tokio::test]
+    async fn backfill_set_heads() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let metrics = Metrics::new(&Registry::new()).unwrap();
+
+        let (room, event) = {
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+
+            let event = factory::Event::new()
+                .room_id(room.id())
+                .kind("message")
+                .set("messages")
+                .label("message-1")
+                .data(&json!({}))
+                .occurred_at(1000)
+                .created_by(agent.agent_id())
+                .insert(&mut conn)
+                .await;
+
+            // Events inserted before the trigger have no set head.
+            sqlx::query("DELETE FROM set_head WHERE room_id = $1")
+                .bind(room.id())
+                .execute(&mut conn)
+                .await
+                .expect("Failed to delete set heads");
+
+            (room, event)
+        };
+
+        let stats = call(db.connection_pool(), &metrics, None, |_| ())
+            .await
+            .expect("Set heads backfill failed");
+
+        assert!(stats.rooms >= 1);
+        assert!(stats.last_room_id.is_some());
+
+        let mut conn = db.get_conn().await;
+
+        let sets = ListQuery::new(room.id())
+            .execute(&mut conn)
+            .await
+            .expect("Failed to list sets");
+
+        assert_eq!(sets.len(), 1);
+        assert_eq!(sets[0].set(), "messages");
+        assert_eq!(sets[0].label_count(), 1);
+        assert_eq!(sets[0].updated_at(), event.created_at());
+    }
+}
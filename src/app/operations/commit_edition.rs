@@ -1,32 +1,50 @@
-use std::ops::Bound;
+use std::future::Future;
+use std::time::Duration as StdDuration;
 
 use anyhow::{Context, Result};
 use chrono::Utc;
 use sqlx::postgres::{PgConnection, PgPool as Db};
-use tracing::{info, instrument};
+use tracing::{error, info, instrument, warn};
 
 use crate::config::AdjustConfig;
 use crate::db::change::{ListQuery as ChangeListQuery, Object as Change};
 use crate::db::edition::Object as Edition;
+use crate::db::edition_commit_job::{
+    FindQuery as JobFindQuery, Object as Job, Status as JobStatus, UpdateQuery as JobUpdateQuery,
+};
 use crate::db::event::{
-    DeleteQuery as EventDeleteQuery, ListQuery as EventListQuery, Object as Event,
+    keep_provisional_seq, DeleteQuery as EventDeleteQuery, EventKind, ListQuery as EventListQuery,
+    Object as Event,
+};
+use crate::db::room::{
+    DeleteQuery as RoomDeleteQuery, FindQuery as RoomFindQuery, InsertQuery as RoomInsertQuery,
+    Object as Room,
 };
-use crate::db::room::{InsertQuery as RoomInsertQuery, Object as Room};
 use crate::db::room_time::RoomTimeBound;
 use crate::{
-    app::operations::adjust_room::{invert_segments, NANOSECONDS_IN_MILLISECOND},
+    app::operations::segments::{self, NANOSECONDS_IN_MILLISECOND},
     metrics::Metrics,
 };
 use crate::{db::adjustment::Segments, metrics::QueryKey};
 
+/// How many times to try a step of the commit when the DB fails transiently.
+const MAX_ATTEMPTS: u32 = 3;
+const RETRY_INTERVAL: StdDuration = StdDuration::from_secs(1);
+
 ////////////////////////////////////////////////////////////////////////////////
 
+/// Commits the edition into a new room continuing the `job` from its checkpoint.
+///
+/// Events are cloned in chunks of `cfg.clone_chunk_size` each in its own transaction
+/// along with the job checkpoint so a commit interrupted in the middle can be resumed.
+/// `progress` is called after each chunk.
 #[instrument(
     skip_all,
     fields(
         source_room_id = %source.id(),
         edition_id = %edition.id(),
-        offset = ?offset,
+        job_id = %job.id(),
+        offset = ?job.time_offset(),
     )
 )]
 pub async fn call(
@@ -34,18 +52,49 @@ pub async fn call(
     metrics: &Metrics,
     edition: &Edition,
     source: &Room,
-    offset: i64,
+    job: Job,
     cfg: AdjustConfig,
+    progress: impl Fn(&Job) + Send + Sync,
 ) -> Result<(Room, Segments)> {
     info!("Edition commit task started");
 
     let start_timestamp = Utc::now();
+    let job_id = job.id();
+    let attempts = job.attempts();
+
+    match commit(db, metrics, edition, source, job, cfg, progress).await {
+        Ok((destination, modified_segments)) => {
+            info!(
+                duration_ms = (Utc::now() - start_timestamp).num_milliseconds(),
+                destination_id = %destination.id(),
+                segments = ?modified_segments,
+                "Edition commit successfully finished",
+            );
+
+            Ok((destination, modified_segments))
+        }
+        Err(err) => {
+            if let Err(fail_err) = fail(db, metrics, job_id, attempts, &err).await {
+                error!(
+                    "Failed to mark edition commit job as failed: {:?}",
+                    fail_err
+                );
+            }
 
-    let mut txn = db
-        .begin()
-        .await
-        .context("Failed to begin sqlx db transaction")?;
+            Err(err)
+        }
+    }
+}
 
+async fn commit(
+    db: &Db,
+    metrics: &Metrics,
+    edition: &Edition,
+    source: &Room,
+    mut job: Job,
+    cfg: AdjustConfig,
+    progress: impl Fn(&Job) + Send + Sync,
+) -> Result<(Room, Segments)> {
     let room_duration = match source.time() {
         Ok(t) => match t.end() {
             RoomTimeBound::Excluded(stop) => stop.signed_duration_since(*t.start()),
@@ -54,19 +103,197 @@ pub async fn call(
         _ => bail!("invalid duration for room = '{}'", source.id()),
     };
 
+    let cut_gaps = retry(|| find_cut_gaps(db, metrics, edition, source)).await?;
+    info!(gaps_count = cut_gaps.len(), "Found cut gaps");
+
+    let destination = match job.destination_room_id() {
+        Some(id) => {
+            let query = RoomFindQuery::by_id(id);
+            let mut conn = db
+                .acquire()
+                .await
+                .context("Failed to acquire db connection")?;
+
+            metrics
+                .measure_query(QueryKey::RoomFindQuery, query.execute(&mut conn))
+                .await
+                .context("Failed to find destination room")?
+                .ok_or_else(|| anyhow!("Destination room = '{}' not found", id))?
+        }
+        None => {
+            let (destination, updated_job) =
+                retry(|| create_destination(db, metrics, source, &job)).await?;
+            job = updated_job;
+            destination
+        }
+    };
+
+    info!(destination_id = %destination.id(), "Destination room is ready");
+
+    let clone = CloneEvents {
+        source,
+        destination: &destination,
+        edition,
+        gaps: &cut_gaps,
+        offset: job.time_offset() * NANOSECONDS_IN_MILLISECOND,
+    };
+
+    let chunk_size = cfg.clone_chunk_size.map(|s| s as i64).unwrap_or(i64::MAX);
+
+    loop {
+        let (count, updated_job) =
+            retry(|| clone_events_chunk(db, metrics, &clone, &job, chunk_size)).await?;
+
+        job = updated_job;
+        progress(&job);
+
+        // A chunk is never smaller than the limit unless there are no more events.
+        if count < chunk_size {
+            break;
+        }
+    }
+
+    retry(|| delete_cut_events(db, metrics, &destination)).await?;
+    info!("Deleted cut events from the destination room");
+
+    let modified_segments = segments::to_millis(&segments::invert(
+        &cut_gaps,
+        room_duration,
+        cfg.min_segment_length,
+    ));
+
+    retry(|| async {
+        let query = JobUpdateQuery::new(&job).status(JobStatus::Succeeded);
+        let mut conn = db
+            .acquire()
+            .await
+            .context("Failed to acquire db connection")?;
+
+        metrics
+            .measure_query(
+                QueryKey::EditionCommitJobUpdateQuery,
+                query.execute(&mut conn),
+            )
+            .await
+            .context("Failed to update edition commit job")?
+            .ok_or_else(|| anyhow!("Edition commit job = '{}' was taken over", job.id()))
+    })
+    .await?;
+
+    Ok((destination, modified_segments))
+}
+
+/// Marks the job as failed and drops the partially cloned room unless the job
+/// has been taken over by another replica meanwhile.
+async fn fail(
+    db: &Db,
+    metrics: &Metrics,
+    job_id: uuid::Uuid,
+    attempts: i32,
+    err: &anyhow::Error,
+) -> Result<()> {
+    let mut conn = db
+        .acquire()
+        .await
+        .context("Failed to acquire db connection")?;
+
+    let query = JobFindQuery::new(job_id);
+
+    let job = metrics
+        .measure_query(
+            QueryKey::EditionCommitJobFindQuery,
+            query.execute(&mut conn),
+        )
+        .await
+        .context("Failed to find edition commit job")?;
+
+    // Someone else runs the job now.
+    let job = match job {
+        Some(job) if job.attempts() == attempts => job,
+        _ => return Ok(()),
+    };
+
+    let query = JobUpdateQuery::new(&job)
+        .status(JobStatus::Failed)
+        .error(format!("{:#}", err));
+
+    let failed_job = metrics
+        .measure_query(
+            QueryKey::EditionCommitJobUpdateQuery,
+            query.execute(&mut conn),
+        )
+        .await
+        .context("Failed to update edition commit job")?;
+
+    if let (Some(job), Some(destination_id)) = (failed_job, job.destination_room_id()) {
+        let query = RoomDeleteQuery::new(destination_id);
+
+        metrics
+            .measure_query(QueryKey::RoomDeleteQuery, query.execute(&mut conn))
+            .await
+            .with_context(|| format!("Failed to delete room = '{}'", destination_id))?;
+
+        info!(job_id = %job.id(), "Deleted partially committed room = '{}'", destination_id);
+    }
+
+    Ok(())
+}
+
+/// Runs `f` once again after a pause if it fails because of a connection loss,
+/// pool timeout, serialization failure or deadlock.
+async fn retry<T, F, Fut>(mut f: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut attempt = 1;
+
+    loop {
+        match f().await {
+            Err(err) if attempt < MAX_ATTEMPTS && is_transient(&err) => {
+                warn!(attempt, "Edition commit step failed, retrying: {:?}", err);
+                tokio::time::sleep(RETRY_INTERVAL * attempt).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+fn is_transient(err: &anyhow::Error) -> bool {
+    err.chain().any(|e| match e.downcast_ref::<sqlx::Error>() {
+        Some(sqlx::Error::Io(_) | sqlx::Error::PoolTimedOut) => true,
+        Some(sqlx::Error::Database(e)) => {
+            matches!(e.code().as_deref(), Some("40001" | "40P01"))
+        }
+        _ => false,
+    })
+}
+
+async fn find_cut_gaps(
+    db: &Db,
+    metrics: &Metrics,
+    edition: &Edition,
+    source: &Room,
+) -> Result<Vec<(i64, i64)>> {
+    let mut conn = db
+        .acquire()
+        .await
+        .context("Failed to acquire db connection")?;
+
     let query = EventListQuery::new()
         .room_id(source.id())
-        .kind("stream".to_string());
+        .kind(EventKind::Stream);
 
     let cut_events = metrics
-        .measure_query(QueryKey::EventListQuery, query.execute(&mut txn))
+        .measure_query(QueryKey::EventListQuery, query.execute(&mut conn))
         .await
         .with_context(|| format!("failed to fetch cut events for room_id = '{}'", source.id()))?;
 
-    let query = ChangeListQuery::new(edition.id()).kind("stream");
+    let query = ChangeListQuery::new(edition.id()).kind(EventKind::Stream.as_str());
 
     let cut_changes = metrics
-        .measure_query(QueryKey::ChangeListQuery, query.execute(&mut txn))
+        .measure_query(QueryKey::ChangeListQuery, query.execute(&mut conn))
         .await
         .with_context(|| {
             format!(
@@ -75,54 +302,37 @@ pub async fn call(
             )
         })?;
 
-    let cut_gaps = collect_gaps(&cut_events, &cut_changes)?;
-    let destination = clone_room(&mut txn, metrics, source).await?;
+    collect_gaps(&cut_events, &cut_changes)
+}
 
-    clone_events(
-        &mut txn,
-        metrics,
-        source,
-        &destination,
-        edition,
-        &cut_gaps,
-        offset * NANOSECONDS_IN_MILLISECOND,
-    )
-    .await?;
+async fn create_destination(
+    db: &Db,
+    metrics: &Metrics,
+    source: &Room,
+    job: &Job,
+) -> Result<(Room, Job)> {
+    let mut txn = db
+        .begin()
+        .await
+        .context("Failed to begin sqlx db transaction")?;
 
-    let query = EventDeleteQuery::new(destination.id(), "stream");
+    let destination = clone_room(&mut txn, metrics, source).await?;
+    let query = JobUpdateQuery::new(job).destination_room_id(destination.id());
 
-    metrics
-        .measure_query(QueryKey::EventDeleteQuery, query.execute(&mut txn))
+    let job = metrics
+        .measure_query(
+            QueryKey::EditionCommitJobUpdateQuery,
+            query.execute(&mut txn),
+        )
         .await
-        .with_context(|| {
-            format!(
-                "failed to delete cut events for room_id = '{}'",
-                destination.id()
-            )
-        })?;
-
-    let modified_segments = invert_segments(&cut_gaps, room_duration, cfg.min_segment_length)?
-        .into_iter()
-        .map(|(start, stop)| {
-            (
-                Bound::Included(start / NANOSECONDS_IN_MILLISECOND),
-                Bound::Excluded(stop / NANOSECONDS_IN_MILLISECOND),
-            )
-        })
-        .collect::<Vec<(Bound<i64>, Bound<i64>)>>();
+        .context("Failed to update edition commit job")?
+        .ok_or_else(|| anyhow!("Edition commit job = '{}' was taken over", job.id()))?;
 
     metrics
         .measure_query(QueryKey::EditionCommitTxnCommit, txn.commit())
         .await?;
 
-    info!(
-        duration_ms = (Utc::now() - start_timestamp).num_milliseconds(),
-        destination_id = %destination.id(),
-        segments = ?modified_segments,
-        "Edition commit successfully finished",
-    );
-
-    Ok((destination, Segments::from(modified_segments))) as Result<(Room, Segments)>
+    Ok((destination, job))
 }
 
 async fn clone_room(conn: &mut PgConnection, metrics: &Metrics, source: &Room) -> Result<Room> {
@@ -148,25 +358,66 @@ async fn clone_room(conn: &mut PgConnection, metrics: &Metrics, source: &Room) -
         .context("Failed to insert room")
 }
 
-async fn clone_events(
-    conn: &mut PgConnection,
-    metrics: &Metrics,
-    source: &Room,
-    destination: &Room,
-    edition: &Edition,
-    gaps: &[(i64, i64)],
+async fn delete_cut_events(db: &Db, metrics: &Metrics, destination: &Room) -> Result<()> {
+    let query = EventDeleteQuery::new(destination.id(), EventKind::Stream);
+    let mut conn = db
+        .acquire()
+        .await
+        .context("Failed to acquire db connection")?;
+
+    metrics
+        .measure_query(QueryKey::EventDeleteQuery, query.execute(&mut conn))
+        .await
+        .with_context(|| {
+            format!(
+                "failed to delete cut events for room_id = '{}'",
+                destination.id()
+            )
+        })?;
+
+    Ok(())
+}
+
+struct CloneEvents<'a> {
+    source: &'a Room,
+    destination: &'a Room,
+    edition: &'a Edition,
+    gaps: &'a [(i64, i64)],
     offset: i64,
-) -> Result<()> {
-    let mut starts = Vec::with_capacity(gaps.len());
-    let mut stops = Vec::with_capacity(gaps.len());
+}
 
-    for (start, stop) in gaps {
+/// Clones at least `limit` events following the job checkpoint in source time order
+/// and moves the checkpoint forward in the same transaction.
+///
+/// Events with the same `occurred_at` always go to the same chunk to keep them apart
+/// in the destination room.
+async fn clone_events_chunk(
+    db: &Db,
+    metrics: &Metrics,
+    clone: &CloneEvents<'_>,
+    job: &Job,
+    limit: i64,
+) -> Result<(i64, Job)> {
+    let mut starts = Vec::with_capacity(clone.gaps.len());
+    let mut stops = Vec::with_capacity(clone.gaps.len());
+
+    for (start, stop) in clone.gaps {
         starts.push(*start);
         stops.push(*stop);
     }
 
+    let mut txn = db
+        .begin()
+        .await
+        .context("Failed to begin sqlx db transaction")?;
+
+    // The destination room is new so there are no concurrent writers to order cloned events with.
+    keep_provisional_seq(&mut txn)
+        .await
+        .context("Failed to keep provisional seq")?;
+
     let query = sqlx::query!(
-        "
+        r#"
         WITH
             gap_starts AS (
                 SELECT start, ROW_NUMBER() OVER () AS row_number
@@ -185,101 +436,159 @@ async fn clone_events(
                 SELECT DISTINCT event_set
                 FROM change
                 WHERE change.edition_id = $3 AND change.kind = 'bulk_removal'
-            )
-        INSERT INTO event (id, room_id, kind, set, label, data, binary_data, occurred_at, created_by, created_at)
-        SELECT
-            id,
-            room_id,
-            kind,
-            set,
-            label,
-            data,
-            binary_data,
-            occurred_at + ROW_NUMBER() OVER (partition by occurred_at order by created_at) - 1 + $6,
-            created_by,
-            created_at
-        FROM (
-            SELECT
-                gen_random_uuid() AS id,
-                $2::UUID AS room_id,
-                (CASE change.kind
-                        WHEN 'addition' THEN change.event_kind
-                        WHEN 'modification' THEN COALESCE(change.event_kind, event.kind)
-                        ELSE event.kind
-                    END
-                ) AS kind,
-                (CASE change.kind
-                    WHEN 'addition' THEN COALESCE(change.event_set, change.event_kind)
-                    WHEN 'modification' THEN COALESCE(change.event_set, event.set, change.event_kind, event.kind)
-                    ELSE event.set
-                    END
-                ) AS set,
-                (CASE change.kind
-                    WHEN 'addition' THEN change.event_label
-                    WHEN 'modification' THEN COALESCE(change.event_label, event.label)
-                    ELSE event.label
-                    END
-                ) AS label,
-                (CASE change.kind
-                    WHEN 'addition' THEN change.event_data
-                    WHEN 'modification' THEN COALESCE(change.event_data, event.data)
-                    ELSE event.data
-                    END
-                ) AS data,
-                event.binary_data,
-                (
+            ),
+            cloned AS (
+                SELECT
+                    gen_random_uuid() AS id,
+                    $2::UUID AS room_id,
+                    (CASE change.kind
+                            WHEN 'addition' THEN change.event_kind
+                            WHEN 'modification' THEN COALESCE(change.event_kind, event.kind)
+                            ELSE event.kind
+                        END
+                    ) AS kind,
+                    (CASE change.kind
+                        WHEN 'addition' THEN COALESCE(change.event_set, change.event_kind)
+                        WHEN 'modification' THEN COALESCE(change.event_set, event.set, change.event_kind, event.kind)
+                        ELSE event.set
+                        END
+                    ) AS set,
+                    (CASE change.kind
+                        WHEN 'addition' THEN change.event_label
+                        WHEN 'modification' THEN COALESCE(change.event_label, event.label)
+                        ELSE event.label
+                        END
+                    ) AS label,
+                    (CASE change.kind
+                        WHEN 'addition' THEN change.event_data
+                        WHEN 'modification' THEN COALESCE(change.event_data, event.data)
+                        ELSE event.data
+                        END
+                    ) AS data,
+                    event.binary_data,
+                    COALESCE(event.content_encrypted, FALSE) AS content_encrypted,
+                    event.key_id,
+                    (
+                        (CASE change.kind
+                            WHEN 'addition' THEN change.event_occurred_at
+                            WHEN 'modification' THEN COALESCE(change.event_occurred_at, event.occurred_at)
+                            ELSE event.occurred_at
+                            END
+                        ) - (
+                            SELECT COALESCE(SUM(LEAST(stop, occurred_at) - start), 0)
+                            FROM gaps
+                            WHERE start < occurred_at
+                        )
+                    ) AS occurred_at,
                     (CASE change.kind
-                        WHEN 'addition' THEN change.event_occurred_at
-                        WHEN 'modification' THEN COALESCE(change.event_occurred_at, event.occurred_at)
-                        ELSE event.occurred_at
+                        WHEN 'addition' THEN change.event_created_by
+                        ELSE event.created_by
                         END
-                    ) - (
-                        SELECT COALESCE(SUM(LEAST(stop, occurred_at) - start), 0)
-                        FROM gaps
-                        WHERE start < occurred_at
-                    )
-                ) AS occurred_at,
-                (CASE change.kind
-                    WHEN 'addition' THEN change.event_created_by
-                    ELSE event.created_by
-                    END
-                ) AS created_by,
-                COALESCE(event.created_at, NOW()) as created_at
-            FROM
-                (SELECT * FROM event 
-                    WHERE   event.room_id = $1 
-                        AND deleted_at IS NULL 
-                        AND event.set NOT IN (SELECT event_set FROM removed_sets)
-                ) AS event
-                FULL OUTER JOIN
-                (SELECT * FROM change WHERE change.edition_id = $3 AND change.kind <> 'bulk_removal')
-                AS change
-                ON change.event_id = event.id
-            WHERE
-                ((event.room_id = $1 AND deleted_at IS NULL) OR event.id IS NULL)
-                AND
-                ((change.edition_id = $3 AND change.kind <> 'removal') OR change.id IS NULL)
-        ) AS subquery
-        ",
-        source.id(),
-        destination.id(),
-        edition.id(),
+                    ) AS created_by,
+                    COALESCE(event.created_at, NOW()) as created_at
+                FROM
+                    (SELECT * FROM event
+                        WHERE   event.room_id = $1
+                            AND deleted_at IS NULL
+                            AND moderation_status = 'approved'
+                            AND event.set NOT IN (SELECT event_set FROM removed_sets)
+                    ) AS event
+                    FULL OUTER JOIN
+                    (SELECT * FROM change WHERE change.edition_id = $3 AND change.kind <> 'bulk_removal')
+                    AS change
+                    ON change.event_id = event.id
+                WHERE
+                    ((event.room_id = $1 AND deleted_at IS NULL) OR event.id IS NULL)
+                    AND
+                    ((change.edition_id = $3 AND change.kind <> 'removal') OR change.id IS NULL)
+            ),
+            chunk AS (
+                SELECT *
+                FROM cloned
+                WHERE $7::BIGINT IS NULL OR occurred_at > $7
+            ),
+            chunk_stop AS (
+                SELECT MAX(occurred_at) AS occurred_at
+                FROM (
+                    SELECT occurred_at
+                    FROM chunk
+                    ORDER BY occurred_at
+                    LIMIT $8
+                ) AS head
+            ),
+            inserted AS (
+                INSERT INTO event (id, room_id, kind, set, label, data, binary_data, content_encrypted, key_id, occurred_at, created_by, created_at)
+                SELECT
+                    id,
+                    room_id,
+                    kind,
+                    set,
+                    label,
+                    data,
+                    binary_data,
+                    content_encrypted,
+                    key_id,
+                    occurred_at + ROW_NUMBER() OVER (partition by occurred_at order by created_at) - 1 + $6,
+                    created_by,
+                    created_at
+                FROM chunk
+                WHERE occurred_at <= (SELECT occurred_at FROM chunk_stop)
+                RETURNING 1
+            )
+        SELECT
+            (SELECT COUNT(*) FROM inserted) AS "count!",
+            (SELECT occurred_at FROM chunk_stop)::BIGINT AS checkpoint
+        "#,
+        clone.source.id(),
+        clone.destination.id(),
+        clone.edition.id(),
         starts.as_slice(),
         stops.as_slice(),
-        sqlx::types::BigDecimal::from(offset)
+        sqlx::types::BigDecimal::from(clone.offset),
+        job.checkpoint(),
+        limit,
     );
 
-    metrics
-        .measure_query(QueryKey::EditionCloneEventsQuery, query.execute(conn))
+    let chunk = metrics
+        .measure_query(QueryKey::EditionCloneEventsQuery, query.fetch_one(&mut txn))
         .await
-        .map(|_| ())
         .with_context(|| {
             format!(
                 "Failed cloning events from room = '{}' to room = {}",
-                source.id(),
-                destination.id(),
+                clone.source.id(),
+                clone.destination.id(),
             )
-        })
+        })?;
+
+    let job = match chunk.checkpoint {
+        Some(checkpoint) => {
+            let query =
+                JobUpdateQuery::new(job).checkpoint(checkpoint, job.cloned_events() + chunk.count);
+
+            metrics
+                .measure_query(
+                    QueryKey::EditionCommitJobUpdateQuery,
+                    query.execute(&mut txn),
+                )
+                .await
+                .context("Failed to update edition commit job")?
+                .ok_or_else(|| anyhow!("Edition commit job = '{}' was taken over", job.id()))?
+        }
+        None => job.to_owned(),
+    };
+
+    metrics
+        .measure_query(QueryKey::EditionCommitTxnCommit, txn.commit())
+        .await?;
+
+    info!(
+        job_id = %job.id(),
+        cloned_events = job.cloned_events(),
+        "Cloned {} events of the edition",
+        chunk.count
+    );
+
+    Ok((chunk.count, job))
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -368,6 +677,7 @@ fn collect_gaps(cut_events: &[Event], cut_changes: &[Change]) -> Result<Vec<(i64
 
 ////////////////////////////////////////////////////////////////////////////////
 
+#[cfg(test)]
 #[cfg(test)]
 mod tests {
     use std::ops::Bound;
@@ -381,9 +691,12 @@ mod tests {
     use svc_agent::{AccountId, AgentId};
     use svc_authn::Authenticable;
 
-    use crate::app::operations::adjust_room::{invert_segments, NANOSECONDS_IN_MILLISECOND};
     use crate::app::operations::commit_edition::collect_gaps;
+    use crate::app::operations::segments;
     use crate::config::AdjustConfig;
+    use crate::db::edition_commit_job::{
+        FindQuery as JobFindQuery, InsertQuery as JobInsertQuery, Status as JobStatus,
+    };
     use crate::db::event::{ListQuery as EventListQuery, Object as Event};
     use crate::db::room::{ClassType, Object as Room};
     use crate::test_helpers::db::TestDb;
@@ -490,18 +803,28 @@ mod tests {
             .insert(&mut conn)
             .await;
 
+        let job = JobInsertQuery::new(edition.id(), 0)
+            .execute(&mut conn)
+            .await
+            .expect("Failed to insert edition commit job");
+
         drop(conn);
 
         let adjust_cfg = AdjustConfig {
             min_segment_length: StdDuration::from_secs(1),
+            clone_chunk_size: None,
+            clone_concurrency: None,
+            thinning: Default::default(),
+            shadow: false,
         };
         let (destination, segments) = super::call(
             &db.connection_pool(),
             &metrics,
             &edition,
             &room,
-            0,
+            job,
             adjust_cfg,
+            |_| (),
         )
         .await
         .expect("edition commit failed");
@@ -627,18 +950,28 @@ mod tests {
             .insert(&mut conn)
             .await;
 
+        let job = JobInsertQuery::new(edition.id(), 0)
+            .execute(&mut conn)
+            .await
+            .expect("Failed to insert edition commit job");
+
         drop(conn);
 
         let adjust_cfg = AdjustConfig {
             min_segment_length: StdDuration::from_secs(1),
+            clone_chunk_size: None,
+            clone_concurrency: None,
+            thinning: Default::default(),
+            shadow: false,
         };
         let (destination, segments) = super::call(
             &db.connection_pool(),
             &metrics,
             &edition,
             &room,
-            0,
+            job,
             adjust_cfg,
+            |_| (),
         )
         .await
         .expect("edition commit failed");
@@ -743,18 +1076,28 @@ mod tests {
             .insert(&mut conn)
             .await;
 
+        let job = JobInsertQuery::new(edition.id(), 0)
+            .execute(&mut conn)
+            .await
+            .expect("Failed to insert edition commit job");
+
         drop(conn);
 
         let adjust_cfg = AdjustConfig {
             min_segment_length: StdDuration::from_secs(1),
+            clone_chunk_size: None,
+            clone_concurrency: None,
+            thinning: Default::default(),
+            shadow: false,
         };
         let (destination, segments) = super::call(
             &db.connection_pool(),
             &metrics,
             &edition,
             &room,
-            0,
+            job,
             adjust_cfg,
+            |_| (),
         )
         .await
         .expect("edition commit failed");
@@ -783,6 +1126,142 @@ mod tests {
         assert_eq!(events[2].occurred_at(), 3_500_000_000);
     }
 
+    #[tokio::test]
+    async fn commit_edition_in_chunks() {
+        let metrics = Metrics::new(&Registry::new()).unwrap();
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let mut conn = db.get_conn().await;
+        let room = shared_helpers::insert_room(&mut conn).await;
+
+        for (occurred_at, message) in [
+            (1_000_000_000, "m1"),
+            (2_000_000_000, "m2"),
+            (2_000_000_000, "m3"),
+            (3_000_000_000, "m4"),
+        ] {
+            create_event(
+                &mut conn,
+                &room,
+                occurred_at,
+                "message",
+                json!({ "message": message }),
+            )
+            .await;
+        }
+
+        let edition = factory::Edition::new(room.id(), agent.agent_id())
+            .insert(&mut conn)
+            .await;
+
+        let job = JobInsertQuery::new(edition.id(), 0)
+            .execute(&mut conn)
+            .await
+            .expect("Failed to insert edition commit job");
+
+        let adjust_cfg = AdjustConfig {
+            min_segment_length: StdDuration::from_secs(1),
+            clone_chunk_size: Some(1),
+            clone_concurrency: None,
+            thinning: Default::default(),
+            shadow: false,
+        };
+
+        let progress = std::sync::Mutex::new(vec![]);
+
+        let (destination, _segments) = super::call(
+            db.connection_pool(),
+            &metrics,
+            &edition,
+            &room,
+            job.clone(),
+            adjust_cfg,
+            |job| progress.lock().unwrap().push(job.cloned_events()),
+        )
+        .await
+        .expect("edition commit failed");
+
+        // Events with the same time go in the same chunk.
+        assert_eq!(progress.into_inner().unwrap(), vec![1, 3, 4, 4]);
+
+        let events = EventListQuery::new()
+            .room_id(destination.id())
+            .execute(&mut conn)
+            .await
+            .expect("Failed to fetch events");
+
+        let times = events.iter().map(|e| e.occurred_at()).collect::<Vec<_>>();
+        assert_eq!(
+            times,
+            vec![1_000_000_000, 2_000_000_000, 2_000_000_001, 3_000_000_000]
+        );
+
+        let job = JobFindQuery::new(job.id())
+            .execute(&mut conn)
+            .await
+            .expect("Failed to find edition commit job")
+            .expect("Edition commit job not found");
+
+        assert_eq!(job.status(), JobStatus::Succeeded);
+        assert_eq!(job.destination_room_id(), Some(destination.id()));
+        assert_eq!(job.checkpoint(), Some(3_000_000_000));
+    }
+
+    #[tokio::test]
+    async fn commit_edition_taken_over() {
+        let metrics = Metrics::new(&Registry::new()).unwrap();
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let mut conn = db.get_conn().await;
+        let room = shared_helpers::insert_room(&mut conn).await;
+
+        let edition = factory::Edition::new(room.id(), agent.agent_id())
+            .insert(&mut conn)
+            .await;
+
+        let job = JobInsertQuery::new(edition.id(), 0)
+            .execute(&mut conn)
+            .await
+            .expect("Failed to insert edition commit job");
+
+        // Another replica resumes the job.
+        sqlx::query("UPDATE edition_commit_job SET attempts = attempts + 1 WHERE id = $1")
+            .bind(job.id())
+            .execute(&mut conn)
+            .await
+            .expect("Failed to update edition commit job");
+
+        let adjust_cfg = AdjustConfig {
+            min_segment_length: StdDuration::from_secs(1),
+            clone_chunk_size: None,
+            clone_concurrency: None,
+            thinning: Default::default(),
+            shadow: false,
+        };
+
+        super::call(
+            db.connection_pool(),
+            &metrics,
+            &edition,
+            &room,
+            job.clone(),
+            adjust_cfg,
+            |_| (),
+        )
+        .await
+        .expect_err("Unexpected success on taken over edition commit");
+
+        // The job is left for the new owner.
+        let job = JobFindQuery::new(job.id())
+            .execute(&mut conn)
+            .await
+            .expect("Failed to find edition commit job")
+            .expect("Edition commit job not found");
+
+        assert_eq!(job.status(), JobStatus::Running);
+        assert_eq!(job.destination_room_id(), None);
+    }
+
     async fn create_event(
         conn: &mut PgConnection,
         room: &Room,
@@ -870,17 +1349,10 @@ mod tests {
 
         let cut_gaps = collect_gaps(&[], &[ch1, ch2, ch3, ch4]).unwrap();
 
-        let modified_segments =
-            invert_segments(&cut_gaps, room_duration, StdDuration::from_secs(1))
-                .unwrap()
-                .into_iter()
-                .map(|(start, stop)| {
-                    (
-                        Included(start / NANOSECONDS_IN_MILLISECOND),
-                        Bound::Excluded(stop / NANOSECONDS_IN_MILLISECOND),
-                    )
-                })
-                .collect::<Vec<(Bound<i64>, Bound<i64>)>>();
+        let modified_segments: Vec<(Bound<i64>, Bound<i64>)> = segments::to_millis(
+            &segments::invert(&cut_gaps, room_duration, StdDuration::from_secs(1)),
+        )
+        .into();
 
         assert_eq!(
             modified_segments,
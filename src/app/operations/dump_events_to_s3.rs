@@ -1,11 +1,15 @@
 use std::time::{Duration, Instant};
 
 use anyhow::{Context, Result};
+use http::header::CONTENT_TYPE;
 use rusoto_s3::PutObjectRequest;
-use serde_derive::Serialize;
+use serde_derive::{Deserialize, Serialize};
 use sqlx::postgres::PgPool as Db;
 use tracing::{error, info};
+use url::Url;
 
+use super::snapshot;
+use crate::config::DumpDestinationsConfig;
 use crate::db::room::Object as Room;
 use crate::{
     app::{
@@ -15,7 +19,7 @@ use crate::{
     metrics::Metrics,
 };
 use crate::{
-    db::event::{ListQuery as EventListQuery, Object as Event},
+    db::event::{DataEncryption, ListQuery as EventListQuery, Object as Event},
     metrics::QueryKey,
 };
 
@@ -23,11 +27,80 @@ use crate::{
 
 const RETRIES: u8 = 3;
 const RETRY_DELAY: Duration = Duration::from_millis(200);
+const UPLOAD_TIMEOUT: Duration = Duration::from_secs(300);
 const EVENTS_DUMP_BUCKET: &str = "eventsdump";
 
-struct S3Destination {
-    bucket: String,
-    key: String,
+/// Where to dump room events to instead of the default bucket.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Destination {
+    /// A bucket of the service's S3 storage. The object key is `{prefix}/{room_id}.{json|bin}`.
+    S3 {
+        bucket: String,
+        #[serde(default)]
+        prefix: Option<String>,
+    },
+    /// A presigned URL to upload the dump to with a `PUT` request.
+    UploadUrl { upload_url: String },
+}
+
+impl Destination {
+    /// Whether the audience's destinations from the config allow this one.
+    pub fn is_allowed(&self, allowed: &DumpDestinationsConfig) -> bool {
+        match self {
+            Self::S3 { bucket, .. } => allowed.buckets.contains(bucket),
+            Self::UploadUrl { upload_url } => match Url::parse(upload_url) {
+                Ok(url) => {
+                    url.scheme() == "https"
+                        && url
+                            .host_str()
+                            .is_some_and(|host| allowed.upload_hosts.iter().any(|h| h == host))
+                }
+                Err(_) => false,
+            },
+        }
+    }
+}
+
+/// How the dump is encoded.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Format {
+    /// `{room: Room, events: [Event]}` JSON.
+    #[default]
+    Json,
+    /// Compact binary [snapshot](super::snapshot) keeping binary encoded events as is.
+    Binary,
+}
+
+impl Format {
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Binary => "bin",
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::Binary => "application/octet-stream",
+        }
+    }
+}
+
+/// Where room events were dumped to.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Location {
+    S3Uri(String),
+    /// The upload URL without the query which carries the signature.
+    UploadUrl(String),
+}
+
+pub(super) struct S3Destination {
+    pub(super) bucket: String,
+    pub(super) key: String,
 }
 
 #[derive(Serialize)]
@@ -36,31 +109,73 @@ struct S3Content {
     events: Vec<Event>,
 }
 
-pub async fn call(db: &Db, metrics: &Metrics, s3_client: S3Client, room: &Room) -> Result<String> {
-    info!(room = ?room.id(), classroom_id = ?room.classroom_id(), "Dump events to S3 task started");
+pub async fn call(
+    db: &Db,
+    metrics: &Metrics,
+    data_encryption: Option<&DataEncryption>,
+    s3_client: Option<S3Client>,
+    room: &Room,
+    destination: Option<Destination>,
+    format: Format,
+) -> Result<Location> {
+    info!(?format, "Dump events to S3 task started");
 
     let start_timestamp = Instant::now();
 
-    let destination = s3_destination(room);
+    let events = load_room_events(db, metrics, data_encryption, room, format).await?;
+    info!(events_count = events.len(), "Loaded room events to dump");
 
-    let events = load_room_events(db, metrics, room).await?;
+    let body = serialize_content(room, events, format).await?;
 
-    let s3_uri = upload_events(s3_client, room, events, destination).await?;
+    let location = match destination {
+        Some(Destination::UploadUrl { upload_url }) => {
+            upload_events_to_url(room, body, format, &upload_url).await?;
+
+            let mut url = Url::parse(&upload_url).context("Invalid upload url")?;
+            url.set_query(None);
+            Location::UploadUrl(url.to_string())
+        }
+        destination => {
+            let s3_client = s3_client.context("No S3 client")?;
+
+            let destination = match destination {
+                Some(Destination::S3 { bucket, prefix }) => S3Destination {
+                    bucket,
+                    key: object_key(prefix.as_deref(), room, format),
+                },
+                _ => s3_destination(room, format),
+            };
+
+            let s3_uri =
+                upload_events(s3_client, room, body, format.content_type(), destination).await?;
+            Location::S3Uri(s3_uri)
+        }
+    };
 
     info!(
-        room = ?room.id(),
-        classroom_id = ?room.classroom_id(),
         duration = %start_timestamp.elapsed().as_millis(),
         "Dump events to S3 task successfully finished"
     );
 
-    Ok(s3_uri)
+    Ok(location)
 }
 
-async fn load_room_events(db: &Db, metrics: &Metrics, room: &Room) -> Result<Vec<Event>> {
+async fn load_room_events(
+    db: &Db,
+    metrics: &Metrics,
+    data_encryption: Option<&DataEncryption>,
+    room: &Room,
+    format: Format,
+) -> Result<Vec<Event>> {
     let mut conn = db.acquire().await.context("Failed to get db connection")?;
 
-    let query = EventListQuery::new().room_id(room.id());
+    let mut query = EventListQuery::new().room_id(room.id());
+
+    // Snapshots keep binary encoded events as is.
+    if format == Format::Binary {
+        query = query.with_binary_data();
+    }
+
     let events = metrics
         .measure_query(QueryKey::EventDumpQuery, query.execute(&mut conn))
         .await
@@ -72,49 +187,31 @@ async fn load_room_events(db: &Db, metrics: &Metrics, room: &Room) -> Result<Vec
             )
         })?;
 
-    Ok(events)
+    // Snapshots keep events encrypted at rest as is to be restored.
+    // Otherwise they're left out unless they are allowed to be dumped.
+    match data_encryption {
+        Some(data_encryption) if format == Format::Json => data_encryption.open_dumpable(events),
+        _ => Ok(events),
+    }
 }
 
-async fn upload_events(
+pub(super) async fn upload_events(
     s3_client: S3Client,
     room: &Room,
-    events: Vec<Event>,
+    body: Vec<u8>,
+    content_type: &str,
     destination: S3Destination,
 ) -> Result<String> {
     let S3Destination { bucket, key } = destination;
     let s3_uri = format!("s3://{bucket}/{key}");
 
-    let body = S3Content {
-        room: room.to_owned(),
-        events,
-    };
-
-    let classroom_id = room.classroom_id();
-    let body = tokio::task::spawn_blocking(move || {
-        serde_json::to_vec(&body).map_err(|e| {
-            anyhow!(
-                "Failed to serialize events, reason = {:?}, classroom_id = {}",
-                e,
-                classroom_id
-            )
-        })
-    })
-    .await
-    .map_err(|e| {
-        anyhow!(
-            "Failed to join events serialization task, reason = {:?}, classroom_id = {}",
-            e,
-            room.classroom_id()
-        )
-    })??;
-
     let mut result;
     for _ in 0..RETRIES {
         let request = PutObjectRequest {
             bucket: bucket.clone(),
             key: key.clone(),
             body: Some(body.clone().into()),
-            content_type: Some("application/json".into()),
+            content_type: Some(content_type.into()),
             ..Default::default()
         };
 
@@ -147,10 +244,105 @@ async fn upload_events(
     Ok(s3_uri)
 }
 
-fn s3_destination(room: &Room) -> S3Destination {
+async fn upload_events_to_url(
+    room: &Room,
+    body: Vec<u8>,
+    format: Format,
+    upload_url: &str,
+) -> Result<()> {
+    // Only the host of the url is checked against the allowlist so a redirect must not
+    // send the dump anywhere else.
+    let client = reqwest::Client::builder()
+        .timeout(UPLOAD_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .context("Failed to build http client")?;
+
+    let mut result = Ok(());
+    for _ in 0..RETRIES {
+        result = client
+            .put(upload_url)
+            .header(CONTENT_TYPE, format.content_type())
+            .body(body.clone())
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            // The url carries the signature so keep it out of logs.
+            .map_err(|e| {
+                anyhow!(
+                    "Failed to upload events, reason = {:?}, classroom_id = {}",
+                    e.without_url(),
+                    room.classroom_id()
+                )
+            })
+            .and_then(|response| {
+                // Redirects aren't followed so they have to fail the upload explicitly.
+                if response.status().is_redirection() {
+                    Err(anyhow!(
+                        "Failed to upload events, redirected with status = {}, classroom_id = {}",
+                        response.status(),
+                        room.classroom_id()
+                    ))
+                } else {
+                    Ok(())
+                }
+            });
+
+        match result {
+            Ok(()) => break,
+            Err(ref e) => {
+                error!(
+                    room = ?room.id(),
+                    classroom_id = ?room.classroom_id(),
+                    "Dump events to upload url errored, error = {:?}",
+                    e
+                );
+
+                tokio::time::sleep(RETRY_DELAY).await;
+            }
+        }
+    }
+
+    result
+}
+
+async fn serialize_content(room: &Room, events: Vec<Event>, format: Format) -> Result<Vec<u8>> {
+    let room = room.to_owned();
+    let classroom_id = room.classroom_id();
+
+    tokio::task::spawn_blocking(move || match format {
+        Format::Json => serde_json::to_vec(&S3Content { room, events }).map_err(|e| {
+            anyhow!(
+                "Failed to serialize events, reason = {:?}, classroom_id = {}",
+                e,
+                classroom_id
+            )
+        }),
+        Format::Binary => snapshot::encode(&room, events),
+    })
+    .await
+    .map_err(|e| {
+        anyhow!(
+            "Failed to join events serialization task, reason = {:?}, classroom_id = {}",
+            e,
+            classroom_id
+        )
+    })?
+}
+
+fn object_key(prefix: Option<&str>, room: &Room, format: Format) -> String {
+    let extension = format.extension();
+
+    match prefix.map(|p| p.trim_matches('/')) {
+        Some(prefix) if !prefix.is_empty() => format!("{}/{}.{}", prefix, room.id(), extension),
+        _ => format!("{}.{}", room.id(), extension),
+    }
+}
+
+pub(super) fn s3_destination(room: &Room, format: Format) -> S3Destination {
     S3Destination {
         bucket: format!("{EVENTS_DUMP_BUCKET}.{}.{}", room.kind(), room.audience()),
-        key: format!("{}.json", room.id()),
+        key: format!("{}.{}", room.id(), format.extension()),
     }
 }
 
@@ -209,14 +401,23 @@ mod tests {
         let mut context = TestContext::new(db, TestAuthz::new());
         context.set_s3(shared_helpers::mock_s3());
 
-        let s3_uri = super::call(
+        let location = super::call(
             context.db(),
             &context.metrics(),
-            context.s3_client().unwrap(),
+            None,
+            context.s3_client(),
             &room,
+            None,
+            Format::Json,
         )
         .await
         .expect("No failure");
+
+        let s3_uri = match location {
+            Location::S3Uri(s3_uri) => s3_uri,
+            Location::UploadUrl(_) => panic!("Unexpected upload to url"),
+        };
+
         assert_eq!(
             s3_uri,
             format!(
@@ -248,6 +449,7 @@ mod tests {
             data.clone(),
             occurred_at,
             created_by,
+            None,
         )
         .expect("Failed to create insert query")
         .created_at(opened_at + chrono::Duration::nanoseconds(occurred_at))
@@ -275,7 +477,42 @@ mod tests {
                 .await
         };
 
-        let S3Destination { bucket, .. } = s3_destination(&room);
-        assert_eq!(bucket, format!("eventsdump.p2p.{}", room.audience()))
+        let S3Destination { bucket, key } = s3_destination(&room, Format::Json);
+        assert_eq!(bucket, format!("eventsdump.p2p.{}", room.audience()));
+        assert_eq!(key, format!("{}.json", room.id()));
+
+        let S3Destination { key, .. } = s3_destination(&room, Format::Binary);
+        assert_eq!(key, format!("{}.bin", room.id()));
+    }
+
+    #[test]
+    fn destination_allowed() {
+        let allowed = DumpDestinationsConfig {
+            buckets: vec!["tenant-exports".to_owned()],
+            upload_hosts: vec!["storage.example.org".to_owned()],
+        };
+
+        let check = |destination: JsonValue| {
+            serde_json::from_value::<Destination>(destination)
+                .expect("Failed to parse destination")
+                .is_allowed(&allowed)
+        };
+
+        assert!(check(
+            json!({ "bucket": "tenant-exports", "prefix": "rooms" })
+        ));
+        assert!(!check(
+            json!({ "bucket": "eventsdump.webinar.example.org" })
+        ));
+        assert!(check(
+            json!({ "upload_url": "https://storage.example.org/dump.json?signature=abc" })
+        ));
+        assert!(!check(
+            json!({ "upload_url": "http://storage.example.org/dump.json" })
+        ));
+        assert!(!check(
+            json!({ "upload_url": "https://evil.example.org/dump.json" })
+        ));
+        assert!(!check(json!({ "upload_url": "not a url" })));
     }
 }
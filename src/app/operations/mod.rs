@@ -1,11 +1,31 @@
+pub use account_events::erase as erase_account_events;
+pub use account_events::export as export_account_events;
+
 pub use adjust_room::call as adjust_room;
-pub use adjust_room::AdjustOutput;
+pub use adjust_room::preview as adjust_room_preview;
+pub use adjust_room::{AdjustOutput, AdjustPreview};
+
+pub use backfill_set_heads::call as backfill_set_heads;
 
 pub use commit_edition::call as commit_edition;
 pub use dump_events_to_s3::call as dump_events_to_s3;
+pub use dump_events_to_s3::{
+    Destination as DumpDestination, Format as DumpFormat, Location as DumpLocation,
+};
+pub use reencode_draw_events::call as reencode_draw_events;
+pub use room_lock::RoomOperationLock;
+pub use transcript::export as export_transcript;
 pub use vacuum::call as vacuum;
+pub use vacuum::dry_run as vacuum_dry_run;
 
+mod account_events;
 mod adjust_room;
+mod backfill_set_heads;
 mod commit_edition;
 mod dump_events_to_s3;
+mod reencode_draw_events;
+mod room_lock;
+pub mod segments;
+pub mod snapshot;
+mod transcript;
 mod vacuum;
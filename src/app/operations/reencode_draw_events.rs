@@ -0,0 +1,230 @@
+use std::time::Duration as StdDuration;
+
+use anyhow::{Context, Result};
+use serde_derive::Serialize;
+use sqlx::postgres::PgPool as Db;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::{
+    db::event::{CompactEvent, LegacyDrawListQuery, PostcardBin, ReencodeQuery},
+    metrics::{Metrics, QueryKey},
+};
+
+const BATCH_SIZE: i64 = 500;
+/// Pause between batches not to load the database along with live traffic.
+const BATCH_INTERVAL: StdDuration = StdDuration::from_millis(100);
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ReencodeStats {
+    pub scanned: u64,
+    pub converted: u64,
+    /// Events which `data` doesn't fit the binary format. They are left as is.
+    pub failed: u64,
+    /// Size of JSON `data` of converted events.
+    pub json_bytes: u64,
+    /// Size of `binary_data` they were converted to.
+    pub binary_bytes: u64,
+    /// The last scanned event to resume the job after.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_id: Option<Uuid>,
+}
+
+impl ReencodeStats {
+    pub fn saved_bytes(&self) -> i64 {
+        self.json_bytes as i64 - self.binary_bytes as i64
+    }
+}
+
+/// Converts `draw` events still stored as JSON `data` to `binary_data` in batches.
+///
+/// Scanning goes in `id` order starting after `after_id` so an interrupted job may be resumed
+/// from the last reported id. Rerunning from the start is safe too since converted events
+/// don't get scanned again. `report` is called with the running totals after each batch.
+pub async fn call(
+    db: &Db,
+    metrics: &Metrics,
+    after_id: Option<Uuid>,
+    report: impl Fn(&ReencodeStats),
+) -> Result<ReencodeStats> {
+    let mut stats = ReencodeStats {
+        last_id: after_id,
+        ..Default::default()
+    };
+
+    loop {
+        let mut conn = db
+            .acquire()
+            .await
+            .context("Failed to acquire db connection")?;
+
+        let query = LegacyDrawListQuery::new(stats.last_id, BATCH_SIZE);
+
+        let events = metrics
+            .measure_query(QueryKey::EventLegacyDrawListQuery, query.execute(&mut conn))
+            .await
+            .context("Failed to list legacy draw events")?;
+
+        let mut query = ReencodeQuery::new();
+
+        for event in &events {
+            let binary_data = CompactEvent::from_json(event.data.clone())
+                .map_err(anyhow::Error::from)
+                .and_then(|e| {
+                    PostcardBin::new(e)
+                        .into_bytes()
+                        .map_err(anyhow::Error::from)
+                });
+
+            match binary_data {
+                Ok(binary_data) => {
+                    stats.json_bytes += event.data.to_string().len() as u64;
+                    stats.binary_bytes += binary_data.len() as u64;
+                    query.push(event.id, binary_data);
+                }
+                Err(err) => {
+                    warn!(event_id = %event.id, "Failed to convert draw event: {:?}", err);
+                    stats.failed += 1;
+                }
+            }
+        }
+
+        if !query.is_empty() {
+            stats.converted += metrics
+                .measure_query(QueryKey::EventReencodeQuery, query.execute(&mut conn))
+                .await
+                .context("Failed to store binary draw events")?;
+        }
+
+        stats.scanned += events.len() as u64;
+
+        if let Some(event) = events.last() {
+            stats.last_id = Some(event.id);
+        }
+
+        report(&stats);
+
+        if (events.len() as i64) < BATCH_SIZE {
+            break;
+        }
+
+        drop(conn);
+        tokio::time::sleep(BATCH_INTERVAL).await;
+    }
+
+    info!(
+        "Draw events re-encoding finished, scanned = {}, converted = {}, failed = {}, saved bytes = {}",
+        stats.scanned,
+        stats.converted,
+        stats.failed,
+        stats.saved_bytes(),
+    );
+
+    Ok(stats)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use prometheus::Registry;
+    use serde_json::json;
+
+    use super::*;
+    use crate::db::event::{InsertQuery, ListQuery};
+    use crate::test_helpers::prelude::*;
+
+    #[tokio::test]
+    async fn reencode_legacy_draw_events() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let metrics = Metrics::new(&Registry::new()).unwrap();
+
+        let draw_data: serde_json::Value = serde_json::from_str(
+            r#"{"rx": 0, "ry": 0, "_id": "c900cf81-8af6-4eb2-82fe-4be2b2eab5b9", "top": 265.76, "fill": "rgba(255,255,255,1)", "left": 440.13, "type": "rect", "angle": 0, "flipX": false, "flipY": false, "skewX": 0, "skewY": 0, "width": 398.96, "_order": -1, "height": 133.43, "scaleX": 1, "scaleY": 1, "shadow": null, "stroke": "rgba(255,255,255,1)", "opacity": 1, "originX": "left", "originY": "top", "version": "4.6.0", "visible": true, "fillRule": "nonzero", "_noHistory": null, "paintFirst": "fill", "strokeWidth": 2, "noScaleCache": false, "_lockedbyuser": null, "strokeLineCap": "butt", "strokeUniform": true, "_drawByStretch": true, "strokeLineJoin": "miter", "backgroundColor": "", "strokeDashArray": null, "strokeDashOffset": 0, "strokeMiterLimit": 4, "globalCompositeOperation": "source-over"}"#,
+        )
+        .unwrap();
+
+        let (room_id, legacy_event, encrypted_event) = {
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+
+            // Insert draw events the way it was done before the binary format.
+            let legacy_event = InsertQuery::new_encrypted(
+                room.id(),
+                "draw".to_owned(),
+                draw_data.clone(),
+                1000,
+                agent.agent_id().to_owned(),
+                None,
+                None,
+            )
+            .expect("Failed to build event insert query")
+            .execute(&mut conn)
+            .await
+            .expect("Failed to insert event");
+
+            sqlx::query("UPDATE event SET content_encrypted = false WHERE id = $1")
+                .bind(legacy_event.id())
+                .execute(&mut conn)
+                .await
+                .expect("Failed to update event");
+
+            let encrypted_event = InsertQuery::new_encrypted(
+                room.id(),
+                "draw".to_owned(),
+                json!({ "ciphertext": "abc" }),
+                2000,
+                agent.agent_id().to_owned(),
+                None,
+                None,
+            )
+            .expect("Failed to build event insert query")
+            .execute(&mut conn)
+            .await
+            .expect("Failed to insert event");
+
+            (room.id(), legacy_event, encrypted_event)
+        };
+
+        let stats = call(db.connection_pool(), &metrics, None, |_| ())
+            .await
+            .expect("Re-encoding failed");
+
+        assert_eq!(stats.scanned, 1);
+        assert_eq!(stats.converted, 1);
+        assert_eq!(stats.failed, 0);
+        assert!(stats.saved_bytes() > 0);
+        assert_eq!(stats.last_id, Some(legacy_event.id()));
+
+        let mut conn = db.get_conn().await;
+
+        let events = ListQuery::new()
+            .room_id(room_id)
+            .with_binary_data()
+            .execute(&mut conn)
+            .await
+            .expect("Failed to list events");
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].id(), legacy_event.id());
+        let binary_data = events[0].binary_data().expect("Missing binary data");
+        // The binary format normalizes the shape so compare it with the same round trip.
+        let expected = CompactEvent::from_json(draw_data)
+            .expect("Failed to compact draw data")
+            .into_json()
+            .expect("Failed to expand draw data");
+        assert_eq!(binary_data.decode(), expected);
+
+        // Encrypted events are opaque.
+        assert_eq!(events[1].id(), encrypted_event.id());
+        assert!(events[1].binary_data().is_none());
+
+        // Nothing is left to convert on the next run.
+        let stats = call(db.connection_pool(), &metrics, None, |_| ())
+            .await
+            .expect("Re-encoding failed");
+
+        assert_eq!(stats.scanned, 0);
+    }
+}
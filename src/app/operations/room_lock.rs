@@ -0,0 +1,137 @@
+use anyhow::{Context, Result};
+use sqlx::postgres::{PgConnection, PgPool as Db};
+use sqlx::Connection;
+use uuid::Uuid;
+
+use crate::db::advisory_lock::{TryLockQuery, UnlockQuery};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Exclusive lock of a source room for long running operations like adjustment
+/// or edition commit so they never derive rooms from it concurrently.
+///
+/// It's a session-level advisory lock held on a connection detached from the pool
+/// so it gets released along with the connection even if the operation panics.
+pub struct RoomOperationLock {
+    conn: PgConnection,
+}
+
+impl RoomOperationLock {
+    /// Takes the lock unless another operation on the room is in progress on any replica.
+    pub async fn try_acquire(db: &Db, room_id: Uuid) -> Result<Option<Self>> {
+        let mut conn = db
+            .acquire()
+            .await
+            .context("Failed to acquire db connection")?
+            .detach();
+
+        let locked = TryLockQuery::new(lock_key(room_id))
+            .execute(&mut conn)
+            .await
+            .context("Failed to take room operation lock")?;
+
+        Ok(locked.then_some(Self { conn }))
+    }
+
+    /// Checks whether an operation on the room is in progress on any replica.
+    pub async fn is_held(db: &Db, room_id: Uuid) -> Result<bool> {
+        let mut conn = db
+            .acquire()
+            .await
+            .context("Failed to acquire db connection")?;
+
+        let key = lock_key(room_id);
+
+        let locked = TryLockQuery::new(key)
+            .execute(&mut conn)
+            .await
+            .context("Failed to take room operation lock")?;
+
+        if locked {
+            UnlockQuery::new(key)
+                .execute(&mut conn)
+                .await
+                .context("Failed to release room operation lock")?;
+        }
+
+        Ok(!locked)
+    }
+
+    pub async fn release(self) -> Result<()> {
+        self.conn
+            .close()
+            .await
+            .context("Failed to release room operation lock")
+    }
+}
+
+/// Advisory lock keys are shared with the service's other locks which have ASCII keys,
+/// the random high half of the room id hardly collides with them.
+fn lock_key(room_id: Uuid) -> i64 {
+    room_id.as_u64_pair().0 as i64
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::db::TestDb;
+
+    #[tokio::test]
+    async fn lock_is_exclusive_per_room() {
+        let db = TestDb::new().await;
+        let pool = db.connection_pool();
+        let room_id = Uuid::new_v4();
+
+        assert!(!RoomOperationLock::is_held(pool, room_id).await.unwrap());
+
+        let lock = RoomOperationLock::try_acquire(pool, room_id)
+            .await
+            .unwrap()
+            .expect("Failed to lock room");
+
+        assert!(RoomOperationLock::is_held(pool, room_id).await.unwrap());
+
+        let second_lock = RoomOperationLock::try_acquire(pool, room_id).await.unwrap();
+        assert!(second_lock.is_none());
+
+        // Other rooms are not affected.
+        let other_lock = RoomOperationLock::try_acquire(pool, Uuid::new_v4())
+            .await
+            .unwrap();
+
+        assert!(other_lock.is_some());
+
+        lock.release().await.unwrap();
+        assert!(!RoomOperationLock::is_held(pool, room_id).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn lock_is_released_on_drop() {
+        let db = TestDb::new().await;
+        let pool = db.connection_pool();
+        let room_id = Uuid::new_v4();
+
+        let lock = RoomOperationLock::try_acquire(pool, room_id)
+            .await
+            .unwrap()
+            .expect("Failed to lock room");
+
+        drop(lock);
+
+        // The server releases the lock as soon as it notices the closed connection.
+        let mut released = false;
+
+        for _ in 0..50 {
+            if !RoomOperationLock::is_held(pool, room_id).await.unwrap() {
+                released = true;
+                break;
+            }
+
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+
+        assert!(released);
+    }
+}
@@ -0,0 +1,305 @@
+//! Segment math shared by room adjustment and edition commit.
+//!
+//! A segment is a half-open `[start, stop)` range of a room's timeline.
+//! Unless stated otherwise functions expect segment lists sorted by start
+//! without overlaps and return lists of the same shape.
+
+use std::cmp;
+use std::ops::Bound;
+use std::time::Duration as StdDuration;
+
+use anyhow::{bail, Result};
+use chrono::Duration;
+
+use crate::db::adjustment::Segments;
+
+pub const NANOSECONDS_IN_MILLISECOND: i64 = 1_000_000;
+
+pub type Segment = (i64, i64);
+
+/// Parses `[start, stop)` millisecond segments.
+pub fn parse(segments: &Segments) -> Result<Vec<Segment>> {
+    let bounded_offset_tuples: Vec<(Bound<i64>, Bound<i64>)> = segments.to_owned().into();
+    let mut parsed_segments = Vec::with_capacity(bounded_offset_tuples.len());
+
+    for segment in bounded_offset_tuples {
+        match segment {
+            (Bound::Included(start), Bound::Excluded(stop)) => parsed_segments.push((start, stop)),
+            segment => bail!("Invalid segment: {:?}", segment),
+        }
+    }
+
+    Ok(parsed_segments)
+}
+
+/// Converts nanosecond segments to millisecond `Segments` truncating negative starts to zero.
+pub fn to_millis(segments: &[Segment]) -> Segments {
+    segments
+        .iter()
+        .map(|(start, stop)| {
+            (
+                Bound::Included(cmp::max(start / NANOSECONDS_IN_MILLISECOND, 0)),
+                Bound::Excluded(stop / NANOSECONDS_IN_MILLISECOND),
+            )
+        })
+        .collect::<Vec<(Bound<i64>, Bound<i64>)>>()
+        .into()
+}
+
+/// Shifts each segment by `delta`.
+pub fn offset(segments: &[Segment], delta: i64) -> Vec<Segment> {
+    segments
+        .iter()
+        .map(|(start, stop)| (start + delta, stop + delta))
+        .collect()
+}
+
+/// Multiplies segment bounds by `factor`, e.g. to turn milliseconds into nanoseconds.
+pub fn scale(segments: &[Segment], factor: i64) -> Vec<Segment> {
+    segments
+        .iter()
+        .map(|(start, stop)| (start * factor, stop * factor))
+        .collect()
+}
+
+/// Total length of the segments.
+pub fn total_length(segments: &[Segment]) -> i64 {
+    segments.iter().map(|(start, stop)| stop - start).sum()
+}
+
+/// Calculates the union of two segment lists merging overlapping and adjacent segments.
+#[allow(dead_code)]
+pub fn union(a: &[Segment], b: &[Segment]) -> Vec<Segment> {
+    let mut all = a.iter().chain(b).copied().collect::<Vec<_>>();
+    all.sort_unstable();
+
+    let mut result: Vec<Segment> = Vec::with_capacity(all.len());
+
+    for (start, stop) in all {
+        if start >= stop {
+            continue;
+        }
+
+        match result.last_mut() {
+            Some((_, last_stop)) if start <= *last_stop => {
+                *last_stop = cmp::max(*last_stop, stop);
+            }
+            _ => result.push((start, stop)),
+        }
+    }
+
+    result
+}
+
+/// Calculates the intersection of two segment lists.
+pub fn intersect(a: &[Segment], b: &[Segment]) -> Vec<Segment> {
+    let mut a = a.iter();
+    let mut b = b.iter();
+    let mut a_state = a.next();
+    let mut b_state = b.next();
+
+    let mut result = vec![];
+
+    while let (Some((a1, a2)), Some((b1, b2))) = (a_state, b_state) {
+        let start = cmp::max(*a1, *b1);
+        let stop = cmp::min(*a2, *b2);
+
+        if start < stop {
+            result.push((start, stop));
+        }
+
+        if a2 < b2 {
+            a_state = a.next();
+        } else {
+            b_state = b.next();
+        }
+    }
+
+    result
+}
+
+/// Turns `segments` into gaps within `[0, duration)`.
+///
+/// The trailing gap is omitted unless it's longer than `min_segment_length`.
+pub fn invert(
+    segments: &[Segment],
+    duration: Duration,
+    min_segment_length: StdDuration,
+) -> Vec<Segment> {
+    let duration_nanos = duration.num_nanoseconds().unwrap_or(i64::MAX);
+
+    if segments.is_empty() {
+        return vec![(0, duration_nanos)];
+    }
+
+    let mut gaps = Vec::with_capacity(segments.len() + 1);
+
+    // A possible gap before the first segment.
+    if let Some((first_segment_start, _)) = segments.first() {
+        if *first_segment_start > 0 {
+            gaps.push((0, *first_segment_start));
+        }
+    }
+
+    // Gaps between segments, adjacent segments have none.
+    for ((_, segment_stop), (next_segment_start, _)) in segments.iter().zip(&segments[1..]) {
+        if segment_stop < next_segment_start {
+            gaps.push((*segment_stop, *next_segment_start));
+        }
+    }
+
+    // A possible gap after the last segment.
+    if let Some((_, last_segment_stop)) = segments.last() {
+        // Don't create segments less than `min_segment_length`
+        if *last_segment_stop < duration_nanos
+            && StdDuration::from_nanos((duration_nanos - last_segment_stop) as u64)
+                .gt(&min_segment_length)
+        {
+            gaps.push((*last_segment_stop, duration_nanos));
+        }
+    }
+
+    gaps
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn contains(segments: &[Segment], point: i64) -> bool {
+        segments
+            .iter()
+            .any(|(start, stop)| *start <= point && point < *stop)
+    }
+
+    fn is_normalized(segments: &[Segment]) -> bool {
+        segments.iter().all(|(start, stop)| start < stop)
+            && segments.windows(2).all(|w| w[0].1 <= w[1].0)
+    }
+
+    // Bounds of every segment and their neighbours which is where off-by-one errors show up.
+    fn probes(lists: &[&[Segment]]) -> Vec<i64> {
+        lists
+            .iter()
+            .flat_map(|segments| segments.iter())
+            .flat_map(|(start, stop)| [start - 1, *start, start + 1, stop - 1, *stop, stop + 1])
+            .collect()
+    }
+
+    prop_compose! {
+        fn segments()(parts in prop::collection::vec((0..100i64, 1..100i64), 0..16)) -> Vec<Segment> {
+            let mut position = 0;
+
+            parts
+                .into_iter()
+                .map(|(gap, length)| {
+                    let start = position + gap;
+                    position = start + length;
+                    (start, position)
+                })
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_intersect() {
+        assert_eq!(intersect(&[(0, 1)], &[(0, 3)]), vec![(0, 1)]);
+        assert_eq!(intersect(&[(0, 1)], &[(2, 3)]), vec![]);
+        assert_eq!(
+            intersect(&[(0, 3), (6, 8)], &[(1, 7)]),
+            vec![(1, 3), (6, 7)]
+        );
+        assert_eq!(intersect(&[(0, 3), (6, 8)], &[]), vec![]);
+        assert_eq!(intersect(&[(0, 3), (6, 8)], &[(7, 10)]), vec![(7, 8)]);
+        assert_eq!(intersect(&[(0, 3)], &[(3, 5)]), vec![]);
+    }
+
+    #[test]
+    fn test_union() {
+        assert_eq!(union(&[(0, 1)], &[(1, 3)]), vec![(0, 3)]);
+        assert_eq!(union(&[(0, 3), (6, 8)], &[(1, 7)]), vec![(0, 8)]);
+        assert_eq!(union(&[(4, 5)], &[(0, 2)]), vec![(0, 2), (4, 5)]);
+        assert_eq!(union(&[], &[]), vec![]);
+    }
+
+    #[test]
+    fn test_invert() {
+        let duration = Duration::nanoseconds(10);
+        let min = StdDuration::from_nanos(1);
+
+        assert_eq!(invert(&[], duration, min), vec![(0, 10)]);
+        assert_eq!(
+            invert(&[(2, 4), (6, 8)], duration, min),
+            vec![(0, 2), (4, 6), (8, 10)]
+        );
+        assert_eq!(invert(&[(0, 4), (4, 9)], duration, min), vec![]);
+        assert_eq!(
+            invert(&[(0, 4)], duration, StdDuration::from_nanos(6)),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_to_millis() {
+        let segments = to_millis(&[(-5, 2_500_000), (3_000_000, 4_000_000)]);
+        let segments: Vec<(Bound<i64>, Bound<i64>)> = segments.into();
+
+        assert_eq!(
+            segments,
+            vec![
+                (Bound::Included(0), Bound::Excluded(2)),
+                (Bound::Included(3), Bound::Excluded(4)),
+            ]
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn intersect_contains_points_of_both(a in segments(), b in segments()) {
+            let result = intersect(&a, &b);
+            prop_assert!(is_normalized(&result));
+            prop_assert_eq!(&result, &intersect(&b, &a));
+
+            for point in probes(&[&a, &b]) {
+                prop_assert_eq!(contains(&result, point), contains(&a, point) && contains(&b, point));
+            }
+        }
+
+        #[test]
+        fn union_contains_points_of_either(a in segments(), b in segments()) {
+            let result = union(&a, &b);
+            prop_assert!(is_normalized(&result));
+            prop_assert!(result.windows(2).all(|w| w[0].1 < w[1].0));
+            prop_assert_eq!(&result, &union(&b, &a));
+
+            for point in probes(&[&a, &b]) {
+                prop_assert_eq!(contains(&result, point), contains(&a, point) || contains(&b, point));
+            }
+        }
+
+        #[test]
+        fn invert_complements_segments(a in segments(), tail in 1..100i64) {
+            let end = a.last().map(|(_, stop)| *stop).unwrap_or(0) + tail;
+            let gaps = invert(&a, Duration::nanoseconds(end), StdDuration::ZERO);
+            prop_assert!(is_normalized(&gaps));
+            prop_assert!(intersect(&a, &gaps).is_empty());
+            prop_assert_eq!(total_length(&a) + total_length(&gaps), end);
+            prop_assert_eq!(union(&a, &gaps), vec![(0, end)]);
+        }
+
+        #[test]
+        fn offset_commutes(a in segments(), b in segments(), delta in -1000..1000i64) {
+            prop_assert_eq!(offset(&offset(&a, delta), -delta), a.clone());
+            prop_assert_eq!(
+                intersect(&offset(&a, delta), &offset(&b, delta)),
+                offset(&intersect(&a, &b), delta)
+            );
+            prop_assert_eq!(
+                union(&offset(&a, delta), &offset(&b, delta)),
+                offset(&union(&a, &b), delta)
+            );
+        }
+    }
+}
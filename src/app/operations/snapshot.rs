@@ -0,0 +1,91 @@
+//! Binary room snapshots produced by `room.dump_events` and consumed by `room.restore`.
+//!
+//! A snapshot is the `EVSNAP` magic followed by postcard encoded manifest and then
+//! postcard encoded list of events. The manifest goes first so that its schema version
+//! is checked before decoding the events.
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use serde_derive::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::db::event::{Object as Event, SnapshotEvent};
+use crate::db::room::Object as Room;
+
+const MAGIC: &[u8] = b"EVSNAP";
+
+/// Bumped on incompatible changes of the manifest or the events layout.
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    pub schema_version: u32,
+    pub room: RoomMeta,
+    pub events_count: u64,
+    /// Milliseconds.
+    pub created_at: i64,
+}
+
+/// The room the snapshot was made of.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RoomMeta {
+    pub id: Uuid,
+    pub audience: String,
+    pub classroom_id: Uuid,
+    pub kind: String,
+    /// JSON text of the room's tags.
+    pub tags: Option<String>,
+}
+
+pub fn encode(room: &Room, events: Vec<Event>) -> Result<Vec<u8>> {
+    let events = events
+        .into_iter()
+        .map(SnapshotEvent::new)
+        .collect::<Result<Vec<_>>>()?;
+
+    let manifest = Manifest {
+        schema_version: SCHEMA_VERSION,
+        room: RoomMeta {
+            id: room.id(),
+            audience: room.audience().to_owned(),
+            classroom_id: room.classroom_id(),
+            kind: room.kind().to_string(),
+            tags: room.tags().map(|tags| tags.to_string()),
+        },
+        events_count: events.len() as u64,
+        created_at: Utc::now().timestamp_millis(),
+    };
+
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend(postcard::to_allocvec(&manifest).context("Failed to encode manifest")?);
+    bytes.extend(postcard::to_allocvec(&events).context("Failed to encode events")?);
+    Ok(bytes)
+}
+
+pub fn decode(bytes: &[u8]) -> Result<(Manifest, Vec<SnapshotEvent>)> {
+    let bytes = bytes.strip_prefix(MAGIC).context("Not a room snapshot")?;
+
+    let (manifest, bytes) =
+        postcard::take_from_bytes::<Manifest>(bytes).context("Failed to decode manifest")?;
+
+    if manifest.schema_version != SCHEMA_VERSION {
+        bail!(
+            "Unsupported snapshot schema version = {}, expected {}",
+            manifest.schema_version,
+            SCHEMA_VERSION
+        );
+    }
+
+    let events =
+        postcard::from_bytes::<Vec<SnapshotEvent>>(bytes).context("Failed to decode events")?;
+
+    if events.len() as u64 != manifest.events_count {
+        bail!(
+            "Snapshot has {} events while its manifest says {}",
+            events.len(),
+            manifest.events_count
+        );
+    }
+
+    Ok((manifest, events))
+}
@@ -0,0 +1,82 @@
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+use serde_derive::Serialize;
+use sqlx::postgres::PgPool as Db;
+use tracing::info;
+
+use super::dump_events_to_s3::{s3_destination, upload_events, Format, S3Destination};
+use crate::app::s3_client::S3Client;
+use crate::db::event::{DataEncryption, TranscriptEntry, TranscriptQuery};
+use crate::db::room::Object as Room;
+use crate::metrics::{Metrics, QueryKey};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Number of messages loaded per query.
+const BATCH_SIZE: i64 = 1000;
+
+#[derive(Serialize)]
+struct S3Content<'a> {
+    room: &'a Room,
+    messages: Vec<TranscriptEntry>,
+}
+
+/// Exports the whole transcript of the room to the default dump bucket next to its events dump
+/// and returns the S3 URI.
+pub async fn export(
+    db: &Db,
+    metrics: &Metrics,
+    data_encryption: Option<&DataEncryption>,
+    s3_client: S3Client,
+    room: &Room,
+) -> Result<String> {
+    info!("Transcript export task started");
+
+    let start_timestamp = Instant::now();
+    let mut conn = db.acquire().await.context("Failed to get db connection")?;
+    let mut messages: Vec<TranscriptEntry> = vec![];
+
+    loop {
+        let mut query = TranscriptQuery::new(room.id(), BATCH_SIZE);
+
+        if let Some(last) = messages.last() {
+            query = query.after(last.occurred_at());
+        }
+
+        if let Some(data_encryption) = data_encryption {
+            query = query.decrypt(data_encryption);
+        }
+
+        let batch = metrics
+            .measure_query(QueryKey::EventTranscriptQuery, query.execute(&mut conn))
+            .await
+            .with_context(|| format!("Failed to load transcript, room_id = '{}'", room.id()))?;
+
+        let is_last = (batch.len() as i64) < BATCH_SIZE;
+        messages.extend(batch);
+
+        if is_last {
+            break;
+        }
+    }
+
+    info!(messages_count = messages.len(), "Loaded room transcript");
+
+    let body = serde_json::to_vec(&S3Content { room, messages })
+        .context("Failed to serialize transcript")?;
+
+    let destination = S3Destination {
+        key: format!("{}.transcript.json", room.id()),
+        ..s3_destination(room, Format::Json)
+    };
+
+    let s3_uri = upload_events(s3_client, room, body, "application/json", destination).await?;
+
+    info!(
+        duration = %start_timestamp.elapsed().as_millis(),
+        "Transcript export task successfully finished"
+    );
+
+    Ok(s3_uri)
+}
@@ -1,28 +1,77 @@
 use anyhow::{Context, Result};
 use sqlx::postgres::PgPool as Db;
+use tracing::info;
 
 use crate::{
     config::VacuumConfig,
+    db::event::{VacuumCountQuery, VacuumQuery, VacuumStats},
     metrics::{Metrics, QueryKey},
 };
 
-pub async fn call(db: &Db, metrics: &Metrics, config: &VacuumConfig) -> Result<()> {
+/// Deletes events in batches of `batch_size` sleeping `batch_interval` in between
+/// not to keep the table locked for long.
+pub async fn call(db: &Db, metrics: &Metrics, config: &VacuumConfig) -> Result<VacuumStats> {
+    let mut total = VacuumStats::default();
+
+    loop {
+        let stats = {
+            let mut conn = db
+                .acquire()
+                .await
+                .context("Failed to acquire db connection")?;
+
+            let query = VacuumQuery::new(
+                config.max_history_size,
+                config.max_history_lifetime,
+                config.max_deleted_lifetime,
+                config.batch_size,
+            );
+
+            metrics
+                .measure_query(QueryKey::EventVacuumQuery, query.execute(&mut conn))
+                .await?
+        };
+
+        metrics.observe_vacuum(&stats);
+        total += stats;
+
+        if (stats.total() as usize) < config.batch_size {
+            break;
+        }
+
+        tokio::time::sleep(config.batch_interval).await;
+    }
+
+    info!(
+        "Vacuum finished, deleted = {}, too deep history = {}, too old history = {}, too old deleted labels = {}, expired by retention = {}",
+        total.total(),
+        total.too_deep_history,
+        total.too_old_history,
+        total.too_old_deleted_labels,
+        total.expired_by_retention,
+    );
+
+    Ok(total)
+}
+
+/// Counts events that would be deleted by vacuum.
+pub async fn dry_run(db: &Db, metrics: &Metrics, config: &VacuumConfig) -> Result<VacuumStats> {
     let mut conn = db
         .acquire()
         .await
         .context("Failed to acquire db connection")?;
 
-    let query = crate::db::event::VacuumQuery::new(
+    let query = VacuumCountQuery::new(
         config.max_history_size,
         config.max_history_lifetime,
         config.max_deleted_lifetime,
     );
 
-    metrics
-        .measure_query(QueryKey::EventVacuumQuery, query.execute(&mut conn))
+    let stats = metrics
+        .measure_query(QueryKey::EventVacuumCountQuery, query.execute(&mut conn))
         .await?;
 
-    Ok(())
+    Ok(stats)
 }
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -40,6 +89,9 @@ mod tests {
 
     use crate::config::VacuumConfig;
     use crate::db::event::{ListQuery as EventListQuery, Object as Event};
+    use crate::db::retention_rule::{
+        InsertQuery as RetentionRuleInsertQuery, Scope as RetentionScope,
+    };
     use crate::db::room::{ClassType, Object as Room};
     use crate::metrics::Metrics;
     use crate::test_helpers::prelude::*;
@@ -168,6 +220,153 @@ mod tests {
         assert!(r4_event_ids.contains(&r4e2.id()));
     }
 
+    #[tokio::test]
+    #[serial]
+    async fn vacuum_in_batches() {
+        let config: VacuumConfig = serde_json::from_value(json!({
+            "max_history_size": 2,
+            "max_history_lifetime": 1_000_000,
+            "max_deleted_lifetime": 1_000_000,
+            "batch_size": 2,
+            "batch_interval": "1ms",
+        }))
+        .expect("Failed to parse vacuum config");
+
+        let metrics = Metrics::new(&Registry::new()).unwrap();
+        let db = TestDb::new().await;
+
+        let mut conn = db.get_conn().await;
+        let room = insert_room(&mut conn, false).await;
+
+        for minutes_ago in 1..=7 {
+            insert_event(&mut conn, &room, minutes_ago).await;
+        }
+
+        drop(conn);
+
+        // Dry run counts without deleting.
+        // Other rooms in the database may contribute to the counters.
+        let stats = super::dry_run(db.connection_pool(), &metrics, &config)
+            .await
+            .expect("Vacuum dry run failed");
+
+        assert!(stats.too_deep_history >= 5);
+
+        let mut conn = db.get_conn().await;
+        assert_eq!(fetch_room_event_ids(&mut conn, &room).await.len(), 7);
+        drop(conn);
+
+        // Vacuum deletes the history in several batches.
+        let stats = super::call(db.connection_pool(), &metrics, &config)
+            .await
+            .expect("Vacuum failed");
+
+        assert!(stats.too_deep_history >= 5);
+
+        let mut conn = db.get_conn().await;
+        assert_eq!(fetch_room_event_ids(&mut conn, &room).await.len(), 2);
+
+        let deleted = metrics
+            .vacuum_deleted_events
+            .with_label_values(&["too_deep_history"])
+            .get();
+
+        assert!(deleted >= 5);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn vacuum_by_retention_rules() {
+        let config: VacuumConfig = serde_json::from_value(json!({
+            "max_history_size": 100,
+            "max_history_lifetime": 1_000_000,
+            "max_deleted_lifetime": 1_000_000,
+        }))
+        .expect("Failed to parse vacuum config");
+
+        let metrics = Metrics::new(&Registry::new()).unwrap();
+        let db = TestDb::new().await;
+        let mut conn = db.get_conn().await;
+
+        // Use a separate audience not to apply its rules to other tests.
+        let audience = format!("{}.example.org", Uuid::new_v4());
+        let now = Utc::now().trunc_subsecs(0);
+
+        let room1 = factory::Room::new(Uuid::new_v4(), ClassType::Webinar)
+            .audience(&audience)
+            .time((Bound::Included(now), Bound::Unbounded))
+            .insert(&mut conn)
+            .await;
+
+        let room2 = factory::Room::new(Uuid::new_v4(), ClassType::Webinar)
+            .audience(&audience)
+            .time((Bound::Included(now), Bound::Unbounded))
+            .insert(&mut conn)
+            .await;
+
+        // Keep messages for 5 days in the audience but for 20 days in the first room.
+        RetentionRuleInsertQuery::new(
+            RetentionScope::Audience(audience.clone()),
+            "message".to_owned(),
+            5 * 86400,
+        )
+        .execute(&mut conn)
+        .await
+        .expect("Failed to insert retention rule");
+
+        RetentionRuleInsertQuery::new(
+            RetentionScope::Room(room1.id()),
+            "message".to_owned(),
+            20 * 86400,
+        )
+        .execute(&mut conn)
+        .await
+        .expect("Failed to insert retention rule");
+
+        let r1_message = insert_kind_event(&mut conn, &room1, "message", 10).await;
+        let r2_message = insert_kind_event(&mut conn, &room2, "message", 10).await;
+        let r2_recent_message = insert_kind_event(&mut conn, &room2, "message", 1).await;
+        let r2_reaction = insert_kind_event(&mut conn, &room2, "reaction", 10).await;
+        drop(conn);
+
+        let stats = super::call(db.connection_pool(), &metrics, &config)
+            .await
+            .expect("Vacuum failed");
+
+        assert!(stats.expired_by_retention >= 1);
+
+        let mut conn = db.get_conn().await;
+
+        let r1_event_ids = fetch_room_event_ids(&mut conn, &room1).await;
+        assert!(r1_event_ids.contains(&r1_message.id()));
+
+        let r2_event_ids = fetch_room_event_ids(&mut conn, &room2).await;
+        assert!(!r2_event_ids.contains(&r2_message.id()));
+        assert!(r2_event_ids.contains(&r2_recent_message.id()));
+        assert!(r2_event_ids.contains(&r2_reaction.id()));
+    }
+
+    async fn insert_kind_event(
+        conn: &mut PgConnection,
+        room: &Room,
+        kind: &str,
+        days_ago: i64,
+    ) -> Event {
+        let creator = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        factory::Event::new()
+            .room_id(room.id())
+            .kind(kind)
+            .set(kind)
+            .label(&Uuid::new_v4().to_string())
+            .occurred_at(10_000_000_000_000 - days_ago * 86_400_000_000_000)
+            .data(&json!({}))
+            .created_at(Utc::now() - Duration::days(days_ago))
+            .created_by(creator.agent_id())
+            .insert(conn)
+            .await
+    }
+
     async fn insert_room(conn: &mut PgConnection, preserve_history: bool) -> Room {
         let now = Utc::now().trunc_subsecs(0);
 
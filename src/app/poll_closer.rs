@@ -0,0 +1,184 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use sqlx::{postgres::PgConnection, Acquire};
+use svc_agent::mqtt::{Agent, OutgoingEvent, OutgoingEventProperties, ShortTermTimingProperties};
+use tokio::{sync::watch, task::JoinHandle};
+use tracing::{error, info};
+
+use crate::app::context::{AppContext, GlobalContext};
+use crate::app::message_handler::{publish_message, Message};
+use crate::app::sentry;
+use crate::db;
+use crate::metrics::QueryKey;
+
+/// Maximum number of polls closed per check.
+const BATCH_SIZE: i64 = 100;
+
+/// Periodically closes polls which time is up and broadcasts their results.
+pub fn run(
+    context: Arc<AppContext>,
+    mut agent: Agent,
+    mut shutdown_rx: watch::Receiver<()>,
+) -> JoinHandle<()> {
+    let check_interval = context.config().polls.check_interval;
+
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(check_interval) => {}
+                _ = shutdown_rx.changed() => return,
+            }
+
+            let result = async {
+                let mut conn = context
+                    .db()
+                    .acquire()
+                    .await
+                    .context("Failed to acquire db connection")?;
+
+                close_due_polls(context.as_ref(), &mut conn).await
+            };
+
+            match result.await {
+                Ok(notifications) => {
+                    for message in notifications {
+                        if let Err(err) = publish_message(&mut agent, message) {
+                            error!("Failed to publish poll results notification: {:?}", err);
+                        }
+                    }
+                }
+                Err(err) => {
+                    error!("Failed to close due polls: {:?}", err);
+
+                    sentry::send(&err, &[]);
+                }
+            }
+        }
+    })
+}
+
+/// Closes polls which `closes_at` has come and returns `poll.close` notifications to publish.
+/// Replicas skip polls being closed by each other so each poll gets closed once.
+pub async fn close_due_polls<C: GlobalContext>(
+    context: &C,
+    conn: &mut PgConnection,
+) -> Result<Vec<Message>> {
+    let mut txn = conn
+        .begin()
+        .await
+        .context("Failed to acquire transaction")?;
+
+    let ids = context
+        .metrics()
+        .measure_query(
+            QueryKey::PollCloseDueQuery,
+            db::poll::CloseDueQuery::new(BATCH_SIZE).execute(&mut txn),
+        )
+        .await
+        .context("Failed to close due polls")?;
+
+    let mut notifications = vec![];
+
+    for id in ids {
+        let results = context
+            .metrics()
+            .measure_query(
+                QueryKey::PollResultsQuery,
+                db::poll::ResultsQuery::new(id).execute(&mut txn),
+            )
+            .await
+            .context("Failed to get poll results")?
+            .context("Closed poll not found")?;
+
+        info!(room_id = %results.room_id(), poll_id = %id, "Closed due poll");
+
+        let room = context
+            .metrics()
+            .measure_query(
+                QueryKey::RoomFindQuery,
+                db::room::FindQuery::by_id(results.room_id()).execute(&mut txn),
+            )
+            .await
+            .context("Failed to find room")?
+            .context("Room of closed poll not found")?;
+
+        let path = context.config().room_topic(room.audience(), room.id());
+        let timing = ShortTermTimingProperties::new(Utc::now());
+        let props = OutgoingEventProperties::new("poll.close", timing);
+        notifications.push(Box::new(OutgoingEvent::broadcast(results, props, &path)) as Message);
+    }
+
+    txn.commit().await.context("Failed to commit transaction")?;
+
+    Ok(notifications)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_helpers::prelude::*;
+
+    async fn insert_poll(
+        conn: &mut PgConnection,
+        room: &db::room::Object,
+        agent: &TestAgent,
+        closes_at: chrono::DateTime<Utc>,
+    ) {
+        let event = factory::Event::new()
+            .room_id(room.id())
+            .kind("poll")
+            .data(&json!({ "question": "2 + 2" }))
+            .occurred_at(1000)
+            .created_by(agent.agent_id())
+            .insert(conn)
+            .await;
+
+        db::poll::InsertQuery::new(event.id(), room.id(), 2)
+            .closes_at(closes_at)
+            .execute(conn)
+            .await
+            .expect("Failed to insert poll");
+    }
+
+    /// Counts notifications to the room only since other tests' rooms may be due as well.
+    async fn count_room_messages(messages: Vec<Message>, room: &db::room::Object) -> usize {
+        let suffix = format!("/rooms/{}/events", room.id());
+
+        parse_messages(Box::new(futures::stream::iter(messages)))
+            .await
+            .iter()
+            .filter(|message| message.topic().ends_with(&suffix))
+            .count()
+    }
+
+    #[tokio::test]
+    async fn close_due_polls() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "admin", USR_AUDIENCE);
+        let mut conn = db.get_conn().await;
+        let room = shared_helpers::insert_room(&mut conn).await;
+
+        insert_poll(&mut conn, &room, &agent, Utc::now() - Duration::seconds(1)).await;
+        insert_poll(&mut conn, &room, &agent, Utc::now() + Duration::minutes(1)).await;
+
+        let context = TestContext::new(db, TestAuthz::new());
+
+        let messages = super::close_due_polls(&context, &mut conn)
+            .await
+            .expect("Failed to close polls");
+
+        assert_eq!(count_room_messages(messages, &room).await, 1);
+
+        // Closed polls are not closed again.
+        let messages = super::close_due_polls(&context, &mut conn)
+            .await
+            .expect("Failed to close polls");
+
+        assert_eq!(count_room_messages(messages, &room).await, 0);
+    }
+}
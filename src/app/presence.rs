@@ -0,0 +1,132 @@
+use std::time::Duration as StdDuration;
+
+use anyhow::{Context, Result};
+use svc_agent::AgentId;
+use svc_authz::cache::{Commands, ConnectionPool as RedisConnectionPool};
+use uuid::Uuid;
+
+/// Marks a room's set as loaded from the DB so an empty room is distinguishable from a missing one.
+const LOADED_MARKER: &str = "";
+
+/// Online agents of rooms kept in Redis sets shared by all replicas.
+///
+/// The DB stays the source of truth: a room's set is loaded from it when missing
+/// and expires when not changed for the configured ttl so drift caused by failed updates
+/// doesn't live long.
+#[derive(Clone)]
+pub struct RoomPresence {
+    pool: RedisConnectionPool,
+    ttl: StdDuration,
+}
+
+impl RoomPresence {
+    pub fn new(pool: RedisConnectionPool, ttl: StdDuration) -> Self {
+        Self { pool, ttl }
+    }
+
+    pub async fn enter(&self, room_id: Uuid, agent_id: &AgentId) -> Result<()> {
+        let (pool, key, ttl) = self.command_args(room_id);
+        let agent_id = agent_id.to_string();
+
+        blocking(move || {
+            let mut conn = pool.get().context("Failed to get redis connection")?;
+            let _: () = conn.sadd(&key, agent_id).context("Failed to add agent")?;
+            let _: () = conn.expire(&key, ttl).context("Failed to set ttl")?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn leave(&self, room_id: Uuid, agent_id: &AgentId) -> Result<()> {
+        let (pool, key, ttl) = self.command_args(room_id);
+        let agent_id = agent_id.to_string();
+
+        blocking(move || {
+            let mut conn = pool.get().context("Failed to get redis connection")?;
+            let _: () = conn
+                .srem(&key, agent_id)
+                .context("Failed to remove agent")?;
+            let _: () = conn.expire(&key, ttl).context("Failed to set ttl")?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Stores the room's online agents read from the DB.
+    pub async fn load(&self, room_id: Uuid, agent_ids: &[AgentId]) -> Result<()> {
+        let (pool, key, ttl) = self.command_args(room_id);
+        let mut members = vec![LOADED_MARKER.to_owned()];
+        members.extend(agent_ids.iter().map(|agent_id| agent_id.to_string()));
+
+        blocking(move || {
+            let mut conn = pool.get().context("Failed to get redis connection")?;
+            let _: () = conn.sadd(&key, members).context("Failed to add agents")?;
+            let _: () = conn.expire(&key, ttl).context("Failed to set ttl")?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Returns `None` when the room's set has to be loaded first.
+    pub async fn list(&self, room_id: Uuid) -> Result<Option<Vec<AgentId>>> {
+        let (pool, key, _) = self.command_args(room_id);
+
+        let members: Vec<String> = blocking(move || {
+            let mut conn = pool.get().context("Failed to get redis connection")?;
+            conn.smembers(&key).context("Failed to list agents")
+        })
+        .await?;
+
+        if !members.iter().any(|member| member == LOADED_MARKER) {
+            return Ok(None);
+        }
+
+        let agent_ids = members
+            .iter()
+            .filter(|member| *member != LOADED_MARKER)
+            .map(|member| {
+                member
+                    .parse::<AgentId>()
+                    .map_err(|err| anyhow!("Invalid agent id '{}': {:?}", member, err))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Some(agent_ids))
+    }
+
+    /// Returns `None` when the room's set has to be loaded first.
+    pub async fn count(&self, room_id: Uuid) -> Result<Option<usize>> {
+        let (pool, key, _) = self.command_args(room_id);
+
+        let (loaded, count): (bool, usize) = blocking(move || {
+            let mut conn = pool.get().context("Failed to get redis connection")?;
+            let loaded = conn
+                .sismember(&key, LOADED_MARKER)
+                .context("Failed to check room presence")?;
+            let count = conn.scard(&key).context("Failed to count agents")?;
+            Ok((loaded, count))
+        })
+        .await?;
+
+        Ok(loaded.then(|| count.saturating_sub(1)))
+    }
+
+    fn command_args(&self, room_id: Uuid) -> (RedisConnectionPool, String, usize) {
+        (
+            self.pool.clone(),
+            format!("event.presence.{room_id}"),
+            self.ttl.as_secs() as usize,
+        )
+    }
+}
+
+/// The pool is blocking so commands run off the async runtime.
+async fn blocking<T, F>(f: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T> + Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .context("Redis task panicked")?
+}
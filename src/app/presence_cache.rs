@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+use std::time::{Duration as StdDuration, Instant};
+
+use parking_lot::Mutex;
+use uuid::Uuid;
+
+/// Expired entries get pruned once there are more rooms than that.
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// Short-lived memory of whether rooms have ready agents.
+#[derive(Default)]
+pub struct PresenceCache {
+    entries: Mutex<HashMap<Uuid, (bool, Instant)>>,
+}
+
+impl PresenceCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, room_id: Uuid, ttl: StdDuration) -> Option<bool> {
+        let mut entries = self.entries.lock();
+
+        match entries.get(&room_id) {
+            Some((has_ready_agents, checked_at)) if checked_at.elapsed() < ttl => {
+                Some(*has_ready_agents)
+            }
+            Some(_) => {
+                entries.remove(&room_id);
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub fn insert(&self, room_id: Uuid, has_ready_agents: bool, ttl: StdDuration) {
+        let mut entries = self.entries.lock();
+
+        if entries.len() >= PRUNE_THRESHOLD {
+            entries.retain(|_, (_, checked_at)| checked_at.elapsed() < ttl);
+        }
+
+        entries.insert(room_id, (has_ready_agents, Instant::now()));
+    }
+
+    /// Forgets the room, e.g. when an agent enters it.
+    pub fn invalidate(&self, room_id: Uuid) {
+        self.entries.lock().remove(&room_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_presence() {
+        let cache = PresenceCache::new();
+        let room_id = Uuid::new_v4();
+        let ttl = StdDuration::from_secs(60);
+        assert_eq!(cache.get(room_id, ttl), None);
+
+        cache.insert(room_id, false, ttl);
+        assert_eq!(cache.get(room_id, ttl), Some(false));
+        assert_eq!(cache.get(Uuid::new_v4(), ttl), None);
+
+        cache.invalidate(room_id);
+        assert_eq!(cache.get(room_id, ttl), None);
+    }
+
+    #[test]
+    fn expire_presence() {
+        let cache = PresenceCache::new();
+        let room_id = Uuid::new_v4();
+
+        cache.insert(room_id, true, StdDuration::ZERO);
+        assert_eq!(cache.get(room_id, StdDuration::ZERO), None);
+    }
+}
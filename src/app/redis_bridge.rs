@@ -0,0 +1,209 @@
+//! Mirroring room broadcasts to Redis pub/sub so lightweight websocket gateways
+//! can fan out events without speaking MQTT.
+//!
+//! Each broadcast is published to `{channel_prefix}{classroom_id}` channel as
+//! `{"room_id": ..., "label": ..., "payload": ...}`. Delivery is best effort:
+//! failures get logged and never affect the MQTT publishing.
+
+use std::collections::HashMap;
+
+use anyhow::{Context as AnyhowContext, Result};
+use parking_lot::Mutex;
+use serde_derive::Serialize;
+use serde_json::Value as JsonValue;
+use sqlx::postgres::PgPool as Db;
+use svc_authz::cache::{Commands, ConnectionPool as RedisConnectionPool};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::app::context::GlobalContext;
+use crate::app::service_utils::Notifications;
+use crate::db;
+use crate::metrics::{Metrics, QueryKey};
+
+/// The memory gets cleared once there are more rooms than that.
+const CLASSROOM_IDS_LIMIT: usize = 10_000;
+
+/// Classrooms of rooms resolved for the bridge. A room never changes its classroom
+/// so entries don't expire.
+#[derive(Default)]
+pub struct ClassroomIds {
+    entries: Mutex<HashMap<Uuid, Uuid>>,
+}
+
+impl ClassroomIds {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, room_id: Uuid) -> Option<Uuid> {
+        self.entries.lock().get(&room_id).copied()
+    }
+
+    pub fn insert(&self, room_id: Uuid, classroom_id: Uuid) {
+        let mut entries = self.entries.lock();
+
+        if entries.len() >= CLASSROOM_IDS_LIMIT {
+            entries.clear();
+        }
+
+        entries.insert(room_id, classroom_id);
+    }
+
+    async fn resolve(&self, db: &Db, metrics: &Metrics, room_id: Uuid) -> Result<Uuid> {
+        if let Some(classroom_id) = self.get(room_id) {
+            return Ok(classroom_id);
+        }
+
+        let mut conn = db
+            .acquire()
+            .await
+            .context("Failed to acquire DB connection")?;
+
+        let room = metrics
+            .measure_query(
+                QueryKey::RoomFindQuery,
+                db::room::FindQuery::by_id(room_id).execute(&mut conn),
+            )
+            .await
+            .context("Failed to find room")?
+            .context("Room not found")?;
+
+        self.insert(room_id, room.classroom_id());
+        Ok(room.classroom_id())
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct BridgedNotification {
+    room_id: Uuid,
+    label: &'static str,
+    payload: JsonValue,
+}
+
+impl BridgedNotification {
+    pub fn new(room_id: Uuid, label: &'static str, payload: JsonValue) -> Self {
+        Self {
+            room_id,
+            label,
+            payload,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RedisBridge {
+    pool: RedisConnectionPool,
+    channel_prefix: String,
+}
+
+impl RedisBridge {
+    pub fn new(pool: RedisConnectionPool, channel_prefix: String) -> Self {
+        Self {
+            pool,
+            channel_prefix,
+        }
+    }
+
+    /// Publishes the classroom's notifications keeping their order.
+    pub async fn publish(
+        &self,
+        classroom_id: Uuid,
+        notifications: Vec<BridgedNotification>,
+    ) -> Result<()> {
+        let pool = self.pool.clone();
+        let channel = format!("{}{}", self.channel_prefix, classroom_id);
+
+        let messages = notifications
+            .iter()
+            .map(serde_json::to_string)
+            .collect::<Result<Vec<_>, _>>()
+            .context("Failed to serialize notification")?;
+
+        tokio::task::spawn_blocking(move || {
+            let mut conn = pool.get().context("Failed to get redis connection")?;
+
+            for message in messages {
+                let _: () = conn
+                    .publish(&channel, message)
+                    .context("Failed to publish notification")?;
+            }
+
+            Ok(())
+        })
+        .await
+        .context("Redis task panicked")?
+    }
+}
+
+/// Redis bridge when it's enabled and the cache is configured.
+pub fn redis_bridge<C: GlobalContext + ?Sized>(context: &C) -> Option<RedisBridge> {
+    let config = context.config();
+
+    if !config.redis_bridge.enabled {
+        return None;
+    }
+
+    context
+        .redis_pool()
+        .clone()
+        .map(|pool| RedisBridge::new(pool, config.redis_bridge.channel_prefix.clone()))
+}
+
+/// Mirrors room broadcasts of a response to the bridge in background.
+pub fn mirror<C: GlobalContext + ?Sized>(context: &C, notifications: &Notifications) {
+    let bridge = match redis_bridge(context) {
+        Some(bridge) => bridge,
+        None => return,
+    };
+
+    let config = context.config();
+    let mut rooms: Vec<(Uuid, Vec<BridgedNotification>)> = vec![];
+
+    for notification in notifications.room_broadcasts(&config) {
+        let room_id = notification.room_id;
+
+        match rooms.iter_mut().find(|(id, _)| *id == room_id) {
+            Some((_, group)) => group.push(notification),
+            None => rooms.push((room_id, vec![notification])),
+        }
+    }
+
+    if rooms.is_empty() {
+        return;
+    }
+
+    let db = context.ro_db().clone();
+    let metrics = context.metrics();
+    let classroom_ids = context.classroom_ids();
+
+    tokio::spawn(async move {
+        for (room_id, notifications) in rooms {
+            let result = async {
+                let classroom_id = classroom_ids.resolve(&db, &metrics, room_id).await?;
+                bridge.publish(classroom_id, notifications).await
+            };
+
+            if let Err(err) = result.await {
+                warn!(%room_id, "Failed to mirror notifications to redis: {:?}", err);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remember_classroom_ids() {
+        let classroom_ids = ClassroomIds::new();
+        let room_id = Uuid::new_v4();
+        let classroom_id = Uuid::new_v4();
+        assert_eq!(classroom_ids.get(room_id), None);
+
+        classroom_ids.insert(room_id, classroom_id);
+        assert_eq!(classroom_ids.get(room_id), Some(classroom_id));
+        assert_eq!(classroom_ids.get(Uuid::new_v4()), None);
+    }
+}
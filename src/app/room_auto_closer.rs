@@ -0,0 +1,201 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use sqlx::postgres::PgConnection;
+use svc_agent::mqtt::{Agent, OutgoingEvent, OutgoingEventProperties, ShortTermTimingProperties};
+use tokio::{sync::watch, task::JoinHandle};
+use tracing::{error, info};
+
+use crate::app::context::{AppContext, GlobalContext};
+use crate::app::message_handler::{publish_message, Message};
+use crate::app::sentry;
+use crate::app::webhook_client::Webhook;
+use crate::db;
+use crate::metrics::QueryKey;
+
+/// Advisory lock key to make sure that only one replica closes rooms at a time.
+const AUTO_CLOSE_LOCK_KEY: i64 = 0x6576_656e_745f_6163; // "event_ac"
+
+/// Periodically closes rooms without ready agents in audiences from the `auto_close` config.
+pub fn run(
+    context: Arc<AppContext>,
+    mut agent: Agent,
+    mut shutdown_rx: watch::Receiver<()>,
+) -> Option<JoinHandle<()>> {
+    let check_interval = context.config().auto_close.check_interval;
+
+    if context.config().auto_close.audiences.is_empty() {
+        return None;
+    }
+
+    let handle = tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(check_interval) => {}
+                _ = shutdown_rx.changed() => return,
+            }
+
+            match run_exclusively(&context).await {
+                Ok(notifications) => {
+                    for message in notifications {
+                        if let Err(err) = publish_message(&mut agent, message) {
+                            error!("Failed to publish room auto close notification: {:?}", err);
+                        }
+                    }
+                }
+                Err(err) => {
+                    error!("Failed to close idle rooms: {:?}", err);
+
+                    sentry::send(&err, &[]);
+                }
+            }
+        }
+    });
+
+    Some(handle)
+}
+
+async fn run_exclusively(context: &AppContext) -> Result<Vec<Message>> {
+    let mut conn = context
+        .db()
+        .acquire()
+        .await
+        .context("Failed to acquire db connection")?;
+
+    let locked = db::advisory_lock::TryLockQuery::new(AUTO_CLOSE_LOCK_KEY)
+        .execute(&mut conn)
+        .await
+        .context("Failed to take room auto close lock")?;
+
+    if !locked {
+        return Ok(vec![]);
+    }
+
+    let result = close_idle_rooms(context, &mut conn).await;
+
+    db::advisory_lock::UnlockQuery::new(AUTO_CLOSE_LOCK_KEY)
+        .execute(&mut conn)
+        .await
+        .context("Failed to release room auto close lock")?;
+
+    result
+}
+
+/// Closes idle rooms and returns notifications about them to publish.
+pub async fn close_idle_rooms<C: GlobalContext>(
+    context: &C,
+    conn: &mut PgConnection,
+) -> Result<Vec<Message>> {
+    let mut notifications = vec![];
+
+    for (audience, config) in context.config().auto_close.audiences.iter() {
+        let query = db::room::AutoCloseQuery::new(audience, config.idle_timeout);
+
+        let rooms = context
+            .metrics()
+            .measure_query(QueryKey::RoomAutoCloseQuery, query.execute(conn))
+            .await
+            .with_context(|| format!("Failed to close idle rooms in '{}'", audience))?;
+
+        for room in rooms {
+            info!(room_id = %room.id(), classroom_id = %room.classroom_id(), "Closed idle room");
+            context.room_cache().invalidate(room.id());
+
+            context
+                .webhook_client()
+                .send(room.audience(), Webhook::new("room.close", &room));
+
+            let path = context.config().audience_topic(room.audience());
+            notifications.push(build_notification("room.update", &path, room.clone()));
+
+            let path = context.config().room_topic(room.audience(), room.id());
+            notifications.push(build_notification("room.close", &path, room));
+        }
+    }
+
+    Ok(notifications)
+}
+
+fn build_notification(label: &'static str, path: &str, room: db::room::Object) -> Message {
+    let timing = ShortTermTimingProperties::new(Utc::now());
+    let props = OutgoingEventProperties::new(label, timing);
+    Box::new(OutgoingEvent::broadcast(room, props, path)) as Message
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Bound;
+    use std::time::Duration as StdDuration;
+
+    use chrono::Duration;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::config::AutoCloseAudience;
+    use crate::db::room::ClassType;
+    use crate::test_helpers::prelude::*;
+
+    // Rooms are closed audience-wide so the test doesn't share one with others.
+    const AUDIENCE: &str = "auto-close.usr.example.org";
+
+    async fn insert_room(conn: &mut PgConnection, keep_open: bool) -> db::room::Object {
+        factory::Room::new(Uuid::new_v4(), ClassType::Webinar)
+            .audience(AUDIENCE)
+            .time((
+                Bound::Included(Utc::now() - Duration::hours(1)),
+                Bound::Unbounded,
+            ))
+            .keep_open(keep_open)
+            .insert(conn)
+            .await
+    }
+
+    #[tokio::test]
+    async fn close_idle_rooms() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", AUDIENCE);
+        let mut conn = db.get_conn().await;
+
+        let idle_room = insert_room(&mut conn, false).await;
+        let kept_room = insert_room(&mut conn, true).await;
+        let occupied_room = insert_room(&mut conn, false).await;
+        shared_helpers::insert_agent(&mut conn, agent.agent_id(), occupied_room.id()).await;
+
+        let context = TestContext::new(db, TestAuthz::new());
+
+        context.update_config(|config| {
+            config.auto_close.audiences.insert(
+                AUDIENCE.to_owned(),
+                AutoCloseAudience {
+                    idle_timeout: StdDuration::from_secs(600),
+                },
+            );
+        });
+
+        let messages = super::close_idle_rooms(&context, &mut conn)
+            .await
+            .expect("Failed to close idle rooms");
+
+        // `room.update` and `room.close` of the idle room only.
+        assert_eq!(messages.len(), 2);
+
+        let room = db::room::FindQuery::by_id(idle_room.id())
+            .execute(&mut conn)
+            .await
+            .expect("Failed to find room")
+            .expect("Room not found");
+
+        assert!(room.is_closed());
+
+        for room in [kept_room, occupied_room] {
+            let room = db::room::FindQuery::by_id(room.id())
+                .execute(&mut conn)
+                .await
+                .expect("Failed to find room")
+                .expect("Room not found");
+
+            assert!(!room.is_closed());
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+use std::time::{Duration as StdDuration, Instant};
+
+use parking_lot::Mutex;
+use uuid::Uuid;
+
+use crate::db::room::Object as Room;
+
+/// Expired entries get pruned once there are more rooms than that.
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// Short-lived memory of rooms found by id or classroom id.
+///
+/// Each instance of the service keeps its own copy so the TTL bounds how long
+/// a change made by another instance stays unnoticed.
+#[derive(Default)]
+pub struct RoomCache {
+    rooms: Mutex<HashMap<Uuid, (Room, Instant)>>,
+    classrooms: Mutex<HashMap<Uuid, (Uuid, Instant)>>,
+}
+
+impl RoomCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, id: Uuid, ttl: StdDuration) -> Option<Room> {
+        let mut rooms = self.rooms.lock();
+
+        match rooms.get(&id) {
+            Some((room, found_at)) if found_at.elapsed() < ttl => Some(room.to_owned()),
+            Some(_) => {
+                rooms.remove(&id);
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub fn get_by_classroom_id(&self, classroom_id: Uuid, ttl: StdDuration) -> Option<Room> {
+        let room_id = {
+            let mut classrooms = self.classrooms.lock();
+
+            match classrooms.get(&classroom_id) {
+                Some((room_id, found_at)) if found_at.elapsed() < ttl => *room_id,
+                Some(_) => {
+                    classrooms.remove(&classroom_id);
+                    return None;
+                }
+                None => return None,
+            }
+        };
+
+        self.get(room_id, ttl)
+    }
+
+    pub fn insert(&self, room: &Room, ttl: StdDuration) {
+        if ttl.is_zero() {
+            return;
+        }
+
+        let now = Instant::now();
+
+        {
+            let mut rooms = self.rooms.lock();
+
+            if rooms.len() >= PRUNE_THRESHOLD {
+                rooms.retain(|_, (_, found_at)| found_at.elapsed() < ttl);
+            }
+
+            rooms.insert(room.id(), (room.to_owned(), now));
+        }
+
+        let mut classrooms = self.classrooms.lock();
+
+        if classrooms.len() >= PRUNE_THRESHOLD {
+            classrooms.retain(|_, (_, found_at)| found_at.elapsed() < ttl);
+        }
+
+        classrooms.insert(room.classroom_id(), (room.id(), now));
+    }
+
+    /// Forgets the room, e.g. when it gets updated.
+    ///
+    /// The classroom entry resolves to nothing afterwards so it doesn't need removing.
+    pub fn invalidate(&self, id: Uuid) {
+        self.rooms.lock().remove(&id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Bound;
+
+    use chrono::{Duration, Utc};
+
+    use super::*;
+    use crate::db::room::{Builder as RoomBuilder, ClassType};
+
+    fn build_room() -> Room {
+        let now = Utc::now();
+
+        RoomBuilder::new()
+            .id(Uuid::new_v4())
+            .audience("dev.svc.example.org".to_owned())
+            .time(
+                (
+                    Bound::Included(now),
+                    Bound::Excluded(now + Duration::hours(1)),
+                )
+                    .into(),
+            )
+            .created_at(now)
+            .preserve_history(true)
+            .classroom_id(Uuid::new_v4())
+            .kind(ClassType::Webinar)
+            .build()
+            .expect("Failed to build room")
+    }
+
+    #[test]
+    fn cache_room() {
+        let cache = RoomCache::new();
+        let room = build_room();
+        let ttl = StdDuration::from_secs(60);
+        assert!(cache.get(room.id(), ttl).is_none());
+
+        cache.insert(&room, ttl);
+        let cached = cache.get(room.id(), ttl).expect("Room not cached");
+        assert_eq!(cached.id(), room.id());
+
+        let cached = cache
+            .get_by_classroom_id(room.classroom_id(), ttl)
+            .expect("Room not cached by classroom id");
+
+        assert_eq!(cached.id(), room.id());
+        assert!(cache.get(Uuid::new_v4(), ttl).is_none());
+
+        cache.invalidate(room.id());
+        assert!(cache.get(room.id(), ttl).is_none());
+        assert!(cache
+            .get_by_classroom_id(room.classroom_id(), ttl)
+            .is_none());
+    }
+
+    #[test]
+    fn skip_caching_without_ttl() {
+        let cache = RoomCache::new();
+        let room = build_room();
+
+        cache.insert(&room, StdDuration::ZERO);
+        assert!(cache.get(room.id(), StdDuration::from_secs(60)).is_none());
+    }
+}
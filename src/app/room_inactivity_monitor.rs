@@ -0,0 +1,194 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use serde_derive::Serialize;
+use sqlx::postgres::PgConnection;
+use tokio::{sync::watch, task::JoinHandle};
+use tracing::{error, warn};
+
+use crate::app::context::{AppContext, GlobalContext};
+use crate::app::sentry;
+use crate::app::webhook_client::Webhook;
+use crate::db;
+use crate::metrics::QueryKey;
+
+/// Advisory lock key to make sure that only one replica checks rooms at a time.
+const INACTIVITY_ALERT_LOCK_KEY: i64 = 0x6576_656e_745f_6961; // "event_ia"
+
+#[derive(Debug, Serialize)]
+struct InactivityAlert<'a> {
+    rooms: &'a [db::room::InactiveRoom],
+}
+
+/// Periodically alerts tenants from the `inactivity_alert` config about their open rooms
+/// where events stopped arriving.
+pub fn run(
+    context: Arc<AppContext>,
+    mut shutdown_rx: watch::Receiver<()>,
+) -> Option<JoinHandle<()>> {
+    let check_interval = context.config().inactivity_alert.check_interval;
+
+    if context.config().inactivity_alert.audiences.is_empty() {
+        return None;
+    }
+
+    let handle = tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(check_interval) => {}
+                _ = shutdown_rx.changed() => return,
+            }
+
+            if let Err(err) = run_exclusively(&context).await {
+                error!("Failed to check inactive rooms: {:?}", err);
+
+                sentry::send(&err, &[]);
+            }
+        }
+    });
+
+    Some(handle)
+}
+
+async fn run_exclusively(context: &AppContext) -> Result<()> {
+    let mut conn = context
+        .db()
+        .acquire()
+        .await
+        .context("Failed to acquire db connection")?;
+
+    let locked = db::advisory_lock::TryLockQuery::new(INACTIVITY_ALERT_LOCK_KEY)
+        .execute(&mut conn)
+        .await
+        .context("Failed to take inactivity alert lock")?;
+
+    if !locked {
+        return Ok(());
+    }
+
+    let result = alert_inactive_rooms(context, &mut conn).await;
+
+    db::advisory_lock::UnlockQuery::new(INACTIVITY_ALERT_LOCK_KEY)
+        .execute(&mut conn)
+        .await
+        .context("Failed to release inactivity alert lock")?;
+
+    result.map(|_| ())
+}
+
+/// Sends a `room.inactive` webhook to each tenant having newly inactive rooms and returns them.
+pub async fn alert_inactive_rooms<C: GlobalContext>(
+    context: &C,
+    conn: &mut PgConnection,
+) -> Result<Vec<db::room::InactiveRoom>> {
+    let mut alerted = vec![];
+
+    for (audience, config) in context.config().inactivity_alert.audiences.iter() {
+        let query = db::room::InactivityAlertQuery::new(audience, config.threshold);
+
+        let rooms = context
+            .metrics()
+            .measure_query(QueryKey::RoomInactivityAlertQuery, query.execute(conn))
+            .await
+            .with_context(|| format!("Failed to find inactive rooms in '{}'", audience))?;
+
+        if rooms.is_empty() {
+            continue;
+        }
+
+        for room in &rooms {
+            warn!(
+                room_id = %room.room_id,
+                classroom_id = %room.classroom_id,
+                last_activity_at = %room.last_activity_at,
+                "No events in open room"
+            );
+        }
+
+        context.webhook_client().send(
+            audience,
+            Webhook::new("room.inactive", InactivityAlert { rooms: &rooms }),
+        );
+
+        alerted.extend(rooms);
+    }
+
+    Ok(alerted)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Bound;
+    use std::time::Duration as StdDuration;
+
+    use chrono::{Duration, Utc};
+    use serde_json::json;
+    use uuid::Uuid;
+
+    use crate::config::InactivityAlertAudience;
+    use crate::db::room::ClassType;
+    use crate::test_helpers::prelude::*;
+
+    // Rooms are checked audience-wide so the test doesn't share one with others.
+    const AUDIENCE: &str = "inactivity-alert.usr.example.org";
+
+    #[tokio::test]
+    async fn alert_inactive_rooms() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", AUDIENCE);
+        let mut conn = db.get_conn().await;
+
+        let insert_room = |opened_at| {
+            factory::Room::new(Uuid::new_v4(), ClassType::Webinar)
+                .audience(AUDIENCE)
+                .time((Bound::Included(opened_at), Bound::Unbounded))
+        };
+
+        let inactive_room = insert_room(Utc::now() - Duration::hours(1))
+            .insert(&mut conn)
+            .await;
+
+        let active_room = insert_room(Utc::now() - Duration::hours(1))
+            .insert(&mut conn)
+            .await;
+
+        factory::Event::new()
+            .room_id(active_room.id())
+            .kind("message")
+            .data(&json!({ "text": "hello" }))
+            .occurred_at(1000)
+            .created_by(agent.agent_id())
+            .insert(&mut conn)
+            .await;
+
+        let just_opened_room = insert_room(Utc::now()).insert(&mut conn).await;
+
+        let context = TestContext::new(db, TestAuthz::new());
+
+        context.update_config(|config| {
+            config.inactivity_alert.audiences.insert(
+                AUDIENCE.to_owned(),
+                InactivityAlertAudience {
+                    threshold: StdDuration::from_secs(600),
+                },
+            );
+        });
+
+        let alerted = super::alert_inactive_rooms(&context, &mut conn)
+            .await
+            .expect("Failed to alert inactive rooms");
+
+        // Rooms of previous runs may be alerted too.
+        let ids = alerted.iter().map(|r| r.room_id).collect::<Vec<_>>();
+        assert!(ids.contains(&inactive_room.id()));
+        assert!(!ids.contains(&active_room.id()));
+        assert!(!ids.contains(&just_opened_room.id()));
+
+        // Alerted once per inactivity spell.
+        let alerted = super::alert_inactive_rooms(&context, &mut conn)
+            .await
+            .expect("Failed to alert inactive rooms");
+
+        assert!(alerted.iter().all(|r| r.room_id != inactive_room.id()));
+    }
+}
@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use sqlx::{postgres::PgConnection, Acquire};
+use svc_agent::mqtt::{Agent, OutgoingEvent, OutgoingEventProperties, ShortTermTimingProperties};
+use tokio::{sync::watch, task::JoinHandle};
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::app::context::{AppContext, GlobalContext};
+use crate::app::message_handler::{publish_message, Message};
+use crate::app::sentry;
+use crate::db;
+use crate::metrics::QueryKey;
+
+/// Advisory lock key to make sure that only one replica applies schedules at a time
+/// so that concurrent merges of locked types don't overwrite each other.
+const LOCK_SCHEDULE_LOCK_KEY: i64 = 0x6576_656e_745f_6c73; // "event_ls"
+
+/// Maximum number of schedules applied per check.
+const BATCH_SIZE: i64 = 100;
+
+/// Periodically applies due changes of room locked types scheduled with `room.schedule_lock`.
+pub fn run(
+    context: Arc<AppContext>,
+    mut agent: Agent,
+    mut shutdown_rx: watch::Receiver<()>,
+) -> JoinHandle<()> {
+    let check_interval = context.config().lock_schedule.check_interval;
+
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(check_interval) => {}
+                _ = shutdown_rx.changed() => return,
+            }
+
+            match run_exclusively(&context).await {
+                Ok(notifications) => {
+                    for message in notifications {
+                        if let Err(err) = publish_message(&mut agent, message) {
+                            error!(
+                                "Failed to publish scheduled room lock notification: {:?}",
+                                err
+                            );
+                        }
+                    }
+                }
+                Err(err) => {
+                    error!("Failed to apply room lock schedules: {:?}", err);
+
+                    sentry::send(&err, &[]);
+                }
+            }
+        }
+    })
+}
+
+async fn run_exclusively(context: &AppContext) -> Result<Vec<Message>> {
+    let mut conn = context
+        .db()
+        .acquire()
+        .await
+        .context("Failed to acquire db connection")?;
+
+    let locked = db::advisory_lock::TryLockQuery::new(LOCK_SCHEDULE_LOCK_KEY)
+        .execute(&mut conn)
+        .await
+        .context("Failed to take room lock schedule lock")?;
+
+    if !locked {
+        return Ok(vec![]);
+    }
+
+    let result = apply_due_schedules(context, &mut conn).await;
+
+    db::advisory_lock::UnlockQuery::new(LOCK_SCHEDULE_LOCK_KEY)
+        .execute(&mut conn)
+        .await
+        .context("Failed to release room lock schedule lock")?;
+
+    result
+}
+
+/// Merges due schedules into locked types of their rooms and returns notifications
+/// about updated rooms to publish.
+pub async fn apply_due_schedules<C: GlobalContext>(
+    context: &C,
+    conn: &mut PgConnection,
+) -> Result<Vec<Message>> {
+    let mut txn = conn
+        .begin()
+        .await
+        .context("Failed to acquire transaction")?;
+
+    let schedules = context
+        .metrics()
+        .measure_query(
+            QueryKey::RoomLockScheduleTakeDueQuery,
+            db::room_lock_schedule::TakeDueQuery::new(BATCH_SIZE).execute(&mut txn),
+        )
+        .await
+        .context("Failed to take due room lock schedules")?;
+
+    // Schedules come in the order they were created so the latest one wins for each type.
+    let mut changes: Vec<(Uuid, HashMap<String, bool>)> = vec![];
+
+    for schedule in schedules {
+        match changes.iter_mut().find(|(id, _)| *id == schedule.room_id()) {
+            Some((_, locked_types)) => locked_types.extend(schedule.locked_types()),
+            None => changes.push((schedule.room_id(), schedule.locked_types())),
+        }
+    }
+
+    let mut notifications = vec![];
+    let room_ids = changes.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+
+    for (room_id, changed_types) in changes {
+        let query = db::room::FindQuery::by_id(room_id);
+
+        let room = context
+            .metrics()
+            .measure_query(QueryKey::RoomFindQuery, query.execute(&mut txn))
+            .await
+            .context("Failed to find room")?;
+
+        // Schedules get deleted along with the room.
+        let room = match room {
+            Some(room) => room,
+            None => continue,
+        };
+
+        let locked_types = room
+            .locked_types()
+            .iter()
+            .map(|(k, v)| (k.to_owned(), *v))
+            .chain(changed_types)
+            .collect::<HashMap<_, _>>();
+
+        let query = db::room::UpdateQuery::new(room.id()).locked_types(locked_types);
+
+        let room = context
+            .metrics()
+            .measure_query(QueryKey::RoomUpdateQuery, query.execute(&mut txn))
+            .await
+            .context("Failed to update room")?;
+
+        info!(room_id = %room.id(), classroom_id = %room.classroom_id(), "Applied scheduled locked types");
+
+        let path = context.config().room_topic(room.audience(), room.id());
+        notifications.push(build_notification("room.update", &path, room));
+    }
+
+    txn.commit().await.context("Failed to commit transaction")?;
+
+    for room_id in room_ids {
+        context.room_cache().invalidate(room_id);
+    }
+
+    Ok(notifications)
+}
+
+fn build_notification(label: &'static str, path: &str, room: db::room::Object) -> Message {
+    let timing = ShortTermTimingProperties::new(Utc::now());
+    let props = OutgoingEventProperties::new(label, timing);
+    Box::new(OutgoingEvent::broadcast(room, props, path)) as Message
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Bound;
+
+    use chrono::Duration;
+
+    use super::*;
+    use crate::db::room::ClassType;
+    use crate::db::room_lock_schedule::InsertQuery;
+    use crate::test_helpers::prelude::*;
+
+    /// Counts notifications to the room only since other tests' rooms may be due as well.
+    async fn count_room_messages(messages: Vec<Message>, room: &db::room::Object) -> usize {
+        let suffix = format!("/rooms/{}/events", room.id());
+
+        parse_messages(Box::new(futures::stream::iter(messages)))
+            .await
+            .iter()
+            .filter(|message| message.topic().ends_with(&suffix))
+            .count()
+    }
+
+    #[tokio::test]
+    async fn apply_due_schedules() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let mut conn = db.get_conn().await;
+        let now = Utc::now();
+
+        let room = factory::Room::new(Uuid::new_v4(), ClassType::Webinar)
+            .audience(USR_AUDIENCE)
+            .time((
+                Bound::Included(now - Duration::hours(1)),
+                Bound::Excluded(now + Duration::minutes(3)),
+            ))
+            .insert(&mut conn)
+            .await;
+
+        let lock = |kind: &str, value: bool| [(kind.to_owned(), value)].into_iter().collect();
+
+        // Due since the room closes in less than 5 minutes.
+        InsertQuery::before_close(
+            room.id(),
+            lock("message", true),
+            300,
+            agent.agent_id().to_owned(),
+        )
+        .execute(&mut conn)
+        .await
+        .expect("Failed to insert schedule");
+
+        // Due at a fixed time.
+        InsertQuery::at(
+            room.id(),
+            lock("document", true),
+            now - Duration::seconds(1),
+            agent.agent_id().to_owned(),
+        )
+        .execute(&mut conn)
+        .await
+        .expect("Failed to insert schedule");
+
+        // Not due yet.
+        InsertQuery::at(
+            room.id(),
+            lock("message", false),
+            now + Duration::minutes(1),
+            agent.agent_id().to_owned(),
+        )
+        .execute(&mut conn)
+        .await
+        .expect("Failed to insert schedule");
+
+        let context = TestContext::new(db, TestAuthz::new());
+
+        let messages = super::apply_due_schedules(&context, &mut conn)
+            .await
+            .expect("Failed to apply schedules");
+
+        // A single `room.update` for both due schedules.
+        assert_eq!(count_room_messages(messages, &room).await, 1);
+
+        let room = db::room::FindQuery::by_id(room.id())
+            .execute(&mut conn)
+            .await
+            .expect("Failed to find room")
+            .expect("Room not found");
+
+        assert_eq!(room.locked_types().get("message"), Some(&true));
+        assert_eq!(room.locked_types().get("document"), Some(&true));
+
+        // Applied schedules are gone.
+        let messages = super::apply_due_schedules(&context, &mut conn)
+            .await
+            .expect("Failed to apply schedules");
+
+        assert_eq!(count_room_messages(messages, &room).await, 0);
+    }
+}
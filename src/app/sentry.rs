@@ -0,0 +1,154 @@
+//! Error reporting to Sentry.
+//!
+//! Incoming messages and HTTP requests are handled within hubs of their own so that
+//! request and room tags set on the scope while handling them get attached only
+//! to the errors reported by the same request.
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use parking_lot::{const_mutex, Mutex};
+use sentry::{
+    protocol::{Breadcrumb, Value},
+    ClientInitGuard, Hub, SentryFuture, SentryFutureExt,
+};
+use svc_error::extension::sentry::Config;
+
+use crate::db::room::Object as Room;
+
+/// Errors of the same kind get reported at most once per this interval
+/// so that an incident doesn't flood Sentry with identical reports.
+const THROTTLE_INTERVAL: Duration = Duration::from_secs(10);
+
+static THROTTLE: Mutex<BTreeMap<&'static str, Throttled>> = const_mutex(BTreeMap::new());
+
+/// Must be called on the main thread for the client to be bound to the process hub.
+/// Reporting stops when the guard gets dropped.
+pub fn init(config: &Config) -> ClientInitGuard {
+    std::env::set_var("RUST_BACKTRACE", "1");
+    std::env::set_var("RUST_LIB_BACKTRACE", "1");
+
+    let options = sentry::ClientOptions {
+        attach_stacktrace: true,
+        release: config.release.clone(),
+        environment: config.environment.clone().map(Cow::from),
+        server_name: config.server_name.clone().map(Cow::from),
+        ..Default::default()
+    };
+
+    let guard = sentry::init((config.dsn.as_str(), options));
+
+    sentry::configure_scope(|scope| {
+        if let Ok(namespace) = std::env::var("KUBE_NAMESPACE") {
+            scope.set_tag("kube_namespace", namespace);
+        }
+    });
+
+    guard
+}
+
+/// Runs the future within a hub of its own inheriting the current scope.
+pub fn bind_hub<F: Future>(future: F) -> SentryFuture<F> {
+    future.bind_hub(Hub::new_from_top(Hub::current()))
+}
+
+/// Tags errors reported while handling the request with its MQTT method or HTTP route.
+pub fn set_request_tags(method: &str) {
+    sentry::configure_scope(|scope| scope.set_tag("method", method));
+
+    sentry::add_breadcrumb(Breadcrumb {
+        category: Some("request".to_owned()),
+        message: Some(method.to_owned()),
+        ..Default::default()
+    });
+}
+
+/// Tags errors reported while handling the request with the room it deals with.
+pub fn set_room_tags(room: &Room) {
+    sentry::configure_scope(|scope| {
+        scope.set_tag("room_id", room.id());
+        scope.set_tag("classroom_id", room.classroom_id());
+        scope.set_tag("audience", room.audience());
+    });
+
+    // A request may deal with several rooms, e.g. the source and the derived one.
+    let mut data = BTreeMap::new();
+    data.insert("room_id".to_owned(), Value::from(room.id().to_string()));
+
+    sentry::add_breadcrumb(Breadcrumb {
+        category: Some("room".to_owned()),
+        message: Some("Room found".to_owned()),
+        data,
+        ..Default::default()
+    });
+}
+
+/// Reports an error with the scope of the current hub and `tags` added.
+pub fn send(err: &anyhow::Error, tags: &[(&str, &str)]) {
+    sentry::with_scope(
+        |scope| {
+            for (key, value) in tags {
+                scope.set_tag(key, value);
+            }
+        },
+        || sentry::integrations::anyhow::capture_anyhow(err),
+    );
+}
+
+/// Tells whether an error of `kind` is to be reported now. Returns the number of reports
+/// of the kind suppressed since the previous one.
+pub fn throttle(kind: &'static str) -> Option<u64> {
+    THROTTLE
+        .lock()
+        .entry(kind)
+        .or_insert_with(Throttled::new)
+        .check(Instant::now())
+}
+
+struct Throttled {
+    reported_at: Option<Instant>,
+    suppressed: u64,
+}
+
+impl Throttled {
+    fn new() -> Self {
+        Self {
+            reported_at: None,
+            suppressed: 0,
+        }
+    }
+
+    fn check(&mut self, now: Instant) -> Option<u64> {
+        match self.reported_at {
+            Some(reported_at) if now.duration_since(reported_at) < THROTTLE_INTERVAL => {
+                self.suppressed += 1;
+                None
+            }
+            _ => {
+                self.reported_at = Some(now);
+                Some(std::mem::take(&mut self.suppressed))
+            }
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn throttle_reports() {
+        let mut throttled = Throttled::new();
+        let now = Instant::now();
+
+        assert_eq!(throttled.check(now), Some(0));
+        assert_eq!(throttled.check(now + Duration::from_secs(1)), None);
+        assert_eq!(throttled.check(now + Duration::from_secs(2)), None);
+        assert_eq!(throttled.check(now + THROTTLE_INTERVAL), Some(2));
+        assert_eq!(throttled.check(now + THROTTLE_INTERVAL), None);
+    }
+}
@@ -1,9 +1,14 @@
+use std::cmp;
+
 use axum::{response::IntoResponse, Json};
 use chrono::{DateTime, Duration, Utc};
 use futures::{future, stream, StreamExt};
-use http::StatusCode;
+use http::{
+    header::{HeaderValue, ETAG},
+    StatusCode,
+};
 use serde::Serialize;
-use serde_json::Value;
+use serde_json::{json, Value};
 use svc_agent::{
     mqtt::{
         IncomingRequestProperties, OutgoingEvent, OutgoingEventProperties,
@@ -12,40 +17,220 @@ use svc_agent::{
     Addressable, AgentId, Authenticable,
 };
 use tokio::task::JoinHandle;
+use tracing::warn;
+use uuid::Uuid;
 
 use crate::app::endpoint::helpers;
 use crate::app::message_handler::{Message, MessageStream, MessageStreamTrait};
+use crate::app::redis_bridge::BridgedNotification;
+use crate::config::{
+    Config, NotificationBatchingConfig, NotificationSizeConfig, OversizedNotificationPolicy,
+};
+use crate::metrics::Metrics;
 
 use super::error;
 
+/// Broadcast notification kept unserialized until publishing so that the ones
+/// to the same topic can be coalesced.
+struct Notification {
+    label: &'static str,
+    path: String,
+    payload: Value,
+    timing: ShortTermTimingProperties,
+}
+
+impl Notification {
+    fn into_message(self, reqp: Option<&IncomingRequestProperties>) -> Message {
+        let props = event_properties(self.label, self.timing, reqp);
+        Box::new(OutgoingEvent::broadcast(self.payload, props, &self.path))
+    }
+}
+
+/// Notifications caused by an MQTT request carry its tracking properties and
+/// `local_tracking_label` so the client may correlate them with the request. The gateway
+/// passes them to MQTT v5 clients as user properties.
+fn event_properties(
+    label: &'static str,
+    timing: ShortTermTimingProperties,
+    reqp: Option<&IncomingRequestProperties>,
+) -> OutgoingEventProperties {
+    match reqp {
+        Some(reqp) => reqp.to_event(label, timing),
+        None => OutgoingEventProperties::new(label, timing),
+    }
+}
+
+#[derive(Serialize)]
+struct BatchItem {
+    label: &'static str,
+    payload: Value,
+}
+
+/// A notification over the size limit the author of the request is told about.
+#[derive(Debug, Serialize)]
+pub struct OversizedNotification {
+    label: &'static str,
+    /// The topic of the notification, the author is subscribed to it as well.
+    #[serde(skip)]
+    path: String,
+    size: usize,
+    max_size: usize,
+    /// `rejected` or `truncated`.
+    action: &'static str,
+}
+
+#[derive(Serialize)]
+struct OversizedNotificationEvent {
+    agent_id: AgentId,
+    #[serde(flatten)]
+    notification: OversizedNotification,
+}
+
 #[derive(Default)]
-pub struct Notifications(Vec<Message>);
+pub struct Notifications(Vec<Notification>);
 
 impl Notifications {
-    fn into_stream(self) -> impl MessageStreamTrait {
-        stream::iter(self.0)
+    /// Records payload sizes and applies the policy to notifications over the limit.
+    pub fn limit_size(
+        &mut self,
+        config: &NotificationSizeConfig,
+        metrics: &Metrics,
+    ) -> Vec<OversizedNotification> {
+        let mut oversized = vec![];
+        let mut kept = Vec::with_capacity(self.0.len());
+
+        for mut notification in self.0.drain(..) {
+            let size = serde_json::to_vec(&notification.payload)
+                .map(|payload| payload.len())
+                .unwrap_or_default();
+
+            metrics.observe_notification_size(notification.label, size);
+
+            let max_size = match config.max_size {
+                Some(max_size) if size > max_size => max_size,
+                _ => {
+                    kept.push(notification);
+                    continue;
+                }
+            };
+
+            let action = match config.policy {
+                OversizedNotificationPolicy::Reject => "rejected",
+                OversizedNotificationPolicy::Truncate => "truncated",
+            };
+
+            metrics.observe_oversized_notification(notification.label, action);
+            warn!(
+                label = notification.label,
+                path = notification.path,
+                size,
+                max_size,
+                action,
+                "Oversized notification"
+            );
+
+            oversized.push(OversizedNotification {
+                label: notification.label,
+                path: notification.path.clone(),
+                size,
+                max_size,
+                action,
+            });
+
+            if config.policy == OversizedNotificationPolicy::Truncate {
+                // Clients may fetch the entity by its id if they need it.
+                notification.payload = match notification.payload.get("id") {
+                    Some(id) => json!({ "id": id, "truncated": true }),
+                    None => json!({ "truncated": true }),
+                };
+
+                kept.push(notification);
+            }
+        }
+
+        self.0 = kept;
+        oversized
     }
 
-    fn push(&mut self, msg: Message) {
-        self.0.push(msg);
+    /// Notifications to rooms' topics in the order they were added.
+    pub fn room_broadcasts<'a>(
+        &'a self,
+        config: &'a Config,
+    ) -> impl Iterator<Item = BridgedNotification> + 'a {
+        self.0.iter().filter_map(|n| {
+            config
+                .topic_room_id(&n.path)
+                .map(|room_id| BridgedNotification::new(room_id, n.label, n.payload.clone()))
+        })
     }
-}
 
-impl IntoIterator for Notifications {
-    type Item = <Vec<Message> as IntoIterator>::Item;
-    type IntoIter = <Vec<Message> as IntoIterator>::IntoIter;
+    /// Builds messages to publish. With batching enabled notifications to the same topic
+    /// are published as a single `notification.batch` message with an array of them
+    /// in the order they were added.
+    pub fn into_messages(
+        self,
+        batching: &NotificationBatchingConfig,
+        reqp: Option<&IncomingRequestProperties>,
+    ) -> Vec<Message> {
+        if !batching.enabled {
+            return self
+                .0
+                .into_iter()
+                .map(|notification| notification.into_message(reqp))
+                .collect();
+        }
+
+        let mut topics: Vec<(String, Vec<Notification>)> = vec![];
+
+        for notification in self.0 {
+            match topics.iter_mut().find(|(p, _)| *p == notification.path) {
+                Some((_, group)) => group.push(notification),
+                None => topics.push((notification.path.clone(), vec![notification])),
+            }
+        }
+
+        let batch_size = cmp::max(batching.max_size, 1);
+        let mut messages = vec![];
+
+        for (path, mut group) in topics {
+            while !group.is_empty() {
+                let mut chunk = group
+                    .drain(..cmp::min(batch_size, group.len()))
+                    .collect::<Vec<_>>();
+
+                let message: Message = if chunk.len() == 1 {
+                    chunk.remove(0).into_message(reqp)
+                } else {
+                    let timing = chunk[0].timing.clone();
+                    let items = chunk
+                        .into_iter()
+                        .map(|n| BatchItem {
+                            label: n.label,
+                            payload: n.payload,
+                        })
+                        .collect::<Vec<_>>();
 
-    fn into_iter(self) -> Self::IntoIter {
-        self.0.into_iter()
+                    let props = event_properties("notification.batch", timing, reqp);
+                    Box::new(OutgoingEvent::broadcast(items, props, &path))
+                };
+
+                messages.push(message);
+            }
+        }
+
+        messages
     }
 }
 
 #[derive(Default)]
-pub struct AsyncTasks(Vec<JoinHandle<Message>>);
+pub struct AsyncTasks {
+    tasks: Vec<JoinHandle<Message>>,
+    streams: Vec<MessageStream>,
+}
 
 impl AsyncTasks {
     fn into_stream(self) -> impl MessageStreamTrait {
-        stream::iter(self.0)
+        stream::iter(self.tasks)
             .flat_map(stream::once)
             .filter(|jh_output| {
                 if let Err(err) = jh_output {
@@ -54,10 +239,15 @@ impl AsyncTasks {
                 future::ready(jh_output.is_ok())
             })
             .map(|jh_output| jh_output.unwrap())
+            .chain(stream::iter(self.streams).flatten())
     }
 
     fn push(&mut self, msg: JoinHandle<Message>) {
-        self.0.push(msg);
+        self.tasks.push(msg);
+    }
+
+    fn push_stream(&mut self, stream: MessageStream) {
+        self.streams.push(stream);
     }
 }
 
@@ -68,6 +258,7 @@ pub struct Response {
     authz_time: Option<Duration>,
     payload: Value,
     async_tasks: AsyncTasks,
+    etag: Option<String>,
 }
 
 impl Response {
@@ -84,14 +275,44 @@ impl Response {
             authz_time: maybe_authz_time,
             payload: serde_json::to_value(&payload).unwrap(),
             async_tasks: Default::default(),
+            etag: None,
         }
     }
 
+    /// Responds to a conditional HTTP request with nothing but the entity tag.
+    pub fn not_modified(
+        etag: String,
+        start_timestamp: DateTime<Utc>,
+        maybe_authz_time: Option<Duration>,
+    ) -> Self {
+        Self::new(
+            StatusCode::NOT_MODIFIED,
+            Value::Null,
+            start_timestamp,
+            maybe_authz_time,
+        )
+        .etag(etag)
+    }
+
+    /// Sets the `ETag` header of the HTTP response. MQTT responses don't carry it.
+    pub fn etag(self, etag: String) -> Self {
+        Self {
+            etag: Some(etag),
+            ..self
+        }
+    }
+
+    /// Status and payload of a reply over a transport without notifications, e.g. NATS.
+    pub fn into_reply(self) -> (StatusCode, Value) {
+        (self.status, self.payload)
+    }
+
     pub fn into_mqtt_messages(
         self,
         reqp: &IncomingRequestProperties,
+        batching: &NotificationBatchingConfig,
     ) -> Result<MessageStream, error::Error> {
-        let mut notifications = self.notifications;
+        let mut messages = self.notifications.into_messages(batching, Some(reqp));
         if self.status != StatusCode::NO_CONTENT {
             let response = helpers::build_response(
                 self.status,
@@ -100,40 +321,101 @@ impl Response {
                 self.start_timestamp,
                 self.authz_time,
             );
-            notifications.push(response);
+            messages.push(response);
         }
 
-        let stream = notifications
-            .into_stream()
-            .chain(self.async_tasks.into_stream());
+        let stream = stream::iter(messages).chain(self.async_tasks.into_stream());
 
         Ok(Box::new(stream))
     }
 
+    /// Fails when the payload can't be serialized which is needed right away
+    /// to batch and limit the size of notifications.
     pub fn add_notification(
         &mut self,
         label: &'static str,
         path: &str,
         payload: impl Serialize + Send + Sync + 'static,
         start_timestamp: DateTime<Utc>,
+    ) -> Result<(), error::Error> {
+        let payload = serde_json::to_value(&payload).map_err(|err| {
+            let err = anyhow::Error::from(err).context(format!("Failed to serialize {label}"));
+            error::Error::new(error::ErrorKind::SerializationFailed, err)
+        })?;
+
+        self.notifications.0.push(Notification {
+            label,
+            path: path.to_owned(),
+            payload,
+            timing: ShortTermTimingProperties::until_now(start_timestamp),
+        });
+
+        Ok(())
+    }
+
+    pub fn notifications(&self) -> &Notifications {
+        &self.notifications
+    }
+
+    /// Applies the size limit to notifications and tells the author about oversized ones
+    /// with `notification.oversized` events after the rest of messages.
+    ///
+    /// Events can't be sent to a single agent so they go to the topic of the oversized
+    /// notification with the author's `agent_id` for other clients to ignore them.
+    pub fn limit_notification_size(
+        &mut self,
+        config: &NotificationSizeConfig,
+        metrics: &Metrics,
+        author: &AgentId,
     ) {
-        let timing = ShortTermTimingProperties::until_now(start_timestamp);
-        let props = OutgoingEventProperties::new(label, timing);
-        self.notifications
-            .0
-            .push(Box::new(OutgoingEvent::broadcast(payload, props, path)))
+        let oversized = self.notifications.limit_size(config, metrics);
+
+        if oversized.is_empty() {
+            return;
+        }
+
+        let messages = oversized
+            .into_iter()
+            .map(|notification| {
+                let timing = ShortTermTimingProperties::until_now(self.start_timestamp);
+                let props = OutgoingEventProperties::new("notification.oversized", timing);
+                let path = notification.path.clone();
+                let payload = OversizedNotificationEvent {
+                    agent_id: author.to_owned(),
+                    notification,
+                };
+                let event = OutgoingEvent::broadcast(payload, props, &path);
+                Box::new(event) as Message
+            })
+            .collect::<Vec<_>>();
+
+        self.add_async_stream(Box::new(stream::iter(messages)));
     }
 
     pub fn add_async_task(&mut self, task: JoinHandle<Message>) {
         self.async_tasks.push(task);
     }
+
+    /// Adds messages which come from a background task during its run, e.g. progress
+    /// notifications. They get published after the async tasks' ones.
+    pub fn add_async_stream(&mut self, stream: MessageStream) {
+        self.async_tasks.push_stream(stream);
+    }
 }
 
 impl IntoResponse for Response {
     fn into_response(self) -> axum::response::Response {
         let tasks_stream = Box::new(self.async_tasks.into_stream()) as MessageStream;
 
-        let mut resp = (self.status, Json(self.payload)).into_response();
+        let mut resp = if self.status == StatusCode::NOT_MODIFIED {
+            self.status.into_response()
+        } else {
+            (self.status, Json(self.payload)).into_response()
+        };
+
+        if let Some(etag) = self.etag.and_then(|e| HeaderValue::from_str(&e).ok()) {
+            resp.headers_mut().insert(ETAG, etag);
+        }
 
         resp.extensions_mut().insert(self.notifications);
         resp.extensions_mut().insert(tasks_stream);
@@ -144,8 +426,25 @@ impl IntoResponse for Response {
 
 #[derive(Debug, Clone, Copy)]
 pub enum RequestParams<'a> {
-    Http { agent_id: &'a AgentId },
+    Http {
+        agent_id: &'a AgentId,
+    },
     MqttParams(&'a IncomingRequestProperties),
+    /// An HTTP request made with a share token of the room instead of authentication.
+    Shared {
+        agent_id: &'a AgentId,
+        room_id: Uuid,
+    },
+}
+
+impl<'a> RequestParams<'a> {
+    /// The room the request is limited to by a share token.
+    pub fn shared_room_id(&self) -> Option<Uuid> {
+        match self {
+            RequestParams::Shared { room_id, .. } => Some(*room_id),
+            _ => None,
+        }
+    }
 }
 
 impl<'a> Addressable for RequestParams<'a> {
@@ -153,6 +452,7 @@ impl<'a> Addressable for RequestParams<'a> {
         match self {
             RequestParams::Http { agent_id } => agent_id,
             RequestParams::MqttParams(reqp) => reqp.as_agent_id(),
+            RequestParams::Shared { agent_id, .. } => agent_id,
         }
     }
 }
@@ -162,6 +462,218 @@ impl<'a> Authenticable for RequestParams<'a> {
         match self {
             RequestParams::Http { agent_id } => agent_id.as_account_id(),
             RequestParams::MqttParams(reqp) => reqp.as_account_id(),
+            RequestParams::Shared { agent_id, .. } => agent_id.as_account_id(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use prometheus::Registry;
+
+    use crate::test_helpers::context::build_config;
+    use crate::test_helpers::outgoing_envelope::OutgoingEnvelopeProperties;
+    use crate::test_helpers::prelude::*;
+
+    use super::*;
+
+    fn build_response() -> Response {
+        let now = Utc::now();
+        let mut response = Response::new(StatusCode::OK, json!({}), now, None);
+        response
+            .add_notification("event.create", "rooms/1/events", json!({"n": 1}), now)
+            .expect("Failed to add notification");
+        response
+            .add_notification("room.update", "rooms/2/events", json!({"n": 2}), now)
+            .expect("Failed to add notification");
+        response
+            .add_notification("event.create", "rooms/1/events", json!({"n": 3}), now)
+            .expect("Failed to add notification");
+        response
+            .add_notification("event.create", "rooms/1/events", json!({"n": 4}), now)
+            .expect("Failed to add notification");
+        response
+    }
+
+    async fn publish(batching: NotificationBatchingConfig) -> Vec<(String, String, Value)> {
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let reqp = build_reqp(agent.agent_id(), "event.create");
+        let messages = build_response()
+            .into_mqtt_messages(&reqp, &batching)
+            .expect("Failed to build messages");
+
+        parse_messages(messages)
+            .await
+            .into_iter()
+            .filter_map(|message| match message.properties() {
+                OutgoingEnvelopeProperties::Event(evp) => Some((
+                    evp.label().to_owned(),
+                    message.topic().to_owned(),
+                    message.payload::<Value>(),
+                )),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn room_broadcasts() {
+        let now = Utc::now();
+        let room_id = Uuid::new_v4();
+        let mut response = Response::new(StatusCode::OK, json!({}), now, None);
+        let path = format!("rooms/{room_id}/events");
+        response
+            .add_notification("event.create", &path, json!({"n": 1}), now)
+            .expect("Failed to add notification");
+        response
+            .add_notification("room.update", "audiences/x/events", json!({"n": 2}), now)
+            .expect("Failed to add notification");
+
+        let config = build_config(None);
+
+        let broadcasts = response
+            .notifications()
+            .room_broadcasts(&config)
+            .map(|n| serde_json::to_value(n).unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            broadcasts,
+            vec![json!({"room_id": room_id, "label": "event.create", "payload": {"n": 1}})]
+        );
+    }
+
+    #[tokio::test]
+    async fn notifications_without_batching() {
+        let events = publish(NotificationBatchingConfig::default()).await;
+        assert_eq!(events.len(), 4);
+        assert!(!events.iter().any(|e| e.0 == "notification.batch"));
+    }
+
+    #[tokio::test]
+    async fn notifications_batched_by_topic() {
+        let events = publish(NotificationBatchingConfig {
+            enabled: true,
+            max_size: 2,
+        })
+        .await;
+
+        assert_eq!(events.len(), 3);
+
+        let (label, topic, payload) = &events[0];
+        assert_eq!(label, "notification.batch");
+        assert!(topic.ends_with("rooms/1/events"));
+        assert_eq!(
+            payload,
+            &json!([
+                {"label": "event.create", "payload": {"n": 1}},
+                {"label": "event.create", "payload": {"n": 3}},
+            ])
+        );
+
+        let (label, topic, payload) = &events[1];
+        assert_eq!(label, "event.create");
+        assert!(topic.ends_with("rooms/1/events"));
+        assert_eq!(payload, &json!({"n": 4}));
+
+        let (label, topic, _) = &events[2];
+        assert_eq!(label, "room.update");
+        assert!(topic.ends_with("rooms/2/events"));
+    }
+
+    fn limit_size(policy: OversizedNotificationPolicy) -> (Response, MessageStream) {
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let metrics = Metrics::new(&Registry::new()).expect("Failed to build metrics");
+        let now = Utc::now();
+
+        let mut response = Response::new(StatusCode::OK, json!({}), now, None);
+        response
+            .add_notification("event.create", "rooms/1/events", json!({"id": 1}), now)
+            .expect("Failed to add notification");
+        let payload = json!({"id": 2, "data": "x".repeat(100)});
+        response
+            .add_notification("event.create", "rooms/1/events", payload, now)
+            .expect("Failed to add notification");
+
+        let config = NotificationSizeConfig {
+            max_size: Some(64),
+            policy,
+        };
+
+        response.limit_notification_size(&config, &metrics, agent.agent_id());
+        let oversized = std::mem::take(&mut response.async_tasks).into_stream();
+        (response, Box::new(oversized))
+    }
+
+    #[tokio::test]
+    async fn oversized_notification_rejected() {
+        let (response, messages) = limit_size(OversizedNotificationPolicy::Reject);
+        let payloads = response
+            .notifications()
+            .0
+            .iter()
+            .map(|n| n.payload.clone())
+            .collect::<Vec<_>>();
+
+        assert_eq!(payloads, vec![json!({"id": 1})]);
+
+        let messages = parse_messages(messages).await;
+        assert_eq!(messages.len(), 1);
+
+        let payload = messages[0].payload::<Value>();
+        assert!(messages[0].topic().ends_with("rooms/1/events"));
+        assert_eq!(payload["agent_id"], format!("web.user123.{USR_AUDIENCE}"));
+        assert_eq!(payload["label"], "event.create");
+        assert_eq!(payload["max_size"], 64);
+        assert_eq!(payload["action"], "rejected");
+    }
+
+    #[tokio::test]
+    async fn oversized_notification_truncated() {
+        let (response, messages) = limit_size(OversizedNotificationPolicy::Truncate);
+        let payloads = response
+            .notifications()
+            .0
+            .iter()
+            .map(|n| n.payload.clone())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            payloads,
+            vec![json!({"id": 1}), json!({"id": 2, "truncated": true})]
+        );
+
+        let messages = parse_messages(messages).await;
+        assert_eq!(messages[0].payload::<Value>()["action"], "truncated");
+    }
+
+    #[tokio::test]
+    async fn notifications_carry_local_tracking_label() {
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let mut reqp_json = serde_json::to_value(build_reqp(agent.agent_id(), "event.create"))
+            .expect("Failed to serialize reqp");
+
+        reqp_json["local_tracking_label"] = json!("client-label");
+
+        let reqp = serde_json::from_value::<IncomingRequestProperties>(reqp_json)
+            .expect("Failed to parse reqp");
+
+        for batching in [
+            NotificationBatchingConfig::default(),
+            NotificationBatchingConfig {
+                enabled: true,
+                max_size: 2,
+            },
+        ] {
+            let messages = build_response()
+                .into_mqtt_messages(&reqp, &batching)
+                .expect("Failed to build messages");
+
+            for message in parse_messages(messages).await {
+                if let OutgoingEnvelopeProperties::Event(evp) = message.properties() {
+                    assert_eq!(evp.local_tracking_label(), Some("client-label"));
+                }
+            }
         }
     }
 }
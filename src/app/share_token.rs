@@ -0,0 +1,175 @@
+//! Read-only room access tokens for viewers without accounts, e.g. recorded lessons
+//! embedded in public pages.
+//!
+//! A token is `room_id.expires_at.signature` where `expires_at` is a unix time in seconds
+//! and `signature` is hex encoded HMAC-SHA256 of the first two parts signed with
+//! `share.secret` from the config. Tokens aren't stored so they can't be revoked one by one,
+//! only all at once by changing the secret.
+
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context as AnyhowContext, Result};
+use axum::{async_trait, extract::FromRequestParts, http::request::Parts};
+use chrono::{DateTime, SubsecRound, TimeZone, Utc};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use svc_agent::{AccountId, AgentId};
+use uuid::Uuid;
+
+use crate::app::context::{AppContext, GlobalContext};
+use crate::app::error::{Error as AppError, ErrorExt, ErrorKind as AppErrorKind};
+
+/// Header carrying the token in HTTP requests.
+pub const SHARE_TOKEN_HEADER: &str = "x-share-token";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShareToken {
+    room_id: Uuid,
+    expires_at: DateTime<Utc>,
+}
+
+impl ShareToken {
+    pub fn new(room_id: Uuid, expires_at: DateTime<Utc>) -> Self {
+        Self {
+            room_id,
+            expires_at: expires_at.trunc_subsecs(0),
+        }
+    }
+
+    pub fn room_id(&self) -> Uuid {
+        self.room_id
+    }
+
+    pub fn expires_at(&self) -> DateTime<Utc> {
+        self.expires_at
+    }
+
+    pub fn sign(&self, secret: &str) -> String {
+        let claims = self.claims();
+        let signature = hex::encode(mac(secret, &claims).finalize().into_bytes());
+        format!("{claims}.{signature}")
+    }
+
+    /// Parses the token and checks its signature and expiration.
+    pub fn verify(token: &str, secret: &str, now: DateTime<Utc>) -> Result<Self> {
+        let (claims, signature) = token.rsplit_once('.').context("Malformed share token")?;
+        let signature = hex::decode(signature).context("Malformed share token signature")?;
+
+        mac(secret, claims)
+            .verify_slice(&signature)
+            .map_err(|_| anyhow!("Share token signature mismatch"))?;
+
+        let (room_id, expires_at) = claims.split_once('.').context("Malformed share token")?;
+        let room_id = room_id.parse().context("Malformed share token room id")?;
+
+        let expires_at = expires_at
+            .parse()
+            .ok()
+            .and_then(|secs| Utc.timestamp_opt(secs, 0).single())
+            .context("Malformed share token expiration")?;
+
+        if expires_at <= now {
+            return Err(anyhow!("Share token expired at {}", expires_at));
+        }
+
+        Ok(Self {
+            room_id,
+            expires_at,
+        })
+    }
+
+    /// Agent the requests made with the token are attributed to.
+    pub fn viewer_agent_id(service_account_id: &AccountId) -> AgentId {
+        AgentId::new(
+            "share",
+            AccountId::new("anonymous", service_account_id.audience()),
+        )
+    }
+
+    fn claims(&self) -> String {
+        format!("{}.{}", self.room_id, self.expires_at.timestamp())
+    }
+}
+
+fn mac(secret: &str, claims: &str) -> Hmac<Sha256> {
+    // HMAC accepts keys of any length.
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("Invalid HMAC key");
+    mac.update(claims.as_bytes());
+    mac
+}
+
+/// Extracts a valid share token from the `x-share-token` header.
+pub struct ShareTokenExtractor(pub ShareToken);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ShareTokenExtractor {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let context = parts
+            .extensions
+            .get::<Arc<AppContext>>()
+            .context("Missing app context")
+            .error(AppErrorKind::InternalServerError)?;
+
+        let config = context.config();
+
+        let share = config
+            .share
+            .as_ref()
+            .context("Room sharing is not configured")
+            .error(AppErrorKind::SharingDisabled)?;
+
+        let token = parts
+            .headers
+            .get(SHARE_TOKEN_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .context("Missing share token")
+            .error(AppErrorKind::InvalidShareToken)?;
+
+        ShareToken::verify(token, &share.secret, Utc::now())
+            .map(Self)
+            .error(AppErrorKind::InvalidShareToken)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+
+    #[test]
+    fn verify_token() {
+        let now = Utc::now();
+        let token = ShareToken::new(Uuid::new_v4(), now + Duration::hours(1));
+        let signed = token.sign("secret");
+
+        assert_eq!(
+            ShareToken::verify(&signed, "secret", now).expect("Failed to verify token"),
+            token
+        );
+
+        // Signed with another secret.
+        assert!(ShareToken::verify(&signed, "another", now).is_err());
+
+        // Expired.
+        assert!(ShareToken::verify(&signed, "secret", now + Duration::hours(2)).is_err());
+
+        // Issued for another room.
+        let (_, rest) = signed.split_once('.').unwrap();
+        let forged = format!("{}.{}", Uuid::new_v4(), rest);
+        assert!(ShareToken::verify(&forged, "secret", now).is_err());
+
+        // Prolonged.
+        let forged = signed.replace(
+            &token.expires_at().timestamp().to_string(),
+            &(token.expires_at() + Duration::days(1))
+                .timestamp()
+                .to_string(),
+        );
+        assert!(ShareToken::verify(&forged, "secret", now).is_err());
+    }
+}
@@ -0,0 +1,302 @@
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, Duration, DurationRound, Timelike, Utc};
+use sqlx::postgres::PgPool as Db;
+use tokio::{sync::watch, task::JoinHandle};
+use tracing::{error, info, warn};
+
+use crate::app::jobs::JobRegistry;
+use crate::app::operations::vacuum;
+use crate::app::sentry;
+use crate::config::VacuumConfig;
+use crate::db;
+use crate::metrics::Metrics;
+
+/// Advisory lock key to make sure that only one replica runs vacuum at a time.
+const VACUUM_LOCK_KEY: i64 = 0x6576_656e_745f_7661; // "event_va"
+
+/// Runs vacuum by the cron `schedule` from the config until shutdown.
+pub fn run(
+    db: Db,
+    metrics: Arc<Metrics>,
+    jobs: Arc<JobRegistry>,
+    config: VacuumConfig,
+    mut shutdown_rx: watch::Receiver<()>,
+) -> Result<Option<JoinHandle<()>>> {
+    let schedule = match config.schedule {
+        Some(ref spec) => spec
+            .parse::<Schedule>()
+            .context("Failed to parse vacuum schedule")?,
+        None => return Ok(None),
+    };
+
+    let handle = tokio::spawn(async move {
+        loop {
+            let now = Utc::now();
+
+            let delay = match schedule.next_after(now) {
+                Some(next) => (next - now).to_std().unwrap_or_default(),
+                None => {
+                    warn!("Vacuum schedule never fires, stopping the scheduler");
+                    return;
+                }
+            };
+
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {}
+                _ = shutdown_rx.changed() => return,
+            }
+
+            if let Err(err) = run_exclusively(&db, &metrics, &jobs, &config).await {
+                error!("Scheduled vacuum failed: {:?}", err);
+
+                sentry::send(&err, &[]);
+            }
+        }
+    });
+
+    Ok(Some(handle))
+}
+
+async fn run_exclusively(
+    db: &Db,
+    metrics: &Metrics,
+    jobs: &JobRegistry,
+    config: &VacuumConfig,
+) -> Result<()> {
+    let mut conn = db
+        .acquire()
+        .await
+        .context("Failed to acquire db connection")?;
+
+    let locked = db::advisory_lock::TryLockQuery::new(VACUUM_LOCK_KEY)
+        .execute(&mut conn)
+        .await
+        .context("Failed to take vacuum lock")?;
+
+    if !locked {
+        info!("Vacuum is running on another replica, skipping");
+        return Ok(());
+    }
+
+    let job_id = jobs.start("scheduled_vacuum", None);
+    let result = vacuum(db, metrics, config).await.map(|_| ());
+    jobs.finish(job_id, &result);
+
+    // Release the lock even if vacuum failed not to block other replicas.
+    db::advisory_lock::UnlockQuery::new(VACUUM_LOCK_KEY)
+        .execute(&mut conn)
+        .await
+        .context("Failed to release vacuum lock")?;
+
+    result
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// A cron expression of 5 fields in UTC: minute, hour, day of month, month, day of week.
+///
+/// Each field is `*`, a number, a range `a-b` or a comma separated list of them
+/// optionally followed by a step, e.g. `*/15`, `1-5`, `0,30`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Schedule {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    // Like in cron day of month and day of week are OR'ed when both are restricted.
+    any_day_of_month: bool,
+    any_day_of_week: bool,
+}
+
+impl Schedule {
+    /// Returns the first matching minute strictly after `time`.
+    pub fn next_after(&self, time: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut candidate = time.duration_trunc(Duration::minutes(1)).ok()? + Duration::minutes(1);
+
+        // Any valid schedule fires at least once in 4 years because of Feb 29.
+        let limit = candidate + Duration::days(4 * 366);
+
+        while candidate < limit {
+            if self.matches(candidate) {
+                return Some(candidate);
+            }
+
+            candidate += Duration::minutes(1);
+        }
+
+        None
+    }
+
+    fn matches(&self, time: DateTime<Utc>) -> bool {
+        let day_of_month = has_bit(self.days_of_month, time.day());
+        let day_of_week = has_bit(self.days_of_week, time.weekday().num_days_from_sunday());
+
+        let day = match (self.any_day_of_month, self.any_day_of_week) {
+            (false, false) => day_of_month || day_of_week,
+            _ => day_of_month && day_of_week,
+        };
+
+        day && has_bit(self.minutes, time.minute())
+            && has_bit(self.hours, time.hour())
+            && has_bit(self.months, time.month())
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = anyhow::Error;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let fields = spec.split_whitespace().collect::<Vec<_>>();
+
+        if fields.len() != 5 {
+            bail!("Expected 5 fields in cron expression, got {}", fields.len());
+        }
+
+        let mut days_of_week = parse_field(fields[4], 0, 7).context("Invalid day of week")?;
+
+        // Both 0 and 7 stand for Sunday.
+        if has_bit(days_of_week, 7) {
+            days_of_week |= 1;
+        }
+
+        Ok(Self {
+            minutes: parse_field(fields[0], 0, 59).context("Invalid minute")?,
+            hours: parse_field(fields[1], 0, 23).context("Invalid hour")?,
+            days_of_month: parse_field(fields[2], 1, 31).context("Invalid day of month")?,
+            months: parse_field(fields[3], 1, 12).context("Invalid month")?,
+            days_of_week,
+            any_day_of_month: fields[2] == "*",
+            any_day_of_week: fields[4] == "*",
+        })
+    }
+}
+
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64> {
+    let mut bits = 0;
+
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().context("Invalid step")?),
+            None => (part, 1),
+        };
+
+        if step == 0 {
+            bail!("Step must be positive");
+        }
+
+        let (start, end) = match range {
+            "*" => (min, max),
+            range => match range.split_once('-') {
+                Some((start, end)) => (start.parse::<u32>()?, end.parse::<u32>()?),
+                None => {
+                    let value = range.parse::<u32>()?;
+                    (value, value)
+                }
+            },
+        };
+
+        if start < min || end > max || start > end {
+            bail!("Value is out of range {}-{}: {}", min, max, part);
+        }
+
+        for value in (start..=end).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+
+    Ok(bits)
+}
+
+fn has_bit(bits: u64, value: u32) -> bool {
+    bits & (1 << value) != 0
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn time(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap()
+    }
+
+    #[test]
+    fn parse_schedule() {
+        assert!("* * * * *".parse::<Schedule>().is_ok());
+        assert!("*/15 0-6,22 1 */2 1-5".parse::<Schedule>().is_ok());
+        assert!("* * * *".parse::<Schedule>().is_err());
+        assert!("60 * * * *".parse::<Schedule>().is_err());
+        assert!("*/0 * * * *".parse::<Schedule>().is_err());
+        assert!("5-1 * * * *".parse::<Schedule>().is_err());
+        assert!("a * * * *".parse::<Schedule>().is_err());
+    }
+
+    #[test]
+    fn next_daily() {
+        let schedule = "30 3 * * *".parse::<Schedule>().unwrap();
+
+        assert_eq!(
+            schedule.next_after(time(2023, 8, 1, 1, 0)),
+            Some(time(2023, 8, 1, 3, 30))
+        );
+
+        assert_eq!(
+            schedule.next_after(time(2023, 8, 1, 3, 30)),
+            Some(time(2023, 8, 2, 3, 30))
+        );
+    }
+
+    #[test]
+    fn next_with_step() {
+        let schedule = "*/15 * * * *".parse::<Schedule>().unwrap();
+
+        assert_eq!(
+            schedule.next_after(time(2023, 8, 1, 1, 7)),
+            Some(time(2023, 8, 1, 1, 15))
+        );
+
+        assert_eq!(
+            schedule.next_after(time(2023, 8, 1, 1, 45)),
+            Some(time(2023, 8, 1, 2, 0))
+        );
+    }
+
+    #[test]
+    fn next_with_day_of_week() {
+        // 2023-08-01 is Tuesday.
+        let schedule = "0 0 * * 0".parse::<Schedule>().unwrap();
+
+        assert_eq!(
+            schedule.next_after(time(2023, 8, 1, 0, 0)),
+            Some(time(2023, 8, 6, 0, 0))
+        );
+
+        let schedule = "0 0 * * 7".parse::<Schedule>().unwrap();
+
+        assert_eq!(
+            schedule.next_after(time(2023, 8, 1, 0, 0)),
+            Some(time(2023, 8, 6, 0, 0))
+        );
+
+        // Either the 3rd or Sunday.
+        let schedule = "0 0 3 * 0".parse::<Schedule>().unwrap();
+
+        assert_eq!(
+            schedule.next_after(time(2023, 8, 1, 0, 0)),
+            Some(time(2023, 8, 3, 0, 0))
+        );
+    }
+
+    #[test]
+    fn never_fires() {
+        let schedule = "0 0 31 2 *".parse::<Schedule>().unwrap();
+        assert_eq!(schedule.next_after(time(2023, 8, 1, 0, 0)), None);
+    }
+}
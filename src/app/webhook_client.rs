@@ -0,0 +1,205 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use arc_swap::ArcSwap;
+
+use chrono::{serde::ts_milliseconds, DateTime, Utc};
+use hmac::{Hmac, Mac};
+#[cfg(test)]
+use mockall::automock;
+use reqwest::header;
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use sha2::Sha256;
+use tracing::{error, warn};
+use uuid::Uuid;
+
+use crate::config::{Config, WebhookTarget};
+use crate::metrics::Metrics;
+
+/// Header carrying hex encoded HMAC-SHA256 of the request body signed with the audience's secret.
+const SIGNATURE_HEADER: &str = "x-event-signature";
+
+/// A callback about a room lifecycle event sent to a tenant's URL.
+#[derive(Clone, Debug, Serialize)]
+pub struct Webhook {
+    /// Stays the same on retries so tenants may deduplicate deliveries.
+    id: Uuid,
+    event: String,
+    payload: JsonValue,
+    #[serde(with = "ts_milliseconds")]
+    created_at: DateTime<Utc>,
+}
+
+impl Webhook {
+    pub fn new(event: &str, payload: impl Serialize) -> Self {
+        let payload = serde_json::to_value(payload).unwrap_or_else(|err| {
+            error!("Failed to serialize {} webhook payload: {:?}", event, err);
+            JsonValue::Null
+        });
+
+        Self {
+            id: Uuid::new_v4(),
+            event: event.to_owned(),
+            payload,
+            created_at: Utc::now(),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn event(&self) -> &str {
+        &self.event
+    }
+
+    #[cfg(test)]
+    pub fn payload(&self) -> &JsonValue {
+        &self.payload
+    }
+}
+
+#[cfg_attr(test, automock)]
+pub trait WebhookClient: Sync + Send {
+    /// Schedules delivery of the webhook in background if the audience has a URL configured.
+    fn send(&self, audience: &str, webhook: Webhook);
+}
+
+pub struct HttpWebhookClient {
+    http: reqwest::Client,
+    /// Shared with the app context to pick up reloaded targets.
+    config: Arc<ArcSwap<Config>>,
+    metrics: Arc<Metrics>,
+}
+
+impl HttpWebhookClient {
+    pub fn new(config: Arc<ArcSwap<Config>>, metrics: Arc<Metrics>) -> anyhow::Result<Self> {
+        let http = reqwest::Client::builder()
+            .user_agent(format!("event-{}", crate::APP_VERSION))
+            .build()?;
+
+        Ok(Self {
+            http,
+            config,
+            metrics,
+        })
+    }
+}
+
+impl WebhookClient for HttpWebhookClient {
+    fn send(&self, audience: &str, webhook: Webhook) {
+        let config = self.config.load();
+
+        let target = match config.webhooks.audiences.get(audience) {
+            Some(target) => target.to_owned(),
+            None => return,
+        };
+
+        let delivery = Delivery {
+            http: self.http.clone(),
+            target,
+            max_attempts: config.webhooks.max_attempts,
+            retry_interval: config.webhooks.retry_interval,
+            timeout: config.webhooks.timeout,
+            metrics: self.metrics.clone(),
+        };
+
+        tokio::spawn(delivery.run(webhook));
+    }
+}
+
+struct Delivery {
+    http: reqwest::Client,
+    target: WebhookTarget,
+    max_attempts: u32,
+    retry_interval: Duration,
+    timeout: Duration,
+    metrics: Arc<Metrics>,
+}
+
+impl Delivery {
+    async fn run(self, webhook: Webhook) {
+        let body = match serde_json::to_vec(&webhook) {
+            Ok(body) => body,
+            Err(err) => {
+                error!(webhook_id = %webhook.id, "Failed to serialize webhook: {:?}", err);
+                return;
+            }
+        };
+
+        let signature = sign(&self.target.secret, &body);
+
+        for attempt in 1..=self.max_attempts {
+            let result = self
+                .http
+                .post(&self.target.url)
+                .timeout(self.timeout)
+                .header(header::CONTENT_TYPE, "application/json")
+                .header(SIGNATURE_HEADER, &signature)
+                .body(body.clone())
+                .send()
+                .await
+                .and_then(|resp| resp.error_for_status());
+
+            match result {
+                Ok(_) => {
+                    self.observe(&webhook, "delivered");
+                    return;
+                }
+                Err(err) => {
+                    warn!(
+                        webhook_id = %webhook.id,
+                        event = %webhook.event,
+                        attempt,
+                        "Failed to deliver webhook: {}",
+                        err
+                    );
+
+                    self.observe(&webhook, "retried");
+                }
+            }
+
+            if attempt < self.max_attempts {
+                tokio::time::sleep(self.retry_interval * 2u32.pow(attempt - 1)).await;
+            }
+        }
+
+        error!(
+            webhook_id = %webhook.id,
+            event = %webhook.event,
+            "Giving up webhook delivery after {} attempts",
+            self.max_attempts
+        );
+
+        self.observe(&webhook, "failed");
+    }
+
+    fn observe(&self, webhook: &Webhook, status: &str) {
+        if let Ok(counter) = self
+            .metrics
+            .webhook_deliveries
+            .get_metric_with_label_values(&[&webhook.event, status])
+        {
+            counter.inc();
+        }
+    }
+}
+
+fn sign(secret: &str, body: &[u8]) -> String {
+    // HMAC accepts keys of any length.
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("Invalid HMAC key");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sign_body() {
+        // Verified with `echo -n '{}' | openssl dgst -sha256 -hmac secret`.
+        assert_eq!(
+            sign("secret", b"{}"),
+            "sha256=77325902caca812dc259733aacd046b73817372c777b8d95b402647474516e13"
+        );
+    }
+}
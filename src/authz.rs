@@ -1,15 +1,22 @@
+use std::num::NonZeroUsize;
 use std::sync::Arc;
+use std::time::{Duration as StdDuration, Instant};
 
 use chrono::Duration;
-use svc_agent::Authenticable;
+use lru::LruCache;
+use parking_lot::Mutex;
+use svc_agent::{AccountId, Authenticable};
 use svc_authz::{ClientMap, Error, IntentObject};
 
+use crate::config::AuthzDecisionCacheConfig;
+use crate::db::room::Object as Room;
 use crate::metrics::Metrics;
 
 #[derive(Clone)]
 pub struct Authz {
     metrics: Arc<Metrics>,
     client_map: Arc<ClientMap>,
+    decision_cache: Option<Arc<DecisionCache>>,
 }
 
 impl Authz {
@@ -17,6 +24,14 @@ impl Authz {
         Self {
             metrics,
             client_map: Arc::new(client_map),
+            decision_cache: None,
+        }
+    }
+
+    pub fn decision_cache(self, config: &AuthzDecisionCacheConfig) -> Self {
+        Self {
+            decision_cache: Some(Arc::new(DecisionCache::new(config.ttl, config.capacity))),
+            ..self
         }
     }
 
@@ -30,10 +45,69 @@ impl Authz {
     where
         A: Authenticable,
     {
+        let cached = match self.decision_cache {
+            Some(ref cache) => {
+                let key = DecisionKey {
+                    audience: audience.clone(),
+                    account_id: subject.as_account_id().to_owned(),
+                    object: object.to_vec(),
+                    action: action.clone(),
+                };
+
+                if cache.contains(&key) {
+                    self.metrics.authz_decision_cache_hit.inc();
+                    return Ok(Duration::zero());
+                }
+
+                self.metrics.authz_decision_cache_miss.inc();
+                Some((cache, key))
+            }
+            None => None,
+        };
+
         let _timer = self.metrics.authorization_time.start_timer();
-        self.client_map
+
+        let result = self
+            .client_map
             .authorize(audience, subject, object, action)
-            .await
+            .await;
+
+        // Only allow decisions get cached so that a denial is never stuck.
+        if let (Ok(_), Some((cache, key))) = (&result, cached) {
+            cache.insert(key);
+        }
+
+        result
+    }
+
+    /// Authorizes against the room's audience and then the co-hosting one if any
+    /// so either tenant may grant access. The first audience's error is returned on denial.
+    pub async fn authorize_room<A>(
+        &self,
+        room: &Room,
+        subject: A,
+        object: Box<dyn IntentObject>,
+        action: String,
+    ) -> Result<Duration, Error>
+    where
+        A: Authenticable + Clone,
+    {
+        let result = self
+            .authorize(
+                room.audience().into(),
+                subject.clone(),
+                object.box_clone(),
+                action.clone(),
+            )
+            .await;
+
+        match (result, room.secondary_audience()) {
+            (Err(err), Some(audience)) => self
+                .authorize(audience.into(), subject, object, action)
+                .await
+                .map_err(|_| err),
+            (result, _) => result,
+        }
     }
 
     pub async fn ban<A>(
@@ -47,8 +121,165 @@ impl Authz {
     where
         A: Authenticable,
     {
+        self.invalidate(subject.as_account_id());
+
         self.client_map
             .ban(audience, subject, object, value, seconds)
             .await
     }
+
+    /// Drops cached decisions of the account, e.g. when it gets banned.
+    pub fn invalidate(&self, account_id: &AccountId) {
+        if let Some(ref cache) = self.decision_cache {
+            cache.invalidate(account_id);
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct DecisionKey {
+    audience: String,
+    account_id: AccountId,
+    object: Vec<String>,
+    action: String,
+}
+
+/// Longest time a cached decision lives regardless of the configured TTL.
+const MAX_DECISION_TTL: StdDuration = StdDuration::from_secs(30);
+
+/// Allow decisions cached for a short time.
+///
+/// Invalidation on ban only reaches the cache of the replica handling it. Other replicas
+/// keep allowing the banned account or the one whose role changed until the entry expires
+/// so the TTL is capped with `MAX_DECISION_TTL`.
+///
+/// Entries are kept in LRU order so a full cache evicts the least recently used one.
+struct DecisionCache {
+    ttl: StdDuration,
+    entries: Option<Mutex<LruCache<DecisionKey, Instant>>>,
+}
+
+impl DecisionCache {
+    fn new(ttl: StdDuration, capacity: usize) -> Self {
+        Self {
+            ttl: ttl.min(MAX_DECISION_TTL),
+            entries: NonZeroUsize::new(capacity).map(|cap| Mutex::new(LruCache::new(cap))),
+        }
+    }
+
+    fn contains(&self, key: &DecisionKey) -> bool {
+        let mut entries = match self.entries {
+            Some(ref entries) => entries.lock(),
+            None => return false,
+        };
+
+        match entries.get(key) {
+            Some(cached_at) if cached_at.elapsed() < self.ttl => true,
+            Some(_) => {
+                entries.pop(key);
+                false
+            }
+            None => false,
+        }
+    }
+
+    fn insert(&self, key: DecisionKey) {
+        if let Some(ref entries) = self.entries {
+            entries.lock().put(key, Instant::now());
+        }
+    }
+
+    fn invalidate(&self, account_id: &AccountId) {
+        let mut entries = match self.entries {
+            Some(ref entries) => entries.lock(),
+            None => return,
+        };
+
+        let keys = entries
+            .iter()
+            .filter(|(key, _)| key.account_id == *account_id)
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+
+        for key in keys {
+            entries.pop(&key);
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(account_label: &str, action: &str) -> DecisionKey {
+        DecisionKey {
+            audience: "dev.usr.example.org".to_owned(),
+            account_id: AccountId::new(account_label, "dev.usr.example.org"),
+            object: vec!["classrooms".to_owned()],
+            action: action.to_owned(),
+        }
+    }
+
+    #[test]
+    fn cache_decision() {
+        let cache = DecisionCache::new(StdDuration::from_secs(60), 10);
+        assert!(!cache.contains(&key("user123", "read")));
+
+        cache.insert(key("user123", "read"));
+        assert!(cache.contains(&key("user123", "read")));
+        assert!(!cache.contains(&key("user123", "update")));
+        assert!(!cache.contains(&key("user456", "read")));
+    }
+
+    #[test]
+    fn expire_decision() {
+        let cache = DecisionCache::new(StdDuration::ZERO, 10);
+        cache.insert(key("user123", "read"));
+        assert!(!cache.contains(&key("user123", "read")));
+    }
+
+    #[test]
+    fn cap_decision_ttl() {
+        let cache = DecisionCache::new(StdDuration::from_secs(3600), 10);
+        assert_eq!(cache.ttl, MAX_DECISION_TTL);
+    }
+
+    #[test]
+    fn evict_least_recently_used_decision() {
+        let cache = DecisionCache::new(StdDuration::from_secs(60), 2);
+        cache.insert(key("user123", "read"));
+        cache.insert(key("user123", "update"));
+
+        // Reading the older entry makes `update` the least recently used one.
+        assert!(cache.contains(&key("user123", "read")));
+        cache.insert(key("user123", "create"));
+
+        assert!(cache.contains(&key("user123", "read")));
+        assert!(!cache.contains(&key("user123", "update")));
+        assert!(cache.contains(&key("user123", "create")));
+    }
+
+    #[test]
+    fn disable_decision_cache_with_zero_capacity() {
+        let cache = DecisionCache::new(StdDuration::from_secs(60), 0);
+        cache.insert(key("user123", "read"));
+        assert!(!cache.contains(&key("user123", "read")));
+    }
+
+    #[test]
+    fn invalidate_account_decisions() {
+        let cache = DecisionCache::new(StdDuration::from_secs(60), 10);
+        cache.insert(key("user123", "read"));
+        cache.insert(key("user123", "update"));
+        cache.insert(key("user456", "read"));
+
+        cache.invalidate(&AccountId::new("user123", "dev.usr.example.org"));
+        assert!(!cache.contains(&key("user123", "read")));
+        assert!(!cache.contains(&key("user123", "update")));
+        assert!(cache.contains(&key("user456", "read")));
+    }
 }
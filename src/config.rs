@@ -1,12 +1,18 @@
+use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::num::NonZeroU32;
 use std::time::Duration as StdDuration;
 
 use chrono::Duration;
 use serde_derive::Deserialize;
+use serde_json::Value as JsonValue;
 use svc_agent::{mqtt::AgentConfig, AccountId};
 use svc_authn::jose::{Algorithm, ConfigMap};
 use svc_authz::ConfigMap as Authz;
 use svc_error::extension::sentry::Config as SentryConfig;
+use uuid::Uuid;
+
+use crate::db::room::{ClassType, Settings as RoomSettings};
 
 const DEFAULT_BAN_DUR_SECS: u64 = 5 * 3600;
 
@@ -18,6 +24,7 @@ pub struct Config {
     pub broker_id: AccountId,
     pub authn: ConfigMap,
     pub authz: Authz,
+    pub authz_decision_cache: Option<AuthzDecisionCacheConfig>,
     pub http_addr: SocketAddr,
     pub mqtt: AgentConfig,
     pub sentry: Option<SentryConfig>,
@@ -25,27 +32,499 @@ pub struct Config {
     ban_duration_s: Option<u64>,
     #[serde(default)]
     pub vacuum: VacuumConfig,
+    #[serde(default)]
+    pub dump: DumpConfig,
+    #[serde(default)]
+    pub mqtt_queue: QueueConfig,
     pub http_broker_client: HttpBrokerClientConfig,
     pub constraint: Constraint,
     pub adjust: AdjustConfig,
     pub nats: Option<svc_nats_client::Config>,
     pub nats_consumer: Option<NatsConsumer>,
+    pub nats_responder: Option<NatsResponder>,
+    #[serde(default)]
+    pub redaction: Vec<RedactionRule>,
+    #[serde(default)]
+    pub webhooks: WebhooksConfig,
+    #[serde(default)]
+    pub auto_close: AutoCloseConfig,
+    #[serde(default)]
+    pub inactivity_alert: InactivityAlertConfig,
+    #[serde(default)]
+    pub derived_room_cleanup: DerivedRoomCleanupConfig,
+    #[serde(default)]
+    pub lock_schedule: LockScheduleConfig,
+    #[serde(default)]
+    pub notification_batching: NotificationBatchingConfig,
+    #[serde(default)]
+    pub notification_size: NotificationSizeConfig,
+    #[serde(default)]
+    pub presence_check: PresenceCheckConfig,
+    #[serde(default)]
+    pub room_cache: RoomCacheConfig,
+    #[serde(default)]
+    pub crdt: CrdtConfig,
+    #[serde(default)]
+    pub room_capacity: RoomCapacityConfig,
+    #[serde(default)]
+    pub presence: PresenceConfig,
+    #[serde(default)]
+    pub redis_bridge: RedisBridgeConfig,
+    #[serde(default)]
+    pub erasure: ErasureConfig,
+    /// Defaults of rooms by their class type, e.g. `[class_types.minigroup]`.
+    #[serde(default)]
+    pub class_types: HashMap<ClassType, ClassTypeConfig>,
+    pub background_db: Option<BackgroundDbConfig>,
+    pub share: Option<ShareConfig>,
+    pub content_filter: Option<ContentFilterConfig>,
+    pub data_encryption: Option<DataEncryptionConfig>,
+    #[serde(default)]
+    pub attachments: AttachmentsConfig,
+    #[serde(default)]
+    pub polls: PollsConfig,
+    /// Notification topics by audience, e.g. `[topics."brand.example.org"]`.
+    #[serde(default)]
+    pub topics: HashMap<String, TopicsConfig>,
 }
 
 impl Config {
     pub fn ban_duration(&self) -> u64 {
         self.ban_duration_s.unwrap_or(DEFAULT_BAN_DUR_SECS)
     }
+
+    /// Adjustment settings of a room of the class type.
+    pub fn adjust_for(&self, kind: ClassType) -> AdjustConfig {
+        let mut adjust = self.adjust.clone();
+
+        if let Some(thinning) = self
+            .class_types
+            .get(&kind)
+            .and_then(|c| c.thinning.as_ref())
+        {
+            adjust.thinning = thinning.clone();
+        }
+
+        adjust
+    }
+
+    /// Topic of notifications about the room in the audience.
+    pub fn room_topic(&self, audience: &str, room_id: Uuid) -> String {
+        self.topics_for(audience).room_topic(audience, room_id)
+    }
+
+    /// Topic of notifications to the whole audience.
+    pub fn audience_topic(&self, audience: &str) -> String {
+        self.topics_for(audience).audience_topic(audience)
+    }
+
+    /// Room of a room topic of any audience.
+    pub fn topic_room_id(&self, topic: &str) -> Option<Uuid> {
+        self.topics
+            .values()
+            .find_map(|topics| topics.room_id(topic))
+            .or_else(|| TopicsConfig::default().room_id(topic))
+    }
+
+    fn topics_for(&self, audience: &str) -> TopicsConfig {
+        self.topics.get(audience).cloned().unwrap_or_default()
+    }
+
+    /// Takes values which may be changed at runtime from a freshly loaded config.
+    ///
+    /// The rest like connections, credentials and background task schedules
+    /// require a restart to change.
+    pub fn with_tunables(&self, fresh: Config) -> Config {
+        Config {
+            constraint: fresh.constraint,
+            dump: fresh.dump,
+            adjust: fresh.adjust,
+            redaction: fresh.redaction,
+            webhooks: fresh.webhooks,
+            notification_batching: fresh.notification_batching,
+            notification_size: fresh.notification_size,
+            presence_check: fresh.presence_check,
+            room_cache: fresh.room_cache,
+            room_capacity: fresh.room_capacity,
+            presence: fresh.presence,
+            redis_bridge: fresh.redis_bridge,
+            erasure: fresh.erasure,
+            class_types: fresh.class_types,
+            content_filter: fresh.content_filter,
+            attachments: fresh.attachments,
+            polls: PollsConfig {
+                check_interval: self.polls.check_interval,
+                ..fresh.polls
+            },
+            auto_close: AutoCloseConfig {
+                check_interval: self.auto_close.check_interval,
+                ..fresh.auto_close
+            },
+            inactivity_alert: InactivityAlertConfig {
+                check_interval: self.inactivity_alert.check_interval,
+                ..fresh.inactivity_alert
+            },
+            derived_room_cleanup: DerivedRoomCleanupConfig {
+                check_interval: self.derived_room_cleanup.check_interval,
+                ..fresh.derived_room_cleanup
+            },
+            ..self.clone()
+        }
+    }
 }
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct Constraint {
     pub payload_size: usize,
+    /// Stricter limits of event `data` size in bytes by event kind.
+    #[serde(default)]
+    pub event_data_size: HashMap<String, usize>,
+    #[serde(default)]
+    pub message: MessageConstraint,
+    /// Maximum size of agent's `presence_meta` in bytes.
+    #[serde(default = "Constraint::default_presence_meta_size")]
+    pub presence_meta_size: usize,
+}
+
+impl Constraint {
+    fn default_presence_meta_size() -> usize {
+        1024
+    }
+}
+
+/// Limits checked for every incoming MQTT message before parsing its payload.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct MessageConstraint {
+    /// Maximum payload size in bytes.
+    pub max_size: usize,
+    /// Maximum nesting level of JSON objects and arrays.
+    pub max_depth: usize,
+}
+
+impl Default for MessageConstraint {
+    fn default() -> Self {
+        Self {
+            max_size: 1024 * 1024,
+            max_depth: 32,
+        }
+    }
+}
+
+/// In-process cache of recent allow decisions.
+///
+/// Bans and role changes made on other replicas are picked up only after the `ttl` expires
+/// so keep it short. It's capped at 30 seconds anyway.
+#[derive(Clone, Debug, Deserialize)]
+pub struct AuthzDecisionCacheConfig {
+    #[serde(with = "humantime_serde")]
+    pub ttl: StdDuration,
+    pub capacity: usize,
+}
+
+/// Hides a part of event data in `event.list` and `state.read` responses
+/// from those who can't update the room.
+#[derive(Clone, Debug, Deserialize)]
+pub struct RedactionRule {
+    /// Event type the rule applies to.
+    pub kind: String,
+    /// JSON pointer to the hidden value inside event data, e.g. `/notes`.
+    pub path: String,
+    /// Value to put instead of the hidden one. The value gets removed when not set.
+    #[serde(default)]
+    pub mask: Option<JsonValue>,
+}
+
+/// HTTP callbacks on room lifecycle events for tenants without MQTT or NATS consumers.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct WebhooksConfig {
+    /// Callback targets by audience.
+    pub audiences: HashMap<String, WebhookTarget>,
+    /// Delivery attempts before giving up.
+    pub max_attempts: u32,
+    /// Delay before the first retry. It doubles on each next one.
+    #[serde(with = "humantime_serde")]
+    pub retry_interval: StdDuration,
+    #[serde(with = "humantime_serde")]
+    pub timeout: StdDuration,
+}
+
+impl Default for WebhooksConfig {
+    fn default() -> Self {
+        Self {
+            audiences: HashMap::new(),
+            max_attempts: 5,
+            retry_interval: StdDuration::from_secs(1),
+            timeout: StdDuration::from_secs(10),
+        }
+    }
+}
+
+#[derive(Clone, Deserialize)]
+pub struct WebhookTarget {
+    pub url: String,
+    /// Key to sign request bodies with.
+    pub secret: String,
+}
+
+impl std::fmt::Debug for WebhookTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WebhookTarget")
+            .field("url", &self.url)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Coalescing of notifications which a single request produces for the same topic
+/// into one message with an array payload.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct NotificationBatchingConfig {
+    pub enabled: bool,
+    /// Maximum number of notifications in a single message.
+    pub max_size: usize,
+}
+
+impl Default for NotificationBatchingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_size: 100,
+        }
+    }
+}
+
+/// Limit of notification payload size. Payloads are measured one by one before batching.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct NotificationSizeConfig {
+    /// Bytes of a serialized payload. Notifications aren't limited when missing.
+    pub max_size: Option<usize>,
+    pub policy: OversizedNotificationPolicy,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OversizedNotificationPolicy {
+    /// Don't publish the notification.
+    Reject,
+    /// Publish the notification with a stub payload keeping only its `id`.
+    Truncate,
+}
+
+impl Default for OversizedNotificationPolicy {
+    fn default() -> Self {
+        Self::Reject
+    }
+}
+
+/// Checking rooms for ready agents before broadcasting events created with
+/// `skip_broadcast_if_empty`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct PresenceCheckConfig {
+    /// How long a check result is reused for the room.
+    #[serde(with = "humantime_serde")]
+    pub cache_ttl: StdDuration,
+}
+
+impl Default for PresenceCheckConfig {
+    fn default() -> Self {
+        Self {
+            cache_ttl: StdDuration::from_secs(5),
+        }
+    }
+}
+
+/// Caching of rooms found by id or classroom id.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct RoomCacheConfig {
+    /// How long a found room is reused. Zero disables the cache.
+    #[serde(with = "humantime_serde")]
+    pub ttl: StdDuration,
+}
+
+impl Default for RoomCacheConfig {
+    fn default() -> Self {
+        Self {
+            ttl: StdDuration::from_secs(2),
+        }
+    }
+}
+
+/// Encryption of `data` of events of the kinds by the service.
+#[derive(Clone, Deserialize)]
+pub struct DataEncryptionConfig {
+    /// Kinds to encrypt, e.g. `private_note`.
+    pub kinds: Vec<String>,
+    /// Key to encrypt new events with.
+    pub key_id: String,
+    /// Base64 encoded 256-bit keys by id. Rotated keys are kept to decrypt older events.
+    /// They are usually passed from KMS through `APP_DATA_ENCRYPTION__KEYS__<ID>` variables.
+    pub keys: HashMap<String, String>,
+    /// Keep encrypted kinds in dumps and transcripts decrypting them.
+    #[serde(default)]
+    pub allow_dump: bool,
+}
+
+impl std::fmt::Debug for DataEncryptionConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DataEncryptionConfig")
+            .field("kinds", &self.kinds)
+            .field("key_id", &self.key_id)
+            .field("keys", &self.keys.keys().collect::<Vec<_>>())
+            .field("allow_dump", &self.allow_dump)
+            .finish()
+    }
+}
+
+/// Templates of notification topics. `{room_id}` and `{audience}` are substituted and
+/// must take whole segments of the topic.
+///
+/// Clients subscribe to the topics so changing them requires a restart.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct TopicsConfig {
+    pub room: String,
+    pub audience: String,
+}
+
+impl TopicsConfig {
+    pub fn room_topic(&self, audience: &str, room_id: Uuid) -> String {
+        self.room
+            .replace("{audience}", audience)
+            .replace("{room_id}", &room_id.to_string())
+    }
+
+    pub fn audience_topic(&self, audience: &str) -> String {
+        self.audience.replace("{audience}", audience)
+    }
+
+    /// Parses the room out of a topic made from the room template.
+    pub fn room_id(&self, topic: &str) -> Option<Uuid> {
+        let mut template = self.room.split('/');
+        let mut segments = topic.split('/');
+        let mut room_id = None;
+
+        loop {
+            match (template.next(), segments.next()) {
+                (None, None) => return room_id,
+                (Some("{room_id}"), Some(segment)) => room_id = Some(segment.parse().ok()?),
+                (Some("{audience}"), Some(_)) => (),
+                (Some(expected), Some(segment)) if expected == segment => (),
+                _ => return None,
+            }
+        }
+    }
+}
+
+impl Default for TopicsConfig {
+    fn default() -> Self {
+        Self {
+            room: "rooms/{room_id}/events".to_owned(),
+            audience: "audiences/{audience}/events".to_owned(),
+        }
+    }
+}
+
+/// Sets whose events are deltas merged into a single document per label.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct CrdtConfig {
+    pub sets: Vec<String>,
+}
+
+impl CrdtConfig {
+    pub fn is_crdt_set(&self, set: &str) -> bool {
+        self.sets.iter().any(|s| s == set)
+    }
+}
+
+/// Limiting the number of agents in a room on `room.enter`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct RoomCapacityConfig {
+    /// Capacity of rooms created without one. Rooms are unlimited when not set.
+    pub default: Option<i32>,
+    /// Puts agents into a waiting queue instead of failing with `room_full`.
+    pub queue: bool,
+    /// How long a place freed for the queue head is held until it enters the room.
+    #[serde(with = "humantime_serde")]
+    pub admission_timeout: StdDuration,
+}
+
+impl Default for RoomCapacityConfig {
+    fn default() -> Self {
+        Self {
+            default: None,
+            queue: false,
+            admission_timeout: StdDuration::from_secs(60),
+        }
+    }
+}
+
+/// Keeping online agents of rooms in Redis for `room.presence` and enter/leave notifications.
+/// Requires the cache to be enabled, otherwise ready agents are read from the DB.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct PresenceConfig {
+    pub enabled: bool,
+    /// A room's online agents are reloaded from the DB when not changed for that long.
+    #[serde(with = "humantime_serde")]
+    pub ttl: StdDuration,
+}
+
+impl Default for PresenceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ttl: StdDuration::from_secs(3600),
+        }
+    }
+}
+
+/// Mirroring room broadcasts to Redis pub/sub channels of their classrooms for websocket
+/// gateways which don't speak MQTT. Requires the cache to be enabled.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct RedisBridgeConfig {
+    pub enabled: bool,
+    /// Channels are named `{channel_prefix}{classroom_id}`.
+    pub channel_prefix: String,
+}
+
+impl Default for RedisBridgeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            channel_prefix: "event.classrooms.".to_owned(),
+        }
+    }
+}
+
+/// Erasure of accounts' events on compliance requests.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ErasureConfig {
+    /// Keys of event `data` identifying its author, e.g. a user name shown next to a message.
+    pub author_fields: Vec<String>,
+}
+
+/// Separate connection pool for adjustments, edition commits, vacuum and dumps.
+/// They share the main pool when it's not configured.
+#[derive(Clone, Debug, Deserialize)]
+pub struct BackgroundDbConfig {
+    pub pool_size: u32,
 }
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct MetricsConfig {
     pub http: MetricsHttpConfig,
+    /// Request duration histogram buckets in seconds. Prometheus defaults are used when missing.
+    #[serde(default)]
+    pub request_duration_buckets: Option<Vec<f64>>,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -53,7 +532,7 @@ pub struct MetricsHttpConfig {
     pub bind_address: SocketAddr,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Deserialize)]
 pub struct JwtConfig {
     #[serde(deserialize_with = "svc_authn::serde::algorithm")]
     pub algorithm: Algorithm,
@@ -61,6 +540,14 @@ pub struct JwtConfig {
     pub key: Vec<u8>,
 }
 
+impl std::fmt::Debug for JwtConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JwtConfig")
+            .field("algorithm", &self.algorithm)
+            .finish_non_exhaustive()
+    }
+}
+
 pub fn load() -> Result<Config, config::ConfigError> {
     config::Config::builder()
         .add_source(config::File::with_name("App"))
@@ -69,6 +556,63 @@ pub fn load() -> Result<Config, config::ConfigError> {
         .and_then(|c| c.try_deserialize::<Config>())
 }
 
+#[derive(Clone, Debug, Deserialize)]
+pub struct DumpConfig {
+    /// Rooms with more events are dumped to S3 instead of responding over HTTP.
+    pub http_max_events: usize,
+    /// Destinations `room.dump_events` may be asked to dump to instead of the default bucket
+    /// by audience.
+    #[serde(default)]
+    pub destinations: HashMap<String, DumpDestinationsConfig>,
+    /// Maximum size of a snapshot `room.restore` accepts in bytes. Changing it requires a restart.
+    #[serde(default = "DumpConfig::default_restore_max_size")]
+    pub restore_max_size: usize,
+}
+
+impl DumpConfig {
+    fn default_restore_max_size() -> usize {
+        100 * 1024 * 1024
+    }
+}
+
+impl Default for DumpConfig {
+    fn default() -> Self {
+        Self {
+            http_max_events: 10000,
+            destinations: HashMap::new(),
+            restore_max_size: Self::default_restore_max_size(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct DumpDestinationsConfig {
+    /// Buckets of the S3 storage the service has credentials for.
+    pub buckets: Vec<String>,
+    /// Hosts of presigned HTTPS URLs to upload dumps to.
+    pub upload_hosts: Vec<String>,
+}
+
+/// Limits on concurrent handling of incoming MQTT messages or NATS requests.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct QueueConfig {
+    /// Messages handled at the same time.
+    pub max_concurrency: usize,
+    /// Messages waiting for handling. Excess ones get rejected.
+    pub max_pending: usize,
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrency: 256,
+            max_pending: 4096,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct VacuumConfig {
     pub max_history_size: usize,
@@ -76,6 +620,26 @@ pub struct VacuumConfig {
     pub max_history_lifetime: Duration,
     #[serde(with = "crate::serde::duration_seconds")]
     pub max_deleted_lifetime: Duration,
+    #[serde(default = "VacuumConfig::default_batch_size")]
+    pub batch_size: usize,
+    #[serde(
+        default = "VacuumConfig::default_batch_interval",
+        with = "humantime_serde"
+    )]
+    pub batch_interval: StdDuration,
+    /// Cron expression in UTC to run vacuum by the service itself, e.g. `0 3 * * *`.
+    #[serde(default)]
+    pub schedule: Option<String>,
+}
+
+impl VacuumConfig {
+    fn default_batch_size() -> usize {
+        1000
+    }
+
+    fn default_batch_interval() -> StdDuration {
+        StdDuration::from_secs(1)
+    }
 }
 
 impl Default for VacuumConfig {
@@ -84,6 +648,222 @@ impl Default for VacuumConfig {
             max_history_size: 10,
             max_history_lifetime: Duration::days(1),
             max_deleted_lifetime: Duration::days(1),
+            batch_size: Self::default_batch_size(),
+            batch_interval: Self::default_batch_interval(),
+            schedule: None,
+        }
+    }
+}
+
+/// Closing rooms nobody stays in. Rooms with `keep_open` flag set are never closed.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct AutoCloseConfig {
+    /// Audiences to close rooms in. Nothing gets closed when empty.
+    pub audiences: HashMap<String, AutoCloseAudience>,
+    #[serde(with = "humantime_serde")]
+    pub check_interval: StdDuration,
+}
+
+impl Default for AutoCloseConfig {
+    fn default() -> Self {
+        Self {
+            audiences: HashMap::new(),
+            check_interval: StdDuration::from_secs(60),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct AutoCloseAudience {
+    /// How long a room stays open without ready agents.
+    #[serde(with = "humantime_serde")]
+    pub idle_timeout: StdDuration,
+}
+
+/// Deleting derived rooms, i.e. adjusted rooms and edition commit destinations,
+/// superseded by a newer derivative of the same source.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct DerivedRoomCleanupConfig {
+    /// How old a derived room and the rooms derived from it must be to get deleted.
+    /// Nothing gets deleted when unset.
+    #[serde(with = "humantime_serde")]
+    pub max_age: Option<StdDuration>,
+    /// Maximum number of stale rooms to delete per check.
+    pub max_rooms: usize,
+    /// Events of a room are deleted in batches of this size sleeping `batch_interval` in between.
+    pub batch_size: usize,
+    #[serde(with = "humantime_serde")]
+    pub batch_interval: StdDuration,
+    #[serde(with = "humantime_serde")]
+    pub check_interval: StdDuration,
+}
+
+impl Default for DerivedRoomCleanupConfig {
+    fn default() -> Self {
+        Self {
+            max_age: None,
+            max_rooms: 100,
+            batch_size: 1000,
+            batch_interval: StdDuration::from_secs(1),
+            check_interval: StdDuration::from_secs(3600),
+        }
+    }
+}
+
+/// Alerting tenants about open rooms where events stopped arriving, e.g. due to a broken
+/// integration.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct InactivityAlertConfig {
+    /// Audiences to watch rooms in. Nothing gets watched when empty.
+    pub audiences: HashMap<String, InactivityAlertAudience>,
+    #[serde(with = "humantime_serde")]
+    pub check_interval: StdDuration,
+}
+
+impl Default for InactivityAlertConfig {
+    fn default() -> Self {
+        Self {
+            audiences: HashMap::new(),
+            check_interval: StdDuration::from_secs(60),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct InactivityAlertAudience {
+    /// How long an open room may go without events.
+    #[serde(with = "humantime_serde")]
+    pub threshold: StdDuration,
+}
+
+/// Read-only room access tokens issued by `room.share`.
+#[derive(Clone, Deserialize)]
+pub struct ShareConfig {
+    /// HMAC key tokens are signed with. Changing it revokes all issued tokens.
+    pub secret: String,
+    /// Token lifetime when the request doesn't specify one.
+    #[serde(default = "ShareConfig::default_ttl", with = "humantime_serde")]
+    pub default_ttl: StdDuration,
+    /// The longest lifetime a token may be issued for.
+    #[serde(default = "ShareConfig::default_max_ttl", with = "humantime_serde")]
+    pub max_ttl: StdDuration,
+}
+
+impl std::fmt::Debug for ShareConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ShareConfig")
+            .field("default_ttl", &self.default_ttl)
+            .field("max_ttl", &self.max_ttl)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ShareConfig {
+    fn default_ttl() -> StdDuration {
+        StdDuration::from_secs(24 * 3600)
+    }
+
+    fn default_max_ttl() -> StdDuration {
+        StdDuration::from_secs(30 * 24 * 3600)
+    }
+}
+
+/// Tenants' storages `attachment` events may reference.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct AttachmentsConfig {
+    /// Attachments are rejected in audiences missing here.
+    #[serde(default)]
+    pub audiences: HashMap<String, AttachmentsAudience>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct AttachmentsAudience {
+    /// URL prefixes of allowed storage locations, e.g. `https://storage.example.org/files/`.
+    pub allowed_urls: Vec<String>,
+    /// Maximum attachment size in bytes.
+    #[serde(default)]
+    pub max_size: Option<u64>,
+}
+
+/// External classifier of `message` events consulted before they're created.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ContentFilterConfig {
+    pub url: String,
+    #[serde(
+        default = "ContentFilterConfig::default_timeout",
+        with = "humantime_serde"
+    )]
+    pub timeout: StdDuration,
+    /// Whether messages are accepted as is when the classifier fails or times out.
+    /// They're rejected otherwise.
+    #[serde(default = "ContentFilterConfig::default_fail_open")]
+    pub fail_open: bool,
+    /// Audiences whose messages are checked. All of them when empty.
+    #[serde(default)]
+    pub audiences: Vec<String>,
+}
+
+impl ContentFilterConfig {
+    fn default_timeout() -> StdDuration {
+        StdDuration::from_secs(1)
+    }
+
+    fn default_fail_open() -> bool {
+        true
+    }
+
+    pub fn applies_to(&self, audience: &str) -> bool {
+        self.audiences.is_empty() || self.audiences.iter().any(|a| a == audience)
+    }
+}
+
+/// Applying locked types changes scheduled with `room.schedule_lock`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct LockScheduleConfig {
+    /// How often due schedules are checked. It's the precision schedules get applied with.
+    #[serde(with = "humantime_serde")]
+    pub check_interval: StdDuration,
+}
+
+impl Default for LockScheduleConfig {
+    fn default() -> Self {
+        Self {
+            check_interval: StdDuration::from_secs(10),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct PollsConfig {
+    /// How often polls are checked to close them when their time is up.
+    #[serde(
+        default = "PollsConfig::default_check_interval",
+        with = "humantime_serde"
+    )]
+    pub check_interval: StdDuration,
+    #[serde(default = "PollsConfig::default_max_options")]
+    pub max_options: usize,
+}
+
+impl PollsConfig {
+    fn default_check_interval() -> StdDuration {
+        StdDuration::from_secs(5)
+    }
+
+    fn default_max_options() -> usize {
+        20
+    }
+}
+
+impl Default for PollsConfig {
+    fn default() -> Self {
+        Self {
+            check_interval: Self::default_check_interval(),
+            max_options: Self::default_max_options(),
         }
     }
 }
@@ -95,10 +875,41 @@ pub struct HttpBrokerClientConfig {
     pub timeout: Option<StdDuration>,
 }
 
+/// Applied to rooms of a class type so tenants don't have to pass the same options
+/// for every room they create.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct ClassTypeConfig {
+    /// Initial `locked_types` of created rooms.
+    #[serde(default)]
+    pub locked_types: HashMap<String, bool>,
+    /// Initial settings of created rooms, see `room.update_settings`.
+    #[serde(default)]
+    pub settings: RoomSettings,
+    /// Overrides `adjust.thinning` on adjustment of the rooms.
+    #[serde(default)]
+    pub thinning: Option<HashMap<String, NonZeroU32>>,
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct AdjustConfig {
     #[serde(with = "humantime_serde")]
     pub min_segment_length: StdDuration,
+    /// Approximate number of events to clone per statement on room adjustment and per
+    /// transaction on edition commit. Events are cloned at once when not set.
+    #[serde(default)]
+    pub clone_chunk_size: Option<usize>,
+    /// Number of chunks cloned at the same time on separate connections of the background
+    /// pool on room adjustment. Chunks are cloned one by one when not set.
+    #[serde(default)]
+    pub clone_concurrency: Option<usize>,
+    /// Maximum number of events per second of each label to keep in modified rooms by kind,
+    /// e.g. `draw = 10`. The last event of each label is always kept.
+    #[serde(default)]
+    pub thinning: HashMap<String, NonZeroU32>,
+    /// Also runs the clone-free segment pipeline of `room.adjust_preview` and records where
+    /// its segments differ from the persisted ones to `adjustment_comparison`.
+    #[serde(default)]
+    pub shadow: bool,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -111,4 +922,72 @@ pub struct NatsConsumer {
     pub suspend_sentry_interval: StdDuration,
     #[serde(with = "humantime_serde")]
     pub resubscribe_interval: StdDuration,
+    /// Splitting messages between replicas. A single replica handles all of them when missing.
+    #[serde(default)]
+    pub sharding: Option<NatsShardingConfig>,
+}
+
+/// Serving reads to NATS-native services with request/reply over the `nats` connection.
+#[derive(Clone, Debug, Deserialize)]
+pub struct NatsResponder {
+    /// Requests come to `<subject_prefix>.<method>`, e.g. `event.requests.room.read`.
+    pub subject_prefix: String,
+    /// Replicas of the service share requests as members of the queue group.
+    pub queue_group: String,
+    /// Requests handled at the same time and waiting for handling. Excess ones get
+    /// `service_overloaded` error.
+    #[serde(default)]
+    pub queue: QueueConfig,
+}
+
+/// Every replica pulls all the messages with its own consumer `<consumer>-<ordinal>` and handles
+/// those of classrooms hashed to its ordinal, so events of a classroom keep their order.
+#[derive(Clone, Debug, Deserialize)]
+pub struct NatsShardingConfig {
+    /// Number of replicas pulling messages.
+    pub replicas: u32,
+    /// Ordinal of the replica in `0..replicas`. When missing it's taken from the `-N` suffix
+    /// of the hostname the way StatefulSet pods are named.
+    #[serde(default)]
+    pub ordinal: Option<u32>,
+    /// Number of tasks handling messages of the replica concurrently.
+    /// Messages of a classroom are always handled by the same task one by one.
+    #[serde(default = "NatsShardingConfig::default_workers")]
+    pub workers: usize,
+}
+
+impl NatsShardingConfig {
+    fn default_workers() -> usize {
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn topics() {
+        let room_id = Uuid::new_v4();
+        let topics = TopicsConfig::default();
+        let topic = topics.room_topic("dev.usr.example.org", room_id);
+        assert_eq!(topic, format!("rooms/{}/events", room_id));
+        assert_eq!(topics.room_id(&topic), Some(room_id));
+
+        let topics = TopicsConfig {
+            room: "brands/{audience}/rooms/{room_id}".to_owned(),
+            audience: "brands/{audience}/events".to_owned(),
+        };
+
+        let topic = topics.room_topic("brand.example.org", room_id);
+        assert_eq!(topic, format!("brands/brand.example.org/rooms/{}", room_id));
+        assert_eq!(topics.room_id(&topic), Some(room_id));
+        assert_eq!(topics.room_id(&format!("rooms/{}/events", room_id)), None);
+        assert_eq!(topics.room_id("brands/brand.example.org/rooms/foo"), None);
+
+        assert_eq!(
+            topics.audience_topic("brand.example.org"),
+            "brands/brand.example.org/events"
+        );
+    }
 }
@@ -3,11 +3,25 @@ use std::ops::Bound;
 use chrono::{serde::ts_seconds, DateTime, Utc};
 
 use serde_derive::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
 use sqlx::postgres::{types::PgRange, PgConnection};
 use uuid::Uuid;
 
 ///////////////////////////////////////////////////////////////////////////////
 
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "adjustment_status")]
+pub enum Status {
+    #[sqlx(rename = "running")]
+    Running,
+    #[sqlx(rename = "succeeded")]
+    Succeeded,
+    #[sqlx(rename = "failed")]
+    Failed,
+}
+
+/// The latest adjustment of a real-time room and its outcome.
 #[derive(Clone, Debug, Serialize)]
 pub struct Object {
     room_id: Uuid,
@@ -18,6 +32,67 @@ pub struct Object {
     offset: i64,
     #[serde(with = "ts_seconds")]
     created_at: DateTime<Utc>,
+    status: Status,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    original_room_id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    modified_room_id: Option<Uuid>,
+    #[serde(
+        serialize_with = "serde::option_segments::serialize",
+        skip_serializing_if = "Option::is_none"
+    )]
+    modified_segments: Option<Segments>,
+    #[serde(
+        serialize_with = "serde::option_segments::serialize",
+        skip_serializing_if = "Option::is_none"
+    )]
+    cut_original_segments: Option<Segments>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<JsonValue>,
+    #[serde(
+        with = "chrono::serde::ts_seconds_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    finished_at: Option<DateTime<Utc>>,
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug)]
+pub struct FindQuery {
+    room_id: Uuid,
+}
+
+impl FindQuery {
+    pub fn new(room_id: Uuid) -> Self {
+        Self { room_id }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Option<Object>> {
+        sqlx::query_as!(
+            Object,
+            r#"
+            SELECT
+                room_id,
+                started_at,
+                segments AS "segments!: Segments",
+                "offset",
+                created_at,
+                status AS "status!: Status",
+                original_room_id,
+                modified_room_id,
+                modified_segments AS "modified_segments: Segments",
+                cut_original_segments AS "cut_original_segments: Segments",
+                error,
+                finished_at
+            FROM adjustment
+            WHERE room_id = $1
+            "#,
+            self.room_id,
+        )
+        .fetch_optional(conn)
+        .await
+    }
 }
 
 ///////////////////////////////////////////////////////////////////////////////
@@ -51,7 +126,14 @@ impl InsertQuery {
                 started_at,
                 segments AS "segments!: Segments",
                 "offset",
-                created_at
+                created_at,
+                status AS "status!: Status",
+                original_room_id,
+                modified_room_id,
+                modified_segments AS "modified_segments: Segments",
+                cut_original_segments AS "cut_original_segments: Segments",
+                error,
+                finished_at
             "#,
             self.room_id,
             self.started_at,
@@ -65,6 +147,130 @@ impl InsertQuery {
 
 ////////////////////////////////////////////////////////////////////////////////
 
+/// Records the outcome of a running adjustment.
+#[derive(Debug)]
+pub struct FinishQuery {
+    room_id: Uuid,
+    status: Status,
+    original_room_id: Option<Uuid>,
+    modified_room_id: Option<Uuid>,
+    modified_segments: Option<Segments>,
+    cut_original_segments: Option<Segments>,
+    error: Option<JsonValue>,
+}
+
+impl FinishQuery {
+    pub fn succeeded(
+        room_id: Uuid,
+        original_room_id: Uuid,
+        modified_room_id: Uuid,
+        modified_segments: Segments,
+        cut_original_segments: Segments,
+    ) -> Self {
+        Self {
+            room_id,
+            status: Status::Succeeded,
+            original_room_id: Some(original_room_id),
+            modified_room_id: Some(modified_room_id),
+            modified_segments: Some(modified_segments),
+            cut_original_segments: Some(cut_original_segments),
+            error: None,
+        }
+    }
+
+    pub fn failed(room_id: Uuid, error: JsonValue) -> Self {
+        Self {
+            room_id,
+            status: Status::Failed,
+            original_room_id: None,
+            modified_room_id: None,
+            modified_segments: None,
+            cut_original_segments: None,
+            error: Some(error),
+        }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Option<Object>> {
+        sqlx::query_as!(
+            Object,
+            r#"
+            UPDATE adjustment
+            SET status = $2,
+                original_room_id = $3,
+                modified_room_id = $4,
+                modified_segments = $5,
+                cut_original_segments = $6,
+                error = $7,
+                finished_at = NOW()
+            WHERE room_id = $1
+            RETURNING
+                room_id,
+                started_at,
+                segments AS "segments!: Segments",
+                "offset",
+                created_at,
+                status AS "status!: Status",
+                original_room_id,
+                modified_room_id,
+                modified_segments AS "modified_segments: Segments",
+                cut_original_segments AS "cut_original_segments: Segments",
+                error,
+                finished_at
+            "#,
+            self.room_id,
+            self.status as Status,
+            self.original_room_id,
+            self.modified_room_id,
+            self.modified_segments as Option<Segments>,
+            self.cut_original_segments as Option<Segments>,
+            self.error,
+        )
+        .fetch_optional(conn)
+        .await
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Records how the shadow pipeline's result of the adjustment differs from the persisted one.
+#[derive(Debug)]
+pub struct ComparisonInsertQuery {
+    room_id: Uuid,
+    matched: bool,
+    diff: JsonValue,
+}
+
+impl ComparisonInsertQuery {
+    pub fn new(room_id: Uuid, matched: bool, diff: JsonValue) -> Self {
+        Self {
+            room_id,
+            matched,
+            diff,
+        }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO adjustment_comparison (room_id, matched, diff)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (room_id) DO UPDATE
+            SET matched = EXCLUDED.matched,
+                diff = EXCLUDED.diff,
+                created_at = NOW()
+            "#,
+            self.room_id,
+            self.matched,
+            self.diff,
+        )
+        .execute(conn)
+        .await
+        .map(|_| ())
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
 type BoundedOffsetTuples = Vec<(Bound<i64>, Bound<i64>)>;
 
 #[derive(Clone, Debug, Deserialize, Serialize, sqlx::Type)]
@@ -114,4 +320,19 @@ pub mod serde {
             milliseconds_bound_tuples::deserialize(d).map(Segments::from)
         }
     }
+
+    pub mod option_segments {
+        use super::super::Segments;
+        use serde::ser;
+
+        pub fn serialize<S>(value: &Option<Segments>, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: ser::Serializer,
+        {
+            match value {
+                Some(segments) => super::segments::serialize(segments, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+    }
 }
@@ -0,0 +1,149 @@
+use sqlx::postgres::PgConnection;
+
+/// Takes a session-level advisory lock if nobody else holds it.
+///
+/// The lock is held until `UnlockQuery` is executed on the same connection
+/// or the connection gets closed.
+#[derive(Debug)]
+pub struct TryLockQuery {
+    key: i64,
+}
+
+impl TryLockQuery {
+    pub fn new(key: i64) -> Self {
+        Self { key }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<bool> {
+        sqlx::query!(r#"SELECT pg_try_advisory_lock($1) AS "locked!""#, self.key)
+            .fetch_one(conn)
+            .await
+            .map(|r| r.locked)
+    }
+}
+
+#[derive(Debug)]
+pub struct UnlockQuery {
+    key: i64,
+}
+
+impl UnlockQuery {
+    pub fn new(key: i64) -> Self {
+        Self { key }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<bool> {
+        sqlx::query!(r#"SELECT pg_advisory_unlock($1) AS "unlocked!""#, self.key)
+            .fetch_one(conn)
+            .await
+            .map(|r| r.unlocked)
+    }
+}
+
+/// Takes a transaction-level advisory lock if nobody else holds it.
+///
+/// The lock is released on the transaction's commit or rollback so it can't outlive
+/// the transaction even if the connection goes back to the pool.
+#[derive(Debug)]
+pub struct TryXactLockQuery {
+    key: i64,
+}
+
+impl TryXactLockQuery {
+    pub fn new(key: i64) -> Self {
+        Self { key }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<bool> {
+        sqlx::query!(
+            r#"SELECT pg_try_advisory_xact_lock($1) AS "locked!""#,
+            self.key
+        )
+        .fetch_one(conn)
+        .await
+        .map(|r| r.locked)
+    }
+}
+
+/// Waits for a transaction-level advisory lock identified by an arbitrary name.
+///
+/// The lock is released on the transaction's commit or rollback.
+#[derive(Debug)]
+pub struct XactLockQuery {
+    name: String,
+}
+
+impl XactLockQuery {
+    pub fn new(name: String) -> Self {
+        Self { name }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<()> {
+        sqlx::query!(
+            r#"
+            SELECT true AS "locked!"
+            FROM pg_advisory_xact_lock(hashtextextended($1, 0))
+            "#,
+            self.name
+        )
+        .fetch_one(conn)
+        .await
+        .map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::Connection;
+
+    use super::*;
+    use crate::test_helpers::prelude::*;
+
+    #[tokio::test]
+    async fn lock_is_exclusive() {
+        let db = TestDb::new().await;
+        let mut conn1 = db.get_conn().await;
+        let mut conn2 = db.get_conn().await;
+        let key = rand::random::<i64>();
+
+        let locked = TryLockQuery::new(key).execute(&mut conn1).await.unwrap();
+        assert!(locked);
+
+        let locked = TryLockQuery::new(key).execute(&mut conn2).await.unwrap();
+        assert!(!locked);
+
+        let unlocked = UnlockQuery::new(key).execute(&mut conn1).await.unwrap();
+        assert!(unlocked);
+
+        let locked = TryLockQuery::new(key).execute(&mut conn2).await.unwrap();
+        assert!(locked);
+
+        UnlockQuery::new(key).execute(&mut conn2).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn xact_lock_is_released_with_transaction() {
+        let db = TestDb::new().await;
+        let mut conn1 = db.get_conn().await;
+        let mut conn2 = db.get_conn().await;
+        let key = rand::random::<i64>();
+
+        let mut txn = conn1.begin().await.unwrap();
+        let locked = TryXactLockQuery::new(key).execute(&mut txn).await.unwrap();
+        assert!(locked);
+
+        let locked = TryXactLockQuery::new(key)
+            .execute(&mut conn2)
+            .await
+            .unwrap();
+        assert!(!locked);
+
+        txn.rollback().await.unwrap();
+
+        let locked = TryXactLockQuery::new(key)
+            .execute(&mut conn2)
+            .await
+            .unwrap();
+        assert!(locked);
+    }
+}
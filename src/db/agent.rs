@@ -1,13 +1,15 @@
 use chrono::serde::ts_seconds;
 use chrono::{DateTime, Utc};
 use serde_derive::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
 use sqlx::postgres::PgConnection;
 use svc_agent::AgentId;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 ////////////////////////////////////////////////////////////////////////////////
 
-#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, sqlx::Type)]
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, sqlx::Type, ToSchema)]
 #[serde(rename_all = "snake_case")]
 #[sqlx(type_name = "agent_status")]
 pub enum Status {
@@ -31,22 +33,33 @@ pub struct Object {
     created_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
 pub struct AgentWithBan {
     #[serde(skip_serializing)]
     #[allow(dead_code)]
     id: Uuid,
+    #[schema(value_type = String)]
     agent_id: AgentId,
     room_id: Uuid,
     #[serde(skip_serializing)]
     #[allow(dead_code)]
     status: Status,
     #[serde(with = "ts_seconds")]
+    #[schema(value_type = i64)]
     created_at: DateTime<Utc>,
     #[serde(skip_serializing_if = "Option::is_none")]
     banned: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     reason: Option<String>,
+    /// Seconds since the agent has entered the room.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    presence_duration: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    whiteboard_access: Option<bool>,
+    /// Client-defined state of the agent in the room set with `agent.update`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    presence_meta: Option<JsonValue>,
 }
 
 impl AgentWithBan {
@@ -129,6 +142,8 @@ pub struct ListWithBansQuery {
     status: Status,
     offset: usize,
     limit: usize,
+    presence: bool,
+    whiteboard_access: bool,
 }
 
 impl ListWithBansQuery {
@@ -138,6 +153,21 @@ impl ListWithBansQuery {
             status,
             offset,
             limit,
+            presence: false,
+            whiteboard_access: false,
+        }
+    }
+
+    /// Adds presence duration counted from the latest `agent_enter` event of the agent.
+    pub fn presence(self, presence: bool) -> Self {
+        Self { presence, ..self }
+    }
+
+    /// Adds whether the agent has access to the room's whiteboard.
+    pub fn whiteboard_access(self, whiteboard_access: bool) -> Self {
+        Self {
+            whiteboard_access,
+            ..self
         }
     }
 
@@ -152,19 +182,44 @@ impl ListWithBansQuery {
                 status AS "status!: Status",
                 agent.created_at,
                 (rban.created_at IS NOT NULL)::boolean AS banned,
-                rban.reason
+                rban.reason,
+                (CASE WHEN $5 THEN
+                    EXTRACT(EPOCH FROM NOW() - COALESCE(enter.entered_at, agent.created_at))::BIGINT
+                END) AS presence_duration,
+                (CASE WHEN $6 THEN
+                    COALESCE((
+                        room.whiteboard_access ->> (
+                            ((agent.agent_id).account_id).label || '.' || ((agent.agent_id).account_id).audience
+                        )
+                    )::BOOLEAN, FALSE)
+                END) AS whiteboard_access,
+                agent.presence_meta
             FROM agent
+            INNER JOIN room
+            ON room.id = agent.room_id
             LEFT OUTER JOIN room_ban rban
             ON rban.room_id = agent.room_id AND rban.account_id = (agent.agent_id).account_id
+            LEFT JOIN LATERAL (
+                SELECT MAX(created_at) AS entered_at
+                FROM event
+                WHERE $5
+                AND   event.room_id = agent.room_id
+                AND   event.set = 'agent_enter'
+                AND   event.kind = 'agent_enter'
+                AND   event.created_by = agent.agent_id
+                AND   event.deleted_at IS NULL
+            ) AS enter ON TRUE
             WHERE agent.room_id = $1 AND agent.status = $2
-            ORDER BY created_at DESC
+            ORDER BY agent.created_at DESC
             LIMIT $3
             OFFSET $4
             "#,
             self.room_id,
             self.status as Status,
             self.limit as i64,
-            self.offset as i64
+            self.offset as i64,
+            self.presence,
+            self.whiteboard_access,
         )
         .fetch_all(conn)
         .await
@@ -193,7 +248,10 @@ impl FindWithBanQuery {
                 status AS "status!: Status",
                 agent.created_at,
                 (rban.created_at IS NOT NULL)::boolean AS banned,
-                rban.reason
+                rban.reason,
+                NULL::BIGINT AS presence_duration,
+                NULL::BOOLEAN AS whiteboard_access,
+                agent.presence_meta
             FROM agent
             LEFT OUTER JOIN room_ban rban
             ON rban.room_id = agent.room_id AND rban.account_id = (agent.agent_id).account_id
@@ -208,6 +266,59 @@ impl FindWithBanQuery {
     }
 }
 
+#[derive(Debug)]
+pub struct ReadyExistsQuery {
+    room_id: Uuid,
+}
+
+impl ReadyExistsQuery {
+    pub fn new(room_id: Uuid) -> Self {
+        Self { room_id }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<bool> {
+        sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS(
+                SELECT 1
+                FROM agent
+                WHERE room_id = $1
+                AND   status = 'ready'
+            ) AS "exists!"
+            "#,
+            self.room_id
+        )
+        .fetch_one(conn)
+        .await
+    }
+}
+
+/// Lists agents which have entered the room and are ready to receive its events.
+#[derive(Debug)]
+pub struct ReadyListQuery {
+    room_id: Uuid,
+}
+
+impl ReadyListQuery {
+    pub fn new(room_id: Uuid) -> Self {
+        Self { room_id }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Vec<AgentId>> {
+        sqlx::query_scalar!(
+            r#"
+            SELECT agent_id AS "agent_id!: AgentId"
+            FROM agent
+            WHERE room_id = $1
+            AND   status = 'ready'
+            "#,
+            self.room_id
+        )
+        .fetch_all(conn)
+        .await
+    }
+}
+
 #[derive(Debug)]
 pub struct InsertQuery {
     agent_id: AgentId,
@@ -303,6 +414,45 @@ impl UpdateQuery {
 
 ///////////////////////////////////////////////////////////////////////////////
 
+/// Replaces `presence_meta` of the agent which has entered the room.
+#[derive(Debug)]
+pub struct UpdatePresenceMetaQuery {
+    agent_id: AgentId,
+    room_id: Uuid,
+    presence_meta: Option<JsonValue>,
+}
+
+impl UpdatePresenceMetaQuery {
+    pub fn new(agent_id: AgentId, room_id: Uuid, presence_meta: Option<JsonValue>) -> Self {
+        Self {
+            agent_id,
+            room_id,
+            presence_meta,
+        }
+    }
+
+    /// Returns whether the agent is in the room.
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<bool> {
+        sqlx::query!(
+            r#"
+            UPDATE agent
+            SET presence_meta = $3
+            WHERE agent_id = $1
+            AND   room_id = $2
+            AND   status = 'ready'
+            "#,
+            self.agent_id as AgentId,
+            self.room_id,
+            self.presence_meta,
+        )
+        .execute(conn)
+        .await
+        .map(|r| r.rows_affected() > 0)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
 #[derive(Debug)]
 pub struct DeleteQuery {
     agent_id: AgentId,
@@ -0,0 +1,111 @@
+use chrono::{serde::ts_milliseconds, DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+use sqlx::postgres::PgConnection;
+use svc_agent::AgentId;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// A file referenced by an `attachment` event.
+///
+/// Rows are maintained by a trigger on event insert.
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+#[schema(as = Attachment)]
+pub struct Object {
+    event_id: Uuid,
+    room_id: Uuid,
+    url: String,
+    /// Size in bytes.
+    size: i64,
+    mime: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[schema(value_type = String)]
+    created_by: AgentId,
+    #[serde(with = "ts_milliseconds")]
+    #[schema(value_type = i64)]
+    created_at: DateTime<Utc>,
+}
+
+impl Object {
+    #[cfg(test)]
+    pub fn event_id(&self) -> Uuid {
+        self.event_id
+    }
+
+    #[cfg(test)]
+    pub fn mime(&self) -> &str {
+        &self.mime
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Attachments of not deleted events of the room, the latest first.
+#[derive(Debug)]
+pub struct RoomListQuery {
+    room_id: Uuid,
+    mime_prefix: Option<String>,
+    before: Option<DateTime<Utc>>,
+    limit: i64,
+}
+
+impl RoomListQuery {
+    pub fn new(room_id: Uuid, limit: i64) -> Self {
+        Self {
+            room_id,
+            mime_prefix: None,
+            before: None,
+            limit,
+        }
+    }
+
+    /// Attachments which MIME type starts with the prefix, e.g. `image/`.
+    pub fn mime_prefix(self, mime_prefix: String) -> Self {
+        Self {
+            mime_prefix: Some(mime_prefix),
+            ..self
+        }
+    }
+
+    /// Attachments created before the moment.
+    pub fn before(self, before: DateTime<Utc>) -> Self {
+        Self {
+            before: Some(before),
+            ..self
+        }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Vec<Object>> {
+        sqlx::query_as!(
+            Object,
+            r#"
+            SELECT
+                a.event_id,
+                a.room_id,
+                a.url,
+                a.size,
+                a.mime,
+                a.name,
+                a.created_by AS "created_by!: AgentId",
+                a.created_at
+            FROM attachment AS a
+            INNER JOIN event AS e
+            ON e.id = a.event_id
+            WHERE a.room_id = $1
+                AND e.deleted_at IS NULL
+                AND ($2::text IS NULL OR starts_with(a.mime, $2))
+                AND ($3::timestamptz IS NULL OR a.created_at < $3)
+            ORDER BY a.created_at DESC
+            LIMIT $4
+            "#,
+            self.room_id,
+            self.mime_prefix,
+            self.before,
+            self.limit,
+        )
+        .fetch_all(conn)
+        .await
+    }
+}
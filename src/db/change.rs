@@ -288,13 +288,39 @@ impl InsertQuery {
 }
 
 ////////////////////////////////////////////////////////////////////////////////
-const DEFAULT_LIST_LIMIT: usize = 25;
+pub const DEFAULT_LIST_LIMIT: usize = 25;
+
+/// Number of changes of each type matching the event kind filter.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Counts {
+    pub addition: i64,
+    pub modification: i64,
+    pub removal: i64,
+    pub bulk_removal: i64,
+}
+
+impl Counts {
+    pub fn get(&self, change_type: ChangeType) -> i64 {
+        match change_type {
+            ChangeType::Addition => self.addition,
+            ChangeType::Modification => self.modification,
+            ChangeType::Removal => self.removal,
+            ChangeType::BulkRemoval => self.bulk_removal,
+        }
+    }
+
+    pub fn total(&self) -> i64 {
+        self.addition + self.modification + self.removal + self.bulk_removal
+    }
+}
 
 #[derive(Debug)]
 pub struct ListQuery {
     id: Uuid,
     last_created_at: Option<DateTime<Utc>>,
+    cursor: Option<Uuid>,
     kind: Option<String>,
+    change_type: Option<ChangeType>,
     limit: usize,
 }
 
@@ -303,8 +329,10 @@ impl ListQuery {
         Self {
             limit: DEFAULT_LIST_LIMIT,
             last_created_at: None,
+            cursor: None,
             id,
             kind: None,
+            change_type: None,
         }
     }
 
@@ -312,6 +340,7 @@ impl ListQuery {
         Self { limit, ..self }
     }
 
+    /// Filters by kind of the event the change targets.
     pub fn kind(self, kind: &str) -> Self {
         Self {
             kind: Some(kind.to_owned()),
@@ -319,6 +348,13 @@ impl ListQuery {
         }
     }
 
+    pub fn change_type(self, change_type: ChangeType) -> Self {
+        Self {
+            change_type: Some(change_type),
+            ..self
+        }
+    }
+
     pub fn last_created_at(self, last_created_at: DateTime<Utc>) -> Self {
         Self {
             last_created_at: Some(last_created_at),
@@ -326,7 +362,15 @@ impl ListQuery {
         }
     }
 
-    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Vec<Object>> {
+    /// Lists changes following the one with the given id.
+    pub fn cursor(self, cursor: Uuid) -> Self {
+        Self {
+            cursor: Some(cursor),
+            ..self
+        }
+    }
+
+    pub async fn execute(&self, conn: &mut PgConnection) -> sqlx::Result<Vec<Object>> {
         let last_created_at: Option<chrono::NaiveDateTime> =
             self.last_created_at.map(|x| x.naive_utc());
         sqlx::query_as!(
@@ -348,16 +392,205 @@ impl ListQuery {
             WHERE edition_id = $1
                 AND ($2::text IS NULL OR event_kind = $2)
                 AND ($3::timestamp IS NULL OR created_at > $3)
-            ORDER BY created_at DESC LIMIT $4
+                AND ($4::change_type IS NULL OR kind = $4)
+                AND (
+                    $5::uuid IS NULL
+                    OR (created_at, id) < (SELECT created_at, id FROM change WHERE id = $5)
+                )
+            ORDER BY created_at DESC, id DESC LIMIT $6
             "#,
             self.id,
             self.kind,
             last_created_at,
+            self.change_type as Option<ChangeType>,
+            self.cursor,
             self.limit as i32,
         )
         .fetch_all(conn)
         .await
     }
+
+    /// Counts changes of each type ignoring pagination and the change type filter.
+    pub async fn counts(&self, conn: &mut PgConnection) -> sqlx::Result<Counts> {
+        sqlx::query_as!(
+            Counts,
+            r#"
+            SELECT
+                COUNT(1) FILTER (WHERE kind = 'addition')     AS "addition!",
+                COUNT(1) FILTER (WHERE kind = 'modification') AS "modification!",
+                COUNT(1) FILTER (WHERE kind = 'removal')      AS "removal!",
+                COUNT(1) FILTER (WHERE kind = 'bulk_removal') AS "bulk_removal!"
+            FROM change
+            WHERE edition_id = $1
+                AND ($2::text IS NULL OR event_kind = $2)
+            "#,
+            self.id,
+            self.kind,
+        )
+        .fetch_one(conn)
+        .await
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Aggregate of an edition's changes of the same type targeting events of the same kind.
+#[derive(Clone, Debug, Deserialize, Serialize, sqlx::FromRow)]
+pub struct SummaryGroup {
+    #[serde(rename = "type")]
+    pub kind: ChangeType,
+    #[serde(rename = "event_type")]
+    pub event_kind: Option<String>,
+    pub count: i64,
+    /// The earliest `occurred_at` of added, modified or removed events.
+    #[serde(skip)]
+    pub started_at: Option<i64>,
+    /// The latest `occurred_at` of added, modified or removed events.
+    #[serde(skip)]
+    pub finished_at: Option<i64>,
+}
+
+#[derive(Debug)]
+pub struct SummaryQuery {
+    edition_id: Uuid,
+}
+
+impl SummaryQuery {
+    pub fn new(edition_id: Uuid) -> Self {
+        Self { edition_id }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Vec<SummaryGroup>> {
+        sqlx::query_as!(
+            SummaryGroup,
+            r#"
+            SELECT
+                c.kind                                           AS "kind!: ChangeType",
+                COALESCE(c.event_kind, e.kind)                   AS event_kind,
+                COUNT(1)                                         AS "count!",
+                MIN(LEAST(c.event_occurred_at, e.occurred_at))   AS started_at,
+                MAX(GREATEST(c.event_occurred_at, e.occurred_at)) AS finished_at
+            FROM change AS c
+            LEFT JOIN event AS e
+            ON e.id = c.event_id
+            WHERE c.edition_id = $1
+            GROUP BY c.kind, COALESCE(c.event_kind, e.kind)
+            ORDER BY c.kind, COALESCE(c.event_kind, e.kind)
+            "#,
+            self.edition_id,
+        )
+        .fetch_all(conn)
+        .await
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// A change targeting an event which is also targeted by other changes of merged editions.
+#[derive(Clone, Debug, sqlx::FromRow)]
+pub struct MergeCandidate {
+    pub event_id: Uuid,
+    pub change_id: Uuid,
+    pub edition_id: Uuid,
+    pub kind: ChangeType,
+}
+
+/// Lists changes of `edition_ids` targeting the same events grouped by the event
+/// in precedence order: changes of later editions and the latest ones first.
+#[derive(Debug)]
+pub struct MergeConflictListQuery<'a> {
+    edition_ids: &'a [Uuid],
+}
+
+impl<'a> MergeConflictListQuery<'a> {
+    pub fn new(edition_ids: &'a [Uuid]) -> Self {
+        Self { edition_ids }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Vec<MergeCandidate>> {
+        sqlx::query_as!(
+            MergeCandidate,
+            r#"
+            SELECT
+                c.event_id  AS "event_id!",
+                c.id        AS change_id,
+                c.edition_id,
+                c.kind      AS "kind!: ChangeType"
+            FROM change AS c
+            INNER JOIN UNNEST($1::UUID[]) WITH ORDINALITY AS e(edition_id, position)
+            ON e.edition_id = c.edition_id
+            WHERE c.event_id IN (
+                SELECT event_id
+                FROM change
+                WHERE edition_id = ANY($1) AND event_id IS NOT NULL
+                GROUP BY event_id
+                HAVING COUNT(1) > 1
+            )
+            ORDER BY c.event_id, e.position DESC, c.created_at DESC
+            "#,
+            self.edition_ids,
+        )
+        .fetch_all(conn)
+        .await
+    }
+}
+
+/// Copies changes of `edition_ids` into `destination_edition_id` keeping only
+/// the one of the highest precedence for each event.
+#[derive(Debug)]
+pub struct MergeQuery<'a> {
+    edition_ids: &'a [Uuid],
+    destination_edition_id: Uuid,
+}
+
+impl<'a> MergeQuery<'a> {
+    pub fn new(edition_ids: &'a [Uuid], destination_edition_id: Uuid) -> Self {
+        Self {
+            edition_ids,
+            destination_edition_id,
+        }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<u64> {
+        sqlx::query!(
+            r#"
+            INSERT INTO change (
+                edition_id,
+                kind,
+                event_id,
+                event_kind,
+                event_set,
+                event_label,
+                event_data,
+                event_occurred_at,
+                event_created_by
+            )
+            SELECT
+                $2,
+                kind,
+                event_id,
+                event_kind,
+                event_set,
+                event_label,
+                event_data,
+                event_occurred_at,
+                event_created_by
+            FROM (
+                SELECT DISTINCT ON (COALESCE(c.event_id, c.id)) c.*, e.position
+                FROM change AS c
+                INNER JOIN UNNEST($1::UUID[]) WITH ORDINALITY AS e(edition_id, position)
+                ON e.edition_id = c.edition_id
+                ORDER BY COALESCE(c.event_id, c.id), e.position DESC, c.created_at DESC
+            ) AS merged
+            ORDER BY position, created_at
+            "#,
+            self.edition_ids,
+            self.destination_edition_id,
+        )
+        .execute(conn)
+        .await
+        .map(|r| r.rows_affected())
+    }
 }
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -0,0 +1,295 @@
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+use sqlx::postgres::PgConnection;
+use uuid::Uuid;
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "edition_commit_job_status")]
+pub enum Status {
+    #[sqlx(rename = "running")]
+    Running,
+    #[sqlx(rename = "succeeded")]
+    Succeeded,
+    #[sqlx(rename = "failed")]
+    Failed,
+}
+
+/// Persistent state of an edition commit which allows to resume it after a failure.
+///
+/// `attempts` is bumped each time the job gets resumed and updates are only applied
+/// with the current value so a replica which lost the job can't mess with it.
+#[derive(Clone, Debug, Serialize, sqlx::FromRow)]
+pub struct Object {
+    id: Uuid,
+    edition_id: Uuid,
+    time_offset: i64,
+    status: Status,
+    destination_room_id: Option<Uuid>,
+    checkpoint: Option<i64>,
+    cloned_events: i64,
+    attempts: i32,
+    error: Option<String>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl Object {
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+
+    pub fn edition_id(&self) -> Uuid {
+        self.edition_id
+    }
+
+    /// Offset in milliseconds to shift events of the destination room by.
+    pub fn time_offset(&self) -> i64 {
+        self.time_offset
+    }
+
+    #[cfg(test)]
+    pub fn status(&self) -> Status {
+        self.status
+    }
+
+    pub fn destination_room_id(&self) -> Option<Uuid> {
+        self.destination_room_id
+    }
+
+    /// Source `occurred_at` of the last cloned event.
+    pub fn checkpoint(&self) -> Option<i64> {
+        self.checkpoint
+    }
+
+    pub fn cloned_events(&self) -> i64 {
+        self.cloned_events
+    }
+
+    pub fn attempts(&self) -> i32 {
+        self.attempts
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug)]
+pub struct FindQuery {
+    id: Uuid,
+}
+
+impl FindQuery {
+    pub fn new(id: Uuid) -> Self {
+        Self { id }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Option<Object>> {
+        sqlx::query_as!(
+            Object,
+            r#"
+            SELECT
+                id,
+                edition_id,
+                time_offset,
+                status AS "status!: Status",
+                destination_room_id,
+                checkpoint,
+                cloned_events,
+                attempts,
+                error,
+                created_at,
+                updated_at
+            FROM edition_commit_job
+            WHERE id = $1
+            "#,
+            self.id,
+        )
+        .fetch_optional(conn)
+        .await
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug)]
+pub struct InsertQuery {
+    edition_id: Uuid,
+    time_offset: i64,
+}
+
+impl InsertQuery {
+    pub fn new(edition_id: Uuid, time_offset: i64) -> Self {
+        Self {
+            edition_id,
+            time_offset,
+        }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Object> {
+        sqlx::query_as!(
+            Object,
+            r#"
+            INSERT INTO edition_commit_job (edition_id, time_offset)
+            VALUES ($1, $2)
+            RETURNING
+                id,
+                edition_id,
+                time_offset,
+                status AS "status!: Status",
+                destination_room_id,
+                checkpoint,
+                cloned_events,
+                attempts,
+                error,
+                created_at,
+                updated_at
+            "#,
+            self.edition_id,
+            self.time_offset,
+        )
+        .fetch_one(conn)
+        .await
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Updates a running job. Returns `None` if the job is not running anymore
+/// or has been resumed by someone else.
+#[derive(Debug)]
+pub struct UpdateQuery {
+    id: Uuid,
+    attempts: i32,
+    status: Option<Status>,
+    destination_room_id: Option<Uuid>,
+    checkpoint: Option<i64>,
+    cloned_events: Option<i64>,
+    error: Option<String>,
+}
+
+impl UpdateQuery {
+    pub fn new(job: &Object) -> Self {
+        Self {
+            id: job.id,
+            attempts: job.attempts,
+            status: None,
+            destination_room_id: None,
+            checkpoint: None,
+            cloned_events: None,
+            error: None,
+        }
+    }
+
+    pub fn status(self, status: Status) -> Self {
+        Self {
+            status: Some(status),
+            ..self
+        }
+    }
+
+    pub fn destination_room_id(self, destination_room_id: Uuid) -> Self {
+        Self {
+            destination_room_id: Some(destination_room_id),
+            ..self
+        }
+    }
+
+    pub fn checkpoint(self, checkpoint: i64, cloned_events: i64) -> Self {
+        Self {
+            checkpoint: Some(checkpoint),
+            cloned_events: Some(cloned_events),
+            ..self
+        }
+    }
+
+    pub fn error(self, error: String) -> Self {
+        Self {
+            error: Some(error),
+            ..self
+        }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Option<Object>> {
+        sqlx::query_as!(
+            Object,
+            r#"
+            UPDATE edition_commit_job
+            SET status = COALESCE($3, status),
+                destination_room_id = COALESCE($4, destination_room_id),
+                checkpoint = COALESCE($5, checkpoint),
+                cloned_events = COALESCE($6, cloned_events),
+                error = COALESCE($7, error),
+                updated_at = NOW()
+            WHERE id = $1
+            AND   attempts = $2
+            AND   status = 'running'
+            RETURNING
+                id,
+                edition_id,
+                time_offset,
+                status AS "status!: Status",
+                destination_room_id,
+                checkpoint,
+                cloned_events,
+                attempts,
+                error,
+                created_at,
+                updated_at
+            "#,
+            self.id,
+            self.attempts,
+            self.status as Option<Status>,
+            self.destination_room_id,
+            self.checkpoint,
+            self.cloned_events,
+            self.error,
+        )
+        .fetch_optional(conn)
+        .await
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Takes over running jobs which haven't been updated for `timeout`
+/// because the replica running them has died.
+#[derive(Debug)]
+pub struct ClaimStaleQuery {
+    timeout: std::time::Duration,
+}
+
+impl ClaimStaleQuery {
+    pub fn new(timeout: std::time::Duration) -> Self {
+        Self { timeout }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Vec<Object>> {
+        sqlx::query_as!(
+            Object,
+            r#"
+            UPDATE edition_commit_job
+            SET attempts = attempts + 1,
+                updated_at = NOW()
+            WHERE status = 'running'
+            AND   updated_at < NOW() - $1::BIGINT * INTERVAL '1 millisecond'
+            RETURNING
+                id,
+                edition_id,
+                time_offset,
+                status AS "status!: Status",
+                destination_room_id,
+                checkpoint,
+                cloned_events,
+                attempts,
+                error,
+                created_at,
+                updated_at
+            "#,
+            self.timeout.as_millis() as i64,
+        )
+        .fetch_all(conn)
+        .await
+    }
+}
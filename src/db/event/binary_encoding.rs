@@ -1,17 +1,43 @@
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
+/// A value stored as postcard binary.
+///
+/// Values fetched from the DB are kept encoded until `into_inner` so they may be
+/// passed through as is without decoding.
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct PostcardBin<S> {
-    value: S,
+    value: Repr<S>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+enum Repr<S> {
+    Decoded(S),
+    Encoded(Vec<u8>),
 }
 
 impl<S> PostcardBin<S> {
     pub fn new(value: S) -> Self {
-        Self { value }
+        Self {
+            value: Repr::Decoded(value),
+        }
+    }
+}
+
+impl<S: DeserializeOwned> PostcardBin<S> {
+    pub fn into_inner(self) -> Result<S, postcard::Error> {
+        match self.value {
+            Repr::Decoded(value) => Ok(value),
+            Repr::Encoded(bytes) => postcard::from_bytes(&bytes),
+        }
     }
+}
 
-    pub fn into_inner(self) -> S {
-        self.value
+impl<S: Serialize> PostcardBin<S> {
+    pub fn into_bytes(self) -> Result<Vec<u8>, postcard::Error> {
+        match self.value {
+            Repr::Decoded(value) => postcard::to_allocvec(&value),
+            Repr::Encoded(bytes) => Ok(bytes),
+        }
     }
 }
 
@@ -20,26 +46,30 @@ impl<S: Serialize> sqlx::Encode<'_, sqlx::Postgres> for PostcardBin<S> {
         &self,
         buf: &mut <sqlx::Postgres as sqlx::database::HasArguments>::ArgumentBuffer,
     ) -> sqlx::encode::IsNull {
-        let encoded =
-            postcard::to_allocvec(&self.value).expect("failed to encode as postcard binary");
+        match self.value {
+            Repr::Decoded(ref value) => {
+                let encoded =
+                    postcard::to_allocvec(value).expect("failed to encode as postcard binary");
 
-        encoded.encode_by_ref(buf)
+                encoded.encode_by_ref(buf)
+            }
+            Repr::Encoded(ref bytes) => {
+                <Vec<u8> as sqlx::Encode<sqlx::Postgres>>::encode_by_ref(bytes, buf)
+            }
+        }
     }
 }
 
-impl<B: DeserializeOwned> sqlx::Decode<'_, sqlx::Postgres> for PostcardBin<B> {
+impl<B> sqlx::Decode<'_, sqlx::Postgres> for PostcardBin<B> {
     fn decode(
         value: <sqlx::Postgres as sqlx::database::HasValueRef<'_>>::ValueRef,
     ) -> Result<Self, sqlx::error::BoxDynError> {
         // can't decode bytea as &[u8]
         let bytes: Vec<u8> = sqlx::Decode::decode(value)?;
 
-        let value: B = {
-            postcard::from_bytes(&bytes)
-                .map_err(|err| format!("failed to decode postcard binary: {err}"))?
-        };
-
-        Ok(PostcardBin::new(value))
+        Ok(PostcardBin {
+            value: Repr::Encoded(bytes),
+        })
     }
 }
 
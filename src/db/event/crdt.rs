@@ -0,0 +1,269 @@
+//! Sets in CRDT mode store deltas instead of whole elements.
+//!
+//! Each event carries a JSON object with only the changed keys of the element's document.
+//! Deltas of a label are merged as an LWW-element-map: for every key the value from the event
+//! with the latest `occurred_at` wins and a `null` value removes the key. A removed event
+//! deletes the whole element so the next delta starts a fresh document.
+//!
+//! The merge order depends only on the events themselves, so every replica replaying the same
+//! deltas ends up with the same document regardless of the order they were received in.
+
+use std::cmp::Ordering;
+
+use serde_json::Value as JsonValue;
+use sqlx::postgres::PgConnection;
+use svc_agent::AgentId;
+use uuid::Uuid;
+
+use super::{CompactEvent, ModerationStatus, Object, PostcardBin, RawObject};
+
+/// Retrieves all deltas of a set and merges them into elements.
+pub struct Query<'a> {
+    room_id: Uuid,
+    set: String,
+    attribute: Option<&'a str>,
+    occurred_at: Option<i64>,
+    original_occurred_at: i64,
+}
+
+impl<'a> Query<'a> {
+    pub fn new(room_id: Uuid, set: String, original_occurred_at: i64) -> Self {
+        Self {
+            room_id,
+            set,
+            attribute: None,
+            occurred_at: None,
+            original_occurred_at,
+        }
+    }
+
+    pub fn occurred_at(self, occurred_at: i64) -> Self {
+        Self {
+            occurred_at: Some(occurred_at),
+            ..self
+        }
+    }
+
+    /// Keeps only elements having the attribute on their latest delta.
+    pub fn attribute(self, attribute: &'a str) -> Self {
+        Self {
+            attribute: Some(attribute),
+            ..self
+        }
+    }
+
+    /// Returns merged elements in the same order as the regular set state.
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Vec<Object>> {
+        let raw_objects = sqlx::query_as!(
+            RawObject,
+            r#"
+            SELECT
+                id,
+                room_id,
+                kind,
+                set,
+                label,
+                attributes,
+                data AS "data?",
+                binary_data AS "binary_data?: PostcardBin<CompactEvent>",
+                occurred_at,
+                created_by as "created_by!: AgentId",
+                created_at,
+                deleted_at,
+                original_occurred_at,
+                original_created_by as "original_created_by: AgentId",
+                removed,
+                moderation_status AS "moderation_status!: ModerationStatus",
+                content_encrypted,
+                key_id,
+                seq
+            FROM event
+            WHERE deleted_at IS NULL
+            AND   room_id = $1
+            AND   set = $2
+            AND   label IS NOT NULL
+            AND   original_occurred_at < $3
+            AND   occurred_at < COALESCE($4, 9223372036854775807)
+            AND   moderation_status = 'approved'
+            ORDER BY label ASC, occurred_at ASC, seq ASC NULLS FIRST, created_at ASC
+            "#,
+            self.room_id,
+            self.set,
+            self.original_occurred_at,
+            self.occurred_at,
+        )
+        .fetch_all(conn)
+        .await?;
+
+        let mut deltas = Vec::with_capacity(raw_objects.len());
+
+        for raw in raw_objects {
+            deltas.push(Object::try_from(raw)?);
+        }
+
+        let mut elements = merge(deltas);
+
+        if let Some(attribute) = self.attribute {
+            elements.retain(|element| element.attributes.iter().any(|a| a == attribute));
+        }
+
+        elements.sort_by(|a, b| {
+            b.original_occurred_at
+                .cmp(&a.original_occurred_at)
+                .then_with(|| a.label.cmp(&b.label))
+        });
+
+        Ok(elements)
+    }
+}
+
+/// Merges deltas into one element per label dropping removed ones.
+///
+/// The merged element is the latest delta with `data` replaced by the merged document.
+pub fn merge(mut deltas: Vec<Object>) -> Vec<Object> {
+    deltas.sort_by(compare);
+
+    let mut elements: Vec<Object> = Vec::new();
+
+    for delta in deltas {
+        let base = match elements.last() {
+            Some(element) if element.label == delta.label => elements.pop(),
+            _ => None,
+        };
+
+        elements.push(apply(base, delta));
+    }
+
+    elements.retain(|element| !element.removed);
+    elements
+}
+
+fn compare(a: &Object, b: &Object) -> Ordering {
+    a.label
+        .cmp(&b.label)
+        .then_with(|| a.occurred_at.cmp(&b.occurred_at))
+        .then_with(|| a.seq.cmp(&b.seq))
+        .then_with(|| a.created_at.cmp(&b.created_at))
+}
+
+fn apply(base: Option<Object>, delta: Object) -> Object {
+    if delta.removed {
+        return Object {
+            data: JsonValue::Object(Default::default()),
+            ..delta
+        };
+    }
+
+    let document = base.filter(|base| !base.removed).map(|base| base.data);
+
+    let data = match (document, delta.data) {
+        (Some(JsonValue::Object(mut document)), JsonValue::Object(changes)) => {
+            for (key, value) in changes {
+                if value.is_null() {
+                    document.remove(&key);
+                } else {
+                    document.insert(key, value);
+                }
+            }
+
+            JsonValue::Object(document)
+        }
+        (_, JsonValue::Object(changes)) => JsonValue::Object(
+            changes
+                .into_iter()
+                .filter(|(_, value)| !value.is_null())
+                .collect(),
+        ),
+        // Anything but an object replaces the whole document.
+        (_, value) => value,
+    };
+
+    Object { data, ..delta }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn delta(label: &str, occurred_at: i64, data: JsonValue) -> Object {
+        serde_json::from_value(json!({
+            "id": Uuid::new_v4(),
+            "room_id": Uuid::nil(),
+            "type": "object",
+            "set": "objects",
+            "label": label,
+            "data": data,
+            "occurred_at": occurred_at,
+            "created_by": "web.user123.usr.example.org",
+            "created_at": 0,
+            "original_occurred_at": 0,
+            "original_created_by": "web.user123.usr.example.org",
+            "removed": false,
+        }))
+        .expect("Failed to build delta")
+    }
+
+    fn removal(label: &str, occurred_at: i64) -> Object {
+        Object {
+            removed: true,
+            ..delta(label, occurred_at, json!({}))
+        }
+    }
+
+    #[test]
+    fn merge_keys() {
+        let elements = merge(vec![
+            delta("a", 1, json!({ "x": 1, "y": 1 })),
+            delta("a", 2, json!({ "y": 2, "z": 2 })),
+            delta("b", 1, json!({ "x": 10 })),
+        ]);
+
+        assert_eq!(elements.len(), 2);
+        assert_eq!(elements[0].data(), &json!({ "x": 1, "y": 2, "z": 2 }));
+        assert_eq!(elements[0].occurred_at(), 2);
+        assert_eq!(elements[1].data(), &json!({ "x": 10 }));
+    }
+
+    #[test]
+    fn merge_is_order_independent() {
+        let deltas = vec![
+            delta("a", 3, json!({ "x": 3 })),
+            delta("a", 1, json!({ "x": 1, "y": 1 })),
+            delta("a", 2, json!({ "x": 2, "y": null })),
+        ];
+
+        let mut reversed = deltas.clone();
+        reversed.reverse();
+
+        let elements = merge(deltas);
+        assert_eq!(elements[0].data(), &json!({ "x": 3 }));
+        assert_eq!(merge(reversed)[0].data(), elements[0].data());
+    }
+
+    #[test]
+    fn merge_removed() {
+        let elements = merge(vec![
+            delta("a", 1, json!({ "x": 1 })),
+            removal("a", 2),
+            delta("b", 1, json!({ "x": 1, "y": 1 })),
+            removal("b", 2),
+            delta("b", 3, json!({ "y": 3 })),
+        ]);
+
+        assert_eq!(elements.len(), 1);
+        assert_eq!(elements[0].data(), &json!({ "y": 3 }));
+    }
+
+    #[test]
+    fn merge_non_object_replaces_document() {
+        let elements = merge(vec![
+            delta("a", 1, json!({ "x": 1 })),
+            delta("a", 2, json!("opaque")),
+            delta("a", 3, json!({ "y": 3 })),
+        ]);
+
+        assert_eq!(elements[0].data(), &json!({ "y": 3 }));
+    }
+}
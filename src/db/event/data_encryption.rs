@@ -0,0 +1,297 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use openssl::symm::{decrypt_aead, encrypt_aead, Cipher};
+use rand::RngCore;
+use serde_json::Value as JsonValue;
+
+use super::Object;
+use crate::config::DataEncryptionConfig;
+
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+/// Application-level encryption of `data` of events of configured kinds with AES-256-GCM.
+///
+/// Encrypted events are stored the same way as client-side encrypted ones: `data` is a string
+/// of base64 of the nonce, the encrypted JSON and the tag, `content_encrypted` is set and
+/// `key_id` names the key. The kind is authenticated along with it so the data can't be moved
+/// to another kind. Only events of configured kinds are ever decrypted.
+pub struct DataEncryption {
+    kinds: HashSet<String>,
+    key_id: String,
+    keys: HashMap<String, Vec<u8>>,
+    allow_dump: bool,
+}
+
+impl DataEncryption {
+    pub fn new(config: &DataEncryptionConfig) -> Result<Self> {
+        let mut keys = HashMap::new();
+
+        for (key_id, key) in config.keys.iter() {
+            let key = BASE64
+                .decode(key)
+                .with_context(|| format!("Invalid base64 of key '{}'", key_id))?;
+
+            if key.len() != KEY_LEN {
+                bail!("Key '{}' must be {} bytes long", key_id, KEY_LEN);
+            }
+
+            keys.insert(key_id.to_owned(), key);
+        }
+
+        if !keys.contains_key(&config.key_id) {
+            bail!("Missing key '{}' to encrypt with", config.key_id);
+        }
+
+        Ok(Self {
+            kinds: config.kinds.iter().cloned().collect(),
+            key_id: config.key_id.to_owned(),
+            keys,
+            allow_dump: config.allow_dump,
+        })
+    }
+
+    pub fn is_encrypted_kind(&self, kind: &str) -> bool {
+        self.kinds.contains(kind)
+    }
+
+    /// Whether events of the kind may leave the service in dumps and transcripts.
+    pub fn is_dumpable(&self, kind: &str) -> bool {
+        self.allow_dump || !self.is_encrypted_kind(kind)
+    }
+
+    /// Id of the key new events are encrypted with and the ciphertext of `data` of the kind.
+    pub(super) fn encrypt(&self, kind: &str, data: &JsonValue) -> Result<(String, String)> {
+        let mut nonce = [0; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce);
+
+        let plaintext = serde_json::to_vec(data).context("Failed to serialize data")?;
+        let mut tag = [0; TAG_LEN];
+
+        let ciphertext = encrypt_aead(
+            Cipher::aes_256_gcm(),
+            &self.keys[&self.key_id],
+            Some(&nonce),
+            kind.as_bytes(),
+            &plaintext,
+            &mut tag,
+        )
+        .context("Failed to encrypt data")?;
+
+        let sealed = [&nonce[..], &ciphertext, &tag].concat();
+        Ok((self.key_id.to_owned(), BASE64.encode(sealed)))
+    }
+
+    /// Ids of all the keys events may be encrypted with.
+    pub(super) fn key_ids(&self) -> Vec<String> {
+        self.keys.keys().cloned().collect()
+    }
+
+    /// Decrypts `data` of an event of the kind encrypted with the key.
+    pub(super) fn open_data(
+        &self,
+        kind: &str,
+        key_id: &str,
+        data: &JsonValue,
+    ) -> Result<JsonValue> {
+        let ciphertext = data.as_str().context("Encrypted data must be a string")?;
+
+        let key = self
+            .keys
+            .get(key_id)
+            .with_context(|| format!("Unknown key '{}'", key_id))?;
+
+        let sealed = BASE64
+            .decode(ciphertext)
+            .context("Invalid base64 of ciphertext")?;
+
+        if sealed.len() < NONCE_LEN + TAG_LEN {
+            bail!("Ciphertext is too short");
+        }
+
+        let (nonce, rest) = sealed.split_at(NONCE_LEN);
+        let (ciphertext, tag) = rest.split_at(rest.len() - TAG_LEN);
+
+        let plaintext = decrypt_aead(
+            Cipher::aes_256_gcm(),
+            key,
+            Some(nonce),
+            kind.as_bytes(),
+            ciphertext,
+            tag,
+        )
+        .context("Failed to decrypt data")?;
+
+        serde_json::from_slice(&plaintext).context("Failed to parse decrypted data")
+    }
+
+    /// Decrypts `data` of the event if it's of an encrypted kind. Others are left as is.
+    pub fn open(&self, event: &mut Object) -> Result<()> {
+        if !event.content_encrypted || !self.is_encrypted_kind(&event.kind) {
+            return Ok(());
+        }
+
+        let key_id = event.key_id.as_deref().context("Missing key id")?;
+
+        // Data is null when the query leaves it out.
+        if !event.data.is_null() {
+            event.data = self
+                .open_data(&event.kind, key_id, &event.data)
+                .with_context(|| format!("Failed to decrypt event = '{}'", event.id))?;
+        }
+
+        event.content_encrypted = false;
+        event.key_id = None;
+        Ok(())
+    }
+
+    /// Leaves events which may be dumped decrypting them.
+    pub fn open_dumpable(&self, events: Vec<Object>) -> Result<Vec<Object>> {
+        events
+            .into_iter()
+            .filter(|event| self.is_dumpable(&event.kind))
+            .map(|mut event| self.open(&mut event).map(|_| event))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use svc_agent::{AccountId, AgentId};
+    use uuid::Uuid;
+
+    use super::super::{Builder, InsertQuery};
+    use super::*;
+
+    fn build_encryption(key_id: &str, keys: &[(&str, u8)]) -> DataEncryption {
+        let config = DataEncryptionConfig {
+            kinds: vec!["private_note".to_owned(), "secret_note".to_owned()],
+            key_id: key_id.to_owned(),
+            keys: keys
+                .iter()
+                .map(|(id, byte)| (id.to_string(), BASE64.encode([*byte; KEY_LEN])))
+                .collect(),
+            allow_dump: false,
+        };
+
+        DataEncryption::new(&config).expect("Failed to build encryption")
+    }
+
+    fn build_event(kind: &str, data: &JsonValue, key_id: Option<&str>) -> Object {
+        let agent_id = AgentId::new("web", AccountId::new("user123", "example.org"));
+
+        let builder = Builder::new()
+            .room_id(Uuid::new_v4())
+            .kind(kind)
+            .data(data)
+            .occurred_at(0)
+            .created_by(&agent_id);
+
+        match key_id {
+            Some(key_id) => builder.encrypted(Some(key_id)),
+            None => builder,
+        }
+        .build()
+        .expect("Failed to build event")
+    }
+
+    fn seal(encryption: &DataEncryption, kind: &str, data: &JsonValue) -> Object {
+        let (key_id, ciphertext) = encryption.encrypt(kind, data).expect("Failed to encrypt");
+        build_event(kind, &JsonValue::String(ciphertext), Some(&key_id))
+    }
+
+    #[test]
+    fn seal_and_open() {
+        let encryption = build_encryption("k1", &[("k1", 1)]);
+        let data = json!({"text": "secret"});
+        let mut event = seal(&encryption, "private_note", &data);
+
+        assert!(!event.data.to_string().contains("secret"));
+
+        // Bound to the kind.
+        let mut moved = seal(&encryption, "private_note", &data);
+        moved.kind = "secret_note".to_owned();
+        assert!(encryption.open(&mut moved).is_err());
+
+        encryption.open(&mut event).expect("Failed to open");
+        assert_eq!(event.data, data);
+        assert!(!event.content_encrypted);
+        assert_eq!(event.key_id, None);
+
+        // Client-side encrypted events of other kinds are left as is.
+        let ciphertext = JsonValue::String("ciphertext".to_owned());
+        let mut message = build_event("message", &ciphertext, Some("k1"));
+        encryption.open(&mut message).expect("Failed to open");
+        assert_eq!(message.data, ciphertext);
+        assert!(message.content_encrypted);
+
+        assert!(encryption.is_dumpable("message"));
+        assert!(!encryption.is_dumpable("private_note"));
+    }
+
+    #[test]
+    fn open_with_rotated_key() {
+        let old = build_encryption("k1", &[("k1", 1)]);
+        let data = json!({"text": "secret"});
+        let event = seal(&old, "private_note", &data);
+
+        let rotated = build_encryption("k2", &[("k1", 1), ("k2", 2)]);
+        let mut opened = event.clone();
+        rotated.open(&mut opened).expect("Failed to open");
+        assert_eq!(opened.data, data);
+
+        let forgotten = build_encryption("k2", &[("k2", 2)]);
+        assert!(forgotten.open(&mut event.clone()).is_err());
+    }
+
+    #[test]
+    fn seal_insert_query() {
+        let encryption = build_encryption("k1", &[("k1", 1)]);
+        let agent_id = AgentId::new("web", AccountId::new("user123", "example.org"));
+        let data = json!({"text": "secret"});
+
+        let query = InsertQuery::new(
+            Uuid::new_v4(),
+            "private_note".to_owned(),
+            data.clone(),
+            0,
+            agent_id.clone(),
+            Some(&encryption),
+        )
+        .expect("Failed to build query");
+
+        assert!(query.content_encrypted);
+        assert_eq!(query.key_id.as_deref(), Some("k1"));
+        assert!(matches!(query.data, Some(JsonValue::String(_))));
+
+        let query = InsertQuery::new(
+            Uuid::new_v4(),
+            "message".to_owned(),
+            data.clone(),
+            0,
+            agent_id.clone(),
+            Some(&encryption),
+        )
+        .expect("Failed to build query");
+
+        assert!(!query.content_encrypted);
+        assert_eq!(query.data, Some(data.clone()));
+
+        // Only the service may encrypt events of encrypted kinds.
+        let result = InsertQuery::new_encrypted(
+            Uuid::new_v4(),
+            "private_note".to_owned(),
+            JsonValue::String("ciphertext".to_owned()),
+            0,
+            agent_id,
+            Some("k1".to_owned()),
+            Some(&encryption),
+        );
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,151 @@
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Event kind, the `type` of an event.
+///
+/// Kinds the service treats specially are listed explicitly, any other one is passed through
+/// as is so tenants are free to introduce their own kinds.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum EventKind {
+    Message,
+    Draw,
+    Stream,
+    Host,
+    Break,
+    VideoGroup,
+    Pin,
+    AgentEnter,
+    AgentLeft,
+    AccountBan,
+    Attachment,
+    Question,
+    Answer,
+    Poll,
+    Page,
+    Other(String),
+}
+
+impl EventKind {
+    pub fn as_str(&self) -> &str {
+        match self {
+            EventKind::Message => "message",
+            EventKind::Draw => "draw",
+            EventKind::Stream => "stream",
+            EventKind::Host => "host",
+            EventKind::Break => "break",
+            EventKind::VideoGroup => "video_group",
+            EventKind::Pin => "pin",
+            EventKind::AgentEnter => "agent_enter",
+            EventKind::AgentLeft => "agent_left",
+            EventKind::AccountBan => "account_ban",
+            EventKind::Attachment => "attachment",
+            EventKind::Question => "question",
+            EventKind::Answer => "answer",
+            EventKind::Poll => "poll",
+            EventKind::Page => "page",
+            EventKind::Other(kind) => kind,
+        }
+    }
+}
+
+impl From<&str> for EventKind {
+    fn from(kind: &str) -> Self {
+        match kind {
+            "message" => EventKind::Message,
+            "draw" => EventKind::Draw,
+            "stream" => EventKind::Stream,
+            "host" => EventKind::Host,
+            "break" => EventKind::Break,
+            "video_group" => EventKind::VideoGroup,
+            "pin" => EventKind::Pin,
+            "agent_enter" => EventKind::AgentEnter,
+            "agent_left" => EventKind::AgentLeft,
+            "account_ban" => EventKind::AccountBan,
+            "attachment" => EventKind::Attachment,
+            "question" => EventKind::Question,
+            "answer" => EventKind::Answer,
+            "poll" => EventKind::Poll,
+            "page" => EventKind::Page,
+            other => EventKind::Other(other.to_owned()),
+        }
+    }
+}
+
+impl From<String> for EventKind {
+    fn from(kind: String) -> Self {
+        match EventKind::from(kind.as_str()) {
+            EventKind::Other(_) => EventKind::Other(kind),
+            known => known,
+        }
+    }
+}
+
+impl From<EventKind> for String {
+    fn from(kind: EventKind) -> Self {
+        match kind {
+            EventKind::Other(kind) => kind,
+            known => known.as_str().to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl PartialEq<str> for EventKind {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<EventKind> for str {
+    fn eq(&self, other: &EventKind) -> bool {
+        self == other.as_str()
+    }
+}
+
+impl PartialEq<EventKind> for &str {
+    fn eq(&self, other: &EventKind) -> bool {
+        *self == other.as_str()
+    }
+}
+
+impl Serialize for EventKind {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for EventKind {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(EventKind::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        for kind in ["message", "draw", "video_group", "agent_left", "whiteboard"] {
+            assert_eq!(String::from(EventKind::from(kind)), kind);
+        }
+
+        assert_eq!(EventKind::from("stream"), EventKind::Stream);
+        assert_eq!(
+            EventKind::from("whiteboard"),
+            EventKind::Other("whiteboard".to_owned())
+        );
+    }
+
+    #[test]
+    fn compare_with_str() {
+        assert!("break" == EventKind::Break);
+        assert!(EventKind::Draw != *"message");
+    }
+}
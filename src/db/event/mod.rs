@@ -1,16 +1,20 @@
 use std::convert::TryFrom;
 
+use base64::Engine;
 use chrono::serde::{ts_milliseconds, ts_milliseconds_option};
-use chrono::{DateTime, Duration, Utc};
+use chrono::{DateTime, Duration, TimeZone, Utc};
 use serde_derive::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
-use sqlx::postgres::PgConnection;
+use sqlx::postgres::{PgConnection, Postgres};
+use sqlx::QueryBuilder;
 use svc_agent::{AccountId, AgentId};
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 ////////////////////////////////////////////////////////////////////////////////
 
-#[derive(Clone, Debug, Serialize, Deserialize, sqlx::FromRow)]
+#[derive(Clone, Debug, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+#[schema(as = Event)]
 pub struct Object {
     id: Uuid,
     room_id: Uuid,
@@ -19,11 +23,16 @@ pub struct Object {
     set: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     label: Option<String>,
-    attribute: Option<String>,
+    #[serde(flatten, with = "crate::serde::attributes")]
+    #[schema(value_type = crate::serde::attributes::MaybeLegacyAttributes)]
+    attributes: Vec<String>,
+    #[schema(value_type = Object)]
     data: JsonValue,
     occurred_at: i64,
+    #[schema(value_type = String)]
     created_by: AgentId,
     #[serde(with = "ts_milliseconds")]
+    #[schema(value_type = i64)]
     created_at: DateTime<Utc>,
     #[serde(
         with = "ts_milliseconds_option",
@@ -31,10 +40,24 @@ pub struct Object {
         skip_deserializing,
         default
     )]
+    #[schema(value_type = Option<i64>)]
     deleted_at: Option<DateTime<Utc>>,
     original_occurred_at: i64,
+    #[schema(value_type = String)]
     original_created_by: AgentId,
     removed: bool,
+    #[serde(default, skip_serializing_if = "ModerationStatus::is_approved")]
+    moderation_status: ModerationStatus,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    content_encrypted: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    key_id: Option<String>,
+    /// Monotonically increasing number missing in events created before it was introduced.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    seq: Option<i64>,
+    /// Binary encoded data passed through without decoding. `data` is null then.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    binary_data: Option<BinaryData>,
 }
 
 impl Object {
@@ -61,15 +84,51 @@ impl Object {
         self.label.as_deref()
     }
 
+    pub fn attributes(&self) -> &[String] {
+        &self.attributes
+    }
+
     #[cfg(test)]
     pub fn attribute(&self) -> Option<&str> {
-        self.attribute.as_deref()
+        self.attributes.first().map(|a| a.as_str())
     }
 
     pub fn data(&self) -> &JsonValue {
         &self.data
     }
 
+    /// Replaces the value of `data` at the JSON `pointer` with `mask` or removes it.
+    pub fn redact(&mut self, pointer: &str, mask: Option<&JsonValue>) {
+        if let Some(mask) = mask {
+            if let Some(value) = self.data.pointer_mut(pointer) {
+                *value = mask.to_owned();
+            }
+
+            return;
+        }
+
+        let (parent, key) = match pointer.rsplit_once('/') {
+            Some(split) => split,
+            None => return,
+        };
+
+        let key = key.replace("~1", "/").replace("~0", "~");
+
+        match self.data.pointer_mut(parent) {
+            Some(JsonValue::Object(object)) => {
+                object.remove(&key);
+            }
+            Some(JsonValue::Array(array)) => {
+                if let Ok(index) = key.parse::<usize>() {
+                    if index < array.len() {
+                        array.remove(index);
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
     pub fn occurred_at(&self) -> i64 {
         self.occurred_at
     }
@@ -78,6 +137,14 @@ impl Object {
         &self.created_by
     }
 
+    pub fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    pub fn original_created_by(&self) -> &AgentId {
+        &self.original_created_by
+    }
+
     #[cfg(test)]
     pub fn original_occurred_at(&self) -> i64 {
         self.original_occurred_at
@@ -87,6 +154,95 @@ impl Object {
     pub fn removed(&self) -> bool {
         self.removed
     }
+
+    #[cfg(test)]
+    pub fn moderation_status(&self) -> ModerationStatus {
+        self.moderation_status
+    }
+
+    /// Whether `data` is an opaque ciphertext string encrypted by clients.
+    pub fn content_encrypted(&self) -> bool {
+        self.content_encrypted
+    }
+
+    #[cfg(test)]
+    pub fn key_id(&self) -> Option<&str> {
+        self.key_id.as_deref()
+    }
+
+    pub fn seq(&self) -> Option<i64> {
+        self.seq
+    }
+
+    #[cfg(test)]
+    pub fn binary_data(&self) -> Option<&BinaryData> {
+        self.binary_data.as_ref()
+    }
+}
+
+/// What's left of a deleted event for clients to drop it from their state.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Tombstone {
+    id: Uuid,
+    room_id: Uuid,
+    #[serde(rename = "type")]
+    kind: String,
+    set: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+    occurred_at: i64,
+    #[serde(with = "ts_milliseconds")]
+    deleted_at: DateTime<Utc>,
+}
+
+impl Tombstone {
+    /// Returns `None` unless the event is deleted.
+    pub fn new(event: &Object) -> Option<Self> {
+        Some(Self {
+            id: event.id,
+            room_id: event.room_id,
+            kind: event.kind.clone(),
+            set: event.set.clone(),
+            label: event.label.clone(),
+            occurred_at: event.occurred_at,
+            deleted_at: event.deleted_at?,
+        })
+    }
+
+    #[cfg(test)]
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+}
+
+/// Event data in its storage binary format for clients decoding it on their side.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct BinaryData {
+    /// Binary format, `postcard` at the moment.
+    encoding: String,
+    /// Base64 encoded bytes.
+    payload: String,
+}
+
+impl BinaryData {
+    fn postcard(bytes: &[u8]) -> Self {
+        Self {
+            encoding: "postcard".to_owned(),
+            payload: base64::engine::general_purpose::STANDARD.encode(bytes),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn decode(&self) -> JsonValue {
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(&self.payload)
+            .expect("Failed to decode base64");
+
+        postcard::from_bytes::<CompactEvent>(&bytes)
+            .expect("Failed to decode postcard")
+            .into_json()
+            .expect("Failed to convert into JSON")
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, sqlx::FromRow)]
@@ -98,7 +254,8 @@ pub struct RawObject {
     set: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     label: Option<String>,
-    attribute: Option<String>,
+    #[serde(flatten, with = "crate::serde::attributes")]
+    attributes: Vec<String>,
     data: Option<JsonValue>,
     binary_data: Option<PostcardBin<CompactEvent>>,
     occurred_at: i64,
@@ -115,6 +272,50 @@ pub struct RawObject {
     original_occurred_at: i64,
     original_created_by: AgentId,
     removed: bool,
+    #[serde(default, skip_serializing_if = "ModerationStatus::is_approved")]
+    moderation_status: ModerationStatus,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    content_encrypted: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    key_id: Option<String>,
+    /// Monotonically increasing number missing in events created before it was introduced.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    seq: Option<i64>,
+}
+
+impl RawObject {
+    /// Converts into an object. Queries which skipped fetching `data` and `binary_data`
+    /// produce objects with null `data`.
+    pub(crate) fn try_into_object(self, without_data: bool) -> sqlx::Result<Object> {
+        if without_data {
+            Object::try_from(RawObject {
+                data: Some(JsonValue::Null),
+                binary_data: None,
+                ..self
+            })
+        } else {
+            Object::try_from(self)
+        }
+    }
+
+    /// Converts into an object keeping binary encoded data as is in `binary_data`.
+    pub(crate) fn try_into_object_with_binary(self) -> sqlx::Result<Object> {
+        let binary_data = match self.binary_data {
+            Some(binary) => binary
+                .into_bytes()
+                .map_err(|err| sqlx::Error::Decode(Box::new(err)))?,
+            None => return Object::try_from(self),
+        };
+
+        let mut object = Object::try_from(RawObject {
+            data: Some(JsonValue::Null),
+            binary_data: None,
+            ..self
+        })?;
+
+        object.binary_data = Some(BinaryData::postcard(&binary_data));
+        Ok(object)
+    }
 }
 
 impl TryFrom<RawObject> for Object {
@@ -124,6 +325,7 @@ impl TryFrom<RawObject> for Object {
         let data = match raw.binary_data {
             Some(binary) => binary
                 .into_inner()
+                .map_err(|err| sqlx::Error::Decode(Box::new(err)))?
                 .into_json()
                 .map_err(|err| sqlx::Error::Decode(Box::new(err)))?,
             None => raw.data.ok_or_else(|| {
@@ -137,7 +339,7 @@ impl TryFrom<RawObject> for Object {
             kind: raw.kind,
             set: raw.set,
             label: raw.label,
-            attribute: raw.attribute,
+            attributes: raw.attributes,
             data,
             occurred_at: raw.occurred_at,
             created_by: raw.created_by,
@@ -146,12 +348,39 @@ impl TryFrom<RawObject> for Object {
             original_occurred_at: raw.original_occurred_at,
             original_created_by: raw.original_created_by,
             removed: raw.removed,
+            moderation_status: raw.moderation_status,
+            content_encrypted: raw.content_encrypted,
+            key_id: raw.key_id,
+            seq: raw.seq,
+            binary_data: None,
         })
     }
 }
 
 ///////////////////////////////////////////////////////////////////////////////
 
+/// Events created by non-privileged agents in moderated rooms are kept `pending`
+/// until a moderator approves or rejects them. Only approved events are visible.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize, sqlx::Type, ToSchema,
+)]
+#[sqlx(type_name = "moderation_status", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ModerationStatus {
+    Pending,
+    #[default]
+    Approved,
+    Rejected,
+}
+
+impl ModerationStatus {
+    pub fn is_approved(&self) -> bool {
+        *self == Self::Approved
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
 #[derive(Default)]
 pub struct Builder {
     room_id: Option<Uuid>,
@@ -161,7 +390,9 @@ pub struct Builder {
     data: Option<JsonValue>,
     occurred_at: Option<i64>,
     created_by: Option<AgentId>,
-    attribute: Option<String>,
+    attributes: Vec<String>,
+    content_encrypted: bool,
+    key_id: Option<String>,
 }
 
 impl Builder {
@@ -197,9 +428,9 @@ impl Builder {
         }
     }
 
-    pub fn attribute(self, attribute: &str) -> Self {
+    pub fn attributes(self, attributes: &[String]) -> Self {
         Self {
-            attribute: Some(attribute.to_owned()),
+            attributes: attributes.to_owned(),
             ..self
         }
     }
@@ -225,6 +456,14 @@ impl Builder {
         }
     }
 
+    pub fn encrypted(self, key_id: Option<&str>) -> Self {
+        Self {
+            content_encrypted: true,
+            key_id: key_id.map(ToOwned::to_owned),
+            ..self
+        }
+    }
+
     pub fn build(self) -> Result<Object, &'static str> {
         let room_id = self.room_id.ok_or("Missing `room_id`")?;
         let kind = self.kind.ok_or("Missing `kind`")?;
@@ -239,7 +478,7 @@ impl Builder {
             kind,
             set,
             label: self.label,
-            attribute: self.attribute,
+            attributes: self.attributes,
             data,
             occurred_at,
             created_by: created_by.clone(),
@@ -248,13 +487,18 @@ impl Builder {
             original_occurred_at: occurred_at,
             original_created_by: created_by,
             removed: false,
+            moderation_status: ModerationStatus::Approved,
+            content_encrypted: self.content_encrypted,
+            key_id: self.key_id,
+            seq: None,
+            binary_data: None,
         })
     }
 }
 
 ///////////////////////////////////////////////////////////////////////////////
 
-#[derive(Clone, Copy, Debug, Deserialize)]
+#[derive(Clone, Copy, Debug, Hash, Deserialize, ToSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum Direction {
     Forward,
@@ -270,22 +514,33 @@ impl Default for Direction {
 ///////////////////////////////////////////////////////////////////////////////
 const DEFAULT_LIST_LIMIT: usize = 100000;
 
-#[derive(Debug)]
+#[derive(Debug, Hash)]
 enum KindFilter {
     Single(String),
     Multiple(Vec<String>),
 }
 
-#[derive(Debug, Default)]
+/// Lists events by an arbitrary combination of filters.
+///
+/// The query is built at runtime with only the conditions which are actually set
+/// so it isn't checked against the schema at compile time like the others in this module.
+#[derive(Debug, Default, Hash)]
 pub struct ListQuery<'a> {
     room_id: Option<Uuid>,
     kind: Option<KindFilter>,
-    set: Option<&'a str>,
+    sets: Vec<String>,
     label: Option<&'a str>,
+    label_prefix: Option<&'a str>,
     attribute: Option<&'a str>,
     last_occurred_at: Option<i64>,
+    from_occurred_at: Option<i64>,
+    to_occurred_at: Option<i64>,
     direction: Direction,
     limit: Option<usize>,
+    moderation_status: ModerationStatus,
+    without_data: bool,
+    with_binary_data: bool,
+    include_deleted: bool,
 }
 
 impl<'a> ListQuery<'a> {
@@ -300,14 +555,16 @@ impl<'a> ListQuery<'a> {
         }
     }
 
-    pub fn kind(self, kind: String) -> Self {
+    pub fn kind(self, kind: impl Into<EventKind>) -> Self {
         Self {
-            kind: Some(KindFilter::Single(kind)),
+            kind: Some(KindFilter::Single(kind.into().into())),
             ..self
         }
     }
 
-    pub fn kinds(self, kinds: Vec<String>) -> Self {
+    pub fn kinds<K: Into<EventKind>>(self, kinds: impl IntoIterator<Item = K>) -> Self {
+        let kinds = kinds.into_iter().map(|kind| kind.into().into()).collect();
+
         Self {
             kind: Some(KindFilter::Multiple(kinds)),
             ..self
@@ -316,11 +573,16 @@ impl<'a> ListQuery<'a> {
 
     pub fn set(self, set: &'a str) -> Self {
         Self {
-            set: Some(set),
+            sets: vec![set.to_owned()],
             ..self
         }
     }
 
+    /// Matches events of any of the sets. An empty list doesn't filter anything.
+    pub fn sets(self, sets: Vec<String>) -> Self {
+        Self { sets, ..self }
+    }
+
     pub fn label(self, label: &'a str) -> Self {
         Self {
             label: Some(label),
@@ -328,6 +590,14 @@ impl<'a> ListQuery<'a> {
         }
     }
 
+    /// Matches events whose label starts with the prefix.
+    pub fn label_prefix(self, label_prefix: &'a str) -> Self {
+        Self {
+            label_prefix: Some(label_prefix),
+            ..self
+        }
+    }
+
     pub fn attribute(self, attribute: &'a str) -> Self {
         Self {
             attribute: Some(attribute),
@@ -342,266 +612,553 @@ impl<'a> ListQuery<'a> {
         }
     }
 
-    pub fn direction(self, direction: Direction) -> Self {
-        Self { direction, ..self }
-    }
-
-    pub fn limit(self, limit: usize) -> Self {
+    /// Inclusive lower bound of `occurred_at`.
+    pub fn occurred_at_from(self, from_occurred_at: i64) -> Self {
         Self {
-            limit: Some(limit),
+            from_occurred_at: Some(from_occurred_at),
             ..self
         }
     }
 
-    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Vec<Object>> {
-        use serde_json::Value;
-
-        // для запросов, которые могут вернуть большое кол-во строк лучше всегда использовать какой-то дефолтный LIMIT
-        // иначе клиент может выбрать млн. строк и на сервере закончится память
-        let limit = self.limit.unwrap_or(DEFAULT_LIST_LIMIT);
-        // приводим все случае к списку kinds, если условие нужно игнорировать то это пустой список
-        // (array_length($4::text[], 1) IS NULL - это аналог $4 IS NULL для списков
-        // kind = ANY($4) это аналог конструкции kind IN ($4)
-        let kinds = match self.kind {
-            Some(KindFilter::Single(ref kind)) => vec![kind.clone()],
-            Some(KindFilter::Multiple(ref kinds)) => kinds.clone(),
-            None => vec![],
-        };
-
-        let raw_objects = match self.direction {
-            Direction::Forward => {
-                sqlx::query_as!(
-                    RawObject,
-                    r#"
-                    SELECT
-                        id,
-                        room_id,
-                        kind,
-                        set,
-                        label,
-                        data                AS "data?: Value",
-                        occurred_at,
-                        created_at,
-                        deleted_at,
-                        created_by          AS "created_by!: AgentId",
-                        original_created_by AS "original_created_by!: AgentId",
-                        original_occurred_at,
-                        removed,
-                        attribute,
-                        binary_data         AS "binary_data?: PostcardBin<CompactEvent>"
-                    FROM event
-                    WHERE deleted_at IS NULL
-                        AND ($2::uuid IS NULL OR room_id = $2)
-                        AND ($3::text IS NULL OR event.attribute = $3)
-                        AND (array_length($4::text[], 1) IS NULL OR kind = ANY($4))
-                        AND ($5::bigint IS NULL OR occurred_at > $5)
-                        AND ($6::text IS NULL OR set = $6)
-                        AND ($7::text IS NULL OR label = $7)
-                    ORDER BY occurred_at ASC, created_at ASC
-                    LIMIT $1
-                    "#,
-                    limit as i64,
-                    self.room_id,
-                    self.attribute,
-                    kinds.as_slice(),
-                    self.last_occurred_at,
-                    self.set,
-                    self.label,
-                )
-                .fetch_all(conn)
-                .await
-            }
-            Direction::Backward => {
-                sqlx::query_as!(
-                    RawObject,
-                    r#"
-                    SELECT
-                        id,
-                        room_id,
-                        kind,
-                        set,
-                        label,
-                        data                AS "data?: Value",
-                        occurred_at,
-                        created_at,
-                        deleted_at,
-                        created_by          AS "created_by!: AgentId",
-                        original_created_by AS "original_created_by!: AgentId",
-                        original_occurred_at,
-                        removed,
-                        attribute,
-                        binary_data         AS "binary_data?: PostcardBin<CompactEvent>"
-                    FROM event
-                    WHERE deleted_at IS NULL
-                        AND ($2::uuid IS NULL OR room_id = $2)
-                        AND ($3::text IS NULL OR attribute = $3)
-                        AND (array_length($4::text[], 1) IS NULL OR kind = ANY($4))
-                        AND ($5::bigint IS NULL OR occurred_at < $5)
-                        AND ($6::text IS NULL OR set = $6)
-                        AND ($7::text IS NULL OR label = $7)
-                    ORDER BY occurred_at DESC, created_at DESC
-                    LIMIT $1
-                    "#,
-                    limit as i64,
-                    self.room_id,
-                    self.attribute,
-                    kinds.as_slice(),
-                    self.last_occurred_at,
-                    self.set,
-                    self.label,
-                )
-                .fetch_all(conn)
-                .await
-            }
-        }?;
-
-        let mut objects = Vec::with_capacity(raw_objects.len());
-
-        for raw in raw_objects {
-            objects.push(Object::try_from(raw)?);
+    /// Exclusive upper bound of `occurred_at`.
+    pub fn occurred_at_to(self, to_occurred_at: i64) -> Self {
+        Self {
+            to_occurred_at: Some(to_occurred_at),
+            ..self
         }
-
-        Ok(objects)
-    }
-}
-
-///////////////////////////////////////////////////////////////////////////////
-
-#[derive(Debug)]
-pub struct InsertQuery {
-    room_id: Uuid,
-    kind: String,
-    set: String,
-    label: Option<String>,
-    data: Option<JsonValue>,
-    binary_data: Option<PostcardBin<CompactEvent>>,
-    attribute: Option<String>,
-    occurred_at: i64,
-    created_by: AgentId,
-    created_at: Option<DateTime<Utc>>,
-    removed: bool,
-    entity_type: Option<String>,
-    entity_event_id: Option<i64>,
-}
-
-impl InsertQuery {
-    pub fn new(
-        room_id: Uuid,
-        kind: String,
-        data: JsonValue,
-        occurred_at: i64,
-        created_by: AgentId,
-    ) -> Result<Self, anyhow::Error> {
-        let (data, binary_data) = match kind.as_str() {
-            "draw" => (None, Some(PostcardBin::new(CompactEvent::from_json(data)?))),
-            _ => (Some(data), None),
-        };
-
-        Ok(Self {
-            room_id,
-            set: kind.clone(),
-            kind,
-            label: None,
-            attribute: None,
-            data,
-            binary_data,
-            occurred_at,
-            created_by,
-            created_at: None,
-            removed: false,
-            entity_type: None,
-            entity_event_id: None,
-        })
     }
 
-    pub fn set(self, set: String) -> Self {
-        Self { set, ..self }
+    pub fn direction(self, direction: Direction) -> Self {
+        Self { direction, ..self }
     }
 
-    pub fn label(self, label: String) -> Self {
+    pub fn limit(self, limit: usize) -> Self {
         Self {
-            label: Some(label),
+            limit: Some(limit),
             ..self
         }
     }
 
-    pub fn attribute(self, attribute: String) -> Self {
+    pub fn moderation_status(self, moderation_status: ModerationStatus) -> Self {
         Self {
-            attribute: Some(attribute),
+            moderation_status,
             ..self
         }
     }
 
-    pub fn removed(self, removed: bool) -> Self {
-        Self { removed, ..self }
-    }
-
-    #[cfg(test)]
-    pub fn created_at(self, created_at: DateTime<Utc>) -> Self {
+    /// Don't fetch `data` and `binary_data` columns. Events are returned with null `data`.
+    pub fn without_data(self) -> Self {
         Self {
-            created_at: Some(created_at),
+            without_data: true,
             ..self
         }
     }
 
-    pub fn entity_type(self, entity_type: String) -> Self {
+    /// Return binary encoded events in `binary_data` as is instead of converting into JSON.
+    pub fn with_binary_data(self) -> Self {
         Self {
-            entity_type: Some(entity_type),
+            with_binary_data: true,
             ..self
         }
     }
 
-    pub fn entity_event_id(self, entity_event_id: i64) -> Self {
+    /// Return deleted events along with the others. They have `deleted_at` set.
+    pub fn include_deleted(self) -> Self {
         Self {
-            entity_event_id: Some(entity_event_id),
+            include_deleted: true,
             ..self
         }
     }
 
-    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Object> {
-        let raw = match self.created_at {
-            Some(created_at) => {
-                sqlx::query_as!(
-                    RawObject,
-                    r#"
-                    INSERT INTO event (
-                        room_id,
-                        set,
-                        kind,
-                        label,
-                        attribute,
-                        data,
-                        occurred_at,
-                        created_by,
-                        created_at,
-                        removed,
-                        binary_data,
-                        entity_type,
-                        entity_event_id
-                    )
-                    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
-                    RETURNING
-                        id,
-                        room_id,
-                        kind,
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Vec<Object>> {
+        let raw_objects = self
+            .build()
+            .build_query_as::<RawObject>()
+            .fetch_all(conn)
+            .await?;
+
+        let mut objects = Vec::with_capacity(raw_objects.len());
+
+        for raw in raw_objects {
+            let object = if self.with_binary_data && !self.without_data {
+                raw.try_into_object_with_binary()?
+            } else {
+                raw.try_into_object(self.without_data)?
+            };
+
+            objects.push(object);
+        }
+
+        Ok(objects)
+    }
+
+    fn build(&self) -> QueryBuilder<'a, Postgres> {
+        let mut query = QueryBuilder::new(
+            r#"
+            SELECT
+                id,
+                room_id,
+                kind,
+                set,
+                label,
+                occurred_at,
+                created_at,
+                deleted_at,
+                created_by,
+                original_created_by,
+                original_occurred_at,
+                removed,
+                moderation_status,
+                content_encrypted,
+                key_id,
+                seq,
+                attributes,
+            "#,
+        );
+
+        if self.without_data {
+            query.push("NULL::jsonb AS data, NULL::bytea AS binary_data");
+        } else {
+            query.push("data, binary_data");
+        }
+
+        query.push(" FROM event WHERE moderation_status = ");
+        query.push_bind(self.moderation_status);
+
+        if !self.include_deleted {
+            query.push(" AND deleted_at IS NULL");
+        }
+
+        if let Some(room_id) = self.room_id {
+            query.push(" AND room_id = ").push_bind(room_id);
+        }
+
+        if let Some(attribute) = self.attribute {
+            query
+                .push(" AND ")
+                .push_bind(attribute)
+                .push(" = ANY(attributes)");
+        }
+
+        match self.kind {
+            Some(KindFilter::Single(ref kind)) => {
+                query.push(" AND kind = ").push_bind(kind.clone());
+            }
+            // Empty list means no filter like it used to be for the `kind IN (...)` condition.
+            Some(KindFilter::Multiple(ref kinds)) if !kinds.is_empty() => {
+                query
+                    .push(" AND kind = ANY(")
+                    .push_bind(kinds.clone())
+                    .push(")");
+            }
+            _ => (),
+        }
+
+        match self.sets.as_slice() {
+            [] => (),
+            [set] => {
+                query.push(" AND set = ").push_bind(set.clone());
+            }
+            sets => {
+                query
+                    .push(" AND set = ANY(")
+                    .push_bind(sets.to_vec())
+                    .push(")");
+            }
+        }
+
+        if let Some(label) = self.label {
+            query.push(" AND label = ").push_bind(label);
+        }
+
+        if let Some(label_prefix) = self.label_prefix {
+            query
+                .push(" AND label LIKE ")
+                .push_bind(format!("{}%", escape_like(label_prefix)));
+        }
+
+        if let Some(last_occurred_at) = self.last_occurred_at {
+            match self.direction {
+                Direction::Forward => query.push(" AND occurred_at > "),
+                Direction::Backward => query.push(" AND occurred_at < "),
+            };
+
+            query.push_bind(last_occurred_at);
+        }
+
+        if let Some(from_occurred_at) = self.from_occurred_at {
+            query
+                .push(" AND occurred_at >= ")
+                .push_bind(from_occurred_at);
+        }
+
+        if let Some(to_occurred_at) = self.to_occurred_at {
+            query.push(" AND occurred_at < ").push_bind(to_occurred_at);
+        }
+
+        match self.direction {
+            Direction::Forward => query.push(" ORDER BY occurred_at ASC, created_at ASC"),
+            Direction::Backward => query.push(" ORDER BY occurred_at DESC, created_at DESC"),
+        };
+
+        // для запросов, которые могут вернуть большое кол-во строк лучше всегда использовать какой-то дефолтный LIMIT
+        // иначе клиент может выбрать млн. строк и на сервере закончится память
+        let limit = self.limit.unwrap_or(DEFAULT_LIST_LIMIT);
+
+        query.push(" LIMIT ").push_bind(limit as i64);
+        query
+    }
+}
+
+/// Escapes `LIKE` wildcards so the string is matched literally.
+fn escape_like(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+
+    for c in value.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            escaped.push('\\');
+        }
+
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Lists approved events of the room created or changed after the given `seq` in commit order.
+/// Soft-deleted events are included too so that the client learns about deletions.
+///
+/// Changes come from the room's `event_change` log. A changed event is returned once with `seq`
+/// of its latest change so that the client may resume after it.
+#[derive(Debug)]
+pub struct SinceQuery {
+    room_id: Uuid,
+    seq: i64,
+    limit: i64,
+}
+
+impl SinceQuery {
+    pub fn new(room_id: Uuid, seq: i64, limit: i64) -> Self {
+        Self {
+            room_id,
+            seq,
+            limit,
+        }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Vec<Object>> {
+        let raw_objects = sqlx::query_as!(
+            RawObject,
+            r#"
+            SELECT
+                event.id,
+                room_id,
+                kind,
+                set,
+                label,
+                attributes,
+                data,
+                binary_data AS "binary_data: PostcardBin<CompactEvent>",
+                occurred_at,
+                created_by AS "created_by!: AgentId",
+                created_at,
+                deleted_at,
+                original_occurred_at,
+                original_created_by AS "original_created_by!: AgentId",
+                removed,
+                moderation_status AS "moderation_status!: ModerationStatus",
+                content_encrypted,
+                key_id,
+                latest.seq AS "seq?"
+            FROM (
+                SELECT id, MAX(seq) AS seq
+                FROM (
+                    SELECT id, seq
+                    FROM event
+                    WHERE room_id = $1
+                    AND   seq > $2
+                    UNION ALL
+                    SELECT event_id AS id, seq
+                    FROM event_change
+                    WHERE room_id = $1
+                    AND   seq > $2
+                ) AS positioned
+                GROUP BY id
+            ) AS latest
+            INNER JOIN event
+            ON event.id = latest.id
+            WHERE moderation_status = 'approved'
+            ORDER BY latest.seq
+            LIMIT $3
+            "#,
+            self.room_id,
+            self.seq,
+            self.limit,
+        )
+        .fetch_all(conn)
+        .await?;
+
+        raw_objects.into_iter().map(Object::try_from).collect()
+    }
+}
+
+/// Finds `seq` of the room's event. Missing for unknown events and those created before
+/// `seq` was introduced.
+#[derive(Debug)]
+pub struct SeqQuery {
+    room_id: Uuid,
+    id: Uuid,
+}
+
+impl SeqQuery {
+    pub fn new(room_id: Uuid, id: Uuid) -> Self {
+        Self { room_id, id }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Option<i64>> {
+        let maybe_row = sqlx::query!(
+            "SELECT seq FROM event WHERE id = $1 AND room_id = $2",
+            self.id,
+            self.room_id,
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        Ok(maybe_row.and_then(|row| row.seq))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Inserts an event.
+///
+/// `original_occurred_at` and `original_created_by` are inherited from the first inserted approved event
+/// with the same label in the set by the `on_event_insert` trigger so there's no need
+/// to look it up before.
+#[derive(Debug)]
+pub struct InsertQuery {
+    room_id: Uuid,
+    kind: String,
+    set: String,
+    label: Option<String>,
+    data: Option<JsonValue>,
+    binary_data: Option<PostcardBin<CompactEvent>>,
+    attributes: Vec<String>,
+    occurred_at: i64,
+    created_by: AgentId,
+    created_at: Option<DateTime<Utc>>,
+    removed: bool,
+    entity_type: Option<String>,
+    entity_event_id: Option<i64>,
+    moderation_status: ModerationStatus,
+    content_encrypted: bool,
+    key_id: Option<String>,
+}
+
+impl InsertQuery {
+    /// Creates an event sealing its `data` if the kind is configured to be encrypted at rest
+    /// so that no caller may store it in plain text.
+    pub fn new(
+        room_id: Uuid,
+        kind: String,
+        data: JsonValue,
+        occurred_at: i64,
+        created_by: AgentId,
+        encryption: Option<&DataEncryption>,
+    ) -> Result<Self, anyhow::Error> {
+        let (data, binary_data) = match EventKind::from(kind.as_str()) {
+            EventKind::Draw => (None, Some(PostcardBin::new(CompactEvent::from_json(data)?))),
+            _ => (Some(data), None),
+        };
+
+        Self {
+            room_id,
+            set: kind.clone(),
+            kind,
+            label: None,
+            attributes: vec![],
+            data,
+            binary_data,
+            occurred_at,
+            created_by,
+            created_at: None,
+            removed: false,
+            entity_type: None,
+            entity_event_id: None,
+            moderation_status: ModerationStatus::Approved,
+            content_encrypted: false,
+            key_id: None,
+        }
+        .seal(encryption)
+    }
+
+    /// Creates an event with opaque client-side encrypted `data` which is stored as is
+    /// without conversion to the binary format whatever the kind is.
+    ///
+    /// Kinds encrypted at rest are rejected since only the service decrypts them.
+    pub fn new_encrypted(
+        room_id: Uuid,
+        kind: String,
+        data: JsonValue,
+        occurred_at: i64,
+        created_by: AgentId,
+        key_id: Option<String>,
+        encryption: Option<&DataEncryption>,
+    ) -> Result<Self, anyhow::Error> {
+        Self {
+            room_id,
+            set: kind.clone(),
+            kind,
+            label: None,
+            attributes: vec![],
+            data: Some(data),
+            binary_data: None,
+            occurred_at,
+            created_by,
+            created_at: None,
+            removed: false,
+            entity_type: None,
+            entity_event_id: None,
+            moderation_status: ModerationStatus::Approved,
+            content_encrypted: true,
+            key_id,
+        }
+        .seal(encryption)
+    }
+
+    pub fn room_id(&self) -> Uuid {
+        self.room_id
+    }
+
+    /// Encrypts `data` if the kind is configured to be encrypted at rest.
+    ///
+    /// Client-side encrypted content of such kinds is rejected since only the service
+    /// decrypts them.
+    fn seal(self, encryption: Option<&DataEncryption>) -> Result<Self, anyhow::Error> {
+        let encryption = match encryption {
+            Some(encryption) if encryption.is_encrypted_kind(&self.kind) => encryption,
+            _ => return Ok(self),
+        };
+
+        if self.content_encrypted {
+            bail!("Kind '{}' doesn't allow encrypted content", self.kind);
+        }
+
+        let data = match self.data {
+            Some(ref data) => data,
+            None => return Ok(self),
+        };
+
+        let (key_id, ciphertext) = encryption.encrypt(&self.kind, data)?;
+
+        Ok(Self {
+            data: Some(JsonValue::String(ciphertext)),
+            content_encrypted: true,
+            key_id: Some(key_id),
+            ..self
+        })
+    }
+
+    pub fn set(self, set: String) -> Self {
+        Self { set, ..self }
+    }
+
+    pub fn label(self, label: String) -> Self {
+        Self {
+            label: Some(label),
+            ..self
+        }
+    }
+
+    pub fn attributes(self, attributes: Vec<String>) -> Self {
+        Self { attributes, ..self }
+    }
+
+    pub fn removed(self, removed: bool) -> Self {
+        Self { removed, ..self }
+    }
+
+    pub fn moderation_status(self, moderation_status: ModerationStatus) -> Self {
+        Self {
+            moderation_status,
+            ..self
+        }
+    }
+
+    pub fn created_at(self, created_at: DateTime<Utc>) -> Self {
+        Self {
+            created_at: Some(created_at),
+            ..self
+        }
+    }
+
+    pub fn entity_type(self, entity_type: String) -> Self {
+        Self {
+            entity_type: Some(entity_type),
+            ..self
+        }
+    }
+
+    pub fn entity_event_id(self, entity_event_id: i64) -> Self {
+        Self {
+            entity_event_id: Some(entity_event_id),
+            ..self
+        }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Object> {
+        let raw = match self.created_at {
+            Some(created_at) => {
+                sqlx::query_as!(
+                    RawObject,
+                    r#"
+                    INSERT INTO event (
+                        room_id,
                         set,
+                        kind,
                         label,
-                        attribute,
+                        attributes,
                         data,
-                        binary_data AS "binary_data: PostcardBin<CompactEvent>",
                         occurred_at,
-                        created_by AS "created_by!: AgentId",
+                        created_by,
                         created_at,
-                        deleted_at,
-                        original_occurred_at,
-                        original_created_by as "original_created_by: AgentId",
-                        removed
+                        removed,
+                        binary_data,
+                        entity_type,
+                        entity_event_id,
+                        moderation_status,
+                        content_encrypted,
+                        key_id
+                    )
+                    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16)
+                    RETURNING
+                        id,
+                        room_id,
+                        kind,
+                        set,
+                        label,
+                        attributes,
+                        data,
+                        binary_data AS "binary_data: PostcardBin<CompactEvent>",
+                        occurred_at,
+                        created_by AS "created_by!: AgentId",
+                        created_at,
+                        deleted_at,
+                        original_occurred_at,
+                        original_created_by as "original_created_by: AgentId",
+                        removed,
+                        moderation_status AS "moderation_status!: ModerationStatus",
+                        content_encrypted,
+                        key_id,
+                        seq
                     "#,
                     self.room_id,
                     self.set,
                     self.kind,
                     self.label,
-                    self.attribute,
+                    self.attributes.as_slice(),
                     self.data,
                     self.occurred_at,
                     self.created_by as AgentId,
@@ -610,6 +1167,9 @@ impl InsertQuery {
                     self.binary_data as Option<PostcardBin<CompactEvent>>,
                     self.entity_type,
                     self.entity_event_id,
+                    self.moderation_status as ModerationStatus,
+                    self.content_encrypted,
+                    self.key_id,
                 )
                 .fetch_one(conn)
                 .await?
@@ -623,23 +1183,26 @@ impl InsertQuery {
                     set,
                     kind,
                     label,
-                    attribute,
+                    attributes,
                     data,
                     occurred_at,
                     created_by,
                     removed,
                     binary_data,
                     entity_type,
-                    entity_event_id
+                    entity_event_id,
+                    moderation_status,
+                    content_encrypted,
+                    key_id
                 )
-                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)
                 RETURNING
                     id,
                     room_id,
                     kind,
                     set,
                     label,
-                    attribute,
+                    attributes,
                     data,
                     binary_data AS "binary_data: PostcardBin<CompactEvent>",
                     occurred_at,
@@ -648,13 +1211,17 @@ impl InsertQuery {
                     deleted_at,
                     original_occurred_at,
                     original_created_by as "original_created_by: AgentId",
-                    removed
+                    removed,
+                    moderation_status AS "moderation_status!: ModerationStatus",
+                    content_encrypted,
+                    key_id,
+                    seq
                 "#,
                     self.room_id,
                     self.set,
                     self.kind,
                     self.label,
-                    self.attribute,
+                    self.attributes.as_slice(),
                     self.data,
                     self.occurred_at,
                     self.created_by as AgentId,
@@ -662,66 +1229,1031 @@ impl InsertQuery {
                     self.binary_data as Option<PostcardBin<CompactEvent>>,
                     self.entity_type,
                     self.entity_event_id,
+                    self.moderation_status as ModerationStatus,
+                    self.content_encrypted,
+                    self.key_id,
                 )
                 .fetch_one(conn)
                 .await?
             }
         };
 
-        Object::try_from(raw)
+        Object::try_from(raw)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// An event as stored in a room snapshot.
+///
+/// Binary encoded data is kept as is and JSON data is kept as text since the snapshot format
+/// can't hold arbitrary JSON values.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SnapshotEvent {
+    kind: String,
+    set: String,
+    label: Option<String>,
+    attributes: Vec<String>,
+    data: Option<String>,
+    binary_data: Option<Vec<u8>>,
+    occurred_at: i64,
+    created_by: String,
+    /// Milliseconds.
+    created_at: i64,
+    removed: bool,
+    content_encrypted: bool,
+    key_id: Option<String>,
+}
+
+impl SnapshotEvent {
+    /// Takes an event fetched with `ListQuery::with_binary_data`.
+    pub fn new(event: Object) -> anyhow::Result<Self> {
+        let (data, binary_data) = match event.binary_data {
+            Some(binary) => {
+                let bytes = base64::engine::general_purpose::STANDARD
+                    .decode(&binary.payload)
+                    .map_err(|err| anyhow!("Invalid binary data: {:?}", err))?;
+
+                (None, Some(bytes))
+            }
+            None => (Some(event.data.to_string()), None),
+        };
+
+        Ok(Self {
+            kind: event.kind,
+            set: event.set,
+            label: event.label,
+            attributes: event.attributes,
+            data,
+            binary_data,
+            occurred_at: event.occurred_at,
+            created_by: event.created_by.to_string(),
+            created_at: event.created_at.timestamp_millis(),
+            removed: event.removed,
+            content_encrypted: event.content_encrypted,
+            key_id: event.key_id,
+        })
+    }
+}
+
+/// Inserts snapshot events into the room in one statement passing binary data through as is.
+///
+/// Snapshots carry decrypted data of kinds encrypted at rest when dumping them is allowed
+/// so it's sealed again the same way as with `InsertQuery`.
+pub struct InsertSnapshotQuery<'a> {
+    room_id: Uuid,
+    events: &'a [SnapshotEvent],
+    encryption: Option<&'a DataEncryption>,
+}
+
+impl<'a> InsertSnapshotQuery<'a> {
+    /// Keep `events` under a few thousands so that the statement fits the parameters limit.
+    pub fn new(
+        room_id: Uuid,
+        events: &'a [SnapshotEvent],
+        encryption: Option<&'a DataEncryption>,
+    ) -> Self {
+        Self {
+            room_id,
+            events,
+            encryption,
+        }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> anyhow::Result<u64> {
+        if self.events.is_empty() {
+            return Ok(0);
+        }
+
+        let mut rows = Vec::with_capacity(self.events.len());
+
+        for event in self.events {
+            let data = event
+                .data
+                .as_deref()
+                .map(serde_json::from_str::<JsonValue>)
+                .transpose()?;
+
+            let created_by = event
+                .created_by
+                .parse::<AgentId>()
+                .map_err(|err| anyhow!("Invalid agent id '{}': {:?}", event.created_by, err))?;
+
+            let created_at = Utc
+                .timestamp_millis_opt(event.created_at)
+                .single()
+                .ok_or_else(|| anyhow!("Invalid created_at = {}", event.created_at))?;
+
+            let (data, content_encrypted, key_id) = match self.encryption {
+                Some(encryption) if encryption.is_encrypted_kind(&event.kind) => {
+                    if event.content_encrypted {
+                        bail!("Kind '{}' doesn't allow encrypted content", event.kind);
+                    }
+
+                    match data {
+                        Some(ref data) => {
+                            let (key_id, ciphertext) = encryption.encrypt(&event.kind, data)?;
+                            (Some(JsonValue::String(ciphertext)), true, Some(key_id))
+                        }
+                        None => (None, false, None),
+                    }
+                }
+                _ => (data, event.content_encrypted, event.key_id.clone()),
+            };
+
+            rows.push((
+                event,
+                data,
+                created_by,
+                created_at,
+                content_encrypted,
+                key_id,
+            ));
+        }
+
+        let mut query = QueryBuilder::<Postgres>::new(
+            r#"
+            INSERT INTO event (
+                room_id,
+                set,
+                kind,
+                label,
+                attributes,
+                data,
+                binary_data,
+                occurred_at,
+                created_by,
+                created_at,
+                removed,
+                content_encrypted,
+                key_id
+            )
+            "#,
+        );
+
+        query.push_values(
+            rows,
+            |mut row, (event, data, created_by, created_at, content_encrypted, key_id)| {
+                row.push_bind(self.room_id)
+                    .push_bind(&event.set)
+                    .push_bind(&event.kind)
+                    .push_bind(&event.label)
+                    .push_bind(&event.attributes)
+                    .push_bind(data)
+                    .push_bind(&event.binary_data)
+                    .push_bind(event.occurred_at)
+                    .push_bind(created_by)
+                    .push_bind(created_at)
+                    .push_bind(event.removed)
+                    .push_bind(content_encrypted)
+                    .push_bind(key_id);
+            },
+        );
+
+        let result = query.build().execute(conn).await?;
+        Ok(result.rows_affected())
+    }
+}
+
+/// Keeps `seq` allocated on insert until the end of the transaction instead of assigning
+/// the final one on commit.
+///
+/// Only for bulk inserts into rooms nobody writes to yet, e.g. adjustment clones,
+/// so that every cloned event isn't written once again on commit.
+pub async fn keep_provisional_seq(conn: &mut PgConnection) -> sqlx::Result<()> {
+    sqlx::query!("SELECT set_config('cfg.event_seq_on_commit', 'FALSE', true)")
+        .fetch_one(conn)
+        .await
+        .map(|_| ())
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Marks an entity event from NATS processed.
+///
+/// NATS delivers messages at least once, so anything caused by an entity event must be done
+/// in the same transaction right after the claim and skipped when the claim fails.
+#[derive(Debug)]
+pub struct ClaimEntityEventQuery<'a> {
+    entity_type: &'a str,
+    entity_event_id: i64,
+}
+
+impl<'a> ClaimEntityEventQuery<'a> {
+    pub fn new(entity_type: &'a str, entity_event_id: i64) -> Self {
+        Self {
+            entity_type,
+            entity_event_id,
+        }
+    }
+
+    /// Returns `false` if the entity event has been processed before.
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<bool> {
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO processed_entity_event (entity_type, entity_event_id)
+            VALUES ($1, $2)
+            ON CONFLICT DO NOTHING
+            "#,
+            self.entity_type,
+            self.entity_event_id,
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug)]
+pub struct DeleteQuery {
+    room_id: Uuid,
+    kind: EventKind,
+}
+
+impl DeleteQuery {
+    pub fn new(room_id: Uuid, kind: EventKind) -> Self {
+        Self { room_id, kind }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<()> {
+        sqlx::query!(
+            "
+            DELETE FROM event
+            WHERE deleted_at IS NULL
+            AND   room_id = $1
+            AND   kind = $2
+            ",
+            self.room_id,
+            self.kind.as_str(),
+        )
+        .execute(conn)
+        .await
+        .map(|_| ())
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Deletes up to `limit` events of the room regardless of their kind.
+#[derive(Debug)]
+pub struct RoomBatchDeleteQuery {
+    room_id: Uuid,
+    limit: i64,
+}
+
+impl RoomBatchDeleteQuery {
+    pub fn new(room_id: Uuid, limit: usize) -> Self {
+        Self {
+            room_id,
+            limit: limit as i64,
+        }
+    }
+
+    /// Returns the number of deleted events.
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<u64> {
+        sqlx::query!(
+            r#"
+            DELETE FROM event
+            WHERE id IN (
+                SELECT id
+                FROM event
+                WHERE room_id = $1
+                LIMIT $2
+            )
+            "#,
+            self.room_id,
+            self.limit,
+        )
+        .execute(conn)
+        .await
+        .map(|r| r.rows_affected())
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Deletes events of the kind keeping the last `max_per_second` ones of each set and label
+/// within every second. The last event of each set and label is kept so the final state
+/// doesn't change. Events without a label are kept as they aren't states of the same object.
+#[derive(Debug)]
+pub struct ThinQuery<'a> {
+    room_id: Uuid,
+    kind: &'a str,
+    max_per_second: i64,
+}
+
+impl<'a> ThinQuery<'a> {
+    pub fn new(room_id: Uuid, kind: &'a str, max_per_second: i64) -> Self {
+        Self {
+            room_id,
+            kind,
+            max_per_second,
+        }
+    }
+
+    /// Returns the number of deleted events.
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<u64> {
+        sqlx::query!(
+            r#"
+            DELETE FROM event
+            WHERE id IN (
+                SELECT id
+                FROM (
+                    SELECT
+                        id,
+                        ROW_NUMBER() OVER (
+                            PARTITION BY set, label, occurred_at / 1000000000
+                            ORDER BY occurred_at DESC, created_at DESC
+                        ) AS second_rank
+                    FROM event
+                    WHERE deleted_at IS NULL
+                    AND   room_id = $1
+                    AND   kind = $2
+                    AND   label IS NOT NULL
+                ) AS ranked
+                WHERE second_rank > $3
+            )
+            "#,
+            self.room_id,
+            self.kind,
+            self.max_per_second,
+        )
+        .execute(conn)
+        .await
+        .map(|result| result.rows_affected())
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug)]
+pub struct OriginalEventQuery {
+    room_id: Uuid,
+    set: String,
+    label: String,
+}
+
+impl OriginalEventQuery {
+    pub fn new(room_id: Uuid, set: String, label: String) -> Self {
+        Self {
+            room_id,
+            set,
+            label,
+        }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Option<Object>> {
+        let raw = sqlx::query_as!(
+            RawObject,
+            r#"
+            SELECT
+                id,
+                room_id,
+                kind,
+                set,
+                label,
+                attributes,
+                data,
+                binary_data as "binary_data: PostcardBin<CompactEvent>",
+                occurred_at,
+                created_by as "created_by!: AgentId",
+                created_at,
+                deleted_at,
+                original_occurred_at,
+                original_created_by as "original_created_by: AgentId",
+                removed,
+                moderation_status AS "moderation_status!: ModerationStatus",
+                content_encrypted,
+                key_id,
+                seq
+            FROM event
+            WHERE deleted_at IS NULL
+            AND   room_id = $1
+            AND   set = $2
+            AND   label = $3
+            AND   moderation_status = 'approved'
+            ORDER BY occurred_at
+            LIMIT 1
+            "#,
+            self.room_id,
+            self.set,
+            self.label,
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        match raw {
+            Some(raw) => Ok(Some(Object::try_from(raw)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug)]
+pub struct LatestEventQuery {
+    room_id: Uuid,
+    set: String,
+    label: String,
+    for_update: bool,
+}
+
+impl LatestEventQuery {
+    pub fn new(room_id: Uuid, set: String, label: String) -> Self {
+        Self {
+            room_id,
+            set,
+            label,
+            for_update: false,
+        }
+    }
+
+    /// Locks the found event until the end of the transaction.
+    pub fn for_update(self) -> Self {
+        Self {
+            for_update: true,
+            ..self
+        }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Option<Object>> {
+        if self.for_update {
+            return self.execute_for_update(conn).await;
+        }
+
+        let raw = sqlx::query_as!(
+            RawObject,
+            r#"
+            SELECT
+                id,
+                room_id,
+                kind,
+                set,
+                label,
+                attributes,
+                data,
+                binary_data as "binary_data: PostcardBin<CompactEvent>",
+                occurred_at,
+                created_by as "created_by!: AgentId",
+                created_at,
+                deleted_at,
+                original_occurred_at,
+                original_created_by as "original_created_by: AgentId",
+                removed,
+                moderation_status AS "moderation_status!: ModerationStatus",
+                content_encrypted,
+                key_id,
+                seq
+            FROM event
+            WHERE deleted_at IS NULL
+            AND   room_id = $1
+            AND   set = $2
+            AND   label = $3
+            AND   moderation_status = 'approved'
+            ORDER BY occurred_at DESC
+            LIMIT 1
+            "#,
+            self.room_id,
+            self.set,
+            self.label,
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        match raw {
+            Some(raw) => Ok(Some(Object::try_from(raw)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn execute_for_update(self, conn: &mut PgConnection) -> sqlx::Result<Option<Object>> {
+        let raw = sqlx::query_as!(
+            RawObject,
+            r#"
+            SELECT
+                id,
+                room_id,
+                kind,
+                set,
+                label,
+                attributes,
+                data,
+                binary_data as "binary_data: PostcardBin<CompactEvent>",
+                occurred_at,
+                created_by as "created_by!: AgentId",
+                created_at,
+                deleted_at,
+                original_occurred_at,
+                original_created_by as "original_created_by: AgentId",
+                removed,
+                moderation_status AS "moderation_status!: ModerationStatus",
+                content_encrypted,
+                key_id,
+                seq
+            FROM event
+            WHERE deleted_at IS NULL
+            AND   room_id = $1
+            AND   set = $2
+            AND   label = $3
+            AND   moderation_status = 'approved'
+            ORDER BY occurred_at DESC
+            LIMIT 1
+            FOR UPDATE
+            "#,
+            self.room_id,
+            self.set,
+            self.label,
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        match raw {
+            Some(raw) => Ok(Some(Object::try_from(raw)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Lists revisions of the label in the set, i.e. all its approved events, in `occurred_at` order.
+#[derive(Debug)]
+pub struct HistoryQuery {
+    room_id: Uuid,
+    set: String,
+    label: String,
+    after_occurred_at: Option<i64>,
+    limit: i64,
+}
+
+impl HistoryQuery {
+    pub fn new(room_id: Uuid, set: String, label: String, limit: i64) -> Self {
+        Self {
+            room_id,
+            set,
+            label,
+            after_occurred_at: None,
+            limit,
+        }
+    }
+
+    /// Skips revisions up to the given `occurred_at` to fetch the next page.
+    pub fn after_occurred_at(self, after_occurred_at: Option<i64>) -> Self {
+        Self {
+            after_occurred_at,
+            ..self
+        }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Vec<Object>> {
+        let raw_objects = sqlx::query_as!(
+            RawObject,
+            r#"
+            SELECT
+                id,
+                room_id,
+                kind,
+                set,
+                label,
+                attributes,
+                data,
+                binary_data as "binary_data: PostcardBin<CompactEvent>",
+                occurred_at,
+                created_by as "created_by!: AgentId",
+                created_at,
+                deleted_at,
+                original_occurred_at,
+                original_created_by as "original_created_by: AgentId",
+                removed,
+                moderation_status AS "moderation_status!: ModerationStatus",
+                content_encrypted,
+                key_id,
+                seq
+            FROM event
+            WHERE deleted_at IS NULL
+            AND   room_id = $1
+            AND   set = $2
+            AND   label = $3
+            AND   ($4::BIGINT IS NULL OR occurred_at > $4)
+            AND   moderation_status = 'approved'
+            ORDER BY occurred_at
+            LIMIT $5
+            "#,
+            self.room_id,
+            self.set,
+            self.label,
+            self.after_occurred_at,
+            self.limit,
+        )
+        .fetch_all(conn)
+        .await?;
+
+        raw_objects.into_iter().map(Object::try_from).collect()
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Finds the version of the room's events bumped by the database on every insert, update
+/// and delete of its events.
+#[derive(Debug)]
+pub struct RoomVersionQuery {
+    room_id: Uuid,
+}
+
+impl RoomVersionQuery {
+    pub fn new(room_id: Uuid) -> Self {
+        Self { room_id }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<i64> {
+        let row = sqlx::query!(
+            r#"
+            SELECT COALESCE(
+                (SELECT version FROM room_version WHERE room_id = $1),
+                0
+            ) AS "version!"
+            "#,
+            self.room_id,
+        )
+        .fetch_one(conn)
+        .await?;
+
+        Ok(row.version)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Counts elements of the set which got new events after the given `occurred_at` or `seq`.
+///
+/// Each label is counted once so editing an element doesn't add to the count.
+/// Removal events are not counted.
+#[derive(Debug)]
+pub struct CountSinceQuery {
+    room_id: Uuid,
+    set: String,
+    occurred_at: Option<i64>,
+    seq: Option<i64>,
+}
+
+impl CountSinceQuery {
+    pub fn new(room_id: Uuid, set: String) -> Self {
+        Self {
+            room_id,
+            set,
+            occurred_at: None,
+            seq: None,
+        }
+    }
+
+    pub fn occurred_at(self, occurred_at: i64) -> Self {
+        Self {
+            occurred_at: Some(occurred_at),
+            ..self
+        }
+    }
+
+    pub fn seq(self, seq: i64) -> Self {
+        Self {
+            seq: Some(seq),
+            ..self
+        }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<i64> {
+        sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(DISTINCT COALESCE(label, id::text)) AS "count!"
+            FROM event
+            WHERE deleted_at IS NULL
+            AND   room_id = $1
+            AND   set = $2
+            AND   ($3::bigint IS NULL OR occurred_at > $3)
+            AND   ($4::bigint IS NULL OR seq > $4)
+            AND   removed = 'f'
+            AND   moderation_status = 'approved'
+            "#,
+            self.room_id,
+            self.set,
+            self.occurred_at,
+            self.seq,
+        )
+        .fetch_one(conn)
+        .await
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Lists events which are currently pinned in the room, i.e. the latest event
+/// of each set/label having the `pinned` attribute.
+#[derive(Debug)]
+pub struct PinnedListQuery {
+    room_id: Uuid,
+}
+
+impl PinnedListQuery {
+    pub fn new(room_id: Uuid) -> Self {
+        Self { room_id }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Vec<Object>> {
+        let raw_objects = sqlx::query_as!(
+            RawObject,
+            r#"
+            SELECT
+                id,
+                room_id,
+                kind,
+                set,
+                label,
+                attributes,
+                data,
+                binary_data as "binary_data: PostcardBin<CompactEvent>",
+                occurred_at,
+                created_by as "created_by!: AgentId",
+                created_at,
+                deleted_at,
+                original_occurred_at,
+                original_created_by as "original_created_by: AgentId",
+                removed,
+                moderation_status AS "moderation_status!: ModerationStatus",
+                content_encrypted,
+                key_id,
+                seq
+            FROM (
+                -- Events without a label are standalone so they're never superseded.
+                SELECT DISTINCT ON(set, COALESCE(label, id::TEXT)) *
+                FROM event
+                WHERE deleted_at IS NULL
+                AND   room_id = $1
+                AND   moderation_status = 'approved'
+                ORDER BY set, COALESCE(label, id::TEXT), occurred_at DESC
+            ) AS latest
+            WHERE 'pinned' = ANY(attributes)
+            AND   removed = 'f'
+            ORDER BY occurred_at
+            "#,
+            self.room_id,
+        )
+        .fetch_all(conn)
+        .await?;
+
+        let mut objects = Vec::with_capacity(raw_objects.len());
+
+        for raw in raw_objects {
+            objects.push(Object::try_from(raw)?);
+        }
+
+        Ok(objects)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Updates attributes of the event in place.
+/// Either adds or removes a single attribute; when none is given all attributes are cleared.
+#[derive(Debug)]
+pub struct AttributeUpdateQuery {
+    id: Uuid,
+    attribute: Option<String>,
+    add: bool,
+}
+
+impl AttributeUpdateQuery {
+    pub fn new(id: Uuid) -> Self {
+        Self {
+            id,
+            attribute: None,
+            add: false,
+        }
+    }
+
+    pub fn add(self, attribute: String) -> Self {
+        Self {
+            attribute: Some(attribute),
+            add: true,
+            ..self
+        }
+    }
+
+    pub fn remove(self, attribute: String) -> Self {
+        Self {
+            attribute: Some(attribute),
+            add: false,
+            ..self
+        }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Option<Object>> {
+        let raw = sqlx::query_as!(
+            RawObject,
+            r#"
+            UPDATE event
+            SET attributes = (
+                CASE
+                    WHEN $2::TEXT IS NULL THEN '{}'
+                    WHEN $3 THEN array_append(array_remove(attributes, $2), $2)
+                    ELSE array_remove(attributes, $2)
+                END
+            )
+            WHERE id = $1
+            RETURNING
+                id,
+                room_id,
+                kind,
+                set,
+                label,
+                attributes,
+                data,
+                binary_data as "binary_data: PostcardBin<CompactEvent>",
+                occurred_at,
+                created_by as "created_by!: AgentId",
+                created_at,
+                deleted_at,
+                original_occurred_at,
+                original_created_by as "original_created_by: AgentId",
+                removed,
+                moderation_status AS "moderation_status!: ModerationStatus",
+                content_encrypted,
+                key_id,
+                seq
+            "#,
+            self.id,
+            self.attribute,
+            self.add,
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        match raw {
+            Some(raw) => Ok(Some(Object::try_from(raw)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Resolves a pending event by setting its moderation status.
+/// Events which are not pending anymore are left untouched.
+#[derive(Debug)]
+pub struct ModerationUpdateQuery {
+    room_id: Uuid,
+    id: Uuid,
+    moderation_status: ModerationStatus,
+}
+
+impl ModerationUpdateQuery {
+    pub fn new(room_id: Uuid, id: Uuid, moderation_status: ModerationStatus) -> Self {
+        Self {
+            room_id,
+            id,
+            moderation_status,
+        }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Option<Object>> {
+        let raw = sqlx::query_as!(
+            RawObject,
+            r#"
+            UPDATE event
+            SET moderation_status = $3
+            WHERE id = $2
+            AND   room_id = $1
+            AND   deleted_at IS NULL
+            AND   moderation_status = 'pending'
+            RETURNING
+                id,
+                room_id,
+                kind,
+                set,
+                label,
+                attributes,
+                data,
+                binary_data as "binary_data: PostcardBin<CompactEvent>",
+                occurred_at,
+                created_by as "created_by!: AgentId",
+                created_at,
+                deleted_at,
+                original_occurred_at,
+                original_created_by as "original_created_by: AgentId",
+                removed,
+                moderation_status AS "moderation_status!: ModerationStatus",
+                content_encrypted,
+                key_id,
+                seq
+            "#,
+            self.room_id,
+            self.id,
+            self.moderation_status as ModerationStatus,
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        match raw {
+            Some(raw) => Ok(Some(Object::try_from(raw)?)),
+            None => Ok(None),
+        }
     }
 }
 
-///////////////////////////////////////////////////////////////////////////////
+////////////////////////////////////////////////////////////////////////////////
 
+/// Soft-deletes events created by the account in the room since the given moment.
 #[derive(Debug)]
-pub struct DeleteQuery<'a> {
+pub struct DeleteByAccountQuery {
     room_id: Uuid,
-    kind: &'a str,
+    account_id: AccountId,
+    since: DateTime<Utc>,
 }
 
-impl<'a> DeleteQuery<'a> {
-    pub fn new(room_id: Uuid, kind: &'a str) -> Self {
-        Self { room_id, kind }
+impl DeleteByAccountQuery {
+    pub fn new(room_id: Uuid, account_id: AccountId, since: DateTime<Utc>) -> Self {
+        Self {
+            room_id,
+            account_id,
+            since,
+        }
     }
 
-    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<()> {
-        sqlx::query!(
-            "
-            DELETE FROM event
-            WHERE deleted_at IS NULL
-            AND   room_id = $1
-            AND   kind = $2
-            ",
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Vec<Object>> {
+        let raws = sqlx::query_as!(
+            RawObject,
+            r#"
+            UPDATE event
+            SET deleted_at = NOW()
+            WHERE room_id = $1
+            AND   (created_by).account_id = $2
+            AND   created_at >= $3
+            AND   deleted_at IS NULL
+            RETURNING
+                id,
+                room_id,
+                kind,
+                set,
+                label,
+                attributes,
+                data,
+                binary_data as "binary_data: PostcardBin<CompactEvent>",
+                occurred_at,
+                created_by as "created_by!: AgentId",
+                created_at,
+                deleted_at,
+                original_occurred_at,
+                original_created_by as "original_created_by: AgentId",
+                removed,
+                moderation_status AS "moderation_status!: ModerationStatus",
+                content_encrypted,
+                key_id,
+                seq
+            "#,
             self.room_id,
-            self.kind,
+            self.account_id as AccountId,
+            self.since,
         )
-        .execute(conn)
-        .await
-        .map(|_| ())
+        .fetch_all(conn)
+        .await?;
+
+        raws.into_iter().map(Object::try_from).collect()
     }
 }
 
-///////////////////////////////////////////////////////////////////////////////
+////////////////////////////////////////////////////////////////////////////////
 
+/// Events created by the account in rooms of the audience including deleted ones.
+///
+/// Goes in `id` order after `after_id` to be read in batches.
 #[derive(Debug)]
-pub struct OriginalEventQuery {
-    room_id: Uuid,
-    set: String,
-    label: String,
+pub struct AccountListQuery {
+    account_id: AccountId,
+    audience: String,
+    after_id: Option<Uuid>,
+    limit: i64,
 }
 
-impl OriginalEventQuery {
-    pub fn new(room_id: Uuid, set: String, label: String) -> Self {
+impl AccountListQuery {
+    pub fn new(
+        account_id: AccountId,
+        audience: String,
+        after_id: Option<Uuid>,
+        limit: i64,
+    ) -> Self {
         Self {
-            room_id,
-            set,
-            label,
+            account_id,
+            audience,
+            after_id,
+            limit,
         }
     }
 
-    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Option<Object>> {
-        let raw = sqlx::query_as!(
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Vec<Object>> {
+        let raws = sqlx::query_as!(
             RawObject,
             r#"
             SELECT
@@ -730,7 +2262,7 @@ impl OriginalEventQuery {
                 kind,
                 set,
                 label,
-                attribute,
+                attributes,
                 data,
                 binary_data as "binary_data: PostcardBin<CompactEvent>",
                 occurred_at,
@@ -739,36 +2271,154 @@ impl OriginalEventQuery {
                 deleted_at,
                 original_occurred_at,
                 original_created_by as "original_created_by: AgentId",
-                removed
+                removed,
+                moderation_status AS "moderation_status!: ModerationStatus",
+                content_encrypted,
+                key_id,
+                seq
             FROM event
-            WHERE deleted_at IS NULL
-            AND   room_id = $1
-            AND   set = $2
-            AND   label = $3
-            ORDER BY occurred_at
-            LIMIT 1
+            WHERE ((created_by).account_id = $1 OR (original_created_by).account_id = $1)
+            AND   room_id IN (SELECT id FROM room WHERE audience = $2)
+            AND   ($3::UUID IS NULL OR id > $3)
+            ORDER BY id
+            LIMIT $4
             "#,
-            self.room_id,
-            self.set,
-            self.label,
+            self.account_id as AccountId,
+            self.audience,
+            self.after_id,
+            self.limit,
         )
-        .fetch_optional(conn)
+        .fetch_all(conn)
         .await?;
 
-        match raw {
-            Some(raw) => Ok(Some(Object::try_from(raw)?)),
-            None => Ok(None),
+        raws.into_iter().map(Object::try_from).collect()
+    }
+}
+
+/// Replaces the account with `anonymous` agent as the author of a batch of its events
+/// in rooms of the audience and drops `author_fields` keys from their `data`.
+/// Encrypted `data` is a string which is left as is since it can't be looked into.
+///
+/// Anonymized events don't match the account anymore so it's executed until nothing's left.
+#[derive(Debug)]
+pub struct AnonymizeAccountQuery<'a> {
+    account_id: AccountId,
+    audience: String,
+    anonymous: AgentId,
+    author_fields: &'a [String],
+    limit: i64,
+}
+
+impl<'a> AnonymizeAccountQuery<'a> {
+    pub fn new(
+        account_id: AccountId,
+        audience: String,
+        anonymous: AgentId,
+        author_fields: &'a [String],
+        limit: i64,
+    ) -> Self {
+        Self {
+            account_id,
+            audience,
+            anonymous,
+            author_fields,
+            limit,
         }
     }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<u64> {
+        sqlx::query!(
+            r#"
+            UPDATE event
+            SET created_by = CASE
+                    WHEN (created_by).account_id = $1 THEN $3
+                    ELSE created_by
+                END,
+                original_created_by = CASE
+                    WHEN (original_created_by).account_id = $1 THEN $3
+                    ELSE original_created_by
+                END,
+                data = CASE
+                    WHEN jsonb_typeof(data) = 'object' THEN data - $4::TEXT[]
+                    ELSE data
+                END
+            WHERE id IN (
+                SELECT id
+                FROM event
+                WHERE ((created_by).account_id = $1 OR (original_created_by).account_id = $1)
+                AND   room_id IN (SELECT id FROM room WHERE audience = $2)
+                LIMIT $5
+            )
+            "#,
+            self.account_id as AccountId,
+            self.audience,
+            self.anonymous as AgentId,
+            self.author_fields,
+            self.limit,
+        )
+        .execute(conn)
+        .await
+        .map(|r| r.rows_affected())
+    }
 }
 
 ////////////////////////////////////////////////////////////////////////////////
 
+/// Number of events that vacuum removes (or would remove) by the reason.
+///
+/// An event matching several reasons is counted once in the first of them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct VacuumStats {
+    pub too_deep_history: i64,
+    pub too_old_history: i64,
+    pub too_old_deleted_labels: i64,
+    pub expired_by_retention: i64,
+}
+
+impl VacuumStats {
+    pub fn total(&self) -> i64 {
+        self.too_deep_history
+            + self.too_old_history
+            + self.too_old_deleted_labels
+            + self.expired_by_retention
+    }
+
+    fn from_rows(rows: Vec<VacuumStatsRow>) -> Self {
+        rows.into_iter().fold(Self::default(), |mut stats, row| {
+            match row.category.as_str() {
+                "too_deep_history" => stats.too_deep_history += row.count,
+                "too_old_history" => stats.too_old_history += row.count,
+                "too_old_deleted_labels" => stats.too_old_deleted_labels += row.count,
+                "expired_by_retention" => stats.expired_by_retention += row.count,
+                _ => (),
+            }
+
+            stats
+        })
+    }
+}
+
+impl std::ops::AddAssign for VacuumStats {
+    fn add_assign(&mut self, other: Self) {
+        self.too_deep_history += other.too_deep_history;
+        self.too_old_history += other.too_old_history;
+        self.too_old_deleted_labels += other.too_old_deleted_labels;
+        self.expired_by_retention += other.expired_by_retention;
+    }
+}
+
+struct VacuumStatsRow {
+    category: String,
+    count: i64,
+}
+
+/// Deletes a batch of at most `limit` events that are out of the history limits.
 #[derive(Debug)]
 pub struct VacuumQuery {
     max_history_size: usize,
     max_history_lifetime: Duration,
     max_deleted_lifetime: Duration,
+    limit: i64,
 }
 
 impl VacuumQuery {
@@ -776,42 +2426,43 @@ impl VacuumQuery {
         max_history_size: usize,
         max_history_lifetime: Duration,
         max_deleted_lifetime: Duration,
+        limit: usize,
     ) -> Self {
         Self {
             max_history_size,
             max_history_lifetime,
             max_deleted_lifetime,
+            limit: limit as i64,
         }
     }
 
-    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<()> {
-        sqlx::query!(
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<VacuumStats> {
+        sqlx::query_as!(
+            VacuumStatsRow,
             r#"
-            DELETE FROM event
-            WHERE id IN (
-                -- Exclude preserved rooms and calculate reverse ordinal (history depth).
-                WITH sub AS (
-                    SELECT
-                        e.*,
-                        ROW_NUMBER() OVER (
-                            PARTITION BY e.room_id, e.set, e.label
-                            ORDER BY e.occurred_at DESC
-                        ) AS reverse_ordinal
-                    FROM event AS e
-                    INNER JOIN room AS r
-                    ON r.id = e.room_id
-                    WHERE r.preserve_history = 'f'
-                )
-
+            -- Exclude preserved rooms and calculate reverse ordinal (history depth).
+            WITH sub AS (
+                SELECT
+                    e.*,
+                    ROW_NUMBER() OVER (
+                        PARTITION BY e.room_id, e.set, e.label
+                        ORDER BY e.occurred_at DESC
+                    ) AS reverse_ordinal
+                FROM event AS e
+                INNER JOIN room AS r
+                ON r.id = e.room_id
+                WHERE r.preserve_history = 'f'
+            ),
+            candidates AS (
                 -- Too deep history.
-                SELECT id
+                SELECT id, 'too_deep_history' AS category, 1 AS priority
                 FROM sub
                 WHERE reverse_ordinal > $1
 
                 UNION ALL
 
                 -- Too old history.
-                SELECT id
+                SELECT id, 'too_old_history' AS category, 2 AS priority
                 FROM sub
                 WHERE reverse_ordinal > 1
                 AND created_at < NOW() - INTERVAL '1 second' * $2
@@ -819,25 +2470,175 @@ impl VacuumQuery {
                 UNION ALL
 
                 -- Too old deleted labels.
-                SELECT e.id
+                SELECT e.id, 'too_old_deleted_labels' AS category, 3 AS priority
                 FROM sub
                 INNER JOIN event AS e
                 ON  e.room_id = sub.room_id
                 AND e.set = sub.set
                 AND e.label = sub.label
                 WHERE e.deleted_at IS NULL
-                AND   sub.attribute = 'deleted'
+                AND   'deleted' = ANY(sub.attributes)
                 AND   sub.reverse_ordinal = 1
                 AND   sub.created_at < NOW() - INTERVAL '1 second' * $3
+
+                UNION ALL
+
+                -- Expired by retention rules, room rules override audience ones.
+                -- Explicit rules apply to preserved rooms as well.
+                SELECT e.id, 'expired_by_retention' AS category, 4 AS priority
+                FROM event AS e
+                INNER JOIN room AS r
+                ON r.id = e.room_id
+                INNER JOIN retention_rule AS rr
+                ON rr.kind = e.kind
+                AND (
+                    rr.room_id = e.room_id
+                    OR (
+                        rr.audience = r.audience
+                        AND NOT EXISTS (
+                            SELECT 1
+                            FROM retention_rule
+                            WHERE room_id = e.room_id
+                            AND   kind = e.kind
+                        )
+                    )
+                )
+                WHERE e.created_at < NOW() - INTERVAL '1 second' * rr.max_lifetime
+            ),
+            batch AS (
+                SELECT DISTINCT ON (id) id, category
+                FROM candidates
+                ORDER BY id, priority
+                LIMIT $4
+            ),
+            deleted AS (
+                DELETE FROM event
+                WHERE id IN (SELECT id FROM batch)
+                RETURNING id
             )
+            SELECT
+                batch.category AS "category!",
+                COUNT(*) AS "count!"
+            FROM batch
+            INNER JOIN deleted
+            ON deleted.id = batch.id
+            GROUP BY batch.category
             "#,
             self.max_history_size as i64,
             self.max_history_lifetime.num_seconds() as i64,
             self.max_deleted_lifetime.num_seconds() as i64,
+            self.limit,
         )
-        .execute(conn)
+        .fetch_all(conn)
         .await
-        .map(|_| ())
+        .map(VacuumStats::from_rows)
+    }
+}
+
+/// Counts events that `VacuumQuery` would delete without deleting them.
+#[derive(Debug)]
+pub struct VacuumCountQuery {
+    max_history_size: usize,
+    max_history_lifetime: Duration,
+    max_deleted_lifetime: Duration,
+}
+
+impl VacuumCountQuery {
+    pub fn new(
+        max_history_size: usize,
+        max_history_lifetime: Duration,
+        max_deleted_lifetime: Duration,
+    ) -> Self {
+        Self {
+            max_history_size,
+            max_history_lifetime,
+            max_deleted_lifetime,
+        }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<VacuumStats> {
+        sqlx::query_as!(
+            VacuumStatsRow,
+            r#"
+            WITH sub AS (
+                SELECT
+                    e.*,
+                    ROW_NUMBER() OVER (
+                        PARTITION BY e.room_id, e.set, e.label
+                        ORDER BY e.occurred_at DESC
+                    ) AS reverse_ordinal
+                FROM event AS e
+                INNER JOIN room AS r
+                ON r.id = e.room_id
+                WHERE r.preserve_history = 'f'
+            ),
+            candidates AS (
+                SELECT id, 'too_deep_history' AS category, 1 AS priority
+                FROM sub
+                WHERE reverse_ordinal > $1
+
+                UNION ALL
+
+                SELECT id, 'too_old_history' AS category, 2 AS priority
+                FROM sub
+                WHERE reverse_ordinal > 1
+                AND created_at < NOW() - INTERVAL '1 second' * $2
+
+                UNION ALL
+
+                SELECT e.id, 'too_old_deleted_labels' AS category, 3 AS priority
+                FROM sub
+                INNER JOIN event AS e
+                ON  e.room_id = sub.room_id
+                AND e.set = sub.set
+                AND e.label = sub.label
+                WHERE e.deleted_at IS NULL
+                AND   'deleted' = ANY(sub.attributes)
+                AND   sub.reverse_ordinal = 1
+                AND   sub.created_at < NOW() - INTERVAL '1 second' * $3
+
+                UNION ALL
+
+                -- Expired by retention rules, room rules override audience ones.
+                -- Explicit rules apply to preserved rooms as well.
+                SELECT e.id, 'expired_by_retention' AS category, 4 AS priority
+                FROM event AS e
+                INNER JOIN room AS r
+                ON r.id = e.room_id
+                INNER JOIN retention_rule AS rr
+                ON rr.kind = e.kind
+                AND (
+                    rr.room_id = e.room_id
+                    OR (
+                        rr.audience = r.audience
+                        AND NOT EXISTS (
+                            SELECT 1
+                            FROM retention_rule
+                            WHERE room_id = e.room_id
+                            AND   kind = e.kind
+                        )
+                    )
+                )
+                WHERE e.created_at < NOW() - INTERVAL '1 second' * rr.max_lifetime
+            ),
+            classified AS (
+                SELECT DISTINCT ON (id) id, category
+                FROM candidates
+                ORDER BY id, priority
+            )
+            SELECT
+                category AS "category!",
+                COUNT(*) AS "count!"
+            FROM classified
+            GROUP BY category
+            "#,
+            self.max_history_size as i64,
+            self.max_history_lifetime.num_seconds() as i64,
+            self.max_deleted_lifetime.num_seconds() as i64,
+        )
+        .fetch_all(conn)
+        .await
+        .map(VacuumStats::from_rows)
     }
 }
 
@@ -847,10 +2648,10 @@ pub enum AgentAction {
 }
 
 impl AgentAction {
-    fn as_str(&self) -> &'static str {
+    fn kind(&self) -> EventKind {
         match self {
-            AgentAction::Left => "agent_left",
-            AgentAction::Enter => "agent_enter",
+            AgentAction::Left => EventKind::AgentLeft,
+            AgentAction::Enter => EventKind::AgentEnter,
         }
     }
 }
@@ -859,6 +2660,7 @@ pub async fn insert_agent_action(
     room: &super::room::Object,
     action: AgentAction,
     agent_id: &AgentId,
+    encryption: Option<&DataEncryption>,
     conn: &mut PgConnection,
 ) -> std::result::Result<(), anyhow::Error> {
     let occurred_at = match room.time().as_ref().map(|t| t.start()) {
@@ -870,13 +2672,13 @@ pub async fn insert_agent_action(
         }
     };
 
-    let action = action.as_str();
     InsertQuery::new(
         room.id(),
-        action.to_owned(),
+        action.kind().into(),
         JsonValue::Null,
         occurred_at,
         agent_id.to_owned(),
+        encryption,
     )?
     .execute(conn)
     .await?;
@@ -889,6 +2691,7 @@ pub async fn insert_account_ban_event(
     value: bool,
     reason: Option<String>,
     agent_id: &AgentId,
+    encryption: Option<&DataEncryption>,
     conn: &mut PgConnection,
 ) -> anyhow::Result<()> {
     let occurred_at = match room.time().as_ref().map(|t| t.start()) {
@@ -902,20 +2705,253 @@ pub async fn insert_account_ban_event(
 
     InsertQuery::new(
         room.id(),
-        "account_ban".to_string(),
+        EventKind::AccountBan.into(),
         serde_json::json!({ "account_id": banned_user.to_owned(), "value": value, "reason": reason }),
         occurred_at,
         agent_id.to_owned(),
+        encryption,
     )?
     .execute(conn)
     .await?;
     Ok(())
 }
 
+////////////////////////////////////////////////////////////////////////////////
+
+/// A `draw` event still stored as JSON `data` from before the binary format.
+#[derive(Debug)]
+pub struct LegacyDraw {
+    pub id: Uuid,
+    pub data: JsonValue,
+}
+
+/// Lists legacy `draw` events in `id` order so the scan can be resumed after the last one.
+/// Encrypted events are opaque so they are never converted.
+#[derive(Debug)]
+pub struct LegacyDrawListQuery {
+    after_id: Option<Uuid>,
+    limit: i64,
+}
+
+impl LegacyDrawListQuery {
+    pub fn new(after_id: Option<Uuid>, limit: i64) -> Self {
+        Self { after_id, limit }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Vec<LegacyDraw>> {
+        sqlx::query_as!(
+            LegacyDraw,
+            r#"
+            SELECT id, data AS "data!"
+            FROM event
+            WHERE kind = 'draw'
+            AND   binary_data IS NULL
+            AND   data IS NOT NULL
+            AND   NOT content_encrypted
+            AND   ($1::UUID IS NULL OR id > $1)
+            ORDER BY id
+            LIMIT $2
+            "#,
+            self.after_id,
+            self.limit,
+        )
+        .fetch_all(conn)
+        .await
+    }
+}
+
+/// Replaces JSON `data` of legacy `draw` events with their `binary_data`.
+///
+/// Events converted meanwhile by another run are left intact.
+#[derive(Debug, Default)]
+pub struct ReencodeQuery {
+    ids: Vec<Uuid>,
+    binary_data: Vec<Vec<u8>>,
+}
+
+impl ReencodeQuery {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn push(&mut self, id: Uuid, binary_data: Vec<u8>) {
+        self.ids.push(id);
+        self.binary_data.push(binary_data);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<u64> {
+        sqlx::query!(
+            r#"
+            UPDATE event
+            SET binary_data = u.binary_data,
+                data = NULL
+            FROM UNNEST($1::UUID[], $2::BYTEA[]) AS u(id, binary_data)
+            WHERE event.id = u.id
+            AND   event.binary_data IS NULL
+            "#,
+            &self.ids,
+            &self.binary_data,
+        )
+        .execute(conn)
+        .await
+        .map(|r| r.rows_affected())
+    }
+}
+
 mod binary_encoding;
+pub mod crdt;
+mod data_encryption;
+mod kind;
 mod schema;
 mod set_state;
+mod transcript;
+mod verification;
 
 pub use self::binary_encoding::PostcardBin;
-pub use schema::CompactEvent;
+pub use data_encryption::DataEncryption;
+pub use kind::EventKind;
+pub use schema::{CompactEvent, Error as SchemaError};
 pub use set_state::Query as SetStateQuery;
+pub use transcript::{Entry as TranscriptEntry, Query as TranscriptQuery};
+pub use verification::{Checksum, ChecksumQuery, Diff, DiffQuery};
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::test_helpers::prelude::*;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn list_query_filter_combinations() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let mut conn = db.get_conn().await;
+        let room = shared_helpers::insert_room(&mut conn).await;
+
+        for (set, label, occurred_at) in [
+            ("messages", "msg_1", 1000),
+            ("messages", "msg%2", 2000),
+            ("reactions", "msg_3", 3000),
+            ("drafts", "msg_4", 4000),
+        ] {
+            factory::Event::new()
+                .room_id(room.id())
+                .kind("message")
+                .set(set)
+                .label(label)
+                .data(&json!({ "text": label }))
+                .occurred_at(occurred_at)
+                .created_by(agent.agent_id())
+                .insert(&mut conn)
+                .await;
+        }
+
+        let labels = |events: Vec<Object>| {
+            events
+                .iter()
+                .map(|event| event.label().unwrap_or_default().to_owned())
+                .collect::<Vec<_>>()
+        };
+
+        let events = ListQuery::new()
+            .room_id(room.id())
+            .sets(vec!["messages".to_owned(), "reactions".to_owned()])
+            .execute(&mut conn)
+            .await
+            .expect("Failed to list events");
+
+        assert_eq!(labels(events), ["msg_1", "msg%2", "msg_3"]);
+
+        // Wildcards in the prefix are matched literally.
+        let events = ListQuery::new()
+            .room_id(room.id())
+            .label_prefix("msg%")
+            .execute(&mut conn)
+            .await
+            .expect("Failed to list events");
+
+        assert_eq!(labels(events), ["msg%2"]);
+
+        let events = ListQuery::new()
+            .room_id(room.id())
+            .sets(vec!["messages".to_owned(), "drafts".to_owned()])
+            .label_prefix("msg_")
+            .occurred_at_from(1000)
+            .occurred_at_to(4000)
+            .direction(Direction::Backward)
+            .without_data()
+            .execute(&mut conn)
+            .await
+            .expect("Failed to list events");
+
+        assert_eq!(labels(events.clone()), ["msg_1"]);
+        assert_eq!(events[0].data(), &JsonValue::Null);
+    }
+
+    #[tokio::test]
+    async fn insert_query_inherits_original_event() {
+        let db = TestDb::new().await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let late_agent = TestAgent::new("web", "user456", USR_AUDIENCE);
+
+        let mut conn = db.get_conn().await;
+        let room = shared_helpers::insert_room(&mut conn).await;
+
+        let insert = |agent: &TestAgent, occurred_at: i64| {
+            InsertQuery::new(
+                room.id(),
+                "message".to_owned(),
+                json!({ "text": occurred_at }),
+                occurred_at,
+                agent.agent_id().to_owned(),
+                None,
+            )
+            .expect("Failed to build insert query")
+            .set("messages".to_owned())
+            .label("msg".to_owned())
+        };
+
+        let first = insert(&agent, 2000)
+            .execute(&mut conn)
+            .await
+            .expect("Failed to insert event");
+
+        assert_eq!(first.original_occurred_at(), 2000);
+
+        let edit = insert(&agent, 3000)
+            .execute(&mut conn)
+            .await
+            .expect("Failed to insert event");
+
+        assert_eq!(edit.original_occurred_at(), 2000);
+
+        // Delivered late but happened before the others, the original stays the same.
+        let late = insert(&late_agent, 1000)
+            .execute(&mut conn)
+            .await
+            .expect("Failed to insert event");
+
+        assert_eq!(late.original_occurred_at(), 2000);
+        assert_eq!(late.original_created_by(), agent.agent_id());
+
+        let events = ListQuery::new()
+            .room_id(room.id())
+            .execute(&mut conn)
+            .await
+            .expect("Failed to list events");
+
+        assert_eq!(events.len(), 3);
+
+        for event in events {
+            assert_eq!(event.original_occurred_at(), 2000);
+            assert_eq!(event.original_created_by(), agent.agent_id());
+        }
+    }
+}
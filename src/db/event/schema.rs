@@ -1,6 +1,9 @@
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+/// Max absolute value of a coordinate or a size of a shape.
+const MAX_COORDINATE: f32 = 1_000_000.0;
+
 #[derive(Debug)]
 pub enum Event {
     Path(EventSchema),
@@ -8,6 +11,12 @@ pub enum Event {
 }
 
 impl Event {
+    fn validate(&self) -> Result<(), Error> {
+        match self {
+            Event::Path(evt) | Event::Other(evt) => evt.validate(),
+        }
+    }
+
     pub fn compact(self) -> Result<CompactEvent, Error> {
         let evt = match self {
             Event::Path(evt) => CompactEvent::Path(CompactPathEvent::try_from_event(evt)?),
@@ -26,8 +35,16 @@ pub enum CompactEvent {
 }
 
 impl CompactEvent {
-    pub fn from_json(v: serde_json::Value) -> Result<Self, anyhow::Error> {
-        let evt: Event = serde_json::from_value(v)?;
+    pub fn from_json(v: serde_json::Value) -> Result<Self, Error> {
+        // Check the shape type first to report it instead of a generic deserialization error.
+        if let Some(kind) = v.get("type") {
+            if serde_json::from_value::<Kind>(kind.to_owned()).is_err() {
+                return Err(Error::UnknownShape(kind.to_string()));
+            }
+        }
+
+        let evt: Event = serde_json::from_value(v).map_err(Error::InvalidData)?;
+        evt.validate()?;
         let compacted = evt.compact()?;
 
         Ok(compacted)
@@ -180,16 +197,33 @@ impl CompactEvent {
 pub enum Error {
     LosingPrecision,
     MissingPath,
+    InvalidData(serde_json::Error),
+    UnknownShape(String),
+    OutOfBounds(&'static str),
+}
+
+impl Error {
+    /// Short reason for metrics.
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Error::LosingPrecision => "losing_precision",
+            Error::MissingPath => "missing_path",
+            Error::InvalidData(_) => "invalid_data",
+            Error::UnknownShape(_) => "unknown_shape",
+            Error::OutOfBounds(_) => "out_of_bounds",
+        }
+    }
 }
 
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let disp = match self {
-            Error::LosingPrecision => "compaction would loose precision",
-            Error::MissingPath => "missing path for path event",
-        };
-
-        write!(f, "{disp:?}")
+        match self {
+            Error::LosingPrecision => write!(f, "compaction would loose precision"),
+            Error::MissingPath => write!(f, "missing path for path event"),
+            Error::InvalidData(err) => write!(f, "invalid draw event: {err}"),
+            Error::UnknownShape(kind) => write!(f, "unknown shape type {kind}"),
+            Error::OutOfBounds(field) => write!(f, "'{field}' is out of bounds"),
+        }
     }
 }
 
@@ -479,6 +513,46 @@ pub struct EventSchema {
 }
 
 impl EventSchema {
+    /// Checks coordinates and sizes to fit the binary format.
+    fn validate(&self) -> Result<(), Error> {
+        let coordinates = [
+            ("top", Some(self.top)),
+            ("left", Some(self.left)),
+            ("x1", self.x1),
+            ("x2", self.x2),
+            ("y1", self.y1),
+            ("y2", self.y2),
+        ];
+
+        for (field, value) in coordinates {
+            if matches!(value, Some(v) if !v.is_finite() || v.abs() > MAX_COORDINATE) {
+                return Err(Error::OutOfBounds(field));
+            }
+        }
+
+        for (field, value) in [("width", self.width), ("height", self.height)] {
+            if !value.is_finite() || !(0.0..=MAX_COORDINATE).contains(&value) {
+                return Err(Error::OutOfBounds(field));
+            }
+        }
+
+        let points = self
+            .path
+            .iter()
+            .flatten()
+            .filter_map(|part| part.as_array())
+            .flatten()
+            .filter_map(|v| v.as_f64());
+
+        for point in points {
+            if !point.is_finite() || point.abs() > MAX_COORDINATE as f64 {
+                return Err(Error::OutOfBounds("path"));
+            }
+        }
+
+        Ok(())
+    }
+
     // Intended for event generation. Generates incorrect
     // event with sane default values.
     #[cfg(test)]
@@ -1480,6 +1554,32 @@ mod tests {
         println!("{evt:#?}");
     }
 
+    #[test]
+    fn test_validation_errors() {
+        let mut evt = CompactEvent::test_rect_event().into_json().unwrap();
+        evt["type"] = serde_json::json!("hexagon");
+        let err = CompactEvent::from_json(evt).unwrap_err();
+        assert_eq!(err.reason(), "unknown_shape");
+
+        let mut evt = CompactEvent::test_rect_event().into_json().unwrap();
+        evt["left"] = serde_json::json!(1e12);
+        let err = CompactEvent::from_json(evt).unwrap_err();
+        assert!(matches!(err, Error::OutOfBounds("left")));
+
+        let mut evt = CompactEvent::test_rect_event().into_json().unwrap();
+        evt["width"] = serde_json::json!(-1);
+        let err = CompactEvent::from_json(evt).unwrap_err();
+        assert!(matches!(err, Error::OutOfBounds("width")));
+
+        let mut evt = CompactEvent::test_path_event().into_json().unwrap();
+        evt["path"] = serde_json::json!([["M", 0, 0], ["L", 5e7, 0]]);
+        let err = CompactEvent::from_json(evt).unwrap_err();
+        assert!(matches!(err, Error::OutOfBounds("path")));
+
+        let err = CompactEvent::from_json(serde_json::json!({ "type": "rect" })).unwrap_err();
+        assert_eq!(err.reason(), "invalid_data");
+    }
+
     #[test]
     fn test_losing_precision_error() {
         let evt = r#"{"type":"path","version":"4.6.0","originX":"left","originY":"top","left":541.18,"top":61.47,"width":97.2,"height":97.2,"fill":"rgba(255,255,255,1)","stroke":"rgba(255,255,255,1)","strokeWidth":2,"strokeDashArray":null,"strokeLineCap":"butt","strokeDashOffset":0,"strokeLineJoin":"miter","strokeUniform":true,"strokeMiterLimit":40,"scaleX":0.07,"scaleY":0.11,"angle":0,"flipX":false,"flipY":false,"opacity":1,"shadow":null,"visible":true,"backgroundColor":"","fillRule":"nonzero","paintFirst":"fill","globalCompositeOperation":"source-over","skewX":0,"skewY":0,"_id":"a52b6755-2c6e-452b-a1b8-2cc108da7f34","noScaleCache":false,"_order":171,"_noHistory":true,"_drawByStretch":true,"path":[["M",0,0],["L",97.2,0],["L",0,97.2],["z"]]}"#;
@@ -1,10 +1,10 @@
-use sqlx::postgres::PgConnection;
-use svc_agent::AgentId;
+use sqlx::postgres::{PgConnection, Postgres};
+use sqlx::QueryBuilder;
 use uuid::Uuid;
 
 use crate::db::event::RawObject;
 
-use super::{CompactEvent, Object, PostcardBin};
+use super::Object;
 
 #[derive(Clone)]
 pub struct Query<'a> {
@@ -14,6 +14,7 @@ pub struct Query<'a> {
     occurred_at: Option<i64>,
     original_occurred_at: i64,
     limit: i64,
+    without_data: bool,
 }
 
 impl<'a> Query<'a> {
@@ -25,6 +26,7 @@ impl<'a> Query<'a> {
             occurred_at: None,
             original_occurred_at,
             limit,
+            without_data: false,
         }
     }
 
@@ -42,165 +44,137 @@ impl<'a> Query<'a> {
         }
     }
 
+    /// Don't fetch `data` and `binary_data` columns. Events are returned with null `data`.
+    pub fn without_data(self) -> Self {
+        Self {
+            without_data: true,
+            ..self
+        }
+    }
+
     pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Vec<Object>> {
-        let raw_objects = if let Some(attribute) = self.attribute {
-            sqlx::query_as!(
-                RawObject,
-                r#"
-                SELECT
-                    id,
-                    room_id,
-                    kind,
-                    set,
-                    label,
-                    attribute,
-                    data,
-                    binary_data as "binary_data: PostcardBin<CompactEvent>",
-                    occurred_at,
-                    created_by as "created_by!: AgentId",
-                    created_at,
-                    deleted_at,
-                    original_occurred_at,
-                    original_created_by as "original_created_by: AgentId",
-                    removed
-                FROM (
-                    SELECT DISTINCT ON(original_occurred_at, label)
-                        *,
-                        ROW_NUMBER() OVER (
-                            PARTITION BY room_id, set, label
-                            ORDER BY occurred_at DESC
-                        ) AS reverse_ordinal
-                    FROM event
-                    WHERE deleted_at IS NULL
-                    AND   room_id = $1
-                    AND   set = $2
-                    AND   original_occurred_at < $4
-                    AND   occurred_at < COALESCE($5, 9223372036854775807)
-                    ORDER BY original_occurred_at DESC, label ASC, occurred_at DESC
-                ) AS q
-                WHERE reverse_ordinal = 1
-                AND   attribute = $3
-                AND   removed = 'f'
-                LIMIT $6
-                "#,
-                self.room_id,
-                self.set,
-                attribute,
-                self.original_occurred_at,
-                self.occurred_at,
-                self.limit,
-            )
-            .fetch_all(conn)
-            .await?
-        } else {
-            sqlx::query_as!(
-                RawObject,
-                r#"
-                SELECT
-                    id,
-                    room_id,
-                    kind,
-                    set,
-                    label,
-                    attribute,
-                    data,
-                    binary_data as "binary_data: PostcardBin<CompactEvent>",
-                    occurred_at,
-                    created_by as "created_by!: AgentId",
-                    created_at,
-                    deleted_at,
-                    original_occurred_at,
-                    original_created_by as "original_created_by: AgentId",
-                    removed
-                FROM (
-                    SELECT DISTINCT ON(original_occurred_at, label) *
-                    FROM event
-                    WHERE deleted_at IS NULL
-                    AND   room_id = $1
-                    AND   set = $2
-                    AND   original_occurred_at < $3
-                    AND   occurred_at < COALESCE($4, 9223372036854775807)
-                    ORDER BY original_occurred_at DESC, label ASC, occurred_at DESC
-                ) AS subq
-                WHERE removed = 'f'
-                LIMIT $5
-                "#,
-                self.room_id,
-                self.set,
-                self.original_occurred_at,
-                self.occurred_at,
-                self.limit,
-            )
+        let raw_objects = self
+            .build()
+            .build_query_as::<RawObject>()
             .fetch_all(conn)
-            .await?
-        };
+            .await?;
 
         let mut objects = Vec::with_capacity(raw_objects.len());
 
         for raw in raw_objects {
-            objects.push(Object::try_from(raw)?);
+            objects.push(raw.try_into_object(self.without_data)?);
         }
 
         Ok(objects)
     }
 
     pub async fn total_count(&self, conn: &mut PgConnection) -> sqlx::Result<i64> {
+        let mut query = QueryBuilder::new(
+            r#"
+            SELECT COUNT(1) FROM (
+                SELECT DISTINCT ON(original_occurred_at, label)
+                    *,
+                    bool_or(removed) OVER (
+                        PARTITION BY room_id, set, label
+                        ORDER BY occurred_at DESC
+                    ) AS removed_windowed
+            "#,
+        );
+
+        self.push_filters(&mut query);
+        query.push(" ) AS subq WHERE removed_windowed = 'f'");
+
         if let Some(attribute) = self.attribute {
-            sqlx::query!(
-                "SELECT COUNT(1) as total FROM (
-                    SELECT DISTINCT ON(original_occurred_at, label)
-                        *,
-                        bool_or(removed) OVER (
-                            PARTITION BY room_id, set, label
-                            ORDER BY occurred_at DESC
-                        ) AS removed_windowed
-                    FROM event
-                    WHERE deleted_at IS NULL
-                    AND   room_id = $1
-                    AND   set = $2
-                    AND   original_occurred_at < $3
-                    AND   occurred_at < COALESCE($4, 9223372036854775807)
-                    ORDER BY original_occurred_at DESC, label ASC, occurred_at DESC
-                ) subq
-                WHERE removed_windowed = 'f' AND attribute = $5::TEXT
-                ",
-                self.room_id,
-                self.set,
-                self.original_occurred_at,
-                self.occurred_at,
-                attribute,
-            )
+            query
+                .push(" AND ")
+                .push_bind(attribute)
+                .push(" = ANY(attributes)");
+        }
+
+        query
+            .build_query_as::<(i64,)>()
             .fetch_one(conn)
             .await
-            .map(|r| r.total.unwrap_or(0))
+            .map(|(total,)| total)
+    }
+
+    fn build(&self) -> QueryBuilder<'a, Postgres> {
+        let mut query = QueryBuilder::new(
+            r#"
+            SELECT
+                id,
+                room_id,
+                kind,
+                set,
+                label,
+                attributes,
+                occurred_at,
+                created_by,
+                created_at,
+                deleted_at,
+                original_occurred_at,
+                original_created_by,
+                removed,
+                moderation_status,
+                content_encrypted,
+                key_id,
+                seq,
+            "#,
+        );
+
+        if self.without_data {
+            query.push("NULL::jsonb AS data, NULL::bytea AS binary_data");
         } else {
-            sqlx::query!(
-                "SELECT COUNT(1) as total FROM (
-                    SELECT DISTINCT ON(original_occurred_at, label)
-                        *,
-                        bool_or(removed) OVER (
-                            PARTITION BY room_id, set, label
-                            ORDER BY occurred_at DESC
-                        ) AS removed_windowed
-                    FROM event
-                    WHERE deleted_at IS NULL
-                    AND   room_id = $1
-                    AND   set = $2
-                    AND   original_occurred_at < $3
-                    AND   occurred_at < COALESCE($4, 9223372036854775807)
-                    ORDER BY original_occurred_at DESC, label ASC, occurred_at DESC
-                ) subq
-                WHERE removed_windowed = 'f'
-                ",
-                self.room_id,
-                self.set,
-                self.original_occurred_at,
-                self.occurred_at,
-            )
-            .fetch_one(conn)
-            .await
-            .map(|r| r.total.unwrap_or(0))
+            query.push("data, binary_data");
+        }
+
+        query.push(" FROM ( SELECT DISTINCT ON(original_occurred_at, label) *");
+
+        // With the attribute filter the element is matched by its latest event
+        // regardless of the pagination by `original_occurred_at`.
+        if self.attribute.is_some() {
+            query.push(
+                r#",
+                ROW_NUMBER() OVER (
+                    PARTITION BY room_id, set, label
+                    ORDER BY occurred_at DESC
+                ) AS reverse_ordinal
+                "#,
+            );
+        }
+
+        self.push_filters(&mut query);
+        query.push(" ) AS q WHERE removed = 'f'");
+
+        if let Some(attribute) = self.attribute {
+            query
+                .push(" AND reverse_ordinal = 1 AND ")
+                .push_bind(attribute)
+                .push(" = ANY(attributes)");
         }
+
+        query.push(" LIMIT ").push_bind(self.limit);
+        query
+    }
+
+    /// Pushes `FROM` and conditions of the inner query selecting the latest event of each element.
+    fn push_filters(&self, query: &mut QueryBuilder<'a, Postgres>) {
+        query
+            .push(" FROM event WHERE deleted_at IS NULL AND room_id = ")
+            .push_bind(self.room_id)
+            .push(" AND set = ")
+            .push_bind(self.set.clone())
+            .push(" AND original_occurred_at < ")
+            .push_bind(self.original_occurred_at);
+
+        if let Some(occurred_at) = self.occurred_at {
+            query.push(" AND occurred_at < ").push_bind(occurred_at);
+        }
+
+        query.push(
+            " AND moderation_status = 'approved' \
+            ORDER BY original_occurred_at DESC, label ASC, occurred_at DESC",
+        );
     }
 }
 
@@ -0,0 +1,188 @@
+use chrono::{serde::ts_milliseconds, DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use sqlx::postgres::PgConnection;
+use svc_agent::{AccountId, AgentId, Authenticable};
+use uuid::Uuid;
+
+use super::DataEncryption;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// A chat message as it's shown in the room transcript: the latest version of a `message`
+/// event placed where its first version has been posted.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Entry {
+    id: Uuid,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+    data: JsonValue,
+    author: Author,
+    /// Nanoseconds since the room opening when the message has been posted.
+    occurred_at: i64,
+    /// `HH:MM:SS` since the room opening when the message has been posted.
+    offset: String,
+    /// Whether the message has been edited after posting.
+    edited: bool,
+    #[serde(with = "ts_milliseconds")]
+    posted_at: DateTime<Utc>,
+}
+
+impl Entry {
+    pub fn occurred_at(&self) -> i64 {
+        self.occurred_at
+    }
+
+    #[cfg(test)]
+    pub fn data(&self) -> &JsonValue {
+        &self.data
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Author {
+    agent_id: AgentId,
+    account_id: AccountId,
+    /// The account label to show when the tenant has no profile for the account.
+    account_label: String,
+}
+
+struct Row {
+    id: Uuid,
+    label: Option<String>,
+    data: JsonValue,
+    key_id: Option<String>,
+    created_by: AgentId,
+    original_occurred_at: i64,
+    occurred_at: i64,
+    posted_at: DateTime<Utc>,
+}
+
+impl From<Row> for Entry {
+    fn from(row: Row) -> Self {
+        let account_id = row.created_by.as_account_id().to_owned();
+        let seconds = row.original_occurred_at.max(0) / 1_000_000_000;
+
+        Self {
+            id: row.id,
+            label: row.label,
+            data: row.data,
+            author: Author {
+                account_label: account_id.label().to_owned(),
+                account_id,
+                agent_id: row.created_by,
+            },
+            occurred_at: row.original_occurred_at,
+            offset: format!(
+                "{:02}:{:02}:{:02}",
+                seconds / 3600,
+                seconds / 60 % 60,
+                seconds % 60
+            ),
+            edited: row.occurred_at > row.original_occurred_at,
+            posted_at: row.posted_at,
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Lists messages of the room for its transcript in the order they have been posted.
+///
+/// Removed, deleted, rejected by moderation and encrypted messages are skipped.
+pub struct Query<'a> {
+    room_id: Uuid,
+    after: Option<i64>,
+    limit: i64,
+    encryption: Option<&'a DataEncryption>,
+}
+
+impl<'a> Query<'a> {
+    pub fn new(room_id: Uuid, limit: i64) -> Self {
+        Self {
+            room_id,
+            after: None,
+            limit,
+            encryption: None,
+        }
+    }
+
+    /// Includes messages encrypted by the service at rest decrypting them if they may be dumped.
+    pub fn decrypt(self, encryption: &'a DataEncryption) -> Self {
+        Self {
+            encryption: Some(encryption),
+            ..self
+        }
+    }
+
+    /// Messages posted after `occurred_at` of the last entry of the previous page.
+    pub fn after(self, occurred_at: i64) -> Self {
+        Self {
+            after: Some(occurred_at),
+            ..self
+        }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Vec<Entry>> {
+        let key_ids = self
+            .encryption
+            .filter(|encryption| {
+                encryption.is_encrypted_kind("message") && encryption.is_dumpable("message")
+            })
+            .map(|encryption| encryption.key_ids())
+            .unwrap_or_default();
+
+        let rows = sqlx::query_as!(
+            Row,
+            r#"
+            SELECT
+                id,
+                label,
+                data AS "data!: JsonValue",
+                key_id,
+                created_by AS "created_by!: AgentId",
+                original_occurred_at,
+                occurred_at,
+                posted_at AS "posted_at!"
+            FROM (
+                SELECT DISTINCT ON (set, original_occurred_at, COALESCE(label, id::text))
+                    *,
+                    MIN(created_at) OVER (
+                        PARTITION BY set, original_occurred_at, COALESCE(label, id::text)
+                    ) AS posted_at
+                FROM event
+                WHERE deleted_at IS NULL
+                AND   room_id = $1
+                AND   kind = 'message'
+                AND   moderation_status = 'approved'
+                AND   original_occurred_at > COALESCE($2::BIGINT, -1)
+                ORDER BY set, original_occurred_at, COALESCE(label, id::text), occurred_at DESC
+            ) AS m
+            WHERE removed = 'f'
+            AND   (content_encrypted = 'f' OR key_id = ANY($4))
+            AND   data IS NOT NULL
+            ORDER BY original_occurred_at, id
+            LIMIT $3
+            "#,
+            self.room_id,
+            self.after,
+            self.limit,
+            &key_ids,
+        )
+        .fetch_all(conn)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| match (self.encryption, row.key_id.as_deref()) {
+                (Some(encryption), Some(key_id)) => {
+                    let data = encryption
+                        .open_data("message", key_id, &row.data)
+                        .map_err(|err| sqlx::Error::Decode(err.into()))?;
+
+                    Ok(Entry::from(Row { data, ..row }))
+                }
+                _ => Ok(Entry::from(row)),
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,206 @@
+use serde_derive::{Deserialize, Serialize};
+use sqlx::postgres::PgConnection;
+use uuid::Uuid;
+
+/// Deterministic checksum over a room's ordered events.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Checksum {
+    pub events_count: i64,
+    /// Hex encoded SHA-256 of `id:occurred_at:data_hash` of each event ordered by `occurred_at`.
+    pub checksum: String,
+}
+
+pub struct ChecksumQuery {
+    room_id: Uuid,
+}
+
+impl ChecksumQuery {
+    pub fn new(room_id: Uuid) -> Self {
+        Self { room_id }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Checksum> {
+        sqlx::query_as!(
+            Checksum,
+            r#"
+            WITH hashed AS (
+                SELECT
+                    id,
+                    occurred_at,
+                    created_at,
+                    encode(
+                        sha256(
+                            convert_to(COALESCE(data::text, ''), 'UTF8')
+                            || COALESCE(binary_data, ''::bytea)
+                        ),
+                        'hex'
+                    ) AS data_hash
+                FROM event
+                WHERE room_id = $1
+                AND   deleted_at IS NULL
+                AND   moderation_status = 'approved'
+            )
+            SELECT
+                COUNT(*) AS "events_count!",
+                encode(
+                    sha256(convert_to(COALESCE(
+                        string_agg(
+                            id::text || ':' || occurred_at || ':' || data_hash,
+                            ','
+                            ORDER BY occurred_at, created_at, id
+                        ),
+                        ''
+                    ), 'UTF8')),
+                    'hex'
+                ) AS "checksum!"
+            FROM hashed
+            "#,
+            self.room_id,
+        )
+        .fetch_one(conn)
+        .await
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Discrepancy {
+    pub source_event_id: Option<Uuid>,
+    pub event_id: Option<Uuid>,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub set: String,
+    pub label: Option<String>,
+}
+
+/// Discrepancies of a kind up to the query limit and their total number.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct DiffGroup {
+    pub total: i64,
+    pub events: Vec<Discrepancy>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Diff {
+    /// Source events without a counterpart in the derived room.
+    pub missing: DiffGroup,
+    /// Events with a counterpart having different data.
+    pub mutated: DiffGroup,
+    /// Derived room's events without a counterpart in the source room.
+    pub extra: DiffGroup,
+}
+
+/// Compares events of a derived room against its source room.
+///
+/// Cloning keeps neither event ids nor `occurred_at` so events are matched by
+/// their type, set, label, author and creation time which are preserved.
+pub struct DiffQuery {
+    source_room_id: Uuid,
+    room_id: Uuid,
+    limit: i64,
+}
+
+impl DiffQuery {
+    pub fn new(source_room_id: Uuid, room_id: Uuid, limit: i64) -> Self {
+        Self {
+            source_room_id,
+            room_id,
+            limit,
+        }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Diff> {
+        let rows = sqlx::query!(
+            r#"
+            WITH hashed AS (
+                SELECT
+                    id,
+                    room_id,
+                    kind,
+                    set,
+                    COALESCE(label, '') AS label_key,
+                    label,
+                    created_by::text AS creator,
+                    created_at,
+                    sha256(
+                        convert_to(COALESCE(data::text, ''), 'UTF8')
+                        || COALESCE(binary_data, ''::bytea)
+                    ) AS data_hash
+                FROM event
+                WHERE room_id IN ($1, $2)
+                AND   deleted_at IS NULL
+                AND   moderation_status = 'approved'
+            ),
+            diff AS (
+                SELECT
+                    CASE
+                        WHEN d.id IS NULL THEN 'missing'
+                        WHEN s.id IS NULL THEN 'extra'
+                        ELSE 'mutated'
+                    END AS status,
+                    s.id AS source_event_id,
+                    d.id AS event_id,
+                    COALESCE(s.kind, d.kind) AS kind,
+                    COALESCE(s.set, d.set) AS set,
+                    COALESCE(s.label, d.label) AS label,
+                    COALESCE(s.created_at, d.created_at) AS created_at
+                FROM (SELECT * FROM hashed WHERE room_id = $1) AS s
+                FULL OUTER JOIN (SELECT * FROM hashed WHERE room_id = $2) AS d
+                ON  s.kind = d.kind
+                AND s.set = d.set
+                AND s.label_key = d.label_key
+                AND s.creator = d.creator
+                AND s.created_at = d.created_at
+                WHERE s.id IS NULL
+                OR    d.id IS NULL
+                OR    s.data_hash <> d.data_hash
+            )
+            SELECT
+                status AS "status!",
+                source_event_id AS "source_event_id?",
+                event_id AS "event_id?",
+                kind AS "kind!",
+                set AS "set!",
+                label,
+                total AS "total!"
+            FROM (
+                SELECT
+                    *,
+                    ROW_NUMBER() OVER (PARTITION BY status ORDER BY created_at) AS ordinal,
+                    COUNT(*) OVER (PARTITION BY status) AS total
+                FROM diff
+            ) AS q
+            WHERE ordinal <= $3
+            ORDER BY status, created_at
+            "#,
+            self.source_room_id,
+            self.room_id,
+            self.limit,
+        )
+        .fetch_all(conn)
+        .await?;
+
+        let mut diff = Diff::default();
+
+        for row in rows {
+            let group = match row.status.as_str() {
+                "missing" => &mut diff.missing,
+                "mutated" => &mut diff.mutated,
+                _ => &mut diff.extra,
+            };
+
+            group.total = row.total;
+
+            group.events.push(Discrepancy {
+                source_event_id: row.source_event_id,
+                event_id: row.event_id,
+                kind: row.kind,
+                set: row.set,
+                label: row.label,
+            });
+        }
+
+        Ok(diff)
+    }
+}
@@ -0,0 +1,171 @@
+use chrono::{serde::ts_milliseconds, DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+use sqlx::postgres::PgConnection;
+use svc_agent::AgentId;
+use uuid::Uuid;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// An agent with a raised hand.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Object {
+    agent_id: AgentId,
+    #[serde(with = "ts_milliseconds")]
+    raised_at: DateTime<Utc>,
+}
+
+impl Object {
+    pub fn agent_id(&self) -> &AgentId {
+        &self.agent_id
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Agents with raised hands in the room, the earliest first.
+#[derive(Debug)]
+pub struct ListQuery {
+    room_id: Uuid,
+}
+
+impl ListQuery {
+    pub fn new(room_id: Uuid) -> Self {
+        Self { room_id }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Vec<Object>> {
+        sqlx::query_as!(
+            Object,
+            r#"
+            SELECT
+                agent_id AS "agent_id!: AgentId",
+                raised_at
+            FROM hand_queue
+            WHERE room_id = $1
+            ORDER BY raised_at, agent_id
+            "#,
+            self.room_id,
+        )
+        .fetch_all(conn)
+        .await
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+pub struct RaiseResult {
+    /// Whether the agent is ready in the room. Others can't raise their hands.
+    pub entered: bool,
+    /// Whether the hand has been raised by the query. It's kept in place if already raised.
+    pub raised: bool,
+}
+
+#[derive(Debug)]
+pub struct RaiseQuery {
+    room_id: Uuid,
+    agent_id: AgentId,
+}
+
+impl RaiseQuery {
+    pub fn new(room_id: Uuid, agent_id: AgentId) -> Self {
+        Self { room_id, agent_id }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<RaiseResult> {
+        sqlx::query_as!(
+            RaiseResult,
+            r#"
+            WITH a AS (
+                SELECT room_id, agent_id
+                FROM agent
+                WHERE room_id = $1
+                AND   agent_id = $2
+                AND   status = 'ready'
+            ),
+            h AS (
+                INSERT INTO hand_queue (room_id, agent_id)
+                SELECT room_id, agent_id
+                FROM a
+                ON CONFLICT DO NOTHING
+                RETURNING room_id
+            )
+            SELECT
+                EXISTS (SELECT 1 FROM a) AS "entered!",
+                EXISTS (SELECT 1 FROM h) AS "raised!"
+            "#,
+            self.room_id,
+            self.agent_id as AgentId,
+        )
+        .fetch_one(conn)
+        .await
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug)]
+pub struct LowerQuery {
+    room_id: Uuid,
+    agent_id: AgentId,
+}
+
+impl LowerQuery {
+    pub fn new(room_id: Uuid, agent_id: AgentId) -> Self {
+        Self { room_id, agent_id }
+    }
+
+    /// Returns whether the hand has been lowered by the query.
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<bool> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM hand_queue
+            WHERE room_id = $1
+            AND   agent_id = $2
+            "#,
+            self.room_id,
+            self.agent_id as AgentId,
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Takes the earliest raised hand out of the queue. Concurrent pops take different hands.
+#[derive(Debug)]
+pub struct PopQuery {
+    room_id: Uuid,
+}
+
+impl PopQuery {
+    pub fn new(room_id: Uuid) -> Self {
+        Self { room_id }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Option<Object>> {
+        sqlx::query_as!(
+            Object,
+            r#"
+            DELETE FROM hand_queue
+            WHERE room_id = $1
+            AND   agent_id = (
+                SELECT agent_id
+                FROM hand_queue
+                WHERE room_id = $1
+                ORDER BY raised_at, agent_id
+                LIMIT 1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING
+                agent_id AS "agent_id!: AgentId",
+                raised_at
+            "#,
+            self.room_id,
+        )
+        .fetch_optional(conn)
+        .await
+    }
+}
@@ -19,11 +19,39 @@ pub async fn create_pool(
         .expect("Failed to create sqlx database pool")
 }
 
+/// Creates another pool to the same database as `db` with its own size.
+///
+/// Timeouts are left default since it's meant for background operations
+/// which may wait longer than requests.
+pub async fn create_sibling_pool(db: &PgPool, size: u32) -> PgPool {
+    PgPoolOptions::new()
+        .max_connections(size)
+        .min_connections(1)
+        .connect_with(db.connect_options().clone())
+        .await
+        .expect("Failed to create sqlx database pool")
+}
+
 pub mod adjustment;
+pub mod advisory_lock;
 pub mod agent;
+pub mod attachment;
 pub mod change;
 pub mod edition;
+pub mod edition_commit_job;
 pub mod event;
+pub mod hand_queue;
+pub mod nats_dead_letter;
+pub mod poll;
+pub mod question;
+pub mod retention_rule;
 pub mod room;
+pub mod room_activity;
 pub mod room_ban;
+pub mod room_lock_schedule;
+pub mod room_queue;
 pub mod room_time;
+pub mod set_head;
+pub mod task;
+pub mod tenant_ban;
+pub mod whiteboard_page;
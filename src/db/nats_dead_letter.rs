@@ -0,0 +1,224 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::postgres::PgConnection;
+use svc_agent::AgentId;
+use uuid::Uuid;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// A NATS event which failed to be handled and was terminated in the stream.
+#[derive(Debug, sqlx::FromRow, Serialize)]
+pub struct Object {
+    id: Uuid,
+    subject: String,
+    classroom_id: Uuid,
+    entity_type: String,
+    entity_event_id: i64,
+    label: String,
+    created_by: AgentId,
+    event_created_at: DateTime<Utc>,
+    error: String,
+    attempts: i32,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl Object {
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+
+    pub fn subject(&self) -> &str {
+        &self.subject
+    }
+
+    pub fn classroom_id(&self) -> Uuid {
+        self.classroom_id
+    }
+
+    pub fn entity_type(&self) -> &str {
+        &self.entity_type
+    }
+
+    pub fn entity_event_id(&self) -> i64 {
+        self.entity_event_id
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    pub fn created_by(&self) -> &AgentId {
+        &self.created_by
+    }
+
+    pub fn event_created_at(&self) -> DateTime<Utc> {
+        self.event_created_at
+    }
+
+    #[cfg(test)]
+    pub fn attempts(&self) -> i32 {
+        self.attempts
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Inserts a dead letter or bumps attempts of the existing one for the same event.
+#[derive(Debug)]
+pub struct InsertQuery {
+    subject: String,
+    classroom_id: Uuid,
+    entity_type: String,
+    entity_event_id: i64,
+    label: String,
+    created_by: AgentId,
+    event_created_at: DateTime<Utc>,
+    error: String,
+}
+
+impl InsertQuery {
+    pub fn new(
+        subject: String,
+        classroom_id: Uuid,
+        entity_type: String,
+        entity_event_id: i64,
+        label: String,
+        created_by: AgentId,
+        event_created_at: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            subject,
+            classroom_id,
+            entity_type,
+            entity_event_id,
+            label,
+            created_by,
+            event_created_at,
+            error: String::new(),
+        }
+    }
+
+    pub fn error(self, error: String) -> Self {
+        Self { error, ..self }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Object> {
+        sqlx::query_as!(
+            Object,
+            r#"
+            INSERT INTO nats_dead_letter (
+                subject, classroom_id, entity_type, entity_event_id,
+                label, created_by, event_created_at, error
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            ON CONFLICT (entity_type, entity_event_id) DO UPDATE
+            SET error = EXCLUDED.error,
+                attempts = nats_dead_letter.attempts + 1,
+                updated_at = NOW()
+            RETURNING
+                id,
+                subject,
+                classroom_id,
+                entity_type,
+                entity_event_id,
+                label,
+                created_by AS "created_by!: AgentId",
+                event_created_at,
+                error,
+                attempts,
+                created_at,
+                updated_at
+            "#,
+            self.subject,
+            self.classroom_id,
+            self.entity_type,
+            self.entity_event_id,
+            self.label,
+            self.created_by as AgentId,
+            self.event_created_at,
+            self.error,
+        )
+        .fetch_one(conn)
+        .await
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ListQuery {
+    ids: Option<Vec<Uuid>>,
+    limit: Option<usize>,
+}
+
+impl ListQuery {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn ids(self, ids: Vec<Uuid>) -> Self {
+        Self {
+            ids: Some(ids),
+            ..self
+        }
+    }
+
+    pub fn limit(self, limit: usize) -> Self {
+        Self {
+            limit: Some(limit),
+            ..self
+        }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Vec<Object>> {
+        sqlx::query_as!(
+            Object,
+            r#"
+            SELECT
+                id,
+                subject,
+                classroom_id,
+                entity_type,
+                entity_event_id,
+                label,
+                created_by AS "created_by!: AgentId",
+                event_created_at,
+                error,
+                attempts,
+                created_at,
+                updated_at
+            FROM nats_dead_letter
+            WHERE ($1::UUID[] IS NULL OR id = ANY($1))
+            ORDER BY created_at
+            LIMIT $2
+            "#,
+            self.ids.as_deref(),
+            self.limit.map(|l| l as i64),
+        )
+        .fetch_all(conn)
+        .await
+    }
+}
+
+#[derive(Debug)]
+pub struct DeleteQuery {
+    id: Uuid,
+}
+
+impl DeleteQuery {
+    pub fn new(id: Uuid) -> Self {
+        Self { id }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<usize> {
+        sqlx::query!(
+            r#"
+            DELETE FROM nats_dead_letter
+            WHERE id = $1
+            "#,
+            self.id,
+        )
+        .execute(conn)
+        .await
+        .map(|r| r.rows_affected() as usize)
+    }
+}
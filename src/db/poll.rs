@@ -0,0 +1,355 @@
+use chrono::serde::{ts_milliseconds, ts_milliseconds_option};
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use sqlx::postgres::PgConnection;
+use svc_agent::{AccountId, AgentId};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// A poll of the room. Its identifier is the one of the `poll` event.
+#[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
+#[schema(as = Poll)]
+pub struct Object {
+    id: Uuid,
+    room_id: Uuid,
+    #[schema(value_type = Object)]
+    data: JsonValue,
+    /// Number of options. Votes are option indexes.
+    options: i32,
+    #[serde(
+        with = "ts_milliseconds_option",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    #[schema(value_type = Option<i64>)]
+    closes_at: Option<DateTime<Utc>>,
+    #[serde(
+        with = "ts_milliseconds_option",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    #[schema(value_type = Option<i64>)]
+    closed_at: Option<DateTime<Utc>>,
+    #[schema(value_type = String)]
+    created_by: AgentId,
+    #[serde(with = "ts_milliseconds")]
+    #[schema(value_type = i64)]
+    created_at: DateTime<Utc>,
+}
+
+impl Object {
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+
+    pub fn options(&self) -> i32 {
+        self.options
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.closed_at.is_none()
+            && self
+                .closes_at
+                .is_none_or(|closes_at| closes_at > Utc::now())
+    }
+}
+
+/// Vote counters of the poll. The correct option of a quiz is revealed once it gets closed.
+#[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
+#[schema(as = PollResults)]
+pub struct Results {
+    poll_id: Uuid,
+    room_id: Uuid,
+    /// Number of votes for each option.
+    votes: Vec<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    correct_option: Option<i32>,
+    #[serde(
+        with = "ts_milliseconds_option",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    #[schema(value_type = Option<i64>)]
+    closed_at: Option<DateTime<Utc>>,
+}
+
+impl Results {
+    pub fn room_id(&self) -> Uuid {
+        self.room_id
+    }
+
+    #[cfg(test)]
+    pub fn votes(&self) -> &[i32] {
+        &self.votes
+    }
+
+    #[cfg(test)]
+    pub fn correct_option(&self) -> Option<i32> {
+        self.correct_option
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Starts counting votes for the poll event.
+#[derive(Debug)]
+pub struct InsertQuery {
+    id: Uuid,
+    room_id: Uuid,
+    options: i32,
+    correct_option: Option<i32>,
+    closes_at: Option<DateTime<Utc>>,
+}
+
+impl InsertQuery {
+    pub fn new(id: Uuid, room_id: Uuid, options: i32) -> Self {
+        Self {
+            id,
+            room_id,
+            options,
+            correct_option: None,
+            closes_at: None,
+        }
+    }
+
+    pub fn correct_option(self, correct_option: i32) -> Self {
+        Self {
+            correct_option: Some(correct_option),
+            ..self
+        }
+    }
+
+    pub fn closes_at(self, closes_at: DateTime<Utc>) -> Self {
+        Self {
+            closes_at: Some(closes_at),
+            ..self
+        }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<()> {
+        sqlx::query!(
+            r#"
+            WITH p AS (
+                INSERT INTO poll (id, room_id, options, correct_option, closes_at)
+                VALUES ($1, $2, $3, $4, $5)
+                RETURNING id, options
+            )
+            INSERT INTO poll_tally (poll_id, option)
+            SELECT p.id, GENERATE_SERIES(0, p.options - 1)
+            FROM p
+            "#,
+            self.id,
+            self.room_id,
+            self.options,
+            self.correct_option,
+            self.closes_at,
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug)]
+pub struct FindQuery {
+    room_id: Uuid,
+    id: Uuid,
+}
+
+impl FindQuery {
+    pub fn new(room_id: Uuid, id: Uuid) -> Self {
+        Self { room_id, id }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Option<Object>> {
+        sqlx::query_as!(
+            Object,
+            r#"
+            SELECT
+                p.id,
+                p.room_id,
+                e.data AS "data!: JsonValue",
+                p.options,
+                p.closes_at,
+                p.closed_at,
+                e.created_by AS "created_by!: AgentId",
+                e.created_at
+            FROM poll AS p
+            INNER JOIN event AS e
+            ON e.id = p.id
+            WHERE p.room_id = $1
+                AND p.id = $2
+                AND e.deleted_at IS NULL
+            "#,
+            self.room_id,
+            self.id,
+        )
+        .fetch_optional(conn)
+        .await
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Counts the account's vote unless it has already voted or the poll is closed.
+#[derive(Debug)]
+pub struct VoteQuery {
+    id: Uuid,
+    account_id: AccountId,
+    option: i32,
+}
+
+impl VoteQuery {
+    pub fn new(id: Uuid, account_id: AccountId, option: i32) -> Self {
+        Self {
+            id,
+            account_id,
+            option,
+        }
+    }
+
+    /// Returns whether the vote has been counted.
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<bool> {
+        let result = sqlx::query!(
+            r#"
+            WITH vote AS (
+                INSERT INTO poll_vote (poll_id, account_id, option)
+                SELECT id, $2, $3
+                FROM poll
+                WHERE id = $1
+                    AND closed_at IS NULL
+                    AND (closes_at IS NULL OR closes_at > NOW())
+                ON CONFLICT DO NOTHING
+                RETURNING poll_id, option
+            )
+            UPDATE poll_tally AS t
+            SET votes = t.votes + 1
+            FROM vote
+            WHERE t.poll_id = vote.poll_id
+                AND t.option = vote.option
+            "#,
+            self.id,
+            self.account_id as AccountId,
+            self.option,
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug)]
+pub struct ResultsQuery {
+    id: Uuid,
+}
+
+impl ResultsQuery {
+    pub fn new(id: Uuid) -> Self {
+        Self { id }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Option<Results>> {
+        sqlx::query_as!(
+            Results,
+            r#"
+            SELECT
+                p.id AS poll_id,
+                p.room_id,
+                ARRAY(
+                    SELECT t.votes
+                    FROM poll_tally AS t
+                    WHERE t.poll_id = p.id
+                    ORDER BY t.option
+                ) AS "votes!",
+                CASE WHEN p.closed_at IS NOT NULL THEN p.correct_option END AS correct_option,
+                p.closed_at
+            FROM poll AS p
+            WHERE p.id = $1
+            "#,
+            self.id,
+        )
+        .fetch_optional(conn)
+        .await
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Closes the poll unless it's already closed.
+#[derive(Debug)]
+pub struct CloseQuery {
+    room_id: Uuid,
+    id: Uuid,
+}
+
+impl CloseQuery {
+    pub fn new(room_id: Uuid, id: Uuid) -> Self {
+        Self { room_id, id }
+    }
+
+    /// Returns whether the poll has been closed by the query.
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<bool> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE poll
+            SET closed_at = NOW()
+            WHERE room_id = $1
+                AND id = $2
+                AND closed_at IS NULL
+            "#,
+            self.room_id,
+            self.id,
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Closes polls which `closes_at` has come and returns their identifiers.
+#[derive(Debug)]
+pub struct CloseDueQuery {
+    limit: i64,
+}
+
+impl CloseDueQuery {
+    pub fn new(limit: i64) -> Self {
+        Self { limit }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Vec<Uuid>> {
+        let rows = sqlx::query!(
+            r#"
+            UPDATE poll
+            SET closed_at = NOW()
+            WHERE id IN (
+                SELECT id
+                FROM poll
+                WHERE closed_at IS NULL
+                    AND closes_at <= NOW()
+                ORDER BY closes_at
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id
+            "#,
+            self.limit,
+        )
+        .fetch_all(conn)
+        .await?;
+
+        Ok(rows.into_iter().map(|row| row.id).collect())
+    }
+}
@@ -0,0 +1,278 @@
+use chrono::serde::{ts_milliseconds, ts_milliseconds_option};
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use sqlx::postgres::PgConnection;
+use svc_agent::{AccountId, AgentId};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// A question of the room's Q&A. Its identifier is the one of the `question` event.
+#[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
+#[schema(as = Question)]
+pub struct Object {
+    id: Uuid,
+    room_id: Uuid,
+    #[schema(value_type = Object)]
+    data: JsonValue,
+    votes: i32,
+    /// The latest `answer` event.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    answer_id: Option<Uuid>,
+    #[serde(
+        with = "ts_milliseconds_option",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    #[schema(value_type = Option<i64>)]
+    answered_at: Option<DateTime<Utc>>,
+    #[schema(value_type = String)]
+    created_by: AgentId,
+    #[serde(with = "ts_milliseconds")]
+    #[schema(value_type = i64)]
+    created_at: DateTime<Utc>,
+}
+
+impl Object {
+    #[cfg(test)]
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+
+    #[cfg(test)]
+    pub fn votes(&self) -> i32 {
+        self.votes
+    }
+
+    #[cfg(test)]
+    pub fn answer_id(&self) -> Option<Uuid> {
+        self.answer_id
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Starts counting votes for the question event.
+#[derive(Debug)]
+pub struct InsertQuery {
+    id: Uuid,
+    room_id: Uuid,
+}
+
+impl InsertQuery {
+    pub fn new(id: Uuid, room_id: Uuid) -> Self {
+        Self { id, room_id }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO question (id, room_id)
+            VALUES ($1, $2)
+            "#,
+            self.id,
+            self.room_id,
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug)]
+pub struct FindQuery {
+    room_id: Uuid,
+    id: Uuid,
+}
+
+impl FindQuery {
+    pub fn new(room_id: Uuid, id: Uuid) -> Self {
+        Self { room_id, id }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Option<Object>> {
+        sqlx::query_as!(
+            Object,
+            r#"
+            SELECT
+                q.id,
+                q.room_id,
+                e.data AS "data!: JsonValue",
+                q.votes,
+                q.answer_id,
+                q.answered_at,
+                e.created_by AS "created_by!: AgentId",
+                e.created_at
+            FROM question AS q
+            INNER JOIN event AS e
+            ON e.id = q.id
+            WHERE q.room_id = $1
+                AND q.id = $2
+                AND e.deleted_at IS NULL
+            "#,
+            self.room_id,
+            self.id,
+        )
+        .fetch_optional(conn)
+        .await
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Sort {
+    /// The most voted first, then the latest.
+    #[default]
+    Votes,
+    /// The latest first.
+    CreatedAt,
+}
+
+/// Questions of not deleted events of the room.
+#[derive(Debug)]
+pub struct ListQuery {
+    room_id: Uuid,
+    sort: Sort,
+    answered: Option<bool>,
+    limit: i64,
+    offset: i64,
+}
+
+impl ListQuery {
+    pub fn new(room_id: Uuid, limit: i64) -> Self {
+        Self {
+            room_id,
+            sort: Sort::default(),
+            answered: None,
+            limit,
+            offset: 0,
+        }
+    }
+
+    pub fn sort(self, sort: Sort) -> Self {
+        Self { sort, ..self }
+    }
+
+    pub fn answered(self, answered: bool) -> Self {
+        Self {
+            answered: Some(answered),
+            ..self
+        }
+    }
+
+    pub fn offset(self, offset: i64) -> Self {
+        Self { offset, ..self }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Vec<Object>> {
+        sqlx::query_as!(
+            Object,
+            r#"
+            SELECT
+                q.id,
+                q.room_id,
+                e.data AS "data!: JsonValue",
+                q.votes,
+                q.answer_id,
+                q.answered_at,
+                e.created_by AS "created_by!: AgentId",
+                e.created_at
+            FROM question AS q
+            INNER JOIN event AS e
+            ON e.id = q.id
+            WHERE q.room_id = $1
+                AND e.deleted_at IS NULL
+                AND ($3::boolean IS NULL OR (q.answered_at IS NOT NULL) = $3)
+            ORDER BY
+                CASE WHEN $2 THEN q.votes ELSE 0 END DESC,
+                e.created_at DESC
+            LIMIT $4
+            OFFSET $5
+            "#,
+            self.room_id,
+            self.sort == Sort::Votes,
+            self.answered,
+            self.limit,
+            self.offset,
+        )
+        .fetch_all(conn)
+        .await
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Counts the account's vote unless it has already voted for the question.
+#[derive(Debug)]
+pub struct UpvoteQuery {
+    id: Uuid,
+    account_id: AccountId,
+}
+
+impl UpvoteQuery {
+    pub fn new(id: Uuid, account_id: AccountId) -> Self {
+        Self { id, account_id }
+    }
+
+    /// Returns whether the vote has been counted.
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<bool> {
+        let result = sqlx::query!(
+            r#"
+            WITH vote AS (
+                INSERT INTO question_vote (question_id, account_id)
+                VALUES ($1, $2)
+                ON CONFLICT DO NOTHING
+                RETURNING question_id
+            )
+            UPDATE question
+            SET votes = votes + 1
+            WHERE id IN (SELECT question_id FROM vote)
+            "#,
+            self.id,
+            self.account_id as AccountId,
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Marks the question answered with the `answer` event.
+#[derive(Debug)]
+pub struct AnswerQuery {
+    id: Uuid,
+    answer_id: Uuid,
+}
+
+impl AnswerQuery {
+    pub fn new(id: Uuid, answer_id: Uuid) -> Self {
+        Self { id, answer_id }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<()> {
+        sqlx::query!(
+            r#"
+            UPDATE question
+            SET answer_id = $2,
+                answered_at = COALESCE(answered_at, NOW())
+            WHERE id = $1
+            "#,
+            self.id,
+            self.answer_id,
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,130 @@
+use serde::Serialize;
+use sqlx::postgres::PgConnection;
+use uuid::Uuid;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Events of the `kind` older than `max_lifetime` seconds get deleted by vacuum.
+///
+/// A rule is set either for a room or for all rooms of an audience.
+/// Room rules take precedence over audience ones for the same kind.
+#[derive(Debug, sqlx::FromRow, Serialize)]
+pub struct Object {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    room_id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    audience: Option<String>,
+    kind: String,
+    max_lifetime: i64,
+}
+
+#[derive(Clone, Debug)]
+pub enum Scope {
+    Room(Uuid),
+    Audience(String),
+}
+
+impl Scope {
+    fn room_id(&self) -> Option<Uuid> {
+        match self {
+            Self::Room(room_id) => Some(*room_id),
+            Self::Audience(_) => None,
+        }
+    }
+
+    fn audience(&self) -> Option<String> {
+        match self {
+            Self::Room(_) => None,
+            Self::Audience(audience) => Some(audience.to_owned()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ListQuery {
+    scope: Scope,
+}
+
+impl ListQuery {
+    pub fn new(scope: Scope) -> Self {
+        Self { scope }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Vec<Object>> {
+        sqlx::query_as!(
+            Object,
+            r#"
+            SELECT room_id, audience, kind, max_lifetime
+            FROM retention_rule
+            WHERE room_id = $1
+            OR    audience = $2
+            ORDER BY kind
+            "#,
+            self.scope.room_id(),
+            self.scope.audience(),
+        )
+        .fetch_all(conn)
+        .await
+    }
+}
+
+#[derive(Debug)]
+pub struct InsertQuery {
+    scope: Scope,
+    kind: String,
+    max_lifetime: i64,
+}
+
+impl InsertQuery {
+    pub fn new(scope: Scope, kind: String, max_lifetime: i64) -> Self {
+        Self {
+            scope,
+            kind,
+            max_lifetime,
+        }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Object> {
+        sqlx::query_as!(
+            Object,
+            r#"
+            INSERT INTO retention_rule (room_id, audience, kind, max_lifetime)
+            VALUES ($1, $2, $3, $4)
+            RETURNING room_id, audience, kind, max_lifetime
+            "#,
+            self.scope.room_id(),
+            self.scope.audience(),
+            self.kind,
+            self.max_lifetime,
+        )
+        .fetch_one(conn)
+        .await
+    }
+}
+
+/// Deletes all rules of the scope.
+#[derive(Debug)]
+pub struct DeleteQuery {
+    scope: Scope,
+}
+
+impl DeleteQuery {
+    pub fn new(scope: Scope) -> Self {
+        Self { scope }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<usize> {
+        sqlx::query!(
+            r#"
+            DELETE FROM retention_rule
+            WHERE room_id = $1
+            OR    audience = $2
+            "#,
+            self.scope.room_id(),
+            self.scope.audience(),
+        )
+        .execute(conn)
+        .await
+        .map(|r| r.rows_affected() as usize)
+    }
+}
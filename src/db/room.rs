@@ -4,6 +4,7 @@ use std::{
     fmt,
     ops::{Bound, RangeBounds},
     str::FromStr,
+    time::Duration as StdDuration,
 };
 
 use chrono::{serde::ts_seconds, DateTime, Utc};
@@ -11,39 +12,113 @@ use serde_derive::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 use sqlx::postgres::{types::PgRange, PgConnection};
 use svc_authn::AccountId;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 ///////////////////////////////////////////////////////////////////////////////
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
+#[schema(as = Room)]
 pub struct Object {
     id: Uuid,
     audience: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     source_room_id: Option<Uuid>,
+    /// `[opened_at, closed_at)` in seconds, `closed_at` is null for unbounded rooms.
     #[serde(with = "serde::time")]
+    #[schema(value_type = Vec<Option<i64>>)]
     time: Time,
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
     tags: Option<JsonValue>,
     #[serde(with = "ts_seconds")]
+    #[schema(value_type = i64)]
     created_at: DateTime<Utc>,
     preserve_history: bool,
     classroom_id: Uuid,
     #[serde(default)]
     locked_types: HashMap<String, bool>,
     #[serde(default)]
+    #[schema(value_type = Object)]
     whiteboard_access: HashMap<AccountId, bool>,
     kind: ClassType,
+    #[serde(default)]
+    moderated: bool,
+    #[serde(default)]
+    keep_open: bool,
+    /// Limit of agents in the room. `config.room_capacity.default` applies when missing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    capacity: Option<i32>,
+    /// Audience of another tenant co-hosting the room. Its authorization grants access too.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    secondary_audience: Option<String>,
+    #[serde(default)]
+    #[schema(value_type = Object)]
+    settings: Settings,
 }
 
-#[derive(Clone, Copy, Debug, sqlx::Type, PartialEq, Eq, Deserialize, Serialize)]
+/// Lowercase names are accepted as well like they're written in the docs and the config.
+#[derive(Clone, Copy, Debug, sqlx::Type, PartialEq, Eq, Hash, Deserialize, Serialize, ToSchema)]
 #[sqlx(type_name = "class_type", rename_all = "lowercase")]
 pub enum ClassType {
+    #[serde(alias = "webinar")]
     Webinar,
+    #[serde(alias = "p2p")]
     P2P,
+    #[serde(alias = "minigroup")]
     Minigroup,
 }
 
+/// Per-room toggles kept in a JSONB column so adding one doesn't need a migration.
+///
+/// Every setting is optional in the column, typed accessors fall back to defaults.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Settings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    agent_actions: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    whiteboard_access_validation: Option<bool>,
+}
+
+impl Settings {
+    /// Whether agents entering and leaving are recorded as `agent_enter` and `agent_left` events.
+    pub fn agent_actions(&self) -> bool {
+        self.agent_actions.unwrap_or(true)
+    }
+
+    /// Applies a JSON merge patch: `null` resets a setting to its default, other values replace it.
+    /// Fails on unknown settings and values of wrong types.
+    pub fn merge(&self, patch: &JsonValue) -> anyhow::Result<Self> {
+        let patch = patch
+            .as_object()
+            .ok_or_else(|| anyhow!("Settings patch must be an object"))?;
+
+        let mut merged = match serde_json::to_value(self)? {
+            JsonValue::Object(map) => map,
+            _ => Default::default(),
+        };
+
+        for (key, value) in patch {
+            if value.is_null() {
+                merged.remove(key);
+            } else {
+                merged.insert(key.to_owned(), value.to_owned());
+            }
+        }
+
+        let merged = JsonValue::Object(merged);
+        let settings: Self = serde_json::from_value(merged.clone())
+            .map_err(|err| anyhow!("Invalid room settings: {}", err))?;
+
+        // Unknown settings get lost on deserialization.
+        if serde_json::to_value(&settings)? != merged {
+            return Err(anyhow!("Unknown room settings in {}", merged));
+        }
+
+        Ok(settings)
+    }
+}
+
 impl fmt::Display for ClassType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         // Used to generate S3 bucket names to dump events
@@ -69,6 +144,11 @@ struct DbObject {
     locked_types: JsonValue,
     whiteboard_access: JsonValue,
     kind: ClassType,
+    moderated: bool,
+    keep_open: bool,
+    capacity: Option<i32>,
+    secondary_audience: Option<String>,
+    settings: JsonValue,
 }
 
 impl TryFrom<DbObject> for Object {
@@ -87,6 +167,11 @@ impl TryFrom<DbObject> for Object {
             locked_types,
             whiteboard_access,
             kind,
+            moderated,
+            keep_open,
+            capacity,
+            secondary_audience,
+            settings,
         } = v;
 
         let locked_types = locked_types
@@ -119,6 +204,12 @@ impl TryFrom<DbObject> for Object {
             })
             .collect();
 
+        let settings =
+            serde_json::from_value(settings).map_err(|err| sqlx::Error::ColumnDecode {
+                index: "settings".into(),
+                source: Box::new(err) as Box<dyn std::error::Error + Sync + Send>,
+            })?;
+
         Ok(Self {
             id,
             audience,
@@ -131,6 +222,11 @@ impl TryFrom<DbObject> for Object {
             locked_types,
             whiteboard_access,
             kind,
+            moderated,
+            keep_open,
+            capacity,
+            secondary_audience,
+            settings,
         })
     }
 }
@@ -149,10 +245,16 @@ impl From<Object> for DbObject {
             locked_types,
             whiteboard_access,
             kind,
+            moderated,
+            keep_open,
+            capacity,
+            secondary_audience,
+            settings,
         } = v;
 
         let locked_types = serde_json::to_value(locked_types).unwrap();
         let whiteboard_access = serde_json::to_value(whiteboard_access).unwrap();
+        let settings = serde_json::to_value(settings).unwrap();
 
         Self {
             id,
@@ -166,6 +268,11 @@ impl From<Object> for DbObject {
             locked_types,
             whiteboard_access,
             kind,
+            moderated,
+            keep_open,
+            capacity,
+            secondary_audience,
+            settings,
         }
     }
 }
@@ -238,8 +345,38 @@ impl Object {
         &self.locked_types
     }
 
+    pub fn moderated(&self) -> bool {
+        self.moderated
+    }
+
+    /// Opts the room out of closing automatically when nobody is there.
+    pub fn keep_open(&self) -> bool {
+        self.keep_open
+    }
+
+    pub fn capacity(&self) -> Option<i32> {
+        self.capacity
+    }
+
+    pub fn secondary_audience(&self) -> Option<&str> {
+        self.secondary_audience.as_deref()
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    /// The room's own audience followed by the co-hosting one if any.
+    pub fn audiences(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.audience.as_str()).chain(self.secondary_audience())
+    }
+
+    /// Whether drawing requires access granted with `room.whiteboard_access`.
+    /// It's required in minigroups unless the room's settings say otherwise.
     pub fn validate_whiteboard_access(&self) -> bool {
-        self.kind == ClassType::Minigroup
+        self.settings
+            .whiteboard_access_validation
+            .unwrap_or(self.kind == ClassType::Minigroup)
     }
 
     pub fn whiteboard_access(&self) -> &HashMap<AccountId, bool> {
@@ -382,6 +519,11 @@ impl Builder {
             locked_types: Default::default(),
             whiteboard_access: Default::default(),
             kind: self.kind.ok_or_else(|| anyhow!("missing kind"))?,
+            moderated: false,
+            keep_open: false,
+            capacity: None,
+            secondary_audience: None,
+            settings: Default::default(),
         })
     }
 }
@@ -424,7 +566,12 @@ impl FindQuery {
                 classroom_id,
                 locked_types,
                 whiteboard_access,
-                kind AS "kind!: ClassType"
+                kind AS "kind!: ClassType",
+                moderated,
+                keep_open,
+                capacity,
+                secondary_audience,
+                settings
             FROM room
             WHERE ($1::uuid IS NULL OR id = $1)
                 AND ($2::uuid IS NULL OR classroom_id = $2)
@@ -441,6 +588,233 @@ impl FindQuery {
 
 ///////////////////////////////////////////////////////////////////////////////
 
+/// Rooms of the audience from the most recently created.
+#[derive(Debug)]
+pub struct ListQuery {
+    audience: String,
+    open: Option<bool>,
+    closed: Option<bool>,
+    classroom_id: Option<Uuid>,
+    created_at: Option<BoundedDateTimeTuple>,
+    cursor: Option<Uuid>,
+    offset: usize,
+    limit: usize,
+}
+
+impl ListQuery {
+    pub fn new(audience: String, offset: usize, limit: usize) -> Self {
+        Self {
+            audience,
+            open: None,
+            closed: None,
+            classroom_id: None,
+            created_at: None,
+            cursor: None,
+            offset,
+            limit,
+        }
+    }
+
+    /// Filters rooms being open right now or the other ones.
+    pub fn open(self, open: bool) -> Self {
+        Self {
+            open: Some(open),
+            ..self
+        }
+    }
+
+    /// Filters rooms which have already been closed or the other ones.
+    pub fn closed(self, closed: bool) -> Self {
+        Self {
+            closed: Some(closed),
+            ..self
+        }
+    }
+
+    pub fn classroom_id(self, classroom_id: Uuid) -> Self {
+        Self {
+            classroom_id: Some(classroom_id),
+            ..self
+        }
+    }
+
+    pub fn created_at(self, created_at: BoundedDateTimeTuple) -> Self {
+        Self {
+            created_at: Some(created_at),
+            ..self
+        }
+    }
+
+    /// Lists rooms following the one with the given id.
+    pub fn cursor(self, cursor: Uuid) -> Self {
+        Self {
+            cursor: Some(cursor),
+            ..self
+        }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Vec<Object>> {
+        let created_at: Option<PgRange<DateTime<Utc>>> = self.created_at.map(|t| t.into());
+
+        sqlx::query_as!(
+            DbObject,
+            r#"
+            SELECT
+                id,
+                audience,
+                source_room_id,
+                time AS "time!: Time",
+                tags,
+                created_at,
+                preserve_history,
+                classroom_id,
+                locked_types,
+                whiteboard_access,
+                kind AS "kind!: ClassType",
+                moderated,
+                keep_open,
+                capacity,
+                secondary_audience,
+                settings
+            FROM room
+            WHERE audience = $1
+                AND ($2::boolean IS NULL OR (time @> NOW()) = $2)
+                AND ($3::uuid IS NULL OR classroom_id = $3)
+                AND ($4::boolean IS NULL OR COALESCE(UPPER(time) <= NOW(), FALSE) = $4)
+                AND ($5::tstzrange IS NULL OR $5::tstzrange @> created_at)
+                AND (
+                    $6::uuid IS NULL
+                    OR (created_at, id) < (SELECT created_at, id FROM room WHERE id = $6)
+                )
+            ORDER BY created_at DESC, id DESC
+            OFFSET $7
+            LIMIT $8
+            "#,
+            self.audience,
+            self.open,
+            self.classroom_id,
+            self.closed,
+            created_at,
+            self.cursor,
+            self.offset as i64,
+            self.limit as i64,
+        )
+        .fetch_all(conn)
+        .await?
+        .into_iter()
+        .map(|v| v.try_into())
+        .collect()
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Looks up an audience's rooms by tenant's attributes.
+#[derive(Debug)]
+pub struct SearchQuery {
+    audience: String,
+    classroom_id: Option<Uuid>,
+    tags: Option<JsonValue>,
+    open: Option<bool>,
+    created_at: Option<BoundedDateTimeTuple>,
+    offset: usize,
+    limit: usize,
+}
+
+impl SearchQuery {
+    pub fn new(audience: String, offset: usize, limit: usize) -> Self {
+        Self {
+            audience,
+            classroom_id: None,
+            tags: None,
+            open: None,
+            created_at: None,
+            offset,
+            limit,
+        }
+    }
+
+    pub fn classroom_id(self, classroom_id: Uuid) -> Self {
+        Self {
+            classroom_id: Some(classroom_id),
+            ..self
+        }
+    }
+
+    /// Filters rooms with tags containing the given JSON object.
+    pub fn tags(self, tags: JsonValue) -> Self {
+        Self {
+            tags: Some(tags),
+            ..self
+        }
+    }
+
+    /// Filters rooms being open right now or the other ones.
+    pub fn open(self, open: bool) -> Self {
+        Self {
+            open: Some(open),
+            ..self
+        }
+    }
+
+    pub fn created_at(self, created_at: BoundedDateTimeTuple) -> Self {
+        Self {
+            created_at: Some(created_at),
+            ..self
+        }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Vec<Object>> {
+        let created_at: Option<PgRange<DateTime<Utc>>> = self.created_at.map(|t| t.into());
+
+        sqlx::query_as!(
+            DbObject,
+            r#"
+            SELECT
+                id,
+                audience,
+                source_room_id,
+                time AS "time!: Time",
+                tags,
+                created_at,
+                preserve_history,
+                classroom_id,
+                locked_types,
+                whiteboard_access,
+                kind AS "kind!: ClassType",
+                moderated,
+                keep_open,
+                capacity,
+                secondary_audience,
+                settings
+            FROM room
+            WHERE audience = $1
+                AND ($2::uuid IS NULL OR classroom_id = $2)
+                AND ($3::jsonb IS NULL OR tags::jsonb @> $3::jsonb)
+                AND ($4::boolean IS NULL OR (time @> NOW()) = $4)
+                AND ($5::tstzrange IS NULL OR $5::tstzrange @> created_at)
+            ORDER BY created_at DESC, id
+            OFFSET $6
+            LIMIT $7
+            "#,
+            self.audience,
+            self.classroom_id,
+            self.tags,
+            self.open,
+            created_at,
+            self.offset as i64,
+            self.limit as i64,
+        )
+        .fetch_all(conn)
+        .await?
+        .into_iter()
+        .map(|v| v.try_into())
+        .collect()
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
 #[derive(Debug)]
 pub struct InsertQuery {
     audience: String,
@@ -452,6 +826,11 @@ pub struct InsertQuery {
     locked_types: HashMap<String, bool>,
     whiteboard_access: HashMap<AccountId, bool>,
     kind: ClassType,
+    moderated: bool,
+    keep_open: bool,
+    capacity: Option<i32>,
+    secondary_audience: Option<String>,
+    settings: Settings,
 }
 
 impl InsertQuery {
@@ -466,6 +845,11 @@ impl InsertQuery {
             locked_types: Default::default(),
             whiteboard_access: Default::default(),
             kind,
+            moderated: false,
+            keep_open: false,
+            capacity: None,
+            secondary_audience: None,
+            settings: Default::default(),
         }
     }
 
@@ -490,19 +874,51 @@ impl InsertQuery {
         }
     }
 
+    pub fn moderated(self, moderated: bool) -> Self {
+        Self { moderated, ..self }
+    }
+
+    pub fn keep_open(self, keep_open: bool) -> Self {
+        Self { keep_open, ..self }
+    }
+
+    pub fn capacity(self, capacity: Option<i32>) -> Self {
+        Self { capacity, ..self }
+    }
+
+    pub fn secondary_audience(self, secondary_audience: Option<String>) -> Self {
+        Self {
+            secondary_audience,
+            ..self
+        }
+    }
+
+    pub fn locked_types(self, locked_types: HashMap<String, bool>) -> Self {
+        Self {
+            locked_types,
+            ..self
+        }
+    }
+
+    pub fn settings(self, settings: Settings) -> Self {
+        Self { settings, ..self }
+    }
+
     pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Object> {
         let time: PgRange<DateTime<Utc>> = self.time.into();
 
         let locked_types = serde_json::to_value(&self.locked_types).unwrap();
         let whiteboard_access = serde_json::to_value(&self.whiteboard_access).unwrap();
+        let settings = serde_json::to_value(&self.settings).unwrap();
 
         sqlx::query_as!(
             DbObject,
             r#"
             INSERT INTO room (
                 audience, source_room_id, time, tags, preserve_history, classroom_id,
-                    locked_types, whiteboard_access, kind)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                    locked_types, whiteboard_access, kind, moderated, keep_open, capacity,
+                    secondary_audience, settings)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
             RETURNING
                 id,
                 audience,
@@ -514,7 +930,12 @@ impl InsertQuery {
                 classroom_id,
                 locked_types,
                 whiteboard_access,
-                kind AS "kind!: ClassType"
+                kind AS "kind!: ClassType",
+                moderated,
+                keep_open,
+                capacity,
+                secondary_audience,
+                settings
             "#,
             self.audience,
             self.source_room_id,
@@ -525,6 +946,11 @@ impl InsertQuery {
             locked_types,
             whiteboard_access,
             self.kind as ClassType,
+            self.moderated,
+            self.keep_open,
+            self.capacity,
+            self.secondary_audience,
+            settings,
         )
         .fetch_one(conn)
         .await?
@@ -542,6 +968,10 @@ pub struct UpdateQuery {
     classroom_id: Option<Uuid>,
     locked_types: Option<HashMap<String, bool>>,
     whiteboard_access: Option<HashMap<AccountId, bool>>,
+    moderated: Option<bool>,
+    keep_open: Option<bool>,
+    capacity: Option<i32>,
+    secondary_audience: Option<String>,
 }
 
 impl UpdateQuery {
@@ -553,6 +983,10 @@ impl UpdateQuery {
             classroom_id: None,
             locked_types: None,
             whiteboard_access: None,
+            moderated: None,
+            keep_open: None,
+            capacity: None,
+            secondary_audience: None,
         }
     }
 
@@ -585,6 +1019,25 @@ impl UpdateQuery {
         }
     }
 
+    pub fn moderated(self, moderated: Option<bool>) -> Self {
+        Self { moderated, ..self }
+    }
+
+    pub fn keep_open(self, keep_open: Option<bool>) -> Self {
+        Self { keep_open, ..self }
+    }
+
+    pub fn capacity(self, capacity: Option<i32>) -> Self {
+        Self { capacity, ..self }
+    }
+
+    pub fn secondary_audience(self, secondary_audience: Option<String>) -> Self {
+        Self {
+            secondary_audience,
+            ..self
+        }
+    }
+
     pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Object> {
         let time: Option<PgRange<DateTime<Utc>>> = self.time.map(|t| t.into());
 
@@ -606,7 +1059,11 @@ impl UpdateQuery {
                 tags = COALESCE($3::JSON, tags),
                 classroom_id = COALESCE($4, classroom_id),
                 locked_types = COALESCE($5, locked_types),
-                whiteboard_access = COALESCE($6, whiteboard_access)
+                whiteboard_access = COALESCE($6, whiteboard_access),
+                moderated = COALESCE($7, moderated),
+                keep_open = COALESCE($8, keep_open),
+                capacity = COALESCE($9, capacity),
+                secondary_audience = COALESCE($10, secondary_audience)
             WHERE id = $1
             RETURNING
                 id,
@@ -619,14 +1076,23 @@ impl UpdateQuery {
                 classroom_id,
                 locked_types,
                 whiteboard_access,
-                kind AS "kind!: ClassType"
+                kind AS "kind!: ClassType",
+                moderated,
+                keep_open,
+                capacity,
+                secondary_audience,
+                settings
             "#,
             self.id,
             time,
             self.tags,
             self.classroom_id,
             locked_types,
-            whiteboard_access
+            whiteboard_access,
+            self.moderated,
+            self.keep_open,
+            self.capacity,
+            self.secondary_audience,
         )
         .fetch_one(conn)
         .await?
@@ -636,6 +1102,286 @@ impl UpdateQuery {
 
 ///////////////////////////////////////////////////////////////////////////////
 
+/// Merges the patch into room settings removing those set to `null`.
+/// Validate it with [`Settings::merge`] first.
+#[derive(Debug)]
+pub struct UpdateSettingsQuery {
+    id: Uuid,
+    patch: JsonValue,
+}
+
+impl UpdateSettingsQuery {
+    pub fn new(id: Uuid, patch: JsonValue) -> Self {
+        Self { id, patch }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Object> {
+        sqlx::query_as!(
+            DbObject,
+            r#"
+            UPDATE room
+            SET settings = JSONB_STRIP_NULLS(settings || $2::JSONB)
+            WHERE id = $1
+            RETURNING
+                id,
+                audience,
+                source_room_id,
+                time AS "time!: Time",
+                tags,
+                created_at,
+                preserve_history,
+                classroom_id,
+                locked_types,
+                whiteboard_access,
+                kind AS "kind!: ClassType",
+                moderated,
+                keep_open,
+                capacity,
+                secondary_audience,
+                settings
+            "#,
+            self.id,
+            self.patch,
+        )
+        .fetch_one(conn)
+        .await?
+        .try_into()
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Closes open rooms of the audience where no agent has been ready for `idle_timeout`.
+///
+/// Agents leaving the room are recorded as `agent_left` events so the last event
+/// time tells when the room has become empty.
+#[derive(Debug)]
+pub struct AutoCloseQuery {
+    audience: String,
+    idle_timeout: StdDuration,
+}
+
+impl AutoCloseQuery {
+    pub fn new(audience: &str, idle_timeout: StdDuration) -> Self {
+        Self {
+            audience: audience.to_owned(),
+            idle_timeout,
+        }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Vec<Object>> {
+        sqlx::query_as!(
+            DbObject,
+            r#"
+            UPDATE room
+            SET time = TSTZRANGE(LOWER(time), NOW(), '[)')
+            WHERE id IN (
+                SELECT r.id
+                FROM room AS r
+                WHERE r.audience = $1
+                AND   r.keep_open = FALSE
+                AND   r.time @> NOW()
+                AND   LOWER(r.time) < NOW() - $2::BIGINT * INTERVAL '1 millisecond'
+                AND   NOT EXISTS (
+                    SELECT 1
+                    FROM agent AS a
+                    WHERE a.room_id = r.id
+                    AND   (a.status = 'ready' OR a.created_at >= NOW() - $2::BIGINT * INTERVAL '1 millisecond')
+                )
+                AND   NOT EXISTS (
+                    SELECT 1
+                    FROM event AS e
+                    WHERE e.room_id = r.id
+                    AND   e.created_at >= NOW() - $2::BIGINT * INTERVAL '1 millisecond'
+                )
+            )
+            RETURNING
+                id,
+                audience,
+                source_room_id,
+                time AS "time!: Time",
+                tags,
+                created_at,
+                preserve_history,
+                classroom_id,
+                locked_types,
+                whiteboard_access,
+                kind AS "kind!: ClassType",
+                moderated,
+                keep_open,
+                capacity,
+                secondary_audience,
+                settings
+            "#,
+            self.audience,
+            self.idle_timeout.as_millis() as i64,
+        )
+        .fetch_all(conn)
+        .await?
+        .into_iter()
+        .map(|v| v.try_into())
+        .collect()
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// An open room where events stopped arriving.
+#[derive(Debug, Serialize)]
+pub struct InactiveRoom {
+    pub room_id: Uuid,
+    pub classroom_id: Uuid,
+    /// Creation time of the last event or the opening time when there're no events.
+    #[serde(with = "ts_seconds")]
+    pub last_activity_at: DateTime<Utc>,
+}
+
+/// Finds rooms of the audience which are open at the moment and have no events for
+/// `threshold` and marks them alerted.
+///
+/// A room is returned once per inactivity spell: it's returned again only after new events
+/// arrive and stop again.
+#[derive(Debug)]
+pub struct InactivityAlertQuery {
+    audience: String,
+    threshold: StdDuration,
+}
+
+impl InactivityAlertQuery {
+    pub fn new(audience: &str, threshold: StdDuration) -> Self {
+        Self {
+            audience: audience.to_owned(),
+            threshold,
+        }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Vec<InactiveRoom>> {
+        sqlx::query_as!(
+            InactiveRoom,
+            r#"
+            UPDATE room
+            SET inactivity_alerted_at = NOW()
+            FROM (
+                SELECT r.id, q.last_activity_at
+                FROM room AS r
+                CROSS JOIN LATERAL (
+                    SELECT COALESCE(MAX(e.created_at), LOWER(r.time)) AS last_activity_at
+                    FROM event AS e
+                    WHERE e.room_id = r.id
+                ) AS q
+                WHERE r.audience = $1
+                AND   r.time @> NOW()
+                AND   q.last_activity_at < NOW() - $2::BIGINT * INTERVAL '1 millisecond'
+                AND   (r.inactivity_alerted_at IS NULL OR r.inactivity_alerted_at < q.last_activity_at)
+            ) AS inactive
+            WHERE room.id = inactive.id
+            RETURNING
+                room.id AS room_id,
+                room.classroom_id,
+                inactive.last_activity_at AS "last_activity_at!"
+            "#,
+            self.audience,
+            self.threshold.as_millis() as i64,
+        )
+        .fetch_all(conn)
+        .await
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Lists derived rooms older than `max_age` having a newer derivative of the same source
+/// along with rooms derived from them. A room is listed only if its whole subtree is older
+/// than `max_age` and doesn't preserve history. Derived rooms come before their sources.
+#[derive(Debug)]
+pub struct StaleDerivedListQuery {
+    max_age: StdDuration,
+    limit: i64,
+}
+
+impl StaleDerivedListQuery {
+    pub fn new(max_age: StdDuration, limit: usize) -> Self {
+        Self {
+            max_age,
+            limit: limit as i64,
+        }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Vec<Uuid>> {
+        sqlx::query_scalar!(
+            r#"
+            WITH RECURSIVE tree AS (
+                SELECT r.id AS root_id, r.id, r.created_at, r.preserve_history, 0 AS depth
+                FROM room AS r
+                WHERE r.source_room_id IS NOT NULL
+                AND   r.created_at < NOW() - $1::BIGINT * INTERVAL '1 millisecond'
+                AND   EXISTS (
+                    SELECT 1
+                    FROM room AS n
+                    WHERE n.source_room_id = r.source_room_id
+                    AND   n.created_at > r.created_at
+                )
+
+                UNION ALL
+
+                SELECT t.root_id, d.id, d.created_at, d.preserve_history, t.depth + 1
+                FROM room AS d
+                INNER JOIN tree AS t
+                ON d.source_room_id = t.id
+            ),
+            roots AS (
+                SELECT root_id
+                FROM tree
+                GROUP BY root_id
+                HAVING BOOL_AND(
+                    preserve_history = 'f'
+                    AND created_at < NOW() - $1::BIGINT * INTERVAL '1 millisecond'
+                )
+                ORDER BY MIN(created_at)
+                LIMIT $2
+            )
+            SELECT tree.id AS "id!"
+            FROM tree
+            INNER JOIN roots
+            ON roots.root_id = tree.root_id
+            ORDER BY tree.root_id, tree.depth DESC
+            "#,
+            self.max_age.as_millis() as i64,
+            self.limit,
+        )
+        .fetch_all(conn)
+        .await
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug)]
+pub struct DeleteQuery {
+    id: Uuid,
+}
+
+impl DeleteQuery {
+    pub fn new(id: Uuid) -> Self {
+        Self { id }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<usize> {
+        sqlx::query!(
+            r#"
+            DELETE FROM room
+            WHERE id = $1
+            "#,
+            self.id,
+        )
+        .execute(conn)
+        .await
+        .map(|r| r.rows_affected() as usize)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
 use crate::db::room_time::BoundedDateTimeTuple;
 use crate::db::room_time::RoomTime;
 
@@ -0,0 +1,96 @@
+use chrono::{DateTime, Utc};
+use sqlx::postgres::PgConnection;
+use uuid::Uuid;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Number of events of the `kind` created in the room during the `hour`.
+///
+/// Rows are maintained by a trigger on event insert.
+#[derive(Debug, sqlx::FromRow)]
+pub struct Object {
+    room_id: Uuid,
+    kind: String,
+    hour: DateTime<Utc>,
+    count: i64,
+    last_event_at: DateTime<Utc>,
+}
+
+impl Object {
+    pub fn room_id(&self) -> Uuid {
+        self.room_id
+    }
+
+    pub fn kind(&self) -> &str {
+        &self.kind
+    }
+
+    pub fn hour(&self) -> DateTime<Utc> {
+        self.hour
+    }
+
+    pub fn count(&self) -> i64 {
+        self.count
+    }
+
+    pub fn last_event_at(&self) -> DateTime<Utc> {
+        self.last_event_at
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Activity of all rooms of the classroom ordered by room and hour.
+#[derive(Debug)]
+pub struct ClassroomListQuery {
+    classroom_id: Uuid,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+}
+
+impl ClassroomListQuery {
+    pub fn new(classroom_id: Uuid) -> Self {
+        Self {
+            classroom_id,
+            from: None,
+            to: None,
+        }
+    }
+
+    /// Hours starting at or after the moment.
+    pub fn from(self, from: DateTime<Utc>) -> Self {
+        Self {
+            from: Some(from),
+            ..self
+        }
+    }
+
+    /// Hours starting before the moment.
+    pub fn to(self, to: DateTime<Utc>) -> Self {
+        Self {
+            to: Some(to),
+            ..self
+        }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Vec<Object>> {
+        sqlx::query_as!(
+            Object,
+            r#"
+            SELECT ra.room_id, ra.kind, ra.hour, ra.count, ra.last_event_at
+            FROM room_activity AS ra
+            INNER JOIN room AS r
+            ON r.id = ra.room_id
+            WHERE r.classroom_id = $1
+                AND ($2::timestamptz IS NULL OR ra.hour >= $2)
+                AND ($3::timestamptz IS NULL OR ra.hour < $3)
+            ORDER BY ra.room_id, ra.hour, ra.kind
+            "#,
+            self.classroom_id,
+            self.from,
+            self.to,
+        )
+        .fetch_all(conn)
+        .await
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+
+use chrono::{
+    serde::{ts_seconds, ts_seconds_option},
+    DateTime, Utc,
+};
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use sqlx::postgres::PgConnection;
+use svc_agent::AgentId;
+use uuid::Uuid;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// A change of room locked types to apply later.
+///
+/// It's due either at `apply_at` or `before_close` seconds before the room closes.
+/// The latter follows changes of the room time and never gets due for unbounded rooms.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Object {
+    id: Uuid,
+    room_id: Uuid,
+    locked_types: JsonValue,
+    #[serde(
+        default,
+        with = "ts_seconds_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    apply_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    before_close: Option<i32>,
+    created_by: AgentId,
+    #[serde(with = "ts_seconds")]
+    created_at: DateTime<Utc>,
+}
+
+impl Object {
+    pub fn room_id(&self) -> Uuid {
+        self.room_id
+    }
+
+    pub fn locked_types(&self) -> HashMap<String, bool> {
+        self.locked_types
+            .as_object()
+            .into_iter()
+            .flatten()
+            .map(|(k, v)| (k.to_owned(), v.as_bool().unwrap_or(false)))
+            .collect()
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug)]
+pub struct InsertQuery {
+    room_id: Uuid,
+    locked_types: HashMap<String, bool>,
+    apply_at: Option<DateTime<Utc>>,
+    before_close: Option<i32>,
+    created_by: AgentId,
+}
+
+impl InsertQuery {
+    pub fn at(
+        room_id: Uuid,
+        locked_types: HashMap<String, bool>,
+        apply_at: DateTime<Utc>,
+        created_by: AgentId,
+    ) -> Self {
+        Self {
+            room_id,
+            locked_types,
+            apply_at: Some(apply_at),
+            before_close: None,
+            created_by,
+        }
+    }
+
+    pub fn before_close(
+        room_id: Uuid,
+        locked_types: HashMap<String, bool>,
+        before_close: i32,
+        created_by: AgentId,
+    ) -> Self {
+        Self {
+            room_id,
+            locked_types,
+            apply_at: None,
+            before_close: Some(before_close),
+            created_by,
+        }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Object> {
+        let locked_types = serde_json::to_value(self.locked_types).unwrap();
+
+        sqlx::query_as!(
+            Object,
+            r#"
+            INSERT INTO room_lock_schedule (room_id, locked_types, apply_at, before_close, created_by)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING
+                id,
+                room_id,
+                locked_types,
+                apply_at,
+                before_close,
+                created_by AS "created_by!: AgentId",
+                created_at
+            "#,
+            self.room_id,
+            locked_types,
+            self.apply_at,
+            self.before_close,
+            self.created_by as AgentId,
+        )
+        .fetch_one(conn)
+        .await
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Deletes schedules which are due and returns them in the order they were created.
+#[derive(Debug)]
+pub struct TakeDueQuery {
+    limit: i64,
+}
+
+impl TakeDueQuery {
+    pub fn new(limit: i64) -> Self {
+        Self { limit }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Vec<Object>> {
+        let mut schedules = sqlx::query_as!(
+            Object,
+            r#"
+            DELETE FROM room_lock_schedule
+            WHERE id IN (
+                SELECT s.id
+                FROM room_lock_schedule AS s
+                INNER JOIN room AS r
+                ON r.id = s.room_id
+                WHERE COALESCE(s.apply_at, UPPER(r.time) - MAKE_INTERVAL(secs => s.before_close)) <= NOW()
+                ORDER BY s.created_at
+                LIMIT $1
+            )
+            RETURNING
+                id,
+                room_id,
+                locked_types,
+                apply_at,
+                before_close,
+                created_by AS "created_by!: AgentId",
+                created_at
+            "#,
+            self.limit,
+        )
+        .fetch_all(conn)
+        .await?;
+
+        // `RETURNING` doesn't keep the order of the subquery.
+        schedules.sort_by_key(|s| s.created_at);
+        Ok(schedules)
+    }
+}
@@ -0,0 +1,204 @@
+use std::time::Duration as StdDuration;
+
+use chrono::{Duration, Utc};
+use sqlx::postgres::PgConnection;
+use svc_agent::AgentId;
+use uuid::Uuid;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Places taken in a room with limited capacity from the point of view of an entering agent.
+#[derive(Debug)]
+pub struct Occupancy {
+    /// Agents in the room except the entering one.
+    pub agents: i64,
+    /// Places held for agents admitted from the queue which haven't entered yet.
+    pub reserved: i64,
+    /// Agents waiting in the queue except the entering one.
+    pub waiting: i64,
+    /// Whether the entering agent has been admitted from the queue.
+    pub admitted: bool,
+}
+
+impl Occupancy {
+    pub fn free_places(&self, capacity: i32) -> i64 {
+        (capacity as i64 - self.agents - self.reserved).max(0)
+    }
+}
+
+#[derive(Debug)]
+pub struct OccupancyQuery {
+    room_id: Uuid,
+    agent_id: AgentId,
+    admission_timeout: StdDuration,
+}
+
+impl OccupancyQuery {
+    pub fn new(room_id: Uuid, agent_id: AgentId, admission_timeout: StdDuration) -> Self {
+        Self {
+            room_id,
+            agent_id,
+            admission_timeout,
+        }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Occupancy> {
+        let admitted_since = Utc::now()
+            - Duration::from_std(self.admission_timeout).unwrap_or_else(|_| Duration::zero());
+
+        sqlx::query_as!(
+            Occupancy,
+            r#"
+            SELECT
+                (
+                    SELECT COUNT(*)
+                    FROM agent
+                    WHERE room_id = $1
+                    AND   agent_id <> $2
+                ) AS "agents!",
+                (
+                    SELECT COUNT(*)
+                    FROM room_queue
+                    WHERE room_id = $1
+                    AND   agent_id <> $2
+                    AND   admitted_at > $3
+                ) AS "reserved!",
+                (
+                    SELECT COUNT(*)
+                    FROM room_queue
+                    WHERE room_id = $1
+                    AND   agent_id <> $2
+                    AND   admitted_at IS NULL
+                ) AS "waiting!",
+                EXISTS(
+                    SELECT 1
+                    FROM room_queue
+                    WHERE room_id = $1
+                    AND   agent_id = $2
+                    AND   admitted_at > $3
+                ) AS "admitted!"
+            "#,
+            self.room_id,
+            self.agent_id as AgentId,
+            admitted_since,
+        )
+        .fetch_one(conn)
+        .await
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Puts the agent at the end of the room's queue and returns its position starting from 1.
+///
+/// An agent which is already waiting keeps its place. The one whose admission has expired
+/// goes to the end again.
+#[derive(Debug)]
+pub struct EnqueueQuery {
+    room_id: Uuid,
+    agent_id: AgentId,
+}
+
+impl EnqueueQuery {
+    pub fn new(room_id: Uuid, agent_id: AgentId) -> Self {
+        Self { room_id, agent_id }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<i64> {
+        sqlx::query_scalar!(
+            r#"
+            WITH entry AS (
+                INSERT INTO room_queue (room_id, agent_id)
+                VALUES ($1, $2)
+                ON CONFLICT (room_id, agent_id) DO UPDATE
+                SET created_at = (CASE WHEN room_queue.admitted_at IS NULL
+                                       THEN room_queue.created_at
+                                       ELSE NOW()
+                                  END),
+                    admitted_at = NULL
+                RETURNING created_at
+            )
+            SELECT (
+                SELECT COUNT(*)
+                FROM room_queue
+                WHERE room_id = $1
+                AND   agent_id <> $2
+                AND   admitted_at IS NULL
+                AND   created_at <= entry.created_at
+            ) + 1 AS "position!"
+            FROM entry
+            "#,
+            self.room_id,
+            self.agent_id as AgentId,
+        )
+        .fetch_one(conn)
+        .await
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Admits up to `limit` agents from the head of the room's queue.
+#[derive(Debug)]
+pub struct AdmitQuery {
+    room_id: Uuid,
+    limit: i64,
+}
+
+impl AdmitQuery {
+    pub fn new(room_id: Uuid, limit: i64) -> Self {
+        Self { room_id, limit }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Vec<AgentId>> {
+        sqlx::query_scalar!(
+            r#"
+            UPDATE room_queue
+            SET admitted_at = NOW()
+            WHERE room_id = $1
+            AND   agent_id IN (
+                SELECT agent_id
+                FROM room_queue
+                WHERE room_id = $1
+                AND   admitted_at IS NULL
+                ORDER BY created_at
+                LIMIT $2
+            )
+            RETURNING agent_id AS "agent_id!: AgentId"
+            "#,
+            self.room_id,
+            self.limit,
+        )
+        .fetch_all(conn)
+        .await
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug)]
+pub struct DeleteQuery {
+    room_id: Uuid,
+    agent_id: AgentId,
+}
+
+impl DeleteQuery {
+    pub fn new(room_id: Uuid, agent_id: AgentId) -> Self {
+        Self { room_id, agent_id }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<usize> {
+        sqlx::query!(
+            r#"
+            DELETE FROM room_queue
+            WHERE room_id = $1
+            AND   agent_id = $2
+            "#,
+            self.room_id,
+            self.agent_id as AgentId,
+        )
+        .execute(conn)
+        .await
+        .map(|r| r.rows_affected() as usize)
+    }
+}
@@ -0,0 +1,132 @@
+use chrono::{DateTime, Utc};
+use sqlx::postgres::PgConnection;
+use uuid::Uuid;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Number of labels of the set in the room and the creation time of its latest event.
+///
+/// Sets and their `updated_at` are maintained by a trigger on event insert and approval
+/// while `label_count` is computed on listing so that deletions and removals are taken into account.
+#[derive(Debug, sqlx::FromRow)]
+pub struct Object {
+    set: String,
+    label_count: i64,
+    updated_at: DateTime<Utc>,
+}
+
+impl Object {
+    pub fn set(&self) -> &str {
+        &self.set
+    }
+
+    pub fn label_count(&self) -> i64 {
+        self.label_count
+    }
+
+    pub fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Sets of the room ordered by name.
+///
+/// A label is counted when its latest approved and not deleted event isn't a removal.
+/// Events without a label are counted on their own like in `CountSinceQuery`.
+#[derive(Debug)]
+pub struct ListQuery {
+    room_id: Uuid,
+}
+
+impl ListQuery {
+    pub fn new(room_id: Uuid) -> Self {
+        Self { room_id }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Vec<Object>> {
+        sqlx::query_as!(
+            Object,
+            r#"
+            SELECT
+                sh.set,
+                COALESCE(lc.label_count, 0) AS "label_count!",
+                sh.updated_at
+            FROM set_head AS sh
+            LEFT JOIN (
+                SELECT set, COUNT(*) FILTER (WHERE NOT removed) AS label_count
+                FROM (
+                    SELECT DISTINCT ON (set, COALESCE(label, id::text)) set, removed
+                    FROM event
+                    WHERE deleted_at IS NULL
+                    AND   room_id = $1
+                    AND   moderation_status = 'approved'
+                    ORDER BY set, COALESCE(label, id::text), occurred_at DESC, created_at DESC
+                ) AS latest
+                GROUP BY set
+            ) AS lc
+            ON lc.set = sh.set
+            WHERE sh.room_id = $1
+            ORDER BY sh.set
+            "#,
+            self.room_id,
+        )
+        .fetch_all(conn)
+        .await
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Fills `set_head` for a batch of rooms taken in `id` order after `after_room_id`.
+///
+/// Returns ids of the processed rooms so the scan can be resumed after the last one.
+/// Existing rows only get their `updated_at` moved forward so it's safe to run along with the trigger.
+#[derive(Debug)]
+pub struct BackfillQuery {
+    after_room_id: Option<Uuid>,
+    limit: i64,
+}
+
+impl BackfillQuery {
+    pub fn new(after_room_id: Option<Uuid>, limit: i64) -> Self {
+        Self {
+            after_room_id,
+            limit,
+        }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Vec<Uuid>> {
+        sqlx::query_scalar!(
+            r#"
+            WITH batch AS (
+                SELECT id
+                FROM room
+                WHERE ($1::UUID IS NULL OR id > $1)
+                ORDER BY id
+                LIMIT $2
+            ),
+            upserted AS (
+                INSERT INTO set_head (room_id, set, updated_at)
+                SELECT room_id, set, MAX(created_at)
+                FROM event
+                WHERE room_id IN (SELECT id FROM batch)
+                AND   deleted_at IS NULL
+                AND   moderation_status = 'approved'
+                GROUP BY room_id, set
+                ORDER BY room_id, set
+                ON CONFLICT (room_id, set) DO UPDATE
+                SET updated_at = GREATEST(set_head.updated_at, EXCLUDED.updated_at)
+            )
+            SELECT id AS "id!"
+            FROM batch
+            ORDER BY id
+            "#,
+            self.after_room_id,
+            self.limit,
+        )
+        .fetch_all(conn)
+        .await
+    }
+}
@@ -0,0 +1,186 @@
+use chrono::{serde::ts_seconds, DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use sqlx::postgres::PgConnection;
+use svc_agent::AgentId;
+use uuid::Uuid;
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "task_status")]
+pub enum Status {
+    #[sqlx(rename = "running")]
+    Running,
+    #[sqlx(rename = "succeeded")]
+    Succeeded,
+    #[sqlx(rename = "failed")]
+    Failed,
+}
+
+/// An asynchronous task started by a request, e.g. a room adjustment, and the notification
+/// it has finished with.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Object {
+    id: Uuid,
+    kind: String,
+    room_id: Uuid,
+    status: Status,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    notification: Option<JsonValue>,
+    created_by: AgentId,
+    #[serde(with = "ts_seconds")]
+    created_at: DateTime<Utc>,
+    #[serde(
+        default,
+        with = "chrono::serde::ts_seconds_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    finished_at: Option<DateTime<Utc>>,
+}
+
+impl Object {
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+
+    pub fn room_id(&self) -> Uuid {
+        self.room_id
+    }
+
+    #[cfg(test)]
+    pub fn status(&self) -> Status {
+        self.status
+    }
+
+    #[cfg(test)]
+    pub fn notification(&self) -> Option<&JsonValue> {
+        self.notification.as_ref()
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug)]
+pub struct FindQuery {
+    id: Uuid,
+}
+
+impl FindQuery {
+    pub fn new(id: Uuid) -> Self {
+        Self { id }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Option<Object>> {
+        sqlx::query_as!(
+            Object,
+            r#"
+            SELECT
+                id,
+                kind,
+                room_id,
+                status AS "status!: Status",
+                notification,
+                created_by AS "created_by!: AgentId",
+                created_at,
+                finished_at
+            FROM task
+            WHERE id = $1
+            "#,
+            self.id,
+        )
+        .fetch_optional(conn)
+        .await
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug)]
+pub struct InsertQuery {
+    kind: String,
+    room_id: Uuid,
+    created_by: AgentId,
+}
+
+impl InsertQuery {
+    pub fn new(kind: &str, room_id: Uuid, created_by: AgentId) -> Self {
+        Self {
+            kind: kind.to_owned(),
+            room_id,
+            created_by,
+        }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Object> {
+        sqlx::query_as!(
+            Object,
+            r#"
+            INSERT INTO task (kind, room_id, created_by)
+            VALUES ($1, $2, $3)
+            RETURNING
+                id,
+                kind,
+                room_id,
+                status AS "status!: Status",
+                notification,
+                created_by AS "created_by!: AgentId",
+                created_at,
+                finished_at
+            "#,
+            self.kind,
+            self.room_id,
+            self.created_by as AgentId,
+        )
+        .fetch_one(conn)
+        .await
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Records the outcome of a running task along with its notification payload.
+#[derive(Debug)]
+pub struct FinishQuery {
+    id: Uuid,
+    status: Status,
+    notification: JsonValue,
+}
+
+impl FinishQuery {
+    pub fn new(id: Uuid, status: Status, notification: JsonValue) -> Self {
+        Self {
+            id,
+            status,
+            notification,
+        }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Option<Object>> {
+        sqlx::query_as!(
+            Object,
+            r#"
+            UPDATE task
+            SET status = $2,
+                notification = $3,
+                finished_at = NOW()
+            WHERE id = $1
+            RETURNING
+                id,
+                kind,
+                room_id,
+                status AS "status!: Status",
+                notification,
+                created_by AS "created_by!: AgentId",
+                created_at,
+                finished_at
+            "#,
+            self.id,
+            self.status as Status,
+            self.notification,
+        )
+        .fetch_optional(conn)
+        .await
+    }
+}
@@ -0,0 +1,219 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::postgres::PgConnection;
+use svc_agent::AccountId;
+use uuid::Uuid;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// A ban of the account in all classrooms of the audience.
+#[derive(Debug, sqlx::FromRow, Serialize)]
+pub struct Object {
+    #[serde(skip_serializing)]
+    #[allow(dead_code)]
+    id: Uuid,
+    audience: String,
+    account_id: AccountId,
+    reason: Option<String>,
+    created_by: AccountId,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+pub struct InsertQuery {
+    audience: String,
+    account_id: AccountId,
+    reason: Option<String>,
+    created_by: AccountId,
+}
+
+impl InsertQuery {
+    pub fn new(audience: String, account_id: AccountId, created_by: AccountId) -> Self {
+        Self {
+            audience,
+            account_id,
+            reason: None,
+            created_by,
+        }
+    }
+
+    pub fn reason(&mut self, reason: &str) {
+        self.reason = Some(reason.to_owned());
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Object> {
+        sqlx::query_as!(
+            Object,
+            r#"
+            INSERT INTO tenant_ban (audience, account_id, reason, created_by)
+            VALUES ($1, $2, $3, $4) ON CONFLICT (account_id, audience) DO UPDATE
+            SET reason = EXCLUDED.reason
+            RETURNING
+                id,
+                audience,
+                account_id AS "account_id!: AccountId",
+                reason,
+                created_by AS "created_by!: AccountId",
+                created_at
+            "#,
+            self.audience,
+            self.account_id as AccountId,
+            self.reason,
+            self.created_by as AccountId,
+        )
+        .fetch_one(conn)
+        .await
+    }
+}
+
+/// Finds a ban of the account in the audience the classroom belongs to.
+#[derive(Debug)]
+pub struct ClassroomFindQuery {
+    account_id: AccountId,
+    classroom_id: Uuid,
+}
+
+impl ClassroomFindQuery {
+    pub fn new(account_id: AccountId, classroom_id: Uuid) -> Self {
+        Self {
+            account_id,
+            classroom_id,
+        }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Option<Object>> {
+        sqlx::query_as!(
+            Object,
+            r#"
+            SELECT
+                id,
+                audience,
+                account_id AS "account_id!: AccountId",
+                reason,
+                created_by AS "created_by!: AccountId",
+                created_at
+            FROM tenant_ban
+            WHERE account_id = $1 AND audience IN (
+                SELECT audience FROM room
+                WHERE classroom_id = $2
+            )
+            LIMIT 1
+            "#,
+            self.account_id as AccountId,
+            self.classroom_id,
+        )
+        .fetch_optional(conn)
+        .await
+    }
+}
+
+#[derive(Debug)]
+pub struct DeleteQuery {
+    audience: String,
+    account_id: AccountId,
+}
+
+impl DeleteQuery {
+    pub fn new(audience: String, account_id: AccountId) -> Self {
+        Self {
+            audience,
+            account_id,
+        }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<usize> {
+        sqlx::query!(
+            r#"
+            DELETE FROM tenant_ban
+            WHERE account_id = $1
+            AND   audience = $2
+            "#,
+            self.account_id as AccountId,
+            self.audience,
+        )
+        .execute(conn)
+        .await
+        .map(|r| r.rows_affected() as usize)
+    }
+}
+
+#[derive(Debug)]
+pub struct ListQuery {
+    audience: String,
+}
+
+impl ListQuery {
+    pub fn new(audience: String) -> Self {
+        Self { audience }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Vec<Object>> {
+        sqlx::query_as!(
+            Object,
+            r#"
+            SELECT
+                id,
+                audience,
+                account_id AS "account_id!: AccountId",
+                reason,
+                created_by AS "created_by!: AccountId",
+                created_at
+            FROM tenant_ban
+            WHERE audience = $1
+            ORDER BY created_at
+            "#,
+            self.audience,
+        )
+        .fetch_all(conn)
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Bound;
+
+    use super::*;
+    use crate::db::room::ClassType;
+    use crate::test_helpers::prelude::*;
+
+    #[tokio::test]
+    async fn find_ban_in_any_classroom_of_audience() {
+        let db = TestDb::new().await;
+        let mut conn = db.get_conn().await;
+
+        let banned_agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let moderator = TestAgent::new("web", "moderator", USR_AUDIENCE);
+
+        // Use a separate audience not to ban the account in other tests.
+        let audience = format!("{}.example.org", Uuid::new_v4());
+
+        let room = factory::Room::new(Uuid::new_v4(), ClassType::Webinar)
+            .audience(&audience)
+            .time((Bound::Included(Utc::now()), Bound::Unbounded))
+            .insert(&mut conn)
+            .await;
+
+        InsertQuery::new(
+            audience,
+            banned_agent.account_id().to_owned(),
+            moderator.account_id().to_owned(),
+        )
+        .execute(&mut conn)
+        .await
+        .expect("Failed to insert tenant ban");
+
+        let ban =
+            ClassroomFindQuery::new(banned_agent.account_id().to_owned(), room.classroom_id())
+                .execute(&mut conn)
+                .await
+                .expect("Ban query failed");
+        assert!(ban.is_some());
+
+        let ban = ClassroomFindQuery::new(moderator.account_id().to_owned(), room.classroom_id())
+            .execute(&mut conn)
+            .await
+            .expect("Ban query failed");
+        assert!(ban.is_none());
+    }
+}
@@ -0,0 +1,73 @@
+use chrono::{serde::ts_milliseconds, DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use sqlx::postgres::PgConnection;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// A whiteboard page as of its latest `page` event.
+///
+/// Rows are maintained by a trigger on event insert.
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+#[schema(as = WhiteboardPage)]
+pub struct Object {
+    /// The page label which is the set of its `draw` events.
+    label: String,
+    /// Pages are ordered by position.
+    position: f64,
+    #[schema(value_type = Object)]
+    data: JsonValue,
+    #[serde(with = "ts_milliseconds")]
+    #[schema(value_type = i64)]
+    created_at: DateTime<Utc>,
+    #[serde(with = "ts_milliseconds")]
+    #[schema(value_type = i64)]
+    updated_at: DateTime<Utc>,
+}
+
+impl Object {
+    #[cfg(test)]
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Pages of the room which are not removed, in order.
+#[derive(Debug)]
+pub struct ListQuery {
+    room_id: Uuid,
+}
+
+impl ListQuery {
+    pub fn new(room_id: Uuid) -> Self {
+        Self { room_id }
+    }
+
+    pub async fn execute(self, conn: &mut PgConnection) -> sqlx::Result<Vec<Object>> {
+        sqlx::query_as!(
+            Object,
+            r#"
+            SELECT
+                p.label,
+                p.position AS "position!",
+                p.data,
+                p.created_at,
+                p.updated_at
+            FROM whiteboard_page AS p
+            INNER JOIN event AS e
+            ON e.id = p.event_id
+            WHERE p.room_id = $1
+                AND NOT p.removed
+                AND e.deleted_at IS NULL
+            ORDER BY p.position, p.created_at, p.label
+            "#,
+            self.room_id,
+        )
+        .fetch_all(conn)
+        .await
+    }
+}
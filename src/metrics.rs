@@ -1,74 +1,203 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
 use enum_iterator::{all, Sequence};
 use futures::Future;
 use parking_lot::RwLock;
 use prometheus::{
     Histogram, HistogramOpts, HistogramTimer, HistogramVec, IntCounter, IntCounterVec, IntGauge,
-    Opts, Registry,
+    IntGaugeVec, Opts, Registry,
 };
 use serde::Serialize;
 use tracing::error;
 
 use crate::app::endpoint;
 use crate::app::error::ErrorKind;
+use crate::db::event::VacuumStats;
 
 #[allow(clippy::enum_variant_names)]
 #[derive(Clone, Copy, Eq, PartialEq, Hash, Serialize, Sequence)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryKey {
+    AdjustmentComparisonInsertQuery,
+    AdjustmentFindQuery,
+    AdjustmentFinishQuery,
     AdjustmentInsertQuery,
     AgentDeleteQuery,
     AgentFindWithBanQuery,
     AgentInsertQuery,
     AgentListQuery,
+    AgentReadyExistsQuery,
+    AgentReadyListQuery,
+    AgentUpdatePresenceMetaQuery,
     AgentUpdateQuery,
+    AttachmentListQuery,
     BanDeleteQuery,
+    BanFindQuery,
     BanInsertQuery,
     BanListQuery,
+    ChangeCountQuery,
     ChangeDeleteQuery,
     ChangeFindWithRoomQuery,
     ChangeInsertQuery,
     ChangeListQuery,
+    ChangeMergeConflictListQuery,
+    ChangeMergeQuery,
+    ChangeSummaryQuery,
+    CrdtStateQuery,
     EditionCloneEventsQuery,
+    EditionCommitJobClaimQuery,
+    EditionCommitJobFindQuery,
+    EditionCommitJobInsertQuery,
+    EditionCommitJobUpdateQuery,
     EditionCommitTxnCommit,
     EditionDeleteQuery,
     EditionFindWithRoomQuery,
     EditionInsertQuery,
     EditionListQuery,
+    EditionMergeTxnCommit,
+    EventAccountListQuery,
+    EventAnonymizeAccountQuery,
+    EventAttributeUpdateQuery,
+    EventChecksumQuery,
+    EventCountSinceQuery,
+    EventDeleteByAccountQuery,
     EventDeleteQuery,
+    EventDiffQuery,
     EventDumpQuery,
+    EventHistoryQuery,
     EventInsertQuery,
+    EventInsertSnapshotQuery,
+    EventLatestEventQuery,
+    EventLegacyDrawListQuery,
     EventListQuery,
+    EventModerationUpdateQuery,
     EventOriginalEventQuery,
+    EventPinnedListQuery,
+    EventReencodeQuery,
+    EventRoomBatchDeleteQuery,
+    EventRoomVersionQuery,
+    EventSeqQuery,
+    EventSinceQuery,
+    EventThinQuery,
+    EventTranscriptQuery,
+    EventVacuumCountQuery,
     EventVacuumQuery,
+    HandQueueListQuery,
+    HandQueueLowerQuery,
+    HandQueuePopQuery,
+    HandQueueRaiseQuery,
+    NatsDeadLetterDeleteQuery,
+    NatsDeadLetterInsertQuery,
+    NatsDeadLetterListQuery,
+    PollCloseDueQuery,
+    PollCloseQuery,
+    PollFindQuery,
+    PollInsertQuery,
+    PollResultsQuery,
+    PollVoteQuery,
+    QuestionAnswerQuery,
+    QuestionFindQuery,
+    QuestionInsertQuery,
+    QuestionListQuery,
+    QuestionUpvoteQuery,
+    RetentionRuleDeleteQuery,
+    RetentionRuleInsertQuery,
+    RetentionRuleListQuery,
+    RoomActivityListQuery,
     RoomAdjustCloneEventsQuery,
+    RoomAdjustCloneRangesQuery,
+    RoomAutoCloseQuery,
+    RoomDeleteQuery,
     RoomFindQuery,
+    RoomInactivityAlertQuery,
     RoomInsertQuery,
+    RoomListQuery,
+    RoomLockScheduleInsertQuery,
+    RoomLockScheduleTakeDueQuery,
+    RoomQueueAdmitQuery,
+    RoomQueueDeleteQuery,
+    RoomQueueEnqueueQuery,
+    RoomQueueOccupancyQuery,
+    RoomSearchQuery,
+    RoomStaleDerivedListQuery,
     RoomUpdateQuery,
+    RoomUpdateSettingsQuery,
+    SetHeadBackfillQuery,
+    SetHeadListQuery,
     StateTotalCountQuery,
     StateQuery,
+    TaskFindQuery,
+    TaskFinishQuery,
+    TaskInsertQuery,
+    TenantBanDeleteQuery,
+    TenantBanInsertQuery,
+    TenantBanListQuery,
+    WhiteboardPageListQuery,
 }
 
 pub struct Metrics {
     pub request_duration: RwLock<HashMap<String, Option<Histogram>>>,
     pub request_duration_vec: HistogramVec,
     pub authorization_time: Histogram,
+    pub authz_decision_cache_hit: IntCounter,
+    pub authz_decision_cache_miss: IntCounter,
     pub db_duration: HashMap<QueryKey, Histogram>,
     pub app_result_ok: IntCounter,
     pub app_results_errors: HashMap<ErrorKind, IntCounter>,
+    pub request_failures: IntCounterVec,
     pub mqtt_reconnection: IntCounter,
     pub mqtt_disconnect: IntCounter,
     pub mqtt_connection_error: IntCounter,
+    pub nats_messages: IntCounterVec,
+    pub nats_consumer_lag: HistogramVec,
+    pub vacuum_deleted_events: IntCounterVec,
+    pub adjust_cloned_events: IntCounter,
+    pub adjust_cloned_chunks: IntCounter,
+    pub adjust_phase_duration: HistogramVec,
+    pub adjust_shadow_matched: IntCounter,
+    pub adjust_shadow_mismatched: IntCounter,
+    pub derived_room_cleanup_rooms: IntCounter,
+    pub derived_room_cleanup_events: IntCounter,
+    pub notification_payload_size: HistogramVec,
+    pub oversized_notifications: IntCounterVec,
     pub total_requests: IntCounter,
     pub running_requests_total: IntGauge,
+    pub mqtt_pending_messages: IntGauge,
+    pub mqtt_rejected_messages: IntCounter,
+    pub nats_pending_requests: IntGauge,
+    pub nats_rejected_requests: IntCounter,
+    pub webhook_deliveries: IntCounterVec,
+    pub db_pool_size: IntGaugeVec,
+    pub db_pool_idle: IntGaugeVec,
+    pub db_pool_waits: IntCounterVec,
+    pub db_pool_acquire_duration: HistogramVec,
+    pub draw_event_rejects: IntCounterVec,
+    pub created_events: IntCounterVec,
 }
 
 impl Metrics {
     pub fn new(registry: &Registry) -> anyhow::Result<Self> {
-        let request_duration = HistogramVec::new(
-            HistogramOpts::new("request_duration", "Request duration"),
-            &["method"],
+        Self::with_request_buckets(registry, None)
+    }
+
+    /// Uses `request_buckets` in seconds for request duration histograms instead of the default ones.
+    pub fn with_request_buckets(
+        registry: &Registry,
+        request_buckets: Option<Vec<f64>>,
+    ) -> anyhow::Result<Self> {
+        let mut request_duration_opts = HistogramOpts::new("request_duration", "Request duration");
+
+        if let Some(buckets) = request_buckets {
+            request_duration_opts = request_duration_opts.buckets(buckets);
+        }
+
+        let request_duration = HistogramVec::new(request_duration_opts, &["method"])?;
+        let request_failures = IntCounterVec::new(
+            Opts::new(
+                "request_failures",
+                "Failed requests by method and error kind",
+            ),
+            &["method", "kind"],
         )?;
         let db_duration = HistogramVec::new(
             HistogramOpts::new("db_duration", "DB duration"),
@@ -79,21 +208,159 @@ impl Metrics {
         let total_requests = IntCounter::new("incoming_requests_total", "Total requests")?;
         let running_requests_total =
             IntGauge::new("running_requests_total", "Total running requests")?;
+        let mqtt_pending_messages = IntGauge::new(
+            "mqtt_pending_messages",
+            "Mqtt messages waiting for handling",
+        )?;
+        let mqtt_rejected_messages = IntCounter::new(
+            "mqtt_rejected_messages",
+            "Mqtt messages rejected because of too many pending ones",
+        )?;
+        let nats_pending_requests = IntGauge::new(
+            "nats_pending_requests",
+            "Nats requests waiting for handling",
+        )?;
+        let nats_rejected_requests = IntCounter::new(
+            "nats_rejected_requests",
+            "Nats requests rejected because of too many pending ones",
+        )?;
         let mqtt_errors = IntCounterVec::new(
             Opts::new("mqtt_messages", "Mqtt message types"),
             &["status"],
         )?;
         let authorization_time =
             Histogram::with_opts(HistogramOpts::new("auth_time", "Authorization time"))?;
+        let authz_decision_cache = IntCounterVec::new(
+            Opts::new(
+                "authz_decision_cache",
+                "Authorization decision cache lookups",
+            ),
+            &["status"],
+        )?;
+        let nats_messages = IntCounterVec::new(
+            Opts::new("nats_messages", "Nats messages by handling status"),
+            &["subject", "status"],
+        )?;
+        let nats_consumer_lag = HistogramVec::new(
+            HistogramOpts::new("nats_consumer_lag", "Nats message lag in seconds").buckets(vec![
+                0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0, 300.0, 900.0, 3600.0,
+            ]),
+            &["subject"],
+        )?;
+        let vacuum_deleted_events = IntCounterVec::new(
+            Opts::new("vacuum_deleted_events", "Events deleted by vacuum"),
+            &["category"],
+        )?;
+        let adjust_cloned = IntCounterVec::new(
+            Opts::new("adjust_cloned", "Events cloned by room adjustment"),
+            &["unit"],
+        )?;
+        let derived_room_cleanup = IntCounterVec::new(
+            Opts::new(
+                "derived_room_cleanup",
+                "Stale derived rooms and their events deleted",
+            ),
+            &["unit"],
+        )?;
+        let adjust_shadow = IntCounterVec::new(
+            Opts::new(
+                "adjust_shadow",
+                "Room adjustments compared with the shadow pipeline",
+            ),
+            &["result"],
+        )?;
+        let notification_payload_size = HistogramVec::new(
+            HistogramOpts::new(
+                "notification_payload_size",
+                "Outgoing notification payload size in bytes",
+            )
+            .buckets(vec![
+                256.0, 1024.0, 4096.0, 16384.0, 65536.0, 262144.0, 1048576.0,
+            ]),
+            &["label"],
+        )?;
+        let oversized_notifications = IntCounterVec::new(
+            Opts::new(
+                "oversized_notifications",
+                "Notifications over the payload size limit",
+            ),
+            &["label", "action"],
+        )?;
+        let adjust_phase_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "adjust_phase_duration",
+                "Room adjustment phase duration in seconds",
+            )
+            .buckets(vec![
+                0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0, 300.0, 900.0, 3600.0,
+            ]),
+            &["phase"],
+        )?;
+        let webhook_deliveries = IntCounterVec::new(
+            Opts::new("webhook_deliveries", "Webhook delivery attempts by status"),
+            &["event", "status"],
+        )?;
+        let db_pool_size =
+            IntGaugeVec::new(Opts::new("db_pool_size", "Open DB connections"), &["pool"])?;
+        let db_pool_idle =
+            IntGaugeVec::new(Opts::new("db_pool_idle", "Idle DB connections"), &["pool"])?;
+        let db_pool_waits = IntCounterVec::new(
+            Opts::new(
+                "db_pool_waits",
+                "DB connection acquisitions started without idle connections in the pool",
+            ),
+            &["pool"],
+        )?;
+        let db_pool_acquire_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "db_pool_acquire_duration",
+                "DB connection acquisition time in seconds",
+            ),
+            &["pool"],
+        )?;
         registry.register(Box::new(mqtt_errors.clone()))?;
         registry.register(Box::new(request_duration.clone()))?;
         registry.register(Box::new(db_duration.clone()))?;
         registry.register(Box::new(request_stats.clone()))?;
+        registry.register(Box::new(request_failures.clone()))?;
         registry.register(Box::new(total_requests.clone()))?;
         registry.register(Box::new(running_requests_total.clone()))?;
+        registry.register(Box::new(mqtt_pending_messages.clone()))?;
+        registry.register(Box::new(mqtt_rejected_messages.clone()))?;
+        registry.register(Box::new(nats_pending_requests.clone()))?;
+        registry.register(Box::new(nats_rejected_requests.clone()))?;
         registry.register(Box::new(authorization_time.clone()))?;
+        registry.register(Box::new(authz_decision_cache.clone()))?;
+        registry.register(Box::new(nats_messages.clone()))?;
+        registry.register(Box::new(nats_consumer_lag.clone()))?;
+        registry.register(Box::new(vacuum_deleted_events.clone()))?;
+        registry.register(Box::new(adjust_cloned.clone()))?;
+        registry.register(Box::new(adjust_phase_duration.clone()))?;
+        registry.register(Box::new(adjust_shadow.clone()))?;
+        registry.register(Box::new(derived_room_cleanup.clone()))?;
+        registry.register(Box::new(notification_payload_size.clone()))?;
+        registry.register(Box::new(oversized_notifications.clone()))?;
+        let draw_event_rejects = IntCounterVec::new(
+            Opts::new("draw_event_rejects", "Invalid draw events by reason"),
+            &["reason"],
+        )?;
+        registry.register(Box::new(webhook_deliveries.clone()))?;
+        registry.register(Box::new(db_pool_size.clone()))?;
+        registry.register(Box::new(db_pool_idle.clone()))?;
+        registry.register(Box::new(db_pool_waits.clone()))?;
+        registry.register(Box::new(db_pool_acquire_duration.clone()))?;
+        registry.register(Box::new(draw_event_rejects.clone()))?;
+        let created_events = IntCounterVec::new(
+            Opts::new("created_events", "Events created with event.create by kind"),
+            &["kind"],
+        )?;
+        registry.register(Box::new(created_events.clone()))?;
         Ok(Self {
             authorization_time,
+            authz_decision_cache_hit: authz_decision_cache
+                .get_metric_with_label_values(&["hit"])?,
+            authz_decision_cache_miss: authz_decision_cache
+                .get_metric_with_label_values(&["miss"])?,
             request_duration: RwLock::new(HashMap::new()),
             request_duration_vec: request_duration,
             total_requests,
@@ -106,11 +373,38 @@ impl Metrics {
                     ))
                 })
                 .collect::<anyhow::Result<_>>()?,
+            request_failures,
             running_requests_total,
+            mqtt_pending_messages,
+            mqtt_rejected_messages,
+            nats_pending_requests,
+            nats_rejected_requests,
             mqtt_connection_error: mqtt_errors
                 .get_metric_with_label_values(&["connection_error"])?,
             mqtt_disconnect: mqtt_errors.get_metric_with_label_values(&["disconnect"])?,
             mqtt_reconnection: mqtt_errors.get_metric_with_label_values(&["reconnect"])?,
+            nats_messages,
+            nats_consumer_lag,
+            vacuum_deleted_events,
+            adjust_cloned_events: adjust_cloned.get_metric_with_label_values(&["events"])?,
+            adjust_cloned_chunks: adjust_cloned.get_metric_with_label_values(&["chunks"])?,
+            adjust_phase_duration,
+            adjust_shadow_matched: adjust_shadow.get_metric_with_label_values(&["matched"])?,
+            adjust_shadow_mismatched: adjust_shadow
+                .get_metric_with_label_values(&["mismatched"])?,
+            derived_room_cleanup_rooms: derived_room_cleanup
+                .get_metric_with_label_values(&["rooms"])?,
+            derived_room_cleanup_events: derived_room_cleanup
+                .get_metric_with_label_values(&["events"])?,
+            notification_payload_size,
+            oversized_notifications,
+            webhook_deliveries,
+            db_pool_size,
+            db_pool_idle,
+            db_pool_waits,
+            db_pool_acquire_duration,
+            draw_event_rejects,
+            created_events,
             db_duration: all::<QueryKey>()
                 .map(|kind| {
                     Ok((
@@ -176,17 +470,148 @@ impl Metrics {
     }
 
     /// This is helpful in MQTT handlers.
-    pub fn observe_app_result(&self, result: &endpoint::RequestResult) {
+    pub fn observe_app_result(&self, method: &str, result: &endpoint::RequestResult) {
         match result {
             Ok(_) => {
                 self.observe_app_ok();
             }
             Err(err) => {
-                self.observe_app_error(&err.error_kind());
+                let kind = err.error_kind();
+                self.observe_app_error(&kind);
+                self.observe_request_failure(method, &kind);
+            }
+        }
+    }
+
+    /// Counts a failed request of the method by the error kind.
+    pub fn observe_request_failure(&self, method: &str, kind: &ErrorKind) {
+        match self
+            .request_failures
+            .get_metric_with_label_values(&[method, kind.kind()])
+        {
+            Ok(m) => m.inc(),
+            Err(err) => error!("Bad metric: {:?}", err),
+        }
+    }
+
+    /// Counts a nats message by the subject pattern and its handling status,
+    /// one of `received`, `ok`, `transient_failure` or `permanent_failure`.
+    pub fn observe_nats_message(&self, subject: &str, status: &str) {
+        match self
+            .nats_messages
+            .get_metric_with_label_values(&[subject, status])
+        {
+            Ok(m) => m.inc(),
+            Err(err) => error!("Bad metric: {:?}", err),
+        }
+    }
+
+    pub fn observe_nats_lag(&self, subject: &str, lag: chrono::Duration) {
+        match self
+            .nats_consumer_lag
+            .get_metric_with_label_values(&[subject])
+        {
+            Ok(m) => m.observe(lag.num_milliseconds().max(0) as f64 / 1000.0),
+            Err(err) => error!("Bad metric: {:?}", err),
+        }
+    }
+
+    pub fn observe_vacuum(&self, stats: &VacuumStats) {
+        let categories = [
+            ("too_deep_history", stats.too_deep_history),
+            ("too_old_history", stats.too_old_history),
+            ("too_old_deleted_labels", stats.too_old_deleted_labels),
+            ("expired_by_retention", stats.expired_by_retention),
+        ];
+
+        for (category, count) in categories {
+            match self
+                .vacuum_deleted_events
+                .get_metric_with_label_values(&[category])
+            {
+                Ok(m) => m.inc_by(count as u64),
+                Err(err) => error!("Bad metric: {:?}", err),
             }
         }
     }
 
+    pub fn observe_adjust_phase(&self, phase: &str, duration: Duration) {
+        match self
+            .adjust_phase_duration
+            .get_metric_with_label_values(&[phase])
+        {
+            Ok(m) => m.observe(duration.as_secs_f64()),
+            Err(err) => error!("Bad metric: {:?}", err),
+        }
+    }
+
+    pub fn observe_notification_size(&self, label: &str, size: usize) {
+        match self
+            .notification_payload_size
+            .get_metric_with_label_values(&[label])
+        {
+            Ok(m) => m.observe(size as f64),
+            Err(err) => error!("Bad metric: {:?}", err),
+        }
+    }
+
+    pub fn observe_oversized_notification(&self, label: &str, action: &str) {
+        match self
+            .oversized_notifications
+            .get_metric_with_label_values(&[label, action])
+        {
+            Ok(m) => m.inc(),
+            Err(err) => error!("Bad metric: {:?}", err),
+        }
+    }
+
+    pub fn observe_draw_event_reject(&self, reason: &str) {
+        match self
+            .draw_event_rejects
+            .get_metric_with_label_values(&[reason])
+        {
+            Ok(m) => m.inc(),
+            Err(err) => error!("Bad metric: {:?}", err),
+        }
+    }
+
+    pub fn observe_created_event(&self, kind: &str) {
+        match self.created_events.get_metric_with_label_values(&[kind]) {
+            Ok(m) => m.inc(),
+            Err(err) => error!("Bad metric: {:?}", err),
+        }
+    }
+
+    /// Records the pool state sampled periodically. `pool` is `rw`, `ro` or `bg`.
+    pub fn observe_db_pool(&self, pool: &str, size: u32, idle: usize) {
+        match self.db_pool_size.get_metric_with_label_values(&[pool]) {
+            Ok(m) => m.set(size as i64),
+            Err(err) => error!("Bad metric: {:?}", err),
+        }
+
+        match self.db_pool_idle.get_metric_with_label_values(&[pool]) {
+            Ok(m) => m.set(idle as i64),
+            Err(err) => error!("Bad metric: {:?}", err),
+        }
+    }
+
+    pub fn observe_db_acquire(&self, pool: &str, waited: bool, duration: Duration) {
+        if waited {
+            match self.db_pool_waits.get_metric_with_label_values(&[pool]) {
+                Ok(m) => m.inc(),
+                Err(err) => error!("Bad metric: {:?}", err),
+            }
+        }
+
+        match self
+            .db_pool_acquire_duration
+            .get_metric_with_label_values(&[pool])
+        {
+            Ok(m) => m.observe(duration.as_secs_f64()),
+            Err(err) => error!("Bad metric: {:?}", err),
+        }
+    }
+
     pub fn request_started(self: Arc<Self>) -> StartedRequest {
         StartedRequest::new(self)
     }
@@ -208,3 +633,35 @@ impl Drop for StartedRequest {
         self.metric.running_requests_total.dec();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_failures_by_method() {
+        let registry = Registry::new();
+        let metrics = Metrics::with_request_buckets(&registry, Some(vec![0.1, 1.0]))
+            .expect("Failed to create metrics");
+
+        metrics.observe_request_failure("event.create", &ErrorKind::AccessDenied);
+        metrics.observe_request_failure("event.create", &ErrorKind::AccessDenied);
+        metrics.observe_request_failure("state.read", &ErrorKind::RoomNotFound);
+
+        let failures = metrics
+            .request_failures
+            .get_metric_with_label_values(&["event.create", "access_denied"])
+            .expect("Missing metric");
+
+        assert_eq!(failures.get(), 2);
+
+        drop(metrics.start_request("event.create"));
+
+        let duration = metrics
+            .request_duration_vec
+            .get_metric_with_label_values(&["event.create"])
+            .expect("Missing metric");
+
+        assert_eq!(duration.get_sample_count(), 1);
+    }
+}
@@ -268,6 +268,51 @@ pub mod duration_seconds {
     }
 }
 
+/// Event attributes list which is also exposed as a single `attribute` (the first one)
+/// for clients unaware of multiple attributes. Use with `#[serde(flatten)]`.
+pub mod attributes {
+    use serde::{de, ser, Deserialize, Serialize};
+    use utoipa::ToSchema;
+
+    #[derive(Serialize)]
+    struct Attributes<'a> {
+        attribute: Option<&'a str>,
+        attributes: &'a [String],
+    }
+
+    /// The first attribute is duplicated to `attribute` for clients not aware of multiple ones.
+    #[derive(Deserialize, ToSchema)]
+    pub struct MaybeLegacyAttributes {
+        #[serde(default)]
+        attribute: Option<String>,
+        #[serde(default)]
+        attributes: Option<Vec<String>>,
+    }
+
+    pub fn serialize<S>(attributes: &[String], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        Attributes {
+            attribute: attributes.first().map(|a| a.as_str()),
+            attributes,
+        }
+        .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(d: D) -> Result<Vec<String>, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        let MaybeLegacyAttributes {
+            attribute,
+            attributes,
+        } = MaybeLegacyAttributes::deserialize(d)?;
+
+        Ok(attributes.unwrap_or_else(|| attribute.into_iter().collect()))
+    }
+}
+
 ///////////////////////////////////////////////////////////////////////////////
 
 #[cfg(test)]
@@ -401,4 +446,33 @@ mod test {
         let data: TestSecondsDurationData = dbg!(serde_json::from_value(val).unwrap());
         assert_eq!(data.duration, Duration::seconds(123))
     }
+
+    #[derive(Debug, Deserialize, Serialize)]
+    struct TestAttributesData {
+        #[serde(flatten, with = "crate::serde::attributes")]
+        attributes: Vec<String>,
+    }
+
+    #[test]
+    fn attributes() {
+        let data = TestAttributesData {
+            attributes: vec!["pinned".into(), "important".into()],
+        };
+
+        let val = serde_json::to_value(&data).unwrap();
+        assert_eq!(
+            val,
+            json!({"attribute": "pinned", "attributes": ["pinned", "important"]})
+        );
+
+        let data: TestAttributesData = serde_json::from_value(val).unwrap();
+        assert_eq!(data.attributes, vec!["pinned", "important"]);
+
+        let data: TestAttributesData =
+            serde_json::from_value(json!({"attribute": "pinned"})).unwrap();
+        assert_eq!(data.attributes, vec!["pinned"]);
+
+        let data: TestAttributesData = serde_json::from_value(json!({})).unwrap();
+        assert!(data.attributes.is_empty());
+    }
 }
@@ -1,5 +1,6 @@
 use std::sync::Arc;
 
+use arc_swap::ArcSwap;
 use chrono::{DateTime, Utc};
 use prometheus::Registry;
 use serde_json::json;
@@ -10,11 +11,19 @@ use svc_authz::cache::ConnectionPool as RedisConnectionPool;
 use crate::{
     app::{
         broker_client::{BrokerClient, MockBrokerClient},
+        content_filter::{ContentFilter, MockContentFilter, Verdict},
         context::{Context, GlobalContext, MessageContext},
+        event_hooks::EventHooks,
+        jobs::JobRegistry,
+        presence_cache::PresenceCache,
+        redis_bridge::ClassroomIds,
+        room_cache::RoomCache,
         s3_client::S3Client,
+        webhook_client::{MockWebhookClient, WebhookClient},
     },
     authz::Authz,
     config::Config,
+    db::event::DataEncryption,
     metrics::Metrics,
 };
 
@@ -24,7 +33,7 @@ use super::SVC_AUDIENCE;
 
 ///////////////////////////////////////////////////////////////////////////////
 
-fn build_config(payload_size: Option<usize>) -> Config {
+pub fn build_config(payload_size: Option<usize>) -> Config {
     let id = format!("event.{}", SVC_AUDIENCE);
     let broker_id = format!("mqtt-gateway.{}", SVC_AUDIENCE);
 
@@ -52,16 +61,34 @@ fn build_config(payload_size: Option<usize>) -> Config {
         },
         "adjust": {
             "min_segment_length": "1 second",
+        },
+        // Tests change rooms in the database directly.
+        "room_cache": {
+            "ttl": "0 seconds",
         }
     });
 
     serde_json::from_value::<Config>(config).expect("Failed to parse test config")
 }
 
+fn build_content_filter() -> MockContentFilter {
+    let mut content_filter = MockContentFilter::new();
+    content_filter
+        .expect_check()
+        .returning(|_, _| Ok(Verdict::Allow));
+    content_filter
+}
+
+fn build_webhook_client() -> MockWebhookClient {
+    let mut webhook_client = MockWebhookClient::new();
+    webhook_client.expect_send().returning(|_, _| ());
+    webhook_client
+}
+
 ///////////////////////////////////////////////////////////////////////////////
 
 pub struct TestContext {
-    config: Config,
+    config: ArcSwap<Config>,
     authz: Authz,
     db: TestDb,
     agent_id: AgentId,
@@ -69,6 +96,14 @@ pub struct TestContext {
     start_timestamp: DateTime<Utc>,
     s3_client: Option<S3Client>,
     broker_client: Arc<MockBrokerClient>,
+    webhook_client: Arc<MockWebhookClient>,
+    content_filter: Arc<MockContentFilter>,
+    jobs: Arc<JobRegistry>,
+    presence_cache: Arc<PresenceCache>,
+    room_cache: Arc<RoomCache>,
+    classroom_ids: Arc<ClassroomIds>,
+    event_hooks: Arc<EventHooks>,
+    data_encryption: Option<Arc<DataEncryption>>,
 }
 
 impl TestContext {
@@ -78,7 +113,7 @@ impl TestContext {
 
         let metrics = Arc::new(Metrics::new(&Registry::new()).unwrap());
         Self {
-            config,
+            config: ArcSwap::from_pointee(config),
             authz: Authz::new(authz.into(), metrics.clone()),
             db,
             agent_id,
@@ -86,6 +121,14 @@ impl TestContext {
             start_timestamp: Utc::now(),
             s3_client: None,
             broker_client: Arc::new(MockBrokerClient::new()),
+            webhook_client: Arc::new(build_webhook_client()),
+            content_filter: Arc::new(build_content_filter()),
+            jobs: Arc::new(JobRegistry::new()),
+            presence_cache: Arc::new(PresenceCache::new()),
+            room_cache: Arc::new(RoomCache::new()),
+            classroom_ids: Arc::new(ClassroomIds::new()),
+            event_hooks: Arc::new(EventHooks::new()),
+            data_encryption: None,
         }
     }
 
@@ -95,7 +138,7 @@ impl TestContext {
 
         let metrics = Arc::new(Metrics::new(&Registry::new()).unwrap());
         Self {
-            config,
+            config: ArcSwap::from_pointee(config),
             authz: Authz::new(authz.into(), metrics.clone()),
             db,
             agent_id,
@@ -103,6 +146,14 @@ impl TestContext {
             start_timestamp: Utc::now(),
             s3_client: None,
             broker_client: Arc::new(MockBrokerClient::new()),
+            webhook_client: Arc::new(build_webhook_client()),
+            content_filter: Arc::new(build_content_filter()),
+            jobs: Arc::new(JobRegistry::new()),
+            presence_cache: Arc::new(PresenceCache::new()),
+            room_cache: Arc::new(RoomCache::new()),
+            classroom_ids: Arc::new(ClassroomIds::new()),
+            event_hooks: Arc::new(EventHooks::new()),
+            data_encryption: None,
         }
     }
 
@@ -112,7 +163,7 @@ impl TestContext {
 
         let metrics = Arc::new(Metrics::new(&Registry::new()).unwrap());
         Self {
-            config,
+            config: ArcSwap::from_pointee(config),
             authz: Authz::new(authz.into(), metrics.clone()),
             db,
             agent_id,
@@ -120,9 +171,31 @@ impl TestContext {
             start_timestamp: Utc::now(),
             s3_client: None,
             broker_client: Arc::new(MockBrokerClient::new()),
+            webhook_client: Arc::new(build_webhook_client()),
+            content_filter: Arc::new(build_content_filter()),
+            jobs: Arc::new(JobRegistry::new()),
+            presence_cache: Arc::new(PresenceCache::new()),
+            room_cache: Arc::new(RoomCache::new()),
+            classroom_ids: Arc::new(ClassroomIds::new()),
+            event_hooks: Arc::new(EventHooks::new()),
+            data_encryption: None,
         }
     }
 
+    pub fn update_config(&self, f: impl FnOnce(&mut Config)) {
+        let mut config = Config::clone(&self.config.load());
+        f(&mut config);
+        self.store_config(config);
+    }
+
+    pub fn set_event_hooks(&mut self, event_hooks: EventHooks) {
+        self.event_hooks = Arc::new(event_hooks);
+    }
+
+    pub fn set_data_encryption(&mut self, data_encryption: DataEncryption) {
+        self.data_encryption = Some(Arc::new(data_encryption));
+    }
+
     pub fn set_s3(&mut self, s3_client: S3Client) {
         self.s3_client = Some(s3_client)
     }
@@ -130,6 +203,16 @@ impl TestContext {
     pub fn broker_client_mock(&mut self) -> &mut MockBrokerClient {
         Arc::get_mut(&mut self.broker_client).expect("Failed to get broker client mock")
     }
+
+    /// Webhooks are ignored by default. Call `checkpoint` on the mock before setting expectations.
+    pub fn webhook_client_mock(&mut self) -> &mut MockWebhookClient {
+        Arc::get_mut(&mut self.webhook_client).expect("Failed to get webhook client mock")
+    }
+
+    /// All messages are allowed by default. Call `checkpoint` on the mock before setting expectations.
+    pub fn content_filter_mock(&mut self) -> &mut MockContentFilter {
+        Arc::get_mut(&mut self.content_filter).expect("Failed to get content filter mock")
+    }
 }
 
 impl GlobalContext for TestContext {
@@ -137,8 +220,12 @@ impl GlobalContext for TestContext {
         &self.authz
     }
 
-    fn config(&self) -> &Config {
-        &self.config
+    fn config(&self) -> Arc<Config> {
+        self.config.load_full()
+    }
+
+    fn store_config(&self, config: Config) {
+        self.config.store(Arc::new(config));
     }
 
     fn db(&self) -> &Db {
@@ -149,6 +236,10 @@ impl GlobalContext for TestContext {
         self.db.connection_pool()
     }
 
+    fn background_db(&self) -> &Db {
+        self.db.connection_pool()
+    }
+
     fn agent_id(&self) -> &AgentId {
         &self.agent_id
     }
@@ -172,6 +263,38 @@ impl GlobalContext for TestContext {
     fn broker_client(&self) -> &dyn BrokerClient {
         self.broker_client.as_ref()
     }
+
+    fn webhook_client(&self) -> Arc<dyn WebhookClient> {
+        self.webhook_client.clone()
+    }
+
+    fn content_filter(&self) -> Arc<dyn ContentFilter> {
+        self.content_filter.clone()
+    }
+
+    fn jobs(&self) -> Arc<JobRegistry> {
+        self.jobs.clone()
+    }
+
+    fn presence_cache(&self) -> Arc<PresenceCache> {
+        self.presence_cache.clone()
+    }
+
+    fn room_cache(&self) -> Arc<RoomCache> {
+        self.room_cache.clone()
+    }
+
+    fn classroom_ids(&self) -> Arc<ClassroomIds> {
+        self.classroom_ids.clone()
+    }
+
+    fn event_hooks(&self) -> Arc<EventHooks> {
+        self.event_hooks.clone()
+    }
+
+    fn data_encryption(&self) -> Option<Arc<DataEncryption>> {
+        self.data_encryption.clone()
+    }
 }
 
 impl MessageContext for TestContext {
@@ -51,14 +51,14 @@ pub fn test_db_ban_callback(db: TestDb) -> BanCallback {
                     if let Some(classroom_id) = intent.to_vec().get(1) {
                         if let Ok(classroom_id) = Uuid::parse_str(classroom_id) {
                             let mut conn = db_.get_conn().await;
-                            if let Ok(maybe_ban) = crate::db::room_ban::ClassroomFindQuery::new(
-                                account_id.to_owned(),
+                            if let Ok(is_banned) = crate::app::endpoint::authz::is_banned(
+                                &mut conn,
+                                &account_id,
                                 classroom_id,
                             )
-                            .execute(&mut conn)
                             .await
                             {
-                                return maybe_ban.is_some();
+                                return is_banned;
                             }
                         }
                     }
@@ -19,6 +19,11 @@ pub struct Room {
     preserve_history: Option<bool>,
     classroom_id: Uuid,
     kind: ClassType,
+    moderated: bool,
+    keep_open: bool,
+    capacity: Option<i32>,
+    secondary_audience: Option<String>,
+    source_room_id: Option<Uuid>,
 }
 
 impl Room {
@@ -30,6 +35,11 @@ impl Room {
             preserve_history: None,
             classroom_id,
             kind,
+            moderated: false,
+            keep_open: false,
+            capacity: None,
+            secondary_audience: None,
+            source_room_id: None,
         }
     }
 
@@ -61,6 +71,35 @@ impl Room {
         }
     }
 
+    pub fn moderated(self, moderated: bool) -> Self {
+        Self { moderated, ..self }
+    }
+
+    pub fn keep_open(self, keep_open: bool) -> Self {
+        Self { keep_open, ..self }
+    }
+
+    pub fn capacity(self, capacity: i32) -> Self {
+        Self {
+            capacity: Some(capacity),
+            ..self
+        }
+    }
+
+    pub fn secondary_audience(self, secondary_audience: &str) -> Self {
+        Self {
+            secondary_audience: Some(secondary_audience.to_owned()),
+            ..self
+        }
+    }
+
+    pub fn source_room_id(self, source_room_id: Uuid) -> Self {
+        Self {
+            source_room_id: Some(source_room_id),
+            ..self
+        }
+    }
+
     pub fn validate_whiteboard_access(self) -> Self {
         Self {
             kind: ClassType::Minigroup,
@@ -82,7 +121,18 @@ impl Room {
             query = query.preserve_history(preserve_history)
         }
 
-        query.execute(conn).await.expect("Failed to insert room")
+        if let Some(source_room_id) = self.source_room_id {
+            query = query.source_room_id(source_room_id)
+        }
+
+        query
+            .moderated(self.moderated)
+            .keep_open(self.keep_open)
+            .capacity(self.capacity)
+            .secondary_audience(self.secondary_audience)
+            .execute(conn)
+            .await
+            .expect("Failed to insert room")
     }
 }
 
@@ -146,12 +196,13 @@ pub struct Event {
     kind: Option<String>,
     set: Option<String>,
     label: Option<String>,
-    attribute: Option<String>,
+    attributes: Vec<String>,
     data: Option<JsonValue>,
     occurred_at: Option<i64>,
     created_by: Option<AgentId>,
     created_at: Option<DateTime<Utc>>,
     removed: bool,
+    moderation_status: db::event::ModerationStatus,
 }
 
 impl Event {
@@ -188,10 +239,9 @@ impl Event {
     }
 
     pub fn attribute(self, attribute: &str) -> Self {
-        Self {
-            attribute: Some(attribute.to_owned()),
-            ..self
-        }
+        let mut attributes = self.attributes;
+        attributes.push(attribute.to_owned());
+        Self { attributes, ..self }
     }
 
     pub fn data(self, data: &JsonValue) -> Self {
@@ -226,6 +276,13 @@ impl Event {
         Self { removed, ..self }
     }
 
+    pub fn moderation_status(self, moderation_status: db::event::ModerationStatus) -> Self {
+        Self {
+            moderation_status,
+            ..self
+        }
+    }
+
     pub async fn insert(self, conn: &mut PgConnection) -> db::event::Object {
         let room_id = self.room_id.expect("Room ID not set");
         let kind = self.kind.expect("Kind not set");
@@ -233,9 +290,11 @@ impl Event {
         let occurred_at = self.occurred_at.expect("Occurrence date not set");
         let created_by = self.created_by.expect("Creator not set");
 
-        let mut query = db::event::InsertQuery::new(room_id, kind, data, occurred_at, created_by)
-            .unwrap()
-            .removed(self.removed);
+        let mut query =
+            db::event::InsertQuery::new(room_id, kind, data, occurred_at, created_by, None)
+                .unwrap()
+                .removed(self.removed)
+                .moderation_status(self.moderation_status);
 
         if let Some(set) = self.set {
             query = query.set(set);
@@ -245,8 +304,8 @@ impl Event {
             query = query.label(label);
         }
 
-        if let Some(attribute) = self.attribute {
-            query = query.attribute(attribute);
+        if !self.attributes.is_empty() {
+            query = query.attributes(self.attributes);
         }
 
         if let Some(created_at) = self.created_at {
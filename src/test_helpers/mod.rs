@@ -4,14 +4,16 @@ use serde::de::DeserializeOwned;
 use serde_json::json;
 use svc_agent::{
     mqtt::{IncomingEventProperties, IncomingRequestProperties},
-    AgentId,
+    AgentId, Authenticable,
 };
 use uuid::Uuid;
 
+use crate::app::context::GlobalContext;
 use crate::app::endpoint::{EventHandler, RequestHandler};
 use crate::app::error::Error as AppError;
 use crate::app::message_handler::MessageStream;
 use crate::app::service_utils::RequestParams;
+use crate::app::share_token::ShareToken;
 use crate::app::API_VERSION;
 
 use self::agent::TestAgent;
@@ -33,7 +35,25 @@ pub async fn handle_request<H: RequestHandler>(
 ) -> Result<Vec<OutgoingEnvelope>, AppError> {
     let reqp = build_reqp(agent.agent_id(), "ignore");
     let messages = H::handle(context, payload, RequestParams::MqttParams(&reqp)).await?;
-    Ok(parse_messages(messages.into_mqtt_messages(&reqp)?).await)
+    let batching = context.config().notification_batching.clone();
+    Ok(parse_messages(messages.into_mqtt_messages(&reqp, &batching)?).await)
+}
+
+/// Handles the request the way it's handled over HTTP with a share token of the room.
+pub async fn handle_shared_request<H: RequestHandler>(
+    context: &mut TestContext,
+    room_id: Uuid,
+    payload: H::Payload,
+) -> Result<Vec<OutgoingEnvelope>, AppError> {
+    let agent_id = ShareToken::viewer_agent_id(context.agent_id().as_account_id());
+    let reqp = build_reqp(&agent_id, "ignore");
+    let reqp_shared = RequestParams::Shared {
+        agent_id: &agent_id,
+        room_id,
+    };
+    let messages = H::handle(context, payload, reqp_shared).await?;
+    let batching = context.config().notification_batching.clone();
+    Ok(parse_messages(messages.into_mqtt_messages(&reqp, &batching)?).await)
 }
 
 pub async fn handle_event<H: EventHandler>(
@@ -46,7 +66,7 @@ pub async fn handle_event<H: EventHandler>(
     Ok(parse_messages(messages).await)
 }
 
-async fn parse_messages(mut messages: MessageStream) -> Vec<OutgoingEnvelope> {
+pub async fn parse_messages(mut messages: MessageStream) -> Vec<OutgoingEnvelope> {
     let mut parsed_messages = vec![];
 
     while let Some(message) = messages.next().await {
@@ -174,7 +194,7 @@ pub mod prelude {
         context::TestContext,
         db::{test_db_ban_callback, TestDb},
         factory, find_event, find_event_by_predicate, find_response, handle_event, handle_request,
-        shared_helpers, SVC_AUDIENCE, USR_AUDIENCE,
+        handle_shared_request, parse_messages, shared_helpers, SVC_AUDIENCE, USR_AUDIENCE,
     };
 }
 
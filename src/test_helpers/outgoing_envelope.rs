@@ -40,12 +40,18 @@ pub enum OutgoingEnvelopeProperties {
 #[derive(Debug, Deserialize)]
 pub struct OutgoingEventProperties {
     label: String,
+    #[serde(default)]
+    local_tracking_label: Option<String>,
 }
 
 impl OutgoingEventProperties {
     pub fn label(&self) -> &str {
         &self.label
     }
+
+    pub fn local_tracking_label(&self) -> Option<&str> {
+        self.local_tracking_label.as_deref()
+    }
 }
 
 #[derive(Debug, Deserialize)]